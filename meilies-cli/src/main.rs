@@ -1,14 +1,16 @@
-use std::net::ToSocketAddrs;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
 
+use futures::future::Either;
 use futures::stream::Stream;
 use log::error;
 use structopt::StructOpt;
 use tokio::prelude::*;
 
-use meilies::reqresp::Request;
+use meilies::reqresp::{Request, Response};
 use meilies::resp::{FromResp, RespValue};
 use meilies::stream::Stream as EsStream;
-use meilies_client::{paired_connect, sub_connect};
+use meilies_client::{ConnectBuilder, TlsConfig};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "meilies-cli", about = "A basic cli for MeiliES.", author)]
@@ -21,6 +23,50 @@ struct Opt {
     #[structopt(short = "p", long = "port", default_value = "6480")]
     port: u16,
 
+    /// Address of a server to fail over to when the previous ones are unreachable.
+    #[structopt(long = "fallback")]
+    fallbacks: Vec<SocketAddr>,
+
+    /// User to authenticate as, the server must use an ACL.
+    #[structopt(short = "u", long = "user")]
+    user: Option<String>,
+
+    /// Password used to authenticate with the server.
+    #[structopt(short = "a", long = "password")]
+    password: Option<String>,
+
+    /// Connect to the server using TLS.
+    #[structopt(long = "tls")]
+    tls: bool,
+
+    /// Domain name expected in the server certificate, defaults to the hostname.
+    #[structopt(long = "tls-domain")]
+    tls_domain: Option<String>,
+
+    /// Path of a PEM file containing additional trusted certificates.
+    #[structopt(long = "tls-ca", parse(from_os_str))]
+    tls_ca: Option<PathBuf>,
+
+    /// Path of the PEM encoded client certificate presented to the server.
+    #[structopt(long = "tls-cert", parse(from_os_str))]
+    tls_cert: Option<PathBuf>,
+
+    /// Path of the PEM encoded private key of the client certificate.
+    #[structopt(long = "tls-key", parse(from_os_str))]
+    tls_key: Option<PathBuf>,
+
+    /// Do not verify the server certificate (insecure, for development only).
+    #[structopt(long = "tls-insecure")]
+    tls_insecure: bool,
+
+    /// Ask the server to compress the responses larger than this number of bytes.
+    #[structopt(long = "compression-threshold")]
+    compression_threshold: Option<u64>,
+
+    /// Send and receive the bulk strings larger than this number of bytes in chunks.
+    #[structopt(long = "frame-size")]
+    frame_size: Option<u64>,
+
     /// Command and arguments that will be sent to the server.
     cmd_args: Vec<String>,
 }
@@ -39,6 +85,44 @@ fn main() {
         Err(e) => return error!("error parsing addr; {}", e),
     };
 
+    let mut builder = ConnectBuilder::new(addr);
+    for fallback in opt.fallbacks {
+        builder = builder.fallback(fallback);
+    }
+    if let Some(user) = opt.user {
+        builder = builder.username(user);
+    }
+    if let Some(password) = opt.password {
+        builder = builder.password(password);
+    }
+    if let Some(threshold) = opt.compression_threshold {
+        builder = builder.compression(threshold);
+    }
+    if let Some(size) = opt.frame_size {
+        builder = builder.frame_size(size);
+    }
+    if opt.tls {
+        let hostname = opt.hostname;
+        let domain = opt.tls_domain.unwrap_or(hostname);
+        let mut tls = TlsConfig::new(domain);
+        if let Some(path) = opt.tls_ca {
+            tls = match tls.add_ca_file(path) {
+                Ok(tls) => tls,
+                Err(e) => return error!("error reading the TLS certificates; {}", e),
+            };
+        }
+        if let (Some(cert), Some(key)) = (opt.tls_cert, opt.tls_key) {
+            tls = match tls.identity(cert, key) {
+                Ok(tls) => tls,
+                Err(e) => return error!("error reading the TLS client certificate; {}", e),
+            };
+        }
+        if opt.tls_insecure {
+            tls = tls.danger_accept_invalid_certs();
+        }
+        builder = builder.tls(tls);
+    }
+
     let args = opt
         .cmd_args
         .into_iter()
@@ -51,11 +135,24 @@ fn main() {
     };
 
     let fut = match command {
-        Request::SubscribeAll { range } => {
-            let fut = sub_connect(addr)
+        Request::SubscribeAll {
+            range,
+            event_names,
+            slow_consumer,
+            credits,
+            batch,
+        } => {
+            let fut = builder
+                .sub()
                 .map_err(|e| error!("{}", e))
                 .and_then(move |(mut ctrl, msgs)| {
-                    ctrl.subscribe_to(EsStream::all(range));
+                    ctrl.subscribe_to(EsStream {
+                        event_names,
+                        slow_consumer,
+                        credits,
+                        batch,
+                        ..EsStream::all(range)
+                    });
 
                     msgs.for_each(move |msg| {
                         match msg {
@@ -74,7 +171,8 @@ fn main() {
             Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
         }
         Request::Subscribe { streams } => {
-            let fut = sub_connect(addr)
+            let fut = builder
+                .sub()
                 .map_err(|e| error!("{}", e))
                 .and_then(|(mut ctrl, msgs)| {
                     for stream in streams {
@@ -97,37 +195,590 @@ fn main() {
 
             Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
         }
+        Request::SubscribeGroup {
+            group,
+            stream,
+            consumer,
+        } => {
+            let fut = builder
+                .sub()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |(mut ctrl, msgs)| {
+                    ctrl.subscribe_group(group.clone(), stream, consumer);
+
+                    msgs.for_each(move |msg| {
+                        match msg {
+                            Ok(Response::Event {
+                                ref stream, number, ..
+                            }) => {
+                                println!("{:?}", msg);
+                                // the events are acknowledged as soon as they are printed
+                                ctrl.ack(group.clone(), stream.clone(), number);
+                            }
+                            Ok(Response::Ok) => (),
+                            Ok(response) => println!("{:?}", response),
+                            Err(error) => eprintln!("Error: {}", error),
+                        }
+                        future::ok(())
+                    })
+                    .map_err(|e| error!("{:?}", e))
+                })
+                .and_then(|_| {
+                    println!("Connection closed by the server");
+                    Err(())
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Ack {
+            group,
+            stream,
+            event_number,
+        } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| {
+                    conn.ack(group, stream, event_number)
+                        .map_err(|e| error!("{}", e))
+                })
+                .map(|_conn| println!("Event acknowledged"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::CommitOffset {
+            group,
+            stream,
+            event_number,
+        } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| {
+                    conn.commit_offset(group, stream, event_number)
+                        .map_err(|e| error!("{}", e))
+                })
+                .map(|_conn| println!("Offset committed"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::FetchOffset { group, stream } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| {
+                    conn.fetch_offset(group, stream)
+                        .map_err(|e| error!("{}", e))
+                })
+                .map(|(number, _conn)| println!("{:?}", number));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Pending { group, stream } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| conn.pending(group, stream).map_err(|e| error!("{}", e)))
+                .map(|(events, _conn)| {
+                    for event in events {
+                        println!("{:?}", event);
+                    }
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Claim {
+            group,
+            stream,
+            event_number,
+            new_consumer,
+        } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| {
+                    conn.claim(group, stream, event_number, new_consumer)
+                        .map_err(|e| error!("{}", e))
+                })
+                .map(|(event, _conn)| println!("{:?}", event));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Unsubscribe { .. } => {
+            return error!("unsubscribe can only be sent on an already subscribed connection");
+        }
+        Request::Flow { .. } => {
+            return error!("flow can only be sent on an already subscribed connection");
+        }
+        Request::Auth { .. } => {
+            return error!("use the password option to authenticate the connection");
+        }
+        Request::SetAckLevel { .. } => {
+            return error!("use the ack option of publish to wait for the replicas");
+        }
+        Request::ReplicaAck { .. } => {
+            return error!("replica-ack can only be sent by a replica");
+        }
+        Request::RequestVote { .. } => {
+            return error!("request-vote can only be sent by a cluster node");
+        }
+        Request::Heartbeat { .. } => {
+            return error!("heartbeat can only be sent by a cluster node");
+        }
+        Request::Compress { .. } => {
+            return error!("use the compression-threshold option to compress the responses");
+        }
+        Request::FrameSize { .. } => {
+            return error!("use the frame-size option to send the bulk strings in chunks");
+        }
+        Request::Publish {
+            expected_version: Some(_),
+            deliver_at: Some(_),
+            ..
+        } => {
+            return error!("version can not be used with deliver-at");
+        }
         Request::Publish {
             stream,
             event_name,
             event_data,
+            dedup_id,
+            headers,
+            deliver_at: Some(deliver_at),
+            ..
         } => {
-            let fut = paired_connect(addr)
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| {
+                    conn.publish_at(
+                        stream, event_name, event_data, dedup_id, headers, deliver_at,
+                    )
+                    .map_err(|e| error!("{}", e))
+                })
+                .map(move |_conn| println!("Event scheduled at {}", deliver_at));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Publish {
+            stream,
+            event_name,
+            event_data,
+            expected_version,
+            dedup_id,
+            headers,
+            deliver_at: None,
+            ack,
+        } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| match ack {
+                    Some(level) => {
+                        Either::A(conn.set_ack_level(level).map_err(|e| error!("{}", e)))
+                    }
+                    None => Either::B(future::ok(conn)),
+                })
+                .and_then(move |conn| {
+                    conn.send_publish(
+                        stream,
+                        event_name,
+                        event_data,
+                        expected_version,
+                        dedup_id,
+                        headers,
+                    )
+                    .map_err(|e| error!("{}", e))
+                })
+                .map(|(number, _conn)| println!("Event sent to the stream ({})", number.0));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::PublishBatch { stream, events } => {
+            let fut = builder
+                .paired()
                 .map_err(|e| error!("{}", e))
                 .and_then(|conn| {
-                    conn.publish(stream, event_name, event_data)
+                    conn.publish_batch(stream, events)
                         .map_err(|e| error!("{}", e))
                 })
-                .map(|_conn| println!("Event sent to the stream"));
+                .map(|(first, last, _conn)| {
+                    println!("Events sent to the stream ({}..={})", first.0, last.0)
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::PublishMulti { writes } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.publish_multi(writes).map_err(|e| error!("{}", e)))
+                .map(|(events, _conn)| {
+                    for (stream, number) in events {
+                        println!("Event sent to the stream {} ({})", stream, number.0)
+                    }
+                });
 
             Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
         }
         Request::LastEventNumber { stream } => {
-            let fut = paired_connect(addr)
+            let fut = builder
+                .paired()
                 .map_err(|e| error!("{}", e))
                 .and_then(|conn| conn.last_event_number(stream).map_err(|e| error!("{}", e)))
                 .map(|(stream, number, _conn)| println!("{} - {:?}", stream, number));
 
             Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
         }
-        Request::StreamNames => {
-            let fut = paired_connect(addr)
+        Request::CreateStream { stream } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.create_stream(stream).map_err(|e| error!("{}", e)))
+                .map(|_conn| println!("Stream created"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::DeleteStream {
+            stream,
+            hard: false,
+        } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.delete_stream(stream).map_err(|e| error!("{}", e)))
+                .map(|_conn| println!("Stream deleted"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::DeleteStream { stream, hard: true } => {
+            let fut = builder
+                .paired()
                 .map_err(|e| error!("{}", e))
-                .and_then(|conn| conn.stream_names().map_err(|e| error!("{}", e)))
+                .and_then(|conn| conn.tombstone_stream(stream).map_err(|e| error!("{}", e)))
+                .map(|_conn| println!("Stream deleted permanently"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::TrimStream { stream, up_to } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| conn.trim_stream(stream, up_to).map_err(|e| error!("{}", e)))
+                .map(|_conn| println!("Stream trimmed"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Backup { path } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.backup(path).map_err(|e| error!("{}", e)))
+                .map(|_conn| println!("Backup written"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::ReplicaOf { primary } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.replica_of(primary).map_err(|e| error!("{}", e)))
+                .map(|_conn| println!("Replication changed"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::ReadOnly { enabled } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| conn.read_only(enabled).map_err(|e| error!("{}", e)))
+                .map(|_conn| println!("Read-only mode changed"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Info => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.info().map_err(|e| error!("{}", e)))
+                .map(|(info, _conn)| {
+                    println!("version:       {}", info.version);
+                    println!("uptime:        {}s", info.uptime);
+                    match info.memory {
+                        Some(memory) => println!("memory:        {} bytes", memory),
+                        None => println!("memory:        unknown"),
+                    }
+                    println!("streams:       {}", info.streams);
+                    println!("events:        {}", info.events);
+                    println!("connections:   {}", info.connections);
+                    println!("subscriptions: {}", info.subscriptions);
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::ClientList => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.client_list().map_err(|e| error!("{}", e)))
+                .map(|(clients, _conn)| {
+                    for client in clients {
+                        let user = client.user.as_ref().map_or("-", String::as_str);
+                        let streams: Vec<_> =
+                            client.subscriptions.iter().map(|s| s.as_str()).collect();
+                        println!(
+                            "id: {}, addr: {}, user: {}, subscriptions: [{}], idle: {}ms",
+                            client.id,
+                            client.addr,
+                            user,
+                            streams.join(", "),
+                            client.idle
+                        );
+                    }
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::SlowLog { reset: false } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.slow_log().map_err(|e| error!("{}", e)))
+                .map(|(entries, _conn)| {
+                    for entry in entries {
+                        let arguments: Vec<_> =
+                            entry.command.iter().map(|a| format!("{:?}", a)).collect();
+                        println!(
+                            "{}: {} {} {}us {}",
+                            entry.id,
+                            entry.timestamp.to_datetime(),
+                            entry.client,
+                            entry.duration,
+                            arguments.join(" ")
+                        );
+                    }
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::SlowLog { reset: true } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.slow_log_reset().map_err(|e| error!("{}", e)))
+                .map(|_conn| println!("Slow log cleared"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Latency => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.latency().map_err(|e| error!("{}", e)))
+                .map(|(latencies, _conn)| {
+                    println!(
+                        "{:<24} {:>10} {:>10} {:>10} {:>10}",
+                        "", "count", "p50", "p99", "p999"
+                    );
+                    for stats in latencies.commands.iter().chain(&latencies.operations) {
+                        println!(
+                            "{:<24} {:>10} {:>8}us {:>8}us {:>8}us",
+                            stats.name, stats.count, stats.p50, stats.p99, stats.p999
+                        );
+                    }
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::ClientKill { id } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| conn.client_kill(id).map_err(|e| error!("{}", e)))
+                .map(|_conn| println!("Client killed"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::ConfigSet { key, value } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| conn.config_set(key, value).map_err(|e| error!("{}", e)))
+                .map(|_conn| println!("Configuration changed"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Monitor => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.monitor().map_err(|e| error!("{}", e)))
+                .and_then(|commands| {
+                    commands
+                        .for_each(|monitored| {
+                            let arguments: Vec<_> = monitored
+                                .command
+                                .iter()
+                                .map(|a| format!("{:?}", a))
+                                .collect();
+                            println!(
+                                "{} [{}] {}",
+                                monitored.timestamp,
+                                monitored.client,
+                                arguments.join(" ")
+                            );
+                            Ok(())
+                        })
+                        .map_err(|e| error!("{}", e))
+                })
+                .and_then(|_| {
+                    println!("Connection closed by the server");
+                    Err(())
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Compact => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.compact().map_err(|e| error!("{}", e)))
+                .map(|(before, after, _conn)| {
+                    println!("before: {} bytes, after: {} bytes", before, after)
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::ReplicationInfo => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.replication_info().map_err(|e| error!("{}", e)))
+                .map(|(info, _conn)| {
+                    println!(
+                        "primary: {:?}, caught up: {}, last global: {:?}, lag: {:?}ms",
+                        info.primary, info.caught_up, info.last_global, info.lag
+                    )
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::SetStreamMeta { stream, key, value } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| {
+                    conn.set_stream_meta(stream, key, value)
+                        .map_err(|e| error!("{}", e))
+                })
+                .map(|_conn| println!("Stream metadata set"));
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::StreamInfo { stream } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.stream_info(stream).map_err(|e| error!("{}", e)))
+                .map(|(info, _conn)| {
+                    let optional =
+                        |value: Option<u64>| value.map_or(String::from("-"), |v| v.to_string());
+                    println!("first:       {}", info.first.0);
+                    println!("last:        {}", optional(info.last.map(|n| n.0)));
+                    println!("events:      {}", info.events);
+                    println!("bytes:       {}", info.bytes);
+                    match info.created {
+                        Some(created) => println!("created:     {}", created.to_datetime()),
+                        None => println!("created:     unknown"),
+                    }
+                    match info.retention_ms {
+                        Some(retention) => println!("retention:   {}ms", retention),
+                        None => println!("retention:   -"),
+                    }
+                    println!("max-events:  {}", optional(info.max_events));
+                    println!("max-bytes:   {}", optional(info.max_bytes));
+                    println!("subscribers: {}", info.subscribers);
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Verify { stream } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.verify(stream).map_err(|e| error!("{}", e)))
+                .map(|(checked, unchecked, corrupted, _conn)| {
+                    println!("checked: {}, unchecked: {}", checked, unchecked);
+                    for number in corrupted {
+                        println!("event {} is corrupted", number.0);
+                    }
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::GetStreamMeta { stream } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.stream_meta(stream).map_err(|e| error!("{}", e)))
+                .map(|(meta, _conn)| {
+                    for (key, value) in meta {
+                        println!("{}: {}", key, value);
+                    }
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::StreamNames { pattern } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.stream_names(pattern).map_err(|e| error!("{}", e)))
                 .map(|(streams, _conn)| println!("{:?}", streams));
 
             Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
         }
+        Request::Ping { payload } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(|conn| conn.ping(payload).map_err(|e| error!("{}", e)))
+                .map(|(payload, _conn)| match payload {
+                    Some(payload) => println!("{}", String::from_utf8_lossy(&payload)),
+                    None => println!("PONG"),
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::Hello { proto } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| conn.hello(proto).map_err(|e| error!("{}", e)))
+                .map(|(hello, _conn)| {
+                    println!("version:  {}", hello.version);
+                    println!("proto:    {}", hello.proto);
+                    println!("features: {}", hello.features.join(", "));
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
+        Request::GetRange { stream, from, to } => {
+            let fut = builder
+                .paired()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |conn| {
+                    conn.get_range(stream, from, to)
+                        .map_err(|e| error!("{}", e))
+                })
+                .map(|(events, _conn)| {
+                    for event in events {
+                        println!("{:?}", event);
+                    }
+                });
+
+            Box::new(fut) as Box<dyn Future<Item = (), Error = ()> + Send>
+        }
     };
 
     tokio::run(fut);
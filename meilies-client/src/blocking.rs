@@ -0,0 +1,159 @@
+use std::net::SocketAddr;
+
+use futures03::{Future, FutureExt, StreamExt, TryFutureExt};
+use meilies::reqresp::ExpectedVersion;
+use meilies::stream::{Event, EventData, EventHeaders, EventName, EventNumber};
+use meilies::stream::{Stream as EsStream, StreamName};
+use tokio::runtime::current_thread::Runtime;
+
+use super::{Client, Confirmation, ConnectBuilder, Error, Subscription};
+
+/// Runs the future to completion on the runtime, the background tasks
+/// of the connections, e.g. the reconnections, only progress meanwhile.
+fn block_on<F: Future>(runtime: &mut Runtime, future: F) -> F::Output {
+    let future = Box::pin(future.map(Ok::<_, ()>)).compat();
+    match runtime.block_on(future) {
+        Ok(output) => output,
+        Err(()) => unreachable!("the future can not fail"),
+    }
+}
+
+/// A client whose commands block the thread until they are answered, for the scripts
+/// and the applications that are not asynchronous. It runs its own runtime.
+pub struct BlockingClient {
+    runtime: Runtime,
+    client: Client,
+}
+
+impl BlockingClient {
+    /// Open a client with the server at this address, see `ConnectBuilder::blocking`
+    /// to authenticate, use TLS or fail over to other servers.
+    pub fn connect(addr: SocketAddr) -> Result<BlockingClient, Error> {
+        BlockingClient::connect_with(ConnectBuilder::new(addr))
+    }
+
+    pub(crate) fn connect_with(builder: ConnectBuilder) -> Result<BlockingClient, Error> {
+        let mut runtime = Runtime::new().map_err(Error::Connect)?;
+        let client = block_on(&mut runtime, builder.client())?;
+        Ok(BlockingClient { runtime, client })
+    }
+
+    /// Publish an event to a stream, returns the number the server assigned to it.
+    pub fn publish(
+        &mut self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+    ) -> Result<EventNumber, Error> {
+        let publish = self.client.publish(stream, event_name, event_data);
+        block_on(&mut self.runtime, publish)
+    }
+
+    /// Publish an event on the pipelined connection of the client, see `Client::publish_with`.
+    ///
+    /// The publications that are not waited for are only written while the
+    /// client runs another command, its runtime is not running meanwhile.
+    pub fn publish_with(
+        &mut self,
+        confirmation: Confirmation,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+    ) -> Result<Option<EventNumber>, Error> {
+        let publish = self
+            .client
+            .publish_with(confirmation, stream, event_name, event_data);
+        block_on(&mut self.runtime, publish)
+    }
+
+    /// Publish an event to a stream only if the stream is at the expected version,
+    /// returns a `WrongVer` server error if the stream is at another version.
+    pub fn publish_expected(
+        &mut self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        expected_version: ExpectedVersion,
+    ) -> Result<EventNumber, Error> {
+        let publish =
+            self.client
+                .publish_expected(stream, event_name, event_data, expected_version);
+        block_on(&mut self.runtime, publish)
+    }
+
+    /// Publish an event to a stream with a deduplication ID, the publication
+    /// can be retried safely, the event is only appended once.
+    pub fn publish_once(
+        &mut self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        dedup_id: String,
+    ) -> Result<EventNumber, Error> {
+        let publish = self
+            .client
+            .publish_once(stream, event_name, event_data, dedup_id);
+        block_on(&mut self.runtime, publish)
+    }
+
+    /// Publish an event to a stream along with headers, e.g. a correlation ID.
+    pub fn publish_with_headers(
+        &mut self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        headers: EventHeaders,
+    ) -> Result<EventNumber, Error> {
+        let publish = self
+            .client
+            .publish_with_headers(stream, event_name, event_data, headers);
+        block_on(&mut self.runtime, publish)
+    }
+
+    /// Request the last event number of the stream, `None` if it does not contain any event.
+    pub fn last_event_number(&mut self, stream: StreamName) -> Result<Option<EventNumber>, Error> {
+        block_on(&mut self.runtime, self.client.last_event_number(stream))
+    }
+
+    /// Request the names of the streams, optionally filtered by a glob pattern.
+    pub fn stream_names(&mut self, pattern: Option<String>) -> Result<Vec<StreamName>, Error> {
+        block_on(&mut self.runtime, self.client.stream_names(pattern))
+    }
+
+    /// Request the events of a stream that are in the given range (exclusive end),
+    /// the unspecified bounds are open.
+    pub fn get_range(
+        &mut self,
+        stream: StreamName,
+        from: Option<EventNumber>,
+        to: Option<EventNumber>,
+    ) -> Result<Vec<Event>, Error> {
+        let range = self.client.get_range(stream, from, to);
+        block_on(&mut self.runtime, range)
+    }
+
+    /// Subscribe to a stream on a connection of its own, the iterator blocks until
+    /// the next event is received and ends with the range subscribed to, if it has
+    /// one, or when the stream is deleted.
+    pub fn subscribe_iter(&mut self, stream: EsStream) -> Result<Events<'_>, Error> {
+        let subscription = block_on(&mut self.runtime, self.client.subscribe(stream))?;
+        Ok(Events {
+            runtime: &mut self.runtime,
+            subscription,
+        })
+    }
+}
+
+/// The events of a subscription, each call to `next` blocks until an event is received.
+pub struct Events<'a> {
+    runtime: &'a mut Runtime,
+    subscription: Subscription,
+}
+
+impl Iterator for Events<'_> {
+    type Item = Result<Event, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        block_on(self.runtime, self.subscription.next())
+    }
+}
@@ -0,0 +1,258 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::future::{self, Either, Loop};
+use futures::sync::mpsc::UnboundedSender;
+use futures::Future;
+use log::warn;
+
+use super::blocking::BlockingClient;
+use super::compat::{authenticate, check_primary, connect, connect_tls};
+use super::compat::{negotiate_compression, negotiate_frame_size};
+use super::compat::{ClientConnection, PairedConnection, SubController, SubStream};
+use super::sub::sub_connect_with;
+use super::{is_retryable, Client, ConnectionState, Credentials, Error, PublishFailure};
+use super::{Pool, RetryPolicy, TlsConfig};
+
+/// Configures and opens connections with a server.
+///
+/// The credentials, if any, are used to authenticate the connection
+/// each time it is opened, reconnections included.
+#[derive(Debug, Clone)]
+pub struct ConnectBuilder {
+    pub(crate) addr: SocketAddr,
+    pub(crate) fallbacks: Vec<SocketAddr>,
+    pub(crate) username: Option<String>,
+    pub(crate) password: Option<String>,
+    pub(crate) keepalive: Option<Duration>,
+    pub(crate) tls: Option<TlsConfig>,
+    pub(crate) max_event_size: Option<usize>,
+    pub(crate) compression: Option<u64>,
+    pub(crate) frame_size: Option<u64>,
+    pub(crate) notifier: Option<UnboundedSender<ConnectionState>>,
+    pub(crate) publish_errors: Option<UnboundedSender<PublishFailure>>,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) command_retry: RetryPolicy,
+}
+
+impl ConnectBuilder {
+    /// Create a builder that connects to the server at the given address.
+    pub fn new(addr: SocketAddr) -> ConnectBuilder {
+        ConnectBuilder {
+            addr,
+            fallbacks: Vec::new(),
+            username: None,
+            password: None,
+            keepalive: None,
+            tls: None,
+            max_event_size: None,
+            compression: None,
+            frame_size: None,
+            notifier: None,
+            publish_errors: None,
+            retry: RetryPolicy::new(),
+            command_retry: RetryPolicy::commands(),
+        }
+    }
+
+    /// Fail over to the server at this address when the ones given before are unreachable.
+    ///
+    /// Paired connections only use the servers that are not replicas, the publications are
+    /// always sent to the primary. Sub connections use the first reachable server, it must
+    /// replicate the others for the subscriptions to resume from the last events received.
+    pub fn fallback(mut self, addr: SocketAddr) -> ConnectBuilder {
+        self.fallbacks.push(addr);
+        self
+    }
+
+    /// Send the changes of the state of the connections to this channel,
+    /// the connections are established and lost as the servers fail over.
+    pub fn notify(mut self, notifier: UnboundedSender<ConnectionState>) -> ConnectBuilder {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Send the failures of the publications that are not waited for to this channel,
+    /// see `Client::publish_with`.
+    pub fn publish_errors(mut self, notifier: UnboundedSender<PublishFailure>) -> ConnectBuilder {
+        self.publish_errors = Some(notifier);
+        self
+    }
+
+    /// Authenticate as this user when connecting, the server must use an ACL.
+    ///
+    /// A password must also be specified.
+    pub fn username<S: Into<String>>(mut self, username: S) -> ConnectBuilder {
+        self.username = Some(username.into());
+        self
+    }
+
+    /// Authenticate with this password when connecting.
+    pub fn password<S: Into<String>>(mut self, password: S) -> ConnectBuilder {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Send a ping after `interval` of inactivity and reconnect if the server
+    /// does not answer in the next `interval`, only used by sub connections.
+    pub fn keepalive(mut self, interval: Duration) -> ConnectBuilder {
+        self.keepalive = Some(interval);
+        self
+    }
+
+    /// Encrypt the connection using TLS, all the servers must present
+    /// a certificate valid for the domain name of the configuration.
+    pub fn tls(mut self, tls: TlsConfig) -> ConnectBuilder {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Reject the publications of events whose data is larger than `size` bytes
+    /// before sending them, it must not be greater than the limit of the server.
+    pub fn max_event_size(mut self, size: usize) -> ConnectBuilder {
+        self.max_event_size = Some(size);
+        self
+    }
+
+    /// Ask the server to compress the responses larger than `threshold` bytes, e.g. the
+    /// events replayed by a subscription. The responses of the servers that do not
+    /// support the compression are received uncompressed.
+    pub fn compression(mut self, threshold: u64) -> ConnectBuilder {
+        self.compression = Some(threshold);
+        self
+    }
+
+    /// Send and receive the bulk strings larger than `size` bytes, e.g. the data of large
+    /// events, in chunks of at most `size` bytes. The servers that do not support it
+    /// exchange them whole.
+    pub fn frame_size(mut self, size: u64) -> ConnectBuilder {
+        self.frame_size = Some(size);
+        self
+    }
+
+    /// Retry to open the connections according to this policy, when connecting
+    /// and when reconnecting after the connection has been lost.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> ConnectBuilder {
+        self.retry = policy;
+        self
+    }
+
+    /// Send the idempotent commands of the `Client` again according to this policy
+    /// when they fail, e.g. the reads or the publications with a deduplication ID.
+    pub fn command_retry_policy(mut self, policy: RetryPolicy) -> ConnectBuilder {
+        self.command_retry = policy;
+        self
+    }
+
+    fn credentials(&self) -> Option<Credentials> {
+        self.password.clone().map(|password| Credentials {
+            username: self.username.clone(),
+            password,
+        })
+    }
+
+    pub(crate) fn notify_state(&self, state: ConnectionState) {
+        if let Some(notifier) = &self.notifier {
+            // the application may not listen to the notifications anymore
+            let _ = notifier.unbounded_send(state);
+        }
+    }
+
+    fn connect_to(
+        &self,
+        addr: SocketAddr,
+    ) -> impl Future<Item = ClientConnection, Error = io::Error> {
+        let connection = match &self.tls {
+            Some(tls) => Either::A(connect_tls(&addr, tls)),
+            None => Either::B(connect(&addr)),
+        };
+
+        let credentials = self.credentials();
+        let compression = self.compression;
+        let frame_size = self.frame_size;
+        connection
+            .and_then(move |connection| match credentials {
+                Some(credentials) => Either::A(authenticate(connection, credentials)),
+                None => Either::B(future::ok(connection)),
+            })
+            .and_then(move |connection| match compression {
+                Some(threshold) => Either::A(negotiate_compression(connection, threshold)),
+                None => Either::B(future::ok(connection)),
+            })
+            .and_then(move |connection| match frame_size {
+                Some(size) => Either::A(negotiate_frame_size(connection, size)),
+                None => Either::B(future::ok(connection)),
+            })
+    }
+
+    /// Open a framed connection with the first server that accepts it, in the order
+    /// the addresses were given, and returns the address of the server.
+    ///
+    /// The servers that are replicas are skipped if `primary` is `true`
+    /// and there are fallbacks to choose from.
+    pub(crate) fn connect_any(
+        &self,
+        primary: bool,
+    ) -> impl Future<Item = (SocketAddr, ClientConnection), Error = io::Error> {
+        let builder = self.clone();
+        let check = primary && !self.fallbacks.is_empty();
+        let mut addrs = vec![self.addr];
+        addrs.extend_from_slice(&self.fallbacks);
+
+        future::loop_fn(0, move |i| {
+            let addr = addrs[i];
+            let last = i + 1 == addrs.len();
+            let connection = builder.connect_to(addr);
+            let connection = if check {
+                Either::A(connection.and_then(check_primary))
+            } else {
+                Either::B(connection)
+            };
+
+            connection.then(move |result| match result {
+                Ok(connection) => Ok(Loop::Break((addr, connection))),
+                Err(e) if last || !is_retryable(&e) => Err(e),
+                Err(e) => {
+                    warn!("Failing over from {}; {}", addr, e);
+                    Ok(Loop::Continue(i + 1))
+                }
+            })
+        })
+    }
+
+    /// Open a framed connection with the server and authenticate it, the fallbacks are
+    /// tried in order if it is unreachable. This connection is not reconnected when lost.
+    pub fn connect(&self) -> impl Future<Item = ClientConnection, Error = io::Error> {
+        self.connect_any(false).map(|(_, connection)| connection)
+    }
+
+    /// Open a paired connection with the server.
+    pub fn paired(
+        self,
+    ) -> impl Future<Item = PairedConnection, Error = tokio_retry::Error<io::Error>> {
+        PairedConnection::connect_with(self)
+    }
+
+    /// Open a client with the server, its commands are `async` functions.
+    pub async fn client(self) -> Result<Client, Error> {
+        Client::connect_with(self).await
+    }
+
+    /// Open a pool of `size` clients with the server, see `Pool`.
+    pub async fn pool(self, size: usize) -> Result<Pool, Error> {
+        Pool::connect_with(self, size).await
+    }
+
+    /// Open a client with the server whose commands block the thread.
+    pub fn blocking(self) -> Result<BlockingClient, Error> {
+        BlockingClient::connect_with(self)
+    }
+
+    /// Open a sub connection with the server.
+    pub fn sub(
+        self,
+    ) -> impl Future<Item = (SubController, SubStream), Error = tokio_retry::Error<io::Error>> {
+        sub_connect_with(self)
+    }
+}
@@ -0,0 +1,452 @@
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use std::{fmt, io};
+
+use futures::sync::{mpsc, oneshot};
+use futures::Future;
+use futures03::compat::{Compat01As03, Future01CompatExt, Stream01CompatExt};
+use futures03::{ready, Stream};
+use log::warn;
+use meilies::reqresp::{ExpectedVersion, Request, Response};
+use meilies::stream::{Event, EventData, EventHeaders, EventName, EventNumber};
+use meilies::stream::{Stream as EsStream, StreamName};
+use tokio::timer::Delay;
+
+use super::compat::{PairedConnection, PairedConnectionError, ProtocolError};
+use super::compat::{SubController, SubStream};
+use super::pipeline::{Confirmation, Pending, Pipeline, Queued};
+use super::{ConnectBuilder, RetryPolicy, ServerError};
+
+/// The errors of the commands of a `Client` and of its subscriptions.
+#[derive(Debug)]
+pub enum Error {
+    /// The connection could not be established, even after retrying.
+    Connect(io::Error),
+    /// The server answered with an error, its kind is given by its code.
+    Server(ServerError),
+    /// The data of an event is larger than the maximum event size
+    /// given to the builder, the publication has not been sent.
+    EventTooLarge(usize),
+    ConnectionClosed,
+    /// A message could not be encoded or decoded.
+    Protocol(String),
+    InvalidServerResponse(Response),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Connect(error) => write!(f, "connection error: {}", error),
+            Error::Server(error) => write!(f, "server side error: {}", error),
+            Error::EventTooLarge(size) => write!(f, "event too large: {} bytes", size),
+            Error::ConnectionClosed => write!(f, "connection closed"),
+            Error::Protocol(error) => write!(f, "protocol error: {}", error),
+            Error::InvalidServerResponse(response) => {
+                write!(f, "invalid server response received: {:?}", response)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Returns the delay after which the command can be sent again if it may succeed then,
+    /// i.e. the connection has been lost or the server throttled the command.
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Error::ConnectionClosed | Error::Protocol(_) => Some(Duration::from_secs(0)),
+            Error::Server(error) => error.retry_after(),
+            _otherwise => None,
+        }
+    }
+}
+
+impl From<tokio_retry::Error<io::Error>> for Error {
+    fn from(error: tokio_retry::Error<io::Error>) -> Error {
+        match error {
+            tokio_retry::Error::OperationError(e) => Error::Connect(e),
+            tokio_retry::Error::TimerError(e) => {
+                Error::Connect(io::Error::new(io::ErrorKind::Other, e))
+            }
+        }
+    }
+}
+
+impl From<PairedConnectionError> for Error {
+    fn from(error: PairedConnectionError) -> Error {
+        match error {
+            PairedConnectionError::ServerSide(error) => Error::Server(error),
+            PairedConnectionError::EventTooLarge(size) => Error::EventTooLarge(size),
+            PairedConnectionError::ConnectionClosed => Error::ConnectionClosed,
+            PairedConnectionError::RequestMsgError(error) => Error::Protocol(error.to_string()),
+            PairedConnectionError::ResponseMsgError(error) => Error::Protocol(error.to_string()),
+            PairedConnectionError::InvalidServerResponse(response) => {
+                Error::InvalidServerResponse(response)
+            }
+        }
+    }
+}
+
+impl From<ProtocolError> for Error {
+    fn from(error: ProtocolError) -> Error {
+        Error::Protocol(error.to_string())
+    }
+}
+
+/// A client of a server whose commands are `async` functions, the commands are sent
+/// one after the other on a connection that is reconnected when lost.
+///
+/// The client runs on the tokio 0.1 runtime, the futures must be polled by it,
+/// e.g. using `futures::compat` to spawn them with `tokio::run`.
+pub struct Client {
+    builder: ConnectBuilder,
+    /// The policy the idempotent commands are sent again with when they fail.
+    retry: RetryPolicy,
+    /// The connection is taken by the command being sent and given back
+    /// once it has been answered, a new one is opened if it failed.
+    connection: Option<PairedConnection>,
+    /// The connection the publications of `publish_with` are pipelined on, opened when
+    /// the first one is sent.
+    pipeline: Option<mpsc::UnboundedSender<Queued>>,
+}
+
+impl Client {
+    /// Open a client with the server at this address, see `ConnectBuilder::client`
+    /// to authenticate, use TLS or fail over to other servers.
+    pub async fn connect(addr: SocketAddr) -> Result<Client, Error> {
+        Client::connect_with(ConnectBuilder::new(addr)).await
+    }
+
+    pub(crate) async fn connect_with(builder: ConnectBuilder) -> Result<Client, Error> {
+        let connection = builder.clone().paired().compat().await?;
+        Ok(Client {
+            retry: builder.command_retry,
+            builder,
+            connection: Some(connection),
+            pipeline: None,
+        })
+    }
+
+    async fn connection(&mut self) -> Result<PairedConnection, Error> {
+        match self.connection.take() {
+            Some(connection) => Ok(connection),
+            None => Ok(self.builder.clone().paired().compat().await?),
+        }
+    }
+
+    /// Replace the retry policy of the idempotent commands, e.g. for the next command only.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    /// Sends an idempotent command, it is sent again on a new connection
+    /// according to the retry policy if it failed and may succeed later.
+    async fn send_idempotent<T, F, C>(&mut self, mut command: F) -> Result<T, Error>
+    where
+        F: FnMut(PairedConnection) -> C,
+        C: Future<Item = (T, PairedConnection), Error = PairedConnectionError>,
+    {
+        let mut delays = self.retry.delays();
+        loop {
+            let connection = self.connection().await?;
+            let error = match command(connection).compat().await {
+                Ok((output, connection)) => {
+                    self.connection = Some(connection);
+                    return Ok(output);
+                }
+                Err(error) => Error::from(error),
+            };
+
+            let delay = match (error.retry_after(), delays.next()) {
+                (Some(retry_after), Some(delay)) => delay.max(retry_after),
+                (_, _) => return Err(error),
+            };
+
+            warn!("Sending the command again in {:?}; {}", delay, error);
+            let delay = Delay::new(Instant::now() + delay).compat().await;
+            delay.map_err(|e| Error::Connect(io::Error::new(io::ErrorKind::Other, e)))?;
+        }
+    }
+
+    /// Send a ping to the server, fails if the connection can not be used.
+    pub async fn ping(&mut self) -> Result<(), Error> {
+        let connection = self.connection().await?;
+        let (_, connection) = connection.ping(None).compat().await?;
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    /// Publish an event to a stream, returns the number the server assigned to it.
+    pub async fn publish(
+        &mut self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+    ) -> Result<EventNumber, Error> {
+        self.send_publish(
+            stream,
+            event_name,
+            event_data,
+            None,
+            None,
+            EventHeaders::new(),
+        )
+        .await
+    }
+
+    /// Publish an event to a stream on a connection where the publications are pipelined,
+    /// it returns according to the confirmation asked. Only the `Durable` publications
+    /// return the number of their event, the failures of the others are sent to the
+    /// channel given to `ConnectBuilder::publish_errors`.
+    ///
+    /// The events are appended in the order they are published with `publish_with`,
+    /// they are not ordered with the ones published with the other methods.
+    pub async fn publish_with(
+        &mut self,
+        confirmation: Confirmation,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+    ) -> Result<Option<EventNumber>, Error> {
+        if let Some(max) = self.builder.max_event_size {
+            if event_data.0.len() > max {
+                return Err(Error::EventTooLarge(event_data.0.len()));
+            }
+        }
+
+        let (answer, answered) = match confirmation {
+            Confirmation::Durable => {
+                let (answer, answered) = oneshot::channel();
+                (Some(answer), Some(answered))
+            }
+            Confirmation::Written | Confirmation::FireAndForget => (None, None),
+        };
+
+        let (written, flushed) = match confirmation {
+            Confirmation::Written => {
+                let (written, flushed) = oneshot::channel();
+                (Some(written), Some(flushed))
+            }
+            Confirmation::Durable | Confirmation::FireAndForget => (None, None),
+        };
+
+        let request = Request::Publish {
+            stream: stream.clone(),
+            event_name,
+            event_data,
+            expected_version: None,
+            dedup_id: None,
+            headers: EventHeaders::new(),
+            deliver_at: None,
+            ack: None,
+        };
+        let queued = Queued {
+            request,
+            written,
+            pending: Pending { stream, answer },
+        };
+
+        let pipeline = match &self.pipeline {
+            Some(pipeline) => pipeline.clone(),
+            None => {
+                let pipeline = Pipeline::spawn(self.builder.clone()).compat().await?;
+                self.pipeline = Some(pipeline.clone());
+                pipeline
+            }
+        };
+
+        if pipeline.unbounded_send(queued).is_err() {
+            self.pipeline = None;
+            return Err(Error::ConnectionClosed);
+        }
+
+        if let Some(flushed) = flushed {
+            flushed
+                .compat()
+                .await
+                .map_err(|_| Error::ConnectionClosed)?;
+        }
+
+        match answered {
+            Some(answered) => match answered.compat().await {
+                Ok(result) => result.map(Some),
+                Err(_canceled) => Err(Error::ConnectionClosed),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Publish an event to a stream only if the stream is at the expected version,
+    /// returns a `WrongVer` server error if the stream is at another version.
+    pub async fn publish_expected(
+        &mut self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        expected_version: ExpectedVersion,
+    ) -> Result<EventNumber, Error> {
+        let expected_version = Some(expected_version);
+        let headers = EventHeaders::new();
+        self.send_publish(
+            stream,
+            event_name,
+            event_data,
+            expected_version,
+            None,
+            headers,
+        )
+        .await
+    }
+
+    /// Publish an event to a stream with a deduplication ID, the event is only appended
+    /// once, the publication is sent again according to the retry policy if it failed.
+    pub async fn publish_once(
+        &mut self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        dedup_id: String,
+    ) -> Result<EventNumber, Error> {
+        self.send_idempotent(move |connection| {
+            let (stream, dedup_id) = (stream.clone(), dedup_id.clone());
+            connection.publish_once(stream, event_name.clone(), event_data.clone(), dedup_id)
+        })
+        .await
+    }
+
+    /// Publish an event to a stream along with headers, e.g. a correlation ID.
+    pub async fn publish_with_headers(
+        &mut self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        headers: EventHeaders,
+    ) -> Result<EventNumber, Error> {
+        self.send_publish(stream, event_name, event_data, None, None, headers)
+            .await
+    }
+
+    async fn send_publish(
+        &mut self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        expected_version: Option<ExpectedVersion>,
+        dedup_id: Option<String>,
+        headers: EventHeaders,
+    ) -> Result<EventNumber, Error> {
+        let connection = self.connection().await?;
+        let publish = connection.send_publish(
+            stream,
+            event_name,
+            event_data,
+            expected_version,
+            dedup_id,
+            headers,
+        );
+        let (number, connection) = publish.compat().await?;
+        self.connection = Some(connection);
+        Ok(number)
+    }
+
+    /// Request the last event number of the stream, `None` if it does not contain any event.
+    pub async fn last_event_number(
+        &mut self,
+        stream: StreamName,
+    ) -> Result<Option<EventNumber>, Error> {
+        self.send_idempotent(move |connection| {
+            let last = connection.last_event_number(stream.clone());
+            last.map(|(_, number, connection)| (number, connection))
+        })
+        .await
+    }
+
+    /// Request the names of the streams, optionally filtered by a glob pattern.
+    pub async fn stream_names(
+        &mut self,
+        pattern: Option<String>,
+    ) -> Result<Vec<StreamName>, Error> {
+        self.send_idempotent(move |connection| connection.stream_names(pattern.clone()))
+            .await
+    }
+
+    /// Request the events of a stream that are in the given range (exclusive end),
+    /// the unspecified bounds are open.
+    pub async fn get_range(
+        &mut self,
+        stream: StreamName,
+        from: Option<EventNumber>,
+        to: Option<EventNumber>,
+    ) -> Result<Vec<Event>, Error> {
+        self.send_idempotent(move |connection| connection.get_range(stream.clone(), from, to))
+            .await
+    }
+
+    /// Subscribe to a stream on a connection of its own, the events are returned in order
+    /// until the end of the range, if it has one, or until the stream is deleted.
+    pub async fn subscribe(&self, stream: EsStream) -> Result<Subscription, Error> {
+        let (mut controller, events) = self.builder.clone().sub().compat().await?;
+        controller.subscribe_to(stream);
+
+        Ok(Subscription {
+            _controller: controller,
+            events: events.compat(),
+            ended: false,
+        })
+    }
+}
+
+/// The events of a subscription to a single stream, a `Stream` of `Result<Event, Error>`.
+pub struct Subscription {
+    // the requests are sent while the controller is alive
+    _controller: SubController,
+    events: Compat01As03<SubStream>,
+    ended: bool,
+}
+
+impl Stream for Subscription {
+    type Item = Result<Event, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        while !self.ended {
+            let response = match ready!(Pin::new(&mut self.events).poll_next(cx)) {
+                Some(Ok(response)) => response,
+                Some(Err(error)) => return Poll::Ready(Some(Err(error.into()))),
+                None => return Poll::Ready(None),
+            };
+
+            match response {
+                Ok(Response::Event {
+                    number,
+                    timestamp,
+                    event_name,
+                    event_data,
+                    headers,
+                    ..
+                }) => {
+                    let event = Event {
+                        number,
+                        timestamp,
+                        name: event_name,
+                        data: event_data,
+                        headers,
+                    };
+                    return Poll::Ready(Some(Ok(event)));
+                }
+                Ok(Response::RangeEnd { .. }) | Ok(Response::StreamDeleted { .. }) => {
+                    self.ended = true;
+                }
+                Ok(_otherwise) => (),
+                Err(error) => {
+                    let error = Error::Server(ServerError::from_error(error));
+                    return Poll::Ready(Some(Err(error)));
+                }
+            }
+        }
+
+        Poll::Ready(None)
+    }
+}
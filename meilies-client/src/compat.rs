@@ -0,0 +1,147 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::stream::{SplitSink, SplitStream};
+use futures::{Future, Sink, Stream};
+use log::warn;
+use meilies::reqresp::{ClientCodec, Request, RequestMsgError, Response, ResponseMsgError};
+use meilies::resp::RespMsgError;
+use tokio::codec::{Decoder, Framed};
+use tokio::net::TcpStream;
+
+pub use crate::paired::{paired_connect, PairedConnection, PairedConnectionError};
+pub use crate::sub::{
+    sub_connect, sub_connect_with_keepalive, ProtocolError, SubController, SubStream,
+};
+pub use crate::tls::MaybeTlsStream;
+
+use crate::tls::tls_handshake;
+use crate::{Credentials, TlsConfig};
+
+pub type ClientConnection = Framed<MaybeTlsStream, ClientCodec>;
+pub type ClientConnectionWriter = SplitSink<Framed<MaybeTlsStream, ClientCodec>>;
+pub type ClientConnectionReader = SplitStream<Framed<MaybeTlsStream, ClientCodec>>;
+
+/// Open a framed connection with a server using RESP
+pub fn connect(addr: &SocketAddr) -> impl Future<Item = ClientConnection, Error = io::Error> {
+    connect_tcp(addr).map(|socket| ClientCodec::default().framed(MaybeTlsStream::Plain(socket)))
+}
+
+/// Open a framed connection with a server using RESP over TLS
+pub fn connect_tls(
+    addr: &SocketAddr,
+    tls: &TlsConfig,
+) -> impl Future<Item = ClientConnection, Error = io::Error> {
+    let tls = tls.clone();
+    connect_tcp(addr)
+        .and_then(move |socket| tls_handshake(socket, &tls))
+        .map(|stream| ClientCodec::default().framed(MaybeTlsStream::Tls(Box::new(stream))))
+}
+
+fn connect_tcp(addr: &SocketAddr) -> impl Future<Item = TcpStream, Error = io::Error> {
+    TcpStream::connect(addr).map(|socket| {
+        let duration = Duration::from_millis(50);
+        if let Err(e) = socket.set_keepalive(Some(duration)) {
+            warn!("set_keepalive error; {}", e);
+        }
+
+        socket
+    })
+}
+
+/// Sends a request and returns the reply of the server, the connection
+/// being closed before replying is an unexpected end of file.
+fn request_reply(
+    connection: ClientConnection,
+    request: Request,
+) -> impl Future<Item = (Result<Response, String>, ClientConnection), Error = io::Error> {
+    connection
+        .send(request)
+        .map_err(|e| match e {
+            RequestMsgError::RespMsgError(RespMsgError::IoError(e)) => e,
+            e => io::Error::new(io::ErrorKind::Other, e.to_string()),
+        })
+        .and_then(|framed| {
+            framed.into_future().map_err(|(e, _)| match e {
+                ResponseMsgError::RespMsgError(RespMsgError::IoError(e)) => e,
+                e => io::Error::new(io::ErrorKind::Other, e.to_string()),
+            })
+        })
+        .and_then(|(first, connection)| match first {
+            Some(reply) => Ok((reply, connection)),
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        })
+}
+
+fn invalid_response(response: Response) -> io::Error {
+    let error = format!("invalid server response received: {:?}", response);
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+pub(crate) fn authenticate(
+    connection: ClientConnection,
+    credentials: Credentials,
+) -> impl Future<Item = ClientConnection, Error = io::Error> {
+    let Credentials { username, password } = credentials;
+    let command = Request::Auth { username, password };
+
+    request_reply(connection, command).and_then(|(reply, connection)| match reply {
+        Ok(Response::Ok) => Ok(connection),
+        Ok(response) => Err(invalid_response(response)),
+        Err(error) => Err(io::Error::new(io::ErrorKind::PermissionDenied, error)),
+    })
+}
+
+/// Fails with a retryable error if the server is a replica, it does not accept the writes.
+pub(crate) fn check_primary(
+    connection: ClientConnection,
+) -> impl Future<Item = ClientConnection, Error = io::Error> {
+    let command = Request::ReplicationInfo;
+    request_reply(connection, command).and_then(|(reply, connection)| match reply {
+        Ok(Response::ReplicationInfo { primary: None, .. }) => Ok(connection),
+        Ok(Response::ReplicationInfo {
+            primary: Some(primary),
+            ..
+        }) => {
+            let error = format!("server is a replica of {}", primary);
+            Err(io::Error::new(io::ErrorKind::Other, error))
+        }
+        Ok(response) => Err(invalid_response(response)),
+        Err(error) => Err(io::Error::new(io::ErrorKind::Other, error)),
+    })
+}
+
+/// Asks the server to compress the responses larger than `threshold` bytes,
+/// the connection is left uncompressed if the server does not support it.
+pub(crate) fn negotiate_compression(
+    connection: ClientConnection,
+    threshold: u64,
+) -> impl Future<Item = ClientConnection, Error = io::Error> {
+    let command = Request::Compress { threshold };
+    request_reply(connection, command).and_then(|(reply, connection)| match reply {
+        Ok(Response::Compression { .. }) => Ok(connection),
+        Ok(response) => Err(invalid_response(response)),
+        Err(error) => {
+            warn!("The responses of the server are not compressed; {}", error);
+            Ok(connection)
+        }
+    })
+}
+
+/// Asks the server to send the bulk strings larger than `size` bytes in chunks, the
+/// requests are chunked too once it agrees, they are sent whole if it does not support it.
+pub(crate) fn negotiate_frame_size(
+    connection: ClientConnection,
+    size: u64,
+) -> impl Future<Item = ClientConnection, Error = io::Error> {
+    let command = Request::FrameSize { size };
+    request_reply(connection, command).and_then(|(reply, connection)| match reply {
+        Ok(Response::FrameSize { .. }) => Ok(connection),
+        Ok(response) => Err(invalid_response(response)),
+        Err(error) => {
+            warn!("The bulk strings are not sent in chunks; {}", error);
+            Ok(connection)
+        }
+    })
+}
@@ -1,34 +1,35 @@
-use std::io;
-use std::net::SocketAddr;
-use std::time::Duration;
-
-use futures::stream::{SplitSink, SplitStream};
-use futures::Future;
-use log::warn;
-use meilies::reqresp::ClientCodec;
-use tokio::codec::{Decoder, Framed};
-use tokio::net::TcpStream;
-
+/// A client that blocks the thread, for the applications that are not asynchronous.
+pub mod blocking;
+mod builder;
+mod client;
+/// The connections built on the futures 0.1 combinators, used by the servers and the bridges.
+pub mod compat;
 mod paired;
+mod pipeline;
+mod pool;
+mod retry;
+mod server_error;
 mod steel_connection;
 mod sub;
+mod tls;
 
-pub use self::paired::{paired_connect, PairedConnection};
-use self::steel_connection::{retry_strategy, SteelConnection};
-pub use self::sub::{sub_connect, ProtocolError, SubController, SubStream};
-
-pub type ClientConnection = Framed<TcpStream, ClientCodec>;
-pub type ClientConnectionWriter = SplitSink<Framed<TcpStream, ClientCodec>>;
-pub type ClientConnectionReader = SplitStream<Framed<TcpStream, ClientCodec>>;
-
-/// Open a framed connection with a server using RESP
-pub fn connect(addr: &SocketAddr) -> impl Future<Item = ClientConnection, Error = io::Error> {
-    TcpStream::connect(addr).map(|socket| {
-        let duration = Duration::from_millis(50);
-        if let Err(e) = socket.set_keepalive(Some(duration)) {
-            warn!("set_keepalive error; {}", e);
-        }
+pub use self::builder::ConnectBuilder;
+pub use self::client::{Client, Error, Subscription};
+pub use self::paired::{
+    Latencies, MonitoredCommand, ReplicationInfo, ServerHello, ServerInfo, StreamInfo,
+};
+pub use self::pipeline::{Confirmation, PublishFailure};
+pub use self::pool::{Pool, PooledClient};
+pub use self::retry::RetryPolicy;
+pub use self::server_error::ServerError;
+pub use self::steel_connection::ConnectionState;
+use self::steel_connection::{is_retryable, SteelConnection};
+pub use self::tls::{load_certs, load_private_key, TlsConfig};
 
-        ClientCodec::default().framed(socket)
-    })
+/// The credentials used to authenticate a connection,
+/// the username is only required when the server uses an ACL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: Option<String>,
+    pub password: String,
 }
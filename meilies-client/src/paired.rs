@@ -1,15 +1,18 @@
 use std::net::SocketAddr;
+use std::time::Duration;
 use std::{fmt, io};
 
-use futures::{Future, Sink, Stream};
+use futures::{future, Future, Sink, Stream};
 use log::warn;
-use meilies::reqresp::{Request, RequestMsgError};
-use meilies::reqresp::{Response, ResponseMsgError};
-use meilies::stream::{EventData, EventName, EventNumber, StreamName};
-use tokio_retry::Retry;
+use meilies::reqresp::{AckLevel, ClientInfo, ExpectedVersion, Request, RequestMsgError};
+use meilies::reqresp::{LatencyStats, Response, ResponseMsgError, SlowLogEntry};
+use meilies::stream::{Event, EventData, EventHeaders, EventName, EventNumber};
+use meilies::stream::{GroupName, PendingEvent};
+use meilies::stream::{StreamName, Timestamp};
+use tokio_retry::RetryIf;
 
-use super::{connect, SteelConnection};
-use crate::steel_connection::retry_strategy;
+use super::{ConnectBuilder, ServerError, SteelConnection};
+use crate::steel_connection::is_retryable;
 
 /// Open a framed paired connection with a server.
 pub fn paired_connect(
@@ -24,9 +27,120 @@ pub struct PairedConnection {
     connection: SteelConnection,
 }
 
+/// The state of the replication of a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicationInfo {
+    /// The address of the primary if the server is a replica.
+    pub primary: Option<String>,
+    /// Whether the replica has received all the events published before it connected.
+    pub caught_up: bool,
+    /// The global number of the last event the server has.
+    pub last_global: Option<EventNumber>,
+    /// The number of milliseconds it took to replicate the last event replicated.
+    pub lag: Option<u64>,
+}
+
+/// The statistics of a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub version: String,
+    /// The number of seconds since the server started.
+    pub uptime: u64,
+    /// The resident memory of the server in bytes, if known.
+    pub memory: Option<u64>,
+    pub streams: u64,
+    /// The number of events published since the creation of the database.
+    pub events: u64,
+    /// The number of clients connected to the server.
+    pub connections: u64,
+    /// The number of subscriptions of the connected clients.
+    pub subscriptions: u64,
+}
+
+/// The state and the storage statistics of a stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// The number of the first event kept, the events below have been removed.
+    pub first: EventNumber,
+    pub last: Option<EventNumber>,
+    /// The number of events kept, the compacted streams have fewer events than numbers.
+    pub events: u64,
+    /// The approximate size in bytes of the events kept.
+    pub bytes: u64,
+    /// The time the stream was created, unknown for the streams created by older servers.
+    pub created: Option<Timestamp>,
+    /// The number of milliseconds the events are kept, `None` if forever.
+    pub retention_ms: Option<u64>,
+    pub max_events: Option<u64>,
+    pub max_bytes: Option<u64>,
+    /// The number of subscriptions of the connected clients to the stream.
+    pub subscribers: u64,
+}
+
+/// The latency percentiles of the commands and of the internal operations of the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Latencies {
+    pub commands: Vec<LatencyStats>,
+    /// The appends, the catch-up reads and the deliveries of the events.
+    pub operations: Vec<LatencyStats>,
+}
+
+/// A command processed by the server, as received by a monitoring connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitoredCommand {
+    pub timestamp: Timestamp,
+    /// The id of the client that sent the command.
+    pub client: u64,
+    /// The name and the arguments of the command.
+    pub command: Vec<String>,
+}
+
+/// The version and the features of a server, returned by the hello command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerHello {
+    pub version: String,
+    /// The version of the protocol used by the connection.
+    pub proto: u64,
+    pub features: Vec<String>,
+}
+
+impl ServerHello {
+    /// Returns `true` if the server supports the feature, e.g. `consumer-groups`.
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+impl ServerInfo {
+    /// Reads the statistics sent by the server, the unknown ones are ignored.
+    fn from_fields(fields: &[(String, String)]) -> Option<ServerInfo> {
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let number = |name: &str| field(name).and_then(|value| value.parse().ok());
+
+        Some(ServerInfo {
+            version: field("version")?.to_owned(),
+            uptime: number("uptime")?,
+            memory: number("memory"),
+            streams: number("streams")?,
+            events: number("events")?,
+            connections: number("connections")?,
+            subscriptions: number("subscriptions")?,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum PairedConnectionError {
-    ServerSide(String),
+    /// The server answered with an error, its kind is given by its code.
+    ServerSide(ServerError),
+    /// The data of an event is larger than the maximum event size
+    /// given to the builder, the publication has not been sent.
+    EventTooLarge(usize),
     ConnectionClosed,
     RequestMsgError(RequestMsgError),
     ResponseMsgError(ResponseMsgError),
@@ -39,6 +153,7 @@ impl fmt::Display for PairedConnectionError {
 
         match self {
             ServerSide(error) => write!(f, "server side error: {}", error),
+            EventTooLarge(size) => write!(f, "event too large: {} bytes", size),
             ConnectionClosed => write!(f, "connection closed"),
             RequestMsgError(error) => write!(f, "invalid Request: {}", error),
             ResponseMsgError(error) => write!(f, "invalid Response received: {}", error),
@@ -49,26 +164,220 @@ impl fmt::Display for PairedConnectionError {
     }
 }
 
+impl PairedConnectionError {
+    /// Returns the delay after which a throttled publication can be retried.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            PairedConnectionError::ServerSide(error) => error.retry_after(),
+            _otherwise => None,
+        }
+    }
+}
+
 impl PairedConnection {
     /// Open a framed paired connection with a server.
     pub fn connect(
         addr: SocketAddr,
     ) -> impl Future<Item = PairedConnection, Error = tokio_retry::Error<io::Error>> {
-        Retry::spawn(retry_strategy(), move || {
-            warn!("Connecting to {}", addr);
-            connect(&addr).map(move |connection| {
-                let connection = SteelConnection::new(addr, connection);
-                PairedConnection { connection }
-            })
-        })
+        PairedConnection::connect_with(ConnectBuilder::new(addr))
+    }
+
+    pub(crate) fn connect_with(
+        builder: ConnectBuilder,
+    ) -> impl Future<Item = PairedConnection, Error = tokio_retry::Error<io::Error>> {
+        RetryIf::spawn(
+            builder.retry.delays(),
+            move || {
+                warn!("Connecting to {}", builder.addr);
+                let builder = builder.clone();
+                builder.connect_any(true).map(move |(addr, connection)| {
+                    let connection = SteelConnection::new(builder, true, addr, connection);
+                    PairedConnection { connection }
+                })
+            },
+            is_retryable,
+        )
     }
 
     /// Publish an event to a stream, specifying the event name and data.
+    ///
+    /// Returns the event number the server assigned to the published event.
     pub fn publish(
         self,
         stream: StreamName,
         event_name: EventName,
         event_data: EventData,
+    ) -> impl Future<Item = (EventNumber, PairedConnection), Error = PairedConnectionError> {
+        self.send_publish(
+            stream,
+            event_name,
+            event_data,
+            None,
+            None,
+            EventHeaders::new(),
+        )
+    }
+
+    /// Publish an event to a stream only if the stream is at the expected version,
+    /// i.e. its last event number is the expected one or it is empty.
+    ///
+    /// Returns a `WrongVer` server error if the stream is at another version.
+    pub fn publish_expected(
+        self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        expected_version: ExpectedVersion,
+    ) -> impl Future<Item = (EventNumber, PairedConnection), Error = PairedConnectionError> {
+        self.send_publish(
+            stream,
+            event_name,
+            event_data,
+            Some(expected_version),
+            None,
+            EventHeaders::new(),
+        )
+    }
+
+    /// Publish an event to a stream with a deduplication ID, if an event has recently been
+    /// published with the same ID it is not appended again and its number is returned.
+    ///
+    /// This allows to safely retry a publication after a timeout.
+    pub fn publish_once(
+        self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        dedup_id: String,
+    ) -> impl Future<Item = (EventNumber, PairedConnection), Error = PairedConnectionError> {
+        self.send_publish(
+            stream,
+            event_name,
+            event_data,
+            None,
+            Some(dedup_id),
+            EventHeaders::new(),
+        )
+    }
+
+    /// Publish an event to a stream along with headers, e.g. a correlation ID,
+    /// the headers are delivered to the subscribers with the event.
+    pub fn publish_with_headers(
+        self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        headers: EventHeaders,
+    ) -> impl Future<Item = (EventNumber, PairedConnection), Error = PairedConnectionError> {
+        self.send_publish(stream, event_name, event_data, None, None, headers)
+    }
+
+    /// Publish an event to a stream and only return once it has been replicated according
+    /// to the given acknowledgement level, whatever the level of the connection is.
+    ///
+    /// Returns a `NotReplicated` server error if the replicas did not acknowledge it in time,
+    /// the event has been published nonetheless.
+    pub fn publish_acked(
+        self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        ack: AckLevel,
+    ) -> impl Future<Item = (EventNumber, PairedConnection), Error = PairedConnectionError> {
+        self.send_publish_command(Request::Publish {
+            stream,
+            event_name,
+            event_data,
+            expected_version: None,
+            dedup_id: None,
+            headers: EventHeaders::new(),
+            deliver_at: None,
+            ack: Some(ack),
+        })
+    }
+
+    /// Publish an event with an optional expected version, an optional deduplication ID
+    /// and headers, see `publish_expected`, `publish_once` and `publish_with_headers`.
+    pub fn send_publish(
+        self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        expected_version: Option<ExpectedVersion>,
+        dedup_id: Option<String>,
+        headers: EventHeaders,
+    ) -> impl Future<Item = (EventNumber, PairedConnection), Error = PairedConnectionError> {
+        self.send_publish_command(Request::Publish {
+            stream,
+            event_name,
+            event_data,
+            expected_version,
+            dedup_id,
+            headers,
+            deliver_at: None,
+            ack: None,
+        })
+    }
+
+    fn send_publish_command(
+        self,
+        command: Request,
+    ) -> impl Future<Item = (EventNumber, PairedConnection), Error = PairedConnectionError> {
+        use PairedConnectionError::*;
+
+        let checked = self.check_event_size(&command);
+        future::result(checked)
+            .and_then(move |()| self.connection.send(command).map_err(RequestMsgError))
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Published { number, .. }) => {
+                    Ok((number, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Returns an error if the data of one of the events published is too large.
+    fn check_event_size(&self, command: &Request) -> Result<(), PairedConnectionError> {
+        let max = match self.connection.max_event_size() {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+
+        let largest = match command {
+            Request::Publish { event_data, .. } => event_data.0.len(),
+            Request::PublishBatch { events, .. } => events
+                .iter()
+                .map(|(_, data)| data.0.len())
+                .max()
+                .unwrap_or(0),
+            Request::PublishMulti { writes } => writes
+                .iter()
+                .map(|(_, _, data)| data.0.len())
+                .max()
+                .unwrap_or(0),
+            _otherwise => 0,
+        };
+
+        if largest > max {
+            Err(PairedConnectionError::EventTooLarge(largest))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Schedule an event to be appended to a stream at the given time, it is
+    /// not visible to the subscribers before. The deduplication ID and the headers
+    /// are optional, see `publish_once` and `publish_with_headers`.
+    pub fn publish_at(
+        self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        dedup_id: Option<String>,
+        headers: EventHeaders,
+        deliver_at: Timestamp,
     ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
         use PairedConnectionError::*;
 
@@ -76,8 +385,654 @@ impl PairedConnection {
             stream,
             event_name,
             event_data,
+            expected_version: None,
+            dedup_id,
+            headers,
+            deliver_at: Some(deliver_at),
+            ack: None,
+        };
+
+        let checked = self.check_event_size(&command);
+        future::result(checked)
+            .and_then(move |()| self.connection.send(command).map_err(RequestMsgError))
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Scheduled { .. }) => Ok(PairedConnection { connection }),
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Publish multiple events to the stream in a single round trip, they are all
+    /// appended or none of them are.
+    ///
+    /// Returns the numbers of the first and the last events appended.
+    pub fn publish_batch(
+        self,
+        stream: StreamName,
+        events: Vec<(EventName, EventData)>,
+    ) -> impl Future<Item = (EventNumber, EventNumber, PairedConnection), Error = PairedConnectionError>
+    {
+        use PairedConnectionError::*;
+
+        let command = Request::PublishBatch { stream, events };
+
+        let checked = self.check_event_size(&command);
+        future::result(checked)
+            .and_then(move |()| self.connection.send(command).map_err(RequestMsgError))
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::PublishedBatch { first, last, .. }) => {
+                    Ok((first, last, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Publish events to multiple streams in a single round trip, they are all
+    /// appended or none of them are.
+    ///
+    /// Returns the stream and the number of each event appended, in the order of the writes.
+    pub fn publish_multi(
+        self,
+        writes: Vec<(StreamName, EventName, EventData)>,
+    ) -> impl Future<
+        Item = (Vec<(StreamName, EventNumber)>, PairedConnection),
+        Error = PairedConnectionError,
+    > {
+        use PairedConnectionError::*;
+
+        let command = Request::PublishMulti { writes };
+
+        let checked = self.check_event_size(&command);
+        future::result(checked)
+            .and_then(move |()| self.connection.send(command).map_err(RequestMsgError))
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::PublishedMulti { events }) => {
+                    Ok((events, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Send a ping to the server, the server answers with the same payload.
+    pub fn ping(
+        self,
+        payload: Option<Vec<u8>>,
+    ) -> impl Future<Item = (Option<Vec<u8>>, PairedConnection), Error = PairedConnectionError>
+    {
+        use PairedConnectionError::*;
+
+        let command = Request::Ping { payload };
+
+        self.connection
+            .send(command)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Pong { payload }) => Ok((payload, PairedConnection { connection })),
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Returns the version and the features of the server, the connection
+    /// is switched to the given version of the protocol, 2 or 3, if any.
+    ///
+    /// The servers that do not support this command answer with a server error.
+    pub fn hello(
+        self,
+        proto: Option<u64>,
+    ) -> impl Future<Item = (ServerHello, PairedConnection), Error = PairedConnectionError> {
+        use PairedConnectionError::*;
+
+        self.connection
+            .send(Request::Hello { proto })
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Hello {
+                    version,
+                    proto,
+                    features,
+                }) => {
+                    let hello = ServerHello {
+                        version,
+                        proto,
+                        features,
+                    };
+                    Ok((hello, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Delete all the events of a stream.
+    ///
+    /// The stream is recreated by publishing to it again, the numbering
+    /// of its events continues after the deleted ones.
+    pub fn delete_stream(
+        self,
+        stream: StreamName,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        let hard = false;
+        self.send_ok_command(Request::DeleteStream { stream, hard })
+    }
+
+    /// Delete a stream and all of its events permanently.
+    ///
+    /// Publishing to this stream or creating it again is refused with a `Tombstoned` server error.
+    pub fn tombstone_stream(
+        self,
+        stream: StreamName,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        let hard = true;
+        self.send_ok_command(Request::DeleteStream { stream, hard })
+    }
+
+    /// Remove all the events of a stream that are strictly below the given event number.
+    ///
+    /// Subscriptions starting before this event number will start from it.
+    pub fn trim_stream(
+        self,
+        stream: StreamName,
+        up_to: u64,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::TrimStream { stream, up_to })
+    }
+
+    /// Create a stream, this is only required to publish to a previously deleted stream.
+    pub fn create_stream(
+        self,
+        stream: StreamName,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::CreateStream { stream })
+    }
+
+    /// Set a metadata of a stream, e.g. its owner or its schema,
+    /// the change is published to the `$meta-<stream>` stream.
+    pub fn set_stream_meta(
+        self,
+        stream: StreamName,
+        key: String,
+        value: String,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::SetStreamMeta { stream, key, value })
+    }
+
+    /// Write a snapshot of all the streams to an archive file on the server,
+    /// the events published during the backup are not part of it.
+    pub fn backup(
+        self,
+        path: String,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::Backup { path })
+    }
+
+    /// Make the server replicate the primary at the given address, the server stops
+    /// accepting writes. Passing `None` stops the replication.
+    pub fn replica_of(
+        self,
+        primary: Option<String>,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::ReplicaOf { primary })
+    }
+
+    /// Make the server reject the publications and the administration commands
+    /// modifying the streams, the subscriptions are still served.
+    pub fn read_only(
+        self,
+        enabled: bool,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::ReadOnly { enabled })
+    }
+
+    /// Set the acknowledgement level of the publications of this connection, a publication
+    /// is only acknowledged once it has been replicated according to this level.
+    pub fn set_ack_level(
+        self,
+        level: AckLevel,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::SetAckLevel { level })
+    }
+
+    /// Request the server to compact the streams and to reclaim the space of the
+    /// events removed, returns the size of the database before and after.
+    pub fn compact(
+        self,
+    ) -> impl Future<Item = (u64, u64, PairedConnection), Error = PairedConnectionError> {
+        use PairedConnectionError::*;
+
+        let command = Request::Compact;
+
+        self.connection
+            .send(command)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Compacted { before, after }) => {
+                    Ok((before, after, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Request the state of the replication of the server and its replication lag.
+    pub fn replication_info(
+        self,
+    ) -> impl Future<Item = (ReplicationInfo, PairedConnection), Error = PairedConnectionError>
+    {
+        use PairedConnectionError::*;
+
+        let command = Request::ReplicationInfo;
+
+        self.connection
+            .send(command)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::ReplicationInfo {
+                    primary,
+                    caught_up,
+                    last_global,
+                    lag,
+                }) => {
+                    let info = ReplicationInfo {
+                        primary,
+                        caught_up,
+                        last_global,
+                        lag,
+                    };
+                    Ok((info, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Request the statistics of the server.
+    pub fn info(
+        self,
+    ) -> impl Future<Item = (ServerInfo, PairedConnection), Error = PairedConnectionError> {
+        use PairedConnectionError::*;
+
+        self.connection
+            .send(Request::Info)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Info { fields }) => match ServerInfo::from_fields(&fields) {
+                    Some(info) => Ok((info, PairedConnection { connection })),
+                    None => Err(InvalidServerResponse(Response::Info { fields })),
+                },
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Request the clients connected to the server ordered by id.
+    pub fn client_list(
+        self,
+    ) -> impl Future<Item = (Vec<ClientInfo>, PairedConnection), Error = PairedConnectionError>
+    {
+        use PairedConnectionError::*;
+
+        self.connection
+            .send(Request::ClientList)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::ClientList { clients }) => {
+                    Ok((clients, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Request the commands and the deliveries that exceeded the latency
+    /// thresholds of the slow log, the most recent first.
+    pub fn slow_log(
+        self,
+    ) -> impl Future<Item = (Vec<SlowLogEntry>, PairedConnection), Error = PairedConnectionError>
+    {
+        use PairedConnectionError::*;
+
+        self.connection
+            .send(Request::SlowLog { reset: false })
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::SlowLog { entries }) => Ok((entries, PairedConnection { connection })),
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Request the server to clear its slow log.
+    pub fn slow_log_reset(
+        self,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::SlowLog { reset: true })
+    }
+
+    /// Request the latency percentiles measured by the server since it started.
+    pub fn latency(
+        self,
+    ) -> impl Future<Item = (Latencies, PairedConnection), Error = PairedConnectionError> {
+        use PairedConnectionError::*;
+
+        self.connection
+            .send(Request::Latency)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Latency {
+                    commands,
+                    operations,
+                }) => {
+                    let latencies = Latencies {
+                        commands,
+                        operations,
+                    };
+                    Ok((latencies, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Turn the connection into a stream of every command processed by the server.
+    pub fn monitor(
+        self,
+    ) -> impl Future<
+        Item = impl Stream<Item = MonitoredCommand, Error = PairedConnectionError>,
+        Error = PairedConnectionError,
+    > {
+        use PairedConnectionError::*;
+
+        self.connection
+            .send(Request::Monitor)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Ok) => {
+                    let commands = connection
+                        .map_err(ResponseMsgError)
+                        .and_then(|msg| match msg {
+                            Ok(Response::Monitored {
+                                timestamp,
+                                client,
+                                command,
+                            }) => Ok(MonitoredCommand {
+                                timestamp,
+                                client,
+                                command,
+                            }),
+                            Ok(response) => Err(InvalidServerResponse(response)),
+                            Err(error) => Err(ServerSide(ServerError::from_error(error))),
+                        });
+                    Ok(commands)
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Close the connection of the client with the given id.
+    pub fn client_kill(
+        self,
+        id: u64,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::ClientKill { id })
+    }
+
+    /// Request the server to change a configuration parameter, e.g. `log-level`.
+    pub fn config_set(
+        self,
+        key: String,
+        value: String,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::ConfigSet { key, value })
+    }
+
+    /// Request all the metadata of a stream ordered by key.
+    pub fn stream_meta(
+        self,
+        stream: StreamName,
+    ) -> impl Future<Item = (Vec<(String, String)>, PairedConnection), Error = PairedConnectionError>
+    {
+        use PairedConnectionError::*;
+
+        let command = Request::GetStreamMeta { stream };
+
+        self.connection
+            .send(command)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::StreamMeta { meta, .. }) => {
+                    Ok((meta, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Request the first and last event numbers of the stream, the number and the size of the
+    /// events kept, its creation time, its retention settings and its number of subscribers.
+    pub fn stream_info(
+        self,
+        stream: StreamName,
+    ) -> impl Future<Item = (StreamInfo, PairedConnection), Error = PairedConnectionError> {
+        use PairedConnectionError::*;
+
+        let command = Request::StreamInfo { stream };
+
+        self.connection
+            .send(command)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::StreamInfo {
+                    first,
+                    last,
+                    bytes,
+                    events,
+                    created,
+                    retention_ms,
+                    max_events,
+                    max_bytes,
+                    subscribers,
+                    ..
+                }) => {
+                    let info = StreamInfo {
+                        first,
+                        last,
+                        events,
+                        bytes,
+                        created,
+                        retention_ms,
+                        max_events,
+                        max_bytes,
+                        subscribers,
+                    };
+                    Ok((info, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Request the server to check the checksums of the events kept in the stream, returns the
+    /// number of events checked, the number of events stored without a checksum and the
+    /// numbers of the corrupted events.
+    pub fn verify(
+        self,
+        stream: StreamName,
+    ) -> impl Future<Item = (u64, u64, Vec<EventNumber>, PairedConnection), Error = PairedConnectionError>
+    {
+        use PairedConnectionError::*;
+
+        let command = Request::Verify { stream };
+
+        self.connection
+            .send(command)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Verified {
+                    checked,
+                    unchecked,
+                    corrupted,
+                    ..
+                }) => Ok((
+                    checked,
+                    unchecked,
+                    corrupted,
+                    PairedConnection { connection },
+                )),
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Acknowledge that an event delivered by a consumer group has been processed.
+    pub fn ack(
+        self,
+        group: GroupName,
+        stream: StreamName,
+        event_number: EventNumber,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::Ack {
+            group,
+            stream,
+            event_number,
+        })
+    }
+
+    /// Store on the server the number of the last event of the stream processed by the group,
+    /// subscriptions can then resume after it (e.g. `stream?from=committed&group=workers`).
+    pub fn commit_offset(
+        self,
+        group: GroupName,
+        stream: StreamName,
+        event_number: EventNumber,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        self.send_ok_command(Request::CommitOffset {
+            group,
+            stream,
+            event_number,
+        })
+    }
+
+    /// Request the number of the last event of the stream processed by the group.
+    ///
+    /// Returns `None` if the group has not committed any offset for this stream.
+    pub fn fetch_offset(
+        self,
+        group: GroupName,
+        stream: StreamName,
+    ) -> impl Future<Item = (Option<EventNumber>, PairedConnection), Error = PairedConnectionError>
+    {
+        use PairedConnectionError::*;
+
+        let command = Request::FetchOffset { group, stream };
+
+        self.connection
+            .send(command)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Offset { number, .. }) => {
+                    Ok((number, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Request the events delivered to the members of the group that have not been
+    /// acknowledged yet, with the consumer they were delivered to and their idle time.
+    pub fn pending(
+        self,
+        group: GroupName,
+        stream: StreamName,
+    ) -> impl Future<Item = (Vec<PendingEvent>, PairedConnection), Error = PairedConnectionError>
+    {
+        use PairedConnectionError::*;
+
+        let command = Request::Pending { group, stream };
+
+        self.connection
+            .send(command)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Pending { events, .. }) => {
+                    Ok((events, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Take over a pending event of the group, e.g. when the consumer
+    /// it was delivered to is stuck, the claimed event is returned.
+    pub fn claim(
+        self,
+        group: GroupName,
+        stream: StreamName,
+        event_number: EventNumber,
+        new_consumer: String,
+    ) -> impl Future<Item = (Event, PairedConnection), Error = PairedConnectionError> {
+        use PairedConnectionError::*;
+
+        let command = Request::Claim {
+            group,
+            stream,
+            event_number,
+            new_consumer,
         };
 
+        self.connection
+            .send(command)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Event {
+                    number,
+                    timestamp,
+                    event_name,
+                    event_data,
+                    headers,
+                    ..
+                }) => {
+                    let event = Event {
+                        number,
+                        timestamp,
+                        name: event_name,
+                        data: event_data,
+                        headers,
+                    };
+                    Ok((event, PairedConnection { connection }))
+                }
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    fn send_ok_command(
+        self,
+        command: Request,
+    ) -> impl Future<Item = PairedConnection, Error = PairedConnectionError> {
+        use PairedConnectionError::*;
+
         self.connection
             .send(command)
             .map_err(RequestMsgError)
@@ -85,7 +1040,7 @@ impl PairedConnection {
             .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
                 Ok(Response::Ok) => Ok(PairedConnection { connection }),
                 Ok(response) => Err(InvalidServerResponse(response)),
-                Err(error) => Err(ServerSide(error)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
             })
     }
 
@@ -112,20 +1067,22 @@ impl PairedConnection {
                     Ok((stream, number, PairedConnection { connection }))
                 }
                 Ok(response) => Err(InvalidServerResponse(response)),
-                Err(error) => Err(ServerSide(error)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
             })
     }
 
-    /// Request the list of stream names
+    /// Request the list of stream names, optionally filtered by a glob pattern
+    /// (e.g. `sensor-*`).
     ///
-    /// Returns an empty Vec if the database does not contain any stream.
+    /// Returns an empty Vec if the database does not contain any matching stream.
     pub fn stream_names(
         self,
+        pattern: Option<String>,
     ) -> impl Future<Item = (Vec<StreamName>, PairedConnection), Error = PairedConnectionError>
     {
         use PairedConnectionError::*;
 
-        let command = Request::StreamNames;
+        let command = Request::StreamNames { pattern };
 
         self.connection
             .send(command)
@@ -136,7 +1093,32 @@ impl PairedConnection {
                     Ok((streams, PairedConnection { connection }))
                 }
                 Ok(response) => Err(InvalidServerResponse(response)),
-                Err(error) => Err(ServerSide(error)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
+            })
+    }
+
+    /// Request the events of a stream that are in the given range (exclusive end).
+    ///
+    /// Unspecified bounds are open, the range starts from the first event and
+    /// ends with the last event of the stream.
+    pub fn get_range(
+        self,
+        stream: StreamName,
+        from: Option<EventNumber>,
+        to: Option<EventNumber>,
+    ) -> impl Future<Item = (Vec<Event>, PairedConnection), Error = PairedConnectionError> {
+        use PairedConnectionError::*;
+
+        let command = Request::GetRange { stream, from, to };
+
+        self.connection
+            .send(command)
+            .map_err(RequestMsgError)
+            .and_then(|framed| framed.into_future().map_err(|(e, _)| ResponseMsgError(e)))
+            .and_then(|(first, connection)| match first.ok_or(ConnectionClosed)? {
+                Ok(Response::Range { events, .. }) => Ok((events, PairedConnection { connection })),
+                Ok(response) => Err(InvalidServerResponse(response)),
+                Err(error) => Err(ServerSide(ServerError::from_error(error))),
             })
     }
 }
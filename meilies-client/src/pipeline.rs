@@ -0,0 +1,249 @@
+use std::collections::VecDeque;
+use std::io;
+
+use futures::sync::{mpsc, oneshot};
+use futures::{Async, AsyncSink, Future, Poll, Sink, Stream};
+use log::{error, warn};
+use meilies::reqresp::{Request, Response};
+use meilies::stream::{EventNumber, StreamName};
+use tokio_retry::RetryIf;
+
+use super::{is_retryable, ConnectBuilder, Error, ServerError, SteelConnection};
+
+/// When a publication sent with `Client::publish_with` returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    /// Once the server answered, i.e. the event has been written according to the flush
+    /// policy of the server and replicated according to the acknowledgement level.
+    Durable,
+    /// Once the publication has been written to the socket, the answer is not waited for.
+    Written,
+    /// Right away, the publication is written in the background.
+    FireAndForget,
+}
+
+/// A publication that has not been confirmed and failed, sent to the
+/// channel given to `ConnectBuilder::publish_errors`.
+#[derive(Debug)]
+pub struct PublishFailure {
+    pub stream: StreamName,
+    pub error: Error,
+}
+
+/// A publication waiting to be written by the pipeline.
+pub(crate) struct Queued {
+    pub request: Request,
+    /// Receives `()` once the publication has been written to the socket.
+    pub written: Option<oneshot::Sender<()>>,
+    pub pending: Pending,
+}
+
+/// A publication waiting for its answer.
+pub(crate) struct Pending {
+    pub stream: StreamName,
+    /// Receives the answer of the server, the failures of the publications
+    /// that are not waited for are sent to the notifier instead.
+    pub answer: Option<oneshot::Sender<Result<EventNumber, Error>>>,
+}
+
+/// Writes the publications of a client one after the other without waiting for the answers
+/// of the previous ones, the answers are matched with the publications in order.
+pub(crate) struct Pipeline {
+    connection: SteelConnection,
+    requests: mpsc::UnboundedReceiver<Queued>,
+    requests_closed: bool,
+    /// The publication that could not be written because the socket was full.
+    sending: Option<Queued>,
+    /// The publications written and waiting for their answer, in order.
+    in_flight: VecDeque<Pending>,
+    /// The publications written since the last flush, confirmed once it completes.
+    unflushed: Vec<oneshot::Sender<()>>,
+    notifier: Option<mpsc::UnboundedSender<PublishFailure>>,
+}
+
+impl Pipeline {
+    /// Opens a connection with the server and spawns its pipeline,
+    /// the publications are sent to the returned channel.
+    pub fn spawn(
+        builder: ConnectBuilder,
+    ) -> impl Future<Item = mpsc::UnboundedSender<Queued>, Error = tokio_retry::Error<io::Error>>
+    {
+        let notifier = builder.publish_errors.clone();
+
+        RetryIf::spawn(
+            builder.retry.delays(),
+            move || {
+                warn!("Connecting to {}", builder.addr);
+                let builder = builder.clone();
+                builder.connect_any(true).map(move |(addr, connection)| {
+                    SteelConnection::new(builder, true, addr, connection)
+                })
+            },
+            is_retryable,
+        )
+        .map(move |connection| {
+            let (sender, requests) = mpsc::unbounded();
+            let pipeline = Pipeline {
+                connection,
+                requests,
+                requests_closed: false,
+                sending: None,
+                in_flight: VecDeque::new(),
+                unflushed: Vec::new(),
+                notifier,
+            };
+
+            tokio::spawn(pipeline);
+            sender
+        })
+    }
+
+    fn answer(&self, pending: Pending, result: Result<EventNumber, Error>) {
+        match (pending.answer, result) {
+            (Some(answer), result) => {
+                // the client may not wait for the answer anymore
+                let _ = answer.send(result);
+            }
+            (None, Ok(_)) => (),
+            (None, Err(error)) => {
+                error!("Publication to {} failed; {}", pending.stream, error);
+                if let Some(notifier) = &self.notifier {
+                    let failure = PublishFailure {
+                        stream: pending.stream,
+                        error,
+                    };
+                    let _ = notifier.unbounded_send(failure);
+                }
+            }
+        }
+    }
+
+    /// Fails the publications written to a connection that has been lost, their answers
+    /// will never be received and they may or may not have been appended.
+    fn fail_in_flight(&mut self) {
+        // the publications not flushed are never confirmed as written
+        self.unflushed.clear();
+        while let Some(pending) = self.in_flight.pop_front() {
+            self.answer(pending, Err(Error::ConnectionClosed));
+        }
+    }
+
+    fn check_reconnected(&mut self) {
+        if self.connection.has_been_reconnected() {
+            warn!(
+                "Connection lost with {} publications in flight",
+                self.in_flight.len()
+            );
+            self.fail_in_flight();
+        }
+    }
+
+    /// Writes the queued publications until the socket is full.
+    fn poll_write(&mut self) {
+        loop {
+            let queued = match self.sending.take() {
+                Some(queued) => queued,
+                None => match self.requests.poll() {
+                    Ok(Async::Ready(Some(queued))) => queued,
+                    Ok(Async::Ready(None)) | Err(()) => {
+                        self.requests_closed = true;
+                        return;
+                    }
+                    Ok(Async::NotReady) => return,
+                },
+            };
+
+            let Queued {
+                request,
+                written,
+                pending,
+            } = queued;
+
+            let result = self.connection.start_send(request);
+            self.check_reconnected();
+
+            match result {
+                Ok(AsyncSink::Ready) => {
+                    self.in_flight.push_back(pending);
+                    self.unflushed.extend(written);
+                }
+                Ok(AsyncSink::NotReady(request)) => {
+                    self.sending = Some(Queued {
+                        request,
+                        written,
+                        pending,
+                    });
+                    return;
+                }
+                Err(error) => {
+                    let error = Error::Protocol(error.to_string());
+                    self.answer(pending, Err(error));
+                }
+            }
+        }
+    }
+
+    fn poll_flush(&mut self) {
+        let result = self.connection.poll_complete();
+        self.check_reconnected();
+
+        match result {
+            Ok(Async::Ready(())) => {
+                for written in self.unflushed.drain(..) {
+                    let _ = written.send(());
+                }
+            }
+            Ok(Async::NotReady) => (),
+            Err(error) => {
+                error!("Error writing the publications; {}", error);
+                self.fail_in_flight();
+            }
+        }
+    }
+
+    fn poll_read(&mut self) {
+        loop {
+            let result = self.connection.poll();
+            self.check_reconnected();
+
+            let response = match result {
+                Ok(Async::Ready(Some(response))) => response,
+                Ok(Async::Ready(None)) | Ok(Async::NotReady) => return,
+                Err(error) => {
+                    error!("Error reading the answers of the publications; {}", error);
+                    self.fail_in_flight();
+                    return;
+                }
+            };
+
+            let result = match response {
+                Ok(Response::Published { number, .. }) => Ok(number),
+                Ok(response) => Err(Error::InvalidServerResponse(response)),
+                Err(error) => Err(Error::Server(ServerError::from_error(error))),
+            };
+
+            match self.in_flight.pop_front() {
+                Some(pending) => self.answer(pending, result),
+                None => warn!("Answer received for no publication; {:?}", result),
+            }
+        }
+    }
+}
+
+impl Future for Pipeline {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        self.poll_write();
+        self.poll_flush();
+        self.poll_read();
+
+        // the pipeline stops once the client is dropped and every publication answered
+        if self.requests_closed && self.sending.is_none() && self.in_flight.is_empty() {
+            Ok(Async::Ready(()))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::net::SocketAddr;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use futures03::compat::{Future01CompatExt, Stream01CompatExt};
+use futures03::future::{self, Either};
+use futures03::lock::{Mutex, MutexGuard};
+use futures03::{FutureExt, StreamExt, TryFutureExt};
+use log::{debug, warn};
+use tokio::timer::{Delay, Interval};
+
+use super::{Client, ConnectBuilder, Error};
+
+/// The time a pooled connection has to answer a health check ping.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A fixed number of clients shared by the tasks and the threads of a service, e.g. to publish
+/// concurrently. The clients are checked out in turn, a task waits if its turn is taken.
+///
+/// The pool is cheap to clone, the clones share the same clients.
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    builder: ConnectBuilder,
+    /// The clients, `None` once found unhealthy until reconnected at the next checkout.
+    slots: Vec<Mutex<Option<Client>>>,
+    next: AtomicUsize,
+}
+
+impl Pool {
+    /// Open a pool of `size` clients with the server at this address, see `ConnectBuilder::pool`
+    /// to authenticate, use TLS or fail over to other servers.
+    pub async fn connect(addr: SocketAddr, size: usize) -> Result<Pool, Error> {
+        Pool::connect_with(ConnectBuilder::new(addr), size).await
+    }
+
+    pub(crate) async fn connect_with(builder: ConnectBuilder, size: usize) -> Result<Pool, Error> {
+        assert!(size > 0, "a pool must have at least one client");
+
+        let mut slots = Vec::with_capacity(size);
+        for _ in 0..size {
+            let client = builder.clone().client().await?;
+            slots.push(Mutex::new(Some(client)));
+        }
+
+        let inner = Inner {
+            builder,
+            slots,
+            next: AtomicUsize::new(0),
+        };
+        Ok(Pool {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// The number of clients of the pool.
+    pub fn size(&self) -> usize {
+        self.inner.slots.len()
+    }
+
+    /// Checks out the next client in turn, or the first free one after it, the client
+    /// returns to the pool when dropped. It is reconnected if it was found unhealthy.
+    pub async fn get(&self) -> Result<PooledClient<'_>, Error> {
+        let slots = &self.inner.slots;
+        let turn = self.inner.next.fetch_add(1, Ordering::Relaxed) % slots.len();
+
+        let free = (0..slots.len())
+            .map(|i| &slots[(turn + i) % slots.len()])
+            .find_map(Mutex::try_lock);
+        let mut guard = match free {
+            Some(guard) => guard,
+            None => slots[turn].lock().await,
+        };
+
+        if guard.is_none() {
+            let client = self.inner.builder.clone().client().await?;
+            *guard = Some(client);
+        }
+
+        Ok(PooledClient { guard })
+    }
+
+    /// Pings the clients that are not checked out, the ones that do not answer
+    /// are dropped and reconnected at their next checkout.
+    ///
+    /// Returns the number of clients that answered.
+    pub async fn check_health(&self) -> usize {
+        let mut healthy = 0;
+
+        for slot in &self.inner.slots {
+            let mut guard = match slot.try_lock() {
+                Some(guard) => guard,
+                None => continue,
+            };
+
+            let client = match guard.as_mut() {
+                Some(client) => client,
+                None => continue,
+            };
+
+            let delay = Delay::new(Instant::now() + HEALTH_CHECK_TIMEOUT).compat();
+            let answered = match future::select(Box::pin(client.ping()), delay).await {
+                Either::Left((result, _)) => Some(result),
+                Either::Right(_) => None,
+            };
+
+            match answered {
+                Some(Ok(())) => healthy += 1,
+                Some(Err(error)) => {
+                    warn!("Dropping an unhealthy pooled connection; {}", error);
+                    *guard = None;
+                }
+                None => {
+                    warn!("Dropping a pooled connection that did not answer the ping");
+                    *guard = None;
+                }
+            }
+        }
+
+        healthy
+    }
+
+    /// Spawns a task on the tokio runtime that checks the health of the
+    /// clients at this interval, it stops once the pool is dropped.
+    pub fn spawn_health_checks(&self, interval: Duration) {
+        let inner = Arc::downgrade(&self.inner);
+        let mut ticks = Interval::new(Instant::now() + interval, interval).compat();
+
+        let checks = async move {
+            while let Some(Ok(_)) = ticks.next().await {
+                let pool = match Weak::upgrade(&inner) {
+                    Some(inner) => Pool { inner },
+                    None => break,
+                };
+
+                let healthy = pool.check_health().await;
+                debug!(
+                    "{} of the {} pooled connections are healthy",
+                    healthy,
+                    pool.size()
+                );
+            }
+        };
+
+        tokio::spawn(Box::pin(checks.unit_error()).compat());
+    }
+}
+
+/// A client checked out from a `Pool`, it returns to the pool when dropped.
+pub struct PooledClient<'a> {
+    guard: MutexGuard<'a, Option<Client>>,
+}
+
+impl Deref for PooledClient<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.guard
+            .as_ref()
+            .expect("the checked out clients are connected")
+    }
+}
+
+impl DerefMut for PooledClient<'_> {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.guard
+            .as_mut()
+            .expect("the checked out clients are connected")
+    }
+}
@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use tokio_retry::strategy::jitter;
+
+/// The number of times the connections are retried by default.
+const CONNECT_ATTEMPTS: usize = 50;
+
+/// The number of times the idempotent commands are retried by default.
+const COMMAND_ATTEMPTS: usize = 3;
+
+/// How many times and after which delays an operation that failed is retried.
+///
+/// The delays grow exponentially from the initial delay up to the maximum delay,
+/// each delay is randomly shortened by the jitter for the clients not to retry all at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    initial_delay: Duration,
+    max_delay: Duration,
+    factor: u32,
+    jitter: bool,
+}
+
+impl RetryPolicy {
+    /// The policy of the connections: 50 retries from 100 ms, doubling up to 10 s, with jitter.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: CONNECT_ATTEMPTS,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            factor: 2,
+            jitter: true,
+        }
+    }
+
+    /// The policy of the idempotent commands: the one of the connections but only 3 retries.
+    pub fn commands() -> RetryPolicy {
+        RetryPolicy::new().max_attempts(COMMAND_ATTEMPTS)
+    }
+
+    /// A policy that never retries.
+    pub fn never() -> RetryPolicy {
+        RetryPolicy::new().max_attempts(0)
+    }
+
+    /// Retry at most this number of times, the first attempt is not counted.
+    pub fn max_attempts(mut self, attempts: usize) -> RetryPolicy {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Wait this long before the first retry.
+    pub fn initial_delay(mut self, delay: Duration) -> RetryPolicy {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Never wait longer than this between two retries.
+    pub fn max_delay(mut self, delay: Duration) -> RetryPolicy {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Multiply the delay by this factor after each retry, 1 waits the same delay each time.
+    pub fn factor(mut self, factor: u32) -> RetryPolicy {
+        self.factor = factor.max(1);
+        self
+    }
+
+    /// Randomly shorten the delays, it is enabled by default.
+    pub fn jitter(mut self, jitter: bool) -> RetryPolicy {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Returns the delays to wait before each retry.
+    pub(crate) fn delays(&self) -> Delays {
+        Delays {
+            policy: *self,
+            next: self.initial_delay.min(self.max_delay),
+            attempts: 0,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy::new()
+    }
+}
+
+/// The delays of a retry policy, one for each retry.
+#[derive(Debug, Clone)]
+pub(crate) struct Delays {
+    policy: RetryPolicy,
+    next: Duration,
+    attempts: usize,
+}
+
+impl Iterator for Delays {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        if self.attempts >= self.policy.max_attempts {
+            return None;
+        }
+
+        let delay = self.next;
+        self.attempts += 1;
+        self.next = delay
+            .checked_mul(self.policy.factor)
+            .map_or(self.policy.max_delay, |next| {
+                next.min(self.policy.max_delay)
+            });
+
+        if self.policy.jitter {
+            Some(jitter(delay))
+        } else {
+            Some(delay)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(delays: Delays) -> Vec<u64> {
+        delays.map(|d| d.as_millis() as u64).collect()
+    }
+
+    #[test]
+    fn exponential_delays() {
+        let policy = RetryPolicy::new()
+            .max_attempts(6)
+            .initial_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(1000))
+            .jitter(false);
+        assert_eq!(
+            millis(policy.delays()),
+            vec![100, 200, 400, 800, 1000, 1000]
+        );
+
+        let policy = policy.factor(1);
+        assert_eq!(millis(policy.delays()), vec![100; 6]);
+
+        // the initial delay is capped too
+        let policy = policy.initial_delay(Duration::from_secs(5));
+        assert_eq!(millis(policy.delays()), vec![1000; 6]);
+    }
+
+    #[test]
+    fn attempts() {
+        assert_eq!(RetryPolicy::new().delays().count(), CONNECT_ATTEMPTS);
+        assert_eq!(RetryPolicy::commands().delays().count(), COMMAND_ATTEMPTS);
+        assert_eq!(RetryPolicy::never().delays().count(), 0);
+    }
+
+    #[test]
+    fn jitter_shortens_delays() {
+        let policy = RetryPolicy::new()
+            .max_attempts(20)
+            .initial_delay(Duration::from_millis(100))
+            .factor(1);
+        assert!(policy.delays().all(|d| d <= Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn factor_overflow() {
+        let policy = RetryPolicy::new()
+            .max_attempts(3)
+            .initial_delay(Duration::from_secs(u64::MAX / 2))
+            .max_delay(Duration::from_secs(u64::MAX))
+            .factor(4)
+            .jitter(false);
+        let delays: Vec<_> = policy.delays().collect();
+        assert_eq!(delays[1], Duration::from_secs(u64::MAX));
+    }
+}
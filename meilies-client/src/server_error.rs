@@ -0,0 +1,133 @@
+use std::fmt;
+use std::time::Duration;
+
+use meilies::reqresp::ErrorCode;
+
+/// An error sent by the server, identified by the code that prefixes it,
+/// each variant contains the message that follows the code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerError {
+    /// A generic error, e.g. an invalid argument or a storage failure.
+    Err(String),
+    /// The connection must be authenticated to send this command.
+    NoAuth(String),
+    /// The username or the password given to authenticate is invalid.
+    WrongPass(String),
+    /// The authenticated user is not allowed to send this command.
+    NoPerm(String),
+    /// The version of the protocol asked is not supported by the server.
+    NoProto(String),
+    /// The stream was not at the expected version when publishing.
+    WrongVer(String),
+    /// The stream has been deleted permanently, it can not be written to anymore.
+    Tombstoned(String),
+    /// The stream read, trimmed or deleted does not exist.
+    NoStream(String),
+    /// The server is in read-only mode or is a replica, it does not accept writes.
+    ReadOnly(String),
+    /// The events have been published but not acknowledged by
+    /// the replicas required by the acknowledgement level in time.
+    NotReplicated(String),
+    /// The server is a node of a cluster that is not the leader, the
+    /// message is the address of the leader the writes must be sent to.
+    Moved(String),
+    /// The server is a node of a cluster that has no leader yet.
+    ClusterDown(String),
+    /// The publication exceeds the rate limit of the connection or of the stream,
+    /// it can be retried after the delay returned by `retry_after`.
+    Throttled(String),
+    /// The free space of the data directory of the server is too low, it does
+    /// not accept writes until enough space is freed.
+    DiskFull(String),
+    /// An error without a code known by the client, it contains the whole error.
+    Unknown(String),
+}
+
+impl ServerError {
+    /// Reads the code of an error sent by the server.
+    pub fn from_error(error: String) -> ServerError {
+        let (code, message) = match ErrorCode::split(&error) {
+            Some((code, message)) => (code, message.to_owned()),
+            None => return ServerError::Unknown(error),
+        };
+
+        match code {
+            ErrorCode::Err => ServerError::Err(message),
+            ErrorCode::NoAuth => ServerError::NoAuth(message),
+            ErrorCode::WrongPass => ServerError::WrongPass(message),
+            ErrorCode::NoPerm => ServerError::NoPerm(message),
+            ErrorCode::NoProto => ServerError::NoProto(message),
+            ErrorCode::WrongVer => ServerError::WrongVer(message),
+            ErrorCode::Tombstoned => ServerError::Tombstoned(message),
+            ErrorCode::NoStream => ServerError::NoStream(message),
+            ErrorCode::ReadOnly => ServerError::ReadOnly(message),
+            ErrorCode::NotReplicated => ServerError::NotReplicated(message),
+            ErrorCode::Moved => ServerError::Moved(message),
+            ErrorCode::ClusterDown => ServerError::ClusterDown(message),
+            ErrorCode::Throttled => ServerError::Throttled(message),
+            ErrorCode::DiskFull => ServerError::DiskFull(message),
+        }
+    }
+
+    /// Returns the code of the error, `None` if it is unknown.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            ServerError::Err(_) => Some(ErrorCode::Err),
+            ServerError::NoAuth(_) => Some(ErrorCode::NoAuth),
+            ServerError::WrongPass(_) => Some(ErrorCode::WrongPass),
+            ServerError::NoPerm(_) => Some(ErrorCode::NoPerm),
+            ServerError::NoProto(_) => Some(ErrorCode::NoProto),
+            ServerError::WrongVer(_) => Some(ErrorCode::WrongVer),
+            ServerError::Tombstoned(_) => Some(ErrorCode::Tombstoned),
+            ServerError::NoStream(_) => Some(ErrorCode::NoStream),
+            ServerError::ReadOnly(_) => Some(ErrorCode::ReadOnly),
+            ServerError::NotReplicated(_) => Some(ErrorCode::NotReplicated),
+            ServerError::Moved(_) => Some(ErrorCode::Moved),
+            ServerError::ClusterDown(_) => Some(ErrorCode::ClusterDown),
+            ServerError::Throttled(_) => Some(ErrorCode::Throttled),
+            ServerError::DiskFull(_) => Some(ErrorCode::DiskFull),
+            ServerError::Unknown(_) => None,
+        }
+    }
+
+    /// Returns the message of the error, without its code.
+    pub fn message(&self) -> &str {
+        match self {
+            ServerError::Err(message)
+            | ServerError::NoAuth(message)
+            | ServerError::WrongPass(message)
+            | ServerError::NoPerm(message)
+            | ServerError::NoProto(message)
+            | ServerError::WrongVer(message)
+            | ServerError::Tombstoned(message)
+            | ServerError::NoStream(message)
+            | ServerError::ReadOnly(message)
+            | ServerError::NotReplicated(message)
+            | ServerError::Moved(message)
+            | ServerError::ClusterDown(message)
+            | ServerError::Throttled(message)
+            | ServerError::DiskFull(message)
+            | ServerError::Unknown(message) => message,
+        }
+    }
+
+    /// Returns the delay after which a throttled publication can be retried.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ServerError::Throttled(message) => {
+                let millis = message.split_whitespace().next()?.parse().ok()?;
+                Some(Duration::from_millis(millis))
+            }
+            _otherwise => None,
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.code() {
+            Some(code) => write!(f, "{} {}", code, self.message()),
+            None => f.write_str(self.message()),
+        }
+    }
+}
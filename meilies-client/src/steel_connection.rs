@@ -5,52 +5,109 @@ use futures::{Async, AsyncSink, Future, Sink, Stream};
 use log::{error, info, warn};
 use meilies::reqresp::{Request, RequestMsgError, Response, ResponseMsgError};
 use tokio_retry::Error as TrError;
-use tokio_retry::{strategy::FibonacciBackoff, Retry};
-
-use super::{connect, ClientConnection};
+use tokio_retry::RetryIf;
+
+use super::compat::ClientConnection;
+use super::ConnectBuilder;
+
+/// The state of a connection sent to the application each time it changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The connection has been established with the server at this address.
+    Connected(SocketAddr),
+    /// The connection with the server at this address has been lost, it is reconnecting.
+    Disconnected(SocketAddr),
+}
 
-/// A connection that try to reconnect when disconnected.
+/// A connection that try to reconnect when disconnected,
+/// to the fallback servers if the current one is unreachable.
 ///
 /// It will keep the stream states (e.g. the stream position).
 pub struct SteelConnection {
     addr: SocketAddr,
+    builder: ConnectBuilder,
+    /// Whether the connection must be established with a server that is not a replica.
+    primary: bool,
     reconnected: bool,
     conn_state: ConnState,
 }
 
+type Connecting = Box<Future<Item = (SocketAddr, ClientConnection), Error = io::Error> + Send>;
+
 enum ConnState {
     Connected(ClientConnection),
-    Connecting(Box<Future<Item = ClientConnection, Error = io::Error> + Send>),
+    Connecting(Connecting),
 }
 
 impl SteelConnection {
-    /// Create a new steel connection.
-    pub fn new(addr: SocketAddr, connection: ClientConnection) -> SteelConnection {
+    /// Create a new steel connection established with the server at the given address.
+    ///
+    /// The builder is used to open and authenticate the new connections when reconnecting.
+    pub fn new(
+        builder: ConnectBuilder,
+        primary: bool,
+        addr: SocketAddr,
+        connection: ClientConnection,
+    ) -> SteelConnection {
+        builder.notify_state(ConnectionState::Connected(addr));
         SteelConnection {
             addr,
+            builder,
+            primary,
             reconnected: false,
             conn_state: ConnState::Connected(connection),
         }
     }
 
+    /// Drop the current connection and start reconnecting.
+    pub fn reconnect(&mut self) {
+        if let ConnState::Connected(_) = self.conn_state {
+            self.builder
+                .notify_state(ConnectionState::Disconnected(self.addr));
+        }
+        let connecting = retry_future(self.builder.clone(), self.primary);
+        self.conn_state = ConnState::Connecting(connecting);
+    }
+
+    fn connected(&mut self, addr: SocketAddr, connection: ClientConnection) {
+        info!("Successfully reconnected to {}", addr);
+        self.builder.notify_state(ConnectionState::Connected(addr));
+        self.addr = addr;
+        self.reconnected = true;
+        self.conn_state = ConnState::Connected(connection);
+    }
+
+    /// Returns the maximum size of the data of the events published, if limited.
+    pub fn max_event_size(&self) -> Option<usize> {
+        self.builder.max_event_size
+    }
+
     /// Returns `true` if the connection has been reconnected since the last time called.
     pub fn has_been_reconnected(&mut self) -> bool {
         mem::replace(&mut self.reconnected, false)
     }
 }
 
-/// The retry strategy used to reconnect.
-pub fn retry_strategy() -> std::iter::Take<FibonacciBackoff> {
-    FibonacciBackoff::from_millis(100).take(50)
+/// Returns `false` for the errors that will not be fixed by retrying,
+/// like a refused password or an invalid server certificate.
+pub fn is_retryable(error: &io::Error) -> bool {
+    match error.kind() {
+        io::ErrorKind::PermissionDenied
+        | io::ErrorKind::InvalidData
+        | io::ErrorKind::InvalidInput => false,
+        _otherwise => true,
+    }
 }
 
-fn retry_future(
-    addr: SocketAddr,
-) -> Box<Future<Item = ClientConnection, Error = io::Error> + Send> {
-    let retry = Retry::spawn(retry_strategy(), move || {
-        warn!("Reconnecting to {}", addr);
-        connect(&addr)
-    })
+fn retry_future(builder: ConnectBuilder, primary: bool) -> Connecting {
+    let retry = RetryIf::spawn(
+        builder.retry.delays(),
+        move || {
+            warn!("Reconnecting to {}", builder.addr);
+            builder.connect_any(primary)
+        },
+        is_retryable,
+    )
     .map_err(|error| match error {
         TrError::OperationError(e) => e,
         TrError::TimerError(e) => io::Error::new(io::ErrorKind::Other, e),
@@ -68,7 +125,7 @@ impl Stream for SteelConnection {
             ConnState::Connected(connection) => match connection.poll() {
                 Ok(Async::Ready(None)) => {
                     error!("Connection closed with {}", self.addr);
-                    self.conn_state = ConnState::Connecting(retry_future(self.addr));
+                    self.reconnect();
                     self.poll()
                 }
                 Err(error) => {
@@ -76,9 +133,9 @@ impl Stream for SteelConnection {
                     use ResponseMsgError::RespMsgError;
 
                     match error {
-                        RespMsgError(IoError(e)) => {
+                        RespMsgError(IoError(ref e)) if is_retryable(e) => {
                             error!("Connection error with {}; {}", self.addr, e);
-                            self.conn_state = ConnState::Connecting(retry_future(self.addr));
+                            self.reconnect();
                             self.poll()
                         }
                         otherwise => Err(otherwise),
@@ -87,10 +144,8 @@ impl Stream for SteelConnection {
                 otherwise => otherwise,
             },
             ConnState::Connecting(connect) => match connect.poll() {
-                Ok(Async::Ready(connection)) => {
-                    info!("Successfully reconnected to {}", self.addr);
-                    self.reconnected = true;
-                    self.conn_state = ConnState::Connected(connection);
+                Ok(Async::Ready((addr, connection))) => {
+                    self.connected(addr, connection);
                     self.poll()
                 }
                 Ok(Async::NotReady) => Ok(Async::NotReady),
@@ -115,10 +170,8 @@ impl Sink for SteelConnection {
                 connection.start_send(item)
             }
             ConnState::Connecting(connect) => match connect.poll() {
-                Ok(Async::Ready(connection)) => {
-                    info!("Successfully reconnected to {}", self.addr);
-                    self.reconnected = true;
-                    self.conn_state = ConnState::Connected(connection);
+                Ok(Async::Ready((addr, connection))) => {
+                    self.connected(addr, connection);
                     self.start_send(item)
                 }
                 Ok(Async::NotReady) => Ok(AsyncSink::NotReady(item)),
@@ -135,9 +188,9 @@ impl Sink for SteelConnection {
                     use RequestMsgError::RespMsgError;
 
                     match error {
-                        RespMsgError(IoError(e)) => {
+                        RespMsgError(IoError(ref e)) if is_retryable(e) => {
                             error!("Connection error with {}; {}", self.addr, e);
-                            self.conn_state = ConnState::Connecting(retry_future(self.addr));
+                            self.reconnect();
                             self.poll_complete()
                         }
                         otherwise => Err(otherwise),
@@ -146,10 +199,8 @@ impl Sink for SteelConnection {
                 otherwise => otherwise,
             },
             ConnState::Connecting(connect) => match connect.poll() {
-                Ok(Async::Ready(connection)) => {
-                    info!("Successfully reconnected to {}", self.addr);
-                    self.reconnected = true;
-                    self.conn_state = ConnState::Connected(connection);
+                Ok(Async::Ready((addr, connection))) => {
+                    self.connected(addr, connection);
                     self.poll_complete()
                 }
                 Ok(Async::NotReady) => Ok(Async::NotReady),
@@ -1,5 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 use std::{fmt, io};
 
 use futures::stream::SplitStream;
@@ -7,41 +8,142 @@ use futures::{Async, AsyncSink, Future, Poll, Sink, Stream};
 use log::{error, warn};
 use meilies::reqresp::{Request, RequestMsgError, Response, ResponseMsgError};
 use meilies::resp::RespMsgError;
-use meilies::stream::{Stream as EsStream, StreamName};
+use meilies::stream::{Batch, EventName, EventNumber, GroupName, ReadRange, Stream as EsStream};
+use meilies::stream::{SlowConsumer, StreamName, Timestamp};
 use tokio::sync::mpsc;
-use tokio_retry::Retry;
+use tokio::timer::Delay;
+use tokio_retry::RetryIf;
 
-use super::{connect, retry_strategy, SteelConnection};
+use super::{is_retryable, ConnectBuilder, SteelConnection};
 
 #[derive(Debug, Default)]
 struct StreamContext {
     reconnected: bool,
     position_start: Option<u64>,
     position_end: Option<u64>,
+    /// The subscription starts from this time if no event has been received yet.
+    position_time: Option<Timestamp>,
+    /// The number of the last event returned, the global number for the virtual streams,
+    /// the events received again after a reconnection are skipped.
+    delivered: Option<u64>,
+    event_names: Option<Vec<EventName>>,
+    committed: Option<GroupName>,
+    slow_consumer: SlowConsumer,
+    credits: Option<u64>,
+    batch: Option<Batch>,
+}
+
+/// A consumer group a connection has joined to receive the events of a stream.
+#[derive(Debug)]
+struct GroupContext {
+    group: GroupName,
+    consumer: String,
+    reconnected: bool,
+}
+
+/// Sends pings when the connection is idle to detect dead connections.
+struct Keepalive {
+    interval: Duration,
+    delay: Delay,
+    ping_sent: bool,
+}
+
+impl Keepalive {
+    fn new(interval: Duration) -> Keepalive {
+        Keepalive {
+            interval,
+            delay: Delay::new(Instant::now() + interval),
+            ping_sent: false,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.delay.reset(Instant::now() + self.interval);
+        self.ping_sent = false;
+    }
 }
 
 /// A tokio Stream that reconnect when the connection is lost.
 ///
+/// The subscriptions are sent again from the last event returned of each stream,
+/// the events received again are skipped, except the ones of the consumer groups.
+///
 /// It preferable to use `sub_connect` to get a `SubController` and `SubStream` tuple.
 pub struct EventStream {
     state: HashMap<StreamName, StreamContext>,
+    groups: HashMap<StreamName, GroupContext>,
     connection: SteelConnection,
+    keepalive: Option<Keepalive>,
+    /// The events of the last batch received that have not been returned yet.
+    batched: VecDeque<Response>,
 }
 
 impl EventStream {
     fn connect(
-        addr: SocketAddr,
+        builder: ConnectBuilder,
     ) -> impl Future<Item = EventStream, Error = tokio_retry::Error<io::Error>> {
-        Retry::spawn(retry_strategy(), move || {
-            warn!("Connecting to {}", addr);
-            connect(&addr).map(move |connection| {
-                let connection = SteelConnection::new(addr, connection);
-                EventStream {
-                    state: HashMap::new(),
-                    connection,
+        let keepalive = builder.keepalive;
+
+        RetryIf::spawn(
+            builder.retry.delays(),
+            move || {
+                warn!("Connecting to {}", builder.addr);
+                let builder = builder.clone();
+                builder.connect_any(false).map(move |(addr, connection)| {
+                    let connection = SteelConnection::new(builder, false, addr, connection);
+                    EventStream {
+                        state: HashMap::new(),
+                        groups: HashMap::new(),
+                        connection,
+                        keepalive: keepalive.map(Keepalive::new),
+                        batched: VecDeque::new(),
+                    }
+                })
+            },
+            is_retryable,
+        )
+    }
+
+    /// Sends a ping if the connection has been idle for too long and reconnects
+    /// if the previous ping has not been answered.
+    fn poll_keepalive(&mut self) -> Result<(), ProtocolError> {
+        let keepalive = match &mut self.keepalive {
+            Some(keepalive) => keepalive,
+            None => return Ok(()),
+        };
+
+        loop {
+            match keepalive.delay.poll() {
+                Ok(Async::Ready(())) => (),
+                Ok(Async::NotReady) => return Ok(()),
+                Err(e) => {
+                    error!("keepalive timer error; {}", e);
+                    return Ok(());
                 }
-            })
-        })
+            }
+
+            if keepalive.ping_sent {
+                warn!("No answer to the keepalive ping, reconnecting");
+                keepalive.reset();
+                self.connection.reconnect();
+                return Ok(());
+            }
+
+            let ping = Request::Ping { payload: None };
+            keepalive.delay.reset(Instant::now() + keepalive.interval);
+            keepalive.ping_sent = true;
+
+            let sent = self
+                .connection
+                .start_send(ping)
+                .map_err(ProtocolError::RequestMsgError)?;
+            if let AsyncSink::NotReady(_) = sent {
+                warn!("Could not send the keepalive ping");
+            }
+            self.connection
+                .poll_complete()
+                .map_err(ProtocolError::RequestMsgError)?;
+        }
     }
 
     fn send_stream_subscriptions(&mut self) -> Result<(), ProtocolError> {
@@ -52,16 +154,44 @@ impl EventStream {
 
         for (name, context) in &mut self.state {
             context.reconnected = true;
-            let stream = EsStream::new_from_to(
-                name.clone(),
-                context.position_start.into(),
-                context.position_end.into(),
-            );
+            let stream = match (context.position_start, context.position_time) {
+                (None, Some(timestamp)) => {
+                    EsStream::new(name.clone(), ReadRange::ReadFromTime(timestamp))
+                }
+                (_, _) => EsStream::new_from_to(
+                    name.clone(),
+                    context.position_start.into(),
+                    context.position_end.into(),
+                ),
+            };
+            let stream = EsStream {
+                event_names: context.event_names.clone(),
+                committed: context.committed.clone(),
+                slow_consumer: context.slow_consumer,
+                credits: context.credits,
+                batch: context.batch,
+                ..stream
+            };
             streams.push(stream);
         }
 
-        let subscription = Request::Subscribe { streams };
-        self.start_send(subscription)?;
+        let mut requests = Vec::with_capacity(self.groups.len() + 1);
+        requests.push(Request::Subscribe { streams });
+
+        // the consumer group members receive the events that were
+        // delivered to them but not acknowledged when joining the group again
+        for (stream, context) in &mut self.groups {
+            context.reconnected = true;
+            requests.push(Request::SubscribeGroup {
+                group: context.group.clone(),
+                stream: stream.clone(),
+                consumer: context.consumer.clone(),
+            });
+        }
+
+        for request in requests {
+            self.start_send(request)?;
+        }
         self.poll_complete()?;
 
         Ok(())
@@ -73,26 +203,40 @@ impl Stream for EventStream {
     type Error = ProtocolError;
 
     fn poll(&mut self) -> Result<Async<Option<Self::Item>>, Self::Error> {
-        let result = match self.connection.poll() {
-            Ok(Async::Ready(Some(item))) => {
-                match &item {
-                    Ok(Response::Event { stream, number, .. }) => {
-                        self.state.entry(stream.clone()).or_default().position_start =
-                            Some(number.0 + 1);
+        let result = loop {
+            // the events of a batch are returned one by one, as if they were sent separately
+            let polled = match self.batched.pop_front() {
+                Some(event) => Ok(Async::Ready(Some(Ok(event)))),
+                None => self.connection.poll(),
+            };
+
+            match polled {
+                Ok(Async::Ready(Some(Ok(Response::Batch { events })))) => {
+                    self.batched.extend(events);
+                }
+                Ok(Async::Ready(Some(item))) => {
+                    if let Some(keepalive) = &mut self.keepalive {
+                        keepalive.reset();
                     }
-                    Ok(Response::Subscribed { stream }) => {
-                        // if we were already subscribed to a stream and we are reconnecting
-                        // we do not return the message validating a subscription to the user
-                        if self.state.get(&stream).map_or(false, |c| c.reconnected) {
-                            return self.poll();
-                        }
+
+                    // the answers to the keepalive pings are not returned to the user
+                    let pong = matches!(item, Ok(Response::Pong { .. }));
+                    if pong && self.keepalive.is_some() {
+                        continue;
                     }
-                    _otherwise => (),
-                }
 
-                Ok(Async::Ready(Some(item)))
+                    if track_response(&mut self.state, &mut self.groups, &item) {
+                        break Ok(Async::Ready(Some(item)));
+                    }
+                }
+                Ok(Async::NotReady) => {
+                    self.poll_keepalive()
+                        .map_err(|e| error!("keepalive error; {}", e))
+                        .ok();
+                    break Ok(Async::NotReady);
+                }
+                otherwise => break otherwise,
             }
-            otherwise => otherwise,
         };
 
         if self.connection.has_been_reconnected() {
@@ -103,6 +247,88 @@ impl Stream for EventStream {
     }
 }
 
+/// Updates the positions of the subscriptions with a response received, returns `false` if it
+/// must not be returned to the user: an event already returned before a reconnection or the
+/// confirmation of a subscription sent again when reconnecting.
+fn track_response(
+    state: &mut HashMap<StreamName, StreamContext>,
+    groups: &mut HashMap<StreamName, GroupContext>,
+    response: &Result<Response, String>,
+) -> bool {
+    match response {
+        Ok(Response::Event {
+            stream,
+            global_number: Some(global_number),
+            ..
+        }) => {
+            // the events of the virtual streams keep the name of their own stream,
+            // these subscriptions are resumed from the global position, an event
+            // is only skipped if all the virtual streams including it returned it
+            let global = global_number.0;
+            let mut contexts = state
+                .iter()
+                .filter(|(name, _)| *name != stream && name.includes(stream))
+                .peekable();
+            let duplicate = contexts.peek().is_some()
+                && contexts.all(|(_, c)| c.delivered.map_or(false, |d| global <= d));
+            if duplicate {
+                return false;
+            }
+
+            let contexts = state
+                .iter_mut()
+                .filter(|(name, _)| *name != stream && name.includes(stream));
+            for (_, context) in contexts {
+                context.position_start = Some(global + 1);
+                context.delivered = Some(global);
+                context.committed = None;
+            }
+        }
+        Ok(Response::Event { stream, number, .. }) => {
+            if !groups.contains_key(stream) {
+                let context = state.entry(stream.clone()).or_default();
+                if context.delivered.map_or(false, |d| number.0 <= d) {
+                    // the event has been returned before the reconnection,
+                    // the subscription overlaps the events already received
+                    return false;
+                }
+
+                // the position is now known, the committed offset must not be used
+                context.position_start = Some(number.0 + 1);
+                context.delivered = Some(number.0);
+                context.committed = None;
+            }
+        }
+        Ok(Response::StreamTrimmed { stream, first }) => {
+            let context = state.entry(stream.clone()).or_default();
+            if context
+                .position_start
+                .map_or(false, |start| start < first.0)
+            {
+                context.position_start = Some(first.0);
+            }
+        }
+        Ok(Response::StreamDeleted { stream }) | Ok(Response::RangeEnd { stream }) => {
+            // the server stops sending the events of a deleted or completely
+            // read stream, we must not subscribe to it again when reconnecting
+            state.remove(stream);
+            groups.remove(stream);
+        }
+        Ok(Response::Subscribed { stream }) => {
+            // if we were already subscribed to a stream and we are reconnecting
+            // we do not return the message validating a subscription to the user
+            if state.get(stream).map_or(false, |c| c.reconnected)
+                || groups.get(stream).map_or(false, |c| c.reconnected)
+            {
+                return false;
+            }
+        }
+        _otherwise => (),
+    }
+
+    true
+}
+
 impl Sink for EventStream {
     type SinkItem = Request;
     type SinkError = ProtocolError;
@@ -112,9 +338,47 @@ impl Sink for EventStream {
         item: Self::SinkItem,
     ) -> Result<AsyncSink<Self::SinkItem>, Self::SinkError> {
         if let Request::Subscribe { streams } = &item {
-            for EsStream { name, range } in streams {
-                self.state.entry(name.clone()).or_default().position_start = range.from();
-                self.state.entry(name.clone()).or_default().position_end = range.to();
+            for EsStream {
+                name,
+                range,
+                event_names,
+                committed,
+                slow_consumer,
+                credits,
+                batch,
+            } in streams
+            {
+                let context = self.state.entry(name.clone()).or_default();
+                context.position_start = range.from();
+                context.position_end = range.to();
+                context.position_time = range.from_time();
+                context.delivered = None;
+                context.event_names = event_names.clone();
+                context.committed = committed.clone();
+                context.slow_consumer = *slow_consumer;
+                context.credits = *credits;
+                context.batch = *batch;
+            }
+        }
+
+        if let Request::SubscribeGroup {
+            group,
+            stream,
+            consumer,
+        } = &item
+        {
+            let context = GroupContext {
+                group: group.clone(),
+                consumer: consumer.clone(),
+                reconnected: false,
+            };
+            self.groups.insert(stream.clone(), context);
+        }
+
+        if let Request::Unsubscribe { streams } = &item {
+            for name in streams {
+                self.state.remove(name);
+                self.groups.remove(name);
             }
         }
 
@@ -142,7 +406,22 @@ impl Sink for EventStream {
 pub fn sub_connect(
     addr: SocketAddr,
 ) -> impl Future<Item = (SubController, SubStream), Error = tokio_retry::Error<io::Error>> {
-    EventStream::connect(addr)
+    ConnectBuilder::new(addr).sub()
+}
+
+/// Open a sup connection with a server that sends a ping after `interval`
+/// of inactivity and reconnects if the server does not answer in the next `interval`.
+pub fn sub_connect_with_keepalive(
+    addr: SocketAddr,
+    interval: Duration,
+) -> impl Future<Item = (SubController, SubStream), Error = tokio_retry::Error<io::Error>> {
+    ConnectBuilder::new(addr).keepalive(interval).sub()
+}
+
+pub(crate) fn sub_connect_with(
+    builder: ConnectBuilder,
+) -> impl Future<Item = (SubController, SubStream), Error = tokio_retry::Error<io::Error>> {
+    EventStream::connect(builder)
         .map_err(|e| dbg!(e))
         .map(|connection| {
             let (writer, reader) = connection.split();
@@ -184,6 +463,67 @@ impl SubController {
             error!("{}", e);
         }
     }
+
+    /// Join a consumer group to receive the events of the given stream,
+    /// each event is only delivered to one member of the group.
+    ///
+    /// Every event received must be acknowledged using `ack`, the events that are not
+    /// are delivered again when the consumer joins the group again.
+    pub fn subscribe_group(&mut self, group: GroupName, stream: StreamName, consumer: String) {
+        let command = Request::SubscribeGroup {
+            group,
+            stream,
+            consumer,
+        };
+
+        if let Err(e) = self.sender.try_send(command) {
+            error!("{}", e);
+        }
+    }
+
+    /// Acknowledge that an event delivered by a consumer group has been processed.
+    pub fn ack(&mut self, group: GroupName, stream: StreamName, event_number: EventNumber) {
+        let command = Request::Ack {
+            group,
+            stream,
+            event_number,
+        };
+
+        if let Err(e) = self.sender.try_send(command) {
+            error!("{}", e);
+        }
+    }
+
+    /// Give credits to the flow controlled subscription to a stream, the
+    /// server sends one more event of the subscription for each credit.
+    pub fn flow(&mut self, stream: StreamName, credits: u64) {
+        let command = Request::Flow { stream, credits };
+
+        if let Err(e) = self.sender.try_send(command) {
+            error!("{}", e);
+        }
+    }
+
+    /// Acknowledge that all the events up to this global event number have been replicated,
+    /// only used by the replicas of a server.
+    pub fn replica_ack(&mut self, global: EventNumber) {
+        let command = Request::ReplicaAck { global };
+
+        if let Err(e) = self.sender.try_send(command) {
+            error!("{}", e);
+        }
+    }
+
+    /// Ask the server to stop sending events of the given stream.
+    pub fn unsubscribe_from(&mut self, stream: StreamName) {
+        let command = Request::Unsubscribe {
+            streams: vec![stream],
+        };
+
+        if let Err(e) = self.sender.try_send(command) {
+            error!("{}", e);
+        }
+    }
 }
 
 /// A tokio Stream that returns every event received on all subscribed streams.
@@ -214,3 +554,140 @@ impl Stream for SubStream {
         self.connection.poll()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use meilies::stream::{EventData, EventHeaders};
+
+    fn event(stream: &str, number: u64, global: Option<u64>) -> Result<Response, String> {
+        Ok(Response::Event {
+            stream: stream.parse().unwrap(),
+            number: EventNumber(number),
+            timestamp: Timestamp(0),
+            event_name: "test-event".parse().unwrap(),
+            event_data: EventData(Vec::new().into()),
+            headers: EventHeaders::new(),
+            global_number: global.map(EventNumber),
+        })
+    }
+
+    /// The subscriptions of an `EventStream`, without its connection.
+    #[derive(Default)]
+    struct Subscriptions {
+        state: HashMap<StreamName, StreamContext>,
+        groups: HashMap<StreamName, GroupContext>,
+    }
+
+    impl Subscriptions {
+        fn track(&mut self, response: Result<Response, String>) -> bool {
+            track_response(&mut self.state, &mut self.groups, &response)
+        }
+
+        fn context(&self, stream: &str) -> &StreamContext {
+            &self.state[&stream.parse::<StreamName>().unwrap()]
+        }
+    }
+
+    #[test]
+    fn events_received_again_are_skipped() {
+        let mut subs = Subscriptions::default();
+
+        assert!(subs.track(event("orders", 0, None)));
+        assert!(subs.track(event("orders", 1, None)));
+
+        // the subscription is sent again from the last event returned
+        assert!(!subs.track(event("orders", 0, None)));
+        assert!(!subs.track(event("orders", 1, None)));
+        assert!(subs.track(event("orders", 2, None)));
+
+        let context = subs.context("orders");
+        assert_eq!(context.position_start, Some(3));
+        assert_eq!(context.delivered, Some(2));
+    }
+
+    #[test]
+    fn group_events_are_not_skipped() {
+        let mut subs = Subscriptions::default();
+        let group = GroupContext {
+            group: "workers".parse().unwrap(),
+            consumer: String::from("worker-1"),
+            reconnected: false,
+        };
+        subs.groups.insert("orders".parse().unwrap(), group);
+
+        // the events delivered but not acknowledged are received again when joining again
+        assert!(subs.track(event("orders", 0, None)));
+        assert!(subs.track(event("orders", 0, None)));
+        assert!(subs.state.is_empty());
+    }
+
+    #[test]
+    fn virtual_events_are_skipped_by_all_their_contexts() {
+        let mut subs = Subscriptions::default();
+        let all = StreamContext {
+            delivered: Some(5),
+            ..StreamContext::default()
+        };
+        let category = StreamContext {
+            delivered: Some(3),
+            ..StreamContext::default()
+        };
+        subs.state.insert("$all".parse().unwrap(), all);
+        subs.state
+            .insert("$category-order".parse().unwrap(), category);
+
+        // the category has not returned the event yet, it is not a duplicate
+        assert!(subs.track(event("order-1", 0, Some(4))));
+        assert_eq!(subs.context("$all").delivered, Some(4));
+        assert_eq!(subs.context("$category-order").delivered, Some(4));
+
+        // both virtual streams returned it
+        assert!(!subs.track(event("order-1", 0, Some(4))));
+
+        // only the contexts including the stream are considered
+        assert!(subs.track(event("invoice-1", 0, Some(5))));
+        assert_eq!(subs.context("$all").delivered, Some(5));
+        assert_eq!(subs.context("$category-order").delivered, Some(4));
+        assert!(!subs.track(event("invoice-1", 0, Some(5))));
+
+        // the events of streams that no virtual stream includes are never skipped
+        subs.state.remove(&"$all".parse::<StreamName>().unwrap());
+        assert!(subs.track(event("invoice-1", 0, Some(5))));
+    }
+
+    #[test]
+    fn subscriptions_sent_again_are_hidden() {
+        let mut subs = Subscriptions::default();
+        let subscribed = Ok(Response::Subscribed {
+            stream: "orders".parse().unwrap(),
+        });
+
+        assert!(subs.track(subscribed.clone()));
+
+        let context = StreamContext {
+            reconnected: true,
+            ..StreamContext::default()
+        };
+        subs.state.insert("orders".parse().unwrap(), context);
+        assert!(!subs.track(subscribed));
+    }
+
+    #[test]
+    fn ended_streams_are_forgotten() {
+        let mut subs = Subscriptions::default();
+        let stream: StreamName = "orders".parse().unwrap();
+
+        assert!(subs.track(event("orders", 4, None)));
+        let trimmed = Ok(Response::StreamTrimmed {
+            stream: stream.clone(),
+            first: EventNumber(10),
+        });
+        assert!(subs.track(trimmed));
+        assert_eq!(subs.context("orders").position_start, Some(10));
+
+        let deleted = Ok(Response::StreamDeleted { stream });
+        assert!(subs.track(deleted));
+        assert!(subs.state.is_empty());
+    }
+}
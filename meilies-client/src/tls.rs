@@ -0,0 +1,188 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use futures::{Future, Poll};
+use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, TLSError};
+use rustls::{ServerCertVerified, ServerCertVerifier};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use webpki::DNSNameRef;
+
+/// The TLS configuration used to connect to a server.
+///
+/// By default the server certificate is verified against the Mozilla root certificates.
+#[derive(Clone)]
+pub struct TlsConfig {
+    config: Arc<ClientConfig>,
+    domain: String,
+}
+
+impl TlsConfig {
+    /// Create a configuration that expects the server to present a certificate
+    /// for the given domain, the domain is also sent as the SNI.
+    pub fn new<S: Into<String>>(domain: S) -> TlsConfig {
+        let mut config = ClientConfig::new();
+        config
+            .root_store
+            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+
+        TlsConfig {
+            config: Arc::new(config),
+            domain: domain.into(),
+        }
+    }
+
+    /// Also trust the certificates of a PEM file, e.g. a self-signed certificate.
+    pub fn add_ca_file<P: AsRef<Path>>(mut self, path: P) -> io::Result<TlsConfig> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let config = Arc::make_mut(&mut self.config);
+        match config.root_store.add_pem_file(&mut reader) {
+            Ok((_valid, 0)) => Ok(self),
+            Ok((_valid, invalid)) => {
+                let error = format!("{} invalid certificates found", invalid);
+                Err(invalid_data(&error))
+            }
+            Err(()) => Err(invalid_data("invalid PEM file")),
+        }
+    }
+
+    /// Present this certificate to the server, used by servers that authenticate
+    /// clients with their certificate. Both files must be PEM encoded.
+    pub fn identity<P, Q>(mut self, cert: P, key: Q) -> io::Result<TlsConfig>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let certs = load_certs(cert.as_ref())?;
+        let key = load_private_key(key.as_ref())?;
+
+        let config = Arc::make_mut(&mut self.config);
+        config.set_single_client_cert(certs, key);
+
+        Ok(self)
+    }
+
+    /// Do not verify the server certificate, must only be used for development.
+    pub fn danger_accept_invalid_certs(mut self) -> TlsConfig {
+        let config = Arc::make_mut(&mut self.config);
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(NoCertificateVerification));
+        self
+    }
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("domain", &self.domain)
+            .finish()
+    }
+}
+
+fn invalid_data(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Reads the certificate chain of a PEM file.
+pub fn load_certs(path: &Path) -> io::Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    match certs(&mut reader) {
+        Ok(ref certs) if certs.is_empty() => Err(invalid_data("no certificate found")),
+        Ok(certs) => Ok(certs),
+        Err(()) => Err(invalid_data("invalid certificate file")),
+    }
+}
+
+/// Reads the private key of a PEM file, in the PKCS #8 or the RSA format.
+pub fn load_private_key(path: &Path) -> io::Result<PrivateKey> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut keys =
+        pkcs8_private_keys(&mut reader).map_err(|()| invalid_data("invalid key file"))?;
+
+    if keys.is_empty() {
+        let mut reader = BufReader::new(File::open(path)?);
+        keys = rsa_private_keys(&mut reader).map_err(|()| invalid_data("invalid key file"))?;
+    }
+
+    keys.pop()
+        .ok_or_else(|| invalid_data("no private key found"))
+}
+
+struct NoCertificateVerification;
+
+impl ServerCertVerifier for NoCertificateVerification {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        _presented_certs: &[Certificate],
+        _dns_name: DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Starts a TLS session on a TCP stream.
+pub fn tls_handshake(
+    socket: TcpStream,
+    tls: &TlsConfig,
+) -> impl Future<Item = TlsStream<TcpStream>, Error = io::Error> {
+    let connector = TlsConnector::from(tls.config.clone());
+    let domain = DNSNameRef::try_from_ascii_str(&tls.domain).map(|d| d.to_owned());
+
+    futures::future::result(domain)
+        .map_err(|_| {
+            let error = "invalid TLS domain name";
+            io::Error::new(io::ErrorKind::InvalidInput, error)
+        })
+        .and_then(move |domain| connector.connect(domain.as_ref(), socket))
+}
+
+/// A TCP stream that may be encrypted.
+pub enum MaybeTlsStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Read for MaybeTlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            MaybeTlsStream::Plain(stream) => stream.read(buf),
+            MaybeTlsStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for MaybeTlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            MaybeTlsStream::Plain(stream) => stream.write(buf),
+            MaybeTlsStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            MaybeTlsStream::Plain(stream) => stream.flush(),
+            MaybeTlsStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+impl AsyncRead for MaybeTlsStream {}
+
+impl AsyncWrite for MaybeTlsStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match self {
+            MaybeTlsStream::Plain(stream) => AsyncWrite::shutdown(stream),
+            MaybeTlsStream::Tls(stream) => stream.shutdown(),
+        }
+    }
+}
@@ -6,7 +6,7 @@ use futures::future::{self, Future};
 use futures::stream::Stream;
 use meilies::reqresp::Response;
 use meilies::stream::Stream as EsStream;
-use meilies_client::sub_connect;
+use meilies_client::compat::sub_connect;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -33,6 +33,7 @@ struct Opt {
     /// `MEILIES_STREAM_NAME` contains the stream name.
     /// `MEILIES_EVENT_NAME` contains the event name.
     /// `MEILIES_EVENT_NUMBER` contains the event number.
+    /// `MEILIES_EVENT_TIMESTAMP` contains the event timestamp in milliseconds since the epoch.
     command: String,
 }
 
@@ -70,8 +71,10 @@ fn main() {
                     Ok(Response::Event {
                         stream,
                         number,
+                        timestamp,
                         event_name,
                         event_data,
+                        ..
                     }) => {
                         eprintln!("processing event number {}", number.0);
 
@@ -82,6 +85,7 @@ fn main() {
                             .env("MEILIES_STREAM_NAME", stream.into_inner())
                             .env("MEILIES_EVENT_NAME", event_name.into_inner())
                             .env("MEILIES_EVENT_NUMBER", number.0.to_string())
+                            .env("MEILIES_EVENT_TIMESTAMP", timestamp.0.to_string())
                             .spawn();
 
                         let mut child = match result {
@@ -89,7 +93,7 @@ fn main() {
                             Err(e) => return future::err(e),
                         };
 
-                        let data = event_data.0.as_slice();
+                        let data = &event_data.0[..];
                         if let Err(e) = child.stdin.as_mut().unwrap().write_all(data) {
                             return future::err(e);
                         }
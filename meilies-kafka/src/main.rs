@@ -0,0 +1,104 @@
+use std::net::ToSocketAddrs;
+use std::str::FromStr;
+
+use log::error;
+use meilies::stream::{StreamName, StreamNameError};
+use meilies_client::ConnectBuilder;
+use structopt::StructOpt;
+
+use self::sink::SinkOpt;
+use self::source::SourceOpt;
+
+mod sink;
+mod source;
+
+/// A stream and the Kafka topic it is paired with, written
+/// `stream=topic` or only `stream` if the topic has the same name.
+#[derive(Debug)]
+struct Mapping {
+    stream: StreamName,
+    topic: String,
+}
+
+impl FromStr for Mapping {
+    type Err = StreamNameError;
+
+    fn from_str(s: &str) -> Result<Mapping, StreamNameError> {
+        let (stream, topic) = match s.find('=') {
+            Some(index) => (&s[..index], &s[index + 1..]),
+            None => (s, s),
+        };
+
+        Ok(Mapping {
+            stream: StreamName::from_str(stream)?,
+            topic: topic.to_owned(),
+        })
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "meilies-kafka",
+    about = "Connects MeiliES streams with Kafka topics.",
+    author
+)]
+struct Opt {
+    /// MeiliES server address (i.e. localhost:6480).
+    #[structopt(long = "server", default_value = "127.0.0.1:6480")]
+    server: String,
+
+    /// User to authenticate as, the server must use an ACL.
+    #[structopt(short = "u", long = "user")]
+    user: Option<String>,
+
+    /// Password used to authenticate with the server.
+    #[structopt(short = "a", long = "password")]
+    password: Option<String>,
+
+    /// Kafka bootstrap servers (i.e. localhost:9092,localhost:9093).
+    #[structopt(long = "brokers", default_value = "127.0.0.1:9092")]
+    brokers: String,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Produces the events of MeiliES streams to Kafka topics.
+    #[structopt(name = "sink")]
+    Sink(SinkOpt),
+
+    /// Appends the messages of Kafka topics to MeiliES streams.
+    #[structopt(name = "source")]
+    Source(SourceOpt),
+}
+
+fn main() {
+    let _ = stderrlog::new().verbosity(2).init();
+
+    let opt = Opt::from_args();
+
+    let addr = match opt
+        .server
+        .to_socket_addrs()
+        .map(|mut addrs| addrs.find(|a| a.is_ipv4()))
+    {
+        Ok(Some(addr)) => addr,
+        Ok(None) => return error!("impossible to dns resolve addr; {:?}", opt.server),
+        Err(e) => return error!("error parsing addr; {}", e),
+    };
+
+    let mut builder = ConnectBuilder::new(addr);
+    if let Some(user) = opt.user {
+        builder = builder.username(user);
+    }
+    if let Some(password) = opt.password {
+        builder = builder.password(password);
+    }
+
+    match opt.command {
+        Command::Sink(sink_opt) => sink::run(builder, &opt.brokers, sink_opt),
+        Command::Source(source_opt) => source::run(builder, &opt.brokers, source_opt),
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use futures03::{FutureExt, TryFutureExt};
+use log::{error, info, warn};
+use meilies::reqresp::Response;
+use meilies::stream::{Event, EventData, EventHeaders, EventName, EventNumber};
+use meilies::stream::{Stream as EsStream, StreamName};
+use meilies_client::compat::PairedConnection;
+use meilies_client::ConnectBuilder;
+use rdkafka::config::ClientConfig;
+use rdkafka::message::OwnedHeaders;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use structopt::StructOpt;
+
+use super::Mapping;
+
+/// The name of the events recording the positions of the connector in the checkpoint stream.
+const CHECKPOINT_EVENT: &str = "Checkpoint";
+
+/// The Kafka headers describing the MeiliES event a message has been produced from,
+/// the headers of the event are copied along.
+const STREAM_HEADER: &str = "meilies-stream";
+const NUMBER_HEADER: &str = "meilies-number";
+pub const EVENT_NAME_HEADER: &str = "meilies-event-name";
+const TIMESTAMP_HEADER: &str = "meilies-timestamp";
+
+#[derive(Debug, StructOpt)]
+pub struct SinkOpt {
+    /// Stream in which the positions of the connector are recorded, only its last event is
+    /// read when the connector starts, the ones before can be removed with a retention.
+    #[structopt(long = "checkpoint-stream", default_value = "kafka-sink-checkpoints")]
+    checkpoint_stream: StreamName,
+
+    /// Number of events produced between two checkpoints, a checkpoint is also
+    /// recorded each time the connector has caught up with a stream.
+    #[structopt(long = "checkpoint-interval", default_value = "100")]
+    checkpoint_interval: u64,
+
+    /// Produce the events already in the streams that have no checkpoint,
+    /// only the events published from now on are produced otherwise.
+    #[structopt(long = "from-beginning")]
+    from_beginning: bool,
+
+    /// List of the streams to produce to Kafka and their topics
+    /// (i.e. payments=meilies-payments, orders).
+    #[structopt(required = true)]
+    mappings: Vec<Mapping>,
+}
+
+/// Returns the positions recorded in the headers of a checkpoint,
+/// the number of the next event to produce by stream.
+fn checkpoint_positions(checkpoint: &Event) -> BTreeMap<StreamName, u64> {
+    checkpoint
+        .headers
+        .0
+        .iter()
+        .filter_map(|(stream, number)| {
+            let stream = StreamName::from_str(stream).ok()?;
+            Some((stream, number.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Reads the positions of the last checkpoint of the connector.
+fn read_checkpoint(
+    connection: PairedConnection,
+    checkpoint_stream: StreamName,
+) -> impl Future<Item = (BTreeMap<StreamName, u64>, PairedConnection), Error = ()> {
+    connection
+        .last_event_number(checkpoint_stream)
+        .and_then(|(stream, number, connection)| match number {
+            Some(number) => {
+                let to = EventNumber(number.0 + 1);
+                let range = connection.get_range(stream, Some(number), Some(to));
+                Either::A(range.map(|(events, connection)| {
+                    let positions = events.last().map(checkpoint_positions);
+                    (positions.unwrap_or_default(), connection)
+                }))
+            }
+            None => Either::B(future::ok((BTreeMap::new(), connection))),
+        })
+        .map_err(|e| error!("error reading the last checkpoint; {}", e))
+}
+
+/// Produces the events received and records the positions of the connector
+/// once the events have been acknowledged by the Kafka brokers.
+struct Connector {
+    producer: FutureProducer,
+    topics: HashMap<StreamName, String>,
+    /// The number of the next event to produce by stream.
+    positions: BTreeMap<StreamName, u64>,
+    checkpoint_stream: StreamName,
+    checkpoint_interval: u64,
+    /// The number of events produced since the last checkpoint.
+    unsaved: u64,
+}
+
+impl Connector {
+    /// Records the positions in the checkpoint stream, the events produced after the last
+    /// checkpoint are produced again when the connector restarts (at-least-once delivery).
+    fn checkpoint(
+        mut self,
+        connection: PairedConnection,
+    ) -> impl Future<Item = (Connector, PairedConnection), Error = ()> {
+        let headers = self
+            .positions
+            .iter()
+            .map(|(stream, number)| (stream.as_str().to_owned(), number.to_string()))
+            .collect();
+        let event_name = EventName::new(CHECKPOINT_EVENT.to_owned()).unwrap();
+        let event_data = EventData(Vec::new().into());
+
+        connection
+            .publish_with_headers(
+                self.checkpoint_stream.clone(),
+                event_name,
+                event_data,
+                EventHeaders(headers),
+            )
+            .map(move |(number, connection)| {
+                info!("checkpoint {:?} recorded", number);
+                self.unsaved = 0;
+                (self, connection)
+            })
+            .map_err(|e| error!("error recording a checkpoint; {}", e))
+    }
+
+    /// Produces the event and waits for the brokers to acknowledge it,
+    /// the events are produced one at a time to keep their order.
+    fn produce(
+        mut self,
+        stream: StreamName,
+        number: EventNumber,
+        event: Event,
+    ) -> impl Future<Item = Connector, Error = ()> {
+        let topic = &self.topics[&stream];
+        let number_header = number.0.to_string();
+        let timestamp_header = event.timestamp.0.to_string();
+        let mut headers = OwnedHeaders::new()
+            .add(STREAM_HEADER, stream.as_str())
+            .add(NUMBER_HEADER, number_header.as_str())
+            .add(EVENT_NAME_HEADER, event.name.as_str())
+            .add(TIMESTAMP_HEADER, timestamp_header.as_str());
+        for (key, value) in &event.headers.0 {
+            headers = headers.add(key, value.as_str());
+        }
+
+        // the messages of a stream are keyed by its name to be produced to the same partition
+        let record = FutureRecord::to(topic)
+            .key(stream.as_str())
+            .payload(&event.data.0[..])
+            .headers(headers);
+        let delivery = match self.producer.send_result(record) {
+            Ok(delivery) => delivery,
+            Err((e, _record)) => {
+                error!("error producing {:?} {:?}; {}", stream, number, e);
+                return Either::A(future::err(()));
+            }
+        };
+
+        // the deliveries are reported by futures 0.3 futures
+        let delivery = delivery.map(Ok::<_, ()>).compat();
+
+        Either::B(delivery.and_then(move |result| match result {
+            Ok(Ok((partition, offset))) => {
+                info!(
+                    "{:?} {:?} produced to partition {} at offset {}",
+                    stream, number, partition, offset
+                );
+                self.positions.insert(stream, number.0 + 1);
+                self.unsaved += 1;
+                Ok(self)
+            }
+            Ok(Err((e, _message))) => {
+                error!("error producing {:?} {:?}; {}", stream, number, e);
+                Err(())
+            }
+            Err(_canceled) => {
+                error!("the Kafka producer has been dropped");
+                Err(())
+            }
+        }))
+    }
+}
+
+type Running = Box<dyn Future<Item = (Connector, PairedConnection), Error = ()> + Send>;
+
+fn handle(
+    connector: Connector,
+    connection: PairedConnection,
+    response: Result<Response, String>,
+) -> Running {
+    match response {
+        Ok(Response::Event {
+            stream,
+            number,
+            timestamp,
+            event_name,
+            event_data,
+            headers,
+            ..
+        }) => {
+            let event = Event {
+                number,
+                timestamp,
+                name: event_name,
+                data: event_data,
+                headers,
+            };
+            let produced = connector.produce(stream, number, event);
+            Box::new(produced.and_then(move |connector| {
+                if connector.unsaved >= connector.checkpoint_interval {
+                    Either::A(connector.checkpoint(connection))
+                } else {
+                    Either::B(future::ok((connector, connection)))
+                }
+            }))
+        }
+        Ok(Response::CaughtUp { stream }) => {
+            info!("caught up with {:?}", stream);
+            if connector.unsaved > 0 {
+                Box::new(connector.checkpoint(connection))
+            } else {
+                Box::new(future::ok((connector, connection)))
+            }
+        }
+        Ok(Response::EventsDropped { stream, count }) => {
+            // the events dropped by the server are lost for the Kafka topic too
+            warn!("{} events of {:?} have been dropped", count, stream);
+            Box::new(future::ok((connector, connection)))
+        }
+        Ok(response) => {
+            info!("{:?}", response);
+            Box::new(future::ok((connector, connection)))
+        }
+        Err(error) => {
+            error!("{}", error);
+            Box::new(future::err(()))
+        }
+    }
+}
+
+/// Produces the events of the streams to their topics until the connection is closed.
+pub fn run(builder: ConnectBuilder, brokers: &str, opt: SinkOpt) {
+    // the events are only checkpointed once they have been written by all the in-sync replicas
+    let producer = ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .set("acks", "all")
+        .create::<FutureProducer>();
+    let producer = match producer {
+        Ok(producer) => producer,
+        Err(e) => return error!("error creating the Kafka producer; {}", e),
+    };
+
+    let from_beginning = opt.from_beginning;
+    let mappings = opt.mappings;
+    let checkpoint_stream = opt.checkpoint_stream;
+    let mut connector = Connector {
+        producer,
+        topics: HashMap::new(),
+        positions: BTreeMap::new(),
+        checkpoint_stream: checkpoint_stream.clone(),
+        checkpoint_interval: opt.checkpoint_interval.max(1),
+        unsaved: 0,
+    };
+
+    let fut = builder
+        .clone()
+        .paired()
+        .map_err(|e| error!("{}", e))
+        .and_then(move |connection| read_checkpoint(connection, checkpoint_stream))
+        .and_then(move |(positions, connection)| {
+            builder
+                .sub()
+                .map_err(|e| error!("{}", e))
+                .and_then(move |(mut ctrl, msgs)| {
+                    for mapping in mappings {
+                        let from = match positions.get(&mapping.stream) {
+                            Some(position) => Some(*position),
+                            None if from_beginning => Some(0),
+                            None => None,
+                        };
+                        info!("producing {:?} from {:?}", mapping.stream, from);
+                        ctrl.subscribe_to(EsStream::new_from_to(
+                            mapping.stream.clone(),
+                            from,
+                            None,
+                        ));
+                        connector.topics.insert(mapping.stream, mapping.topic);
+                    }
+                    // the positions of the streams that are no longer produced are kept
+                    connector.positions = positions;
+
+                    msgs.map_err(|e| error!("{}", e))
+                        .fold((connector, connection), |(connector, connection), msg| {
+                            handle(connector, connection, msg)
+                        })
+                })
+        })
+        .and_then(|_| {
+            error!("Connection closed by the server");
+            Err(())
+        });
+
+    tokio::run(fut);
+}
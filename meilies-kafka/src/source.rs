@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::str::{self, FromStr};
+use std::time::Duration;
+
+use log::{error, info};
+use meilies::stream::{EventData, EventHeaders, EventName, StreamName};
+use meilies_client::ConnectBuilder;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{BaseConsumer, Consumer};
+use rdkafka::message::{BorrowedMessage, Headers, Message};
+use structopt::StructOpt;
+use tokio::runtime::Runtime;
+
+use super::sink::EVENT_NAME_HEADER;
+use super::Mapping;
+
+/// The headers describing the Kafka message an event has been appended from.
+const TOPIC_HEADER: &str = "kafka-topic";
+const PARTITION_HEADER: &str = "kafka-partition";
+const OFFSET_HEADER: &str = "kafka-offset";
+const KEY_HEADER: &str = "kafka-key";
+
+/// The time to wait for a message before polling the consumer again.
+const POLL_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, StructOpt)]
+pub struct SourceOpt {
+    /// Kafka consumer group of the importer, the offsets it commits are the positions
+    /// it resumes from when it restarts.
+    #[structopt(long = "group-id", default_value = "meilies-kafka")]
+    group_id: String,
+
+    /// Name of the events appended for the messages without a `meilies-event-name` header.
+    #[structopt(long = "event-name", default_value = "KafkaMessage")]
+    event_name: EventName,
+
+    /// Consume the messages already in the topics if the group has no committed offset,
+    /// only the messages produced from now on are consumed otherwise.
+    #[structopt(long = "from-beginning")]
+    from_beginning: bool,
+
+    /// List of the streams the messages are appended to and their topics
+    /// (i.e. payments=meilies-payments, orders).
+    #[structopt(required = true)]
+    mappings: Vec<Mapping>,
+}
+
+/// Returns the name, the data and the headers of the event appended for a message,
+/// the headers of the message are kept if their values are valid UTF-8.
+fn message_event(
+    message: &BorrowedMessage,
+    default_name: &EventName,
+) -> (EventName, EventData, EventHeaders) {
+    let mut headers = EventHeaders::new();
+    let mut event_name = None;
+
+    if let Some(message_headers) = message.headers() {
+        for index in 0..message_headers.count() {
+            let (key, value) = match message_headers.get(index) {
+                Some((key, value)) => match str::from_utf8(value) {
+                    Ok(value) => (key, value),
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+
+            if key == EVENT_NAME_HEADER {
+                event_name = EventName::from_str(value).ok();
+            } else {
+                headers.insert(key.to_owned(), value.to_owned());
+            }
+        }
+    }
+
+    headers.insert(TOPIC_HEADER.to_owned(), message.topic().to_owned());
+    headers.insert(PARTITION_HEADER.to_owned(), message.partition().to_string());
+    headers.insert(OFFSET_HEADER.to_owned(), message.offset().to_string());
+    if let Some(key) = message.key().and_then(|key| str::from_utf8(key).ok()) {
+        headers.insert(KEY_HEADER.to_owned(), key.to_owned());
+    }
+
+    let event_name = event_name.unwrap_or_else(|| default_name.clone());
+    let event_data = EventData(message.payload().unwrap_or_default().to_vec().into());
+
+    (event_name, event_data, headers)
+}
+
+/// Appends the messages of the topics to their streams, one at a time in the order of
+/// their partitions, until an event can not be appended.
+pub fn run(builder: ConnectBuilder, brokers: &str, opt: SourceOpt) {
+    // the offsets are stored once the messages have been appended and committed periodically
+    let offset_reset = if opt.from_beginning {
+        "earliest"
+    } else {
+        "latest"
+    };
+    let consumer = ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .set("group.id", &opt.group_id)
+        .set("enable.auto.commit", "true")
+        .set("enable.auto.offset.store", "false")
+        .set("auto.offset.reset", offset_reset)
+        .create::<BaseConsumer>();
+    let consumer = match consumer {
+        Ok(consumer) => consumer,
+        Err(e) => return error!("error creating the Kafka consumer; {}", e),
+    };
+
+    let streams: HashMap<_, _> = opt
+        .mappings
+        .into_iter()
+        .map(|mapping| (mapping.topic, mapping.stream))
+        .collect();
+    let topics: Vec<_> = streams.keys().map(String::as_str).collect();
+    if let Err(e) = consumer.subscribe(&topics) {
+        return error!("error subscribing to the Kafka topics; {}", e);
+    }
+
+    let mut runtime = match Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => return error!("error creating the runtime; {}", e),
+    };
+    let mut connection = match runtime.block_on(builder.paired()) {
+        Ok(connection) => connection,
+        Err(e) => return error!("{}", e),
+    };
+
+    loop {
+        let message = match consumer.poll(POLL_TIMEOUT) {
+            Some(Ok(message)) => message,
+            Some(Err(e)) => {
+                error!("error consuming a message; {}", e);
+                continue;
+            }
+            None => continue,
+        };
+
+        let stream: StreamName = match streams.get(message.topic()) {
+            Some(stream) => stream.clone(),
+            None => continue,
+        };
+        let (event_name, event_data, headers) = message_event(&message, &opt.event_name);
+
+        // a message appended again after a restart is deduplicated by the server
+        let dedup_id = format!(
+            "kafka:{}:{}:{}",
+            message.topic(),
+            message.partition(),
+            message.offset()
+        );
+        let publish = connection.send_publish(
+            stream.clone(),
+            event_name,
+            event_data,
+            None,
+            Some(dedup_id),
+            headers,
+        );
+        connection = match runtime.block_on(publish) {
+            Ok((number, connection)) => {
+                info!(
+                    "{}:{}:{} appended to {:?} as {:?}",
+                    message.topic(),
+                    message.partition(),
+                    message.offset(),
+                    stream,
+                    number
+                );
+                connection
+            }
+            Err(e) => return error!("error appending a message to {:?}; {}", stream, e),
+        };
+
+        // the offset is committed only once the message has been appended
+        if let Err(e) = consumer.store_offset_from_message(&message) {
+            return error!("error storing the offset of a message; {}", e);
+        }
+    }
+}
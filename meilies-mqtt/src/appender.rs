@@ -0,0 +1,104 @@
+use futures::future::{self, Either};
+use futures::sync::{mpsc, oneshot};
+use futures::{Future, Sink, Stream};
+use log::error;
+use meilies::stream::{EventData, EventHeaders, EventName, EventNumber, StreamName};
+use meilies_client::compat::PairedConnection;
+use meilies_client::ConnectBuilder;
+
+/// The number of messages waiting to be appended, the
+/// sessions stop reading their packets when it is reached.
+const APPEND_BUFFER: usize = 1000;
+
+struct Append {
+    stream: StreamName,
+    event_name: EventName,
+    event_data: EventData,
+    headers: EventHeaders,
+    result: oneshot::Sender<Option<EventNumber>>,
+}
+
+/// Appends the messages of all the sessions, one at a time, using a single paired
+/// connection. The connection is opened again after an event could not be appended.
+#[derive(Clone)]
+pub struct Appender {
+    sender: mpsc::Sender<Append>,
+}
+
+impl Appender {
+    /// Spawns the task appending the messages, it must be called from a tokio runtime.
+    pub fn spawn(builder: ConnectBuilder) -> Appender {
+        let (sender, receiver) = mpsc::channel(APPEND_BUFFER);
+
+        let appending = receiver
+            .fold(None, move |connection, append| {
+                append_event(builder.clone(), connection, append)
+            })
+            .map(|_| ());
+        tokio::spawn(appending);
+
+        Appender { sender }
+    }
+
+    /// Appends an event and returns its number once appended.
+    pub fn append(
+        &self,
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        headers: EventHeaders,
+    ) -> impl Future<Item = EventNumber, Error = ()> {
+        let (result, receiver) = oneshot::channel();
+        let append = Append {
+            stream,
+            event_name,
+            event_data,
+            headers,
+            result,
+        };
+
+        self.sender
+            .clone()
+            .send(append)
+            .map_err(|_| error!("the appender is stopped"))
+            .and_then(|_| receiver.map_err(|_| ()))
+            .and_then(|number| number.ok_or(()))
+    }
+}
+
+fn append_event(
+    builder: ConnectBuilder,
+    connection: Option<PairedConnection>,
+    append: Append,
+) -> impl Future<Item = Option<PairedConnection>, Error = ()> {
+    let Append {
+        stream,
+        event_name,
+        event_data,
+        headers,
+        result,
+    } = append;
+
+    let connection = match connection {
+        Some(connection) => Either::A(future::ok(connection)),
+        None => Either::B(builder.paired().map_err(|e| e.to_string())),
+    };
+
+    connection
+        .and_then(move |connection| {
+            connection
+                .publish_with_headers(stream.clone(), event_name, event_data, headers)
+                .map_err(move |e| format!("error appending to {}; {}", stream, e))
+        })
+        .then(move |appended| match appended {
+            Ok((number, connection)) => {
+                let _ = result.send(Some(number));
+                Ok(Some(connection))
+            }
+            Err(e) => {
+                error!("{}", e);
+                let _ = result.send(None);
+                Ok(None)
+            }
+        })
+}
@@ -0,0 +1,107 @@
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+
+use futures::{future, Stream};
+use log::error;
+use meilies_client::ConnectBuilder;
+use structopt::StructOpt;
+use tokio::net::TcpListener;
+
+use self::appender::Appender;
+use self::mapping::Mapping;
+use self::session::Adapter;
+
+mod appender;
+mod mapping;
+mod packet;
+mod session;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "meilies-mqtt",
+    about = "Appends the messages published by MQTT clients to MeiliES streams.",
+    author
+)]
+struct Opt {
+    /// MeiliES server address (i.e. localhost:6480).
+    #[structopt(long = "server", default_value = "127.0.0.1:6480")]
+    server: String,
+
+    /// User to authenticate as, the server must use an ACL.
+    #[structopt(short = "u", long = "user")]
+    user: Option<String>,
+
+    /// Password used to authenticate with the server.
+    #[structopt(short = "a", long = "password")]
+    password: Option<String>,
+
+    /// Address the MQTT clients connect to.
+    #[structopt(long = "mqtt-addr", default_value = "127.0.0.1:1883")]
+    mqtt_addr: SocketAddr,
+
+    /// Require the MQTT clients to connect with this password, whatever their username is.
+    #[structopt(long = "requirepass")]
+    requirepass: Option<String>,
+
+    /// Maximum size of the packets sent by the MQTT clients, in bytes.
+    #[structopt(long = "max-packet-size", default_value = "1048576")]
+    max_packet_size: usize,
+
+    /// List of the topic filters and the streams their messages are appended to, the first
+    /// filter matching the topic of a message is used (i.e. sensors/+/temperature=temperatures).
+    #[structopt(required = true)]
+    mappings: Vec<Mapping>,
+}
+
+fn main() {
+    let _ = stderrlog::new().verbosity(2).init();
+
+    let opt = Opt::from_args();
+
+    let addr = match opt
+        .server
+        .to_socket_addrs()
+        .map(|mut addrs| addrs.find(|a| a.is_ipv4()))
+    {
+        Ok(Some(addr)) => addr,
+        Ok(None) => return error!("impossible to dns resolve addr; {:?}", opt.server),
+        Err(e) => return error!("error parsing addr; {}", e),
+    };
+
+    let mut builder = ConnectBuilder::new(addr);
+    if let Some(user) = opt.user {
+        builder = builder.username(user);
+    }
+    if let Some(password) = opt.password {
+        builder = builder.password(password);
+    }
+
+    let listener = match TcpListener::bind(&opt.mqtt_addr) {
+        Ok(listener) => listener,
+        Err(e) => return error!("error binding address; {}", e),
+    };
+    println!("MQTT adapter is listening on {}", opt.mqtt_addr);
+
+    let mappings = Arc::new(opt.mappings);
+    let requirepass = opt.requirepass;
+    let max_packet_size = opt.max_packet_size;
+
+    let fut = future::lazy(move || {
+        let adapter = Adapter {
+            mappings,
+            requirepass,
+            max_packet_size,
+            appender: Appender::spawn(builder),
+        };
+
+        listener
+            .incoming()
+            .map_err(|e| error!("error accepting socket; {}", e))
+            .for_each(move |socket| {
+                tokio::spawn(session::serve(adapter.clone(), socket));
+                Ok(())
+            })
+    });
+
+    tokio::run(fut);
+}
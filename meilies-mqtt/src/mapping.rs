@@ -0,0 +1,80 @@
+use std::fmt;
+use std::str::FromStr;
+
+use meilies::stream::{StreamName, StreamNameError};
+
+/// A topic filter and the stream the messages of the matching topics are appended to,
+/// written `filter=stream`, e.g. `sensors/+/temperature=temperatures`.
+#[derive(Debug)]
+pub struct Mapping {
+    filter: String,
+    pub stream: StreamName,
+}
+
+impl Mapping {
+    /// Returns `true` if the topic matches the filter, `+` matches a single level
+    /// and a trailing `#` matches the parent level and any number of child levels.
+    /// The topics starting with a `$` are not matched by the wildcards.
+    pub fn matches(&self, topic: &str) -> bool {
+        if topic.starts_with('$') && self.filter.starts_with(|c| c == '+' || c == '#') {
+            return false;
+        }
+
+        let mut levels = topic.split('/');
+        for filter_level in self.filter.split('/') {
+            if filter_level == "#" {
+                return true;
+            }
+            match levels.next() {
+                Some(level) if filter_level == "+" || filter_level == level => (),
+                _otherwise => return false,
+            }
+        }
+
+        levels.next().is_none()
+    }
+}
+
+#[derive(Debug)]
+pub enum MappingError {
+    MissingStream,
+    InvalidFilter,
+    InvalidStream(StreamNameError),
+}
+
+impl fmt::Display for MappingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MappingError::MissingStream => f.write_str("expected a mapping written filter=stream"),
+            MappingError::InvalidFilter => f.write_str("invalid topic filter"),
+            MappingError::InvalidStream(e) => write!(f, "invalid stream name; {}", e),
+        }
+    }
+}
+
+impl FromStr for Mapping {
+    type Err = MappingError;
+
+    fn from_str(s: &str) -> Result<Mapping, MappingError> {
+        // the stream names are less likely to contain an equal sign than the topics
+        let (filter, stream) = match s.rfind('=') {
+            Some(index) => (&s[..index], &s[index + 1..]),
+            None => return Err(MappingError::MissingStream),
+        };
+
+        let levels: Vec<_> = filter.split('/').collect();
+        let valid_levels = levels.iter().enumerate().all(|(i, level)| match *level {
+            "#" => i == levels.len() - 1,
+            "+" => true,
+            level => !level.contains(|c| c == '+' || c == '#'),
+        });
+        if filter.is_empty() || !valid_levels {
+            return Err(MappingError::InvalidFilter);
+        }
+
+        Ok(Mapping {
+            filter: filter.to_owned(),
+            stream: StreamName::from_str(stream).map_err(MappingError::InvalidStream)?,
+        })
+    }
+}
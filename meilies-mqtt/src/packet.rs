@@ -0,0 +1,314 @@
+use std::{fmt, io, str};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::codec::{Decoder, Encoder};
+
+/// The protocol names and levels of MQTT 3.1.1 and MQTT 3.1.
+pub const MQTT_3_1_1: (&str, u8) = ("MQTT", 4);
+pub const MQTT_3_1: (&str, u8) = ("MQIsdp", 3);
+
+/// A packet sent by a client, the packets only sent to the
+/// clients that publish messages are not accepted.
+#[derive(Debug)]
+pub enum Packet {
+    Connect {
+        protocol: (String, u8),
+        clean_session: bool,
+        keep_alive: u16,
+        client_id: String,
+        username: Option<String>,
+        password: Option<Bytes>,
+    },
+    Publish {
+        qos: u8,
+        topic: String,
+        packet_id: Option<u16>,
+        payload: Bytes,
+    },
+    PubRel {
+        packet_id: u16,
+    },
+    Subscribe {
+        packet_id: u16,
+        filters: usize,
+    },
+    Unsubscribe {
+        packet_id: u16,
+    },
+    PingReq,
+    Disconnect,
+}
+
+/// A packet sent to a client.
+#[derive(Debug)]
+pub enum Reply {
+    ConnAck { return_code: u8 },
+    PubAck { packet_id: u16 },
+    PubRec { packet_id: u16 },
+    PubComp { packet_id: u16 },
+    SubAck { packet_id: u16, filters: usize },
+    UnsubAck { packet_id: u16 },
+    PingResp,
+}
+
+/// The return codes of a `CONNACK` packet.
+pub const CONNECTION_ACCEPTED: u8 = 0x00;
+pub const UNACCEPTABLE_PROTOCOL: u8 = 0x01;
+pub const IDENTIFIER_REJECTED: u8 = 0x02;
+pub const NOT_AUTHORIZED: u8 = 0x05;
+
+/// The return code of a `SUBACK` packet refusing a subscription,
+/// the messages are only received from the clients.
+const SUBSCRIPTION_FAILURE: u8 = 0x80;
+
+#[derive(Debug)]
+pub enum PacketError {
+    IoError(io::Error),
+    TooLarge(usize),
+    Malformed(&'static str),
+}
+
+impl fmt::Display for PacketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PacketError::IoError(e) => write!(f, "io error; {}", e),
+            PacketError::TooLarge(size) => write!(f, "packet of {} bytes is too large", size),
+            PacketError::Malformed(reason) => write!(f, "malformed packet; {}", reason),
+        }
+    }
+}
+
+impl From<io::Error> for PacketError {
+    fn from(error: io::Error) -> PacketError {
+        PacketError::IoError(error)
+    }
+}
+
+/// Reads the fields of the body of a packet.
+struct Reader {
+    bytes: Bytes,
+}
+
+impl Reader {
+    fn u8(&mut self) -> Result<u8, PacketError> {
+        if self.bytes.is_empty() {
+            return Err(PacketError::Malformed("unexpected end of packet"));
+        }
+        Ok(self.bytes.split_to(1)[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, PacketError> {
+        Ok((u16::from(self.u8()?) << 8) | u16::from(self.u8()?))
+    }
+
+    fn binary(&mut self) -> Result<Bytes, PacketError> {
+        let len = self.u16()? as usize;
+        if self.bytes.len() < len {
+            return Err(PacketError::Malformed("unexpected end of packet"));
+        }
+        Ok(self.bytes.split_to(len))
+    }
+
+    fn string(&mut self) -> Result<String, PacketError> {
+        let binary = self.binary()?;
+        match str::from_utf8(&binary) {
+            Ok(string) => Ok(string.to_owned()),
+            Err(_) => Err(PacketError::Malformed("invalid UTF-8 string")),
+        }
+    }
+
+    fn rest(&mut self) -> Bytes {
+        self.bytes.split_off(0)
+    }
+}
+
+/// Returns the length of the fixed header and the remaining length it announces,
+/// encoded on up to four bytes of seven bits, if the header has been received.
+fn fixed_header(buf: &[u8]) -> Result<Option<(usize, usize)>, PacketError> {
+    let mut remaining = 0;
+    for (i, byte) in buf.iter().skip(1).take(4).enumerate() {
+        remaining |= (*byte as usize & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(Some((i + 2, remaining)));
+        }
+    }
+
+    if buf.len() > 4 {
+        return Err(PacketError::Malformed("invalid remaining length"));
+    }
+
+    Ok(None)
+}
+
+fn decode_connect(body: &mut Reader) -> Result<Packet, PacketError> {
+    let protocol = (body.string()?, body.u8()?);
+    let flags = body.u8()?;
+    if flags & 0x01 != 0 {
+        return Err(PacketError::Malformed("reserved connect flag is set"));
+    }
+    let keep_alive = body.u16()?;
+    let client_id = body.string()?;
+
+    // the wills are not published by this adapter
+    if flags & 0x04 != 0 {
+        body.string()?;
+        body.binary()?;
+    }
+    let username = if flags & 0x80 != 0 {
+        Some(body.string()?)
+    } else {
+        None
+    };
+    let password = if flags & 0x40 != 0 {
+        Some(body.binary()?)
+    } else {
+        None
+    };
+
+    Ok(Packet::Connect {
+        protocol,
+        clean_session: flags & 0x02 != 0,
+        keep_alive,
+        client_id,
+        username,
+        password,
+    })
+}
+
+fn decode_publish(flags: u8, body: &mut Reader) -> Result<Packet, PacketError> {
+    let qos = (flags >> 1) & 0x03;
+    if qos == 3 {
+        return Err(PacketError::Malformed("invalid QoS level"));
+    }
+
+    let topic = body.string()?;
+    if topic.is_empty() || topic.contains(|c| c == '+' || c == '#') {
+        return Err(PacketError::Malformed("invalid topic name"));
+    }
+    let packet_id = if qos > 0 { Some(body.u16()?) } else { None };
+
+    Ok(Packet::Publish {
+        qos,
+        topic,
+        packet_id,
+        payload: body.rest(),
+    })
+}
+
+/// The MQTT codec of the adapter, it decodes the packets of the clients
+/// and rejects the ones larger than `max_packet_size` bytes.
+#[derive(Debug, Copy, Clone)]
+pub struct MqttCodec {
+    max_packet_size: usize,
+}
+
+impl MqttCodec {
+    pub fn new(max_packet_size: usize) -> MqttCodec {
+        MqttCodec { max_packet_size }
+    }
+}
+
+impl Decoder for MqttCodec {
+    type Item = Packet;
+    type Error = PacketError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let (header_len, remaining) = match fixed_header(buf)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        if remaining > self.max_packet_size {
+            return Err(PacketError::TooLarge(remaining));
+        }
+
+        if buf.len() < header_len + remaining {
+            buf.reserve(header_len + remaining - buf.len());
+            return Ok(None);
+        }
+
+        let mut packet = buf.split_to(header_len + remaining).freeze();
+        let (kind, flags) = (packet[0] >> 4, packet[0] & 0x0f);
+        let mut body = Reader {
+            bytes: packet.split_off(header_len),
+        };
+
+        let packet = match (kind, flags) {
+            (1, 0) => decode_connect(&mut body)?,
+            (3, flags) => decode_publish(flags, &mut body)?,
+            (6, 2) => Packet::PubRel {
+                packet_id: body.u16()?,
+            },
+            (8, 2) => {
+                let packet_id = body.u16()?;
+                let mut filters = 0;
+                while !body.bytes.is_empty() {
+                    body.string()?;
+                    body.u8()?;
+                    filters += 1;
+                }
+                if filters == 0 {
+                    return Err(PacketError::Malformed("subscribe without topic filter"));
+                }
+                Packet::Subscribe { packet_id, filters }
+            }
+            (10, 2) => Packet::Unsubscribe {
+                packet_id: body.u16()?,
+            },
+            (12, 0) => Packet::PingReq,
+            (14, 0) => Packet::Disconnect,
+            _otherwise => return Err(PacketError::Malformed("unexpected packet type")),
+        };
+
+        Ok(Some(packet))
+    }
+}
+
+impl Encoder for MqttCodec {
+    type Item = Reply;
+    type Error = PacketError;
+
+    fn encode(&mut self, msg: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        let (kind, packet_id) = match msg {
+            Reply::ConnAck { return_code } => {
+                // the sessions are never kept by this adapter
+                buf.reserve(4);
+                buf.put_slice(&[0x20, 2, 0, return_code]);
+                return Ok(());
+            }
+            Reply::PubAck { packet_id } => (0x40, packet_id),
+            Reply::PubRec { packet_id } => (0x50, packet_id),
+            Reply::PubComp { packet_id } => (0x70, packet_id),
+            Reply::UnsubAck { packet_id } => (0xb0, packet_id),
+            Reply::SubAck { packet_id, filters } => {
+                let mut remaining = 2 + filters;
+                let mut header = vec![0x90];
+                loop {
+                    let byte = (remaining & 0x7f) as u8;
+                    remaining >>= 7;
+                    if remaining == 0 {
+                        header.push(byte);
+                        break;
+                    }
+                    header.push(byte | 0x80);
+                }
+
+                buf.reserve(header.len() + 2 + filters);
+                buf.put_slice(&header);
+                buf.put_u16_be(packet_id);
+                buf.put_slice(&vec![SUBSCRIPTION_FAILURE; filters]);
+                return Ok(());
+            }
+            Reply::PingResp => {
+                buf.reserve(2);
+                buf.put_slice(&[0xd0, 0]);
+                return Ok(());
+            }
+        };
+
+        buf.reserve(4);
+        buf.put_slice(&[kind, 2]);
+        buf.put_u16_be(packet_id);
+        Ok(())
+    }
+}
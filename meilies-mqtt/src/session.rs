@@ -0,0 +1,266 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::{self, Either};
+use futures::{Future, Sink, Stream};
+use log::{info, warn};
+use meilies::stream::{EventData, EventHeaders, EventName};
+use ring::constant_time::verify_slices_are_equal;
+use tokio::codec::Framed;
+use tokio::net::TcpStream;
+use tokio::timer::{timeout, Timeout};
+
+use crate::appender::Appender;
+use crate::mapping::Mapping;
+use crate::packet::{MqttCodec, Packet, PacketError, Reply};
+use crate::packet::{CONNECTION_ACCEPTED, IDENTIFIER_REJECTED, NOT_AUTHORIZED};
+use crate::packet::{MQTT_3_1, MQTT_3_1_1, UNACCEPTABLE_PROTOCOL};
+
+/// The headers of the appended events describing the message they come from.
+const TOPIC_HEADER: &str = "mqtt-topic";
+const CLIENT_ID_HEADER: &str = "mqtt-client-id";
+const USERNAME_HEADER: &str = "mqtt-username";
+
+/// The time given to a client to send its connect packet.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The settings shared by the sessions of the adapter.
+#[derive(Clone)]
+pub struct Adapter {
+    pub mappings: Arc<Vec<Mapping>>,
+    pub requirepass: Option<String>,
+    pub max_packet_size: usize,
+    pub appender: Appender,
+}
+
+impl Adapter {
+    /// Returns the return code of the connect packet and the session of the client.
+    fn connect(&self, packet: Option<Packet>) -> Result<(u8, Session), String> {
+        let (protocol, clean_session, keep_alive, client_id, username, password) = match packet {
+            Some(Packet::Connect {
+                protocol,
+                clean_session,
+                keep_alive,
+                client_id,
+                username,
+                password,
+            }) => (
+                protocol,
+                clean_session,
+                keep_alive,
+                client_id,
+                username,
+                password,
+            ),
+            Some(_) => return Err(String::from("expected a connect packet")),
+            None => return Err(String::from("connection closed before connecting")),
+        };
+
+        let protocol = (protocol.0.as_str(), protocol.1);
+        let authorized = match (&self.requirepass, &password) {
+            (Some(requirepass), Some(password)) => {
+                // compared in constant time to not leak the password through timings
+                verify_slices_are_equal(requirepass.as_bytes(), password).is_ok()
+            }
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        // the sessions are not kept, the clients asking to resume one are refused
+        let return_code = if protocol != MQTT_3_1_1 && protocol != MQTT_3_1 {
+            UNACCEPTABLE_PROTOCOL
+        } else if client_id.is_empty() && !clean_session {
+            IDENTIFIER_REJECTED
+        } else if !authorized {
+            NOT_AUTHORIZED
+        } else {
+            CONNECTION_ACCEPTED
+        };
+
+        let session = Session {
+            client_id,
+            username,
+            keep_alive,
+            mappings: self.mappings.clone(),
+            appender: self.appender.clone(),
+            unreleased: HashSet::new(),
+        };
+
+        Ok((return_code, session))
+    }
+}
+
+/// The state of a connected client.
+struct Session {
+    client_id: String,
+    username: Option<String>,
+    keep_alive: u16,
+    mappings: Arc<Vec<Mapping>>,
+    appender: Appender,
+    /// The identifiers of the QoS 2 messages appended but not released by the client yet.
+    unreleased: HashSet<u16>,
+}
+
+type Handled = Box<dyn Future<Item = (Session, Option<Reply>), Error = String> + Send>;
+
+impl Session {
+    /// Returns the reply acknowledging a message, once it has been appended.
+    fn acknowledge(&mut self, qos: u8, packet_id: Option<u16>) -> Option<Reply> {
+        match (qos, packet_id) {
+            (1, Some(packet_id)) => Some(Reply::PubAck { packet_id }),
+            (2, Some(packet_id)) => {
+                self.unreleased.insert(packet_id);
+                Some(Reply::PubRec { packet_id })
+            }
+            _otherwise => None,
+        }
+    }
+
+    fn handle(mut self, packet: Packet) -> Handled {
+        let (qos, topic, packet_id, payload) = match packet {
+            Packet::Publish {
+                qos,
+                topic,
+                packet_id,
+                payload,
+            } => (qos, topic, packet_id, payload),
+            Packet::PubRel { packet_id } => {
+                self.unreleased.remove(&packet_id);
+                return Box::new(future::ok((self, Some(Reply::PubComp { packet_id }))));
+            }
+            Packet::Subscribe { packet_id, filters } => {
+                warn!("client {:?} can not subscribe to topics", self.client_id);
+                let reply = Reply::SubAck { packet_id, filters };
+                return Box::new(future::ok((self, Some(reply))));
+            }
+            Packet::Unsubscribe { packet_id } => {
+                let reply = Reply::UnsubAck { packet_id };
+                return Box::new(future::ok((self, Some(reply))));
+            }
+            Packet::PingReq => return Box::new(future::ok((self, Some(Reply::PingResp)))),
+            Packet::Connect { .. } => {
+                return Box::new(future::err(String::from("unexpected connect packet")))
+            }
+            Packet::Disconnect => return Box::new(future::ok((self, None))),
+        };
+
+        // a QoS 2 message sent again before being released is only appended once
+        if let (2, Some(packet_id)) = (qos, packet_id) {
+            if self.unreleased.contains(&packet_id) {
+                return Box::new(future::ok((self, Some(Reply::PubRec { packet_id }))));
+            }
+        }
+
+        let stream = match self.mappings.iter().find(|m| m.matches(&topic)) {
+            Some(mapping) => mapping.stream.clone(),
+            None => {
+                warn!(
+                    "no stream is mapped to the topic {}, the message is dropped",
+                    topic
+                );
+                let reply = self.acknowledge(qos, packet_id);
+                return Box::new(future::ok((self, reply)));
+            }
+        };
+
+        let mut headers = EventHeaders::new();
+        headers.insert(TOPIC_HEADER.to_owned(), topic.clone());
+        headers.insert(CLIENT_ID_HEADER.to_owned(), self.client_id.clone());
+        if let Some(username) = &self.username {
+            headers.insert(USERNAME_HEADER.to_owned(), username.clone());
+        }
+
+        // the topic names are never empty
+        let event_name = EventName::new(topic.clone()).unwrap();
+        let append = self
+            .appender
+            .append(stream, event_name, EventData(payload), headers);
+
+        Box::new(append.then(move |result| match result {
+            Ok(_) => {
+                let reply = self.acknowledge(qos, packet_id);
+                Ok((self, reply))
+            }
+            // the message is not acknowledged, the client sends it again once reconnected
+            Err(()) if qos > 0 => Err(format!("error appending a message of {}", topic)),
+            Err(()) => {
+                warn!("a message of {} has been lost", topic);
+                Ok((self, None))
+            }
+        }))
+    }
+}
+
+fn timeout_error(error: timeout::Error<PacketError>) -> String {
+    match error.into_inner() {
+        Some(error) => error.to_string(),
+        None => String::from("timed out"),
+    }
+}
+
+/// Runs the session of a client until it disconnects, it can only publish messages,
+/// the ones published on the mapped topics are appended to their streams.
+pub fn serve(adapter: Adapter, socket: TcpStream) -> impl Future<Item = (), Error = ()> {
+    let addr = match socket.peer_addr() {
+        Ok(addr) => addr.to_string(),
+        Err(_) => String::from("unknown address"),
+    };
+    let connected_addr = addr.clone();
+
+    let framed = Framed::new(socket, MqttCodec::new(adapter.max_packet_size));
+    let (sink, packets) = framed.split();
+
+    let connect = packets.into_future().map_err(|(e, _)| e);
+    Timeout::new(connect, CONNECT_TIMEOUT)
+        .map_err(timeout_error)
+        .and_then(move |(packet, packets)| {
+            let (return_code, session) = adapter.connect(packet)?;
+            Ok((return_code, session, packets))
+        })
+        .and_then(move |(return_code, session, packets)| {
+            sink.send(Reply::ConnAck { return_code })
+                .map_err(|e| e.to_string())
+                .and_then(move |sink| {
+                    if return_code != CONNECTION_ACCEPTED {
+                        let error = format!("connection refused with the code {}", return_code);
+                        return Either::A(future::err(error));
+                    }
+                    info!("{:?} connected from {}", session.client_id, connected_addr);
+
+                    // the client is disconnected after one and a half keep alive without packet
+                    let packets = match session.keep_alive {
+                        0 => Either::A(packets.map_err(|e| e.to_string())),
+                        keep_alive => {
+                            let keep_alive = Duration::from_millis(u64::from(keep_alive) * 1500);
+                            Either::B(Timeout::new(packets, keep_alive).map_err(timeout_error))
+                        }
+                    };
+
+                    let handling = packets
+                        .take_while(|packet| Ok(!matches!(packet, Packet::Disconnect)))
+                        .fold((session, sink), |(session, sink), packet| {
+                            session
+                                .handle(packet)
+                                .and_then(|(session, reply)| match reply {
+                                    Some(reply) => Either::A(
+                                        sink.send(reply)
+                                            .map_err(|e| e.to_string())
+                                            .map(|sink| (session, sink)),
+                                    ),
+                                    None => Either::B(future::ok((session, sink))),
+                                })
+                        })
+                        .map(|_| ());
+
+                    Either::B(handling)
+                })
+        })
+        .then(move |result| {
+            match result {
+                Ok(()) => info!("{} disconnected", addr),
+                Err(e) => warn!("{} disconnected; {}", addr, e),
+            }
+            Ok(())
+        })
+}
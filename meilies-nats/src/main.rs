@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::thread;
+
+use futures::sync::mpsc;
+use futures::{future, Future, Sink, Stream};
+use log::{error, info, warn};
+use meilies::reqresp::Response;
+use meilies::stream::{EventData, EventHeaders, EventName, Stream as EsStream, StreamName};
+use meilies_client::compat::PairedConnection;
+use meilies_client::ConnectBuilder;
+use structopt::StructOpt;
+
+use self::routes::Route;
+
+mod routes;
+
+/// The header of the ingested events containing the subject of their message.
+const SUBJECT_HEADER: &str = "nats-subject";
+
+/// The number of messages received from NATS waiting to be appended,
+/// the subscriptions stop reading their messages when it is reached.
+const INGEST_BUFFER: usize = 100;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "meilies-nats",
+    about = "A bridge between MeiliES streams and NATS subjects.",
+    author
+)]
+struct Opt {
+    /// MeiliES server address (i.e. localhost:6480).
+    #[structopt(long = "server", default_value = "127.0.0.1:6480")]
+    server: String,
+
+    /// User to authenticate as, the server must use an ACL.
+    #[structopt(short = "u", long = "user")]
+    user: Option<String>,
+
+    /// Password used to authenticate with the server.
+    #[structopt(short = "a", long = "password")]
+    password: Option<String>,
+
+    /// NATS server URL (i.e. nats://localhost:4222).
+    #[structopt(long = "nats-url", default_value = "nats://127.0.0.1:4222")]
+    nats_url: String,
+
+    /// Queue group joined by the subscriptions of the `ingest` routes, each message
+    /// is then appended by a single bridge of the group.
+    #[structopt(long = "queue-group")]
+    queue_group: Option<String>,
+
+    /// Path of the file describing the routes between the streams and the subjects.
+    #[structopt(long = "routes", parse(from_os_str))]
+    routes: PathBuf,
+}
+
+/// A message received on the subject of an `ingest` route.
+struct Ingested {
+    stream: StreamName,
+    event_name: EventName,
+    message: nats::Message,
+}
+
+type Bridge = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+/// Appends the message to its stream, the requests are answered with the number of the event.
+fn append(
+    connection: PairedConnection,
+    ingested: Ingested,
+) -> impl Future<Item = PairedConnection, Error = ()> {
+    let Ingested {
+        stream,
+        event_name,
+        message,
+    } = ingested;
+
+    let mut headers = EventHeaders::new();
+    headers.insert(SUBJECT_HEADER.to_owned(), message.subject.clone());
+    let event_data = EventData(message.data.clone().into());
+
+    connection
+        .publish_with_headers(stream.clone(), event_name, event_data, headers)
+        .then(move |result| match result {
+            Ok((number, connection)) => {
+                if message.reply.is_some() {
+                    if let Err(e) = message.respond(number.0.to_string()) {
+                        warn!("error answering a request of {}; {}", message.subject, e);
+                    }
+                }
+                Ok(connection)
+            }
+            Err(e) => {
+                error!(
+                    "error appending a message of {} to {:?}; {}",
+                    message.subject, stream, e
+                );
+                Err(())
+            }
+        })
+}
+
+/// Subscribes to the subjects and appends their messages to the streams, in the order
+/// they are received, until an event can not be appended.
+fn ingest(
+    builder: ConnectBuilder,
+    nats_client: &nats::Connection,
+    queue_group: Option<&str>,
+    routes: Vec<(String, StreamName, Option<EventName>)>,
+) -> Result<Bridge, String> {
+    if routes.is_empty() {
+        return Ok(Box::new(future::empty()));
+    }
+
+    let (sender, receiver) = mpsc::channel(INGEST_BUFFER);
+    for (subject, stream, event_name) in routes {
+        let subscription = match queue_group {
+            Some(queue_group) => nats_client.queue_subscribe(&subject, queue_group),
+            None => nats_client.subscribe(&subject),
+        };
+        let subscription = subscription
+            .map_err(|e| format!("error subscribing to the subject {}; {}", subject, e))?;
+        info!("ingesting {} into {:?}", subject, stream);
+
+        let mut sender = sender.clone();
+        thread::Builder::new()
+            .name(String::from("nats-ingest"))
+            .spawn(move || {
+                for message in subscription.messages() {
+                    let event_name = match &event_name {
+                        Some(event_name) => event_name.clone(),
+                        // the subject of a message is never empty
+                        None => EventName::new(message.subject.clone()).unwrap(),
+                    };
+                    let ingested = Ingested {
+                        stream: stream.clone(),
+                        event_name,
+                        message,
+                    };
+                    sender = match sender.send(ingested).wait() {
+                        Ok(sender) => sender,
+                        Err(_) => return,
+                    };
+                }
+            })
+            .map_err(|e| format!("error spawning an ingest thread; {}", e))?;
+    }
+
+    let ingesting = builder
+        .paired()
+        .map_err(|e| error!("{}", e))
+        .and_then(|connection| receiver.fold(connection, append))
+        .map(|_| ());
+
+    Ok(Box::new(ingesting))
+}
+
+/// Subscribes to the streams and publishes the data of their events onto the subjects.
+fn publish(
+    builder: ConnectBuilder,
+    nats_client: nats::Connection,
+    routes: Vec<(EsStream, String)>,
+) -> Bridge {
+    if routes.is_empty() {
+        return Box::new(future::empty());
+    }
+
+    let publishing =
+        builder
+            .sub()
+            .map_err(|e| error!("{}", e))
+            .and_then(move |(mut ctrl, msgs)| {
+                let mut subjects: HashMap<StreamName, Vec<String>> = HashMap::new();
+                for (stream, subject) in routes {
+                    info!("publishing {} onto {}", stream, subject);
+                    subjects
+                        .entry(stream.name.clone())
+                        .or_default()
+                        .push(subject);
+                    ctrl.subscribe_to(stream);
+                }
+
+                msgs.map_err(|e| error!("{}", e))
+                    .for_each(move |msg| match msg {
+                        Ok(Response::Event {
+                            stream, event_data, ..
+                        }) => {
+                            for subject in subjects.get(&stream).into_iter().flatten() {
+                                if let Err(e) = nats_client.publish(subject, &event_data.0[..]) {
+                                    error!("error publishing onto {}; {}", subject, e);
+                                    return Err(());
+                                }
+                            }
+                            Ok(())
+                        }
+                        Ok(response) => {
+                            info!("{:?}", response);
+                            Ok(())
+                        }
+                        Err(error) => {
+                            error!("{}", error);
+                            Err(())
+                        }
+                    })
+            });
+
+    Box::new(publishing)
+}
+
+fn main() {
+    let _ = stderrlog::new().verbosity(2).init();
+
+    let opt = Opt::from_args();
+
+    let addr = match opt
+        .server
+        .to_socket_addrs()
+        .map(|mut addrs| addrs.find(|a| a.is_ipv4()))
+    {
+        Ok(Some(addr)) => addr,
+        Ok(None) => return error!("impossible to dns resolve addr; {:?}", opt.server),
+        Err(e) => return error!("error parsing addr; {}", e),
+    };
+
+    let mut builder = ConnectBuilder::new(addr);
+    if let Some(user) = opt.user {
+        builder = builder.username(user);
+    }
+    if let Some(password) = opt.password {
+        builder = builder.password(password);
+    }
+
+    let routes = match routes::from_path(&opt.routes) {
+        Ok(routes) => routes,
+        Err(e) => return error!("error reading the routes {:?}; {}", opt.routes, e),
+    };
+
+    let mut publish_routes = Vec::new();
+    let mut ingest_routes = Vec::new();
+    for route in routes {
+        match route {
+            Route::Publish { stream, subject } => publish_routes.push((stream, subject)),
+            Route::Ingest {
+                subject,
+                stream,
+                event_name,
+            } => ingest_routes.push((subject, stream, event_name)),
+        }
+    }
+
+    if publish_routes.is_empty() && ingest_routes.is_empty() {
+        return error!(
+            "the routes file {:?} does not contain any route",
+            opt.routes
+        );
+    }
+
+    let nats_client = match nats::connect(&opt.nats_url) {
+        Ok(nats_client) => nats_client,
+        Err(e) => return error!("error connecting to NATS; {}", e),
+    };
+
+    let queue_group = opt.queue_group.as_deref();
+    let ingesting = match ingest(builder.clone(), &nats_client, queue_group, ingest_routes) {
+        Ok(ingesting) => ingesting,
+        Err(e) => return error!("{}", e),
+    };
+    let publishing = publish(builder, nats_client, publish_routes);
+
+    // the bridge stops as soon as one of the directions stops
+    let fut = publishing.select(ingesting).map_err(|_| ()).and_then(|_| {
+        error!("Connection closed by the server");
+        Err(())
+    });
+
+    tokio::run(fut);
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+use std::{fmt, fs, io};
+
+use meilies::stream::{EventName, Stream as EsStream, StreamName};
+
+/// A route between a MeiliES stream and a NATS subject.
+#[derive(Debug)]
+pub enum Route {
+    /// Publishes the data of the events of a stream onto a subject.
+    Publish { stream: EsStream, subject: String },
+    /// Appends the messages received on a subject, which can contain wildcards, to a stream,
+    /// the events are named after the subject of the messages if no name is given.
+    Ingest {
+        subject: String,
+        stream: StreamName,
+        event_name: Option<EventName>,
+    },
+}
+
+#[derive(Debug)]
+pub enum RoutesError {
+    IoError(io::Error),
+    InvalidLine(usize),
+    UnknownDirection(usize, String),
+}
+
+impl fmt::Display for RoutesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RoutesError::IoError(e) => write!(f, "io error; {}", e),
+            RoutesError::InvalidLine(line) => write!(f, "invalid route at line {}", line),
+            RoutesError::UnknownDirection(line, direction) => {
+                write!(f, "unknown direction {:?} at line {}", direction, line)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for RoutesError {
+    fn from(error: io::Error) -> RoutesError {
+        RoutesError::IoError(error)
+    }
+}
+
+/// Read the routes of a routes file.
+///
+/// A routes file contains one route per line, `publish <stream> <subject>` publishes the
+/// events of a stream onto a subject and `ingest <subject> <stream> [event-name]` appends
+/// the messages of a subject to a stream, e.g. `publish orders orders.events` and
+/// `ingest sensors.> sensors`. The stream of a `publish` route can be followed by the
+/// number of the first event to publish, e.g. `orders:0`, only the events published from
+/// now on are published otherwise. Empty lines and lines starting with a `#` are ignored.
+pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Vec<Route>, RoutesError> {
+    let content = fs::read_to_string(path)?;
+    let mut routes = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let number = i + 1;
+        let fields: Vec<_> = line.split_whitespace().collect();
+        let route = match fields.as_slice() {
+            ["publish", stream, subject] => Route::Publish {
+                stream: stream
+                    .parse()
+                    .map_err(|_| RoutesError::InvalidLine(number))?,
+                subject: subject.to_string(),
+            },
+            ["ingest", subject, stream] | ["ingest", subject, stream, _] => {
+                let event_name = match fields.get(3) {
+                    Some(name) => Some(name.parse().map_err(|_| RoutesError::InvalidLine(number))?),
+                    None => None,
+                };
+
+                Route::Ingest {
+                    subject: subject.to_string(),
+                    stream: stream
+                        .parse()
+                        .map_err(|_| RoutesError::InvalidLine(number))?,
+                    event_name,
+                }
+            }
+            ["publish", ..] | ["ingest", ..] => return Err(RoutesError::InvalidLine(number)),
+            [direction, ..] => {
+                return Err(RoutesError::UnknownDirection(number, direction.to_string()))
+            }
+            [] => continue,
+        };
+
+        routes.push(route);
+    }
+
+    Ok(routes)
+}
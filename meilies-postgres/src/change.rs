@@ -0,0 +1,115 @@
+use std::fmt;
+
+use meilies::stream::{EventData, EventHeaders, EventName, StreamName};
+use serde_json::Value;
+
+/// The headers describing the change an event has been appended from.
+const LSN_HEADER: &str = "pg-lsn";
+const XID_HEADER: &str = "pg-xid";
+const SCHEMA_HEADER: &str = "pg-schema";
+const TABLE_HEADER: &str = "pg-table";
+
+/// The name of the events appended for the logical messages without an event name.
+const MESSAGE_EVENT_NAME: &str = "Message";
+
+/// A record decoded by the wal2json plugin with the format version 2.
+pub enum Record {
+    Begin,
+    /// The end of a transaction, the slot can be advanced up to it.
+    Commit,
+    Event {
+        stream: StreamName,
+        event_name: EventName,
+        event_data: EventData,
+        headers: EventHeaders,
+    },
+}
+
+#[derive(Debug)]
+pub enum RecordError {
+    InvalidJson(serde_json::Error),
+    MissingField(&'static str),
+    UnknownAction(String),
+    InvalidStreamName(String),
+    InvalidEventName(String),
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordError::InvalidJson(e) => write!(f, "invalid JSON; {}", e),
+            RecordError::MissingField(field) => write!(f, "missing field {:?}", field),
+            RecordError::UnknownAction(action) => write!(f, "unknown action {:?}", action),
+            RecordError::InvalidStreamName(name) => write!(f, "invalid stream name {:?}", name),
+            RecordError::InvalidEventName(prefix) => {
+                write!(f, "invalid event name in the prefix {:?}", prefix)
+            }
+        }
+    }
+}
+
+fn field<'a>(record: &'a Value, name: &'static str) -> Result<&'a str, RecordError> {
+    record[name].as_str().ok_or(RecordError::MissingField(name))
+}
+
+/// Decodes a record of a slot, the row changes are appended to the stream of their table,
+/// named `schema.table` after the given prefix, as `RowInserted`, `RowUpdated`, `RowDeleted`
+/// and `TableTruncated` events containing the JSON of the change.
+///
+/// The logical messages, emitted with `pg_logical_emit_message`, are appended to the stream
+/// named after their prefix, which can be followed by a colon and the name of the event,
+/// e.g. `orders:OrderPlaced`, and contain the content of the message.
+pub fn decode(lsn: &str, xid: &str, data: &str, prefix: &str) -> Result<Record, RecordError> {
+    let record: Value = serde_json::from_str(data).map_err(RecordError::InvalidJson)?;
+
+    let mut headers = EventHeaders::new();
+    headers.insert(LSN_HEADER.to_owned(), lsn.to_owned());
+    headers.insert(XID_HEADER.to_owned(), xid.to_owned());
+
+    let event_name = match field(&record, "action")? {
+        "B" => return Ok(Record::Begin),
+        "C" => return Ok(Record::Commit),
+        "I" => "RowInserted",
+        "U" => "RowUpdated",
+        "D" => "RowDeleted",
+        "T" => "TableTruncated",
+        "M" => {
+            let prefix = field(&record, "prefix")?;
+            let (stream, event_name) = match prefix.find(':') {
+                Some(index) => (&prefix[..index], &prefix[index + 1..]),
+                None => (prefix, MESSAGE_EVENT_NAME),
+            };
+            let event_name = EventName::new(event_name.to_owned())
+                .map_err(|_| RecordError::InvalidEventName(prefix.to_owned()))?;
+
+            let content = field(&record, "content")?;
+            return Ok(Record::Event {
+                stream: stream_name(stream.to_owned())?,
+                event_name,
+                event_data: EventData(content.as_bytes().to_vec().into()),
+                headers,
+            });
+        }
+        action => return Err(RecordError::UnknownAction(action.to_owned())),
+    };
+
+    let schema = field(&record, "schema")?;
+    let table = field(&record, "table")?;
+    headers.insert(SCHEMA_HEADER.to_owned(), schema.to_owned());
+    headers.insert(TABLE_HEADER.to_owned(), table.to_owned());
+
+    Ok(Record::Event {
+        stream: stream_name(format!("{}{}.{}", prefix, schema, table))?,
+        event_name: EventName::new(event_name.to_owned()).unwrap(),
+        event_data: EventData(data.as_bytes().to_vec().into()),
+        headers,
+    })
+}
+
+fn stream_name(name: String) -> Result<StreamName, RecordError> {
+    // the streams starting with a dollar are reserved to the server
+    if name.starts_with('$') {
+        return Err(RecordError::InvalidStreamName(name));
+    }
+    StreamName::new(name.clone()).map_err(|_| RecordError::InvalidStreamName(name))
+}
@@ -0,0 +1,223 @@
+use std::net::ToSocketAddrs;
+use std::thread;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use meilies_client::ConnectBuilder;
+use postgres::{Client, NoTls};
+use structopt::StructOpt;
+use tokio::runtime::Runtime;
+
+use self::change::Record;
+
+mod change;
+
+/// The output plugin the replication slot decodes the changes with.
+const OUTPUT_PLUGIN: &str = "wal2json";
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "meilies-postgres",
+    about = "Appends the row changes of a Postgres database to MeiliES streams.",
+    author
+)]
+struct Opt {
+    /// MeiliES server address (i.e. localhost:6480).
+    #[structopt(long = "server", default_value = "127.0.0.1:6480")]
+    server: String,
+
+    /// User to authenticate as, the server must use an ACL.
+    #[structopt(short = "u", long = "user")]
+    user: Option<String>,
+
+    /// Password used to authenticate with the server.
+    #[structopt(short = "a", long = "password")]
+    password: Option<String>,
+
+    /// Postgres connection string (i.e. "host=localhost user=postgres dbname=shop").
+    #[structopt(long = "postgres", default_value = "host=localhost user=postgres")]
+    postgres: String,
+
+    /// Logical replication slot the changes are consumed from, the changes are
+    /// kept by Postgres until they have been appended.
+    #[structopt(long = "slot", default_value = "meilies")]
+    slot: String,
+
+    /// Create the replication slot with the wal2json plugin if it does not exist.
+    #[structopt(long = "create-slot")]
+    create_slot: bool,
+
+    /// Only append the changes of these tables (i.e. public.orders,public.*),
+    /// the changes of all the tables are appended otherwise.
+    #[structopt(long = "tables")]
+    tables: Option<String>,
+
+    /// Prefix of the streams the row changes are appended to, followed by `schema.table`.
+    #[structopt(long = "stream-prefix", default_value = "")]
+    stream_prefix: String,
+
+    /// Maximum number of changes read from the slot at once,
+    /// the transactions are never split.
+    #[structopt(long = "batch-size", default_value = "1000")]
+    batch_size: i32,
+
+    /// Time to wait before reading the slot again when there was no change, in milliseconds.
+    #[structopt(long = "poll-interval", default_value = "500")]
+    poll_interval: u64,
+}
+
+/// Creates the replication slot if there is no slot with this name.
+fn create_slot(client: &mut Client, slot: &str) -> Result<(), postgres::Error> {
+    let rows = client.query(
+        "SELECT 1 FROM pg_replication_slots WHERE slot_name = $1",
+        &[&slot],
+    )?;
+
+    if rows.is_empty() {
+        client.query(
+            "SELECT pg_create_logical_replication_slot($1, $2)",
+            &[&slot, &OUTPUT_PLUGIN],
+        )?;
+        info!("replication slot {:?} created", slot);
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let _ = stderrlog::new().verbosity(2).init();
+
+    let opt = Opt::from_args();
+
+    let addr = match opt
+        .server
+        .to_socket_addrs()
+        .map(|mut addrs| addrs.find(|a| a.is_ipv4()))
+    {
+        Ok(Some(addr)) => addr,
+        Ok(None) => return error!("impossible to dns resolve addr; {:?}", opt.server),
+        Err(e) => return error!("error parsing addr; {}", e),
+    };
+
+    let mut builder = ConnectBuilder::new(addr);
+    if let Some(user) = opt.user {
+        builder = builder.username(user);
+    }
+    if let Some(password) = opt.password {
+        builder = builder.password(password);
+    }
+
+    let mut client = match Client::connect(&opt.postgres, NoTls) {
+        Ok(client) => client,
+        Err(e) => return error!("error connecting to Postgres; {}", e),
+    };
+
+    if opt.create_slot {
+        if let Err(e) = create_slot(&mut client, &opt.slot) {
+            return error!("error creating the replication slot; {}", e);
+        }
+    }
+
+    let mut options = vec![
+        String::from("format-version"),
+        String::from("2"),
+        String::from("include-transaction"),
+        String::from("true"),
+    ];
+    if let Some(tables) = opt.tables {
+        options.push(String::from("add-tables"));
+        options.push(tables);
+    }
+
+    let mut runtime = match Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => return error!("error creating the runtime; {}", e),
+    };
+    let mut connection = match runtime.block_on(builder.paired()) {
+        Ok(connection) => connection,
+        Err(e) => return error!("{}", e),
+    };
+
+    let poll_interval = Duration::from_millis(opt.poll_interval);
+
+    loop {
+        // the changes are only peeked, they are consumed once they have been appended
+        let rows = client.query(
+            "SELECT lsn::text, xid::text, data \
+             FROM pg_logical_slot_peek_changes($1, NULL, $2, VARIADIC $3::text[])",
+            &[&opt.slot, &opt.batch_size, &options],
+        );
+        let rows = match rows {
+            Ok(rows) => rows,
+            Err(e) => return error!("error reading the changes of the slot; {}", e),
+        };
+
+        if rows.is_empty() {
+            thread::sleep(poll_interval);
+            continue;
+        }
+
+        let mut last_commit = None;
+        let mut previous_lsn = String::new();
+        let mut index = 0;
+
+        for row in &rows {
+            let lsn: String = row.get(0);
+            let xid: String = row.get(1);
+            let data: String = row.get(2);
+
+            // the changes of a transaction can share the same position
+            index = if lsn == previous_lsn { index + 1 } else { 0 };
+            previous_lsn = lsn.clone();
+
+            let (stream, event_name, event_data, headers) =
+                match change::decode(&lsn, &xid, &data, &opt.stream_prefix) {
+                    Ok(Record::Begin) => continue,
+                    Ok(Record::Commit) => {
+                        last_commit = Some(lsn);
+                        continue;
+                    }
+                    Ok(Record::Event {
+                        stream,
+                        event_name,
+                        event_data,
+                        headers,
+                    }) => (stream, event_name, event_data, headers),
+                    Err(e) => {
+                        warn!("change at {} skipped; {}", lsn, e);
+                        continue;
+                    }
+                };
+
+            // a change appended again after a restart is deduplicated by the server
+            let dedup_id = format!("pg:{}:{}:{}", opt.slot, lsn, index);
+            let publish = connection.send_publish(
+                stream.clone(),
+                event_name,
+                event_data,
+                None,
+                Some(dedup_id),
+                headers,
+            );
+            connection = match runtime.block_on(publish) {
+                Ok((number, connection)) => {
+                    info!("change at {} appended to {:?} as {:?}", lsn, stream, number);
+                    connection
+                }
+                Err(e) => return error!("error appending a change to {:?}; {}", stream, e),
+            };
+        }
+
+        // the slot is advanced up to the last transaction entirely appended
+        if let Some(lsn) = last_commit {
+            let consumed = client.query(
+                "SELECT count(*) \
+                 FROM pg_logical_slot_get_changes($1, $2::text::pg_lsn, NULL, VARIADIC $3::text[])",
+                &[&opt.slot, &lsn, &options],
+            );
+            if let Err(e) = consumed {
+                return error!("error advancing the slot to {}; {}", lsn, e);
+            }
+        }
+    }
+}
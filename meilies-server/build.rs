@@ -0,0 +1,31 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc::generate();
+}
+
+/// Generates the messages and the service of `proto/service.proto`, `protoc` must be installed.
+#[cfg(feature = "grpc")]
+mod grpc {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    pub fn generate() {
+        println!("cargo:rerun-if-changed=proto/service.proto");
+
+        let out_dir = env::var("OUT_DIR").unwrap();
+        protoc_grpcio::compile_grpc_protos(&["service.proto"], &["proto"], &out_dir, None)
+            .expect("error generating the gRPC service");
+
+        // the generated files are included in modules, where inner attributes are not allowed
+        for file in &["service.rs", "service_grpc.rs"] {
+            let path = Path::new(&out_dir).join(file);
+            let code = fs::read_to_string(&path).unwrap();
+            let code: Vec<_> = code
+                .lines()
+                .filter(|line| !line.starts_with("#![") && !line.starts_with("//!"))
+                .collect();
+            fs::write(&path, code.join("\n")).unwrap();
+        }
+    }
+}
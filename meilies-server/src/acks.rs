@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Future};
+use futures::sync::oneshot;
+
+use meilies::reqresp::AckLevel;
+use meilies::stream::EventNumber;
+
+use super::commit::GroupCommit;
+
+/// A response of the connection waiting for its publication to be on disk.
+type Deferred = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+#[derive(Default)]
+struct Positions {
+    next_id: u64,
+    /// The last global event number acknowledged by each replica connection.
+    replicas: HashMap<u64, EventNumber>,
+}
+
+/// The positions acknowledged by the replicas connected to the server,
+/// the publications wait for them according to their acknowledgement level.
+#[derive(Clone)]
+pub struct ReplicaAcks {
+    positions: Arc<(Mutex<Positions>, Condvar)>,
+    timeout: Duration,
+    default_level: AckLevel,
+    /// The number of nodes of the cluster the server is part of, if any.
+    cluster_nodes: Option<usize>,
+    /// The publications are acknowledged once flushed if specified.
+    commit: Option<GroupCommit>,
+}
+
+impl ReplicaAcks {
+    /// Creates the acknowledgements, the publications wait at most `timeout` for the replicas.
+    pub fn new(timeout: Duration) -> ReplicaAcks {
+        ReplicaAcks {
+            positions: Arc::default(),
+            timeout,
+            default_level: AckLevel::default(),
+            cluster_nodes: None,
+            commit: None,
+        }
+    }
+
+    /// The publications sent to a cluster are acknowledged once a majority of its nodes
+    /// have received them, whether they are connected or not.
+    pub fn with_cluster_nodes(self, nodes: usize) -> ReplicaAcks {
+        ReplicaAcks {
+            default_level: AckLevel::Quorum,
+            cluster_nodes: Some(nodes),
+            ..self
+        }
+    }
+
+    /// The publications are acknowledged once the database has been flushed by the group commit.
+    pub fn with_group_commit(self, commit: GroupCommit) -> ReplicaAcks {
+        ReplicaAcks {
+            commit: Some(commit),
+            ..self
+        }
+    }
+
+    /// Returns the acknowledgements of a new connection, it is considered
+    /// to be a replica once it acknowledges a global event number.
+    pub fn connection(&self) -> ConnectionAcks {
+        let (positions, _) = &*self.positions;
+        let mut positions = positions.lock().unwrap();
+        let id = positions.next_id;
+        positions.next_id += 1;
+
+        ConnectionAcks {
+            acks: self.clone(),
+            id,
+            level: self.default_level,
+            deferred: None,
+        }
+    }
+
+    /// Waits until enough replicas have acknowledged the given global event number.
+    ///
+    /// Returns the number of replicas that acknowledged it and
+    /// the number of replicas required if the timeout elapsed.
+    pub fn wait(&self, level: AckLevel, global: EventNumber) -> Result<(), (usize, usize)> {
+        let (positions, condvar) = &*self.positions;
+        let deadline = Instant::now() + self.timeout;
+
+        let mut positions = positions.lock().unwrap();
+        loop {
+            let replicas = &positions.replicas;
+            let required = match level {
+                AckLevel::Local => 0,
+                AckLevel::OneReplica => 1,
+                // the majority of the primary and its replicas, the primary included
+                AckLevel::Quorum => self.cluster_nodes.unwrap_or(replicas.len() + 1) / 2,
+            };
+            let acked = replicas.values().filter(|n| **n >= global).count();
+
+            if acked >= required {
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err((acked, required));
+            }
+
+            positions = condvar.wait_timeout(positions, deadline - now).unwrap().0;
+        }
+    }
+}
+
+/// The acknowledgement level of a connection and, if the connection is
+/// a replica, the position it acknowledged. It is forgotten when dropped.
+pub struct ConnectionAcks {
+    acks: ReplicaAcks,
+    id: u64,
+    pub level: AckLevel,
+    deferred: Option<Deferred>,
+}
+
+impl ConnectionAcks {
+    /// Records that the replica has received all the events up to this global event number.
+    pub fn replica_ack(&self, global: EventNumber) {
+        let (positions, condvar) = &*self.acks.positions;
+        positions.lock().unwrap().replicas.insert(self.id, global);
+        condvar.notify_all();
+    }
+
+    /// Returns the level of a publication: the one specified, otherwise the level of the connection.
+    pub fn level(&self, requested: Option<AckLevel>) -> AckLevel {
+        requested.unwrap_or(self.level)
+    }
+
+    pub fn replica_acks(&self) -> &ReplicaAcks {
+        &self.acks
+    }
+
+    /// Returns a receiver notified once the publications done so far are on disk,
+    /// `None` if the publications are acknowledged without waiting for the flush.
+    pub fn durable(&self) -> Option<oneshot::Receiver<Result<(), String>>> {
+        self.acks.commit.as_ref().map(GroupCommit::durable)
+    }
+
+    /// Defers the handling of the next requests of the connection until
+    /// the given response is sent, the responses are sent in order.
+    pub fn defer(&mut self, response: impl Future<Item = (), Error = ()> + Send + 'static) {
+        self.deferred = Some(Box::new(response));
+    }
+
+    /// Returns the response deferred by the last request, if any.
+    pub fn take_deferred(&mut self) -> Deferred {
+        match self.deferred.take() {
+            Some(deferred) => deferred,
+            None => Box::new(future::ok(())),
+        }
+    }
+}
+
+impl Drop for ConnectionAcks {
+    fn drop(&mut self) {
+        let (positions, condvar) = &*self.acks.positions;
+        let removed = positions.lock().unwrap().replicas.remove(&self.id);
+        if removed.is_some() {
+            condvar.notify_all();
+        }
+    }
+}
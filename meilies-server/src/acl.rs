@@ -0,0 +1,582 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::{fmt, fs, io};
+
+use meilies::reqresp::{ErrorCode, Request};
+use meilies::stream::StreamName;
+use ring::constant_time::verify_slices_are_equal;
+use sha2::{Digest, Sha256};
+
+use super::audit::AUDIT_STREAM;
+
+/// The password of the users that can only be authenticated by a client certificate.
+const NO_PASSWORD: &str = "-";
+
+/// The prefix of the passwords stored as a salted hash, written `sha256:salt:hash`.
+const SHA256_PREFIX: &str = "sha256:";
+
+/// Compares the passwords in a time that does not tell how much of the password is right.
+fn password_matches(expected: &str, password: &str) -> bool {
+    verify_slices_are_equal(expected.as_bytes(), password.as_bytes()).is_ok()
+}
+
+/// Decodes a string of hexadecimal digits.
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// The password of a user as written in the ACL file.
+#[derive(Debug)]
+enum Password {
+    /// The user can only be authenticated by a client certificate.
+    None,
+    Plain(String),
+    /// The SHA-256 of the salt followed by the password.
+    Sha256 { salt: String, hash: Vec<u8> },
+}
+
+impl Password {
+    fn from_str(s: &str) -> Option<Password> {
+        if s == NO_PASSWORD {
+            return Some(Password::None);
+        }
+
+        if s.starts_with(SHA256_PREFIX) {
+            let mut parts = s[SHA256_PREFIX.len()..].splitn(2, ':');
+            let salt = parts.next()?.to_owned();
+            let hash = decode_hex(parts.next()?)?;
+            if hash.len() != Sha256::output_size() {
+                return None;
+            }
+            return Some(Password::Sha256 { salt, hash });
+        }
+
+        Some(Password::Plain(s.to_owned()))
+    }
+
+    fn matches(&self, password: &str) -> bool {
+        match self {
+            Password::None => false,
+            Password::Plain(expected) => password_matches(expected, password),
+            Password::Sha256 { salt, hash } => {
+                let mut hasher = Sha256::new();
+                hasher.input(salt.as_bytes());
+                hasher.input(password.as_bytes());
+                verify_slices_are_equal(hash, &hasher.result()).is_ok()
+            }
+        }
+    }
+}
+
+/// An operation a user can be allowed to run on streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Publish events.
+    Publish,
+    /// Subscribe to streams and read their events.
+    Subscribe,
+    /// Create, delete and trim streams.
+    Admin,
+}
+
+impl Permission {
+    fn from_str(s: &str) -> Option<Permission> {
+        match s {
+            "publish" => Some(Permission::Publish),
+            "subscribe" => Some(Permission::Subscribe),
+            "admin" => Some(Permission::Admin),
+            _otherwise => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Permission::Publish => "publish",
+            Permission::Subscribe => "subscribe",
+            Permission::Admin => "admin",
+        }
+    }
+}
+
+/// A user allowed to run some operations on the streams matching some patterns.
+#[derive(Debug)]
+pub struct User {
+    name: String,
+    password: Password,
+    permissions: Vec<Permission>,
+    patterns: Vec<String>,
+}
+
+impl User {
+    /// A user that is allowed to do anything, used when there is no ACL.
+    fn superuser() -> User {
+        User {
+            name: String::from("default"),
+            password: Password::None,
+            permissions: vec![
+                Permission::Publish,
+                Permission::Subscribe,
+                Permission::Admin,
+            ],
+            patterns: vec![String::from("*")],
+        }
+    }
+
+    fn allows(&self, permission: Permission, stream: &StreamName) -> bool {
+        // the audit stream can only be read by the administrators of all the streams
+        if stream.as_str() == AUDIT_STREAM {
+            return self.check_all(Permission::Admin).is_ok();
+        }
+        self.permissions.contains(&permission) && self.patterns.iter().any(|p| stream.matches(p))
+    }
+
+    fn check(&self, permission: Permission, stream: &StreamName) -> Result<(), String> {
+        if self.allows(permission, stream) {
+            return Ok(());
+        }
+
+        Err(ErrorCode::NoPerm.error(format_args!(
+            "user {} has no permission to {} on stream {}",
+            self.name,
+            permission.as_str(),
+            stream
+        )))
+    }
+
+    /// Checks that the user can run the operation on all the streams, i.e. on the `*` pattern.
+    fn check_all(&self, permission: Permission) -> Result<(), String> {
+        if self.permissions.contains(&permission) && self.patterns.iter().any(|p| p == "*") {
+            return Ok(());
+        }
+
+        Err(ErrorCode::NoPerm.error(format_args!(
+            "user {} has no permission to {} on all the streams",
+            self.name,
+            permission.as_str(),
+        )))
+    }
+}
+
+/// The users allowed to connect to the server.
+///
+/// An ACL file contains one user per line, described by its name, its password,
+/// the comma separated operations it can run and the comma separated patterns
+/// of the streams it can run them on, e.g. `alice s3cr3t publish,subscribe orders-*`.
+/// A password written `sha256:salt:hash` is stored as the hexadecimal SHA-256 of the
+/// salt followed by the password, the other ones are stored as is.
+/// A `-` password forbids the user to authenticate with a password, it can only be
+/// authenticated by a client certificate with its name as the subject common name.
+/// Empty lines and lines starting with a `#` are ignored.
+#[derive(Debug)]
+pub struct Acl {
+    users: Vec<Arc<User>>,
+}
+
+#[derive(Debug)]
+pub enum AclError {
+    IoError(io::Error),
+    InvalidLine(usize),
+    InvalidPassword(usize),
+    UnknownPermission(usize, String),
+}
+
+impl fmt::Display for AclError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AclError::IoError(e) => write!(f, "io error; {}", e),
+            AclError::InvalidLine(line) => write!(f, "invalid user definition at line {}", line),
+            AclError::InvalidPassword(line) => write!(f, "invalid password hash at line {}", line),
+            AclError::UnknownPermission(line, permission) => {
+                write!(f, "unknown permission {:?} at line {}", permission, line)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for AclError {
+    fn from(error: io::Error) -> AclError {
+        AclError::IoError(error)
+    }
+}
+
+impl Acl {
+    /// Read the users from an ACL file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Acl, AclError> {
+        let content = fs::read_to_string(path)?;
+        Acl::parse(&content)
+    }
+
+    fn parse(content: &str) -> Result<Acl, AclError> {
+        let mut users = Vec::new();
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let number = i + 1;
+            let fields: Vec<_> = line.split_whitespace().collect();
+            let (name, password, permissions, patterns) = match fields.as_slice() {
+                [name, password, permissions, patterns] => (name, password, permissions, patterns),
+                _otherwise => return Err(AclError::InvalidLine(number)),
+            };
+
+            let permissions = permissions
+                .split(',')
+                .map(|p| {
+                    Permission::from_str(p)
+                        .ok_or_else(|| AclError::UnknownPermission(number, p.to_owned()))
+                })
+                .collect::<Result<_, _>>()?;
+
+            let patterns = patterns.split(',').map(String::from).collect();
+            let password =
+                Password::from_str(password).ok_or(AclError::InvalidPassword(number))?;
+
+            users.push(Arc::new(User {
+                name: name.to_string(),
+                password,
+                permissions,
+                patterns,
+            }));
+        }
+
+        Ok(Acl { users })
+    }
+
+    fn authenticate(&self, name: &str, password: &str) -> Option<Arc<User>> {
+        self.users
+            .iter()
+            .find(|u| u.name == name && u.password.matches(password))
+            .cloned()
+    }
+
+    fn user(&self, name: &str) -> Option<Arc<User>> {
+        self.users.iter().find(|u| u.name == name).cloned()
+    }
+}
+
+/// The authentication state of a connection.
+#[derive(Clone)]
+pub struct Authentication {
+    requirepass: Option<String>,
+    acl: Option<Arc<Acl>>,
+    user: Option<Arc<User>>,
+}
+
+impl Authentication {
+    pub fn new(requirepass: Option<String>, acl: Option<Arc<Acl>>) -> Authentication {
+        let user = if requirepass.is_none() && acl.is_none() {
+            Some(Arc::new(User::superuser()))
+        } else {
+            None
+        };
+
+        Authentication {
+            requirepass,
+            acl,
+            user,
+        }
+    }
+
+    pub fn authenticate(&mut self, username: Option<&str>, password: &str) -> Result<(), String> {
+        let user = match (username, &self.requirepass, &self.acl) {
+            (None, None, Some(_)) => return Err(ErrorCode::Err.error("a username is required")),
+            (None, None, None) => return Err(ErrorCode::Err.error("no password is set")),
+            (None, Some(requirepass), _) if password_matches(requirepass, password) => {
+                Some(Arc::new(User::superuser()))
+            }
+            (Some(_), _, None) => return Err(ErrorCode::Err.error("no ACL is set")),
+            (Some(name), _, Some(acl)) => acl.authenticate(name, password),
+            (None, Some(_), _) => None,
+        };
+
+        match user {
+            Some(user) => {
+                self.user = Some(user);
+                Ok(())
+            }
+            None => {
+                self.user = None;
+                Err(ErrorCode::WrongPass.error("invalid username-password pair"))
+            }
+        }
+    }
+
+    /// Authenticates the connection with the common name of a verified client certificate,
+    /// it identifies the ACL user with the same name or the default user if there is no ACL.
+    pub fn authenticate_certificate(&mut self, common_name: &str) {
+        self.user = match &self.acl {
+            Some(acl) => acl.user(common_name),
+            None => Some(Arc::new(User::superuser())),
+        };
+    }
+
+    /// Returns the name of the user the connection is authenticated as.
+    pub fn user_name(&self) -> Option<&str> {
+        self.user.as_ref().map(|u| u.name.as_str())
+    }
+
+    /// Returns `true` if the connection is allowed to read the given stream.
+    pub fn can_subscribe(&self, stream: &StreamName) -> bool {
+        self.user
+            .as_ref()
+            .map_or(false, |u| u.allows(Permission::Subscribe, stream))
+    }
+
+    /// Checks that the connection is allowed to run the given request.
+    ///
+    /// Unauthenticated connections are only allowed to authenticate,
+    /// ping the server or choose the version of the protocol.
+    pub fn check(&self, request: &Request) -> Result<(), String> {
+        let user = match &self.user {
+            Some(user) => user,
+            None => match request {
+                Request::Auth { .. } | Request::Ping { .. } | Request::Hello { .. } => {
+                    return Ok(())
+                }
+                _otherwise => return Err(ErrorCode::NoAuth.error("authentication required")),
+            },
+        };
+
+        match request {
+            // the events of the category and pattern streams are filtered
+            // by the handler like the `$all` ones
+            Request::Subscribe { streams } => streams
+                .iter()
+                .filter(|s| !s.name.is_virtual())
+                .try_for_each(|s| user.check(Permission::Subscribe, &s.name)),
+            Request::LastEventNumber { stream }
+            | Request::GetRange { stream, .. }
+            | Request::SubscribeGroup { stream, .. }
+            | Request::Ack { stream, .. }
+            | Request::CommitOffset { stream, .. }
+            | Request::FetchOffset { stream, .. }
+            | Request::Pending { stream, .. }
+            | Request::Claim { stream, .. }
+            | Request::GetStreamMeta { stream }
+            | Request::StreamInfo { stream } => user.check(Permission::Subscribe, stream),
+            Request::Publish { stream, .. } | Request::PublishBatch { stream, .. } => {
+                user.check(Permission::Publish, stream)
+            }
+            Request::PublishMulti { writes } => writes
+                .iter()
+                .try_for_each(|(stream, _, _)| user.check(Permission::Publish, stream)),
+            Request::CreateStream { stream }
+            | Request::DeleteStream { stream, .. }
+            | Request::TrimStream { stream, .. }
+            | Request::SetStreamMeta { stream, .. }
+            | Request::Verify { stream } => user.check(Permission::Admin, stream),
+            Request::Backup { .. }
+            | Request::Compact
+            | Request::ReplicaOf { .. }
+            | Request::RequestVote { .. }
+            | Request::Heartbeat { .. }
+            | Request::ReadOnly { .. }
+            | Request::ClientList
+            | Request::ClientKill { .. }
+            | Request::ConfigSet { .. }
+            | Request::SlowLog { .. }
+            | Request::Latency
+            | Request::Monitor => user.check_all(Permission::Admin),
+            // a replica reads all the streams of its primary
+            Request::ReplicaAck { .. } => user.check_all(Permission::Subscribe),
+            // the streams a connection subscribes to with SubscribeAll
+            // and the listed stream names are filtered by the handler
+            Request::SubscribeAll { .. }
+            | Request::StreamNames { .. }
+            | Request::Unsubscribe { .. }
+            | Request::Flow { .. }
+            | Request::ReplicationInfo
+            | Request::Info
+            | Request::SetAckLevel { .. }
+            | Request::Ping { .. }
+            | Request::Compress { .. }
+            | Request::FrameSize { .. }
+            | Request::Hello { .. }
+            | Request::Auth { .. } => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requirepass() {
+        let mut authentication = Authentication::new(Some(String::from("s3cr3t")), None);
+        let ping = Request::Ping { payload: None };
+        let names = Request::StreamNames { pattern: None };
+        assert!(authentication.check(&ping).is_ok());
+
+        let error = authentication.check(&names).unwrap_err();
+        assert_eq!(ErrorCode::split(&error).unwrap().0, ErrorCode::NoAuth);
+
+        let error = authentication.authenticate(None, "s3cr3").unwrap_err();
+        assert_eq!(ErrorCode::split(&error).unwrap().0, ErrorCode::WrongPass);
+        let error = authentication.authenticate(None, "s3cr3t!").unwrap_err();
+        assert_eq!(ErrorCode::split(&error).unwrap().0, ErrorCode::WrongPass);
+        assert!(authentication.check(&names).is_err());
+
+        authentication.authenticate(None, "s3cr3t").unwrap();
+        assert!(authentication.check(&names).is_ok());
+    }
+
+    fn stream_name(name: &str) -> StreamName {
+        StreamName::new(name.to_owned()).unwrap()
+    }
+
+    fn sha256_password(salt: &str, password: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.input(salt.as_bytes());
+        hasher.input(password.as_bytes());
+        let hash: String = hasher.result().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}{}:{}", SHA256_PREFIX, salt, hash)
+    }
+
+    fn authenticated(acl: &Arc<Acl>, name: &str, password: &str) -> Authentication {
+        let mut authentication = Authentication::new(None, Some(acl.clone()));
+        authentication.authenticate(Some(name), password).unwrap();
+        authentication
+    }
+
+    #[test]
+    fn parse_acl() {
+        let content = format!(
+            "# the users of the tests\n\
+             \n\
+             alice s3cr3t publish,subscribe orders-*,users\n\
+             \tbob {} subscribe *\n\
+             carol - admin *\n",
+            sha256_password("pepper", "hunter2"),
+        );
+        let acl = Acl::parse(&content).unwrap();
+        assert_eq!(acl.users.len(), 3);
+
+        let alice = acl.user("alice").unwrap();
+        assert_eq!(alice.permissions, [Permission::Publish, Permission::Subscribe]);
+        assert_eq!(alice.patterns, ["orders-*", "users"]);
+        assert!(acl.user("dave").is_none());
+
+        assert!(Acl::parse("alice s3cr3t publish").is_err());
+        assert!(Acl::parse("alice s3cr3t publish * extra").is_err());
+        assert!(matches!(
+            Acl::parse("\nalice s3cr3t publish,delete *"),
+            Err(AclError::UnknownPermission(2, ref p)) if p == "delete"
+        ));
+        assert!(matches!(
+            Acl::parse("alice sha256:pepper:0123 publish *"),
+            Err(AclError::InvalidPassword(1))
+        ));
+        assert!(matches!(
+            Acl::parse("alice sha256:pepper publish *"),
+            Err(AclError::InvalidPassword(1))
+        ));
+    }
+
+    #[test]
+    fn acl_passwords() {
+        let content = format!(
+            "alice s3cr3t publish *\n\
+             bob {} subscribe *\n\
+             carol - admin *\n",
+            sha256_password("pepper", "hunter2"),
+        );
+        let acl = Acl::parse(&content).unwrap();
+
+        assert!(acl.authenticate("alice", "s3cr3t").is_some());
+        assert!(acl.authenticate("alice", "s3cr3").is_none());
+        assert!(acl.authenticate("bob", "hunter2").is_some());
+        assert!(acl.authenticate("bob", "hunter3").is_none());
+        // the hash itself is not a password
+        assert!(acl.authenticate("bob", &sha256_password("pepper", "hunter2")).is_none());
+        // the users without a password can only be authenticated by a certificate
+        assert!(acl.authenticate("carol", "-").is_none());
+        assert!(acl.authenticate("carol", "").is_none());
+        assert!(acl.authenticate("dave", "s3cr3t").is_none());
+
+        let mut authentication = Authentication::new(None, Some(Arc::new(acl)));
+        let error = authentication.authenticate(None, "s3cr3t").unwrap_err();
+        assert_eq!(ErrorCode::split(&error).unwrap().0, ErrorCode::Err);
+        let error = authentication.authenticate(Some("alice"), "wrong").unwrap_err();
+        assert_eq!(ErrorCode::split(&error).unwrap().0, ErrorCode::WrongPass);
+
+        authentication.authenticate_certificate("carol");
+        assert_eq!(authentication.user_name(), Some("carol"));
+    }
+
+    #[test]
+    fn permissions() {
+        let content = "publisher pw publish orders-*\n\
+                       reader pw subscribe orders-*,users\n\
+                       operator pw admin orders-*\n\
+                       root pw publish,subscribe,admin *\n";
+        let acl = Arc::new(Acl::parse(content).unwrap());
+
+        let publish = |stream: &str| Request::Publish {
+            stream: stream_name(stream),
+            event_name: meilies::stream::EventName::new(String::from("created")).unwrap(),
+            event_data: meilies::stream::EventData(Vec::new().into()),
+            expected_version: None,
+            dedup_id: None,
+            headers: meilies::stream::EventHeaders::new(),
+            deliver_at: None,
+            ack: None,
+        };
+        let get_range = |stream: &str| Request::GetRange {
+            stream: stream_name(stream),
+            from: None,
+            to: None,
+        };
+        let trim = |stream: &str| Request::TrimStream {
+            stream: stream_name(stream),
+            up_to: 1,
+        };
+
+        // (user, request, allowed)
+        let matrix = vec![
+            ("publisher", publish("orders-eu"), true),
+            ("publisher", publish("users"), false),
+            ("publisher", get_range("orders-eu"), false),
+            ("publisher", trim("orders-eu"), false),
+            ("reader", publish("orders-eu"), false),
+            ("reader", get_range("orders-eu"), true),
+            ("reader", get_range("users"), true),
+            ("reader", get_range("payments"), false),
+            ("reader", trim("users"), false),
+            ("operator", trim("orders-eu"), true),
+            ("operator", trim("users"), false),
+            ("operator", get_range("orders-eu"), false),
+            ("operator", Request::Compact, false),
+            ("operator", get_range("$audit"), false),
+            ("root", publish("payments"), true),
+            ("root", trim("payments"), true),
+            ("root", Request::Compact, true),
+            ("root", get_range("$audit"), true),
+        ];
+
+        for (user, request, allowed) in matrix {
+            let authentication = authenticated(&acl, user, "pw");
+            let result = authentication.check(&request);
+            assert_eq!(result.is_ok(), allowed, "{} {:?}", user, request);
+            if let Err(error) = result {
+                assert_eq!(ErrorCode::split(&error).unwrap().0, ErrorCode::NoPerm);
+            }
+        }
+
+        // every user can run the commands that do not target a stream
+        let authentication = authenticated(&acl, "publisher", "pw");
+        assert!(authentication.check(&Request::Info).is_ok());
+        assert!(!authentication.can_subscribe(&stream_name("orders-eu")));
+        let authentication = authenticated(&acl, "reader", "pw");
+        assert!(authentication.can_subscribe(&stream_name("orders-eu")));
+    }
+}
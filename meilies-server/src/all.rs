@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use sled::{Db, Event, IVec, Tree};
+use tokio::sync::mpsc;
+use tracing::info;
+
+use meilies::reqresp::Response;
+use meilies::stream::{EventNumber, RawEvent};
+use meilies::stream::{Stream as EsStream, StreamName as EsStreamName};
+
+use super::acl::Authentication;
+use super::compression;
+use super::cursor::Cursor;
+use super::{last_event_number, search_timestamp, send_response, Subscription};
+
+/// The tree that keeps the global order of the events published to all the streams,
+/// keys are the global event numbers and values the stream and the number of the event.
+pub const ALL_TREE: &str = "meilies:all";
+
+/// Encodes the position of an event in its stream: its number then the name of the stream.
+pub fn entry(stream: &EsStreamName, number: EventNumber) -> Vec<u8> {
+    let mut entry = number.to_be_bytes().to_vec();
+    entry.extend_from_slice(stream.as_str().as_bytes());
+    entry
+}
+
+fn decode_entry(entry: &[u8]) -> (EsStreamName, EventNumber) {
+    let number = EventNumber::try_from(&entry[..8]).unwrap();
+    let stream = String::from_utf8(entry[8..].to_vec()).unwrap();
+    (EsStreamName::new(stream).unwrap(), number)
+}
+
+/// Forgets the global positions of the events of a deleted stream.
+pub fn clear_stream_entries(db: &Db, stream: &EsStreamName) -> sled::Result<()> {
+    let all = db.open_tree(ALL_TREE)?;
+    for result in all.iter() {
+        let (key, entry) = result?;
+        if &entry[8..] == stream.as_str().as_bytes() {
+            all.remove(key)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the events referenced by the global log, the stream trees are opened once.
+pub struct EventReader {
+    db: Db,
+    trees: HashMap<EsStreamName, Tree>,
+}
+
+impl EventReader {
+    pub fn new(db: Db) -> EventReader {
+        EventReader {
+            db,
+            trees: HashMap::new(),
+        }
+    }
+
+    /// Returns the stream, the number and the content of the event at the given global position,
+    /// or `None` if the event has been trimmed or its stream deleted.
+    pub fn read(
+        &mut self,
+        entry: &[u8],
+    ) -> sled::Result<Option<(EsStreamName, EventNumber, IVec)>> {
+        let (stream, number) = decode_entry(entry);
+
+        if !self.trees.contains_key(&stream) {
+            let tree = self.db.open_tree(stream.as_ref())?;
+            self.trees.insert(stream.clone(), tree);
+        }
+
+        let value = self.trees[&stream].get(number.to_be_bytes())?;
+        Ok(value.map(|value| (stream, number, value)))
+    }
+}
+
+/// Returns the event response to send or `None` if the event is filtered out.
+fn event_response(
+    stream: &EsStream,
+    authentication: &Authentication,
+    reader: &mut EventReader,
+    key: &[u8],
+    entry: &[u8],
+) -> sled::Result<Option<Response>> {
+    let (name, number, value) = match reader.read(entry)? {
+        Some(event) => event,
+        None => return Ok(None),
+    };
+
+    if !stream.name.includes(&name) || !authentication.can_subscribe(&name) {
+        return Ok(None);
+    }
+
+    let raw_event = RawEvent::new(value);
+    let event_name = raw_event.name().unwrap();
+
+    if !stream.accepts(&event_name) {
+        return Ok(None);
+    }
+
+    Ok(Some(Response::Event {
+        stream: name,
+        number,
+        timestamp: raw_event.timestamp(),
+        event_name,
+        event_data: compression::decompress(raw_event.data()),
+        headers: raw_event.headers().unwrap(),
+        global_number: Some(EventNumber::try_from(key).unwrap()),
+    }))
+}
+
+/// Sends the events of the streams included in a virtual stream in the order they were published,
+/// only the events of the streams the connection is allowed to read are sent.
+pub fn send_all_events(
+    stream: EsStream,
+    db: Db,
+    mut sender: mpsc::Sender<Result<Response, String>>,
+    subscription: Arc<Subscription>,
+    authentication: Authentication,
+) -> sled::Result<()> {
+    let all = db.open_tree(ALL_TREE)?;
+    let mut reader = EventReader::new(db.clone());
+
+    let mut next_number = match (stream.range.from(), stream.range.from_time()) {
+        (Some(from), _) => EventNumber(from),
+        (None, Some(timestamp)) => {
+            let low = match all.iter().keys().next() {
+                Some(key) => EventNumber::try_from(key?.as_ref()).unwrap(),
+                None => EventNumber::zero(),
+            };
+            let high = last_event_number(&db, &EsStreamName::all())?
+                .map_or(EventNumber::zero(), EventNumber::next);
+
+            search_timestamp(low, high, timestamp, |number| {
+                let entry = match all.get(number.to_be_bytes())? {
+                    Some(entry) => entry,
+                    None => return Ok(None),
+                };
+                let (name, number) = decode_entry(&entry);
+                let value = db.open_tree(name.as_ref())?.get(number.to_be_bytes())?;
+                Ok(value.map(|value| RawEvent::new(value).timestamp()))
+            })?
+        }
+        (None, None) => match last_event_number(&db, &EsStreamName::all())? {
+            Some(number) => number.next(),
+            None => EventNumber::zero(),
+        },
+    };
+
+    // The watcher must be registered before reading the
+    // global log to be sure that no event will be missed.
+    let mut watcher = subscription.watch(&all);
+
+    let end_number = stream.range.to().map(EventNumber);
+    let reached_end = |next_number| matches!(end_number, Some(end) if next_number >= end);
+    let range_end = Response::RangeEnd {
+        stream: stream.name.clone(),
+    };
+
+    if reached_end(next_number) {
+        send_response(sender, &subscription, range_end);
+        return Ok(());
+    }
+
+    for result in Cursor::new(all.clone(), next_number, end_number) {
+        let (key, entry) = result?;
+        let number = EventNumber::try_from(key.as_ref()).unwrap();
+
+        if let Some(event) = event_response(&stream, &authentication, &mut reader, &key, &entry)? {
+            sender = match send_response(sender, &subscription, event) {
+                Some(sender) => sender,
+                None => return Ok(()),
+            };
+        }
+
+        next_number = number.next();
+        if reached_end(next_number) {
+            send_response(sender, &subscription, range_end);
+            return Ok(());
+        }
+
+        // We renew the watcher to avoid filling it with
+        // events that we will read directly from the tree.
+        watcher = subscription.watch(&all);
+    }
+
+    let caught_up = Response::CaughtUp {
+        stream: stream.name.clone(),
+    };
+    sender = match send_response(sender, &subscription, caught_up) {
+        Some(sender) => sender,
+        None => return Ok(()),
+    };
+
+    for event in watcher {
+        if subscription.is_unsubscribed() {
+            info!("subscription stopped");
+            return Ok(());
+        }
+
+        // the entries of the deleted streams are removed from the log
+        let key = match event {
+            Event::Insert { key, .. } => key,
+            Event::Remove { .. } => continue,
+        };
+
+        let number = EventNumber::try_from(key.as_ref()).unwrap();
+        if number < next_number {
+            continue;
+        }
+
+        // The notifications of the events published concurrently
+        // can be received out of order, we read the missing events from the tree.
+        let range = all.range(next_number.to_be_bytes()..=number.to_be_bytes());
+        for result in range {
+            let (key, entry) = result?;
+            let number = EventNumber::try_from(key.as_ref()).unwrap();
+
+            next_number = number.next();
+            if let Some(event) =
+                event_response(&stream, &authentication, &mut reader, &key, &entry)?
+            {
+                sender = match send_response(sender, &subscription, event) {
+                    Some(sender) => sender,
+                    None => return Ok(()),
+                };
+            }
+
+            if reached_end(next_number) {
+                send_response(sender, &subscription, range_end);
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
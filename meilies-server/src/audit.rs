@@ -0,0 +1,113 @@
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use sha2::{Digest, Sha256};
+use sled::Db;
+
+use meilies::reqresp::{ErrorCode, Request};
+use meilies::stream::{EventHeaders, RawEvent, StreamName as EsStreamName};
+
+use super::clients::command_arguments;
+use super::publish_system_event;
+
+/// The stream that records the administrative commands, only the
+/// administrators of all the streams can read it and it can not be modified.
+pub const AUDIT_STREAM: &str = "$audit";
+
+const USER_HEADER: &str = "user";
+const CLIENT_HEADER: &str = "client";
+/// The header that chains the audit events, it is the SHA-256 of the hash of the previous
+/// event, the name, the user, the client and the data of the event separated by newlines,
+/// a modified or removed event breaks the chain.
+const HASH_HEADER: &str = "hash";
+
+/// The hash the first audit event is chained to.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+lazy_static! {
+    /// The audit events are chained, they are appended one at a time.
+    static ref APPEND: Mutex<()> = Mutex::default();
+}
+
+/// Returns `true` if the request changes the streams or the configuration of the server.
+pub fn is_audited(request: &Request) -> bool {
+    match request {
+        Request::CreateStream { .. }
+        | Request::DeleteStream { .. }
+        | Request::TrimStream { .. }
+        | Request::SetStreamMeta { .. }
+        | Request::Backup { .. }
+        | Request::Compact
+        | Request::ReplicaOf { .. }
+        | Request::ReadOnly { .. }
+        | Request::ClientKill { .. }
+        | Request::ConfigSet { .. } => true,
+        Request::SlowLog { reset } => *reset,
+        _otherwise => false,
+    }
+}
+
+/// Returns the error to send if the request would modify the audit stream.
+pub fn protected_error(stream: &EsStreamName) -> Option<String> {
+    if stream.as_str() == AUDIT_STREAM {
+        let error = ErrorCode::Err.error(format_args!("stream {} can not be modified", stream));
+        return Some(error);
+    }
+    None
+}
+
+fn last_hash(db: &Db) -> sled::Result<String> {
+    let tree = db.open_tree(AUDIT_STREAM)?;
+    let last = match tree.iter().next_back().transpose()? {
+        Some((_, value)) => RawEvent::new(value).headers(),
+        None => None,
+    };
+
+    let hash = last.as_ref().and_then(|headers| headers.get(HASH_HEADER));
+    Ok(hash.unwrap_or(GENESIS_HASH).to_owned())
+}
+
+fn chain_hash(previous: &str, command: &str, user: &str, client: &str, data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    for field in &[previous, command, user, client] {
+        hasher.input(field.as_bytes());
+        hasher.input(b"\n");
+    }
+    hasher.input(data);
+
+    hasher
+        .result()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Appends the command to the audit stream, the event is named after the command
+/// and its data are the quoted arguments separated by spaces.
+pub fn record(
+    db: &Db,
+    user: Option<&str>,
+    client: SocketAddr,
+    request: &Request,
+) -> sled::Result<()> {
+    let mut arguments = command_arguments(request).into_iter();
+    let command = arguments.next().unwrap();
+    let arguments: Vec<_> = arguments.map(|a| format!("{:?}", a)).collect();
+    let data = arguments.join(" ");
+
+    let user = user.unwrap_or("default");
+    let client = client.to_string();
+
+    let _append = APPEND.lock().unwrap();
+    let previous = last_hash(db)?;
+    let hash = chain_hash(&previous, &command, user, &client, data.as_bytes());
+
+    let mut headers = EventHeaders::new();
+    headers.insert(USER_HEADER.to_owned(), user.to_owned());
+    headers.insert(CLIENT_HEADER.to_owned(), client);
+    headers.insert(HASH_HEADER.to_owned(), hash);
+
+    let stream = EsStreamName::new(AUDIT_STREAM.to_owned()).unwrap();
+    publish_system_event(db, &stream, &command, &headers, data.as_bytes())
+}
@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::{fmt, mem};
+
+use sled::Db;
+
+use meilies::stream::{EventNumber, StreamName as EsStreamName, ALL_STREAMS};
+
+use super::all::ALL_TREE;
+use super::{last_event_number, stream_names, Error, INTERNAL_TREE_PREFIX};
+
+/// The bytes an archive starts with, followed by the version of its format.
+const ARCHIVE_MAGIC: &[u8] = b"MEILIES-BACKUP\x01";
+
+/// The name of the sled tree that keeps the last event number of each stream.
+pub const DEFAULT_TREE: &[u8] = b"__sled__default";
+
+/// Followed by the name of a tree, the entries that follow belong to it.
+const TREE_TAG: u8 = b'T';
+
+/// Followed by the key and the value of an entry of the current tree.
+const ENTRY_TAG: u8 = b'E';
+
+/// Followed by the number of entries of the current tree and their CRC32 checksum.
+const CHECKSUM_TAG: u8 = b'C';
+
+/// Followed by the number of trees of the archive, it is the last record.
+const END_TAG: u8 = b'Z';
+
+/// Writes the records of an archive: every length is encoded on eight big-endian bytes.
+pub struct ArchiveWriter<W> {
+    writer: W,
+    hasher: crc32fast::Hasher,
+    entries: u64,
+    trees: u64,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<ArchiveWriter<W>> {
+        writer.write_all(ARCHIVE_MAGIC)?;
+        Ok(ArchiveWriter {
+            writer,
+            hasher: crc32fast::Hasher::new(),
+            entries: 0,
+            trees: 0,
+        })
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let length = (bytes.len() as u64).to_be_bytes();
+        self.hasher.update(&length);
+        self.hasher.update(bytes);
+        self.writer.write_all(&length)?;
+        self.writer.write_all(bytes)
+    }
+
+    pub fn start_tree(&mut self, name: &[u8]) -> io::Result<()> {
+        self.hasher = crc32fast::Hasher::new();
+        self.entries = 0;
+        self.writer.write_all(&[TREE_TAG])?;
+        self.write_bytes(name)
+    }
+
+    pub fn entry(&mut self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        self.entries += 1;
+        self.writer.write_all(&[ENTRY_TAG])?;
+        self.write_bytes(key)?;
+        self.write_bytes(value)
+    }
+
+    pub fn end_tree(&mut self) -> io::Result<()> {
+        let hasher = mem::replace(&mut self.hasher, crc32fast::Hasher::new());
+        self.trees += 1;
+        self.writer.write_all(&[CHECKSUM_TAG])?;
+        self.writer.write_all(&self.entries.to_be_bytes())?;
+        self.writer.write_all(&hasher.finalize().to_be_bytes())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(&[END_TAG])?;
+        self.writer.write_all(&self.trees.to_be_bytes())?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Writes all the trees of the database to an archive file without blocking the publications.
+///
+/// The last event numbers are read first and act as fences: the events and the `$all` positions
+/// published afterward are not written. The other internal trees are copied as they are read.
+pub fn backup(db: &Db, path: &Path) -> Result<(), Error> {
+    let mut fences = BTreeMap::new();
+    for result in db.iter() {
+        let (key, value) = result?;
+        fences.insert(key.to_vec(), value);
+    }
+
+    // the archive is only visible at its final path once it is complete
+    let tmp_path = path.with_extension("tmp");
+    let file = BufWriter::new(File::create(&tmp_path)?);
+    let mut writer = ArchiveWriter::new(file)?;
+
+    writer.start_tree(DEFAULT_TREE)?;
+    for (key, value) in &fences {
+        writer.entry(key, value)?;
+    }
+    writer.end_tree()?;
+
+    for name in db.tree_names() {
+        let name: &[u8] = name.as_ref();
+        if name == DEFAULT_TREE {
+            continue;
+        }
+
+        // the keys of the streams and of the global log are big-endian event numbers
+        let is_stream = !name.starts_with(INTERNAL_TREE_PREFIX.as_bytes());
+        let fence = if name == ALL_TREE.as_bytes() {
+            Some(fences.get(ALL_STREAMS.as_bytes()))
+        } else if is_stream {
+            Some(fences.get(name))
+        } else {
+            None
+        };
+
+        let tree = db.open_tree(name)?;
+        writer.start_tree(name)?;
+        for result in tree.iter() {
+            let (key, value) = result?;
+            match fence {
+                Some(Some(last)) if key.as_ref() > last.as_ref() => break,
+                Some(None) => break,
+                _otherwise => writer.entry(&key, &value)?,
+            }
+        }
+        writer.end_tree()?;
+    }
+
+    let file = writer.finish()?;
+    file.get_ref().sync_all()?;
+    drop(file);
+
+    fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum RestoreError {
+    IoError(io::Error),
+    InternalError(sled::Error),
+    /// The file is not an archive or it is truncated.
+    InvalidArchive,
+    /// The entries of this tree do not match their checksum.
+    ChecksumMismatch(String),
+    /// The database already contains some data.
+    NotEmpty,
+}
+
+impl fmt::Display for RestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RestoreError::IoError(e) => write!(f, "io error; {}", e),
+            RestoreError::InternalError(e) => write!(f, "internal error; {}", e),
+            RestoreError::InvalidArchive => write!(f, "invalid or truncated archive"),
+            RestoreError::ChecksumMismatch(tree) => write!(f, "corrupted entries in tree {}", tree),
+            RestoreError::NotEmpty => write!(f, "the database is not empty"),
+        }
+    }
+}
+
+impl From<io::Error> for RestoreError {
+    fn from(error: io::Error) -> RestoreError {
+        match error.kind() {
+            ErrorKind::UnexpectedEof => RestoreError::InvalidArchive,
+            _otherwise => RestoreError::IoError(error),
+        }
+    }
+}
+
+impl From<sled::Error> for RestoreError {
+    fn from(error: sled::Error) -> RestoreError {
+        RestoreError::InternalError(error)
+    }
+}
+
+/// Reads the records written by an `ArchiveWriter`.
+struct ArchiveReader<R> {
+    reader: R,
+    hasher: crc32fast::Hasher,
+}
+
+impl<R: Read> ArchiveReader<R> {
+    fn new(mut reader: R) -> Result<ArchiveReader<R>, RestoreError> {
+        let mut magic = [0; ARCHIVE_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic[..] != *ARCHIVE_MAGIC {
+            return Err(RestoreError::InvalidArchive);
+        }
+
+        Ok(ArchiveReader {
+            reader,
+            hasher: crc32fast::Hasher::new(),
+        })
+    }
+
+    fn read_array<A: AsMut<[u8]> + Default>(&mut self) -> io::Result<A> {
+        let mut array = A::default();
+        self.reader.read_exact(array.as_mut())?;
+        Ok(array)
+    }
+
+    fn read_bytes(&mut self) -> io::Result<Vec<u8>> {
+        let length: [u8; 8] = self.read_array()?;
+        let mut bytes = Vec::new();
+        (&mut self.reader)
+            .take(u64::from_be_bytes(length))
+            .read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != u64::from_be_bytes(length) {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+
+        self.hasher.update(&length);
+        self.hasher.update(&bytes);
+        Ok(bytes)
+    }
+
+    /// Reads the whole archive, validating the checksum of each tree,
+    /// and calls the given function with the tree, the key and the value of each entry.
+    fn read_entries<F>(mut self, mut f: F) -> Result<(), RestoreError>
+    where
+        F: FnMut(&[u8], &[u8], &[u8]) -> sled::Result<()>,
+    {
+        let mut tree = None;
+        let mut entries = 0u64;
+        let mut trees = 0u64;
+
+        loop {
+            let [tag]: [u8; 1] = self.read_array()?;
+            match (tag, &tree) {
+                (TREE_TAG, None) => {
+                    self.hasher = crc32fast::Hasher::new();
+                    entries = 0;
+                    tree = Some(self.read_bytes()?);
+                }
+                (ENTRY_TAG, Some(name)) => {
+                    let key = self.read_bytes()?;
+                    let value = self.read_bytes()?;
+                    entries += 1;
+                    f(name, &key, &value)?;
+                }
+                (CHECKSUM_TAG, Some(name)) => {
+                    let count = u64::from_be_bytes(self.read_array()?);
+                    let checksum = u32::from_be_bytes(self.read_array()?);
+                    let hasher = mem::replace(&mut self.hasher, crc32fast::Hasher::new());
+                    if count != entries || checksum != hasher.finalize() {
+                        let name = String::from_utf8_lossy(name).into_owned();
+                        return Err(RestoreError::ChecksumMismatch(name));
+                    }
+                    tree = None;
+                    trees += 1;
+                }
+                (END_TAG, None) => {
+                    let count = u64::from_be_bytes(self.read_array()?);
+                    if count != trees {
+                        return Err(RestoreError::InvalidArchive);
+                    }
+                    return Ok(());
+                }
+                _otherwise => return Err(RestoreError::InvalidArchive),
+            }
+        }
+    }
+}
+
+/// Fills an empty database with the trees of archives written by `backup` or uploaded
+/// segments, applied in order. The archives are entirely validated before anything is written.
+/// Returns the last event number of each stream.
+pub fn restore<P: AsRef<Path>>(
+    db: &Db,
+    paths: &[P],
+) -> Result<Vec<(EsStreamName, Option<EventNumber>)>, RestoreError> {
+    if !db.is_empty() || db.tree_names().len() > 1 {
+        return Err(RestoreError::NotEmpty);
+    }
+
+    let open = |path: &P| -> Result<_, RestoreError> {
+        let reader = BufReader::new(File::open(path)?);
+        ArchiveReader::new(reader)
+    };
+
+    for path in paths {
+        open(path)?.read_entries(|_, _, _| Ok(()))?;
+    }
+
+    for path in paths {
+        open(path)?.read_entries(|name, key, value| {
+            if name == DEFAULT_TREE {
+                db.insert(key, value)?;
+            } else {
+                db.open_tree(name)?.insert(key, value)?;
+            }
+            Ok(())
+        })?;
+    }
+
+    db.flush()?;
+
+    let mut heads = Vec::new();
+    for stream in stream_names(db) {
+        let last = last_event_number(db, &stream)?;
+        heads.push((stream, last));
+    }
+
+    Ok(heads)
+}
@@ -0,0 +1,258 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::{Future, Sink};
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use meilies::reqresp::Response;
+use meilies::stream::{Batch, EventNumber, SlowConsumer, StreamName as EsStreamName};
+use meilies::stream::{Timestamp, TraceContext};
+
+use super::clients::KillSwitch;
+use super::{metrics, slowlog, trace};
+
+/// The number of events a subscription keeps when its subscriber does not keep up.
+const BUFFER_CAPACITY: usize = 1000;
+
+struct State {
+    responses: VecDeque<Response>,
+    /// The number of events dropped since the last notification.
+    dropped: u64,
+    closed: bool,
+}
+
+/// The events of a subscription that are waiting to be sent to a slow subscriber,
+/// the slow consumer policy of the subscription is applied when it is full.
+///
+/// The events of a batched subscription are also sent by its buffer, the ones
+/// waiting to be sent are coalesced into a batch by the forwarding thread.
+pub struct SubscriptionBuffer {
+    policy: SlowConsumer,
+    batch: Option<Batch>,
+    kill: KillSwitch,
+    state: Mutex<State>,
+    available: Condvar,
+    /// Notified when responses are sent, a blocking subscription waits for room.
+    room: Condvar,
+}
+
+impl SubscriptionBuffer {
+    /// Creates the buffer of a subscription and spawns the thread
+    /// that forwards its responses to the connection.
+    pub fn spawn(
+        policy: SlowConsumer,
+        batch: Option<Batch>,
+        kill: KillSwitch,
+        client: SocketAddr,
+        stream: EsStreamName,
+        sender: mpsc::Sender<Result<Response, String>>,
+    ) -> Arc<SubscriptionBuffer> {
+        let buffer = Arc::new(SubscriptionBuffer {
+            policy,
+            batch,
+            kill,
+            state: Mutex::new(State {
+                responses: VecDeque::new(),
+                dropped: 0,
+                closed: false,
+            }),
+            available: Condvar::new(),
+            room: Condvar::new(),
+        });
+
+        let forwarded = buffer.clone();
+        let result = thread::Builder::new().spawn(move || {
+            forwarded.forward(client, stream, sender);
+        });
+
+        if let Err(e) = result {
+            error!("error spawning the forwarder of a subscription; {}", e);
+            buffer.discard();
+        }
+
+        buffer
+    }
+
+    /// Buffers a response, returns `false` if the subscription must stop.
+    pub fn push(&self, response: Response) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return false;
+        }
+
+        // only the events can be dropped, the other responses are always sent
+        let is_event = matches!(response, Response::Event { .. });
+        if is_event && state.responses.len() >= BUFFER_CAPACITY {
+            match self.policy {
+                SlowConsumer::Block => {
+                    while !state.closed && state.responses.len() >= BUFFER_CAPACITY {
+                        state = self.room.wait(state).unwrap();
+                    }
+                    if state.closed {
+                        return false;
+                    }
+                }
+                SlowConsumer::DropOldest => {
+                    let oldest = state
+                        .responses
+                        .iter()
+                        .position(|r| matches!(r, Response::Event { .. }));
+                    state.dropped += 1;
+                    match oldest {
+                        Some(position) => {
+                            state.responses.remove(position);
+                        }
+                        None => return true,
+                    }
+                }
+                SlowConsumer::DropNewest => {
+                    state.dropped += 1;
+                    return true;
+                }
+                SlowConsumer::Disconnect => {
+                    info!("slow subscriber disconnected");
+                    state.closed = true;
+                    state.responses.clear();
+                    self.available.notify_one();
+                    self.kill.kill();
+                    return false;
+                }
+            }
+        }
+
+        state.responses.push_back(response);
+        self.available.notify_one();
+        true
+    }
+
+    /// Stops the forwarding once the buffered responses have been sent.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.available.notify_one();
+        self.room.notify_all();
+    }
+
+    /// Stops the forwarding, the buffered responses are not sent.
+    pub fn discard(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.responses.clear();
+        state.dropped = 0;
+        self.available.notify_one();
+        self.room.notify_all();
+    }
+
+    /// Returns the next response to send, the dropped events are notified first.
+    fn next(&self, stream: &EsStreamName) -> Option<Response> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.dropped != 0 {
+                let count = state.dropped;
+                state.dropped = 0;
+                let stream = stream.clone();
+                return Some(Response::EventsDropped { stream, count });
+            }
+
+            if let Some(response) = state.responses.pop_front() {
+                let response = match self.batch {
+                    Some(batch) if matches!(response, Response::Event { .. }) => {
+                        self.fill_batch(state, response, batch)
+                    }
+                    _otherwise => response,
+                };
+                self.room.notify_all();
+                return Some(response);
+            }
+
+            if state.closed {
+                return None;
+            }
+
+            state = self.available.wait(state).unwrap();
+        }
+    }
+
+    /// Returns the batch starting with the given event, the events that follow
+    /// it are added until the batch is full or the window has elapsed.
+    fn fill_batch(&self, mut state: MutexGuard<State>, first: Response, batch: Batch) -> Response {
+        let deadline = Instant::now() + Duration::from_millis(batch.window);
+        let mut events = vec![first];
+
+        while (events.len() as u64) < batch.size {
+            match state.responses.front() {
+                Some(Response::Event { .. }) => events.extend(state.responses.pop_front()),
+                // the other responses are sent after the events that precede them
+                Some(_) => break,
+                None => {
+                    let now = Instant::now();
+                    if state.closed || state.dropped != 0 || now >= deadline {
+                        break;
+                    }
+                    let (guard, _timeout) =
+                        self.available.wait_timeout(state, deadline - now).unwrap();
+                    state = guard;
+                }
+            }
+        }
+
+        if events.len() == 1 {
+            events.remove(0)
+        } else {
+            Response::Batch { events }
+        }
+    }
+
+    fn forward(
+        &self,
+        client: SocketAddr,
+        stream: EsStreamName,
+        mut sender: mpsc::Sender<Result<Response, String>>,
+    ) {
+        while let Some(response) = self.next(&stream) {
+            let delivered: Vec<_> = match &response {
+                Response::Batch { events } => events.iter().filter_map(delivered).collect(),
+                response => delivered(response).into_iter().collect(),
+            };
+
+            let timer = metrics::operation_timer(metrics::DELIVERY);
+            sender = match sender.send(Ok(response)).wait() {
+                Ok(sender) => {
+                    timer.observe_duration();
+                    sender
+                }
+                Err(_) => {
+                    info!("encountered closed channel");
+                    self.discard();
+                    return;
+                }
+            };
+
+            for (delivered, number, timestamp, context) in delivered {
+                metrics::delivered(client, &stream, &delivered, timestamp);
+                slowlog::delivered(client, &delivered, number, timestamp);
+                trace::delivered(client, &delivered, context);
+            }
+        }
+    }
+}
+
+/// The stream, the number, the time and the trace context of an event sent.
+type Delivered = (EsStreamName, EventNumber, Timestamp, Option<TraceContext>);
+
+/// Returns what is recorded of the event sent, `None` if the response is not an event.
+fn delivered(response: &Response) -> Option<Delivered> {
+    match response {
+        Response::Event {
+            stream,
+            number,
+            timestamp,
+            headers,
+            ..
+        } => Some((stream.clone(), *number, *timestamp, headers.trace_context())),
+        _otherwise => None,
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use futures::sync::oneshot;
+use sled::Db;
+use tokio::sync::mpsc;
+
+use meilies::reqresp::{ClientInfo, Request, Response};
+use meilies::resp::RespValue;
+use meilies::stream::{StreamName as EsStreamName, Timestamp};
+
+use super::polled::PolledSubscriptions;
+use super::rate::{self, Limit, Limiter};
+use super::{metrics, Subscriptions};
+
+struct Entry {
+    addr: SocketAddr,
+    user: Option<String>,
+    subscriptions: Subscriptions,
+    last_activity: Instant,
+    kill: KillSwitch,
+    /// Receives the commands of all the clients when the client is monitoring.
+    monitor: Option<mpsc::Sender<Result<Response, String>>>,
+}
+
+/// Closes the connection of a client, it can be shared with the subscriptions of the client.
+#[derive(Clone)]
+pub struct KillSwitch {
+    sender: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+}
+
+impl KillSwitch {
+    fn new(sender: oneshot::Sender<()>) -> KillSwitch {
+        KillSwitch {
+            sender: Arc::new(Mutex::new(Some(sender))),
+        }
+    }
+
+    /// Closes the connection, returns `false` if it has already been closed.
+    pub fn kill(&self) -> bool {
+        match self.sender.lock().unwrap().take() {
+            Some(sender) => {
+                let _ = sender.send(());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    entries: BTreeMap<u64, Entry>,
+}
+
+/// The clients connected to the server, each one is identified by a unique id.
+#[derive(Clone)]
+pub struct Clients {
+    registry: Arc<Mutex<Registry>>,
+    /// The publish rate limit of each connection.
+    limit: Limit,
+    /// The subscriptions of the clients of a simulated server, polled instead of run by threads.
+    polled: Option<PolledSubscriptions>,
+}
+
+impl Clients {
+    pub fn new(limit: Limit) -> Clients {
+        Clients {
+            registry: Arc::default(),
+            limit,
+            polled: None,
+        }
+    }
+
+    /// Makes the subscriptions of the clients polled by the simulation of the server.
+    pub fn with_polled_subscriptions(mut self, polled: PolledSubscriptions) -> Clients {
+        self.polled = Some(polled);
+        self
+    }
+
+    pub fn polled_subscriptions(&self) -> Option<&PolledSubscriptions> {
+        self.polled.as_ref()
+    }
+
+    /// Registers a new connection, returns the client and a future
+    /// that resolves when the connection must be closed.
+    pub fn register(
+        &self,
+        addr: SocketAddr,
+        user: Option<&str>,
+    ) -> (Client, oneshot::Receiver<()>) {
+        let (kill, killed) = oneshot::channel();
+        let kill = KillSwitch::new(kill);
+        let subscriptions = Subscriptions::new(addr, kill.clone());
+
+        let mut registry = self.registry.lock().unwrap();
+        let id = registry.next_id;
+        registry.next_id += 1;
+
+        let entry = Entry {
+            addr,
+            user: user.map(ToOwned::to_owned),
+            subscriptions: subscriptions.clone(),
+            last_activity: Instant::now(),
+            kill,
+            monitor: None,
+        };
+        registry.entries.insert(id, entry);
+        metrics::CONNECTED_CLIENTS.inc();
+
+        let client = Client {
+            id,
+            addr,
+            clients: self.clone(),
+            subscriptions,
+            limiter: Mutex::default(),
+            proto: AtomicU64::new(2),
+        };
+
+        (client, killed)
+    }
+
+    /// Returns the clients ordered by id.
+    pub fn list(&self) -> Vec<ClientInfo> {
+        let registry = self.registry.lock().unwrap();
+        registry
+            .entries
+            .iter()
+            .map(|(id, entry)| {
+                let mut subscriptions: Vec<_> = {
+                    let streams = entry.subscriptions.streams.lock().unwrap();
+                    streams.keys().cloned().collect()
+                };
+                subscriptions.sort();
+
+                ClientInfo {
+                    id: *id,
+                    addr: entry.addr.to_string(),
+                    user: entry.user.clone(),
+                    subscriptions,
+                    idle: entry.last_activity.elapsed().as_millis() as u64,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the number of subscriptions of the connected clients to the stream.
+    pub fn subscribers(&self, stream: &EsStreamName) -> u64 {
+        let registry = self.registry.lock().unwrap();
+        registry
+            .entries
+            .values()
+            .filter(|entry| {
+                let streams = entry.subscriptions.streams.lock().unwrap();
+                streams.contains_key(stream)
+            })
+            .count() as u64
+    }
+
+    /// Closes the connection of a client, returns `false` if there is no client with this id.
+    pub fn kill(&self, id: u64) -> bool {
+        let registry = self.registry.lock().unwrap();
+        registry.entries.get(&id).map_or(false, |e| e.kill.kill())
+    }
+
+    /// Sends a command to the monitoring clients, the command
+    /// is dropped for the clients that are not reading fast enough.
+    fn monitored(&self, id: u64, request: &Request) {
+        let mut registry = self.registry.lock().unwrap();
+        if registry.entries.values().all(|e| e.monitor.is_none()) {
+            return;
+        }
+
+        let response = Response::Monitored {
+            timestamp: Timestamp::now(),
+            client: id,
+            command: command_arguments(request),
+        };
+
+        for entry in registry.entries.values_mut() {
+            if let Some(monitor) = &mut entry.monitor {
+                if let Err(e) = monitor.try_send(Ok(response.clone())) {
+                    if e.is_closed() {
+                        entry.monitor = None;
+                    }
+                }
+            }
+        }
+    }
+
+    fn with_entry<F: FnOnce(&mut Entry)>(&self, id: u64, f: F) {
+        if let Some(entry) = self.registry.lock().unwrap().entries.get_mut(&id) {
+            f(entry)
+        }
+    }
+}
+
+/// Returns the name and the arguments of a command as strings, the passwords are redacted.
+pub fn command_arguments(request: &Request) -> Vec<String> {
+    fn push_strings(value: RespValue, strings: &mut Vec<String>) {
+        match value {
+            RespValue::SimpleString(string) | RespValue::Error(string) => strings.push(string),
+            RespValue::Integer(integer) => strings.push(integer.to_string()),
+            RespValue::BulkString(bytes) => strings.push(String::from_utf8_lossy(&bytes).into()),
+            RespValue::Array(values) | RespValue::Push(values) => {
+                for value in values {
+                    push_strings(value, strings);
+                }
+            }
+            RespValue::Map(pairs) => {
+                for (key, value) in pairs {
+                    push_strings(key, strings);
+                    push_strings(value, strings);
+                }
+            }
+            RespValue::Double(double) => strings.push(double.to_string()),
+            RespValue::Nil => strings.push(String::from("nil")),
+        }
+    }
+
+    if let Request::Auth { .. } = request {
+        return vec![String::from("auth"), String::from("(redacted)")];
+    }
+
+    let mut strings = Vec::new();
+    push_strings(request.clone().into(), &mut strings);
+    strings
+}
+
+/// A connected client, it is removed from the clients when dropped.
+pub struct Client {
+    id: u64,
+    addr: SocketAddr,
+    clients: Clients,
+    subscriptions: Subscriptions,
+    limiter: Mutex<Limiter>,
+    /// The version of the protocol used by the connection.
+    proto: AtomicU64,
+}
+
+impl Client {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn clients(&self) -> &Clients {
+        &self.clients
+    }
+
+    pub fn subscriptions(&self) -> &Subscriptions {
+        &self.subscriptions
+    }
+
+    /// Returns the error to send if a publication of the client exceeds the rate limits.
+    pub fn throttle_error(&self, db: &Db, request: &Request) -> sled::Result<Option<String>> {
+        rate::throttle_error(db, &self.limiter, self.clients.limit, request)
+    }
+
+    /// Records that the client sent a command.
+    pub fn touch(&self) {
+        self.clients
+            .with_entry(self.id, |entry| entry.last_activity = Instant::now());
+    }
+
+    /// Sends a command of the client to the monitoring clients.
+    pub fn monitored(&self, request: &Request) {
+        self.clients.monitored(self.id, request);
+    }
+
+    /// Makes the client receive the commands of all the clients.
+    pub fn set_monitor(&self, sender: mpsc::Sender<Result<Response, String>>) {
+        self.clients
+            .with_entry(self.id, |entry| entry.monitor = Some(sender));
+    }
+
+    pub fn proto(&self) -> u64 {
+        self.proto.load(Ordering::Relaxed)
+    }
+
+    pub fn set_proto(&self, proto: u64) {
+        self.proto.store(proto, Ordering::Relaxed);
+    }
+
+    pub fn set_user(&self, user: Option<&str>) {
+        let user = user.map(ToOwned::to_owned);
+        self.clients.with_entry(self.id, |entry| entry.user = user);
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.clients
+            .registry
+            .lock()
+            .unwrap()
+            .entries
+            .remove(&self.id);
+        metrics::CONNECTED_CLIENTS.dec();
+
+        // the subscriptions are woken up to stop
+        for subscription in self.subscriptions.streams.lock().unwrap().values() {
+            subscription.unsubscribe();
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+use std::io;
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use futures::sync::oneshot;
+use sled::{Config, Db};
+use tracing::{debug, error};
+
+/// The interval between two flushes of the database used by sled by default.
+const DEFAULT_FLUSH_INTERVAL: u64 = 500;
+
+/// When the writes are flushed to disk, it bounds the writes lost if the server crashes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// The publications are acknowledged once flushed by the group commit.
+    EveryWrite,
+    /// The database is flushed every this number of milliseconds.
+    Interval(u64),
+    /// The database is never flushed explicitly, the writes are
+    /// written to disk when the buffers of sled and the OS are full.
+    Os,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> FlushPolicy {
+        FlushPolicy::Interval(DEFAULT_FLUSH_INTERVAL)
+    }
+}
+
+impl FlushPolicy {
+    /// Parses the values of the `--flush` option, e.g. `every-write` or `interval 100`.
+    pub fn from_args(args: &[String]) -> Result<FlushPolicy, String> {
+        let args: Vec<_> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            ["every-write"] => Ok(FlushPolicy::EveryWrite),
+            ["interval", millis] => match millis.parse() {
+                Ok(0) | Err(_) => Err(format!("invalid flush interval {:?}", millis)),
+                Ok(millis) => Ok(FlushPolicy::Interval(millis)),
+            },
+            ["os"] => Ok(FlushPolicy::Os),
+            _otherwise => Err(String::from(
+                "the flush policy must be every-write, interval <ms> or os",
+            )),
+        }
+    }
+
+    /// Configures the periodic flush of the database.
+    pub fn configure(self, config: Config) -> Config {
+        match self {
+            // the other writes, e.g. the acknowledgements of the consumers, are flushed periodically
+            FlushPolicy::EveryWrite => config.flush_every_ms(Some(DEFAULT_FLUSH_INTERVAL)),
+            FlushPolicy::Interval(millis) => config.flush_every_ms(Some(millis)),
+            FlushPolicy::Os => config.flush_every_ms(None),
+        }
+    }
+}
+
+impl fmt::Display for FlushPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlushPolicy::EveryWrite => f.write_str("every-write"),
+            FlushPolicy::Interval(millis) => write!(f, "interval {}", millis),
+            FlushPolicy::Os => f.write_str("os"),
+        }
+    }
+}
+
+/// The publications waiting for the next flush, notified with its outcome.
+type Waiters = Vec<oneshot::Sender<Result<(), String>>>;
+
+/// Flushes the database once for all the publications made during a window,
+/// each publication is acknowledged once the flush that includes it is done.
+#[derive(Clone)]
+pub struct GroupCommit {
+    waiters: Arc<(Mutex<Waiters>, Condvar)>,
+}
+
+impl GroupCommit {
+    /// Spawns the thread that flushes the database, it waits `window` after
+    /// the first publication of a group for the concurrent ones to join it.
+    pub fn spawn(db: Db, window: Duration) -> io::Result<GroupCommit> {
+        let commit = GroupCommit {
+            waiters: Arc::default(),
+        };
+
+        let waiters = commit.waiters.clone();
+        thread::Builder::new()
+            .name(String::from("commit"))
+            .spawn(move || loop {
+                let group = next_group(&waiters, window);
+                let result = db.flush().map(drop).map_err(|e| e.to_string());
+                if let Err(e) = &result {
+                    error!("error flushing the publications; {}", e);
+                }
+
+                debug!("{} publications flushed", group.len());
+                for waiter in group {
+                    // the connection of the publisher may have been closed
+                    let _ = waiter.send(result.clone());
+                }
+            })?;
+
+        Ok(commit)
+    }
+
+    /// Returns a receiver notified once the writes done so far are on disk.
+    pub fn durable(&self) -> oneshot::Receiver<Result<(), String>> {
+        let (sender, receiver) = oneshot::channel();
+        let (waiters, condvar) = &*self.waiters;
+        waiters.lock().unwrap().push(sender);
+        condvar.notify_one();
+        receiver
+    }
+}
+
+/// Waits for a publication and then for the window to elapse, returns the group to flush.
+fn next_group(waiters: &(Mutex<Waiters>, Condvar), window: Duration) -> Waiters {
+    let (waiters, condvar) = waiters;
+
+    let mut group = waiters.lock().unwrap();
+    while group.is_empty() {
+        group = condvar.wait(group).unwrap();
+    }
+    drop(group);
+
+    // the publications made during the window join the group,
+    // the ones made during the flush join the next one
+    thread::sleep(window);
+    mem::take(&mut *waiters.lock().unwrap())
+}
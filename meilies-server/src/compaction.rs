@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use sled::{Db, IVec};
+use tracing::{error, info};
+
+use meilies::stream::{EventNumber, RawEvent, StreamName as EsStreamName};
+
+use super::shutdown::Shutdown;
+use super::{last_event_number, meta, retention, stream_names, tail};
+
+/// The stream metadata that enables the compaction of the stream when set to `true`.
+const COMPACTION: &str = "compaction";
+
+/// The stream metadata that specifies the number of latest events that are never compacted.
+const COMPACTION_TAIL: &str = "compaction.tail";
+
+/// The event header that contains the compaction key of an event,
+/// the events without this header are never compacted.
+const KEY_HEADER: &str = "key";
+
+/// Returns an error if the value of a compaction metadata is invalid.
+pub fn check_meta(key: &str, value: &str) -> Result<(), String> {
+    match key {
+        COMPACTION if value.parse::<bool>().is_err() => {
+            Err(format!("{} must be true or false, found {:?}", key, value))
+        }
+        COMPACTION_TAIL if value.parse::<u64>().is_err() => Err(format!(
+            "{} must be a positive number, found {:?}",
+            key, value
+        )),
+        _otherwise => Ok(()),
+    }
+}
+
+/// Spawns the thread that periodically compacts the streams for which it is enabled.
+pub fn spawn_compactor(
+    db: Db,
+    interval: Duration,
+    shutdown: Shutdown,
+) -> io::Result<JoinHandle<()>> {
+    thread::Builder::new()
+        .name(String::from("compactor"))
+        .spawn(move || {
+            while !shutdown.wait(interval) {
+                if let Err(e) = compact_streams(&db) {
+                    error!("error compacting the streams; {}", e);
+                }
+            }
+        })
+}
+
+/// Compacts the streams for which it is enabled right away and flushes the database, sled then
+/// reuses the segments of the events removed. Returns the size on disk before and after.
+pub fn reclaim(db: &Db) -> sled::Result<(u64, u64)> {
+    let before = db.size_on_disk()?;
+    compact_streams(db)?;
+    db.flush()?;
+    let after = db.size_on_disk()?;
+    Ok((before, after))
+}
+
+/// Spawns the thread that periodically reclaims the space of the events removed.
+pub fn spawn_reclaimer(db: Db, interval: Duration) -> io::Result<()> {
+    thread::Builder::new()
+        .name(String::from("reclaimer"))
+        .spawn(move || loop {
+            thread::sleep(interval);
+            match reclaim(&db) {
+                Ok((before, after)) => {
+                    info!("database reclaimed from {} to {} bytes", before, after)
+                }
+                Err(e) => error!("error reclaiming the database; {}", e),
+            }
+        })?;
+
+    Ok(())
+}
+
+pub fn compact_streams(db: &Db) -> sled::Result<()> {
+    for stream in stream_names(db) {
+        match meta::get(db, &stream, COMPACTION)? {
+            Some(enabled) if enabled.parse::<bool>() == Ok(true) => (),
+            _otherwise => continue,
+        }
+
+        let tail = meta::get(db, &stream, COMPACTION_TAIL)?;
+        let tail = tail.map_or(0, |t| t.parse().unwrap());
+
+        let removed = compact_stream(db, &stream, tail)?;
+        if removed != 0 {
+            info!("stream {} compacted, {} events removed", stream, removed);
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the events that are followed by an event with the same compaction key,
+/// the `tail` latest events are kept. Returns the number of events removed.
+fn compact_stream(db: &Db, stream: &EsStreamName, tail: u64) -> sled::Result<usize> {
+    let tree = db.open_tree(stream.as_ref())?;
+    let end = match last_event_number(db, stream)? {
+        Some(last) => EventNumber((last.0 + 1).saturating_sub(tail)),
+        None => return Ok(0),
+    };
+
+    let compaction_key = |value: IVec| {
+        let headers = RawEvent::new(value).headers()?;
+        headers.get(KEY_HEADER).map(ToOwned::to_owned)
+    };
+
+    // the latest events of the tail supersede the previous ones too
+    let mut latest = HashMap::new();
+    for result in tree.iter() {
+        let (key, value) = result?;
+        if let Some(compaction_key) = compaction_key(value) {
+            latest.insert(compaction_key, EventNumber::try_from(key.as_ref()).unwrap());
+        }
+    }
+
+    let mut removed = 0;
+    let mut bytes = 0;
+    for result in tree.range(..end.to_be_bytes()) {
+        let (key, value) = result?;
+        let number = EventNumber::try_from(key.as_ref()).unwrap();
+        let size = value.len() as u64;
+
+        let superseded = match compaction_key(value) {
+            Some(compaction_key) => latest.get(&compaction_key) != Some(&number),
+            None => false,
+        };
+
+        if superseded && tree.remove(key)?.is_some() {
+            removed += 1;
+            bytes += size;
+        }
+    }
+
+    retention::removed(db, stream, bytes)?;
+    if removed != 0 {
+        tail::invalidate(stream);
+    }
+
+    Ok(removed)
+}
@@ -0,0 +1,75 @@
+use std::borrow::Cow;
+
+use sled::Db;
+use tracing::error;
+
+use meilies::stream::{EventData, StreamName as EsStreamName};
+
+use super::meta;
+
+/// The stream metadata that specifies how the data of the events appended is compressed.
+const COMPRESSION: &str = "compression";
+
+/// The values of the compression metadata, the data is not compressed by default.
+const ZSTD: &str = "zstd";
+const NONE: &str = "none";
+
+/// The header of the event data compressed with zstd, the data of the events
+/// appended without compression does not start with it and is read as is.
+const MAGIC: &[u8] = b"\0meilies-zstd\0";
+
+const LEVEL: i32 = 3;
+
+/// Returns an error if the value of the compression metadata is invalid.
+pub fn check_meta(key: &str, value: &str) -> Result<(), String> {
+    match key {
+        COMPRESSION if value != ZSTD && value != NONE => Err(format!(
+            "{} must be {} or {}, found {:?}",
+            key, ZSTD, NONE, value
+        )),
+        _otherwise => Ok(()),
+    }
+}
+
+/// Returns `true` if the data of the events appended to the stream must be compressed.
+pub fn is_enabled(db: &Db, stream: &EsStreamName) -> sled::Result<bool> {
+    let compression = meta::get(db, stream, COMPRESSION)?;
+    Ok(compression.as_deref() == Some(ZSTD))
+}
+
+/// Returns the event data to store, it is compressed if `enabled` and smaller once compressed.
+///
+/// A data starting with the magic header is always compressed,
+/// otherwise it would be decompressed when read.
+pub fn compress(data: &[u8], enabled: bool) -> Cow<[u8]> {
+    let ambiguous = data.starts_with(MAGIC);
+    if !enabled && !ambiguous {
+        return Cow::Borrowed(data);
+    }
+
+    // compressing into memory can not fail
+    let compressed = zstd::encode_all(data, LEVEL).unwrap();
+    if !ambiguous && MAGIC.len() + compressed.len() >= data.len() {
+        return Cow::Borrowed(data);
+    }
+
+    let mut stored = Vec::with_capacity(MAGIC.len() + compressed.len());
+    stored.extend_from_slice(MAGIC);
+    stored.extend_from_slice(&compressed);
+    Cow::Owned(stored)
+}
+
+/// Returns the event data as published, decompressing it if it starts with the magic header.
+pub fn decompress(data: EventData) -> EventData {
+    if !data.0.starts_with(MAGIC) {
+        return data;
+    }
+
+    match zstd::decode_all(&data.0[MAGIC.len()..]) {
+        Ok(decompressed) => EventData(decompressed.into()),
+        Err(e) => {
+            error!("error decompressing an event data, it is sent as is; {}", e);
+            data
+        }
+    }
+}
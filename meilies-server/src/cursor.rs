@@ -0,0 +1,67 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+
+use sled::{IVec, Tree};
+
+use meilies::stream::EventNumber;
+
+use super::metrics;
+
+/// The number of entries read from the tree at once.
+const READ_AHEAD: usize = 64;
+
+/// Iterates over the entries of a tree keyed by event number, the entries are read by
+/// windows of `READ_AHEAD` entries and no range of the tree is kept open between two windows.
+///
+/// A subscriber replaying a large stream does not pin the tree while it is slowly
+/// sending the events, the memory used only depends on the size of the window.
+pub struct Cursor {
+    tree: Tree,
+    next: EventNumber,
+    end: Option<EventNumber>,
+    window: VecDeque<(IVec, IVec)>,
+}
+
+impl Cursor {
+    /// Reads the entries from `next` until `end`, excluded.
+    pub fn new(tree: Tree, next: EventNumber, end: Option<EventNumber>) -> Cursor {
+        Cursor {
+            tree,
+            next,
+            end,
+            window: VecDeque::with_capacity(READ_AHEAD),
+        }
+    }
+
+    fn read_window(&mut self) -> sled::Result<()> {
+        let _timer = metrics::operation_timer(metrics::CATCH_UP);
+        let range = match self.end {
+            Some(end) => self.tree.range(self.next.to_be_bytes()..end.to_be_bytes()),
+            None => self.tree.range(self.next.to_be_bytes()..),
+        };
+
+        for result in range.take(READ_AHEAD) {
+            self.window.push_back(result?);
+        }
+
+        Ok(())
+    }
+}
+
+impl Iterator for Cursor {
+    type Item = sled::Result<(IVec, IVec)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // the end is only reached when an empty window is read, the entries
+        // inserted while the previous window was being sent are not missed
+        if self.window.is_empty() {
+            if let Err(e) = self.read_window() {
+                return Some(Err(e));
+            }
+        }
+
+        let (key, value) = self.window.pop_front()?;
+        self.next = EventNumber::try_from(key.as_ref()).unwrap().next();
+        Some(Ok((key, value)))
+    }
+}
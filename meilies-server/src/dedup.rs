@@ -0,0 +1,72 @@
+use sled::{Db, Tree};
+
+use meilies::stream::{EventNumber, StreamName as EsStreamName};
+
+/// The tree that keeps the deduplication IDs of the recent events, keys are
+/// the stream name followed by a colon and the ID, values the event numbers.
+const DEDUP_IDS_TREE: &str = "meilies:dedup-ids";
+
+/// The tree that keeps the deduplication IDs in publication order to forget the oldest ones,
+/// keys are the stream name followed by a colon and the event number, values the IDs.
+const DEDUP_LOG_TREE: &str = "meilies:dedup-log";
+
+/// The number of events of a stream during which a deduplication ID is remembered.
+const DEDUP_WINDOW: u64 = 10_000;
+
+fn stream_prefix(stream: &EsStreamName) -> Vec<u8> {
+    let mut prefix = stream.as_str().as_bytes().to_vec();
+    prefix.push(b':');
+    prefix
+}
+
+pub fn id_key(stream: &EsStreamName, id: &str) -> Vec<u8> {
+    let mut key = stream_prefix(stream);
+    key.extend_from_slice(id.as_bytes());
+    key
+}
+
+pub fn log_key(stream: &EsStreamName, number: EventNumber) -> Vec<u8> {
+    let mut key = stream_prefix(stream);
+    key.extend_from_slice(&number.to_be_bytes());
+    key
+}
+
+/// Opens the trees in which the deduplication IDs and their publication order are stored.
+pub fn trees(db: &Db) -> sled::Result<(Tree, Tree)> {
+    Ok((db.open_tree(DEDUP_IDS_TREE)?, db.open_tree(DEDUP_LOG_TREE)?))
+}
+
+/// Forgets the IDs of the events published more than `DEDUP_WINDOW` events before the given one.
+pub fn prune(db: &Db, stream: &EsStreamName, last: EventNumber) -> sled::Result<()> {
+    let oldest = match last.0.checked_sub(DEDUP_WINDOW) {
+        Some(oldest) => EventNumber(oldest),
+        None => return Ok(()),
+    };
+
+    let (ids, log) = trees(db)?;
+    let start = log_key(stream, EventNumber::zero());
+    let end = log_key(stream, oldest);
+
+    for result in log.range(start..end) {
+        let (key, id) = result?;
+        ids.remove(id_key(stream, &String::from_utf8_lossy(&id)))?;
+        log.remove(key)?;
+    }
+
+    Ok(())
+}
+
+/// Forgets all the deduplication IDs of a stream.
+pub fn clear_stream_ids(db: &Db, stream: &EsStreamName) -> sled::Result<()> {
+    let prefix = stream_prefix(stream);
+    let (ids, log) = trees(db)?;
+
+    for tree in &[ids, log] {
+        for result in tree.scan_prefix(&prefix) {
+            let (key, _) = result?;
+            tree.remove(key)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use tracing::{error, info};
+
+use meilies::reqresp::ErrorCode;
+
+use super::metrics;
+
+/// The interval at which the free space of the data directory is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref LOW_SPACE: AtomicBool = AtomicBool::new(false);
+}
+
+/// Returns an error if the free space of the data directory is below the threshold,
+/// the writes are rejected until enough space is freed.
+pub fn write_error() -> Option<String> {
+    if LOW_SPACE.load(Ordering::SeqCst) {
+        Some(ErrorCode::DiskFull.error("not enough free space on the data directory"))
+    } else {
+        None
+    }
+}
+
+/// Checks the free space of the data directory, the server switches to read-only
+/// below `threshold` bytes and accepts the writes again once it is above.
+fn check(path: &Path, threshold: u64) -> io::Result<()> {
+    let available = fs2::available_space(path)?;
+    metrics::disk_available(available);
+
+    let low_space = available < threshold;
+    if LOW_SPACE.swap(low_space, Ordering::SeqCst) != low_space {
+        if low_space {
+            error!(
+                "{} bytes available on the data directory, writes are rejected",
+                available
+            );
+        } else {
+            info!(
+                "{} bytes available on the data directory, writes are accepted",
+                available
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Spawns the thread that periodically checks the free space of the data directory.
+pub fn spawn_monitor(path: PathBuf, threshold: u64) -> io::Result<()> {
+    // the server must not accept writes before the first check
+    check(&path, threshold)?;
+
+    thread::Builder::new()
+        .name(String::from("disk-monitor"))
+        .spawn(move || loop {
+            thread::sleep(CHECK_INTERVAL);
+            if let Err(e) = check(&path, threshold) {
+                error!("error checking the free space of the data directory; {}", e);
+            }
+        })?;
+
+    Ok(())
+}
@@ -0,0 +1,296 @@
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use futures::executor::{self, Notify, Spawn};
+use futures::{future, Async, Future, Sink, Stream};
+use sled::{Config, Db};
+use tokio::codec::Decoder;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use meilies::reqresp::{ErrorCode, Request, Response, ServerCodec};
+
+use super::acks::{ConnectionAcks, ReplicaAcks};
+use super::acl::Authentication;
+use super::clients::{Client, Clients};
+use super::commit::FlushPolicy;
+use super::polled::PolledSubscriptions;
+use super::replication::Replication;
+use super::shutdown::Shutdown;
+use super::{compaction, dispatch_blocking, handle_connection, info as server_info};
+use super::{rate, retention, scheduled, Error};
+
+/// Ignores the wake ups of the embedded clients, they poll their responses when asked.
+struct NoopNotify;
+
+impl Notify for NoopNotify {
+    fn notify(&self, _id: usize) {}
+}
+
+/// The address the embedded clients are listed with, they are not connected with a socket.
+const LOOPBACK_ADDR: ([u8; 4], u16) = ([127, 0, 0, 1], 0);
+
+/// The intervals of the expiry and of the compaction of the streams, the defaults of the server.
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The time a publication waits for the acknowledgements, there is never any replica.
+const ACK_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// The number of responses waiting to be received by an embedded client, the largest
+/// capacity of the channel. The commands run on the thread of the client, which only reads
+/// the responses once they return, they must never wait for the channel to be read.
+/// The channel allocates its blocks as the responses arrive.
+const RESPONSES_CAPACITY: usize = usize::MAX >> 2;
+
+/// The threads of an embedded server and the runtimes of its connections, they run until
+/// the server is shut down.
+#[derive(Default)]
+struct Background {
+    shutdown: Shutdown,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+    runtimes: Mutex<Vec<Runtime>>,
+}
+
+/// An event store running in the process of the application, it only accepts connections
+/// once `listen` is called. The commands of its clients are run like the ones of the connections.
+#[derive(Clone)]
+pub struct EmbeddedServer {
+    db: Db,
+    clients: Clients,
+    replication: Replication,
+    acks: ReplicaAcks,
+    background: Arc<Background>,
+}
+
+impl EmbeddedServer {
+    /// Opens the database at this path, creating it if needed, and spawns the threads that
+    /// expire, compact and publish the scheduled events. It is flushed every 500 ms.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<EmbeddedServer, Error> {
+        let server = EmbeddedServer::start(Config::new().path(path))?;
+        info!("embedded server opened");
+        Ok(server)
+    }
+
+    /// Opens a temporary database, removed once the server and its clients are dropped, and
+    /// spawns the same threads as `open`. On Linux the database is kept in shared memory.
+    pub fn temporary() -> Result<EmbeddedServer, Error> {
+        let server = EmbeddedServer::start(Config::new().temporary(true))?;
+        info!("temporary embedded server opened");
+        Ok(server)
+    }
+
+    fn start(config: Config) -> Result<EmbeddedServer, Error> {
+        let db = FlushPolicy::default().configure(config).open()?;
+
+        let shutdown = Shutdown::new();
+        let threads = vec![
+            retention::spawn_expiry(db.clone(), EXPIRY_INTERVAL, shutdown.clone())?,
+            compaction::spawn_compactor(db.clone(), COMPACTION_INTERVAL, shutdown.clone())?,
+            scheduled::spawn_scheduler(db.clone(), shutdown.clone())?,
+        ];
+        let background = Background {
+            shutdown,
+            threads: Mutex::new(threads),
+            runtimes: Mutex::default(),
+        };
+
+        Ok(EmbeddedServer::new(
+            db,
+            Clients::new(rate::Limit::default()),
+            background,
+        ))
+    }
+
+    /// Opens the database at this path for a simulation, nothing runs in the background:
+    /// the database is only flushed by `flush`, the events are only expired, compacted
+    /// and published at their scheduled time by `run_maintenance` and the events of the
+    /// subscriptions are only sent by `poll_subscriptions`.
+    pub fn simulated<P: AsRef<Path>>(path: P) -> Result<EmbeddedServer, Error> {
+        let db = FlushPolicy::Os.configure(Config::new().path(path)).open()?;
+        info!("simulated server opened");
+
+        let clients = Clients::new(rate::Limit::default())
+            .with_polled_subscriptions(PolledSubscriptions::default());
+        Ok(EmbeddedServer::new(db, clients, Background::default()))
+    }
+
+    fn new(db: Db, clients: Clients, background: Background) -> EmbeddedServer {
+        server_info::started();
+
+        EmbeddedServer {
+            db,
+            clients,
+            replication: Replication::new(None, None),
+            acks: ReplicaAcks::new(ACK_TIMEOUT),
+            background: Arc::new(background),
+        }
+    }
+
+    /// Stops the threads of the server and closes the connections accepted by `listen`, then
+    /// waits for them to end. The database stays open for the embedded clients not dropped yet.
+    pub fn shutdown(&self) {
+        self.background.shutdown.raise();
+
+        let runtimes: Vec<_> = self.background.runtimes.lock().unwrap().drain(..).collect();
+        for runtime in runtimes {
+            let _ = runtime.shutdown_now().wait();
+        }
+
+        let threads: Vec<_> = self.background.threads.lock().unwrap().drain(..).collect();
+        for thread in threads {
+            if thread.join().is_err() {
+                error!("a thread of the embedded server panicked");
+            }
+        }
+
+        info!("embedded server shut down");
+    }
+
+    /// Writes the database to disk, returns the number of bytes written.
+    pub fn flush(&self) -> Result<usize, Error> {
+        Ok(self.db.flush()?)
+    }
+
+    /// Runs once what the background threads run periodically: the expiry, the compaction
+    /// and the publication of the scheduled events whose time has arrived.
+    pub fn run_maintenance(&self) -> Result<(), Error> {
+        retention::expire_events(&self.db)?;
+        compaction::compact_streams(&self.db)?;
+        scheduled::deliver_events(&self.db)?;
+        Ok(())
+    }
+
+    /// Sends the events published since the last call to the subscribers of a simulated
+    /// server, in the order the subscriptions were made. It does nothing for the other servers.
+    pub fn poll_subscriptions(&self) {
+        if let Some(polled) = self.clients.polled_subscriptions() {
+            polled.poll(&self.db);
+        }
+    }
+
+    /// Returns a new client of the server, it is authenticated as a superuser.
+    pub fn connect(&self) -> EmbeddedClient {
+        let addr = SocketAddr::from(LOOPBACK_ADDR);
+        let authentication = Authentication::new(None, None);
+        let (client, _killed) = self.clients.register(addr, authentication.user_name());
+        let (sender, receiver) = mpsc::channel(RESPONSES_CAPACITY);
+
+        EmbeddedClient {
+            server: self.clone(),
+            client,
+            authentication,
+            acks: self.acks.connection(),
+            sender,
+            responses: executor::spawn(receiver),
+        }
+    }
+
+    /// Accepts the RESP connections on this address in a background runtime, without TLS nor
+    /// authentication. Returns the address listened on, the port chosen by the OS if it is 0.
+    pub fn listen(&self, addr: &SocketAddr) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let server = self.clone();
+
+        let incoming = listener
+            .incoming()
+            .map_err(|e| error!("error accepting socket; {}", e))
+            .for_each(move |socket| {
+                let addr = match socket.peer_addr() {
+                    Ok(addr) => addr,
+                    Err(e) => {
+                        error!("error reading the address of the client; {}", e);
+                        return future::ok(());
+                    }
+                };
+
+                handle_connection(
+                    ServerCodec::default().framed(socket),
+                    addr,
+                    server.clients.clone(),
+                    server.db.clone(),
+                    Authentication::new(None, None),
+                    server.replication.clone(),
+                    server.acks.connection(),
+                );
+                future::ok(())
+            });
+
+        let mut runtime = Runtime::new()?;
+        runtime.spawn(incoming);
+        self.background.runtimes.lock().unwrap().push(runtime);
+        info!("embedded server is listening on {}", local_addr);
+
+        Ok(local_addr)
+    }
+}
+
+/// A client of an embedded server, the responses of its commands and the events of
+/// its subscriptions are received in the order they are sent, like on a connection.
+///
+/// The responses are kept until received, the events of a subscription
+/// accumulate in memory if the client does not call `recv`.
+pub struct EmbeddedClient {
+    server: EmbeddedServer,
+    client: Client,
+    authentication: Authentication,
+    acks: ConnectionAcks,
+    sender: mpsc::Sender<Result<Response, String>>,
+    responses: Spawn<mpsc::Receiver<Result<Response, String>>>,
+}
+
+impl EmbeddedClient {
+    /// Runs the command on the calling thread, it returns once the command has been run
+    /// and its responses are buffered until returned by `recv`. The events of the
+    /// subscriptions are sent later by the threads of the server, or by `poll_subscriptions`.
+    pub fn send(&mut self, request: Request) {
+        let result = dispatch_blocking(
+            request,
+            self.server.db.clone(),
+            self.sender.clone(),
+            &self.client,
+            &mut self.authentication,
+            &self.server.replication,
+            &mut self.acks,
+        );
+
+        if let Err(error) = result {
+            let error = ErrorCode::Err.error(error);
+            if self.sender.clone().send(Err(error)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+    }
+
+    /// Waits for the next response, the errors are the ones a connection receives.
+    pub fn recv(&mut self) -> Result<Response, String> {
+        match self.responses.wait_stream() {
+            Some(Ok(response)) => response,
+            // the client keeps a sender, the channel is never closed
+            _otherwise => Err(ErrorCode::Err.error("the responses channel is closed")),
+        }
+    }
+
+    /// Returns the next response if there is one, without waiting.
+    pub fn try_recv(&mut self) -> Option<Result<Response, String>> {
+        let notify = Arc::new(NoopNotify);
+        match self.responses.poll_stream_notify(&notify, 0) {
+            Ok(Async::Ready(Some(response))) => Some(response),
+            _otherwise => None,
+        }
+    }
+
+    /// Runs a command and waits for its response, the commands
+    /// sending several responses must use `send` and `recv`.
+    pub fn request(&mut self, request: Request) -> Result<Response, String> {
+        self.send(request);
+        self.recv()
+    }
+}
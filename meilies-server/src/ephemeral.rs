@@ -0,0 +1,293 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use tokio::sync::mpsc;
+use tracing::info;
+
+use meilies::reqresp::{ExpectedVersion, Response};
+use meilies::stream::{
+    Event, EventData, EventHeaders, EventName, EventNumber, Stream as EsStream,
+    StreamName as EsStreamName, Timestamp,
+};
+
+use super::{send_response, Subscription};
+
+/// The number of events kept by default for each ephemeral stream.
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// The interval at which a subscription waiting for events checks if it has been stopped.
+const WAIT_INTERVAL: Duration = Duration::from_millis(500);
+
+lazy_static! {
+    static ref STREAMS: Mutex<HashMap<EsStreamName, Arc<Ring>>> = Mutex::default();
+    static ref CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_CAPACITY);
+}
+
+/// The last events published to an ephemeral stream, the oldest
+/// ones are dropped when the capacity of the ring is reached.
+#[derive(Default)]
+struct Ring {
+    events: Mutex<Events>,
+    published: Condvar,
+}
+
+#[derive(Default)]
+struct Events {
+    buffer: VecDeque<Event>,
+    /// The number of the next event to be published.
+    next: u64,
+    /// The number of the first event kept, the events below have been trimmed or dropped.
+    first: u64,
+    deleted: bool,
+}
+
+impl Events {
+    fn last(&self) -> Option<EventNumber> {
+        self.next.checked_sub(1).map(EventNumber)
+    }
+
+    /// Drops the events below the given number.
+    fn drop_below(&mut self, number: u64) {
+        self.first = self.first.max(number).min(self.next);
+        while self
+            .buffer
+            .front()
+            .map_or(false, |e| e.number.0 < self.first)
+        {
+            self.buffer.pop_front();
+        }
+    }
+}
+
+/// Sets the number of events kept for each ephemeral stream.
+pub fn set_capacity(capacity: usize) {
+    CAPACITY.store(capacity, Ordering::SeqCst);
+}
+
+/// Returns the ring of the stream, creating it if it does not exist.
+fn ring(stream: &EsStreamName) -> Arc<Ring> {
+    let mut streams = STREAMS.lock().unwrap();
+    streams.entry(stream.clone()).or_default().clone()
+}
+
+fn existing_ring(stream: &EsStreamName) -> Option<Arc<Ring>> {
+    STREAMS.lock().unwrap().get(stream).cloned()
+}
+
+/// Appends the events to the stream if it is at the expected version, returns the numbers of
+/// the first and the last events appended or the last event number of the stream on conflict.
+pub fn publish(
+    stream: &EsStreamName,
+    events: Vec<(EventName, EventData, EventHeaders)>,
+    expected_version: Option<ExpectedVersion>,
+) -> Result<(EventNumber, EventNumber), Option<EventNumber>> {
+    let ring = ring(stream);
+    let mut state = ring.events.lock().unwrap();
+
+    if let Some(expected) = expected_version {
+        if !expected.matches(state.last()) {
+            return Err(state.last());
+        }
+    }
+
+    let timestamp = Timestamp::now();
+    let first = EventNumber(state.next);
+    for (name, data, headers) in events {
+        let number = EventNumber(state.next);
+        state.buffer.push_back(Event {
+            number,
+            timestamp,
+            name,
+            data,
+            headers,
+        });
+        state.next += 1;
+    }
+
+    let capacity = CAPACITY.load(Ordering::SeqCst) as u64;
+    let floor = state.next.saturating_sub(capacity);
+    state.drop_below(floor);
+
+    let last = state.last().unwrap_or(first);
+    drop(state);
+    ring.published.notify_all();
+
+    Ok((first, last))
+}
+
+/// Returns the events kept from `from` included to `to` excluded.
+/// Returns `true` if the ephemeral stream has been created or published to.
+pub fn exists(stream: &EsStreamName) -> bool {
+    existing_ring(stream).is_some()
+}
+
+pub fn read_range(stream: &EsStreamName, from: EventNumber, to: Option<EventNumber>) -> Vec<Event> {
+    let ring = match existing_ring(stream) {
+        Some(ring) => ring,
+        None => return Vec::new(),
+    };
+    let state = ring.events.lock().unwrap();
+    state
+        .buffer
+        .iter()
+        .filter(|e| e.number >= from && to.map_or(true, |to| e.number < to))
+        .cloned()
+        .collect()
+}
+
+pub fn last_event_number(stream: &EsStreamName) -> Option<EventNumber> {
+    let ring = existing_ring(stream)?;
+    let state = ring.events.lock().unwrap();
+    state.last()
+}
+
+/// Removes the events of the stream that are strictly below the given
+/// event number, returns the number of the first event kept.
+pub fn trim(stream: &EsStreamName, up_to: EventNumber) -> EventNumber {
+    let ring = ring(stream);
+    let mut state = ring.events.lock().unwrap();
+    state.drop_below(up_to.0);
+    EventNumber(state.first)
+}
+
+/// Creates the stream, it is kept until deleted even if no event is published to it.
+pub fn create(stream: &EsStreamName) {
+    ring(stream);
+}
+
+/// Deletes the stream and its events, the subscriptions to it are stopped.
+pub fn delete(stream: &EsStreamName) {
+    let ring = STREAMS.lock().unwrap().remove(stream);
+    if let Some(ring) = ring {
+        ring.events.lock().unwrap().deleted = true;
+        ring.published.notify_all();
+    }
+}
+
+pub fn stream_names() -> Vec<EsStreamName> {
+    STREAMS.lock().unwrap().keys().cloned().collect()
+}
+
+/// Sends the events of an ephemeral stream to the subscriber, the events that have been
+/// dropped from the ring are skipped and the subscriber is notified of where it resumes.
+pub fn send_stream_events(
+    stream: EsStream,
+    mut sender: mpsc::Sender<Result<Response, String>>,
+    subscription: Arc<Subscription>,
+) {
+    info!("ephemeral subscription on {} spawned", stream);
+
+    let ring = ring(&stream.name);
+    let end_number = stream.range.to().map(EventNumber);
+    let reached_end = |next_number| matches!(end_number, Some(end) if next_number >= end);
+    let range_end = Response::RangeEnd {
+        stream: stream.name.clone(),
+    };
+
+    let mut next_number = {
+        let state = ring.events.lock().unwrap();
+        match (stream.range.from(), stream.range.from_time()) {
+            (Some(from), _) => EventNumber(from),
+            (None, Some(timestamp)) => state
+                .buffer
+                .iter()
+                .find(|e| e.timestamp >= timestamp)
+                .map_or(EventNumber(state.next), |e| e.number),
+            (None, None) => EventNumber(state.next),
+        }
+    };
+
+    let mut caught_up = false;
+    loop {
+        if reached_end(next_number) {
+            send_response(sender, &subscription, range_end);
+            return;
+        }
+
+        let (first, events, deleted) = {
+            let mut state = ring.events.lock().unwrap();
+            // the history is sent before waiting for the events to be published
+            while caught_up && state.next <= next_number.0 && !state.deleted {
+                if subscription.is_unsubscribed() {
+                    info!("subscription stopped");
+                    return;
+                }
+                let (guard, _timeout) = ring.published.wait_timeout(state, WAIT_INTERVAL).unwrap();
+                state = guard;
+            }
+
+            let events: Vec<_> = state
+                .buffer
+                .iter()
+                .filter(|e| e.number >= next_number)
+                .cloned()
+                .collect();
+            (EventNumber(state.first), events, state.deleted)
+        };
+
+        if deleted {
+            let deleted = Response::StreamDeleted {
+                stream: stream.name.clone(),
+            };
+            send_response(sender, &subscription, deleted);
+            return;
+        }
+
+        // The events have been dropped from the ring before they were sent,
+        // we notify the client that the subscription resumes later.
+        if next_number < first {
+            next_number = first;
+            let trimmed = Response::StreamTrimmed {
+                stream: stream.name.clone(),
+                first,
+            };
+            sender = match send_response(sender, &subscription, trimmed) {
+                Some(sender) => sender,
+                None => return,
+            };
+        }
+
+        for event in events {
+            if reached_end(next_number) {
+                break;
+            }
+
+            next_number = event.number.next();
+            if !stream.accepts(&event.name) {
+                continue;
+            }
+
+            let event = Response::Event {
+                stream: stream.name.clone(),
+                number: event.number,
+                timestamp: event.timestamp,
+                event_name: event.name,
+                event_data: event.data,
+                headers: event.headers,
+                global_number: None,
+            };
+            sender = match send_response(sender, &subscription, event) {
+                Some(sender) => sender,
+                None => return,
+            };
+        }
+
+        if reached_end(next_number) {
+            continue;
+        }
+
+        if !caught_up {
+            caught_up = true;
+            let caught_up = Response::CaughtUp {
+                stream: stream.name.clone(),
+            };
+            sender = match send_response(sender, &subscription, caught_up) {
+                Some(sender) => sender,
+                None => return,
+            };
+        }
+    }
+}
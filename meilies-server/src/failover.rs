@@ -0,0 +1,412 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use futures::future::Either;
+use sled::Db;
+use tokio::prelude::*;
+use tokio::timer::Interval;
+use tracing::{error, info};
+
+use meilies::reqresp::{ErrorCode, Request, Response};
+use meilies::stream::{EventNumber, StreamName as EsStreamName};
+use meilies_client::compat::ClientConnection;
+use meilies_client::ConnectBuilder;
+
+use super::last_event_number;
+use super::replication::Replication;
+
+/// The tree that keeps the current term of the node and the node it voted for in this term.
+const CLUSTER_TREE: &str = "meilies:cluster";
+
+const TERM_KEY: &str = "term";
+const VOTED_FOR_KEY: &str = "voted-for";
+
+/// The interval at which the node checks its election timeout and sends its heartbeats.
+const TICK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The interval at which the leader sends heartbeats to the other nodes.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(150);
+
+/// The minimum time without heartbeat after which a node starts an election,
+/// a random delay of up to `ELECTION_JITTER_MS` is added to avoid split votes.
+const ELECTION_TIMEOUT: Duration = Duration::from_millis(1000);
+const ELECTION_JITTER_MS: u64 = 1000;
+
+/// The time after which a node that does not answer a request is considered unreachable.
+const RPC_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+struct State {
+    role: Role,
+    term: u64,
+    voted_for: Option<SocketAddr>,
+    leader: Option<SocketAddr>,
+    /// The node starts an election if it has not heard from a leader by then.
+    election_deadline: Instant,
+    next_heartbeat: Instant,
+    votes: usize,
+}
+
+/// The connections with the other nodes, at most one request is sent to a node at a time.
+#[derive(Default)]
+struct Peers {
+    idle: HashMap<SocketAddr, ClientConnection>,
+    busy: HashSet<SocketAddr>,
+}
+
+struct Inner {
+    db: Db,
+    node: SocketAddr,
+    peers: Vec<SocketAddr>,
+    username: Option<String>,
+    password: Option<String>,
+    state: Mutex<State>,
+    connections: Mutex<Peers>,
+}
+
+/// A node of a cluster that fails over to a new leader, the leader is elected using the
+/// Raft election algorithm. Only the election follows Raft: the leader is the only node
+/// accepting writes and the other nodes replicate it like the replicas of `--replica-of`,
+/// the event log is not replicated with the log matching of Raft and the events of a
+/// follower that diverged from the leader are not truncated. The publications are
+/// acknowledged once a majority of the nodes have received them.
+#[derive(Clone)]
+pub struct Failover {
+    inner: Arc<Inner>,
+}
+
+fn election_timeout() -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    ELECTION_TIMEOUT + Duration::from_millis(u64::from(nanos) % ELECTION_JITTER_MS)
+}
+
+impl Failover {
+    /// Creates the node at the given address, it starts as a follower of the term it was in.
+    pub fn new(
+        db: Db,
+        node: SocketAddr,
+        peers: Vec<SocketAddr>,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> sled::Result<Failover> {
+        let tree = db.open_tree(CLUSTER_TREE)?;
+        let term = tree.get(TERM_KEY)?;
+        let term = term.map_or(0, |t| {
+            u64::from_be_bytes(<[u8; 8]>::try_from(t.as_ref()).unwrap())
+        });
+        let voted_for = tree.get(VOTED_FOR_KEY)?;
+        let voted_for = voted_for.and_then(|v| String::from_utf8_lossy(&v).parse().ok());
+
+        let state = State {
+            role: Role::Follower,
+            term,
+            voted_for,
+            leader: None,
+            election_deadline: Instant::now() + election_timeout(),
+            next_heartbeat: Instant::now(),
+            votes: 0,
+        };
+
+        let inner = Inner {
+            db,
+            node,
+            peers,
+            username,
+            password,
+            state: Mutex::new(state),
+            connections: Mutex::default(),
+        };
+
+        Ok(Failover {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// The number of nodes of the cluster, this one included.
+    pub fn nodes(&self) -> usize {
+        self.inner.peers.len() + 1
+    }
+
+    /// Returns an error redirecting the client to the leader if this node is not the leader.
+    pub fn write_error(&self) -> Option<String> {
+        let state = self.inner.state.lock().unwrap();
+        match (state.role, state.leader) {
+            (Role::Leader, _) => None,
+            (_, Some(leader)) => Some(ErrorCode::Moved.error(leader)),
+            (_, None) => Some(ErrorCode::ClusterDown.error("no leader has been elected yet")),
+        }
+    }
+
+    /// Spawns the task that elects a leader and maintains its leadership.
+    ///
+    /// Must be called from a tokio runtime.
+    pub fn spawn(&self, replication: Replication) {
+        let failover = self.clone();
+        let ticks = Interval::new_interval(TICK_INTERVAL)
+            .map_err(|e| error!("failover timer error; {}", e))
+            .for_each(move |_| {
+                if let Err(e) = failover.tick(&replication) {
+                    error!("failover error; {}", e);
+                }
+                Ok(())
+            });
+
+        tokio::spawn(ticks);
+    }
+
+    fn tick(&self, replication: &Replication) -> sled::Result<()> {
+        let now = Instant::now();
+        let mut state = self.inner.state.lock().unwrap();
+        let role = state.role;
+
+        match role {
+            Role::Leader if now >= state.next_heartbeat => {
+                state.next_heartbeat = now + HEARTBEAT_INTERVAL;
+                let term = state.term;
+                drop(state);
+
+                for peer in &self.inner.peers {
+                    let heartbeat = Request::Heartbeat {
+                        term,
+                        leader: self.inner.node.to_string(),
+                    };
+                    self.call(*peer, heartbeat, |failover, term, _| {
+                        failover.observe_term(term)
+                    });
+                }
+            }
+            Role::Follower | Role::Candidate if now >= state.election_deadline => {
+                state.role = Role::Candidate;
+                state.term += 1;
+                state.voted_for = Some(self.inner.node);
+                state.leader = None;
+                state.votes = 1;
+                state.election_deadline = now + election_timeout();
+                self.persist(&state)?;
+
+                let term = state.term;
+                info!("starting the election of the term {}", term);
+
+                if state.votes > self.nodes() / 2 {
+                    return self.become_leader(&mut state, replication);
+                }
+                drop(state);
+
+                let last_global = last_event_number(&self.inner.db, &EsStreamName::all())?;
+                for peer in &self.inner.peers {
+                    let request = Request::RequestVote {
+                        term,
+                        candidate: self.inner.node.to_string(),
+                        last_global,
+                    };
+                    let replication = replication.clone();
+                    self.call(*peer, request, move |failover, reply_term, granted| {
+                        failover.vote_received(term, reply_term, granted, &replication)
+                    });
+                }
+            }
+            _otherwise => (),
+        }
+
+        Ok(())
+    }
+
+    fn persist(&self, state: &State) -> sled::Result<()> {
+        let tree = self.inner.db.open_tree(CLUSTER_TREE)?;
+        tree.insert(TERM_KEY, &state.term.to_be_bytes()[..])?;
+        match state.voted_for {
+            Some(node) => tree.insert(VOTED_FOR_KEY, node.to_string().as_bytes())?,
+            None => tree.remove(VOTED_FOR_KEY)?,
+        };
+        tree.flush()?;
+        Ok(())
+    }
+
+    /// Becomes a follower of the given term if it is greater than the current one.
+    fn step_down(&self, state: &mut State, term: u64) -> sled::Result<()> {
+        if term > state.term {
+            if state.role == Role::Leader {
+                info!("stepping down, a node is at the term {}", term);
+            }
+            state.role = Role::Follower;
+            state.term = term;
+            state.voted_for = None;
+            state.leader = None;
+            state.election_deadline = Instant::now() + election_timeout();
+            self.persist(state)?;
+        }
+        Ok(())
+    }
+
+    fn observe_term(&self, term: u64) -> sled::Result<()> {
+        let mut state = self.inner.state.lock().unwrap();
+        self.step_down(&mut state, term)
+    }
+
+    fn vote_received(
+        &self,
+        election_term: u64,
+        term: u64,
+        granted: bool,
+        replication: &Replication,
+    ) -> sled::Result<()> {
+        let mut state = self.inner.state.lock().unwrap();
+        self.step_down(&mut state, term)?;
+
+        if state.role == Role::Candidate && state.term == election_term && granted {
+            state.votes += 1;
+            if state.votes > self.nodes() / 2 {
+                self.become_leader(&mut state, replication)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn become_leader(&self, state: &mut State, replication: &Replication) -> sled::Result<()> {
+        info!("elected leader of the term {}", state.term);
+        state.role = Role::Leader;
+        state.leader = Some(self.inner.node);
+        state.next_heartbeat = Instant::now();
+        replication.replicate(self.inner.db.clone(), None)
+    }
+
+    /// Answers the vote request of a candidate, a node votes for a single candidate per term
+    /// and only if the candidate has all the events it has. Returns the term of the node
+    /// and whether the vote is granted.
+    pub fn request_vote(
+        &self,
+        term: u64,
+        candidate: &str,
+        last_global: Option<EventNumber>,
+    ) -> sled::Result<(u64, bool)> {
+        let mut state = self.inner.state.lock().unwrap();
+        self.step_down(&mut state, term)?;
+
+        let candidate = match candidate.parse() {
+            Ok(candidate) => candidate,
+            Err(_) => return Ok((state.term, false)),
+        };
+
+        let local_last = last_event_number(&self.inner.db, &EsStreamName::all())?;
+        let granted = term == state.term
+            && last_global >= local_last
+            && state.voted_for.map_or(true, |node| node == candidate);
+
+        if granted {
+            state.voted_for = Some(candidate);
+            state.election_deadline = Instant::now() + election_timeout();
+            self.persist(&state)?;
+        }
+
+        Ok((state.term, granted))
+    }
+
+    /// Accepts the leader of the term if it is not outdated, the node
+    /// then replicates it. Returns the term of the node and whether the leader is accepted.
+    pub fn heartbeat(
+        &self,
+        term: u64,
+        leader: &str,
+        replication: &Replication,
+    ) -> sled::Result<(u64, bool)> {
+        let mut state = self.inner.state.lock().unwrap();
+        self.step_down(&mut state, term)?;
+
+        let leader = match leader.parse() {
+            Ok(leader) if term == state.term => leader,
+            _otherwise => return Ok((state.term, false)),
+        };
+
+        state.role = Role::Follower;
+        state.election_deadline = Instant::now() + election_timeout();
+
+        if state.leader != Some(leader) {
+            info!("{} is the leader of the term {}", leader, term);
+            state.leader = Some(leader);
+            replication.replicate(self.inner.db.clone(), Some(leader))?;
+        }
+
+        Ok((state.term, true))
+    }
+
+    /// Sends a request to another node and calls the given function with its term and answer,
+    /// the request is not sent if the previous one has not been answered yet.
+    fn call<F>(&self, peer: SocketAddr, request: Request, on_reply: F)
+    where
+        F: FnOnce(&Failover, u64, bool) -> sled::Result<()> + Send + 'static,
+    {
+        let connection = {
+            let mut connections = self.inner.connections.lock().unwrap();
+            if !connections.busy.insert(peer) {
+                return;
+            }
+            connections.idle.remove(&peer)
+        };
+
+        let connection = match connection {
+            Some(connection) => Either::A(future::ok(connection)),
+            None => {
+                let mut builder = ConnectBuilder::new(peer);
+                if let Some(username) = &self.inner.username {
+                    builder = builder.username(username.as_str());
+                }
+                if let Some(password) = &self.inner.password {
+                    builder = builder.password(password.as_str());
+                }
+                Either::B(builder.connect().map_err(|e| e.to_string()))
+            }
+        };
+
+        let failover = self.clone();
+        let call = connection
+            .and_then(|connection| connection.send(request).map_err(|e| e.to_string()))
+            .and_then(|connection| connection.into_future().map_err(|(e, _)| e.to_string()))
+            .timeout(RPC_TIMEOUT)
+            .then(move |result| {
+                let mut connections = failover.inner.connections.lock().unwrap();
+                connections.busy.remove(&peer);
+
+                let reply = match result {
+                    Ok((Some(reply), connection)) => {
+                        connections.idle.insert(peer, connection);
+                        reply
+                    }
+                    Ok((None, _)) => {
+                        info!("node {} closed the connection", peer);
+                        return Ok(());
+                    }
+                    Err(_) => {
+                        info!("node {} is unreachable", peer);
+                        return Ok(());
+                    }
+                };
+                drop(connections);
+
+                match reply {
+                    Ok(Response::RaftReply { term, success }) => {
+                        if let Err(e) = on_reply(&failover, term, success) {
+                            error!("failover error; {}", e);
+                        }
+                    }
+                    Ok(response) => error!("invalid response of node {}; {:?}", peer, response),
+                    Err(e) => error!("error received from node {}; {}", peer, e),
+                }
+
+                Ok(())
+            });
+
+        tokio::spawn(call);
+    }
+}
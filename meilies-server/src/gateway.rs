@@ -0,0 +1,634 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::str;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use futures::{Future, Sink, Stream};
+use sled::Db;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use meilies::reqresp::{ErrorCode, Request, Response};
+use meilies::stream::{Event, EventData, EventHeaders, EventName, EventNumber};
+use meilies::stream::{Stream as EsStream, StreamName as EsStreamName, TRACEPARENT_HEADER};
+
+use super::acks::ReplicaAcks;
+use super::acl::{Acl, Authentication};
+use super::clients::Clients;
+use super::dispatch;
+use super::dispatch_blocking;
+use super::replication::Replication;
+use super::websocket::{self, Handshake, WEBSOCKET_PATH};
+
+/// The time a client has to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The path under which the streams are served, followed by the name of the stream.
+const STREAMS_PATH: &str = "/streams/";
+
+/// The time a subscriber has to read an event before the connection is closed.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The interval at which a comment is sent to an idle subscriber, the
+/// disconnections are noticed and the proxies do not close the connection.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The path segment that follows the name of a stream to subscribe to it.
+const SUBSCRIBE_SEGMENT: &str = "subscribe";
+
+/// The header a reconnecting `EventSource` sends with the id of the last event received.
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+/// The maximum size of the body of a publication if no `--max-event-size` is given.
+const DEFAULT_MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// The number of events returned by a read when no `limit` is given.
+const DEFAULT_LIMIT: u64 = 100;
+
+/// What the gateway shares with the RESP listener to run the commands.
+#[derive(Clone)]
+pub struct Gateway {
+    pub db: Db,
+    pub clients: Clients,
+    pub replication: Replication,
+    pub acks: ReplicaAcks,
+    pub requirepass: Option<String>,
+    pub acl: Option<Arc<Acl>>,
+    /// The maximum size in bytes of the body of a publication, like the `--max-event-size`
+    /// of the RESP connections, `DEFAULT_MAX_BODY_SIZE` if it is not set.
+    pub max_event_size: Option<usize>,
+}
+
+impl Gateway {
+    /// The maximum size in bytes of a body or of a WebSocket message.
+    pub fn max_body_size(&self) -> usize {
+        self.max_event_size.unwrap_or(DEFAULT_MAX_BODY_SIZE)
+    }
+}
+
+/// What is done with an authenticated request.
+enum Action {
+    Command(Request),
+    WebSocket(Handshake),
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    /// The headers with their names in lowercase.
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+struct HttpResponse {
+    status: u16,
+    body: String,
+}
+
+impl HttpResponse {
+    fn ok(body: String) -> HttpResponse {
+        HttpResponse { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> HttpResponse {
+        let body = format!("{{\"error\":{}}}", json_string(message));
+        HttpResponse { status, body }
+    }
+
+    /// Returns the response of an error sent by the server, the status depends on its code.
+    fn server_error(error: &str) -> HttpResponse {
+        let status = match ErrorCode::split(error).map(|(code, _)| code) {
+            Some(ErrorCode::NoAuth) | Some(ErrorCode::WrongPass) => 401,
+            Some(ErrorCode::NoPerm) => 403,
+            Some(ErrorCode::NoStream) => 404,
+            Some(ErrorCode::WrongVer) => 409,
+            Some(ErrorCode::Tombstoned) => 410,
+            Some(ErrorCode::Throttled) => 429,
+            Some(ErrorCode::NotReplicated) => 504,
+            Some(ErrorCode::ReadOnly) | Some(ErrorCode::Moved) | Some(ErrorCode::ClusterDown) => {
+                503
+            }
+            Some(ErrorCode::DiskFull) => 507,
+            _otherwise => 400,
+        };
+        HttpResponse::error(status, error)
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            410 => "Gone",
+            413 => "Payload Too Large",
+            429 => "Too Many Requests",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            507 => "Insufficient Storage",
+            _otherwise => "Internal Server Error",
+        };
+
+        let mut headers = format!("HTTP/1.1 {} {}\r\n", self.status, reason);
+        if self.status == 401 {
+            headers.push_str("WWW-Authenticate: Basic realm=\"meilies\"\r\n");
+        }
+        headers.push_str(&format!(
+            "Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        ));
+        writer.write_all(headers.as_bytes())?;
+        writer.write_all(self.body.as_bytes())
+    }
+}
+
+/// Decodes the `%XX` sequences and the `+` of a path segment or a query parameter.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'%' => {
+                let hex = [iter.next()?, iter.next()?];
+                let hex = str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            b'+' => bytes.push(b' '),
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Formats an event as a JSON object, the data is a string if it is valid UTF-8
+/// and is encoded in base64 under the `data_base64` field otherwise.
+pub fn json_event(event: &Event) -> String {
+    let data = match str::from_utf8(&event.data.0) {
+        Ok(data) => format!("\"data\":{}", json_string(data)),
+        Err(_) => format!("\"data_base64\":\"{}\"", base64::encode(&event.data.0)),
+    };
+
+    let headers: Vec<_> = event
+        .headers
+        .0
+        .iter()
+        .map(|(key, value)| format!("{}:{}", json_string(key), json_string(value)))
+        .collect();
+
+    format!(
+        "{{\"number\":{},\"timestamp\":{},\"name\":{},{},\"headers\":{{{}}}}}",
+        event.number.0,
+        event.timestamp.0,
+        json_string(event.name.as_str()),
+        data,
+        headers.join(",")
+    )
+}
+
+fn read_request(stream: &TcpStream, max_body_size: usize) -> Result<HttpRequest, HttpResponse> {
+    let invalid = |_: io::Error| HttpResponse::error(400, "invalid HTTP request");
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).map_err(invalid)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_owned(), target),
+        _otherwise => return Err(HttpResponse::error(400, "invalid HTTP request")),
+    };
+
+    let (path, query) = match target.find('?') {
+        Some(index) => (&target[..index], &target[index + 1..]),
+        None => (target, ""),
+    };
+    let query = query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (name, value) = match p.find('=') {
+                Some(index) => (&p[..index], &p[index + 1..]),
+                None => (p, ""),
+            };
+            Some((percent_decode(name)?, percent_decode(value)?))
+        })
+        .collect::<Option<_>>()
+        .ok_or_else(|| HttpResponse::error(400, "invalid query string"))?;
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(invalid)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some(index) = line.find(':') {
+            let name = line[..index].trim().to_lowercase();
+            headers.push((name, line[index + 1..].trim().to_owned()));
+        }
+    }
+
+    let mut request = HttpRequest {
+        method,
+        path: path.to_owned(),
+        query,
+        headers,
+        body: Vec::new(),
+    };
+
+    let length = match request.header("content-length") {
+        Some(length) => length
+            .parse()
+            .map_err(|_| HttpResponse::error(400, "invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > max_body_size {
+        return Err(HttpResponse::error(413, "the body is too large"));
+    }
+
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).map_err(invalid)?;
+
+    Ok(request)
+}
+
+/// Authenticates a request with its `Authorization` header, the Basic scheme carries the
+/// user and the password of the ACL, or an empty user and the `--requirepass` password.
+pub fn authenticate(
+    gateway: &Gateway,
+    authorization: Option<&str>,
+) -> Result<Authentication, String> {
+    let mut authentication = Authentication::new(gateway.requirepass.clone(), gateway.acl.clone());
+
+    let credentials = match authorization {
+        Some(authorization) if authorization.starts_with("Basic ") => {
+            let decoded = base64::decode(&authorization["Basic ".len()..]).ok();
+            decoded.and_then(|d| String::from_utf8(d).ok())
+        }
+        Some(_) => None,
+        None => return Ok(authentication),
+    };
+
+    let credentials =
+        credentials.ok_or_else(|| ErrorCode::Err.error("invalid Authorization header"))?;
+    let (username, password) = match credentials.find(':') {
+        Some(index) => (&credentials[..index], &credentials[index + 1..]),
+        None => return Err(ErrorCode::Err.error("invalid Authorization header")),
+    };
+    let username = if username.is_empty() {
+        None
+    } else {
+        Some(username)
+    };
+
+    authentication.authenticate(username, password)?;
+    Ok(authentication)
+}
+
+/// Returns the value of a numeric query parameter, if it is given.
+fn number_param(request: &HttpRequest, name: &str) -> Result<Option<u64>, HttpResponse> {
+    match request.query(name) {
+        Some(value) => value.parse().map(Some).map_err(|_| {
+            let message = format!("the {} parameter must be a number", name);
+            HttpResponse::error(400, &message)
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Returns the command of the request, `POST /streams/{name}?event={event-name}` publishes
+/// the body as an event, `GET /streams/{name}?from=0&limit=100` reads events and
+/// `GET /streams/{name}/subscribe?from=0` subscribes to the stream.
+fn route(request: &HttpRequest) -> Result<Request, HttpResponse> {
+    let path = match request.path.find(STREAMS_PATH) {
+        Some(0) => &request.path[STREAMS_PATH.len()..],
+        _otherwise => return Err(HttpResponse::error(404, "not found")),
+    };
+    let mut segments = path.splitn(2, '/');
+    let stream = segments.next().filter(|s| !s.is_empty());
+    let (stream, subscribe) = match (stream, segments.next()) {
+        (Some(stream), None) => (stream, false),
+        (Some(stream), Some(SUBSCRIBE_SEGMENT)) => (stream, true),
+        _otherwise => return Err(HttpResponse::error(404, "not found")),
+    };
+    let stream = percent_decode(stream)
+        .and_then(|name| EsStreamName::new(name).ok())
+        .ok_or_else(|| HttpResponse::error(400, "invalid stream name"))?;
+
+    match request.method.as_str() {
+        "POST" if !subscribe => {
+            let event_name = request
+                .query("event")
+                .and_then(|name| EventName::new(name.to_owned()).ok())
+                .ok_or_else(|| HttpResponse::error(400, "the event parameter is required"))?;
+
+            // the trace context of the caller is propagated like the one of a RESP client
+            let mut headers = EventHeaders::new();
+            if let Some(traceparent) = request.header(TRACEPARENT_HEADER) {
+                headers.insert(TRACEPARENT_HEADER.to_owned(), traceparent.to_owned());
+            }
+
+            Ok(Request::Publish {
+                stream,
+                event_name,
+                event_data: EventData(request.body.clone().into()),
+                expected_version: None,
+                dedup_id: None,
+                headers,
+                deliver_at: None,
+                ack: None,
+            })
+        }
+        "GET" if subscribe => {
+            // a reconnecting EventSource resumes after the last event it received
+            let from = match request.header(LAST_EVENT_ID_HEADER) {
+                Some(id) => match id.parse::<u64>() {
+                    Ok(id) => Some(id + 1),
+                    Err(_) => return Err(HttpResponse::error(400, "invalid Last-Event-ID")),
+                },
+                None => number_param(request, "from")?,
+            };
+
+            let stream = EsStream::new_from_to(stream, from, None);
+            Ok(Request::Subscribe {
+                streams: vec![stream],
+            })
+        }
+        "GET" => {
+            let from = number_param(request, "from")?.unwrap_or(0);
+            let limit = number_param(request, "limit")?.unwrap_or(DEFAULT_LIMIT);
+
+            Ok(Request::GetRange {
+                stream,
+                from: Some(EventNumber(from)),
+                to: Some(EventNumber(from.saturating_add(limit))),
+            })
+        }
+        _otherwise => Err(HttpResponse::error(405, "method not allowed")),
+    }
+}
+
+/// Runs the command like the ones of the RESP connections and returns its response.
+fn run(
+    gateway: &Gateway,
+    addr: SocketAddr,
+    mut authentication: Authentication,
+    request: Request,
+) -> HttpResponse {
+    let (client, _killed) = gateway.clients.register(addr, authentication.user_name());
+    let mut acks = gateway.acks.connection();
+    // the commands of the gateway send a single response
+    let (sender, receiver) = mpsc::channel(1);
+
+    let result = dispatch_blocking(
+        request,
+        gateway.db.clone(),
+        sender,
+        &client,
+        &mut authentication,
+        &gateway.replication,
+        &mut acks,
+    );
+    if let Err(e) = result {
+        error!("error; {}", e);
+        return HttpResponse::error(500, &e.to_string());
+    }
+
+    match receiver.wait().next() {
+        Some(Ok(Ok(Response::Published { stream, number }))) => HttpResponse::ok(format!(
+            "{{\"stream\":{},\"number\":{}}}",
+            json_string(stream.as_str()),
+            number.0
+        )),
+        Some(Ok(Ok(Response::Range { stream, events }))) => {
+            let events: Vec<_> = events.iter().map(json_event).collect();
+            HttpResponse::ok(format!(
+                "{{\"stream\":{},\"events\":[{}]}}",
+                json_string(stream.as_str()),
+                events.join(",")
+            ))
+        }
+        Some(Ok(Err(error))) => HttpResponse::server_error(&error),
+        _otherwise => HttpResponse::error(500, "unexpected response"),
+    }
+}
+
+/// Sends a comment to an idle subscriber every `KEEPALIVE_INTERVAL`, the
+/// thread stops once the subscriber is gone and the receiver dropped.
+fn spawn_keepalive(sender: mpsc::Sender<Result<Response, String>>) -> io::Result<()> {
+    thread::Builder::new()
+        .name(String::from("http-keepalive"))
+        .spawn(move || {
+            let mut sender = sender;
+            loop {
+                thread::sleep(KEEPALIVE_INTERVAL);
+                sender = match sender.send(Ok(Response::Pong { payload: None })).wait() {
+                    Ok(sender) => sender,
+                    Err(_) => return,
+                };
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Subscribes to the stream and sends its events as Server-Sent Events, with the event
+/// numbers as ids, until the subscriber disconnects or the stream is deleted.
+fn subscribe(
+    gateway: &Gateway,
+    addr: SocketAddr,
+    mut authentication: Authentication,
+    request: Request,
+    mut writer: TcpStream,
+) -> io::Result<()> {
+    let (client, _killed) = gateway.clients.register(addr, authentication.user_name());
+    let mut acks = gateway.acks.connection();
+    let (sender, receiver) = mpsc::channel(10);
+    let keepalive = sender.clone();
+
+    let result = dispatch(
+        request,
+        gateway.db.clone(),
+        sender,
+        &client,
+        &mut authentication,
+        &gateway.replication,
+        &mut acks,
+    );
+    if let Err(e) = result {
+        error!("error; {}", e);
+        return HttpResponse::error(500, &e.to_string()).write_to(&mut writer);
+    }
+
+    // the errors sent before the subscription starts are answered with a status
+    let mut responses = receiver.wait();
+    match responses.next() {
+        Some(Ok(Ok(Response::Subscribed { .. }))) => (),
+        Some(Ok(Err(error))) => return HttpResponse::server_error(&error).write_to(&mut writer),
+        _otherwise => return HttpResponse::error(500, "unexpected response").write_to(&mut writer),
+    }
+
+    writer.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    writer.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+          Cache-Control: no-cache\r\nConnection: close\r\n\r\n",
+    )?;
+    spawn_keepalive(keepalive)?;
+
+    for response in responses {
+        let (message, last) = match response {
+            Ok(Ok(Response::Event {
+                number,
+                timestamp,
+                event_name,
+                event_data,
+                headers,
+                ..
+            })) => {
+                let event = Event {
+                    number,
+                    timestamp,
+                    name: event_name,
+                    data: event_data,
+                    headers,
+                };
+                let message = format!("id: {}\ndata: {}\n\n", number.0, json_event(&event));
+                (message, false)
+            }
+            Ok(Ok(Response::CaughtUp { stream })) => {
+                let data = format!("{{\"stream\":{}}}", json_string(stream.as_str()));
+                (format!("event: caught-up\ndata: {}\n\n", data), false)
+            }
+            Ok(Ok(Response::StreamDeleted { stream })) => {
+                let data = format!("{{\"stream\":{}}}", json_string(stream.as_str()));
+                (format!("event: deleted\ndata: {}\n\n", data), true)
+            }
+            Ok(Ok(Response::Pong { .. })) => (String::from(": keepalive\n\n"), false),
+            Ok(Err(error)) => {
+                let data = format!("{{\"error\":{}}}", json_string(&error));
+                (format!("event: error\ndata: {}\n\n", data), true)
+            }
+            _otherwise => continue,
+        };
+
+        if writer.write_all(message.as_bytes()).is_err() {
+            info!("subscriber {} disconnected", addr);
+            break;
+        }
+        if last {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn serve(gateway: &Gateway, stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let addr = stream.peer_addr()?;
+
+    let action = read_request(&stream, gateway.max_body_size()).and_then(|request| {
+        let authentication = authenticate(gateway, request.header("authorization"))
+            .map_err(|e| HttpResponse::server_error(&e))?;
+        if request.path == WEBSOCKET_PATH {
+            let handshake = Handshake::new(
+                &request.method,
+                request.header("upgrade"),
+                request.header("sec-websocket-key"),
+                request.header("sec-websocket-protocol"),
+            );
+            let handshake =
+                handshake.ok_or_else(|| HttpResponse::error(400, "invalid WebSocket upgrade"))?;
+            return Ok((authentication, Action::WebSocket(handshake)));
+        }
+        let command = route(&request)?;
+        Ok((authentication, Action::Command(command)))
+    });
+
+    let mut writer = stream;
+    match action {
+        Ok((authentication, Action::WebSocket(handshake))) => {
+            websocket::serve(gateway, addr, authentication, handshake, writer)
+        }
+        Ok((authentication, Action::Command(command @ Request::Subscribe { .. }))) => {
+            subscribe(gateway, addr, authentication, command, writer)
+        }
+        Ok((authentication, Action::Command(command))) => {
+            run(gateway, addr, authentication, command).write_to(&mut writer)
+        }
+        Err(response) => response.write_to(&mut writer),
+    }
+}
+
+/// Spawns the thread that accepts the HTTP connections, each one is served by its own
+/// thread as the publications can wait for the replicas and the subscriptions last.
+pub fn spawn(gateway: Gateway, addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    info!("the HTTP gateway is listening on http://{}", addr);
+
+    thread::Builder::new()
+        .name(String::from("http-gateway"))
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        error!("error accepting an HTTP connection; {}", e);
+                        continue;
+                    }
+                };
+
+                let gateway = gateway.clone();
+                let spawned = thread::Builder::new()
+                    .name(String::from("http-connection"))
+                    .spawn(move || {
+                        if let Err(e) = serve(&gateway, stream) {
+                            error!("error serving an HTTP request; {}", e);
+                        }
+                    });
+                if let Err(e) = spawned {
+                    error!("error spawning an HTTP connection thread; {}", e);
+                }
+            }
+        })?;
+
+    Ok(())
+}
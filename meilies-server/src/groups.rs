@@ -0,0 +1,403 @@
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use sled::{Db, Event};
+use tokio::sync::mpsc;
+use tracing::info;
+
+use meilies::reqresp::Response;
+use meilies::stream::{EventNumber, GroupName, PendingEvent, ReadRange, Timestamp};
+use meilies::stream::{Stream as EsStream, StreamName as EsStreamName};
+
+use super::Subscription;
+use super::TOMBSTONES_TREE;
+use super::{event_response, first_event_number, last_event_number, send_response};
+
+/// The tree that keeps the next event number to deliver to each consumer group,
+/// keys are the stream name followed by a colon and the group name.
+const GROUP_OFFSETS_TREE: &str = "meilies:group-offsets";
+
+/// The tree that keeps the events delivered to a consumer group member but not yet
+/// acknowledged, keys are the group key followed by a colon and the event number.
+const GROUP_PENDING_TREE: &str = "meilies:group-pending";
+
+/// The tree that keeps the offsets committed by the consumer groups,
+/// keys are the group key and values the number of the last processed event.
+const COMMITTED_OFFSETS_TREE: &str = "meilies:committed-offsets";
+
+fn group_key(stream: &EsStreamName, group: &GroupName) -> Vec<u8> {
+    format!("{}:{}", stream, group).into_bytes()
+}
+
+fn pending_prefix(stream: &EsStreamName, group: &GroupName) -> Vec<u8> {
+    let mut prefix = group_key(stream, group);
+    prefix.push(b':');
+    prefix
+}
+
+fn pending_key(stream: &EsStreamName, group: &GroupName, number: EventNumber) -> Vec<u8> {
+    let mut key = pending_prefix(stream, group);
+    key.extend_from_slice(&number.to_be_bytes());
+    key
+}
+
+/// The current time, the virtual time of the simulation for a simulated server.
+fn now_millis() -> u64 {
+    Timestamp::now().0
+}
+
+/// An event delivered to a consumer group member that has not been acknowledged yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingEntry {
+    /// The consumer the event has been delivered to.
+    pub consumer: String,
+    /// The time of the last delivery, in milliseconds since the UNIX epoch.
+    pub delivered_at: u64,
+    /// The number of times the event has been delivered.
+    pub deliveries: u64,
+}
+
+impl PendingEntry {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + self.consumer.len());
+        bytes.extend_from_slice(&self.delivered_at.to_be_bytes());
+        bytes.extend_from_slice(&self.deliveries.to_be_bytes());
+        bytes.extend_from_slice(self.consumer.as_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> PendingEntry {
+        let (delivered_at, bytes) = bytes.split_at(8);
+        let (deliveries, consumer) = bytes.split_at(8);
+
+        PendingEntry {
+            consumer: String::from_utf8_lossy(consumer).into_owned(),
+            delivered_at: u64::from_be_bytes(<[u8; 8]>::try_from(delivered_at).unwrap()),
+            deliveries: u64::from_be_bytes(<[u8; 8]>::try_from(deliveries).unwrap()),
+        }
+    }
+}
+
+/// Reserves the next event of the stream that was never delivered to the group,
+/// returns `None` if all the events of the stream have been delivered.
+pub fn claim_next_event(
+    db: &Db,
+    stream: &EsStreamName,
+    group: &GroupName,
+) -> sled::Result<Option<EventNumber>> {
+    let offsets = db.open_tree(GROUP_OFFSETS_TREE)?;
+    let key = group_key(stream, group);
+
+    loop {
+        let current = offsets.get(&key)?;
+        let mut next = match &current {
+            Some(number) => EventNumber::try_from(number.as_ref()).unwrap(),
+            None => EventNumber::zero(),
+        };
+
+        // the first events of the stream could have been trimmed
+        if let Some(first) = first_event_number(db, stream)? {
+            next = next.max(first);
+        }
+
+        match last_event_number(db, stream)? {
+            Some(last) if next <= last => (),
+            _otherwise => return Ok(None),
+        }
+
+        let new = next.next().to_be_bytes();
+        if offsets
+            .compare_and_swap(&key, current, Some(&new[..]))?
+            .is_ok()
+        {
+            return Ok(Some(next));
+        }
+    }
+}
+
+/// Records that the event has been delivered to the consumer.
+fn mark_pending(
+    db: &Db,
+    stream: &EsStreamName,
+    group: &GroupName,
+    number: EventNumber,
+    consumer: &str,
+) -> sled::Result<()> {
+    let pending = db.open_tree(GROUP_PENDING_TREE)?;
+    let key = pending_key(stream, group, number);
+
+    pending.update_and_fetch(key, |previous| {
+        let deliveries = previous.map_or(0, |p| PendingEntry::from_bytes(p).deliveries);
+        let entry = PendingEntry {
+            consumer: consumer.to_owned(),
+            delivered_at: now_millis(),
+            deliveries: deliveries + 1,
+        };
+        Some(entry.to_bytes())
+    })?;
+
+    Ok(())
+}
+
+/// Removes the event from the pending events of the group.
+pub fn ack(
+    db: &Db,
+    stream: &EsStreamName,
+    group: &GroupName,
+    number: EventNumber,
+) -> sled::Result<()> {
+    let pending = db.open_tree(GROUP_PENDING_TREE)?;
+    pending.remove(pending_key(stream, group, number))?;
+    Ok(())
+}
+
+/// Lists the events delivered to the members of the group and not yet acknowledged.
+pub fn pending_events(
+    db: &Db,
+    stream: &EsStreamName,
+    group: &GroupName,
+) -> sled::Result<Vec<PendingEvent>> {
+    let pending = db.open_tree(GROUP_PENDING_TREE)?;
+    let prefix = pending_prefix(stream, group);
+    let now = now_millis();
+
+    let mut events = Vec::new();
+    for result in pending.scan_prefix(&prefix) {
+        let (key, value) = result?;
+        let entry = PendingEntry::from_bytes(&value);
+        events.push(PendingEvent {
+            number: EventNumber::try_from(&key[prefix.len()..]).unwrap(),
+            consumer: entry.consumer,
+            idle: now.saturating_sub(entry.delivered_at),
+            deliveries: entry.deliveries,
+        });
+    }
+
+    Ok(events)
+}
+
+/// Transfers a pending event to another member of the group and returns it,
+/// returns `None` if the event is not pending or has been trimmed.
+pub fn claim(
+    db: &Db,
+    stream: &EsStreamName,
+    group: &GroupName,
+    number: EventNumber,
+    consumer: &str,
+) -> sled::Result<Option<Response>> {
+    let tree = db.open_tree(stream.as_ref())?;
+    let pending = db.open_tree(GROUP_PENDING_TREE)?;
+    let pending_key = pending_key(stream, group, number);
+
+    let key = number.to_be_bytes();
+    let value = match tree.get(key)? {
+        Some(value) => value,
+        None => {
+            pending.remove(pending_key)?;
+            return Ok(None);
+        }
+    };
+
+    let claimed = pending.update_and_fetch(pending_key, |previous| {
+        previous.map(|p| {
+            let entry = PendingEntry {
+                consumer: consumer.to_owned(),
+                delivered_at: now_millis(),
+                deliveries: PendingEntry::from_bytes(p).deliveries + 1,
+            };
+            entry.to_bytes()
+        })
+    })?;
+
+    if claimed.is_none() {
+        return Ok(None);
+    }
+
+    let es_stream = EsStream::from(stream.clone());
+    Ok(event_response(&es_stream, &key, value))
+}
+
+/// Stores the number of the last event of the stream processed by the group.
+pub fn commit_offset(
+    db: &Db,
+    stream: &EsStreamName,
+    group: &GroupName,
+    number: EventNumber,
+) -> sled::Result<()> {
+    let offsets = db.open_tree(COMMITTED_OFFSETS_TREE)?;
+    offsets.insert(group_key(stream, group), &number.to_be_bytes())?;
+    Ok(())
+}
+
+/// Returns the number of the last event of the stream processed by the group, if any.
+pub fn fetch_offset(
+    db: &Db,
+    stream: &EsStreamName,
+    group: &GroupName,
+) -> sled::Result<Option<EventNumber>> {
+    let offsets = db.open_tree(COMMITTED_OFFSETS_TREE)?;
+    let offset = offsets.get(group_key(stream, group))?;
+    Ok(offset.map(|n| EventNumber::try_from(n.as_ref()).unwrap()))
+}
+
+/// Moves the start of the stream range just after the offset committed
+/// by its consumer group, the range is kept if nothing has been committed yet.
+pub fn resolve_committed(db: &Db, stream: EsStream) -> sled::Result<EsStream> {
+    let group = match &stream.committed {
+        Some(group) => group,
+        None => return Ok(stream),
+    };
+
+    let next = match fetch_offset(db, &stream.name, group)? {
+        Some(number) => number.next().0,
+        None => return Ok(stream),
+    };
+
+    let range = match stream.range.to() {
+        Some(to) if next >= to => ReadRange::ReadFromUntil(to, to),
+        Some(to) => ReadRange::ReadFromUntil(next, to),
+        None => ReadRange::ReadFrom(next),
+    };
+
+    Ok(EsStream { range, ..stream })
+}
+
+/// Removes the state of all the consumer groups of a stream.
+pub fn clear_stream_groups(db: &Db, stream: &EsStreamName) -> sled::Result<()> {
+    let mut prefix = stream.as_str().as_bytes().to_vec();
+    prefix.push(b':');
+
+    for name in &[
+        GROUP_OFFSETS_TREE,
+        GROUP_PENDING_TREE,
+        COMMITTED_OFFSETS_TREE,
+    ] {
+        let tree = db.open_tree(name)?;
+        for result in tree.scan_prefix(&prefix) {
+            let (key, _) = result?;
+            tree.remove(key)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Delivers the events of a stream to a consumer group member.
+///
+/// The events previously delivered to this consumer and not yet acknowledged are
+/// delivered again first, then the consumer competes with the other members of the group
+/// for the events that were never delivered, each one being delivered to a single member.
+/// Returns the events delivered to the consumer and not acknowledged yet, they are delivered
+/// again when the consumer subscribes.
+pub fn consumer_pending(
+    db: &Db,
+    stream: &EsStreamName,
+    group: &GroupName,
+    consumer: &str,
+) -> sled::Result<Vec<EventNumber>> {
+    let pending = db.open_tree(GROUP_PENDING_TREE)?;
+    let prefix = pending_prefix(stream, group);
+
+    let mut numbers = Vec::new();
+    for result in pending.scan_prefix(&prefix) {
+        let (key, value) = result?;
+        if PendingEntry::from_bytes(&value).consumer == consumer {
+            numbers.push(EventNumber::try_from(&key[prefix.len()..]).unwrap());
+        }
+    }
+
+    Ok(numbers)
+}
+
+/// Sends the event to the consumer and records it as pending, returns `None` if the
+/// subscription must stop. The events trimmed meanwhile are not pending anymore.
+pub fn deliver(
+    db: &Db,
+    stream: &EsStreamName,
+    group: &GroupName,
+    consumer: &str,
+    number: EventNumber,
+    sender: mpsc::Sender<Result<Response, String>>,
+    subscription: &Subscription,
+) -> sled::Result<Option<mpsc::Sender<Result<Response, String>>>> {
+    let tree = db.open_tree(stream.as_ref())?;
+    let key = number.to_be_bytes();
+    let value = match tree.get(key)? {
+        Some(value) => value,
+        None => {
+            // the event has been trimmed, it can not be delivered anymore
+            let pending = db.open_tree(GROUP_PENDING_TREE)?;
+            pending.remove(pending_key(stream, group, number))?;
+            return Ok(Some(sender));
+        }
+    };
+
+    mark_pending(db, stream, group, number, consumer)?;
+
+    let es_stream = EsStream::from(stream.clone());
+    match event_response(&es_stream, &key, value) {
+        Some(event) => Ok(send_response(sender, subscription, event)),
+        None => Ok(Some(sender)),
+    }
+}
+
+pub fn send_group_events(
+    db: Db,
+    group: GroupName,
+    stream: EsStreamName,
+    consumer: String,
+    mut sender: mpsc::Sender<Result<Response, String>>,
+    subscription: Arc<Subscription>,
+) -> sled::Result<()> {
+    let tree = db.open_tree(stream.as_ref())?;
+    let tombstones = db.open_tree(TOMBSTONES_TREE)?;
+
+    let deliver = |number: EventNumber, sender| {
+        deliver(
+            &db,
+            &stream,
+            &group,
+            &consumer,
+            number,
+            sender,
+            &subscription,
+        )
+    };
+
+    for number in consumer_pending(&db, &stream, &group, &consumer)? {
+        sender = match deliver(number, sender)? {
+            Some(sender) => sender,
+            None => return Ok(()),
+        };
+    }
+
+    loop {
+        // The watcher must be registered before claiming
+        // the events to be sure that no event will be missed.
+        let mut watcher = subscription.watch(&tree);
+        if subscription.is_unsubscribed() {
+            info!("subscription stopped");
+            return Ok(());
+        }
+
+        while let Some(number) = claim_next_event(&db, &stream, &group)? {
+            sender = match deliver(number, sender)? {
+                Some(sender) => sender,
+                None => return Ok(()),
+            };
+        }
+
+        match watcher.next() {
+            Some(Event::Insert { .. }) => (),
+            Some(Event::Remove { .. }) if !tombstones.contains_key(&stream)? => (),
+            Some(Event::Remove { .. }) => {
+                let deleted = Response::StreamDeleted {
+                    stream: stream.clone(),
+                };
+                send_response(sender, &subscription, deleted);
+                return Ok(());
+            }
+            None => return Ok(()),
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use std::net::SocketAddr;
+use std::str;
+use std::sync::Arc;
+use std::thread;
+
+use futures::stream::Wait;
+use futures::{Future, Sink, Stream};
+use grpcio::{Environment, RpcContext, RpcStatus, RpcStatusCode, Server, ServerBuilder};
+use grpcio::{ServerStreamingSink, UnarySink, WriteFlags};
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use meilies::reqresp::{ErrorCode, ExpectedVersion, Request, Response};
+use meilies::stream::{EventData, EventHeaders, EventName, EventNumber};
+use meilies::stream::{Stream as EsStream, StreamName as EsStreamName};
+
+use self::service::{
+    BackupRequest, CompactReply, DeleteStreamRequest, Empty, InfoField, InfoReply, PublishReply,
+    PublishRequest, StreamInfoReply, StreamRequest, SubscribeReply, SubscribeRequest,
+    TrimStreamRequest,
+};
+use self::service_grpc::{create_meilies, Meilies};
+use super::acl::Authentication;
+use super::clients::Client;
+use super::dispatch_blocking;
+use super::gateway::{authenticate, Gateway};
+
+/// The messages generated from `proto/service.proto` by the build script.
+#[allow(clippy::all, non_camel_case_types, non_snake_case, unused_imports)]
+mod service {
+    include!(concat!(env!("OUT_DIR"), "/service.rs"));
+}
+
+#[allow(clippy::all, non_camel_case_types, non_snake_case, unused_imports)]
+mod service_grpc {
+    include!(concat!(env!("OUT_DIR"), "/service_grpc.rs"));
+}
+
+/// The number of threads polling the completion queues, the calls
+/// run on their own threads as they can wait for the replicas.
+const COMPLETION_QUEUES: usize = 2;
+
+type Responses = Wait<mpsc::Receiver<Result<Response, String>>>;
+
+/// Returns the status of an error sent by the server, the code depends on its code.
+fn status(error: &str) -> RpcStatus {
+    let code = match ErrorCode::split(error).map(|(code, _)| code) {
+        Some(ErrorCode::NoAuth) | Some(ErrorCode::WrongPass) => RpcStatusCode::UNAUTHENTICATED,
+        Some(ErrorCode::NoPerm) => RpcStatusCode::PERMISSION_DENIED,
+        Some(ErrorCode::NoStream) => RpcStatusCode::NOT_FOUND,
+        Some(ErrorCode::WrongVer) | Some(ErrorCode::Tombstoned) => {
+            RpcStatusCode::FAILED_PRECONDITION
+        }
+        Some(ErrorCode::Throttled) | Some(ErrorCode::DiskFull) => RpcStatusCode::RESOURCE_EXHAUSTED,
+        Some(ErrorCode::NotReplicated) => RpcStatusCode::DEADLINE_EXCEEDED,
+        Some(ErrorCode::ReadOnly) | Some(ErrorCode::Moved) | Some(ErrorCode::ClusterDown) => {
+            RpcStatusCode::UNAVAILABLE
+        }
+        _otherwise => RpcStatusCode::INVALID_ARGUMENT,
+    };
+    RpcStatus::new(code, Some(error.to_owned()))
+}
+
+fn invalid_argument(message: &str) -> RpcStatus {
+    RpcStatus::new(RpcStatusCode::INVALID_ARGUMENT, Some(message.to_owned()))
+}
+
+fn unexpected_response() -> RpcStatus {
+    RpcStatus::new(
+        RpcStatusCode::INTERNAL,
+        Some(String::from("unexpected response")),
+    )
+}
+
+fn stream_name(name: String) -> Result<EsStreamName, RpcStatus> {
+    EsStreamName::new(name).map_err(|_| invalid_argument("invalid stream name"))
+}
+
+/// Returns the address of the peer, that gRPC formats as `ipv4:127.0.0.1:5000`.
+fn peer_addr(ctx: &RpcContext) -> SocketAddr {
+    let peer = ctx.peer();
+    let addr = peer
+        .splitn(2, ':')
+        .nth(1)
+        .and_then(|addr| addr.parse().ok());
+    addr.unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0)))
+}
+
+/// Runs the command like the ones of the RESP connections and returns the receiver of its
+/// responses, the client is listed by the `client-list` command until it is dropped.
+fn execute(
+    gateway: &Gateway,
+    addr: SocketAddr,
+    mut authentication: Authentication,
+    request: Request,
+) -> Result<(Client, Responses), RpcStatus> {
+    let (client, _killed) = gateway.clients.register(addr, authentication.user_name());
+    let mut acks = gateway.acks.connection();
+    let (sender, receiver) = mpsc::channel(10);
+
+    let result = dispatch_blocking(
+        request,
+        gateway.db.clone(),
+        sender,
+        &client,
+        &mut authentication,
+        &gateway.replication,
+        &mut acks,
+    );
+    if let Err(e) = result {
+        error!("error; {}", e);
+        return Err(RpcStatus::new(RpcStatusCode::INTERNAL, Some(e.to_string())));
+    }
+
+    Ok((client, receiver.wait()))
+}
+
+fn publish_request(mut req: PublishRequest) -> Result<Request, RpcStatus> {
+    let stream = stream_name(req.take_stream())?;
+    let event_name = EventName::new(req.take_event_name())
+        .map_err(|_| invalid_argument("invalid event name"))?;
+
+    let expected_version = if req.get_expect_empty() {
+        Some(ExpectedVersion::Empty)
+    } else if req.has_expected_number() {
+        Some(ExpectedVersion::Number(EventNumber(
+            req.get_expected_number(),
+        )))
+    } else {
+        None
+    };
+
+    Ok(Request::Publish {
+        stream,
+        event_name,
+        event_data: EventData(req.take_event_data().into()),
+        expected_version,
+        dedup_id: Some(req.take_dedup_id()).filter(|id| !id.is_empty()),
+        headers: EventHeaders(req.take_headers().into_iter().collect()),
+        deliver_at: None,
+        ack: None,
+    })
+}
+
+/// Returns the reply of a subscription to a response, and whether it is the last one.
+fn subscribe_reply(response: Response) -> Option<(SubscribeReply, bool)> {
+    let mut reply = SubscribeReply::new();
+    match response {
+        Response::Event {
+            stream,
+            number,
+            timestamp,
+            event_name,
+            event_data,
+            headers,
+            ..
+        } => {
+            let mut event = service::Event::new();
+            event.set_stream(stream.as_str().to_owned());
+            event.set_number(number.0);
+            event.set_timestamp(timestamp.0);
+            event.set_name(event_name.as_str().to_owned());
+            event.set_data(event_data.0.to_vec());
+            event.set_headers(headers.0.into_iter().collect());
+            reply.set_event(event);
+        }
+        Response::CaughtUp { .. } => reply.set_caught_up(Empty::new()),
+        Response::StreamDeleted { .. } => {
+            reply.set_deleted(Empty::new());
+            return Some((reply, true));
+        }
+        Response::StreamTrimmed { first, .. } => reply.set_trimmed(first.0),
+        Response::EventsDropped { count, .. } => reply.set_dropped(count),
+        _otherwise => return None,
+    }
+    Some((reply, false))
+}
+
+fn empty_reply(response: Response) -> Option<Empty> {
+    match response {
+        Response::Ok => Some(Empty::new()),
+        _otherwise => None,
+    }
+}
+
+#[derive(Clone)]
+struct MeiliesService {
+    gateway: Gateway,
+}
+
+impl MeiliesService {
+    /// Authenticates the call with its `authorization` metadata, like the HTTP gateway.
+    fn authentication(&self, ctx: &RpcContext) -> Result<Authentication, RpcStatus> {
+        let authorization = ctx
+            .request_headers()
+            .iter()
+            .find(|(name, _)| *name == "authorization")
+            .and_then(|(_, value)| str::from_utf8(value).ok());
+        authenticate(&self.gateway, authorization).map_err(|e| status(&e))
+    }
+
+    /// Runs the command on its own thread and answers with the reply built from its response.
+    fn unary<T, F>(
+        &self,
+        ctx: &RpcContext,
+        request: Result<Request, RpcStatus>,
+        sink: UnarySink<T>,
+        reply: F,
+    ) where
+        T: Send + 'static,
+        F: FnOnce(Response) -> Option<T> + Send + 'static,
+    {
+        let gateway = self.gateway.clone();
+        let addr = peer_addr(ctx);
+        let authentication = self.authentication(ctx);
+
+        let spawned = thread::Builder::new()
+            .name(String::from("grpc-call"))
+            .spawn(move || {
+                let result = authentication.and_then(|authentication| {
+                    let (_client, mut responses) =
+                        execute(&gateway, addr, authentication, request?)?;
+                    match responses.next() {
+                        Some(Ok(Ok(response))) => reply(response).ok_or_else(unexpected_response),
+                        Some(Ok(Err(error))) => Err(status(&error)),
+                        _otherwise => Err(unexpected_response()),
+                    }
+                });
+
+                let sent = match result {
+                    Ok(reply) => sink.success(reply).wait(),
+                    Err(status) => sink.fail(status).wait(),
+                };
+                if let Err(e) = sent {
+                    info!("error answering a gRPC call; {}", e);
+                }
+            });
+        if let Err(e) = spawned {
+            error!("error spawning a gRPC call thread; {}", e);
+        }
+    }
+}
+
+impl Meilies for MeiliesService {
+    fn publish(&mut self, ctx: RpcContext, req: PublishRequest, sink: UnarySink<PublishReply>) {
+        self.unary(
+            &ctx,
+            publish_request(req),
+            sink,
+            |response| match response {
+                Response::Published { stream, number } => {
+                    let mut reply = PublishReply::new();
+                    reply.set_stream(stream.as_str().to_owned());
+                    reply.set_number(number.0);
+                    Some(reply)
+                }
+                _otherwise => None,
+            },
+        );
+    }
+
+    /// Sends the events of the subscription until the subscriber disconnects
+    /// or the stream is deleted, the subscription has its own thread.
+    fn subscribe(
+        &mut self,
+        ctx: RpcContext,
+        mut req: SubscribeRequest,
+        sink: ServerStreamingSink<SubscribeReply>,
+    ) {
+        let from = if req.has_from() {
+            Some(req.get_from())
+        } else {
+            None
+        };
+        let request = stream_name(req.take_stream()).map(|stream| Request::Subscribe {
+            streams: vec![EsStream::new_from_to(stream, from, None)],
+        });
+
+        let gateway = self.gateway.clone();
+        let addr = peer_addr(&ctx);
+        let authentication = self.authentication(&ctx);
+
+        let spawned = thread::Builder::new()
+            .name(String::from("grpc-subscription"))
+            .spawn(move || {
+                // the errors sent before the subscription starts are answered with a status
+                let result = authentication.and_then(|authentication| {
+                    let (client, mut responses) =
+                        execute(&gateway, addr, authentication, request?)?;
+                    match responses.next() {
+                        Some(Ok(Ok(Response::Subscribed { .. }))) => Ok((client, responses)),
+                        Some(Ok(Err(error))) => Err(status(&error)),
+                        _otherwise => Err(unexpected_response()),
+                    }
+                });
+                let (_client, responses) = match result {
+                    Ok(subscription) => subscription,
+                    Err(status) => {
+                        let _ = sink.fail(status).wait();
+                        return;
+                    }
+                };
+
+                let mut sink = sink.wait();
+                for response in responses {
+                    let (reply, last) = match response {
+                        Ok(Ok(response)) => match subscribe_reply(response) {
+                            Some(reply) => reply,
+                            None => continue,
+                        },
+                        Ok(Err(error)) => {
+                            let _ = sink.into_inner().fail(status(&error)).wait();
+                            return;
+                        }
+                        Err(_) => break,
+                    };
+
+                    if sink.send((reply, WriteFlags::default())).is_err() {
+                        info!("gRPC subscriber {} disconnected", addr);
+                        return;
+                    }
+                    if last {
+                        break;
+                    }
+                }
+
+                let _ = sink.close();
+            });
+        if let Err(e) = spawned {
+            error!("error spawning a gRPC subscription thread; {}", e);
+        }
+    }
+
+    fn stream_info(
+        &mut self,
+        ctx: RpcContext,
+        mut req: StreamRequest,
+        sink: UnarySink<StreamInfoReply>,
+    ) {
+        let request = stream_name(req.take_stream()).map(|stream| Request::StreamInfo { stream });
+        self.unary(&ctx, request, sink, |response| match response {
+            Response::StreamInfo {
+                stream,
+                first,
+                last,
+                bytes,
+                events,
+                created,
+                retention_ms,
+                max_events,
+                max_bytes,
+                subscribers,
+            } => {
+                let mut reply = StreamInfoReply::new();
+                reply.set_stream(stream.as_str().to_owned());
+                reply.set_first(first.0);
+                if let Some(last) = last {
+                    reply.set_last(last.0);
+                }
+                reply.set_bytes(bytes);
+                reply.set_events(events);
+                if let Some(created) = created {
+                    reply.set_created(created.0);
+                }
+                if let Some(retention_ms) = retention_ms {
+                    reply.set_retention_ms(retention_ms);
+                }
+                if let Some(max_events) = max_events {
+                    reply.set_max_events(max_events);
+                }
+                if let Some(max_bytes) = max_bytes {
+                    reply.set_max_bytes(max_bytes);
+                }
+                reply.set_subscribers(subscribers);
+                Some(reply)
+            }
+            _otherwise => None,
+        });
+    }
+
+    fn create_stream(&mut self, ctx: RpcContext, mut req: StreamRequest, sink: UnarySink<Empty>) {
+        let request = stream_name(req.take_stream()).map(|stream| Request::CreateStream { stream });
+        self.unary(&ctx, request, sink, empty_reply);
+    }
+
+    fn delete_stream(
+        &mut self,
+        ctx: RpcContext,
+        mut req: DeleteStreamRequest,
+        sink: UnarySink<Empty>,
+    ) {
+        let hard = req.get_hard();
+        let request =
+            stream_name(req.take_stream()).map(|stream| Request::DeleteStream { stream, hard });
+        self.unary(&ctx, request, sink, empty_reply);
+    }
+
+    fn trim_stream(&mut self, ctx: RpcContext, mut req: TrimStreamRequest, sink: UnarySink<Empty>) {
+        let up_to = req.get_up_to();
+        let request =
+            stream_name(req.take_stream()).map(|stream| Request::TrimStream { stream, up_to });
+        self.unary(&ctx, request, sink, empty_reply);
+    }
+
+    fn compact(&mut self, ctx: RpcContext, _req: Empty, sink: UnarySink<CompactReply>) {
+        self.unary(
+            &ctx,
+            Ok(Request::Compact),
+            sink,
+            |response| match response {
+                Response::Compacted { before, after } => {
+                    let mut reply = CompactReply::new();
+                    reply.set_before(before);
+                    reply.set_after(after);
+                    Some(reply)
+                }
+                _otherwise => None,
+            },
+        );
+    }
+
+    fn backup(&mut self, ctx: RpcContext, mut req: BackupRequest, sink: UnarySink<Empty>) {
+        let request = Request::Backup {
+            path: req.take_path(),
+        };
+        self.unary(&ctx, Ok(request), sink, empty_reply);
+    }
+
+    fn info(&mut self, ctx: RpcContext, _req: Empty, sink: UnarySink<InfoReply>) {
+        self.unary(&ctx, Ok(Request::Info), sink, |response| match response {
+            Response::Info { fields } => {
+                let mut reply = InfoReply::new();
+                for (name, value) in fields {
+                    let mut field = InfoField::new();
+                    field.set_name(name);
+                    field.set_value(value);
+                    reply.mut_fields().push(field);
+                }
+                Some(reply)
+            }
+            _otherwise => None,
+        });
+    }
+}
+
+/// Starts the gRPC server, it runs on the threads of gRPC until it is dropped.
+pub fn start(gateway: Gateway, addr: SocketAddr) -> grpcio::Result<Server> {
+    let env = Arc::new(Environment::new(COMPLETION_QUEUES));
+    let service = create_meilies(MeiliesService { gateway });
+
+    let mut server = ServerBuilder::new(env)
+        .register_service(service)
+        .bind(addr.ip().to_string(), addr.port())
+        .build()?;
+    server.start();
+    info!("the gRPC server is listening on {}", addr);
+
+    Ok(server)
+}
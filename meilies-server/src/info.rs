@@ -0,0 +1,94 @@
+use std::fs;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use lazy_static::lazy_static;
+use sled::Db;
+
+use meilies::reqresp::Response;
+use meilies::stream::StreamName as EsStreamName;
+
+use super::commit::FlushPolicy;
+use super::{last_event_number, metrics, stream_names};
+
+/// The features supported by the server, the clients can check
+/// that a feature is listed before using the commands it adds.
+const FEATURES: &[&str] = &[
+    "event-names",
+    "headers",
+    "expected-version",
+    "dedup",
+    "scheduled",
+    "batches",
+    "consumer-groups",
+    "stream-meta",
+    "slow-consumers",
+    "flow-control",
+    "monitor",
+    "compression",
+    "resp3",
+    "chunked",
+    "batched-delivery",
+    "ephemeral",
+    "checksums",
+];
+
+lazy_static! {
+    static ref STARTED_AT: Instant = Instant::now();
+    static ref FLUSH_POLICY: Mutex<FlushPolicy> = Mutex::default();
+}
+
+/// Records the time the server started at, the uptime is computed from it.
+pub fn started() {
+    lazy_static::initialize(&STARTED_AT);
+}
+
+/// Records the flush policy chosen when starting the server.
+pub fn flush_policy(policy: FlushPolicy) {
+    *FLUSH_POLICY.lock().unwrap() = policy;
+}
+
+/// Returns the resident memory of the process in bytes, only known on Linux.
+fn resident_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Returns the statistics of the server as sent to the clients.
+pub fn server_info(db: &Db) -> sled::Result<Response> {
+    let events = last_event_number(db, &EsStreamName::all())?.map_or(0, |n| n.0 + 1);
+    let connections = metrics::CONNECTED_CLIENTS.get();
+    let subscriptions = metrics::ACTIVE_SUBSCRIPTIONS.get();
+
+    let mut fields = vec![
+        ("version", env!("CARGO_PKG_VERSION").to_owned()),
+        ("uptime", STARTED_AT.elapsed().as_secs().to_string()),
+        ("streams", stream_names(db).len().to_string()),
+        ("events", events.to_string()),
+        ("connections", connections.to_string()),
+        ("subscriptions", subscriptions.to_string()),
+        ("flush", FLUSH_POLICY.lock().unwrap().to_string()),
+    ];
+
+    if let Some(memory) = resident_memory() {
+        fields.push(("memory", memory.to_string()));
+    }
+
+    let fields = fields
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value))
+        .collect();
+
+    Ok(Response::Info { fields })
+}
+
+/// Returns the version and the features of the server for a connection using this protocol.
+pub fn hello(proto: u64) -> Response {
+    Response::Hello {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        proto,
+        features: FEATURES.iter().map(|f| f.to_string()).collect(),
+    }
+}
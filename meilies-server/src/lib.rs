@@ -0,0 +1,2973 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{Error as IoError, ErrorKind};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::sync::oneshot;
+use sled::transaction::TransactionError;
+use sled::{Config, Db, Event, IVec, Transactional, Tree};
+use structopt::StructOpt;
+use tokio::codec::{Decoder, Framed};
+use tokio::net::TcpListener;
+use tokio::prelude::*;
+use tokio::sync::mpsc;
+use tracing::{error, info, info_span};
+use tracing_futures::Instrument;
+
+use meilies::reqresp::{AckLevel, ErrorCode, ExpectedVersion, Request, Response, ServerCodec};
+use meilies::reqresp::{RequestMsgError, ResponseMsgError};
+use meilies::resp::{RespBytesConvertError, RespMsgError, RespVecConvertError};
+use meilies::storage::StorageBackend;
+use meilies::stream::{
+    Batch, EventData, EventHeaders, EventName, EventNumber, GroupName, RawEvent, SlowConsumer,
+    Stream as EsStream, StreamName as EsStreamName, Timestamp, ALL_STREAMS, CHECKSUM_FLAG,
+};
+
+mod acks;
+mod acl;
+mod all;
+mod audit;
+mod backup;
+mod buffer;
+mod clients;
+mod commit;
+mod compaction;
+mod compression;
+mod cursor;
+mod dedup;
+mod disk;
+mod embedded;
+mod ephemeral;
+mod failover;
+mod gateway;
+mod groups;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod info;
+mod logging;
+mod meta;
+mod metrics;
+mod polled;
+mod rate;
+mod replication;
+mod retention;
+mod scheduled;
+mod shutdown;
+mod slowlog;
+mod storage;
+mod tail;
+mod tls;
+mod trace;
+#[cfg(feature = "object-storage")]
+mod upload;
+mod verify;
+#[cfg(feature = "webhooks")]
+mod webhook;
+mod websocket;
+
+use self::acks::{ConnectionAcks, ReplicaAcks};
+use self::acl::{Acl, Authentication};
+use self::all::ALL_TREE;
+use self::buffer::SubscriptionBuffer;
+use self::clients::{Client, Clients, KillSwitch};
+use self::failover::Failover;
+use self::commit::{FlushPolicy, GroupCommit};
+use self::cursor::Cursor;
+pub use self::embedded::{EmbeddedClient, EmbeddedServer};
+use self::gateway::Gateway;
+use self::replication::Replication;
+use self::shutdown::Shutdown;
+use self::storage::{SledStorage, StorageError};
+use self::tls::{peer_common_name, tls_acceptor};
+#[cfg(feature = "webhooks")]
+use self::webhook::check_meta as check_webhook_meta;
+
+/// The prefix of the sled trees used internally, a stream name can not contain a colon.
+const INTERNAL_TREE_PREFIX: &str = "meilies:";
+
+/// The tree that keeps the names of the deleted streams, the value of the tombstone
+/// is `HARD_DELETED` for the streams that can not be recreated and empty otherwise.
+const TOMBSTONES_TREE: &str = "meilies:tombstones";
+
+/// The tombstone of the streams deleted permanently.
+const HARD_DELETED: &[u8] = b"hard";
+
+/// The tree that keeps the first event number of the trimmed streams.
+const FIRST_NUMBERS_TREE: &str = "meilies:first-numbers";
+
+/// The tree that keeps the time at which each stream was created.
+const CREATION_TIMES_TREE: &str = "meilies:creation-times";
+
+/// The maximum number of events returned by a `get-range` command, the
+/// following events are read with another command starting after the last one.
+const MAX_RANGE_EVENTS: u64 = 10_000;
+
+/// The key removed from a watched tree to wake up its subscriptions,
+/// it is never written as it is not the number of an event.
+const WAKE_KEY: &[u8] = b"meilies:wake";
+
+/// The stream that receives a `StreamCreated` event when a stream is first written to and a
+/// `StreamDeleted` event when it is deleted, the data of these events is the name of the stream.
+const STREAMS_STREAM: &str = "$streams";
+
+/// Encodes an event as stored in the stream trees: the timestamp, the length of the name,
+/// the name, the length of the headers, the headers, the data, which is compressed
+/// if `compressed` is `true`, and then the CRC32 checksum of all these bytes.
+fn raw_event(
+    timestamp: Timestamp,
+    event_name: &EventName,
+    headers: &EventHeaders,
+    event_data: &[u8],
+    compressed: bool,
+) -> Vec<u8> {
+    let event_data = compression::compress(event_data, compressed);
+    let raw_timestamp = timestamp.to_be_bytes();
+    let raw_length = (event_name.as_str().len() as u64 | CHECKSUM_FLAG).to_be_bytes();
+    let raw_name = event_name.as_str().as_bytes();
+    let raw_headers = headers.to_bytes();
+    let raw_headers_length = raw_headers.len().to_be_bytes();
+
+    let capacity = raw_timestamp.len()
+        + raw_length.len()
+        + raw_name.len()
+        + raw_headers_length.len()
+        + raw_headers.len()
+        + event_data.len()
+        + 4;
+    let mut raw_event = Vec::with_capacity(capacity);
+    raw_event.extend_from_slice(&raw_timestamp);
+    raw_event.extend_from_slice(&raw_length);
+    raw_event.extend_from_slice(raw_name);
+    raw_event.extend_from_slice(&raw_headers_length);
+    raw_event.extend_from_slice(&raw_headers);
+    raw_event.extend_from_slice(&event_data);
+    let checksum = crc32fast::hash(&raw_event);
+    raw_event.extend_from_slice(&checksum.to_be_bytes());
+    raw_event
+}
+
+/// Appends all the events to the stream in a single transaction,
+/// returns the numbers of the first and the last events appended.
+fn publish_batch(
+    db: &Db,
+    tree: &Tree,
+    name: &EsStreamName,
+    events: &[(EventName, EventData)],
+) -> sled::Result<(EventNumber, EventNumber)> {
+    let all = db.open_tree(ALL_TREE)?;
+    let first_numbers = db.open_tree(FIRST_NUMBERS_TREE)?;
+    let sizes = retention::sizes_tree(db)?;
+    let limits = retention::limits(db, name)?;
+    let compressed = compression::is_enabled(db, name)?;
+
+    let timestamp = Timestamp::now();
+    let headers = EventHeaders::new();
+    let raw_events: Vec<IVec> = events
+        .iter()
+        .map(|(name, data)| raw_event(timestamp, name, &headers, &data.0, compressed).into())
+        .collect();
+
+    let trees = (&**db, tree, &all, &first_numbers, &sizes);
+    let result = trees.transaction(|(numbers, tree, all, first_numbers, sizes)| {
+        let previous = numbers.get(name)?;
+        let previous = previous.map(|n| EventNumber::try_from(n.as_ref()).unwrap());
+        let first = previous.map_or(EventNumber::zero(), EventNumber::next);
+
+        let mut last = first;
+        let mut bytes = 0;
+        for (i, raw_event) in raw_events.iter().enumerate() {
+            last = EventNumber(first.0 + i as u64);
+            bytes += raw_event.len() as u64;
+            tree.insert(&last.to_be_bytes()[..], raw_event.clone())?;
+
+            let global = numbers.get(ALL_STREAMS)?;
+            let global = global.map(|n| EventNumber::try_from(n.as_ref()).unwrap());
+            let global = global.map_or(EventNumber::zero(), EventNumber::next);
+            all.insert(&global.to_be_bytes()[..], all::entry(name, last))?;
+            numbers.insert(ALL_STREAMS, &global.to_be_bytes()[..])?;
+        }
+
+        numbers.insert(name.as_ref(), &last.to_be_bytes()[..])?;
+        retention::appended(limits, name, tree, first_numbers, sizes, last, bytes)?;
+
+        Ok((first, last))
+    });
+
+    match result {
+        Ok((first, last)) => {
+            tail::appended(name, limits, first, raw_events);
+            Ok((first, last))
+        }
+        Err(TransactionError::Storage(e)) => Err(e),
+        Err(TransactionError::Abort(())) => unreachable!("the transaction is never aborted"),
+    }
+}
+
+/// Appends the events to their streams in a single transaction, all of them
+/// are appended or none are, returns the number assigned to each event.
+fn publish_multi(
+    db: &Db,
+    writes: &[(EsStreamName, EventName, EventData)],
+) -> sled::Result<Vec<(EsStreamName, EventNumber)>> {
+    // The same stream can be written multiple times, its tree is only opened once.
+    let mut names: Vec<&EsStreamName> = Vec::new();
+    for (stream, _, _) in writes {
+        if !names.contains(&stream) {
+            names.push(stream);
+        }
+    }
+
+    let mut trees = vec![
+        (**db).clone(),
+        db.open_tree(ALL_TREE)?,
+        db.open_tree(FIRST_NUMBERS_TREE)?,
+        retention::sizes_tree(db)?,
+    ];
+    let mut limits = Vec::with_capacity(names.len());
+    let mut compressions = Vec::with_capacity(names.len());
+    for name in &names {
+        trees.push(db.open_tree(name.as_ref())?);
+        limits.push(retention::limits(db, name)?);
+        compressions.push(compression::is_enabled(db, name)?);
+    }
+
+    let timestamp = Timestamp::now();
+    let headers = EventHeaders::new();
+    let raw_events: Vec<IVec> = writes
+        .iter()
+        .map(|(stream, event_name, event_data)| {
+            let index = names.iter().position(|n| *n == stream).unwrap();
+            let compressed = compressions[index];
+            raw_event(timestamp, event_name, &headers, &event_data.0, compressed).into()
+        })
+        .collect();
+
+    let result = trees.as_slice().transaction(|trees| {
+        let numbers = &trees[0];
+        let all = &trees[1];
+        let first_numbers = &trees[2];
+        let sizes = &trees[3];
+
+        // the last event number and the number of bytes appended to each stream
+        let mut appended = vec![(EventNumber::zero(), 0); names.len()];
+
+        let mut events = Vec::with_capacity(writes.len());
+        for ((stream, _, _), raw_event) in writes.iter().zip(&raw_events) {
+            let index = names.iter().position(|n| *n == stream).unwrap();
+            let tree = &trees[index + 4];
+
+            // the transaction reads its own writes, numbers follow each other in the same stream
+            let previous = numbers.get(stream)?;
+            let previous = previous.map(|n| EventNumber::try_from(n.as_ref()).unwrap());
+            let number = previous.map_or(EventNumber::zero(), EventNumber::next);
+
+            let key = &number.to_be_bytes()[..];
+            appended[index] = (number, appended[index].1 + raw_event.len() as u64);
+            tree.insert(key, raw_event.clone())?;
+            numbers.insert(stream.as_ref(), key)?;
+
+            let global = numbers.get(ALL_STREAMS)?;
+            let global = global.map(|n| EventNumber::try_from(n.as_ref()).unwrap());
+            let global = global.map_or(EventNumber::zero(), EventNumber::next);
+            all.insert(&global.to_be_bytes()[..], all::entry(stream, number))?;
+            numbers.insert(ALL_STREAMS, &global.to_be_bytes()[..])?;
+
+            events.push((stream.clone(), number));
+        }
+
+        for (index, (last, bytes)) in appended.into_iter().enumerate() {
+            let tree = &trees[index + 4];
+            let limits = limits[index];
+            retention::appended(
+                limits,
+                names[index],
+                tree,
+                first_numbers,
+                sizes,
+                last,
+                bytes,
+            )?;
+        }
+
+        Ok(events)
+    });
+
+    match result {
+        Ok(events) => {
+            for ((stream, number), raw_event) in events.iter().zip(raw_events) {
+                let index = names.iter().position(|n| *n == stream).unwrap();
+                tail::appended(stream, limits[index], *number, vec![raw_event]);
+            }
+            Ok(events)
+        }
+        Err(TransactionError::Storage(e)) => Err(e),
+        Err(TransactionError::Abort(())) => unreachable!("the transaction is never aborted"),
+    }
+}
+
+/// Appends an event to the `$streams` stream.
+fn publish_stream_event(db: &Db, event_name: &str, stream: &EsStreamName) -> sled::Result<()> {
+    let name = EsStreamName::new(STREAMS_STREAM.to_owned()).unwrap();
+    let data = stream.as_str().as_bytes();
+    publish_system_event(db, &name, event_name, &EventHeaders::new(), data)
+}
+
+/// Appends an event to a stream maintained by the server,
+/// the events of these streams are not part of the `$all` stream.
+fn publish_system_event(
+    db: &Db,
+    name: &EsStreamName,
+    event_name: &str,
+    headers: &EventHeaders,
+    event_data: &[u8],
+) -> sled::Result<()> {
+    let event_name = EventName::new(event_name.to_owned()).unwrap();
+    let compressed = compression::is_enabled(db, name)?;
+    let timestamp = Timestamp::now();
+    let raw_event = raw_event(timestamp, &event_name, headers, event_data, compressed);
+
+    let tree = db.open_tree(name.as_ref())?;
+    let first_numbers = db.open_tree(FIRST_NUMBERS_TREE)?;
+    let sizes = retention::sizes_tree(db)?;
+    let limits = retention::limits(db, name)?;
+
+    let trees = (&**db, &tree, &first_numbers, &sizes);
+    let result = trees.transaction(|(numbers, tree, first_numbers, sizes)| {
+        let last = numbers.get(name)?;
+        let last = last.map(|n| EventNumber::try_from(n.as_ref()).unwrap());
+        let number = last.map_or(EventNumber::zero(), EventNumber::next);
+
+        let key = &number.to_be_bytes()[..];
+        tree.insert(key, raw_event.as_slice())?;
+        numbers.insert(name.as_ref(), key)?;
+
+        let bytes = raw_event.len() as u64;
+        retention::appended(limits, name, tree, first_numbers, sizes, number, bytes)?;
+
+        Ok(number)
+    });
+
+    match result {
+        Ok(number) => {
+            tail::appended(name, limits, number, vec![raw_event.into()]);
+            Ok(())
+        }
+        Err(TransactionError::Storage(e)) => Err(e),
+        Err(TransactionError::Abort(())) => unreachable!("the transaction is never aborted"),
+    }
+}
+
+/// The outcome of the publication of an event.
+enum Publication {
+    /// The event has been appended with this number.
+    Published(EventNumber),
+    /// An event with the same deduplication ID has already been appended with this number.
+    Duplicate(EventNumber),
+    /// The stream is not at the expected version, its last event number is returned.
+    Conflict(Option<EventNumber>),
+}
+
+/// Appends the event to the stream if it is at the expected version, if any, and
+/// if no event with the same deduplication ID has been published recently.
+fn publish_checked(
+    db: &Db,
+    tree: &Tree,
+    name: &EsStreamName,
+    raw_event: &[u8],
+    expected_version: Option<ExpectedVersion>,
+    dedup_id: Option<&str>,
+) -> sled::Result<Publication> {
+    let (ids, log) = dedup::trees(db)?;
+    let all = db.open_tree(ALL_TREE)?;
+    let first_numbers = db.open_tree(FIRST_NUMBERS_TREE)?;
+    let sizes = retention::sizes_tree(db)?;
+    let limits = retention::limits(db, name)?;
+
+    let trees = (&**db, tree, &ids, &log, &all, &first_numbers, &sizes);
+    let result = trees.transaction(|(numbers, tree, ids, log, all, first_numbers, sizes)| {
+        if let Some(id) = dedup_id {
+            if let Some(number) = ids.get(dedup::id_key(name, id))? {
+                let number = EventNumber::try_from(number.as_ref()).unwrap();
+                return Ok(Publication::Duplicate(number));
+            }
+        }
+
+        let last = numbers.get(name)?;
+        let last = last.map(|n| EventNumber::try_from(n.as_ref()).unwrap());
+        if let Some(expected) = expected_version {
+            if !expected.matches(last) {
+                return Ok(Publication::Conflict(last));
+            }
+        }
+
+        let number = last.map_or(EventNumber::zero(), EventNumber::next);
+        let key = &number.to_be_bytes()[..];
+        tree.insert(key, raw_event)?;
+        numbers.insert(name.as_ref(), key)?;
+
+        let global = numbers.get(ALL_STREAMS)?;
+        let global = global.map(|n| EventNumber::try_from(n.as_ref()).unwrap());
+        let global = global.map_or(EventNumber::zero(), EventNumber::next);
+        all.insert(&global.to_be_bytes()[..], all::entry(name, number))?;
+        numbers.insert(ALL_STREAMS, &global.to_be_bytes()[..])?;
+
+        if let Some(id) = dedup_id {
+            ids.insert(dedup::id_key(name, id), key)?;
+            log.insert(dedup::log_key(name, number), id.as_bytes())?;
+        }
+
+        let bytes = raw_event.len() as u64;
+        retention::appended(limits, name, tree, first_numbers, sizes, number, bytes)?;
+
+        Ok(Publication::Published(number))
+    });
+
+    match result {
+        Ok(Publication::Published(number)) => {
+            tail::appended(name, limits, number, vec![raw_event.into()]);
+            Ok(Publication::Published(number))
+        }
+        Ok(publication) => Ok(publication),
+        Err(TransactionError::Storage(e)) => Err(e),
+        Err(TransactionError::Abort(())) => unreachable!("the transaction is never aborted"),
+    }
+}
+
+/// Returns the reason why events can not be published to the stream, if any.
+fn publish_error(db: &Db, stream: &EsStreamName) -> sled::Result<Option<String>> {
+    // the events of the virtual streams are the ones published to the other streams
+    // and the streams starting with a `$` are maintained by the server
+    if stream.is_virtual() || stream.as_str().starts_with('$') {
+        let error = ErrorCode::Err.error(format_args!("stream {} is read-only", stream));
+        return Ok(Some(error));
+    }
+
+    if is_tombstoned(db, stream)? {
+        return Ok(Some(tombstoned_error(stream)));
+    }
+
+    Ok(None)
+}
+
+/// Returns `true` if the stream has been deleted permanently.
+fn is_tombstoned(db: &Db, stream: &EsStreamName) -> sled::Result<bool> {
+    let tombstone = db.open_tree(TOMBSTONES_TREE)?.get(stream)?;
+    Ok(tombstone.map_or(false, |t| t.as_ref() == HARD_DELETED))
+}
+
+/// Returns the error to send when the stream is not at the expected version.
+fn version_error(
+    stream: &EsStreamName,
+    last: Option<EventNumber>,
+    expected: ExpectedVersion,
+) -> String {
+    let version = match last {
+        Some(number) => ExpectedVersion::Number(number),
+        None => ExpectedVersion::Empty,
+    };
+    ErrorCode::WrongVer.error(format_args!(
+        "stream {} is at version {}, expected {}",
+        stream, version, expected
+    ))
+}
+
+fn tombstoned_error(stream: &EsStreamName) -> String {
+    ErrorCode::Tombstoned.error(format_args!(
+        "stream {} has been deleted permanently",
+        stream
+    ))
+}
+
+/// Returns the error to send if the stream has never been created nor published to,
+/// the ephemeral streams are looked up in memory.
+fn missing_stream_error(
+    storage: &SledStorage,
+    stream: &EsStreamName,
+) -> Result<Option<String>, StorageError> {
+    let exists = if stream.is_ephemeral() {
+        ephemeral::exists(stream)
+    } else {
+        storage.contains(stream)?
+    };
+    if exists {
+        return Ok(None);
+    }
+    let error = ErrorCode::NoStream.error(format_args!("stream {} does not exist", stream));
+    Ok(Some(error))
+}
+
+/// Recreates the stream if it has been deleted and publishes a `StreamCreated` event
+/// if the stream has been created or recreated by the publication of the given event.
+fn stream_written(db: &Db, stream: &EsStreamName, number: EventNumber) -> sled::Result<()> {
+    let recreated = db.open_tree(TOMBSTONES_TREE)?.remove(stream)?.is_some();
+    if number == EventNumber::zero() || recreated {
+        set_creation_time(db, stream, recreated)?;
+        publish_stream_event(db, "StreamCreated", stream)?;
+    }
+    Ok(())
+}
+
+/// Creates an empty stream, or recreates it if it has been deleted.
+fn create_stream(db: &Db, stream: &EsStreamName) -> sled::Result<()> {
+    let tombstones = db.open_tree(TOMBSTONES_TREE)?;
+    if tombstones.remove(stream)?.is_some() {
+        set_creation_time(db, stream, true)?;
+        publish_stream_event(db, "StreamCreated", stream)?;
+    } else if last_event_number(db, stream)?.is_none() {
+        set_creation_time(db, stream, false)?;
+    }
+    db.open_tree(stream.as_ref())?;
+    Ok(())
+}
+
+/// Records the time at which the stream is created, the creation time of a stream
+/// created empty is kept when its first event is published unless it is `recreated`.
+fn set_creation_time(db: &Db, stream: &EsStreamName, recreated: bool) -> sled::Result<()> {
+    let creation_times = db.open_tree(CREATION_TIMES_TREE)?;
+    let now = Timestamp::now().to_be_bytes();
+    if recreated {
+        creation_times.insert(stream, &now[..])?;
+    } else {
+        // the stream is already created if the time is set
+        let absent: Option<&[u8]> = None;
+        let _ = creation_times.compare_and_swap(stream, absent, Some(&now[..]))?;
+    }
+    Ok(())
+}
+
+fn creation_time(db: &Db, stream: &EsStreamName) -> sled::Result<Option<Timestamp>> {
+    let time = db.open_tree(CREATION_TIMES_TREE)?.get(stream)?;
+    Ok(time.map(|t| Timestamp::try_from(t.as_ref()).unwrap()))
+}
+
+/// Removes all the events of a stream but keeps its last event number, the events
+/// published to the stream afterward recreate it and continue its numbering.
+fn delete_stream(db: &Db, stream: &EsStreamName) -> sled::Result<()> {
+    let last = match last_event_number(db, stream)? {
+        Some(number) => number,
+        None => return Ok(()),
+    };
+
+    // The tombstone must be inserted before removing the events, the
+    // subscribers woken up by the removals will find it and notify their clients.
+    let tombstones = db.open_tree(TOMBSTONES_TREE)?;
+    if tombstones.insert(stream, vec![])?.is_some() {
+        return Ok(());
+    }
+
+    trim_stream(db, stream, last.next())?;
+    groups::clear_stream_groups(db, stream)?;
+    dedup::clear_stream_ids(db, stream)?;
+    publish_stream_event(db, "StreamDeleted", stream)
+}
+
+/// Removes a stream, its events and everything known about it,
+/// the stream can not be published to nor created anymore.
+fn tombstone_stream(db: &Db, stream: &EsStreamName) -> sled::Result<()> {
+    let tombstones = db.open_tree(TOMBSTONES_TREE)?;
+    tombstones.insert(stream, HARD_DELETED)?;
+
+    // Clearing the tree wakes up the subscribers of this stream,
+    // they will find the tombstone and notify their clients.
+    let tree = db.open_tree(stream.as_ref())?;
+    tree.clear()?;
+    db.drop_tree(stream.as_ref())?;
+    tail::invalidate(stream);
+    let last_number = db.remove(stream)?;
+    db.open_tree(FIRST_NUMBERS_TREE)?.remove(stream)?;
+    groups::clear_stream_groups(db, stream)?;
+    dedup::clear_stream_ids(db, stream)?;
+    meta::clear_stream_meta(db, stream)?;
+    retention::sizes_tree(db)?.remove(stream)?;
+    db.open_tree(CREATION_TIMES_TREE)?.remove(stream)?;
+    all::clear_stream_entries(db, stream)?;
+    if last_number.is_some() {
+        publish_stream_event(db, "StreamTombstoned", stream)?;
+    }
+
+    Ok(())
+}
+
+/// Removes the events of the stream that are strictly below the given
+/// event number, returns the number of the first event kept.
+fn trim_stream(db: &Db, stream: &EsStreamName, up_to: EventNumber) -> sled::Result<EventNumber> {
+    let tree = db.open_tree(stream.as_ref())?;
+    let first_numbers = db.open_tree(FIRST_NUMBERS_TREE)?;
+
+    // The floor never goes backward nor after the next event to be published.
+    let next_number = last_event_number(db, stream)?.map_or(EventNumber::zero(), EventNumber::next);
+    let first = first_event_number(db, stream)?.unwrap_or_else(EventNumber::zero);
+    let floor = up_to.min(next_number).max(first);
+
+    first_numbers.insert(stream, &floor.to_be_bytes()[..])?;
+
+    // The events are removed one by one rather than by clearing or dropping the tree of the
+    // stream, the events published concurrently above the floor must not be removed.
+    let mut bytes = 0;
+    for result in tree.range(..floor.to_be_bytes()).keys() {
+        if let Some(event) = tree.remove(result?)? {
+            bytes += event.len() as u64;
+        }
+    }
+    retention::removed(db, stream, bytes)?;
+    tail::invalidate(stream);
+
+    Ok(floor)
+}
+
+fn last_event_number(db: &Db, name: &EsStreamName) -> sled::Result<Option<EventNumber>> {
+    let number = db.get(name)?;
+    Ok(number.map(|n| EventNumber::try_from(n.as_ref()).unwrap()))
+}
+
+fn first_event_number(db: &Db, name: &EsStreamName) -> sled::Result<Option<EventNumber>> {
+    let number = db.open_tree(FIRST_NUMBERS_TREE)?.get(name)?;
+    Ok(number.map(|n| EventNumber::try_from(n.as_ref()).unwrap()))
+}
+
+/// Returns the number of the first event appended at or after the given time, or the number
+/// of the next event to be published if there is none.
+///
+/// The events are appended in order, their timestamps are searched in the stream by dichotomy.
+fn first_event_number_at(
+    db: &Db,
+    tree: &Tree,
+    name: &EsStreamName,
+    timestamp: Timestamp,
+) -> sled::Result<EventNumber> {
+    let low = first_event_number(db, name)?.unwrap_or_else(EventNumber::zero);
+    let high = last_event_number(db, name)?.map_or(EventNumber::zero(), EventNumber::next);
+
+    // the events removed by the compaction are skipped
+    search_timestamp(low, high, timestamp, |number| {
+        match tree.range(number.to_be_bytes()..).next() {
+            Some(result) => {
+                let (_, value) = result?;
+                Ok(Some(RawEvent::new(value).timestamp()))
+            }
+            None => Ok(None),
+        }
+    })
+}
+
+/// Returns the first number between `low` and `high` of an event appended at or after the given
+/// time, `timestamp_of` returns the timestamp of an event or `None` if it has been removed.
+fn search_timestamp<F>(
+    low: EventNumber,
+    high: EventNumber,
+    timestamp: Timestamp,
+    timestamp_of: F,
+) -> sled::Result<EventNumber>
+where
+    F: Fn(EventNumber) -> sled::Result<Option<Timestamp>>,
+{
+    let (mut low, mut high) = (low.0, high.0);
+
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let before = match timestamp_of(EventNumber(middle))? {
+            Some(event_timestamp) => event_timestamp < timestamp,
+            // the event has just been removed
+            None => true,
+        };
+
+        if before {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+
+    Ok(EventNumber(low))
+}
+
+/// The number of events a flow controlled subscription can still send.
+struct Credits {
+    available: Mutex<u64>,
+    given: Condvar,
+}
+
+/// A subscription of a client to a stream, it has a flag
+/// that is raised when the client unsubscribes from it.
+pub struct Subscription {
+    client: SocketAddr,
+    stream: EsStreamName,
+    unsubscribed: AtomicBool,
+    /// The tree watched by the subscription, used to wake it up when the client unsubscribes.
+    watched: Mutex<Option<Tree>>,
+    /// The events waiting to be sent when the subscription does not block on a slow consumer.
+    buffer: Option<Arc<SubscriptionBuffer>>,
+    /// The credits given by the client when the subscription is flow controlled.
+    credits: Option<Credits>,
+}
+
+impl Subscription {
+    fn new(
+        client: SocketAddr,
+        stream: EsStreamName,
+        buffer: Option<Arc<SubscriptionBuffer>>,
+        credits: Option<u64>,
+    ) -> Subscription {
+        metrics::ACTIVE_SUBSCRIPTIONS.inc();
+        Subscription {
+            client,
+            stream,
+            unsubscribed: AtomicBool::new(false),
+            watched: Mutex::new(None),
+            buffer,
+            credits: credits.map(|credits| Credits {
+                available: Mutex::new(credits),
+                given: Condvar::new(),
+            }),
+        }
+    }
+
+    fn unsubscribe(&self) {
+        self.unsubscribed.store(true, Ordering::SeqCst);
+        if let Some(buffer) = &self.buffer {
+            buffer.discard();
+        }
+
+        // wakes up the subscription waiting for credits
+        if let Some(credits) = &self.credits {
+            let _available = credits.available.lock().unwrap();
+            credits.given.notify_all();
+        }
+
+        // wakes up the subscription waiting for the next event of a quiet stream,
+        // removing a key that is never written notifies the watchers of the tree
+        if let Some(tree) = &*self.watched.lock().unwrap() {
+            if let Err(e) = tree.remove(WAKE_KEY) {
+                error!("error waking up a subscription; {}", e);
+            }
+        }
+    }
+
+    /// Watches the events of the tree, the watcher is woken up when the client unsubscribes.
+    fn watch(&self, tree: &Tree) -> sled::Subscriber {
+        let mut watched = self.watched.lock().unwrap();
+        *watched = Some(tree.clone());
+        tree.watch_prefix(vec![])
+    }
+
+    fn is_unsubscribed(&self) -> bool {
+        self.unsubscribed.load(Ordering::SeqCst)
+    }
+
+    /// Adds credits to the subscription, returns `false` if it is not flow controlled.
+    fn give_credits(&self, count: u64) -> bool {
+        match &self.credits {
+            Some(credits) => {
+                let mut available = credits.available.lock().unwrap();
+                *available = available.saturating_add(count);
+                credits.given.notify_all();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Waits for a credit to send an event, returns `false` if the client unsubscribed.
+    fn take_credit(&self) -> bool {
+        let credits = match &self.credits {
+            Some(credits) => credits,
+            None => return true,
+        };
+
+        let mut available = credits.available.lock().unwrap();
+        while *available == 0 {
+            if self.is_unsubscribed() {
+                return false;
+            }
+            available = credits.given.wait(available).unwrap();
+        }
+
+        *available -= 1;
+        true
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        metrics::ACTIVE_SUBSCRIPTIONS.dec();
+        metrics::unsubscribed(self.client, &self.stream);
+        if let Some(buffer) = &self.buffer {
+            buffer.close();
+        }
+    }
+}
+
+/// The streams the connection of a client is subscribed to.
+#[derive(Clone)]
+pub struct Subscriptions {
+    client: SocketAddr,
+    kill: KillSwitch,
+    streams: Arc<Mutex<HashMap<EsStreamName, Arc<Subscription>>>>,
+}
+
+impl Subscriptions {
+    fn new(client: SocketAddr, kill: KillSwitch) -> Subscriptions {
+        Subscriptions {
+            client,
+            kill,
+            streams: Arc::default(),
+        }
+    }
+}
+
+fn register_subscription(
+    subscriptions: &Subscriptions,
+    name: &EsStreamName,
+    slow_consumer: SlowConsumer,
+    credits: Option<u64>,
+    batch: Option<Batch>,
+    sender: &mpsc::Sender<Result<Response, String>>,
+) -> Arc<Subscription> {
+    let client = subscriptions.client;
+    let mut streams = subscriptions.streams.lock().unwrap();
+    let subscription = streams.entry(name.clone()).or_insert_with(|| {
+        // the events of a batched subscription are coalesced by its buffer
+        let buffer = match (slow_consumer, batch) {
+            (SlowConsumer::Block, None) => None,
+            (policy, batch) => {
+                let kill = subscriptions.kill.clone();
+                let sender = sender.clone();
+                let name = name.clone();
+                let buffer = SubscriptionBuffer::spawn(policy, batch, kill, client, name, sender);
+                Some(buffer)
+            }
+        };
+        Arc::new(Subscription::new(client, name.clone(), buffer, credits))
+    });
+    subscription.clone()
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "meilies-server", about = "Start the server", author)]
+pub struct Opt {
+    /// Server hostname.
+    #[structopt(short = "h", long = "hostname", default_value = "127.0.0.1")]
+    hostname: String,
+
+    /// Server port.
+    #[structopt(short = "p", long = "port", default_value = "6480")]
+    port: u16,
+
+    /// Specify the zstd compression factor (irreversible)
+    #[structopt(long = "compression-factor")]
+    compression_factor: Option<i32>,
+
+    /// Require clients to authenticate with this password.
+    #[structopt(long = "requirepass")]
+    requirepass: Option<String>,
+
+    /// Path of the file listing the users and the streams they are allowed to access.
+    #[structopt(long = "acl-file", parse(from_os_str))]
+    acl_file: Option<PathBuf>,
+
+    /// Path of the PEM encoded certificate chain used to accept TLS connections.
+    #[structopt(long = "tls-cert", parse(from_os_str))]
+    tls_cert: Option<PathBuf>,
+
+    /// Path of the PEM encoded private key of the TLS certificate.
+    #[structopt(long = "tls-key", parse(from_os_str))]
+    tls_key: Option<PathBuf>,
+
+    /// Path of the PEM encoded CA certificates used to verify the client certificates,
+    /// clients must present a certificate signed by one of them.
+    #[structopt(long = "tls-client-ca", parse(from_os_str))]
+    tls_client_ca: Option<PathBuf>,
+
+    /// The number of seconds between two removals of the events older than the
+    /// retention of their stream, set with the `retention.ms` stream metadata.
+    #[structopt(long = "expiry-interval", default_value = "60")]
+    expiry_interval: u64,
+
+    /// The number of seconds between two compactions of the streams
+    /// for which it is enabled with the `compaction` stream metadata.
+    #[structopt(long = "compaction-interval", default_value = "60")]
+    compaction_interval: u64,
+
+    /// The number of seconds between two reclamations of the space of the events removed,
+    /// which also compacts the streams and flushes the database. Disabled by default.
+    #[structopt(long = "reclaim-interval")]
+    reclaim_interval: Option<u64>,
+
+    /// Paths of the backup archives to restore before serving, the database must be empty.
+    /// Uploaded segments can be given after a backup, they are applied in order.
+    #[structopt(long = "restore", parse(from_os_str))]
+    restore: Vec<PathBuf>,
+
+    /// URL of the S3 compatible bucket, and optional prefix, to which the published events
+    /// are continuously uploaded (e.g. `https://s3.eu-west-3.amazonaws.com/my-bucket/meilies`),
+    /// the credentials are read from the `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` variables.
+    #[structopt(long = "upload-url")]
+    upload_url: Option<String>,
+
+    /// The region of the bucket the events are uploaded to.
+    #[structopt(long = "upload-region", default_value = "us-east-1")]
+    upload_region: String,
+
+    /// The number of seconds between two uploads of the events published in the meantime.
+    #[structopt(long = "upload-interval", default_value = "60")]
+    upload_interval: u64,
+
+    /// Secret the deliveries of the webhooks are signed with, the `x-meilies-signature` header
+    /// is the hex encoded HMAC-SHA256 of the `x-meilies-timestamp` header, a dot and the body.
+    #[structopt(long = "webhook-secret")]
+    webhook_secret: Option<String>,
+
+    /// The number of attempts to deliver an event to a webhook before appending it
+    /// to the `$webhook-dead-letters` stream, the retries are exponentially delayed.
+    #[structopt(long = "webhook-max-attempts", default_value = "10")]
+    webhook_max_attempts: u32,
+
+    /// Address of the primary server to replicate, the server does not accept writes while it
+    /// is a replica. The replication can be changed or stopped with the `replica-of` command.
+    #[structopt(long = "replica-of")]
+    replica_of: Option<SocketAddr>,
+
+    /// Addresses of the other nodes of the cluster, the nodes elect a leader that accepts
+    /// the writes and is replicated by the others like a primary, only the election follows
+    /// Raft. Cannot be used with `--replica-of`.
+    #[structopt(long = "peer")]
+    peers: Vec<SocketAddr>,
+
+    /// The address of this node the other nodes of the cluster connect to,
+    /// defaults to the hostname and port of the server.
+    #[structopt(long = "advertise-addr")]
+    advertise_addr: Option<SocketAddr>,
+
+    /// The username used to authenticate on the primary or on the other nodes of the cluster,
+    /// it must be allowed to read all the streams and to administrate a cluster node.
+    #[structopt(long = "primary-user")]
+    primary_user: Option<String>,
+
+    /// The password used to authenticate on the primary or on the other nodes of the cluster.
+    #[structopt(long = "primary-password")]
+    primary_password: Option<String>,
+
+    /// Reject the publications and the administration commands modifying the streams, the
+    /// subscriptions are still served. It can be changed with the `read-only` command.
+    #[structopt(long = "read-only")]
+    read_only: bool,
+
+    /// The free space in bytes of the data directory below which the publications and the
+    /// commands modifying the streams are rejected with a `DISKFULL` error, until enough
+    /// space is freed. Zero disables the check.
+    #[structopt(long = "min-free-space", default_value = "104857600")]
+    min_free_space: u64,
+
+    /// The number of milliseconds a publication waits for the replicas required by its
+    /// acknowledgement level, an error is returned to the publisher after this delay.
+    #[structopt(long = "ack-timeout", default_value = "5000")]
+    ack_timeout: u64,
+
+    /// When the database is flushed to disk: `every-write` acknowledges the publications
+    /// once flushed, `interval <ms>` flushes it periodically and `os` lets sled and the OS
+    /// write it when their buffers are full. Defaults to `interval 500`.
+    #[structopt(long = "flush", min_values = 1, max_values = 2)]
+    flush: Vec<String>,
+
+    /// The number of milliseconds the publications wait for the concurrent ones before the
+    /// database is flushed once for all of them, it implies the `every-write` flush policy.
+    #[structopt(long = "group-commit-window")]
+    group_commit_window: Option<u64>,
+
+    /// The number of events a connection can publish per second, the publications
+    /// exceeding it are rejected with a `THROTTLED` error. Not limited by default.
+    #[structopt(long = "max-connection-events-per-sec")]
+    max_connection_events_per_sec: Option<u64>,
+
+    /// The number of bytes of event data a connection can publish per second.
+    #[structopt(long = "max-connection-bytes-per-sec")]
+    max_connection_bytes_per_sec: Option<u64>,
+
+    /// The maximum size in bytes of the data of an event, the connections sending a larger
+    /// one receive an error and are closed before it is read. Not limited by default.
+    #[structopt(long = "max-event-size")]
+    max_event_size: Option<usize>,
+
+    /// The number of events kept in memory for each ephemeral stream, i.e. the streams whose
+    /// name starts with a `~`, the oldest events are dropped when it is reached.
+    #[structopt(long = "ephemeral-capacity", default_value = "1000")]
+    ephemeral_capacity: usize,
+
+    /// The number of recent events of each stream kept in memory to serve the subscribers
+    /// close to the head without reading the database, zero disables the cache.
+    #[structopt(long = "tail-cache-size", default_value = "128")]
+    tail_cache_size: usize,
+
+    /// Write the logs as JSON lines, with the fields of the connection and of the command
+    /// they belong to. The level is read from `RUST_LOG` and can be changed at runtime
+    /// with the `config-set log-level` command.
+    #[structopt(long = "log-json")]
+    log_json: bool,
+
+    /// The number of milliseconds above which a command is recorded in the slow log.
+    #[structopt(long = "slowlog-threshold", default_value = "10")]
+    slowlog_threshold: u64,
+
+    /// The number of milliseconds between the publication and the delivery of
+    /// an event to a subscriber above which the delivery is recorded in the slow log.
+    #[structopt(long = "slowlog-delivery-threshold", default_value = "1000")]
+    slowlog_delivery_threshold: u64,
+
+    /// The number of entries kept in the slow log, zero disables it.
+    #[structopt(long = "slowlog-max-len", default_value = "128")]
+    slowlog_max_len: usize,
+
+    /// Address of the HTTP listener serving the Prometheus metrics on the `/metrics` path.
+    #[structopt(long = "metrics-addr")]
+    metrics_addr: Option<SocketAddr>,
+
+    /// Address of the HTTP gateway, `POST /streams/{name}?event={event-name}` publishes
+    /// the body, `GET /streams/{name}?from=0&limit=100` reads events and
+    /// `GET /streams/{name}/subscribe?from=0` sends them as Server-Sent Events and
+    /// `GET /ws` upgrades to a WebSocket connection that runs the RESP commands.
+    #[structopt(long = "http-addr")]
+    http_addr: Option<SocketAddr>,
+
+    /// Address of the gRPC server, available if the server is built with the `grpc`
+    /// feature, its service is described in `meilies-server/proto/service.proto`.
+    #[structopt(long = "grpc-addr")]
+    grpc_addr: Option<SocketAddr>,
+
+    /// Disable vigil initialization.
+    #[structopt(long = "no-vigil")]
+    no_vigil: bool,
+
+    /// Disable sentry initialization.
+    #[structopt(long = "no-sentry")]
+    no_sentry: bool,
+
+    /// Database path
+    #[structopt(
+        long = "db-path",
+        parse(from_os_str),
+        default_value = "/var/lib/meilies"
+    )]
+    db_path: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    RequestMsgError(RequestMsgError),
+    InvalidRequest,
+    InternalError(sled::Error),
+    StorageError(StorageError),
+    IoError(IoError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::RequestMsgError(e) => write!(f, "invalid request message; {}", e),
+            Error::InvalidRequest => write!(f, "invalid request"),
+            Error::InternalError(e) => write!(f, "internal error; {}", e),
+            Error::StorageError(e) => write!(f, "storage error; {}", e),
+            Error::IoError(e) => write!(f, "io error; {}", e),
+        }
+    }
+}
+
+impl From<sled::Error> for Error {
+    fn from(error: sled::Error) -> Error {
+        Error::InternalError(error)
+    }
+}
+
+impl From<StorageError> for Error {
+    fn from(error: StorageError) -> Error {
+        Error::StorageError(error)
+    }
+}
+
+impl From<RespVecConvertError<RespBytesConvertError>> for Error {
+    fn from(_: RespVecConvertError<RespBytesConvertError>) -> Error {
+        Error::InvalidRequest
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(error: IoError) -> Error {
+        Error::IoError(error)
+    }
+}
+
+/// Returns the event response to send or `None` if the event is filtered out by the stream.
+fn event_response(stream: &EsStream, key: &[u8], value: IVec) -> Option<Response> {
+    let raw_event = RawEvent::new(value);
+    let event_name = raw_event.name().unwrap();
+
+    if !stream.accepts(&event_name) {
+        return None;
+    }
+
+    Some(Response::Event {
+        stream: stream.name.clone(),
+        number: EventNumber::try_from(key).unwrap(),
+        timestamp: raw_event.timestamp(),
+        event_name,
+        event_data: compression::decompress(raw_event.data()),
+        headers: raw_event.headers().unwrap(),
+        global_number: None,
+    })
+}
+
+/// Sends a response to the subscriber, returns `None` if the subscription must stop.
+fn send_response(
+    sender: mpsc::Sender<Result<Response, String>>,
+    subscription: &Subscription,
+    response: Response,
+) -> Option<mpsc::Sender<Result<Response, String>>> {
+    if subscription.is_unsubscribed() {
+        info!("subscription stopped");
+        return None;
+    }
+
+    // the events of a flow controlled subscription are only sent for the credits given
+    let is_event = matches!(response, Response::Event { .. });
+    if is_event && !subscription.take_credit() {
+        info!("subscription stopped");
+        return None;
+    }
+
+    // the responses of a subscription that does not block or is batched are sent by its buffer
+    if let Some(buffer) = &subscription.buffer {
+        return if buffer.push(response) {
+            Some(sender)
+        } else {
+            None
+        };
+    }
+
+    let delivered = match &response {
+        Response::Event {
+            stream,
+            number,
+            timestamp,
+            headers,
+            ..
+        } => Some((stream.clone(), *number, *timestamp, headers.trace_context())),
+        _otherwise => None,
+    };
+
+    // only the time spent sending the events is measured
+    let timer = delivered
+        .as_ref()
+        .map(|_| metrics::operation_timer(metrics::DELIVERY));
+
+    match sender.send(Ok(response)).wait() {
+        Ok(sender) => {
+            drop(timer);
+            if let Some((stream, number, timestamp, context)) = delivered {
+                let (client, subscribed) = (subscription.client, &subscription.stream);
+                metrics::delivered(client, subscribed, &stream, timestamp);
+                slowlog::delivered(client, &stream, number, timestamp);
+                trace::delivered(client, &stream, context);
+            }
+            Some(sender)
+        }
+        Err(_) => {
+            info!("encountered closed channel");
+            None
+        }
+    }
+}
+
+/// Returns the number of the first event a subscription to the stream sends, the
+/// subscriptions without a start are sent the events published from now on.
+fn subscription_start(db: &Db, tree: &Tree, stream: &EsStream) -> sled::Result<EventNumber> {
+    match (stream.range.from(), stream.range.from_time()) {
+        (Some(from), _) => Ok(EventNumber(from)),
+        (None, Some(timestamp)) => first_event_number_at(db, tree, &stream.name, timestamp),
+        (None, None) => match last_event_number(db, &stream.name)? {
+            Some(number) => Ok(number.next()),
+            None => Ok(EventNumber::zero()),
+        },
+    }
+}
+
+/// Returns the first event kept if the events of the stream from this number have been
+/// trimmed, the subscription then starts at it and the client is notified.
+fn trimmed_start(
+    db: &Db,
+    stream: &EsStreamName,
+    next_number: EventNumber,
+) -> sled::Result<Option<EventNumber>> {
+    match first_event_number(db, stream)? {
+        Some(first) if next_number < first => Ok(Some(first)),
+        _otherwise => Ok(None),
+    }
+}
+
+fn send_stream_events(
+    stream: EsStream,
+    db: Db,
+    mut sender: mpsc::Sender<Result<Response, String>>,
+    subscription: Arc<Subscription>,
+) -> sled::Result<()> {
+    info!("blocking subscription on {} spawned", stream);
+
+    let tree = db.open_tree(stream.name.as_ref())?;
+    let tombstones = db.open_tree(TOMBSTONES_TREE)?;
+
+    // When reading from the end or from a point in time, the position must be computed before
+    // registering the watcher: the events published in between are assigned greater numbers
+    // and will be read from the tree.
+    let mut next_number = subscription_start(&db, &tree, &stream)?;
+
+    // The watcher must be registered before reading the
+    // stream to be sure that no event will be missed.
+    let mut watcher = subscription.watch(&tree);
+
+    // The first events of the stream could have been trimmed,
+    // we notify the client that the subscription starts later.
+    if let Some(first) = trimmed_start(&db, &stream.name, next_number)? {
+        next_number = first;
+        let trimmed = Response::StreamTrimmed {
+            stream: stream.name.clone(),
+            first,
+        };
+        sender = match send_response(sender, &subscription, trimmed) {
+            Some(sender) => sender,
+            None => return Ok(()),
+        };
+    }
+
+    let end_number = stream.range.to().map(EventNumber);
+    let reached_end = |next_number| matches!(end_number, Some(end) if next_number >= end);
+    let range_end = Response::RangeEnd {
+        stream: stream.name.clone(),
+    };
+
+    if reached_end(next_number) {
+        send_response(sender, &subscription, range_end);
+        return Ok(());
+    }
+
+    // The subscribers slightly behind the head are served from the tail cache,
+    // the events that are not cached and the ones published since are read from the tree.
+    let cached = match last_event_number(&db, &stream.name)? {
+        Some(head) if head >= next_number => {
+            tail::read(&stream.name, next_number, head).unwrap_or_default()
+        }
+        _otherwise => Vec::new(),
+    };
+    let cursor_number = match cached.last() {
+        Some((key, _)) => EventNumber::try_from(key.as_ref()).unwrap().next(),
+        None => next_number,
+    };
+    let cursor = Cursor::new(tree.clone(), cursor_number, end_number);
+
+    for result in cached.into_iter().map(Ok).chain(cursor) {
+        let (key, value) = result?;
+        let number = EventNumber::try_from(key.as_ref()).unwrap();
+
+        if let Some(event) = event_response(&stream, &key, value) {
+            sender = match send_response(sender, &subscription, event) {
+                Some(sender) => sender,
+                None => return Ok(()),
+            };
+        }
+
+        next_number = number.next();
+        if reached_end(next_number) {
+            send_response(sender, &subscription, range_end);
+            return Ok(());
+        }
+
+        // We renew the watcher to avoid filling it with
+        // events that we will read directly from the tree.
+        watcher = subscription.watch(&tree);
+    }
+
+    // The history has been sent, the following events are sent as soon as they are published.
+    let caught_up = Response::CaughtUp {
+        stream: stream.name.clone(),
+    };
+    sender = match send_response(sender, &subscription, caught_up) {
+        Some(sender) => sender,
+        None => return Ok(()),
+    };
+
+    for event in watcher {
+        if subscription.is_unsubscribed() {
+            info!("subscription stopped");
+            return Ok(());
+        }
+
+        match event {
+            Event::Insert { key, .. } => {
+                let number = EventNumber::try_from(key.as_ref()).unwrap();
+                if number < next_number {
+                    continue;
+                }
+
+                // The notifications of the events published in the same batch can be
+                // received out of order, we read the missing events from the cache or the tree.
+                let events = match tail::read(&stream.name, next_number, number) {
+                    Some(events) => events,
+                    None => {
+                        let range = tree.range(next_number.to_be_bytes()..=number.to_be_bytes());
+                        range.collect::<sled::Result<_>>()?
+                    }
+                };
+                for (key, value) in events {
+                    let number = EventNumber::try_from(key.as_ref()).unwrap();
+
+                    next_number = number.next();
+                    if let Some(event) = event_response(&stream, &key, value) {
+                        sender = match send_response(sender, &subscription, event) {
+                            Some(sender) => sender,
+                            None => return Ok(()),
+                        };
+                    }
+
+                    if reached_end(next_number) {
+                        send_response(sender, &subscription, range_end);
+                        return Ok(());
+                    }
+                }
+            }
+            Event::Remove { .. } => {
+                if !tombstones.contains_key(&stream.name)? {
+                    continue;
+                }
+
+                let deleted = Response::StreamDeleted {
+                    stream: stream.name.clone(),
+                };
+                send_response(sender, &subscription, deleted);
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Rejects the webhook metadata, the server can not deliver the events without the feature.
+#[cfg(not(feature = "webhooks"))]
+fn check_webhook_meta(key: &str, _value: &str) -> Result<(), String> {
+    if key.starts_with("webhook.") {
+        return Err(String::from(
+            "the server has been built without the webhooks feature",
+        ));
+    }
+    Ok(())
+}
+
+/// Returns `true` if the tree of the stream exists, the stream has been created or published to.
+fn stream_exists(db: &Db, stream: &EsStreamName) -> bool {
+    let name: &[u8] = stream.as_ref();
+    db.tree_names().iter().any(|n| &n[..] == name)
+}
+
+/// Returns the names of the streams stored in the database,
+/// internal trees are not listed.
+fn stream_names(db: &Db) -> Vec<EsStreamName> {
+    db.tree_names()
+        .into_iter()
+        .filter(|n| n != b"__sled__default")
+        .map(|b| String::from_utf8(b.to_vec()).unwrap())
+        .filter(|s| !s.starts_with(INTERNAL_TREE_PREFIX))
+        .map(|s| EsStreamName::new(s).unwrap())
+        .collect()
+}
+
+fn spawn_subscription(
+    db: &Db,
+    stream: EsStream,
+    sender: mpsc::Sender<Result<Response, String>>,
+    subscriptions: &Subscriptions,
+) -> Result<(), Error> {
+    let db = db.clone();
+    let subscription = register_subscription(
+        subscriptions,
+        &stream.name,
+        stream.slow_consumer,
+        stream.credits,
+        stream.batch,
+        &sender,
+    );
+
+    thread::Builder::new().spawn(|| {
+        let mut sender = sender;
+
+        let subscribed = Response::Subscribed {
+            stream: stream.name.clone(),
+        };
+        match sender.send(Ok(subscribed)).wait() {
+            Ok(s) => sender = s,
+            Err(_) => {
+                info!("encountered closed channel");
+                return;
+            }
+        }
+
+        if stream.name.is_ephemeral() {
+            ephemeral::send_stream_events(stream, sender, subscription);
+            return;
+        }
+
+        let result = send_stream_events(stream, db, sender.clone(), subscription);
+        if let Err(e) = result {
+            if sender.send(Err(ErrorCode::Err.error(e))).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+    })?;
+
+    Ok(())
+}
+
+fn spawn_all_subscription(
+    db: &Db,
+    stream: EsStream,
+    sender: mpsc::Sender<Result<Response, String>>,
+    subscriptions: &Subscriptions,
+    authentication: Authentication,
+) -> Result<(), Error> {
+    let db = db.clone();
+    let subscription = register_subscription(
+        subscriptions,
+        &stream.name,
+        stream.slow_consumer,
+        stream.credits,
+        stream.batch,
+        &sender,
+    );
+
+    thread::Builder::new().spawn(|| {
+        let mut sender = sender;
+
+        let subscribed = Response::Subscribed {
+            stream: stream.name.clone(),
+        };
+        match sender.send(Ok(subscribed)).wait() {
+            Ok(s) => sender = s,
+            Err(_) => {
+                info!("encountered closed channel");
+                return;
+            }
+        }
+
+        let result = all::send_all_events(stream, db, sender.clone(), subscription, authentication);
+        if let Err(e) = result {
+            if sender.send(Err(ErrorCode::Err.error(e))).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+    })?;
+
+    Ok(())
+}
+
+fn spawn_group_subscription(
+    db: &Db,
+    group: GroupName,
+    stream: EsStreamName,
+    consumer: String,
+    sender: mpsc::Sender<Result<Response, String>>,
+    subscriptions: &Subscriptions,
+) -> Result<(), Error> {
+    let db = db.clone();
+    let subscription = register_subscription(
+        subscriptions,
+        &stream,
+        SlowConsumer::Block,
+        None,
+        None,
+        &sender,
+    );
+
+    thread::Builder::new().spawn(|| {
+        let mut sender = sender;
+
+        let subscribed = Response::Subscribed {
+            stream: stream.clone(),
+        };
+        match sender.send(Ok(subscribed)).wait() {
+            Ok(s) => sender = s,
+            Err(_) => {
+                info!("encountered closed channel");
+                return;
+            }
+        }
+
+        let result =
+            groups::send_group_events(db, group, stream, consumer, sender.clone(), subscription);
+        if let Err(e) = result {
+            if sender.send(Err(ErrorCode::Err.error(e))).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+    })?;
+
+    Ok(())
+}
+
+/// Returns the error to send if the events published could not be flushed.
+fn flush_error(flushed: Result<Result<(), String>, oneshot::Canceled>) -> Option<String> {
+    let error = match flushed {
+        Ok(Ok(())) => return None,
+        Ok(Err(error)) => error,
+        Err(canceled) => canceled.to_string(),
+    };
+    Some(ErrorCode::Err.error(format_args!(
+        "the events have been published but not flushed; {}",
+        error
+    )))
+}
+
+/// Sends the response of a publication once enough replicas have acknowledged the events
+/// published so far, the replicas are waited for by another thread.
+///
+/// With the group commit, the response is also sent once the events are on disk.
+fn send_acknowledged(
+    db: &Db,
+    acks: &mut ConnectionAcks,
+    level: Option<AckLevel>,
+    sender: mpsc::Sender<Result<Response, String>>,
+    response: Response,
+) -> Result<(), Error> {
+    let level = acks.level(level);
+    let durable = acks.durable();
+
+    if level == AckLevel::Local {
+        match durable {
+            // the next requests of the connection wait for the flush, the responses stay in order
+            Some(durable) => acks.defer(durable.then(move |flushed| {
+                let response = match flush_error(flushed) {
+                    Some(error) => Err(error),
+                    None => Ok(response),
+                };
+                sender.send(response).then(|result| {
+                    if result.is_err() {
+                        info!("encountered closed channel");
+                    }
+                    Ok(())
+                })
+            })),
+            None => {
+                if sender.send(Ok(response)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // the events published concurrently may have greater global
+    // numbers, waiting for them too is harmless
+    let global = last_event_number(db, &EsStreamName::all())?.unwrap_or_else(EventNumber::zero);
+    let replica_acks = acks.replica_acks().clone();
+
+    thread::Builder::new()
+        .name(String::from("ack"))
+        .spawn(move || {
+            let response = match durable.and_then(|durable| flush_error(durable.wait())) {
+                Some(error) => Err(error),
+                None => match replica_acks.wait(level, global) {
+                    Ok(()) => Ok(response),
+                    Err((acked, required)) => Err(ErrorCode::NotReplicated.error(format_args!(
+                        "the events have been published but only \
+                         {} of the {} replicas required acknowledged them",
+                        acked, required
+                    ))),
+                },
+            };
+            if sender.send(response).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        })?;
+
+    Ok(())
+}
+
+fn handle_request(
+    request: Request,
+    db: Db,
+    sender: mpsc::Sender<Result<Response, String>>,
+    client: &Client,
+    authentication: &mut Authentication,
+    replication: &Replication,
+    acks: &mut ConnectionAcks,
+) -> Result<(), Error> {
+    if let Err(error) = authentication.check(&request) {
+        if sender.send(Err(error)).wait().is_err() {
+            info!("encountered closed channel");
+        }
+        return Ok(());
+    }
+
+    if let Some(error) = replication.write_error(&request) {
+        if sender.send(Err(error)).wait().is_err() {
+            info!("encountered closed channel");
+        }
+        return Ok(());
+    }
+
+    if let Some(error) = client.throttle_error(&db, &request)? {
+        if sender.send(Err(error)).wait().is_err() {
+            info!("encountered closed channel");
+        }
+        return Ok(());
+    }
+
+    // the administrative commands are recorded before being run, the failed ones too
+    if audit::is_audited(&request) {
+        audit::record(&db, authentication.user_name(), client.addr(), &request)?;
+    }
+
+    let subscriptions = client.subscriptions();
+    let storage = SledStorage::new(db.clone());
+
+    match request {
+        Request::SubscribeAll {
+            range,
+            event_names,
+            slow_consumer,
+            credits,
+            batch,
+        } => {
+            let stream = EsStream {
+                event_names,
+                slow_consumer,
+                credits,
+                batch,
+                ..EsStream::all(range)
+            };
+            if client.clients().polled_subscriptions().is_some() {
+                let error = ErrorCode::Err.error(
+                    "a subscription to all the streams is not supported by a simulated server",
+                );
+                if sender.send(Err(error)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            let authentication = authentication.clone();
+            spawn_all_subscription(&db, stream, sender, subscriptions, authentication)?;
+        }
+        Request::Subscribe { streams } => {
+            for stream in streams {
+                let stream = groups::resolve_committed(&db, stream)?;
+                if let Some(polled) = client.clients().polled_subscriptions() {
+                    polled.subscribe(&db, stream, sender.clone(), subscriptions)?;
+                } else if stream.name.is_virtual() {
+                    let authentication = authentication.clone();
+                    spawn_all_subscription(
+                        &db,
+                        stream,
+                        sender.clone(),
+                        subscriptions,
+                        authentication,
+                    )?;
+                } else {
+                    spawn_subscription(&db, stream, sender.clone(), subscriptions)?;
+                }
+            }
+        }
+        Request::SubscribeGroup {
+            group,
+            stream,
+            consumer,
+        } => {
+            if stream.is_ephemeral() {
+                let error = ErrorCode::Err.error(format_args!(
+                    "the ephemeral stream {} can not be consumed by a group",
+                    stream
+                ));
+                if sender.send(Err(error)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            match client.clients().polled_subscriptions() {
+                Some(polled) => {
+                    polled.subscribe_group(&db, group, stream, consumer, sender, subscriptions)?
+                }
+                None => {
+                    spawn_group_subscription(&db, group, stream, consumer, sender, subscriptions)?
+                }
+            }
+        }
+        Request::Ack {
+            group,
+            stream,
+            event_number,
+        } => {
+            groups::ack(&db, &stream, &group, event_number)?;
+            if sender.send(Ok(Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Flow { stream, credits } => {
+            let given = match subscriptions.streams.lock().unwrap().get(&stream) {
+                Some(subscription) => subscription.give_credits(credits),
+                None => false,
+            };
+
+            let response = if given {
+                Ok(Response::Ok)
+            } else {
+                Err(ErrorCode::Err.error(format_args!(
+                    "no flow controlled subscription to {}",
+                    stream
+                )))
+            };
+            if sender.send(response).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::CommitOffset {
+            group,
+            stream,
+            event_number,
+        } => {
+            groups::commit_offset(&db, &stream, &group, event_number)?;
+            if sender.send(Ok(Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::FetchOffset { group, stream } => {
+            let number = groups::fetch_offset(&db, &stream, &group)?;
+
+            let offset = Response::Offset {
+                group,
+                stream,
+                number,
+            };
+            if sender.send(Ok(offset)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Pending { group, stream } => {
+            let events = groups::pending_events(&db, &stream, &group)?;
+
+            let pending = Response::Pending {
+                group,
+                stream,
+                events,
+            };
+            if sender.send(Ok(pending)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Claim {
+            group,
+            stream,
+            event_number,
+            new_consumer,
+        } => {
+            let claimed = groups::claim(&db, &stream, &group, event_number, &new_consumer)?;
+
+            let response = claimed.ok_or_else(|| {
+                ErrorCode::Err.error(format_args!(
+                    "event {} of stream {} is not pending in group {}",
+                    event_number.0, stream, group
+                ))
+            });
+            if sender.send(response).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Unsubscribe { streams } => {
+            for stream in streams {
+                let removed = subscriptions.streams.lock().unwrap().remove(&stream);
+                if let Some(subscription) = removed {
+                    subscription.unsubscribe();
+                }
+
+                let unsubscribed = Response::Unsubscribed { stream };
+                if sender.clone().send(Ok(unsubscribed)).wait().is_err() {
+                    info!("encountered closed channel");
+                    break;
+                }
+            }
+        }
+        Request::Publish {
+            stream,
+            event_name,
+            event_data,
+            expected_version,
+            dedup_id,
+            mut headers,
+            deliver_at,
+            ack,
+        } => {
+            if let Some(error) = publish_error(&db, &stream)? {
+                if sender.send(Err(error)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            trace::appended(&stream, &mut headers);
+
+            // the events of an ephemeral stream are neither stored nor replicated
+            if stream.is_ephemeral() {
+                let result = if dedup_id.is_some() || deliver_at.is_some() {
+                    Err(ErrorCode::Err.error(format_args!(
+                        "dedup-id and deliver-at can not be used with the ephemeral stream {}",
+                        stream
+                    )))
+                } else {
+                    let events = vec![(event_name, event_data, headers)];
+                    match ephemeral::publish(&stream, events, expected_version) {
+                        Ok((number, _)) => {
+                            metrics::published(&stream, 1);
+                            Ok(Response::Published { stream, number })
+                        }
+                        Err(last) => Err(version_error(&stream, last, expected_version.unwrap())),
+                    }
+                };
+                if sender.send(result).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            let tree = db.open_tree(stream.clone().into_bytes())?;
+            let compressed = compression::is_enabled(&db, &stream)?;
+            let timestamp = Timestamp::now();
+            let raw_event = raw_event(timestamp, &event_name, &headers, &event_data.0, compressed);
+
+            let dedup_id = dedup_id.as_deref();
+
+            if let Some(deliver_at) = deliver_at {
+                // the version of the stream can only be checked when the event is appended
+                if expected_version.is_some() {
+                    let error = ErrorCode::Err.error("version can not be used with deliver-at");
+                    if sender.send(Err(error)).wait().is_err() {
+                        info!("encountered closed channel");
+                    }
+                    return Ok(());
+                }
+
+                scheduled::schedule(&db, &stream, deliver_at, &raw_event, dedup_id)?;
+                info!("{:?} {:?} scheduled at {}", stream, event_name, deliver_at);
+
+                let scheduled = Response::Scheduled { stream, deliver_at };
+                if sender.send(Ok(scheduled)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            let timer = metrics::operation_timer(metrics::APPEND);
+            let publication =
+                publish_checked(&db, &tree, &stream, &raw_event, expected_version, dedup_id)?;
+            timer.observe_duration();
+
+            let event_number = match publication {
+                Publication::Published(number) => {
+                    if dedup_id.is_some() {
+                        dedup::prune(&db, &stream, number)?;
+                    }
+                    stream_written(&db, &stream, number)?;
+                    metrics::published(&stream, 1);
+                    number
+                }
+                Publication::Duplicate(number) => {
+                    // the event has already been published, probably by a client retry
+                    let published = Response::Published { stream, number };
+                    return send_acknowledged(&db, acks, ack, sender, published);
+                }
+                Publication::Conflict(last) => {
+                    let error = version_error(&stream, last, expected_version.unwrap());
+                    if sender.send(Err(error)).wait().is_err() {
+                        info!("encountered closed channel");
+                    }
+                    return Ok(());
+                }
+            };
+
+            info!("{:?} {:?} {:?}", stream, event_name, event_number);
+
+            let published = Response::Published {
+                stream,
+                number: event_number,
+            };
+            send_acknowledged(&db, acks, ack, sender, published)?;
+        }
+        Request::PublishBatch { stream, events } => {
+            if let Some(error) = publish_error(&db, &stream)? {
+                if sender.send(Err(error)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            let count = events.len();
+            let is_ephemeral = stream.is_ephemeral();
+            let (first, last) = if is_ephemeral {
+                let events = events
+                    .into_iter()
+                    .map(|(name, data)| (name, data, EventHeaders::new()))
+                    .collect();
+                // publishing without an expected version never conflicts
+                ephemeral::publish(&stream, events, None).unwrap()
+            } else {
+                let timer = metrics::operation_timer(metrics::APPEND);
+                let numbers = storage.append(&stream, &events)?;
+                timer.observe_duration();
+                stream_written(&db, &stream, numbers.0)?;
+                numbers
+            };
+            metrics::published(&stream, count);
+
+            info!("{:?} {} events {:?}..={:?}", stream, count, first, last);
+
+            let published = Response::PublishedBatch {
+                stream,
+                first,
+                last,
+            };
+            if is_ephemeral {
+                if sender.send(Ok(published)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+            } else {
+                send_acknowledged(&db, acks, None, sender, published)?;
+            }
+        }
+        Request::PublishMulti { writes } => {
+            for (stream, _, _) in &writes {
+                let error = if stream.is_ephemeral() {
+                    Some(ErrorCode::Err.error(format_args!(
+                        "the ephemeral stream {} can not be written with publish-multi",
+                        stream
+                    )))
+                } else {
+                    publish_error(&db, stream)?
+                };
+                if let Some(error) = error {
+                    if sender.send(Err(error)).wait().is_err() {
+                        info!("encountered closed channel");
+                    }
+                    return Ok(());
+                }
+            }
+
+            let timer = metrics::operation_timer(metrics::APPEND);
+            let events = publish_multi(&db, &writes)?;
+            timer.observe_duration();
+
+            for (stream, number) in &events {
+                stream_written(&db, stream, *number)?;
+                metrics::published(stream, 1);
+                info!("{:?} {:?}", stream, number);
+            }
+
+            let published = Response::PublishedMulti { events };
+            send_acknowledged(&db, acks, None, sender, published)?;
+        }
+        Request::DeleteStream { stream, hard } => {
+            if let Some(error) = audit::protected_error(&stream) {
+                if sender.send(Err(error)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            // the streams deleted permanently do not exist anymore but keep their tombstone
+            if !is_tombstoned(&db, &stream)? {
+                if let Some(error) = missing_stream_error(&storage, &stream)? {
+                    if sender.send(Err(error)).wait().is_err() {
+                        info!("encountered closed channel");
+                    }
+                    return Ok(());
+                }
+            }
+
+            if stream.is_ephemeral() {
+                let result = if hard {
+                    Err(ErrorCode::Err.error(format_args!(
+                        "the ephemeral stream {} can not be deleted permanently",
+                        stream
+                    )))
+                } else {
+                    ephemeral::delete(&stream);
+                    info!("stream {} deleted", stream);
+                    Ok(Response::Ok)
+                };
+                if sender.send(result).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            if hard {
+                tombstone_stream(&db, &stream)?;
+                info!("stream {} deleted permanently", stream);
+            } else if is_tombstoned(&db, &stream)? {
+                if sender.send(Err(tombstoned_error(&stream))).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            } else {
+                delete_stream(&db, &stream)?;
+                info!("stream {} deleted", stream);
+            }
+
+            if sender.send(Ok(Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::TrimStream { stream, up_to } => {
+            if let Some(error) = audit::protected_error(&stream) {
+                if sender.send(Err(error)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            if let Some(error) = missing_stream_error(&storage, &stream)? {
+                if sender.send(Err(error)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            let floor = if stream.is_ephemeral() {
+                ephemeral::trim(&stream, EventNumber(up_to))
+            } else {
+                storage.trim(&stream, EventNumber(up_to))?
+            };
+            info!("stream {} trimmed up to {:?}", stream, floor);
+
+            if sender.send(Ok(Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::CreateStream { stream } => {
+            if is_tombstoned(&db, &stream)? {
+                if sender.send(Err(tombstoned_error(&stream))).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            if stream.is_ephemeral() {
+                ephemeral::create(&stream);
+            } else {
+                create_stream(&db, &stream)?;
+            }
+
+            if sender.send(Ok(Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::LastEventNumber { stream } => {
+            let number = if stream.is_ephemeral() {
+                ephemeral::last_event_number(&stream)
+            } else {
+                storage.head(&stream)?
+            };
+
+            let last_event_number = Response::LastEventNumber { stream, number };
+            if sender.send(Ok(last_event_number)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::SetStreamMeta { stream, key, value } => {
+            if let Some(error) = audit::protected_error(&stream) {
+                if sender.send(Err(error)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            let checked = retention::check_meta(&key, &value)
+                .and_then(|()| compaction::check_meta(&key, &value))
+                .and_then(|()| rate::check_meta(&key, &value))
+                .and_then(|()| compression::check_meta(&key, &value))
+                .and_then(|()| check_webhook_meta(&key, &value));
+            if let Err(error) = checked {
+                let error = ErrorCode::Err.error(error);
+                if sender.send(Err(error)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            meta::set(&db, &stream, &key, &value)?;
+
+            // the events previously published to a stream switched
+            // to the last-value mode are removed right away
+            if retention::is_last_value(&db, &stream)? {
+                if let Some(last) = last_event_number(&db, &stream)? {
+                    trim_stream(&db, &stream, last)?;
+                }
+            }
+
+            // the subscribers of the meta stream are notified of the change
+            let mut headers = EventHeaders::new();
+            headers.insert(String::from("key"), key);
+            let meta_stream = meta::meta_stream(&stream);
+            publish_system_event(&db, &meta_stream, "MetaSet", &headers, value.as_bytes())?;
+
+            if sender.send(Ok(Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::StreamInfo { stream } => {
+            if let Some(error) = missing_stream_error(&storage, &stream)? {
+                if sender.send(Err(error)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            let first = first_event_number(&db, &stream)?.unwrap_or_else(EventNumber::zero);
+            let last = last_event_number(&db, &stream)?;
+            let bytes = retention::stream_size(&db, &stream)?;
+            // the tree is not opened for the streams never written, it would create them
+            let events = match last {
+                Some(_) => db.open_tree(stream.as_ref())?.len() as u64,
+                None => 0,
+            };
+            let created = creation_time(&db, &stream)?;
+            let retention_ms = retention::retention_ms(&db, &stream)?;
+            let limits = retention::limits(&db, &stream)?;
+            let subscribers = client.clients().subscribers(&stream);
+
+            let info = Response::StreamInfo {
+                stream,
+                first,
+                last,
+                bytes,
+                events,
+                created,
+                retention_ms,
+                max_events: limits.max_events(),
+                max_bytes: limits.max_bytes(),
+                subscribers,
+            };
+            if sender.send(Ok(info)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Verify { stream } => {
+            let verified = verify::verify_stream(&db, stream)?;
+            if sender.send(Ok(verified)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::GetStreamMeta { stream } => {
+            let meta = meta::get_all(&db, &stream)?;
+            let response = Response::StreamMeta { stream, meta };
+            if sender.send(Ok(response)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Compact => {
+            // the streams are compacted by another thread to keep serving the connection
+            thread::Builder::new()
+                .name(String::from("compact"))
+                .spawn(move || {
+                    let response = match compaction::reclaim(&db) {
+                        Ok((before, after)) => {
+                            info!("database compacted from {} to {} bytes", before, after);
+                            Ok(Response::Compacted { before, after })
+                        }
+                        Err(e) => {
+                            Err(ErrorCode::Err.error(format_args!("compaction failed; {}", e)))
+                        }
+                    };
+                    if sender.send(response).wait().is_err() {
+                        info!("encountered closed channel");
+                    }
+                })?;
+        }
+        Request::Backup { path } => {
+            // the backup is written by another thread to keep serving the connection
+            thread::Builder::new()
+                .name(String::from("backup"))
+                .spawn(move || {
+                    let response = match backup::backup(&db, Path::new(&path)) {
+                        Ok(()) => {
+                            info!("backup written to {}", path);
+                            Ok(Response::Ok)
+                        }
+                        Err(e) => Err(ErrorCode::Err.error(format_args!("backup failed; {}", e))),
+                    };
+                    if sender.send(response).wait().is_err() {
+                        info!("encountered closed channel");
+                    }
+                })?;
+        }
+        Request::ReplicaOf { .. } if replication.failover().is_some() => {
+            let error = ErrorCode::Err.error("the replication is managed by the cluster");
+            if sender.send(Err(error)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::ReplicaOf { primary } => {
+            let primary = match primary.map(|p| p.parse::<SocketAddr>()).transpose() {
+                Ok(primary) => primary,
+                Err(e) => {
+                    let error =
+                        ErrorCode::Err.error(format_args!("invalid primary address; {}", e));
+                    if sender.send(Err(error)).wait().is_err() {
+                        info!("encountered closed channel");
+                    }
+                    return Ok(());
+                }
+            };
+
+            replication.replicate(db, primary)?;
+            match primary {
+                Some(primary) => info!("server is now a replica of {}", primary),
+                None => info!("server is no longer a replica"),
+            }
+
+            if sender.send(Ok(Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::ReplicationInfo => {
+            let info = replication.info(&db)?;
+            if sender.send(Ok(info)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::RequestVote {
+            term,
+            candidate,
+            last_global,
+        } => {
+            let response = match replication.failover() {
+                Some(failover) => {
+                    let (term, success) = failover.request_vote(term, &candidate, last_global)?;
+                    Ok(Response::RaftReply { term, success })
+                }
+                None => Err(ErrorCode::Err.error("the server is not part of a cluster")),
+            };
+            if sender.send(response).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Heartbeat { term, leader } => {
+            let response = match replication.failover() {
+                Some(failover) => {
+                    let (term, success) = failover.heartbeat(term, &leader, replication)?;
+                    Ok(Response::RaftReply { term, success })
+                }
+                None => Err(ErrorCode::Err.error("the server is not part of a cluster")),
+            };
+            if sender.send(response).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::ReadOnly { enabled } => {
+            replication.set_read_only(enabled);
+            if enabled {
+                info!("server is now read-only");
+            } else {
+                info!("server is no longer read-only");
+            }
+
+            if sender.send(Ok(Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::ClientList => {
+            let clients = Response::ClientList {
+                clients: client.clients().list(),
+            };
+            if sender.send(Ok(clients)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::ClientKill { id } => {
+            let response = if client.clients().kill(id) {
+                info!("client {} killed", id);
+                Ok(Response::Ok)
+            } else {
+                Err(ErrorCode::Err.error(format_args!("no client with the id {}", id)))
+            };
+            if sender.send(response).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::ConfigSet { key, value } => {
+            let result = match key.as_str() {
+                logging::LOG_LEVEL => logging::set_level(&value),
+                _otherwise => Err(format!("unknown configuration parameter {:?}", key)),
+            };
+            let response = match result {
+                Ok(()) => {
+                    info!("{} set to {:?}", key, value);
+                    Ok(Response::Ok)
+                }
+                Err(e) => Err(ErrorCode::Err.error(e)),
+            };
+            if sender.send(response).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::SlowLog { reset } => {
+            let response = if reset {
+                slowlog::reset();
+                Response::Ok
+            } else {
+                Response::SlowLog {
+                    entries: slowlog::entries(),
+                }
+            };
+            if sender.send(Ok(response)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Latency => {
+            let (commands, operations) = metrics::latencies();
+            let latency = Response::Latency {
+                commands,
+                operations,
+            };
+            if sender.send(Ok(latency)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Monitor => {
+            info!("client {} is monitoring the commands", client.id());
+            client.set_monitor(sender.clone());
+            if sender.send(Ok(Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Info => {
+            let info = info::server_info(&db)?;
+            if sender.send(Ok(info)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::SetAckLevel { level } => {
+            acks.level = level;
+            if sender.send(Ok(Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::ReplicaAck { global } => {
+            acks.replica_ack(global);
+            if sender.send(Ok(Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::StreamNames { pattern } => {
+            let stream_names = storage
+                .stream_names()?
+                .into_iter()
+                .chain(ephemeral::stream_names())
+                .filter(|n| match &pattern {
+                    Some(pattern) => n.matches(pattern),
+                    None => true,
+                })
+                .filter(|n| authentication.can_subscribe(n))
+                .collect();
+            let streams = Response::StreamNames {
+                streams: stream_names,
+            };
+
+            if sender.send(Ok(streams)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Ping { payload } => {
+            if sender.send(Ok(Response::Pong { payload })).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Hello { proto } => {
+            // the codec switches to the new protocol when sending this response
+            let response = match proto {
+                Some(proto) if proto != 2 && proto != 3 => {
+                    let message = format!("unsupported protocol version {}", proto);
+                    Err(ErrorCode::NoProto.error(message))
+                }
+                Some(proto) => {
+                    client.set_proto(proto);
+                    Ok(info::hello(proto))
+                }
+                None => Ok(info::hello(client.proto())),
+            };
+            if sender.send(response).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Compress { threshold } => {
+            // the codec compresses the responses that follow this one
+            let response = Response::Compression { threshold };
+            if sender.send(Ok(response)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::FrameSize { size } => {
+            // the codec chunks the bulk strings of the responses that follow this one
+            let result = if size == 0 {
+                Err(ErrorCode::Err.error("the frame size must not be zero"))
+            } else {
+                Ok(Response::FrameSize { size })
+            };
+            if sender.send(result).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::Auth { username, password } => {
+            let result = authentication.authenticate(username.as_deref(), &password);
+            client.set_user(authentication.user_name());
+            if sender.send(result.map(|()| Response::Ok)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+        Request::GetRange { stream, from, to } => {
+            if let Some(error) = missing_stream_error(&storage, &stream)? {
+                if sender.send(Err(error)).wait().is_err() {
+                    info!("encountered closed channel");
+                }
+                return Ok(());
+            }
+
+            // the events are not all loaded in memory, the range is read in slices
+            let from = from.unwrap_or_else(EventNumber::zero);
+            let max_to = EventNumber(from.0.saturating_add(MAX_RANGE_EVENTS));
+            let to = Some(to.map_or(max_to, |to| to.min(max_to)));
+            let events = if stream.is_ephemeral() {
+                ephemeral::read_range(&stream, from, to)
+            } else {
+                storage.read_range(&stream, from, to)?
+            };
+
+            let range = Response::Range { stream, events };
+            if sender.send(Ok(range)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "sentry")]
+fn init_sentry() {
+    let guard = sentry::init(sentry::ClientOptions::default());
+
+    if guard.is_enabled() {
+        eprintln!("I am sentrified! 🎉");
+    }
+
+    sentry::integrations::panic::register_panic_handler();
+    sentry::integrations::env_logger::init(None, Default::default());
+}
+
+#[cfg(feature = "vigil")]
+fn init_vigil() {
+    use std::env;
+    use vigil::Reporter;
+
+    let endpoint = env::var("VIGIL_ENDPOINT").expect("VIGIL_ENDPOINT");
+    let token = env::var("VIGIL_TOKEN").expect("VIGIL_TOKEN");
+    let probe = env::var("VIGIL_PROBE").expect("VIGIL_PROBE");
+    let node = env::var("VIGIL_NODE").expect("VIGIL_NODE");
+    let replica = env::var("VIGIL_REPLICA").expect("VIGIL_REPLICA");
+
+    let reporter = Reporter::new(&endpoint, &token)
+        .probe_id(&probe)
+        .node_id(&node)
+        .replica_id(&replica)
+        .interval(Duration::from_secs(10))
+        .build();
+
+    reporter.run().expect("Can not start vigil");
+
+    eprintln!("I am vigiled! 🎉");
+}
+
+/// Runs a command sent by a client and records it, the responses are sent to the sender.
+/// The commands of the RESP connections and of the HTTP gateway go through it.
+fn dispatch(
+    request: Request,
+    db: Db,
+    sender: mpsc::Sender<Result<Response, String>>,
+    client: &Client,
+    authentication: &mut Authentication,
+    replication: &Replication,
+    acks: &mut ConnectionAcks,
+) -> Result<(), Error> {
+    client.touch();
+    client.monitored(&request);
+    let command = metrics::COMMAND_DURATION.with_label_values(&[request.command_name()]);
+    let timer = command.start_timer();
+    // the request is only kept for the slow log when it is enabled
+    let logged = if slowlog::is_enabled() {
+        Some(request.clone())
+    } else {
+        None
+    };
+    let started = Instant::now();
+    let result = {
+        let span = info_span!("command", name = request.command_name());
+        let _enter = span.enter();
+        handle_request(
+            request,
+            db,
+            sender,
+            client,
+            authentication,
+            replication,
+            acks,
+        )
+    };
+    timer.observe_duration();
+    if let Some(request) = logged {
+        slowlog::command(client.addr(), &request, started.elapsed());
+    }
+
+    result
+}
+
+/// Runs a command like `dispatch` from a thread that can block, e.g. a worker of the HTTP
+/// gateway, and waits for the response of a publication waiting for the group commit.
+fn dispatch_blocking(
+    request: Request,
+    db: Db,
+    sender: mpsc::Sender<Result<Response, String>>,
+    client: &Client,
+    authentication: &mut Authentication,
+    replication: &Replication,
+    acks: &mut ConnectionAcks,
+) -> Result<(), Error> {
+    let result = dispatch(
+        request,
+        db,
+        sender,
+        client,
+        authentication,
+        replication,
+        acks,
+    );
+
+    // the response of a publication waiting for the group commit is sent first
+    let _ = acks.take_deferred().wait();
+
+    result
+}
+
+/// Spawns the tasks that handle the requests of a connection and send back the responses.
+fn handle_connection<S>(
+    framed: Framed<S, ServerCodec>,
+    addr: SocketAddr,
+    clients: Clients,
+    db: Db,
+    mut authentication: Authentication,
+    replication: Replication,
+    mut acks: ConnectionAcks,
+) where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (writer, reader) = framed.split();
+    let (sender, receiver) = mpsc::channel(10);
+    let (client, killed) = clients.register(addr, authentication.user_name());
+
+    // the connection is only closed when the client is killed,
+    // not when the client is dropped at the end of the requests
+    let killed = killed
+        .then(|result| match result {
+            Ok(()) => future::Either::A(future::ok::<(), ()>(())),
+            Err(_canceled) => future::Either::B(future::empty()),
+        })
+        .shared();
+
+    let error_sender = sender.clone();
+    let span = info_span!("connection", client = client.id(), addr = %addr);
+
+    let requests = reader
+        .map_err(Error::RequestMsgError)
+        .for_each(move |request| {
+            let db = db.clone();
+            let sender = sender.clone();
+            let result = dispatch(
+                request,
+                db,
+                sender,
+                &client,
+                &mut authentication,
+                &replication,
+                &mut acks,
+            );
+
+            // the response of a publication waiting for the group commit is sent first
+            let deferred = acks.take_deferred();
+            future::result(result).and_then(|()| deferred.then(|_| Ok(())))
+        })
+        .or_else(move |error| {
+            error!("error; {}", error);
+            let error = ErrorCode::Err.error(error);
+            if error_sender.send(Err(error)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+
+            future::ok::<(), ()>(())
+        })
+        .select2(killed.clone())
+        .then(|_| Ok(()))
+        .instrument(span.clone());
+
+    let responses = receiver
+        .map_err(|e| {
+            let error = RespMsgError::IoError(IoError::new(ErrorKind::BrokenPipe, e));
+            ResponseMsgError::RespMsgError(error)
+        })
+        .forward(writer)
+        .map_err(|error| {
+            use crate::RespMsgError::IoError;
+            use ResponseMsgError::RespMsgError;
+
+            match error {
+                RespMsgError(IoError(ref e)) if e.kind() == ErrorKind::BrokenPipe => {
+                    info!("{}", e);
+                }
+                other => error!("{}", other),
+            }
+        })
+        .map(drop)
+        .select2(killed)
+        .then(|_| Ok(()))
+        .instrument(span);
+
+    tokio::spawn(requests);
+    tokio::spawn(responses);
+}
+
+/// Starts the server with the options of the command line, until it is stopped.
+pub fn run(opt: Opt) {
+    info::started();
+
+    #[cfg(feature = "sentry")]
+    {
+        if !opt.no_sentry {
+            init_sentry();
+        }
+    }
+
+    #[cfg(feature = "vigil")]
+    {
+        if !opt.no_vigil {
+            init_vigil();
+        }
+    }
+
+    if !cfg!(feature = "sentry") || opt.no_sentry {
+        if let Err(e) = logging::init(opt.log_json) {
+            eprintln!("error initializing the logs; {}", e);
+        }
+    }
+
+    let addr = match opt.hostname.parse() {
+        Ok(addr) => addr,
+        Err(e) => return error!("error parsing addr {:?}; {}", opt.hostname, e),
+    };
+
+    let addr = SocketAddr::new(addr, opt.port);
+
+    let now = Instant::now();
+
+    let flush = if !opt.flush.is_empty() {
+        match FlushPolicy::from_args(&opt.flush) {
+            Ok(policy) => policy,
+            Err(e) => return error!("{}", e),
+        }
+    } else if opt.group_commit_window.is_some() {
+        FlushPolicy::EveryWrite
+    } else {
+        FlushPolicy::default()
+    };
+    if opt.group_commit_window.is_some() && flush != FlushPolicy::EveryWrite {
+        return error!("the group commit requires the every-write flush policy");
+    }
+    info::flush_policy(flush);
+
+    if opt.ephemeral_capacity == 0 {
+        return error!("the ephemeral capacity must not be zero");
+    }
+    ephemeral::set_capacity(opt.ephemeral_capacity);
+    tail::set_capacity(opt.tail_cache_size);
+    slowlog::configure(
+        Duration::from_millis(opt.slowlog_threshold),
+        Duration::from_millis(opt.slowlog_delivery_threshold),
+        opt.slowlog_max_len,
+    );
+
+    let db_path = opt.db_path.clone();
+    let mut config = flush.configure(Config::new().path(opt.db_path));
+
+    if let Some(compression_factor) = opt.compression_factor {
+        config = config
+            .use_compression(true)
+            .compression_factor(compression_factor);
+    }
+
+    let db = match config.open() {
+        Ok(db) => db,
+        Err(e) => return error!("error opening database; {}", e),
+    };
+    info!("kv-store loaded in {:.2?}", now.elapsed());
+
+    if !opt.restore.is_empty() {
+        let now = Instant::now();
+        match backup::restore(&db, &opt.restore) {
+            Ok(heads) => {
+                for (stream, last) in heads {
+                    info!("stream {} restored up to {:?}", stream, last);
+                }
+                info!("backup {:?} restored in {:.2?}", opt.restore, now.elapsed());
+            }
+            Err(e) => return error!("error restoring the backup {:?}; {}", opt.restore, e),
+        }
+    }
+
+    #[cfg(feature = "object-storage")]
+    {
+        if let Some(url) = &opt.upload_url {
+            let interval = Duration::from_secs(opt.upload_interval);
+            let region = opt.upload_region.clone();
+            if let Err(e) = upload::spawn_uploader(db.clone(), url, region, interval) {
+                return error!("error spawning the upload thread; {}", e);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "object-storage"))]
+    {
+        if opt.upload_url.is_some() {
+            return error!("the server has been built without the object-storage feature");
+        }
+    }
+
+    // the background threads of the server run until the end of the process
+    let shutdown = Shutdown::new();
+
+    let expiry_interval = Duration::from_secs(opt.expiry_interval);
+    if let Err(e) = retention::spawn_expiry(db.clone(), expiry_interval, shutdown.clone()) {
+        return error!("error spawning the expiry thread; {}", e);
+    }
+
+    let compaction_interval = Duration::from_secs(opt.compaction_interval);
+    if let Err(e) = compaction::spawn_compactor(db.clone(), compaction_interval, shutdown.clone()) {
+        return error!("error spawning the compaction thread; {}", e);
+    }
+
+    if let Some(reclaim_interval) = opt.reclaim_interval {
+        let reclaim_interval = Duration::from_secs(reclaim_interval);
+        if let Err(e) = compaction::spawn_reclaimer(db.clone(), reclaim_interval) {
+            return error!("error spawning the reclamation thread; {}", e);
+        }
+    }
+
+    if opt.min_free_space != 0 {
+        if let Err(e) = disk::spawn_monitor(db_path, opt.min_free_space) {
+            return error!("error spawning the disk monitor thread; {}", e);
+        }
+    }
+
+    if let Err(e) = scheduled::spawn_scheduler(db.clone(), shutdown) {
+        return error!("error spawning the scheduler thread; {}", e);
+    }
+
+    if let Some(addr) = opt.metrics_addr {
+        if let Err(e) = metrics::spawn_exporter(db.clone(), addr) {
+            return error!("error spawning the metrics thread; {}", e);
+        }
+    }
+
+    let mut replication = Replication::new(opt.primary_user.clone(), opt.primary_password.clone());
+    let replica_of = opt.replica_of;
+    replication.set_read_only(opt.read_only);
+    let mut replica_acks = ReplicaAcks::new(Duration::from_millis(opt.ack_timeout));
+    if flush == FlushPolicy::EveryWrite {
+        // the concurrent publications are flushed together even without a window
+        let window = Duration::from_millis(opt.group_commit_window.unwrap_or(0));
+        match GroupCommit::spawn(db.clone(), window) {
+            Ok(commit) => replica_acks = replica_acks.with_group_commit(commit),
+            Err(e) => return error!("error spawning the group commit thread; {}", e),
+        }
+    }
+
+    let failover = if opt.peers.is_empty() {
+        None
+    } else if replica_of.is_some() {
+        return error!("a cluster node cannot be a replica of another server");
+    } else {
+        let node = opt.advertise_addr.unwrap_or(addr);
+        let username = opt.primary_user;
+        let password = opt.primary_password;
+        match Failover::new(db.clone(), node, opt.peers, username, password) {
+            Ok(failover) => Some(failover),
+            Err(e) => return error!("error loading the cluster state; {}", e),
+        }
+    };
+
+    if let Some(failover) = &failover {
+        replica_acks = replica_acks.with_cluster_nodes(failover.nodes());
+        replication = replication.with_failover(failover.clone());
+    }
+
+    #[cfg(feature = "webhooks")]
+    {
+        let secret = opt.webhook_secret;
+        let max_attempts = opt.webhook_max_attempts;
+        let replication = replication.clone();
+        if let Err(e) = webhook::spawn_deliverer(db.clone(), replication, secret, max_attempts) {
+            return error!("error spawning the webhooks thread; {}", e);
+        }
+    }
+
+    #[cfg(not(feature = "webhooks"))]
+    {
+        if opt.webhook_secret.is_some() {
+            return error!("the server has been built without the webhooks feature");
+        }
+    }
+
+    let requirepass = opt.requirepass;
+    let acl = match opt.acl_file.map(Acl::from_path).transpose() {
+        Ok(acl) => acl.map(Arc::new),
+        Err(e) => return error!("error reading the ACL file; {}", e),
+    };
+
+    let client_ca = opt.tls_client_ca;
+    let acceptor = match (opt.tls_cert, opt.tls_key) {
+        (Some(cert), Some(key)) => match tls_acceptor(&cert, &key, client_ca.as_deref()) {
+            Ok(acceptor) => Some(acceptor),
+            Err(e) => return error!("error loading the TLS certificate; {}", e),
+        },
+        (None, None) if client_ca.is_none() => None,
+        _otherwise => return error!("both a TLS certificate and a private key are required"),
+    };
+
+    let listener = match TcpListener::bind(&addr) {
+        Ok(listener) => listener,
+        Err(e) => return error!("error binding address; {}", e),
+    };
+    println!("server is listening on {}", addr);
+
+    let replication_db = db.clone();
+    let server_replication = replication.clone();
+    let clients = Clients::new(rate::Limit {
+        events: opt.max_connection_events_per_sec,
+        bytes: opt.max_connection_bytes_per_sec,
+    });
+    let codec = match opt.max_event_size {
+        Some(size) => ServerCodec::with_max_event_size(size),
+        None => ServerCodec::default(),
+    };
+
+    let gateway = Gateway {
+        db: db.clone(),
+        clients: clients.clone(),
+        replication: server_replication.clone(),
+        acks: replica_acks.clone(),
+        requirepass: requirepass.clone(),
+        acl: acl.clone(),
+        max_event_size: opt.max_event_size,
+    };
+
+    // the gRPC server is stopped when dropped, at the end of the main function
+    #[cfg(feature = "grpc")]
+    let _grpc_server = match opt.grpc_addr {
+        Some(grpc_addr) => match grpc::start(gateway.clone(), grpc_addr) {
+            Ok(server) => Some(server),
+            Err(e) => return error!("error starting the gRPC server; {}", e),
+        },
+        None => None,
+    };
+
+    #[cfg(not(feature = "grpc"))]
+    {
+        if opt.grpc_addr.is_some() {
+            return error!("the server has been built without the grpc feature");
+        }
+    }
+
+    if let Some(http_addr) = opt.http_addr {
+        if let Err(e) = gateway::spawn(gateway, http_addr) {
+            return error!("error spawning the HTTP gateway; {}", e);
+        }
+    }
+
+    let server = listener
+        .incoming()
+        .map_err(|e| error!("error accepting socket; {}", e))
+        .for_each(move |socket| {
+            let addr = match socket.peer_addr() {
+                Ok(addr) => addr,
+                Err(e) => {
+                    error!("error reading the address of the client; {}", e);
+                    return future::ok(());
+                }
+            };
+            let authentication = Authentication::new(requirepass.clone(), acl.clone());
+            let replication = server_replication.clone();
+            let acks = replica_acks.connection();
+            let clients = clients.clone();
+            let db = db.clone();
+
+            match &acceptor {
+                Some(acceptor) => {
+                    let accept = acceptor
+                        .accept(socket)
+                        .map(move |stream| {
+                            let mut authentication = authentication;
+                            if let Some(name) = peer_common_name(stream.get_ref().1) {
+                                authentication.authenticate_certificate(&name);
+                            }
+                            handle_connection(
+                                codec.framed(stream),
+                                addr,
+                                clients,
+                                db,
+                                authentication,
+                                replication,
+                                acks,
+                            )
+                        })
+                        .map_err(|e| error!("error during the TLS handshake; {}", e));
+                    tokio::spawn(accept);
+                }
+                None => {
+                    let framed = codec.framed(socket);
+                    handle_connection(framed, addr, clients, db, authentication, replication, acks)
+                }
+            }
+
+            future::ok(())
+        });
+
+    // the replication and the failover are spawned on the runtime of the server
+    let server = future::lazy(move || {
+        if let Some(failover) = failover {
+            failover.spawn(replication.clone());
+        }
+        if let Some(primary) = replica_of {
+            if let Err(e) = replication.replicate(replication_db, Some(primary)) {
+                error!("error starting the replication of {}; {}", primary, e);
+            }
+        }
+        server
+    });
+
+    tokio::run(server)
+}
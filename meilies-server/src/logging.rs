@@ -0,0 +1,44 @@
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+/// The configuration parameter that changes the log level at runtime.
+pub const LOG_LEVEL: &str = "log-level";
+
+/// The log level used when the `RUST_LOG` variable is not set.
+const DEFAULT_LEVEL: &str = "error";
+
+lazy_static! {
+    static ref FILTER: Mutex<Option<reload::Handle<EnvFilter, Registry>>> = Mutex::default();
+}
+
+/// Installs the subscriber that writes the logs to the standard output, as JSON lines if
+/// `json` is `true`. The logs of the dependencies using the `log` crate are written too.
+pub fn init(json: bool) -> Result<(), String> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LEVEL));
+    let (filter, handle) = reload::Layer::new(filter);
+    let registry = tracing_subscriber::registry().with(filter);
+
+    let result = if json {
+        registry.with(fmt::layer().json()).try_init()
+    } else {
+        registry.with(fmt::layer()).try_init()
+    };
+    result.map_err(|e| e.to_string())?;
+
+    *FILTER.lock().unwrap() = Some(handle);
+    Ok(())
+}
+
+/// Changes the logs written, the level uses the syntax of the `RUST_LOG` variable,
+/// e.g. `debug` or `info,meilies_server=trace`.
+pub fn set_level(level: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(level).map_err(|e| e.to_string())?;
+    match &*FILTER.lock().unwrap() {
+        Some(handle) => handle.reload(filter).map_err(|e| e.to_string()),
+        None => Err(String::from("the logs are not written by the server")),
+    }
+}
@@ -0,0 +1,69 @@
+use sled::Db;
+
+use meilies::stream::StreamName as EsStreamName;
+
+/// The tree that keeps the metadata of the streams, keys are the
+/// stream name followed by a colon and the metadata key.
+const STREAM_META_TREE: &str = "meilies:stream-meta";
+
+/// The prefix of the streams that receive a `MetaSet` event each time a metadata
+/// of a stream is set, e.g. `$meta-orders` for the `orders` stream.
+const META_STREAM_PREFIX: &str = "$meta-";
+
+fn stream_prefix(stream: &EsStreamName) -> Vec<u8> {
+    let mut prefix = stream.as_str().as_bytes().to_vec();
+    prefix.push(b':');
+    prefix
+}
+
+fn meta_key(stream: &EsStreamName, key: &str) -> Vec<u8> {
+    let mut meta_key = stream_prefix(stream);
+    meta_key.extend_from_slice(key.as_bytes());
+    meta_key
+}
+
+/// Returns the name of the stream that receives the metadata changes of the given stream.
+pub fn meta_stream(stream: &EsStreamName) -> EsStreamName {
+    EsStreamName::new(format!("{}{}", META_STREAM_PREFIX, stream)).unwrap()
+}
+
+pub fn set(db: &Db, stream: &EsStreamName, key: &str, value: &str) -> sled::Result<()> {
+    let tree = db.open_tree(STREAM_META_TREE)?;
+    tree.insert(meta_key(stream, key), value.as_bytes())?;
+    Ok(())
+}
+
+pub fn get(db: &Db, stream: &EsStreamName, key: &str) -> sled::Result<Option<String>> {
+    let tree = db.open_tree(STREAM_META_TREE)?;
+    let value = tree.get(meta_key(stream, key))?;
+    Ok(value.map(|v| String::from_utf8(v.to_vec()).unwrap()))
+}
+
+/// Returns all the metadata of a stream ordered by key.
+pub fn get_all(db: &Db, stream: &EsStreamName) -> sled::Result<Vec<(String, String)>> {
+    let prefix = stream_prefix(stream);
+    let tree = db.open_tree(STREAM_META_TREE)?;
+
+    let mut meta = Vec::new();
+    for result in tree.scan_prefix(&prefix) {
+        let (key, value) = result?;
+        let key = String::from_utf8(key[prefix.len()..].to_vec()).unwrap();
+        let value = String::from_utf8(value.to_vec()).unwrap();
+        meta.push((key, value));
+    }
+
+    Ok(meta)
+}
+
+/// Forgets all the metadata of a stream.
+pub fn clear_stream_meta(db: &Db, stream: &EsStreamName) -> sled::Result<()> {
+    let prefix = stream_prefix(stream);
+    let tree = db.open_tree(STREAM_META_TREE)?;
+
+    for result in tree.scan_prefix(&prefix) {
+        let (key, _) = result?;
+        tree.remove(key)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,263 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use prometheus::core::Collector;
+use prometheus::proto::Bucket;
+use prometheus::{Encoder, HistogramOpts, HistogramTimer, HistogramVec, IntCounterVec, IntGauge};
+use prometheus::{IntGaugeVec, Opts, TextEncoder};
+use sled::Db;
+use tracing::{error, info};
+
+use meilies::reqresp::LatencyStats;
+use meilies::stream::{StreamName as EsStreamName, Timestamp};
+
+/// The time a scraper has to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    pub static ref CONNECTED_CLIENTS: IntGauge = int_gauge(
+        "meilies_connected_clients",
+        "The number of clients connected to the server."
+    );
+    pub static ref ACTIVE_SUBSCRIPTIONS: IntGauge = int_gauge(
+        "meilies_active_subscriptions",
+        "The number of subscriptions of the connected clients."
+    );
+    static ref EVENTS_PUBLISHED: IntCounterVec = int_counter_vec(
+        "meilies_events_published_total",
+        "The number of events published to each stream.",
+        &["stream"]
+    );
+    static ref EVENTS_DELIVERED: IntCounterVec = int_counter_vec(
+        "meilies_events_delivered_total",
+        "The number of events of each stream sent to the subscribers.",
+        &["stream"]
+    );
+    static ref DELIVERY_LAG: IntGaugeVec = int_gauge_vec(
+        "meilies_delivery_lag_milliseconds",
+        "The time elapsed between the publication of the last event sent to a subscriber and its delivery.",
+        &["subscriber", "stream"]
+    );
+    static ref TAIL_CACHE_READS: IntCounterVec = int_counter_vec(
+        "meilies_tail_cache_reads_total",
+        "The number of reads of the recent events served by the tail cache or by the database.",
+        &["result"]
+    );
+    static ref DB_SIZE: IntGauge = int_gauge(
+        "meilies_db_size_bytes",
+        "The size of the database on disk."
+    );
+    static ref DISK_AVAILABLE: IntGauge = int_gauge(
+        "meilies_disk_available_bytes",
+        "The free space of the data directory, the writes are rejected below the threshold."
+    );
+    pub static ref COMMAND_DURATION: HistogramVec = histogram_vec(
+        "meilies_command_duration_seconds",
+        "The time spent handling each command, without the time spent waiting for the replicas.",
+        &["command"]
+    );
+    static ref OPERATION_DURATION: HistogramVec = histogram_vec(
+        "meilies_operation_duration_seconds",
+        "The time spent appending the events, reading a window of the history of a catching up subscriber and delivering an event.",
+        &["operation"]
+    );
+}
+
+/// The operations whose latency is measured, other than the commands.
+pub const APPEND: &str = "append";
+pub const CATCH_UP: &str = "catch-up";
+pub const DELIVERY: &str = "delivery";
+
+fn int_gauge(name: &str, help: &str) -> IntGauge {
+    let gauge = IntGauge::new(name, help).unwrap();
+    prometheus::register(Box::new(gauge.clone())).unwrap();
+    gauge
+}
+
+fn int_gauge_vec(name: &str, help: &str, labels: &[&str]) -> IntGaugeVec {
+    let gauges = IntGaugeVec::new(Opts::new(name, help), labels).unwrap();
+    prometheus::register(Box::new(gauges.clone())).unwrap();
+    gauges
+}
+
+fn int_counter_vec(name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    let counters = IntCounterVec::new(Opts::new(name, help), labels).unwrap();
+    prometheus::register(Box::new(counters.clone())).unwrap();
+    counters
+}
+
+/// The histograms measure latencies, the buckets range from 50µs to 6.5s
+/// to estimate the percentiles of the fast commands as well as the slow ones.
+fn histogram_vec(name: &str, help: &str, labels: &[&str]) -> HistogramVec {
+    let buckets = prometheus::exponential_buckets(0.000_05, 2.0, 18).unwrap();
+    let opts = HistogramOpts::new(name, help).buckets(buckets);
+    let histograms = HistogramVec::new(opts, labels).unwrap();
+    prometheus::register(Box::new(histograms.clone())).unwrap();
+    histograms
+}
+
+/// Counts the events published to a stream.
+pub fn published(stream: &EsStreamName, count: usize) {
+    let counter = EVENTS_PUBLISHED.with_label_values(&[stream.as_str()]);
+    counter.inc_by(count as i64);
+}
+
+/// Counts an event sent to a subscriber and records the time it took to deliver it.
+pub fn delivered(
+    subscriber: SocketAddr,
+    subscription: &EsStreamName,
+    stream: &EsStreamName,
+    timestamp: Timestamp,
+) {
+    EVENTS_DELIVERED.with_label_values(&[stream.as_str()]).inc();
+
+    let lag = Timestamp::now().0.saturating_sub(timestamp.0);
+    let subscriber = subscriber.to_string();
+    let labels = [subscriber.as_str(), subscription.as_str()];
+    DELIVERY_LAG.with_label_values(&labels).set(lag as i64);
+}
+
+/// Counts a read of the recent events of a stream, `hit` if it was served by the tail cache.
+pub fn tail_cache_read(hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    TAIL_CACHE_READS.with_label_values(&[result]).inc();
+}
+
+/// Records the free space of the data directory, in bytes.
+pub fn disk_available(bytes: u64) {
+    DISK_AVAILABLE.set(bytes as i64);
+}
+
+/// Measures an operation, the duration is recorded when the timer is dropped.
+pub fn operation_timer(operation: &str) -> HistogramTimer {
+    OPERATION_DURATION
+        .with_label_values(&[operation])
+        .start_timer()
+}
+
+/// Estimates the value below which the `quantile` of the durations fall, in microseconds,
+/// by interpolating in the bucket that contains it like the Prometheus `histogram_quantile`.
+fn estimate(quantile: f64, count: u64, buckets: &[Bucket]) -> u64 {
+    let rank = quantile * count as f64;
+    let mut lower = (0.0, 0);
+
+    for bucket in buckets {
+        let (upper_bound, cumulative) = (bucket.get_upper_bound(), bucket.get_cumulative_count());
+        if cumulative as f64 >= rank {
+            let (lower_bound, lower_count) = lower;
+            let in_bucket = (cumulative - lower_count) as f64;
+            let fraction = (rank - lower_count as f64) / in_bucket;
+            let seconds = lower_bound + (upper_bound - lower_bound) * fraction;
+            return (seconds * 1_000_000.0) as u64;
+        }
+        lower = (upper_bound, cumulative);
+    }
+
+    // the duration is above the last bucket, its upper bound is the best estimation
+    (lower.0 * 1_000_000.0) as u64
+}
+
+fn latency_stats(histograms: &HistogramVec) -> Vec<LatencyStats> {
+    let mut stats: Vec<_> = histograms
+        .collect()
+        .iter()
+        .flat_map(|family| family.get_metric())
+        .filter_map(|metric| {
+            let name = metric.get_label().first()?.get_value().to_owned();
+            let histogram = metric.get_histogram();
+            let (count, buckets) = (histogram.get_sample_count(), histogram.get_bucket());
+            if count == 0 {
+                return None;
+            }
+            Some(LatencyStats {
+                name,
+                count,
+                p50: estimate(0.5, count, buckets),
+                p99: estimate(0.99, count, buckets),
+                p999: estimate(0.999, count, buckets),
+            })
+        })
+        .collect();
+
+    stats.sort_by(|a, b| a.name.cmp(&b.name));
+    stats
+}
+
+/// Returns the latency percentiles of the commands and of the operations that were measured.
+pub fn latencies() -> (Vec<LatencyStats>, Vec<LatencyStats>) {
+    (
+        latency_stats(&COMMAND_DURATION),
+        latency_stats(&OPERATION_DURATION),
+    )
+}
+
+/// Forgets the delivery lag of a subscription that stopped.
+pub fn unsubscribed(subscriber: SocketAddr, subscription: &EsStreamName) {
+    let subscriber = subscriber.to_string();
+    let _ = DELIVERY_LAG.remove_label_values(&[subscriber.as_str(), subscription.as_str()]);
+}
+
+/// Answers a scrape with the metrics in the Prometheus text format,
+/// the `/metrics` path is the only one served.
+fn serve(db: &Db, stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // the headers of the request are not used
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+    }
+
+    let mut writer = stream;
+    let path = request_line.split_whitespace().nth(1);
+    if path != Some("/metrics") {
+        let response = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        return writer.write_all(response.as_bytes());
+    }
+
+    match db.size_on_disk() {
+        Ok(size) => DB_SIZE.set(size as i64),
+        Err(e) => error!("error reading the size of the database; {}", e),
+    }
+
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    if let Err(e) = encoder.encode(&prometheus::gather(), &mut body) {
+        return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+    }
+
+    let headers = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        encoder.format_type(),
+        body.len()
+    );
+    writer.write_all(headers.as_bytes())?;
+    writer.write_all(&body)
+}
+
+/// Spawns the thread that serves the metrics over HTTP at the given address.
+pub fn spawn_exporter(db: Db, addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    info!("metrics are served on http://{}/metrics", addr);
+
+    thread::Builder::new()
+        .name(String::from("metrics"))
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|stream| serve(&db, stream));
+                if let Err(e) = result {
+                    error!("error serving the metrics; {}", e);
+                }
+            }
+        })?;
+
+    Ok(())
+}
@@ -0,0 +1,312 @@
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+
+use futures::{Future, Sink};
+use sled::Db;
+use tokio::sync::mpsc;
+use tracing::info;
+
+use meilies::reqresp::{ErrorCode, Response};
+use meilies::stream::{EventNumber, GroupName, SlowConsumer};
+use meilies::stream::{Stream as EsStream, StreamName as EsStreamName};
+
+use super::cursor::Cursor;
+use super::{event_response, groups, register_subscription, send_response};
+use super::{subscription_start, trimmed_start, Subscription, Subscriptions, TOMBSTONES_TREE};
+
+/// What a polled subscription sends.
+enum Source {
+    Stream {
+        stream: EsStream,
+        next: EventNumber,
+        end: Option<EventNumber>,
+        caught_up: bool,
+    },
+    Group {
+        group: GroupName,
+        stream: EsStreamName,
+        consumer: String,
+        redelivered: bool,
+    },
+}
+
+impl Source {
+    fn stream_name(&self) -> &EsStreamName {
+        match self {
+            Source::Stream { stream, .. } => &stream.name,
+            Source::Group { stream, .. } => stream,
+        }
+    }
+}
+
+struct Polled {
+    source: Source,
+    /// The stream was already deleted when subscribed to, the deletion is not notified.
+    deleted: bool,
+    sender: mpsc::Sender<Result<Response, String>>,
+    subscription: Arc<Subscription>,
+}
+
+impl Polled {
+    /// Sends the events published since the last poll, returns `false` once the subscription ended.
+    fn poll(&mut self, db: &Db) -> sled::Result<bool> {
+        let Polled {
+            source,
+            deleted,
+            sender,
+            subscription,
+        } = self;
+        let send = |response| send_response(sender.clone(), subscription, response).is_some();
+
+        if subscription.is_unsubscribed() {
+            info!("subscription stopped");
+            return Ok(false);
+        }
+
+        // the tombstone is checked first, the tree of a deleted stream must not be reopened
+        let name = source.stream_name().clone();
+        if !*deleted && is_deleted(db, &name)? {
+            send(Response::StreamDeleted { stream: name });
+            return Ok(false);
+        }
+
+        match source {
+            Source::Stream {
+                stream,
+                next,
+                end,
+                caught_up,
+            } => {
+                let end = *end;
+                let reached_end = |next| matches!(end, Some(end) if next >= end);
+                let range_end = Response::RangeEnd {
+                    stream: name.clone(),
+                };
+
+                if reached_end(*next) {
+                    send(range_end);
+                    return Ok(false);
+                }
+
+                let tree = db.open_tree(stream.name.as_ref())?;
+                for result in Cursor::new(tree, *next, end) {
+                    let (key, value) = result?;
+                    *next = EventNumber::try_from(key.as_ref()).unwrap().next();
+
+                    if let Some(event) = event_response(stream, &key, value) {
+                        if !send(event) {
+                            return Ok(false);
+                        }
+                    }
+
+                    if reached_end(*next) {
+                        send(range_end);
+                        return Ok(false);
+                    }
+                }
+
+                if !*caught_up {
+                    *caught_up = true;
+                    let caught_up = Response::CaughtUp { stream: name };
+                    return Ok(send(caught_up));
+                }
+            }
+            Source::Group {
+                group,
+                stream,
+                consumer,
+                redelivered,
+            } => {
+                let deliver = |number| {
+                    let sender = sender.clone();
+                    groups::deliver(db, stream, group, consumer, number, sender, subscription)
+                };
+
+                if !*redelivered {
+                    *redelivered = true;
+                    for number in groups::consumer_pending(db, stream, group, consumer)? {
+                        if deliver(number)?.is_none() {
+                            return Ok(false);
+                        }
+                    }
+                }
+
+                while let Some(number) = groups::claim_next_event(db, stream, group)? {
+                    if deliver(number)?.is_none() {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// The subscriptions of a simulated server, they are not run by a thread each: their events
+/// are sent when the simulation polls them, in the order the subscriptions were made.
+///
+/// The subscriptions to the virtual and the ephemeral streams, the flow controlled,
+/// batched or non blocking ones are rejected.
+#[derive(Clone, Default)]
+pub struct PolledSubscriptions {
+    polled: Arc<Mutex<Vec<Polled>>>,
+}
+
+impl PolledSubscriptions {
+    /// Subscribes to the stream, the subscription starts at the next poll.
+    pub fn subscribe(
+        &self,
+        db: &Db,
+        stream: EsStream,
+        sender: mpsc::Sender<Result<Response, String>>,
+        subscriptions: &Subscriptions,
+    ) -> sled::Result<()> {
+        if let Some(unsupported) = unsupported(&stream) {
+            let error = ErrorCode::Err.error(format_args!(
+                "{} is not supported by a simulated server",
+                unsupported
+            ));
+            if sender.send(Err(error)).wait().is_err() {
+                info!("encountered closed channel");
+            }
+            return Ok(());
+        }
+
+        let subscription = register_subscription(
+            subscriptions,
+            &stream.name,
+            stream.slow_consumer,
+            stream.credits,
+            stream.batch,
+            &sender,
+        );
+
+        let subscribed = Response::Subscribed {
+            stream: stream.name.clone(),
+        };
+        if send_response(sender.clone(), &subscription, subscribed).is_none() {
+            return Ok(());
+        }
+
+        let tree = db.open_tree(stream.name.as_ref())?;
+        let mut next = subscription_start(db, &tree, &stream)?;
+        if let Some(first) = trimmed_start(db, &stream.name, next)? {
+            next = first;
+            let trimmed = Response::StreamTrimmed {
+                stream: stream.name.clone(),
+                first,
+            };
+            if send_response(sender.clone(), &subscription, trimmed).is_none() {
+                return Ok(());
+            }
+        }
+
+        let deleted = is_deleted(db, &stream.name)?;
+        let end = stream.range.to().map(EventNumber);
+        let source = Source::Stream {
+            stream,
+            next,
+            end,
+            caught_up: false,
+        };
+        self.push(source, deleted, sender, subscription);
+
+        Ok(())
+    }
+
+    /// Subscribes the consumer to the stream for the group, the pending events
+    /// of the consumer are delivered again at the next poll.
+    pub fn subscribe_group(
+        &self,
+        db: &Db,
+        group: GroupName,
+        stream: EsStreamName,
+        consumer: String,
+        sender: mpsc::Sender<Result<Response, String>>,
+        subscriptions: &Subscriptions,
+    ) -> sled::Result<()> {
+        let subscription = register_subscription(
+            subscriptions,
+            &stream,
+            SlowConsumer::Block,
+            None,
+            None,
+            &sender,
+        );
+
+        let subscribed = Response::Subscribed {
+            stream: stream.clone(),
+        };
+        if send_response(sender.clone(), &subscription, subscribed).is_none() {
+            return Ok(());
+        }
+
+        let deleted = is_deleted(db, &stream)?;
+        let source = Source::Group {
+            group,
+            stream,
+            consumer,
+            redelivered: false,
+        };
+        self.push(source, deleted, sender, subscription);
+
+        Ok(())
+    }
+
+    fn push(
+        &self,
+        source: Source,
+        deleted: bool,
+        sender: mpsc::Sender<Result<Response, String>>,
+        subscription: Arc<Subscription>,
+    ) {
+        let polled = Polled {
+            source,
+            deleted,
+            sender,
+            subscription,
+        };
+        self.polled.lock().unwrap().push(polled);
+    }
+
+    /// Sends the events published since the last poll to the subscribers, the subscriptions
+    /// that ended or whose client unsubscribed are removed.
+    pub fn poll(&self, db: &Db) {
+        let mut polled = self.polled.lock().unwrap();
+        for mut subscription in polled.drain(..).collect::<Vec<_>>() {
+            match subscription.poll(db) {
+                Ok(true) => polled.push(subscription),
+                Ok(false) => (),
+                Err(e) => {
+                    let error = Err(ErrorCode::Err.error(e));
+                    if subscription.sender.send(error).wait().is_err() {
+                        info!("encountered closed channel");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns what a simulated server does not support in the subscription, if anything.
+fn unsupported(stream: &EsStream) -> Option<&'static str> {
+    if stream.name.is_virtual() {
+        Some("a subscription to a virtual stream")
+    } else if stream.name.is_ephemeral() {
+        Some("a subscription to an ephemeral stream")
+    } else if stream.slow_consumer != SlowConsumer::Block {
+        Some("a subscription that does not block on slow consumers")
+    } else if stream.credits.is_some() {
+        Some("a flow controlled subscription")
+    } else if stream.batch.is_some() {
+        Some("a batched subscription")
+    } else {
+        None
+    }
+}
+
+/// Returns `true` if the stream has been deleted, permanently or not.
+fn is_deleted(db: &Db, stream: &EsStreamName) -> sled::Result<bool> {
+    db.open_tree(TOMBSTONES_TREE)?.contains_key(stream)
+}
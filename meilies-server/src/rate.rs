@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use sled::Db;
+
+use meilies::reqresp::{ErrorCode, Request};
+use meilies::stream::StreamName as EsStreamName;
+
+use super::meta;
+
+/// The stream metadata that specifies the number of events that can be published per second.
+const MAX_EVENTS_PER_SEC: &str = "rate.max-events-per-sec";
+
+/// The stream metadata that specifies the number of bytes that can be published per second.
+const MAX_BYTES_PER_SEC: &str = "rate.max-bytes-per-sec";
+
+lazy_static! {
+    /// The publications allowed on each stream that is rate limited.
+    static ref STREAM_LIMITERS: Mutex<HashMap<EsStreamName, Limiter>> = Mutex::default();
+}
+
+/// Returns an error if the value of a rate metadata is invalid.
+pub fn check_meta(key: &str, value: &str) -> Result<(), String> {
+    match key {
+        MAX_EVENTS_PER_SEC | MAX_BYTES_PER_SEC if value.parse::<u64>().is_err() => Err(format!(
+            "{} must be a positive number, found {:?}",
+            key, value
+        )),
+        _otherwise => Ok(()),
+    }
+}
+
+/// The number of events and bytes that can be published per second.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Limit {
+    pub events: Option<u64>,
+    pub bytes: Option<u64>,
+}
+
+impl Limit {
+    fn is_unlimited(self) -> bool {
+        self.events.is_none() && self.bytes.is_none()
+    }
+}
+
+/// A token bucket that is refilled at the rate of the limit and can hold one second of it.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u64) -> Bucket {
+        Bucket {
+            tokens: rate as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, rate: u64, now: Instant) {
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        self.refilled_at = now;
+    }
+
+    /// Returns the time to wait before the amount can be taken, a bucket that is full
+    /// accepts an amount greater than its capacity and is left in debt.
+    fn wait_time(&self, rate: u64, amount: u64) -> Option<Duration> {
+        let needed = amount.min(rate) as f64;
+        if rate == 0 {
+            Some(Duration::from_secs(1))
+        } else if self.tokens >= needed {
+            None
+        } else {
+            let missing = needed - self.tokens;
+            Some(Duration::from_secs_f64(missing / rate as f64))
+        }
+    }
+}
+
+/// Limits the events and bytes published by a connection or to a stream.
+#[derive(Debug, Default)]
+pub struct Limiter {
+    events: Option<Bucket>,
+    bytes: Option<Bucket>,
+}
+
+impl Limiter {
+    /// Takes the events and bytes published from the buckets,
+    /// returns the time to wait before retrying if the limit is exceeded.
+    pub fn take(&mut self, limit: Limit, events: u64, bytes: u64) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = Vec::with_capacity(2);
+        if let Some((bucket, rate)) = refilled(&mut self.events, limit.events, now) {
+            buckets.push((bucket, rate, events));
+        }
+        if let Some((bucket, rate)) = refilled(&mut self.bytes, limit.bytes, now) {
+            buckets.push((bucket, rate, bytes));
+        }
+
+        let wait = buckets
+            .iter()
+            .filter_map(|(bucket, rate, amount)| bucket.wait_time(*rate, *amount))
+            .max();
+        if let Some(wait) = wait {
+            return Err(wait);
+        }
+
+        for (bucket, _rate, amount) in buckets {
+            bucket.tokens -= amount as f64;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the bucket of a limit refilled up to now, the bucket is removed if there is no limit.
+fn refilled(
+    bucket: &mut Option<Bucket>,
+    rate: Option<u64>,
+    now: Instant,
+) -> Option<(&mut Bucket, u64)> {
+    match rate {
+        Some(rate) => {
+            let bucket = bucket.get_or_insert_with(|| Bucket::new(rate));
+            bucket.refill(rate, now);
+            Some((bucket, rate))
+        }
+        None => {
+            *bucket = None;
+            None
+        }
+    }
+}
+
+/// Returns the events and bytes published to each stream by a request.
+fn publications(request: &Request) -> Vec<(&EsStreamName, u64, u64)> {
+    match request {
+        Request::Publish {
+            stream, event_data, ..
+        } => vec![(stream, 1, event_data.0.len() as u64)],
+        Request::PublishBatch { stream, events } => {
+            let bytes = events.iter().map(|(_, data)| data.0.len() as u64).sum();
+            vec![(stream, events.len() as u64, bytes)]
+        }
+        Request::PublishMulti { writes } => {
+            let mut publications: Vec<(&EsStreamName, u64, u64)> = Vec::new();
+            for (stream, _, data) in writes {
+                match publications.iter_mut().find(|(s, _, _)| *s == stream) {
+                    Some((_, events, bytes)) => {
+                        *events += 1;
+                        *bytes += data.0.len() as u64;
+                    }
+                    None => publications.push((stream, 1, data.0.len() as u64)),
+                }
+            }
+            publications
+        }
+        _otherwise => Vec::new(),
+    }
+}
+
+fn stream_limit(db: &Db, stream: &EsStreamName) -> sled::Result<Limit> {
+    let limit = |key| -> sled::Result<Option<u64>> {
+        let value = meta::get(db, stream, key)?;
+        Ok(value.map(|v| v.parse().unwrap()))
+    };
+
+    Ok(Limit {
+        events: limit(MAX_EVENTS_PER_SEC)?,
+        bytes: limit(MAX_BYTES_PER_SEC)?,
+    })
+}
+
+fn throttled(wait: Duration, limited: &str) -> String {
+    // the client can retry after the number of milliseconds that follows the prefix
+    let retry_after = wait.as_millis().max(1);
+    ErrorCode::Throttled.error(format_args!(
+        "{} publish rate of {} exceeded",
+        retry_after, limited
+    ))
+}
+
+/// Returns the error to send if a publication exceeds the rate limit of the
+/// connection or of one of its streams, the publication is counted otherwise.
+pub fn throttle_error(
+    db: &Db,
+    connection: &Mutex<Limiter>,
+    connection_limit: Limit,
+    request: &Request,
+) -> sled::Result<Option<String>> {
+    let publications = publications(request);
+    if publications.is_empty() {
+        return Ok(None);
+    }
+
+    let mut limits = Vec::with_capacity(publications.len());
+    for (stream, events, bytes) in publications {
+        let limit = stream_limit(db, stream)?;
+        limits.push((stream, limit, events, bytes));
+    }
+
+    if !connection_limit.is_unlimited() {
+        let events = limits.iter().map(|(_, _, events, _)| events).sum();
+        let bytes = limits.iter().map(|(_, _, _, bytes)| bytes).sum();
+        let mut connection = connection.lock().unwrap();
+        if let Err(wait) = connection.take(connection_limit, events, bytes) {
+            return Ok(Some(throttled(wait, "the connection")));
+        }
+    }
+
+    let mut limiters = STREAM_LIMITERS.lock().unwrap();
+    for (stream, limit, events, bytes) in limits {
+        if limit.is_unlimited() {
+            limiters.remove(stream);
+            continue;
+        }
+
+        let limiter = limiters.entry(stream.clone()).or_default();
+        if let Err(wait) = limiter.take(limit, events, bytes) {
+            let limited = format!("the {} stream", stream);
+            return Ok(Some(throttled(wait, &limited)));
+        }
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,356 @@
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::sync::oneshot;
+use sled::transaction::TransactionError;
+use sled::{Db, Transactional};
+use tokio::prelude::*;
+use tracing::{error, info};
+
+use meilies::reqresp::{ErrorCode, Request, Response};
+use meilies::stream::{EventData, EventName, EventNumber, ReadRange, Timestamp};
+use meilies::stream::{Stream as EsStream, StreamName as EsStreamName, ALL_STREAMS};
+use meilies_client::ConnectBuilder;
+
+use super::all::{self, ALL_TREE};
+use super::compression;
+use super::disk;
+use super::failover::Failover;
+use super::tail;
+use super::{create_stream, delete_stream, is_tombstoned, last_event_number, raw_event};
+use super::{retention, stream_written, tombstone_stream, FIRST_NUMBERS_TREE, STREAMS_STREAM};
+
+/// The tree that keeps the number of the next event of the `$streams` stream of the primary.
+const REPLICATION_TREE: &str = "meilies:replication";
+
+const STREAMS_POSITION_KEY: &str = "streams";
+
+/// The interval at which the connection with the primary is checked.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The size in bytes above which the primary compresses the events it sends.
+const COMPRESSION_THRESHOLD: u64 = 1024;
+
+#[derive(Default)]
+struct State {
+    primary: Option<SocketAddr>,
+    /// Stops the replication of the current primary.
+    stop: Option<oneshot::Sender<()>>,
+    caught_up: bool,
+    lag: Option<u64>,
+}
+
+/// The replication of the events of a primary server, the server does not accept
+/// writes while it is a replica and serves the replicated events to its subscribers.
+///
+/// The primary of a cluster node is the leader of the cluster.
+#[derive(Clone, Default)]
+pub struct Replication {
+    state: Arc<Mutex<State>>,
+    /// The server does not accept writes in read-only mode, even if it is not a replica.
+    read_only: Arc<AtomicBool>,
+    username: Option<String>,
+    password: Option<String>,
+    failover: Option<Failover>,
+}
+
+impl Replication {
+    /// Creates a replication that authenticates with these credentials on the primary.
+    pub fn new(username: Option<String>, password: Option<String>) -> Replication {
+        Replication {
+            username,
+            password,
+            ..Replication::default()
+        }
+    }
+
+    /// Makes the replication managed by the failover of the cluster.
+    pub fn with_failover(self, failover: Failover) -> Replication {
+        Replication {
+            failover: Some(failover),
+            ..self
+        }
+    }
+
+    pub fn failover(&self) -> Option<&Failover> {
+        self.failover.as_ref()
+    }
+
+    pub fn set_read_only(&self, enabled: bool) {
+        self.read_only.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if the server is neither a replica nor a cluster node that is not the
+    /// leader, the background tasks that must only run once per deployment only run on it.
+    #[cfg(feature = "webhooks")]
+    pub fn is_primary(&self) -> bool {
+        match &self.failover {
+            Some(failover) => failover.write_error().is_none(),
+            None => self.state.lock().unwrap().primary.is_none(),
+        }
+    }
+
+    /// Returns an error if the request writes to the database and the server is read-only
+    /// or a replica, the writes sent to a cluster node are redirected to the leader.
+    pub fn write_error(&self, request: &Request) -> Option<String> {
+        match request {
+            Request::Publish { .. }
+            | Request::PublishBatch { .. }
+            | Request::PublishMulti { .. }
+            | Request::CreateStream { .. }
+            | Request::DeleteStream { .. }
+            | Request::TrimStream { .. }
+            | Request::SetStreamMeta { .. } => (),
+            _otherwise => return None,
+        }
+
+        if self.read_only.load(Ordering::SeqCst) {
+            return Some(ErrorCode::ReadOnly.error("server is in read-only mode"));
+        }
+
+        if let Some(error) = disk::write_error() {
+            return Some(error);
+        }
+
+        if let Some(failover) = &self.failover {
+            return failover.write_error();
+        }
+
+        let primary = self.state.lock().unwrap().primary?;
+        Some(ErrorCode::ReadOnly.error(format_args!("server is a replica of {}", primary)))
+    }
+
+    /// Returns the state of the replication as sent to the clients.
+    pub fn info(&self, db: &Db) -> sled::Result<Response> {
+        let state = self.state.lock().unwrap();
+        let last_global = last_event_number(db, &EsStreamName::all())?;
+
+        Ok(Response::ReplicationInfo {
+            primary: state.primary.map(|addr| addr.to_string()),
+            caught_up: state.caught_up,
+            last_global,
+            lag: state.lag,
+        })
+    }
+
+    /// Stops replicating the current primary, if any, and starts replicating the given one
+    /// from the last event replicated. The events are read from `$all` and the deletions
+    /// of the streams from `$streams`, both are resumed when the connection is lost.
+    ///
+    /// Must be called from a tokio runtime.
+    pub fn replicate(&self, db: Db, primary: Option<SocketAddr>) -> sled::Result<()> {
+        let (stop, stopped) = oneshot::channel();
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(stop) = state.stop.take() {
+                let _ = stop.send(());
+            }
+            *state = State {
+                primary,
+                stop: Some(stop),
+                ..State::default()
+            };
+        }
+
+        let primary = match primary {
+            Some(primary) => primary,
+            None => return Ok(()),
+        };
+
+        let next_global = last_event_number(&db, &EsStreamName::all())?;
+        let next_global = next_global.map_or(EventNumber::zero(), EventNumber::next);
+        let next_admin = streams_position(&db)?;
+
+        let mut builder = ConnectBuilder::new(primary)
+            .keepalive(KEEPALIVE_INTERVAL)
+            .compression(COMPRESSION_THRESHOLD);
+        if let Some(username) = &self.username {
+            builder = builder.username(username.as_str());
+        }
+        if let Some(password) = &self.password {
+            builder = builder.password(password.as_str());
+        }
+
+        let state = self.state.clone();
+        let replication = builder
+            .sub()
+            .map_err(move |e| error!("error connecting to the primary {}; {}", primary, e))
+            .and_then(move |(mut controller, events)| {
+                info!("replicating the primary {}", primary);
+
+                controller.subscribe_to(EsStream::all(ReadRange::ReadFrom(next_global.0)));
+                let streams = EsStreamName::new(STREAMS_STREAM.to_owned()).unwrap();
+                controller.subscribe_to(EsStream::new(streams, ReadRange::ReadFrom(next_admin.0)));
+
+                events
+                    .map_err(|e| error!("replication connection error; {}", e))
+                    .for_each(move |response| {
+                        let response = match response {
+                            Ok(response) => response,
+                            Err(error) => {
+                                error!("error received from the primary; {}", error);
+                                return Ok(());
+                            }
+                        };
+
+                        // the primary waits for the acknowledgements of the
+                        // publications that require their replication
+                        match apply_response(&db, &state, response) {
+                            Ok(Some(global)) => controller.replica_ack(global),
+                            Ok(None) => (),
+                            Err(e) => error!("error applying a replicated event; {}", e),
+                        }
+
+                        Ok(())
+                    })
+            })
+            .select2(stopped)
+            .then(move |_| {
+                info!("replication of the primary {} stopped", primary);
+                Ok(())
+            });
+
+        tokio::spawn(replication);
+
+        Ok(())
+    }
+}
+
+fn streams_position(db: &Db) -> sled::Result<EventNumber> {
+    let position = db.open_tree(REPLICATION_TREE)?.get(STREAMS_POSITION_KEY)?;
+    Ok(position.map_or(EventNumber::zero(), |n| {
+        EventNumber::try_from(n.as_ref()).unwrap()
+    }))
+}
+
+/// Applies a response of the primary, returns the global event
+/// number up to which the events have been replicated, if any.
+fn apply_response(
+    db: &Db,
+    state: &Mutex<State>,
+    response: Response,
+) -> sled::Result<Option<EventNumber>> {
+    match response {
+        Response::Event {
+            stream,
+            number,
+            timestamp,
+            event_name,
+            event_data,
+            headers,
+            global_number: Some(global),
+        } => {
+            let compressed = compression::is_enabled(db, &stream)?;
+            let raw_event = raw_event(timestamp, &event_name, &headers, &event_data.0, compressed);
+            if apply_event(db, &stream, number, global, &raw_event)? {
+                stream_written(db, &stream, number)?;
+            }
+
+            let lag = Timestamp::now().0.saturating_sub(timestamp.0);
+            state.lock().unwrap().lag = Some(lag);
+
+            Ok(Some(global))
+        }
+        Response::Event {
+            stream,
+            number,
+            event_name,
+            event_data,
+            ..
+        } if stream.as_str() == STREAMS_STREAM => {
+            apply_stream_event(db, &event_name, &event_data)?;
+            let next = number.next().to_be_bytes();
+            db.open_tree(REPLICATION_TREE)?
+                .insert(STREAMS_POSITION_KEY, &next[..])?;
+
+            Ok(None)
+        }
+        Response::CaughtUp { stream } if stream == EsStreamName::all() => {
+            info!("replica caught up with the primary");
+            state.lock().unwrap().caught_up = true;
+
+            // the primary counts the replica from its first acknowledgement
+            last_event_number(db, &EsStreamName::all())
+        }
+        _otherwise => Ok(None),
+    }
+}
+
+/// Appends an event of the primary with the same number and global number, returns `false`
+/// if the event has already been replicated or if its stream has been deleted permanently.
+fn apply_event(
+    db: &Db,
+    stream: &EsStreamName,
+    number: EventNumber,
+    global: EventNumber,
+    raw_event: &[u8],
+) -> sled::Result<bool> {
+    if is_tombstoned(db, stream)? {
+        return Ok(false);
+    }
+
+    let tree = db.open_tree(stream.as_ref())?;
+    let all = db.open_tree(ALL_TREE)?;
+    let first_numbers = db.open_tree(FIRST_NUMBERS_TREE)?;
+    let sizes = retention::sizes_tree(db)?;
+    let limits = retention::limits(db, stream)?;
+
+    let trees = (&**db, &tree, &all, &first_numbers, &sizes);
+    let result = trees.transaction(|(numbers, tree, all, first_numbers, sizes)| {
+        let last = numbers.get(stream)?;
+        let last = last.map(|n| EventNumber::try_from(n.as_ref()).unwrap());
+        if last.map_or(false, |last| last >= number) {
+            return Ok(false);
+        }
+
+        // the events trimmed on the primary before being replicated are missing
+        if first_numbers.get(stream)?.is_none() {
+            first_numbers.insert(stream.as_ref(), &number.to_be_bytes()[..])?;
+        }
+
+        let key = &number.to_be_bytes()[..];
+        tree.insert(key, raw_event)?;
+        numbers.insert(stream.as_ref(), key)?;
+
+        all.insert(&global.to_be_bytes()[..], all::entry(stream, number))?;
+        numbers.insert(ALL_STREAMS, &global.to_be_bytes()[..])?;
+
+        let bytes = raw_event.len() as u64;
+        retention::appended(limits, stream, tree, first_numbers, sizes, number, bytes)?;
+
+        Ok(true)
+    });
+
+    match result {
+        Ok(applied) => {
+            if applied {
+                tail::appended(stream, limits, number, vec![raw_event.into()]);
+            }
+            Ok(applied)
+        }
+        Err(TransactionError::Storage(e)) => Err(e),
+        Err(TransactionError::Abort(())) => unreachable!("the transaction is never aborted"),
+    }
+}
+
+/// Applies the creation or the deletion of a stream of the primary.
+fn apply_stream_event(db: &Db, event_name: &EventName, data: &EventData) -> sled::Result<()> {
+    let stream = match String::from_utf8(data.0.to_vec()).map(EsStreamName::new) {
+        Ok(Ok(stream)) => stream,
+        _otherwise => return Ok(()),
+    };
+
+    match event_name.as_str() {
+        "StreamCreated" if !is_tombstoned(db, &stream)? => create_stream(db, &stream)?,
+        "StreamDeleted" => delete_stream(db, &stream)?,
+        "StreamTombstoned" => tombstone_stream(db, &stream)?,
+        _otherwise => return Ok(()),
+    }
+
+    info!("{} {} replicated", event_name.as_str(), stream);
+
+    Ok(())
+}
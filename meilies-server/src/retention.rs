@@ -0,0 +1,206 @@
+use std::convert::TryFrom;
+use std::io;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use sled::transaction::{TransactionalTree, UnabortableTransactionError};
+use sled::{Db, Tree};
+use tracing::{error, info};
+
+use meilies::stream::{EventNumber, StreamName as EsStreamName, Timestamp};
+
+use super::shutdown::Shutdown;
+use super::{first_event_number, first_event_number_at, meta, stream_names, trim_stream};
+
+/// The stream metadata that specifies the number of milliseconds
+/// after which the events of the stream are removed.
+const RETENTION_MS: &str = "retention.ms";
+
+/// The stream metadata that specifies the maximum number of events kept in the stream.
+const MAX_EVENTS: &str = "retention.max-events";
+
+/// The stream metadata that specifies the maximum size in bytes of the events of the stream.
+const MAX_BYTES: &str = "retention.max-bytes";
+
+/// The stream metadata that specifies how the events of the stream are kept, `log` by default.
+const MODE: &str = "mode";
+
+/// The mode of the streams that keep all their events, within their limits.
+const LOG_MODE: &str = "log";
+
+/// The mode of the streams that only keep their latest event, like a retained message.
+const LAST_VALUE_MODE: &str = "last-value";
+
+/// The tree that keeps the size in bytes of the events of each stream, keys are the stream names.
+const STREAM_SIZES_TREE: &str = "meilies:stream-sizes";
+
+/// Returns an error if the value of a retention metadata is invalid.
+pub fn check_meta(key: &str, value: &str) -> Result<(), String> {
+    match key {
+        RETENTION_MS | MAX_EVENTS | MAX_BYTES if value.parse::<u64>().is_err() => Err(format!(
+            "{} must be a positive number, found {:?}",
+            key, value
+        )),
+        MODE if value != LOG_MODE && value != LAST_VALUE_MODE => Err(format!(
+            "{} must be {} or {}, found {:?}",
+            key, LOG_MODE, LAST_VALUE_MODE, value
+        )),
+        _otherwise => Ok(()),
+    }
+}
+
+/// The maximum number of events and bytes a stream can keep.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Limits {
+    max_events: Option<u64>,
+    max_bytes: Option<u64>,
+}
+
+impl Limits {
+    /// Returns `true` if the stream keeps all its events.
+    pub fn is_unlimited(self) -> bool {
+        self.max_events.is_none() && self.max_bytes.is_none()
+    }
+
+    pub fn max_events(self) -> Option<u64> {
+        self.max_events
+    }
+
+    pub fn max_bytes(self) -> Option<u64> {
+        self.max_bytes
+    }
+
+    fn exceeded(self, events: u64, bytes: u64) -> bool {
+        self.max_events.map_or(false, |max| events > max)
+            || self.max_bytes.map_or(false, |max| bytes > max)
+    }
+}
+
+/// Returns the limits set on the stream with the retention metadata,
+/// a stream in the last-value mode can only keep a single event.
+pub fn limits(db: &Db, stream: &EsStreamName) -> sled::Result<Limits> {
+    let limit = |key| -> sled::Result<Option<u64>> {
+        let value = meta::get(db, stream, key)?;
+        Ok(value.map(|v| v.parse().unwrap()))
+    };
+
+    let max_events = if is_last_value(db, stream)? {
+        Some(1)
+    } else {
+        limit(MAX_EVENTS)?
+    };
+
+    Ok(Limits {
+        max_events,
+        max_bytes: limit(MAX_BYTES)?,
+    })
+}
+
+/// Returns the number of milliseconds the events of the stream are kept, `None` if forever.
+pub fn retention_ms(db: &Db, stream: &EsStreamName) -> sled::Result<Option<u64>> {
+    let retention = meta::get(db, stream, RETENTION_MS)?;
+    Ok(retention.map(|r| r.parse().unwrap()))
+}
+
+/// Returns `true` if the stream only keeps its latest event.
+pub fn is_last_value(db: &Db, stream: &EsStreamName) -> sled::Result<bool> {
+    let mode = meta::get(db, stream, MODE)?;
+    Ok(mode.map_or(false, |m| m == LAST_VALUE_MODE))
+}
+
+/// Opens the tree in which the size of the events of each stream is stored.
+pub fn sizes_tree(db: &Db) -> sled::Result<Tree> {
+    db.open_tree(STREAM_SIZES_TREE)
+}
+
+/// Returns the size in bytes of the events of a stream.
+pub fn stream_size(db: &Db, stream: &EsStreamName) -> sled::Result<u64> {
+    let size = sizes_tree(db)?.get(stream)?;
+    Ok(size.map_or(0, |s| {
+        u64::from_be_bytes(<[u8; 8]>::try_from(s.as_ref()).unwrap())
+    }))
+}
+
+/// Subtracts the size of the events removed from the stream.
+pub fn removed(db: &Db, stream: &EsStreamName, bytes: u64) -> sled::Result<()> {
+    sizes_tree(db)?.update_and_fetch(stream, |size| {
+        let size = size.map_or(0, |s| u64::from_be_bytes(<[u8; 8]>::try_from(s).unwrap()));
+        Some(size.saturating_sub(bytes).to_be_bytes().to_vec())
+    })?;
+    Ok(())
+}
+
+/// Adds the size of the events appended to the stream then removes its oldest events until
+/// it respects its limits, in the transaction of the publication. The last event is always kept.
+pub fn appended(
+    limits: Limits,
+    stream: &EsStreamName,
+    tree: &TransactionalTree,
+    first_numbers: &TransactionalTree,
+    sizes: &TransactionalTree,
+    last: EventNumber,
+    bytes: u64,
+) -> Result<(), UnabortableTransactionError> {
+    let size = sizes.get(stream)?;
+    let size = size.map_or(0, |s| {
+        u64::from_be_bytes(<[u8; 8]>::try_from(s.as_ref()).unwrap())
+    });
+    let mut size = size + bytes;
+
+    let first = first_numbers.get(stream)?;
+    let first = first.map_or(EventNumber::zero(), |n| {
+        EventNumber::try_from(n.as_ref()).unwrap()
+    });
+
+    let mut floor = first;
+    while floor < last && limits.exceeded(last.0 + 1 - floor.0, size) {
+        if let Some(event) = tree.remove(&floor.to_be_bytes()[..])? {
+            size = size.saturating_sub(event.len() as u64);
+        }
+        floor = floor.next();
+    }
+
+    if floor != first {
+        first_numbers.insert(stream.as_ref(), &floor.to_be_bytes()[..])?;
+    }
+    sizes.insert(stream.as_ref(), &size.to_be_bytes()[..])?;
+
+    Ok(())
+}
+
+/// Spawns the thread that periodically removes the events older than the retention of their stream.
+pub fn spawn_expiry(db: Db, interval: Duration, shutdown: Shutdown) -> io::Result<JoinHandle<()>> {
+    thread::Builder::new()
+        .name(String::from("expiry"))
+        .spawn(move || {
+            while !shutdown.wait(interval) {
+                if let Err(e) = expire_events(&db) {
+                    error!("error removing the expired events; {}", e);
+                }
+            }
+        })
+}
+
+/// Trims the streams up to their first event that is not older than their retention.
+pub fn expire_events(db: &Db) -> sled::Result<()> {
+    let now = Timestamp::now();
+
+    for stream in stream_names(db) {
+        let retention = match retention_ms(db, &stream)? {
+            Some(retention) => retention,
+            None => continue,
+        };
+
+        let tree = db.open_tree(stream.as_ref())?;
+        let oldest = Timestamp(now.0.saturating_sub(retention));
+        let up_to = first_event_number_at(db, &tree, &stream, oldest)?;
+
+        let first = first_event_number(db, &stream)?.unwrap_or_else(EventNumber::zero);
+        if up_to > first {
+            let floor = trim_stream(db, &stream, up_to)?;
+            info!("stream {} expired up to {:?}", stream, floor);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use std::cmp;
+use std::convert::TryFrom;
+use std::io;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use sled::{Db, IVec};
+use tracing::{error, info};
+
+use meilies::stream::{RawEvent, StreamName as EsStreamName, Timestamp};
+
+use super::shutdown::Shutdown;
+use super::{
+    compression, dedup, metrics, publish_checked, publish_error, raw_event, stream_written,
+    Publication,
+};
+
+/// The tree that keeps the events waiting for their delivery time, keys are the delivery
+/// timestamp followed by a unique ID, values the stream, the deduplication ID and the event.
+const SCHEDULED_TREE: &str = "meilies:scheduled";
+
+/// The longest time the scheduler waits before looking for events to deliver.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn scheduled_key(deliver_at: Timestamp, id: u64) -> Vec<u8> {
+    let mut key = deliver_at.to_be_bytes().to_vec();
+    key.extend_from_slice(&id.to_be_bytes());
+    key
+}
+
+/// Encodes a scheduled event: the length of the stream name, the name, the length of the
+/// deduplication ID plus one or zero if there is none, the ID and then the raw event.
+fn encode(stream: &EsStreamName, dedup_id: Option<&str>, raw_event: &[u8]) -> Vec<u8> {
+    let raw_stream = stream.as_str().as_bytes();
+    let raw_dedup_id = dedup_id.map_or(&[][..], str::as_bytes);
+    let raw_dedup_length = dedup_id.map_or(0, |id| id.len() + 1);
+
+    let mut value = Vec::new();
+    value.extend_from_slice(&raw_stream.len().to_be_bytes());
+    value.extend_from_slice(raw_stream);
+    value.extend_from_slice(&raw_dedup_length.to_be_bytes());
+    value.extend_from_slice(raw_dedup_id);
+    value.extend_from_slice(raw_event);
+    value
+}
+
+fn decode(value: &[u8]) -> (EsStreamName, Option<String>, RawEvent<&[u8]>) {
+    let read_length = |bytes: &[u8]| usize::from_be_bytes(<[u8; 8]>::try_from(bytes).unwrap());
+
+    let (length, value) = value.split_at(8);
+    let (stream, value) = value.split_at(read_length(length));
+    let stream = String::from_utf8(stream.to_vec()).unwrap();
+
+    let (length, value) = value.split_at(8);
+    let (dedup_id, value) = match read_length(length) {
+        0 => (None, value),
+        length => {
+            let (dedup_id, value) = value.split_at(length - 1);
+            (Some(String::from_utf8(dedup_id.to_vec()).unwrap()), value)
+        }
+    };
+
+    (
+        EsStreamName::new(stream).unwrap(),
+        dedup_id,
+        RawEvent::new(value),
+    )
+}
+
+/// Keeps the event aside until its delivery time, it is then appended to the stream.
+pub fn schedule(
+    db: &Db,
+    stream: &EsStreamName,
+    deliver_at: Timestamp,
+    raw_event: &[u8],
+    dedup_id: Option<&str>,
+) -> sled::Result<()> {
+    let tree = db.open_tree(SCHEDULED_TREE)?;
+    let key = scheduled_key(deliver_at, db.generate_id()?);
+    tree.insert(key, encode(stream, dedup_id, raw_event))?;
+    Ok(())
+}
+
+/// Spawns the thread that appends the scheduled events to their stream when their time arrives.
+pub fn spawn_scheduler(db: Db, shutdown: Shutdown) -> io::Result<JoinHandle<()>> {
+    thread::Builder::new()
+        .name(String::from("scheduler"))
+        .spawn(move || loop {
+            let wait = match deliver_events(&db) {
+                Ok(Some(next)) => cmp::min(next, POLL_INTERVAL),
+                Ok(None) => POLL_INTERVAL,
+                Err(e) => {
+                    error!("error delivering the scheduled events; {}", e);
+                    POLL_INTERVAL
+                }
+            };
+            if shutdown.wait(wait) {
+                break;
+            }
+        })
+}
+
+/// Delivers the events whose time has arrived, returns the time
+/// to wait before the next scheduled event must be delivered.
+pub fn deliver_events(db: &Db) -> sled::Result<Option<Duration>> {
+    let tree = db.open_tree(SCHEDULED_TREE)?;
+
+    for result in tree.iter() {
+        let (key, value) = result?;
+
+        let deliver_at = Timestamp::try_from(&key[..8]).unwrap();
+        let now = Timestamp::now();
+        if deliver_at > now {
+            return Ok(Some(Duration::from_millis(deliver_at.0 - now.0)));
+        }
+
+        deliver_event(db, &value)?;
+        tree.remove(key)?;
+    }
+
+    Ok(None)
+}
+
+/// Appends a scheduled event to its stream, the event is timestamped with its delivery time.
+fn deliver_event(db: &Db, value: &IVec) -> sled::Result<()> {
+    let (stream, dedup_id, scheduled) = decode(value);
+
+    // the stream may have been deleted since the event has been scheduled
+    if let Some(error) = publish_error(db, &stream)? {
+        error!("dropping a scheduled event; {}", error);
+        return Ok(());
+    }
+
+    let event_name = scheduled.name().unwrap();
+    let headers = scheduled.headers().unwrap();
+    let event_data = compression::decompress(scheduled.data());
+    let compressed = compression::is_enabled(db, &stream)?;
+    let timestamp = Timestamp::now();
+    let raw_event = raw_event(timestamp, &event_name, &headers, &event_data.0, compressed);
+
+    let tree = db.open_tree(stream.as_ref())?;
+    let dedup_id = dedup_id.as_deref();
+    let publication = publish_checked(db, &tree, &stream, &raw_event, None, dedup_id)?;
+
+    match publication {
+        Publication::Published(number) => {
+            if dedup_id.is_some() {
+                dedup::prune(db, &stream, number)?;
+            }
+            stream_written(db, &stream, number)?;
+            metrics::published(&stream, 1);
+            info!("{:?} {:?} {:?} (scheduled)", stream, event_name, number);
+        }
+        Publication::Duplicate(number) => {
+            info!(
+                "{:?} scheduled event already published as {:?}",
+                stream, number
+            );
+        }
+        Publication::Conflict(_) => unreachable!("no version is expected"),
+    }
+
+    Ok(())
+}
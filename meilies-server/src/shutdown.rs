@@ -0,0 +1,38 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// The signal that stops the background threads of a server, they wait on it between their
+/// runs. The threads of the server binary run until the end of the process, they are given
+/// a signal that is never raised, the embedded servers raise it when they are shut down.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    inner: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        Shutdown::default()
+    }
+
+    /// Raises the signal, the threads waiting on it are woken up.
+    pub fn raise(&self) {
+        let (raised, waiting) = &*self.inner;
+        *raised.lock().unwrap() = true;
+        waiting.notify_all();
+    }
+
+    /// Waits for the duration unless the signal is raised, returns `true` if it is.
+    pub fn wait(&self, duration: Duration) -> bool {
+        let (raised, waiting) = &*self.inner;
+        let deadline = Instant::now() + duration;
+        let mut raised = raised.lock().unwrap();
+        while !*raised {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            raised = waiting.wait_timeout(raised, deadline - now).unwrap().0;
+        }
+        true
+    }
+}
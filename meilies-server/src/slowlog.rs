@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+
+use meilies::reqresp::{Request, SlowLogEntry};
+use meilies::stream::{EventNumber, StreamName as EsStreamName, Timestamp};
+
+use super::clients::command_arguments;
+
+/// The number of characters kept of each argument of a command, the event data can be large.
+const MAX_ARGUMENT_LEN: usize = 128;
+
+lazy_static! {
+    static ref ENTRIES: Mutex<VecDeque<SlowLogEntry>> = Mutex::default();
+    static ref NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    static ref MAX_LEN: AtomicUsize = AtomicUsize::new(0);
+    /// The thresholds in microseconds.
+    static ref COMMAND_THRESHOLD: AtomicU64 = AtomicU64::new(0);
+    static ref DELIVERY_THRESHOLD: AtomicU64 = AtomicU64::new(0);
+}
+
+/// Sets the latency thresholds above which the commands and the deliveries are logged,
+/// and the number of entries kept, the slow log is disabled with zero.
+pub fn configure(command: Duration, delivery: Duration, max_len: usize) {
+    COMMAND_THRESHOLD.store(command.as_micros() as u64, Ordering::SeqCst);
+    DELIVERY_THRESHOLD.store(delivery.as_micros() as u64, Ordering::SeqCst);
+    MAX_LEN.store(max_len, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    MAX_LEN.load(Ordering::SeqCst) != 0
+}
+
+fn push(client: SocketAddr, duration: u64, command: Vec<String>) {
+    let max_len = MAX_LEN.load(Ordering::SeqCst);
+    if max_len == 0 {
+        return;
+    }
+
+    let entry = SlowLogEntry {
+        id: NEXT_ID.fetch_add(1, Ordering::SeqCst),
+        timestamp: Timestamp::now(),
+        duration,
+        client: client.to_string(),
+        command,
+    };
+
+    let mut entries = ENTRIES.lock().unwrap();
+    entries.push_front(entry);
+    entries.truncate(max_len);
+}
+
+fn truncate(argument: String) -> String {
+    match argument.char_indices().nth(MAX_ARGUMENT_LEN) {
+        Some((end, _)) => format!("{}...", &argument[..end]),
+        None => argument,
+    }
+}
+
+/// Logs the command if it took longer than the threshold to be processed.
+pub fn command(client: SocketAddr, request: &Request, duration: Duration) {
+    let duration = duration.as_micros() as u64;
+    if duration < COMMAND_THRESHOLD.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let arguments = command_arguments(request)
+        .into_iter()
+        .map(truncate)
+        .collect();
+    push(client, duration, arguments);
+}
+
+/// Logs the delivery of the event if it has been published longer than the threshold ago.
+pub fn delivered(
+    client: SocketAddr,
+    stream: &EsStreamName,
+    number: EventNumber,
+    timestamp: Timestamp,
+) {
+    let lag = Timestamp::now().0.saturating_sub(timestamp.0) * 1000;
+    if lag < DELIVERY_THRESHOLD.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let command = vec![
+        String::from("deliver"),
+        stream.to_string(),
+        number.0.to_string(),
+    ];
+    push(client, lag, command);
+}
+
+/// Returns the entries of the slow log, the most recent first.
+pub fn entries() -> Vec<SlowLogEntry> {
+    ENTRIES.lock().unwrap().iter().cloned().collect()
+}
+
+pub fn reset() {
+    ENTRIES.lock().unwrap().clear();
+}
@@ -0,0 +1,121 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use sled::Db;
+
+use meilies::storage::StorageBackend;
+use meilies::stream::{Event, EventData, EventName, EventNumber, RawEvent, StreamName};
+
+use super::compression;
+use super::{last_event_number, publish_batch, stream_exists, stream_names, trim_stream};
+
+#[derive(Debug)]
+pub enum StorageError {
+    InternalError(sled::Error),
+    /// An event stored in the tree of the stream can not be decoded.
+    InvalidEvent(StreamName, String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::InternalError(e) => write!(f, "internal error; {}", e),
+            StorageError::InvalidEvent(stream, e) => {
+                write!(f, "invalid event in stream {}; {}", stream, e)
+            }
+        }
+    }
+}
+
+impl From<sled::Error> for StorageError {
+    fn from(error: sled::Error) -> StorageError {
+        StorageError::InternalError(error)
+    }
+}
+
+/// The default storage backend, each stream is stored in its own tree of the sled database.
+#[derive(Clone)]
+pub struct SledStorage {
+    db: Db,
+}
+
+impl SledStorage {
+    pub fn new(db: Db) -> SledStorage {
+        SledStorage { db }
+    }
+}
+
+impl StorageBackend for SledStorage {
+    type Error = StorageError;
+
+    fn append(
+        &self,
+        stream: &StreamName,
+        events: &[(EventName, EventData)],
+    ) -> Result<(EventNumber, EventNumber), StorageError> {
+        let tree = self.db.open_tree(stream.as_ref())?;
+        Ok(publish_batch(&self.db, &tree, stream, events)?)
+    }
+
+    fn contains(&self, stream: &StreamName) -> Result<bool, StorageError> {
+        Ok(stream_exists(&self.db, stream))
+    }
+
+    fn read_range(
+        &self,
+        stream: &StreamName,
+        from: EventNumber,
+        to: Option<EventNumber>,
+    ) -> Result<Vec<Event>, StorageError> {
+        // opening the tree of a stream that does not exist would create it
+        if !stream_exists(&self.db, stream) {
+            return Ok(Vec::new());
+        }
+        let tree = self.db.open_tree(stream.as_ref())?;
+
+        let from = from.to_be_bytes();
+        let iter = match to {
+            Some(to) => tree.range(from..to.to_be_bytes()),
+            None => tree.range(from..),
+        };
+
+        // a corrupted event is reported instead of panicking the connection
+        let invalid = |error: String| StorageError::InvalidEvent(stream.clone(), error);
+
+        let mut events = Vec::new();
+        for result in iter {
+            let (key, value) = result?;
+            let number = EventNumber::try_from(key.as_ref())
+                .map_err(|e| invalid(format!("invalid event number; {}", e)))?;
+            let raw_event = RawEvent::new(value);
+            let name = raw_event
+                .name()
+                .map_err(|e| invalid(format!("invalid name of event {}; {}", number.0, e)))?;
+            let headers = raw_event
+                .headers()
+                .ok_or_else(|| invalid(format!("invalid headers of event {}", number.0)))?;
+
+            events.push(Event {
+                number,
+                timestamp: raw_event.timestamp(),
+                name,
+                data: compression::decompress(raw_event.data()),
+                headers,
+            });
+        }
+
+        Ok(events)
+    }
+
+    fn head(&self, stream: &StreamName) -> Result<Option<EventNumber>, StorageError> {
+        Ok(last_event_number(&self.db, stream)?)
+    }
+
+    fn trim(&self, stream: &StreamName, up_to: EventNumber) -> Result<EventNumber, StorageError> {
+        Ok(trim_stream(&self.db, stream, up_to)?)
+    }
+
+    fn stream_names(&self) -> Result<Vec<StreamName>, StorageError> {
+        Ok(stream_names(&self.db))
+    }
+}
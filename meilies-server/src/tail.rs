@@ -0,0 +1,105 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use sled::IVec;
+
+use meilies::stream::{EventNumber, StreamName as EsStreamName};
+
+use super::metrics;
+use super::retention::Limits;
+
+lazy_static! {
+    static ref TAILS: Mutex<HashMap<EsStreamName, Tail>> = Mutex::default();
+    static ref CAPACITY: AtomicUsize = AtomicUsize::new(0);
+}
+
+/// The last events appended to a stream, as stored in its tree.
+struct Tail {
+    /// The number of the first event cached.
+    first: EventNumber,
+    events: VecDeque<IVec>,
+}
+
+impl Tail {
+    fn end(&self) -> EventNumber {
+        EventNumber(self.first.0 + self.events.len() as u64)
+    }
+}
+
+/// Sets the number of events cached for each stream, the cache is disabled with zero.
+pub fn set_capacity(capacity: usize) {
+    CAPACITY.store(capacity, Ordering::SeqCst);
+}
+
+/// Caches the raw events appended to the stream from the `first` event number,
+/// once the transaction that appended them is committed.
+///
+/// The streams with retention limits are not cached, their oldest
+/// events are removed in the transaction that appends the new ones.
+pub fn appended(stream: &EsStreamName, limits: Limits, first: EventNumber, events: Vec<IVec>) {
+    let capacity = CAPACITY.load(Ordering::SeqCst);
+    if capacity == 0 {
+        return;
+    }
+
+    if !limits.is_unlimited() {
+        invalidate(stream);
+        return;
+    }
+
+    let mut tails = TAILS.lock().unwrap();
+    let tail = tails.entry(stream.clone()).or_insert_with(|| Tail {
+        first,
+        events: VecDeque::with_capacity(capacity),
+    });
+
+    // the events of concurrent publications can be cached out of order,
+    // the cache only keeps the events that follow each other
+    if tail.end() != first {
+        tail.first = first;
+        tail.events.clear();
+    }
+
+    tail.events.extend(events);
+    while tail.events.len() > capacity {
+        tail.events.pop_front();
+        tail.first = tail.first.next();
+    }
+}
+
+/// Forgets the events cached for the stream, it must be called when its events are removed.
+pub fn invalidate(stream: &EsStreamName) {
+    TAILS.lock().unwrap().remove(stream);
+}
+
+/// Returns the entries of the events of the stream from `from` to `to` included,
+/// keyed by event number like in its tree, `None` if they are not all cached.
+pub fn read(
+    stream: &EsStreamName,
+    from: EventNumber,
+    to: EventNumber,
+) -> Option<Vec<(IVec, IVec)>> {
+    let tails = TAILS.lock().unwrap();
+    let cached = tails
+        .get(stream)
+        .filter(|tail| tail.first <= from && from <= to && to < tail.end())
+        .map(|tail| {
+            let skip = (from.0 - tail.first.0) as usize;
+            let count = (to.0 - from.0) as usize + 1;
+            tail.events
+                .iter()
+                .skip(skip)
+                .take(count)
+                .enumerate()
+                .map(|(i, event)| {
+                    let number = EventNumber(from.0 + i as u64);
+                    (IVec::from(&number.to_be_bytes()[..]), event.clone())
+                })
+                .collect()
+        });
+
+    metrics::tail_cache_read(cached.is_some());
+    cached
+}
@@ -0,0 +1,58 @@
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+use meilies_client::{load_certs, load_private_key};
+use tokio_rustls::rustls::{AllowAnyAuthenticatedClient, NoClientAuth, RootCertStore};
+use tokio_rustls::rustls::{ServerConfig, ServerSession, Session};
+use tokio_rustls::TlsAcceptor;
+use x509_parser::objects::oid2sn;
+use x509_parser::parse_x509_der;
+
+fn load_root_store(path: &Path) -> io::Result<RootCertStore> {
+    let mut store = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        store.add(&cert).map_err(|e| {
+            let message = format!("invalid CA certificate; {:?}", e);
+            io::Error::new(ErrorKind::InvalidData, message)
+        })?;
+    }
+    Ok(store)
+}
+
+/// Creates the acceptor used to start the TLS sessions of the incoming connections,
+/// the certificate chain and the private key must be PEM encoded.
+///
+/// If a client CA is given, clients must present a certificate signed by it.
+pub fn tls_acceptor(cert: &Path, key: &Path, client_ca: Option<&Path>) -> io::Result<TlsAcceptor> {
+    let certs = load_certs(cert)?;
+    let key = load_private_key(key)?;
+
+    let verifier = match client_ca {
+        Some(path) => AllowAnyAuthenticatedClient::new(load_root_store(path)?),
+        None => NoClientAuth::new(),
+    };
+
+    let mut config = ServerConfig::new(verifier);
+    config
+        .set_single_cert(certs, key)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Returns the common name of the subject of the certificate the client presented.
+pub fn peer_common_name(session: &ServerSession) -> Option<String> {
+    let certs = session.get_peer_certificates()?;
+    let (_, cert) = parse_x509_der(&certs.first()?.0).ok()?;
+
+    let subject = &cert.tbs_certificate.subject;
+    let attribute = subject
+        .rdn_seq
+        .iter()
+        .flat_map(|rdn| rdn.set.iter())
+        .find(|attr| oid2sn(&attr.attr_type) == Ok("CN"))?;
+
+    let name = attribute.attr_value.as_slice().ok()?;
+    String::from_utf8(name.to_vec()).ok()
+}
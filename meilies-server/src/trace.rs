@@ -0,0 +1,61 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use lazy_static::lazy_static;
+use tracing::{info, info_span};
+
+use meilies::stream::{EventHeaders, StreamName as EsStreamName, TraceContext, TRACEPARENT_HEADER};
+
+lazy_static! {
+    static ref SPAN_IDS: AtomicU64 = AtomicU64::new(0);
+}
+
+/// Returns a new random span id, it is never zero.
+fn span_id() -> u64 {
+    loop {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(SPAN_IDS.fetch_add(1, Ordering::Relaxed));
+        let id = hasher.finish();
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+/// Records the append of an event published with a trace context, the `traceparent`
+/// header of the event is replaced by the context of the append span, the
+/// deliveries to the consumers are then linked to it.
+pub fn appended(stream: &EsStreamName, headers: &mut EventHeaders) {
+    let parent = match headers.trace_context() {
+        Some(parent) => parent,
+        None => return,
+    };
+
+    let context = parent.child(span_id());
+    headers.insert(TRACEPARENT_HEADER.to_owned(), context.to_string());
+
+    let span = info_span!(
+        "append",
+        stream = stream.as_str(),
+        trace_id = %format!("{:032x}", context.trace_id),
+        parent_id = %format!("{:016x}", parent.parent_id),
+        span_id = %format!("{:016x}", context.parent_id),
+    );
+    span.in_scope(|| info!("event appended"));
+}
+
+/// Records the delivery of an event with a trace context to a subscriber.
+pub fn delivered(client: SocketAddr, stream: &EsStreamName, context: Option<TraceContext>) {
+    if let Some(context) = context {
+        let span = info_span!(
+            "deliver",
+            stream = stream.as_str(),
+            trace_id = %format!("{:032x}", context.trace_id),
+            parent_id = %format!("{:016x}", context.parent_id),
+            span_id = %format!("{:016x}", span_id()),
+        );
+        span.in_scope(|| info!(%client, "event delivered"));
+    }
+}
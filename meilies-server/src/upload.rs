@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io;
+use std::thread;
+use std::time::Duration;
+use std::{env, fmt};
+
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Client, Url};
+use ring::{digest, hmac};
+use sled::{Db, IVec};
+use tracing::{error, info};
+
+use meilies::stream::{EventNumber, RawEvent, StreamName as EsStreamName, Timestamp, ALL_STREAMS};
+
+use super::all::{EventReader, ALL_TREE};
+use super::backup::{ArchiveWriter, DEFAULT_TREE};
+use super::last_event_number;
+
+/// The tree that keeps the progress of the uploads: the next global
+/// event number to upload and the content of the manifest.
+const UPLOADS_TREE: &str = "meilies:uploads";
+
+const NEXT_NUMBER_KEY: &str = "next";
+const MANIFEST_KEY: &str = "manifest";
+
+/// The name of the object that lists the uploaded segments, one per line: the name of the
+/// segment, its first and last global event numbers and the time of its last event.
+const MANIFEST_OBJECT: &str = "manifest";
+
+/// The maximum number of events uploaded in a single segment.
+const SEGMENT_EVENTS: usize = 10_000;
+
+#[derive(Debug)]
+pub enum UploadError {
+    InternalError(sled::Error),
+    IoError(io::Error),
+    HttpError(reqwest::Error),
+    /// The bucket answered with this HTTP status code.
+    Rejected(u16),
+    MissingCredentials,
+    InvalidUrl,
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UploadError::InternalError(e) => write!(f, "internal error; {}", e),
+            UploadError::IoError(e) => write!(f, "io error; {}", e),
+            UploadError::HttpError(e) => write!(f, "http error; {}", e),
+            UploadError::Rejected(status) => write!(f, "upload rejected with status {}", status),
+            UploadError::MissingCredentials => {
+                write!(f, "AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set")
+            }
+            UploadError::InvalidUrl => write!(f, "invalid bucket url"),
+        }
+    }
+}
+
+impl From<sled::Error> for UploadError {
+    fn from(error: sled::Error) -> UploadError {
+        UploadError::InternalError(error)
+    }
+}
+
+impl From<reqwest::Error> for UploadError {
+    fn from(error: reqwest::Error) -> UploadError {
+        UploadError::HttpError(error)
+    }
+}
+
+impl From<io::Error> for UploadError {
+    fn from(error: io::Error) -> UploadError {
+        UploadError::IoError(error)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    hex(digest::digest(&digest::SHA256, bytes).as_ref())
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::sign(&key, data.as_bytes()).as_ref().to_vec()
+}
+
+/// An S3 compatible bucket the objects are put in, requests are signed with AWS Signature V4.
+struct Bucket {
+    client: Client,
+    url: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl Bucket {
+    fn new(url: &str, region: String) -> Result<Bucket, UploadError> {
+        Url::parse(url).map_err(|_| UploadError::InvalidUrl)?;
+
+        let access_key = env::var("AWS_ACCESS_KEY_ID");
+        let secret_key = env::var("AWS_SECRET_ACCESS_KEY");
+        let (access_key, secret_key) = match (access_key, secret_key) {
+            (Ok(access_key), Ok(secret_key)) => (access_key, secret_key),
+            _otherwise => return Err(UploadError::MissingCredentials),
+        };
+
+        Ok(Bucket {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_owned(),
+            region,
+            access_key,
+            secret_key,
+        })
+    }
+
+    fn put(&self, name: &str, body: Vec<u8>) -> Result<(), UploadError> {
+        let url =
+            Url::parse(&format!("{}/{}", self.url, name)).map_err(|_| UploadError::InvalidUrl)?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => return Err(UploadError::InvalidUrl),
+        };
+
+        // 2021-06-01T00:00:00.000Z becomes 20210601T000000Z
+        let datetime = Timestamp::now().to_datetime();
+        let amz_date = format!("{}Z", datetime[..19].replace(&['-', ':'][..], ""));
+        let date = &amz_date[..8];
+
+        let payload_hash = sha256(&body);
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            url.path(),
+            host,
+            payload_hash,
+            amz_date,
+            signed_headers,
+            payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256(canonical_request.as_bytes())
+        );
+
+        let key = format!("AWS4{}", self.secret_key);
+        let key = hmac_sha256(key.as_bytes(), date);
+        let key = hmac_sha256(&key, &self.region);
+        let key = hmac_sha256(&key, "s3");
+        let key = hmac_sha256(&key, "aws4_request");
+        let signature = hex(&hmac_sha256(&key, &string_to_sign));
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+
+        let response = self
+            .client
+            .put(url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header(AUTHORIZATION, authorization)
+            .body(body)
+            .send()?;
+
+        if !response.status().is_success() {
+            return Err(UploadError::Rejected(response.status().as_u16()));
+        }
+
+        Ok(())
+    }
+}
+
+/// The events of a range of the global log, encoded like a backup archive.
+struct Segment {
+    archive: Vec<u8>,
+    last: EventNumber,
+    last_timestamp: Option<Timestamp>,
+}
+
+/// Encodes the events published from the given global event number, the segment
+/// contains the `$all` positions, the events and the last event number of their stream.
+fn read_segment(db: &Db, next: EventNumber, fence: EventNumber) -> Result<Segment, UploadError> {
+    let all = db.open_tree(ALL_TREE)?;
+    let mut reader = EventReader::new(db.clone());
+
+    let mut positions = Vec::new();
+    let mut streams = BTreeMap::<EsStreamName, Vec<(EventNumber, IVec)>>::new();
+    let mut last = next;
+    let mut last_timestamp = None;
+
+    let range = all.range(next.to_be_bytes()..=fence.to_be_bytes());
+    for result in range.take(SEGMENT_EVENTS) {
+        let (key, entry) = result?;
+        last = EventNumber::try_from(key.as_ref()).unwrap();
+
+        // the events trimmed before being uploaded are lost
+        if let Some((stream, number, value)) = reader.read(&entry)? {
+            last_timestamp = Some(RawEvent::new(&value).timestamp());
+            streams.entry(stream).or_default().push((number, value));
+        }
+        positions.push((key, entry));
+    }
+
+    // the positions of the deleted streams are removed from the global log,
+    // the segment covers the whole range if it has not been cut
+    if positions.len() < SEGMENT_EVENTS {
+        last = fence;
+    }
+
+    let write = || -> io::Result<Vec<u8>> {
+        let mut writer = ArchiveWriter::new(Vec::new())?;
+
+        writer.start_tree(DEFAULT_TREE)?;
+        writer.entry(ALL_STREAMS.as_bytes(), &last.to_be_bytes())?;
+        for (stream, events) in &streams {
+            let (number, _) = events.last().unwrap();
+            writer.entry(stream.as_str().as_bytes(), &number.to_be_bytes())?;
+        }
+        writer.end_tree()?;
+
+        writer.start_tree(ALL_TREE.as_bytes())?;
+        for (key, entry) in &positions {
+            writer.entry(key, entry)?;
+        }
+        writer.end_tree()?;
+
+        for (stream, events) in &streams {
+            writer.start_tree(stream.as_str().as_bytes())?;
+            for (number, value) in events {
+                writer.entry(&number.to_be_bytes(), value)?;
+            }
+            writer.end_tree()?;
+        }
+
+        writer.finish()
+    };
+
+    Ok(Segment {
+        archive: write()?,
+        last,
+        last_timestamp,
+    })
+}
+
+/// Uploads the events published since the last upload as segments, each one followed
+/// by the manifest. The progress is saved once the manifest has been uploaded.
+fn upload_segments(db: &Db, bucket: &Bucket) -> Result<(), UploadError> {
+    let uploads = db.open_tree(UPLOADS_TREE)?;
+
+    // the events published during the upload are part of the next one
+    let fence = match last_event_number(db, &EsStreamName::all())? {
+        Some(fence) => fence,
+        None => return Ok(()),
+    };
+
+    loop {
+        let next = uploads.get(NEXT_NUMBER_KEY)?;
+        let next = next.map_or(EventNumber::zero(), |n| {
+            EventNumber::try_from(n.as_ref()).unwrap()
+        });
+
+        if next > fence {
+            return Ok(());
+        }
+
+        let segment = read_segment(db, next, fence)?;
+        let name = format!("segment-{:020}-{:020}", next.0, segment.last.0);
+        bucket.put(&name, segment.archive)?;
+
+        let manifest = uploads.get(MANIFEST_KEY)?;
+        let mut manifest = manifest.map_or_else(Vec::new, |m| m.to_vec());
+        let last_time = segment
+            .last_timestamp
+            .map_or(String::from("-"), Timestamp::to_datetime);
+        let line = format!("{} {} {} {}\n", name, next.0, segment.last.0, last_time);
+        manifest.extend_from_slice(line.as_bytes());
+        bucket.put(MANIFEST_OBJECT, manifest.clone())?;
+
+        uploads.insert(MANIFEST_KEY, manifest)?;
+        uploads.insert(NEXT_NUMBER_KEY, &segment.last.next().to_be_bytes()[..])?;
+
+        info!("segment {} uploaded", name);
+    }
+}
+
+/// Spawns the thread that periodically uploads the newly published events to a bucket.
+pub fn spawn_uploader(
+    db: Db,
+    url: &str,
+    region: String,
+    interval: Duration,
+) -> Result<(), UploadError> {
+    let bucket = Bucket::new(url, region)?;
+
+    thread::Builder::new()
+        .name(String::from("upload"))
+        .spawn(move || loop {
+            thread::sleep(interval);
+            if let Err(e) = upload_segments(&db, &bucket) {
+                error!("error uploading the events; {}", e);
+            }
+        })?;
+
+    Ok(())
+}
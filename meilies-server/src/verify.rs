@@ -0,0 +1,38 @@
+use std::convert::TryFrom;
+
+use sled::Db;
+use tracing::error;
+
+use meilies::reqresp::Response;
+use meilies::stream::{EventNumber, RawEvent, StreamName as EsStreamName};
+
+/// Scans the events kept in the stream and compares their checksum with their content,
+/// the events stored before the checksums were introduced can not be checked.
+pub fn verify_stream(db: &Db, stream: EsStreamName) -> sled::Result<Response> {
+    let tree = db.open_tree(stream.as_ref())?;
+
+    let mut checked = 0;
+    let mut unchecked = 0;
+    let mut corrupted = Vec::new();
+    for result in tree.iter() {
+        let (key, value) = result?;
+        let number = EventNumber::try_from(key.as_ref()).unwrap();
+
+        match RawEvent::new(&value).checksum() {
+            Some((checksum, covered)) if crc32fast::hash(covered) == checksum => checked += 1,
+            Some(_) => corrupted.push(number),
+            None => unchecked += 1,
+        }
+    }
+
+    if !corrupted.is_empty() {
+        error!("stream {} has {} corrupted events", stream, corrupted.len());
+    }
+
+    Ok(Response::Verified {
+        stream,
+        checked,
+        unchecked,
+        corrupted,
+    })
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{fmt, io};
+
+use reqwest::{Client, Url};
+use ring::hmac;
+use sled::Db;
+use tracing::{error, info, warn};
+
+use meilies::stream::{
+    Event, EventHeaders, EventNumber, RawEvent, StreamName as EsStreamName, Timestamp,
+};
+
+use super::gateway::json_event;
+use super::replication::Replication;
+use super::stream_names;
+use super::{compression, first_event_number, last_event_number, meta, publish_system_event};
+
+/// The stream metadata that specifies the URL the events of the stream are posted to,
+/// an empty URL stops the deliveries.
+const WEBHOOK_URL: &str = "webhook.url";
+
+/// The stream metadata that specifies the comma separated names of the events delivered,
+/// all the events are delivered if it is not set or empty.
+const WEBHOOK_EVENTS: &str = "webhook.events";
+
+/// The tree that keeps the number of the next event to deliver of each stream with a webhook.
+const WEBHOOK_OFFSETS_TREE: &str = "meilies:webhook-offsets";
+
+/// The stream that receives the events that could not be delivered after all the attempts.
+const DEAD_LETTERS_STREAM: &str = "$webhook-dead-letters";
+
+/// The headers of the deliveries, the signature is the hex encoded HMAC-SHA256
+/// of the timestamp and the body separated by a dot, if a secret is configured.
+const STREAM_HEADER: &str = "x-meilies-stream";
+const NUMBER_HEADER: &str = "x-meilies-event-number";
+const ATTEMPT_HEADER: &str = "x-meilies-attempt";
+const TIMESTAMP_HEADER: &str = "x-meilies-timestamp";
+const SIGNATURE_HEADER: &str = "x-meilies-signature";
+
+/// The interval at which the streams are checked for new events to deliver.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The maximum number of events delivered for a stream before delivering the other ones.
+const DELIVERY_BATCH: usize = 100;
+
+/// The time an endpoint has to answer a delivery.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The delay before the first retry of a delivery, doubled after each failed attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(600);
+
+/// Returns an error if the value of a webhook metadata is invalid.
+pub fn check_meta(key: &str, value: &str) -> Result<(), String> {
+    match key {
+        WEBHOOK_URL if !value.is_empty() => match Url::parse(value) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(()),
+            _otherwise => Err(format!("{} must be an HTTP URL, found {:?}", key, value)),
+        },
+        WEBHOOK_EVENTS if !value.is_empty() && value.split(',').any(|n| n.trim().is_empty()) => {
+            Err(format!(
+                "{} contains an empty event name, found {:?}",
+                key, value
+            ))
+        }
+        _otherwise => Ok(()),
+    }
+}
+
+#[derive(Debug)]
+enum DeliveryError {
+    HttpError(reqwest::Error),
+    /// The endpoint answered with this HTTP status code.
+    Rejected(u16),
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeliveryError::HttpError(e) => write!(f, "http error; {}", e),
+            DeliveryError::Rejected(status) => {
+                write!(f, "delivery rejected with status {}", status)
+            }
+        }
+    }
+}
+
+impl From<reqwest::Error> for DeliveryError {
+    fn from(error: reqwest::Error) -> DeliveryError {
+        DeliveryError::HttpError(error)
+    }
+}
+
+/// The failed attempts to deliver the next event of a stream.
+struct Retry {
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+/// Posts the events of the streams with a webhook to their endpoints, in order, and
+/// retries the failed deliveries with an exponential backoff.
+struct Deliverer {
+    db: Db,
+    client: Client,
+    secret: Option<hmac::Key>,
+    max_attempts: u32,
+    retries: HashMap<EsStreamName, Retry>,
+}
+
+impl Deliverer {
+    fn post(
+        &self,
+        url: &str,
+        stream: &EsStreamName,
+        number: EventNumber,
+        body: &str,
+        attempt: u32,
+    ) -> Result<(), DeliveryError> {
+        let mut request = self
+            .client
+            .post(url)
+            .header("content-type", "application/json")
+            .header(STREAM_HEADER, stream.as_str())
+            .header(NUMBER_HEADER, number.0.to_string())
+            .header(ATTEMPT_HEADER, attempt.to_string());
+
+        if let Some(secret) = &self.secret {
+            let timestamp = Timestamp::now().0.to_string();
+            let signed = format!("{}.{}", timestamp, body);
+            let signature = hmac::sign(secret, signed.as_bytes());
+            let signature: String = signature
+                .as_ref()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            request = request
+                .header(TIMESTAMP_HEADER, timestamp)
+                .header(SIGNATURE_HEADER, signature);
+        }
+
+        let response = request.body(body.to_owned()).send()?;
+        if !response.status().is_success() {
+            return Err(DeliveryError::Rejected(response.status().as_u16()));
+        }
+
+        Ok(())
+    }
+
+    /// Appends the event that could not be delivered to the dead letters stream,
+    /// along with the stream and the number of the event and the last error.
+    fn dead_letter(
+        &self,
+        url: &str,
+        stream: &EsStreamName,
+        number: EventNumber,
+        body: &str,
+        error: &DeliveryError,
+    ) -> sled::Result<()> {
+        let mut headers = EventHeaders::new();
+        headers.insert(String::from("stream"), stream.to_string());
+        headers.insert(String::from("number"), number.0.to_string());
+        headers.insert(String::from("url"), url.to_owned());
+        headers.insert(String::from("error"), error.to_string());
+
+        let dead_letters = EsStreamName::new(DEAD_LETTERS_STREAM.to_owned()).unwrap();
+        publish_system_event(
+            &self.db,
+            &dead_letters,
+            "DeliveryFailed",
+            &headers,
+            body.as_bytes(),
+        )
+    }
+
+    /// Delivers the next events of the stream, stops at the first failed delivery
+    /// that must be retried later or once a batch of events has been delivered.
+    fn deliver_stream(&mut self, stream: &EsStreamName, url: &str) -> sled::Result<()> {
+        if let Some(retry) = self.retries.get(stream) {
+            if retry.next_attempt > Instant::now() {
+                return Ok(());
+            }
+        }
+
+        let offsets = self.db.open_tree(WEBHOOK_OFFSETS_TREE)?;
+        let next = match offsets.get(stream)? {
+            Some(next) => EventNumber::try_from(next.as_ref()).unwrap(),
+            // only the events published after the webhook has been set are delivered
+            None => {
+                let next = last_event_number(&self.db, stream)?
+                    .map_or(EventNumber::zero(), EventNumber::next);
+                offsets.insert(stream, &next.to_be_bytes()[..])?;
+                info!(
+                    "delivering the events of {} to {} from {:?}",
+                    stream, url, next
+                );
+                next
+            }
+        };
+
+        // the events trimmed before being delivered are lost
+        let first = first_event_number(&self.db, stream)?.unwrap_or_else(EventNumber::zero);
+        let next = next.max(first);
+
+        let event_names = meta::get(&self.db, stream, WEBHOOK_EVENTS)?.unwrap_or_default();
+        let event_names: Vec<_> = event_names
+            .split(',')
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .collect();
+
+        let tree = self.db.open_tree(stream.as_ref())?;
+        for result in tree.range(next.to_be_bytes()..).take(DELIVERY_BATCH) {
+            let (key, value) = result?;
+            let number = EventNumber::try_from(key.as_ref()).unwrap();
+            let raw_event = RawEvent::new(value);
+            let name = raw_event.name().unwrap();
+
+            if event_names.is_empty() || event_names.contains(&name.as_str()) {
+                let event = Event {
+                    number,
+                    timestamp: raw_event.timestamp(),
+                    name,
+                    data: compression::decompress(raw_event.data()),
+                    headers: raw_event.headers().unwrap(),
+                };
+                let body = json_event(&event);
+
+                let attempt = self.retries.get(stream).map_or(1, |r| r.attempts + 1);
+                match self.post(url, stream, number, &body, attempt) {
+                    Ok(()) => {
+                        self.retries.remove(stream);
+                    }
+                    Err(e) if attempt < self.max_attempts => {
+                        let backoff = INITIAL_BACKOFF * 2u32.saturating_pow(attempt - 1);
+                        let backoff = backoff.min(MAX_BACKOFF);
+                        warn!(
+                            "error delivering the event {:?} of {} to {}, attempt {}; {}",
+                            number, stream, url, attempt, e
+                        );
+                        let retry = Retry {
+                            attempts: attempt,
+                            next_attempt: Instant::now() + backoff,
+                        };
+                        self.retries.insert(stream.clone(), retry);
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!(
+                            "error delivering the event {:?} of {} to {}, giving up; {}",
+                            number, stream, url, e
+                        );
+                        self.dead_letter(url, stream, number, &body, &e)?;
+                        self.retries.remove(stream);
+                    }
+                }
+            }
+
+            offsets.insert(stream, &number.next().to_be_bytes()[..])?;
+        }
+
+        Ok(())
+    }
+
+    fn deliver_streams(&mut self) -> sled::Result<()> {
+        let offsets = self.db.open_tree(WEBHOOK_OFFSETS_TREE)?;
+        for stream in stream_names(&self.db) {
+            // the dead letters are not delivered again, it could loop forever
+            if stream.as_str() == DEAD_LETTERS_STREAM {
+                continue;
+            }
+
+            match meta::get(&self.db, &stream, WEBHOOK_URL)? {
+                Some(url) if !url.is_empty() => self.deliver_stream(&stream, &url)?,
+                // the webhooks set again later only deliver the events published from then on
+                _otherwise => {
+                    offsets.remove(&stream)?;
+                    self.retries.remove(&stream);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Spawns the thread that delivers the events of the streams with a `webhook.url` metadata,
+/// the events are only delivered by the primary server. The deliveries are signed with the
+/// secret if there is one and given up after `max_attempts` failed attempts.
+pub fn spawn_deliverer(
+    db: Db,
+    replication: Replication,
+    secret: Option<String>,
+    max_attempts: u32,
+) -> io::Result<()> {
+    let client = Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .build()
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+
+    let mut deliverer = Deliverer {
+        db,
+        client,
+        secret: secret.map(|s| hmac::Key::new(hmac::HMAC_SHA256, s.as_bytes())),
+        max_attempts: max_attempts.max(1),
+        retries: HashMap::new(),
+    };
+
+    thread::Builder::new()
+        .name(String::from("webhooks"))
+        .spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+            if !replication.is_primary() {
+                continue;
+            }
+            if let Err(e) = deliverer.deliver_streams() {
+                error!("error delivering the events to the webhooks; {}", e);
+            }
+        })?;
+
+    Ok(())
+}
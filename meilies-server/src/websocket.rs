@@ -0,0 +1,515 @@
+use std::io::{self, BufReader, Read, Write};
+use std::iter::Peekable;
+use std::mem;
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::str::{self, Chars};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use bytes::BytesMut;
+use futures::{Future, Sink, Stream};
+use sha1::{Digest, Sha1};
+use tokio::codec::{Decoder, Encoder};
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use meilies::reqresp::{ErrorCode, Request, Response, ServerCodec};
+use meilies::resp::{FromResp, RespValue};
+
+use super::acl::Authentication;
+use super::clients::Client;
+use super::dispatch_blocking;
+use super::gateway::{json_string, Gateway};
+
+/// The path of the gateway that is upgraded to a WebSocket connection.
+pub const WEBSOCKET_PATH: &str = "/ws";
+
+/// The subprotocol of the connections whose responses are sent as JSON text frames,
+/// the responses are sent as RESP binary frames otherwise.
+const JSON_PROTOCOL: &str = "meilies-json";
+const RESP_PROTOCOL: &str = "meilies-resp";
+
+/// The GUID appended to the key of the client to compute the accept key (RFC 6455).
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// The upgrade request of a WebSocket client.
+pub struct Handshake {
+    key: String,
+    protocol: Option<&'static str>,
+}
+
+impl Handshake {
+    /// Returns the handshake of an upgrade request, `None` if it is not a valid one.
+    /// The JSON subprotocol is chosen if the client offers it.
+    pub fn new(
+        method: &str,
+        upgrade: Option<&str>,
+        key: Option<&str>,
+        protocols: Option<&str>,
+    ) -> Option<Handshake> {
+        let upgrade = upgrade?.eq_ignore_ascii_case("websocket");
+        if method != "GET" || !upgrade {
+            return None;
+        }
+
+        let offered: Vec<_> = protocols
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .collect();
+        let protocol = if offered.contains(&JSON_PROTOCOL) {
+            Some(JSON_PROTOCOL)
+        } else if offered.contains(&RESP_PROTOCOL) {
+            Some(RESP_PROTOCOL)
+        } else {
+            None
+        };
+
+        Some(Handshake {
+            key: key?.to_owned(),
+            protocol,
+        })
+    }
+
+    fn accept_key(&self) -> String {
+        let mut hasher = Sha1::new();
+        hasher.input(self.key.as_bytes());
+        hasher.input(WEBSOCKET_GUID.as_bytes());
+        base64::encode(&hasher.result())
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n",
+            self.accept_key()
+        );
+        if let Some(protocol) = self.protocol {
+            response.push_str(&format!("Sec-WebSocket-Protocol: {}\r\n", protocol));
+        }
+        response.push_str("\r\n");
+        writer.write_all(response.as_bytes())
+    }
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_frame(reader: &mut impl Read, max_len: usize) -> io::Result<Frame> {
+    let mut head = [0; 2];
+    reader.read_exact(&mut head)?;
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0f;
+
+    if head[1] & 0x80 == 0 {
+        return Err(invalid_data("the frames of a client must be masked"));
+    }
+
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            u64::from(u16::from_be_bytes(len))
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => u64::from(len),
+    };
+    if len > max_len as u64 {
+        return Err(invalid_data("the frame is too large"));
+    }
+
+    let mut mask = [0; 4];
+    reader.read_exact(&mut mask)?;
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok(Frame {
+        fin,
+        opcode,
+        payload,
+    })
+}
+
+/// Writes an unfragmented frame, the frames of the server are not masked.
+fn write_frame(writer: &Mutex<TcpStream>, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= std::u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+
+    writer.lock().unwrap().write_all(&frame)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().map_or(false, |c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Option<u32> {
+    let hex: String = chars.take(4).collect();
+    if hex.len() != 4 {
+        return None;
+    }
+    u32::from_str_radix(&hex, 16).ok()
+}
+
+/// Parses a JSON string whose opening quote has been consumed.
+fn parse_json_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut string = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(string),
+            '\\' => match chars.next()? {
+                '"' => string.push('"'),
+                '\\' => string.push('\\'),
+                '/' => string.push('/'),
+                'b' => string.push('\u{8}'),
+                'f' => string.push('\u{c}'),
+                'n' => string.push('\n'),
+                'r' => string.push('\r'),
+                't' => string.push('\t'),
+                'u' => {
+                    let mut code = parse_hex4(chars)?;
+                    // the characters outside of the BMP are escaped as surrogate pairs
+                    if (0xd800..0xdc00).contains(&code) {
+                        if chars.next()? != '\\' || chars.next()? != 'u' {
+                            return None;
+                        }
+                        let low = parse_hex4(chars)?;
+                        if !(0xdc00..0xe000).contains(&low) {
+                            return None;
+                        }
+                        code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                    }
+                    string.push(std::char::from_u32(code)?);
+                }
+                _otherwise => return None,
+            },
+            c => string.push(c),
+        }
+    }
+}
+
+/// Parses a command sent as a JSON array of strings and numbers,
+/// e.g. `["publish", "my-stream", "MyEvent", "data"]`.
+fn parse_json_command(text: &str) -> Option<RespValue> {
+    let mut chars = text.chars().peekable();
+    skip_whitespace(&mut chars);
+    if chars.next()? != '[' {
+        return None;
+    }
+
+    let mut args = Vec::new();
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&']') {
+        chars.next();
+    } else {
+        loop {
+            skip_whitespace(&mut chars);
+            let arg = match chars.next()? {
+                '"' => parse_json_string(&mut chars)?,
+                c if c == '-' || c.is_ascii_digit() => {
+                    let mut number = c.to_string();
+                    while let Some(&c) = chars.peek() {
+                        if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+                            break;
+                        }
+                        number.push(c);
+                        chars.next();
+                    }
+                    number
+                }
+                _otherwise => return None,
+            };
+            args.push(RespValue::bulk_string(arg));
+
+            skip_whitespace(&mut chars);
+            match chars.next()? {
+                ',' => continue,
+                ']' => break,
+                _otherwise => return None,
+            }
+        }
+    }
+
+    skip_whitespace(&mut chars);
+    if chars.next().is_some() {
+        return None;
+    }
+
+    Some(RespValue::Array(args))
+}
+
+/// Formats a response as JSON, the bulk strings that are not valid UTF-8
+/// are encoded as `{"base64": ...}` objects and the errors as `{"error": ...}` objects.
+fn json_value(value: RespValue, json: &mut String) {
+    match value {
+        RespValue::SimpleString(string) => json.push_str(&json_string(&string)),
+        RespValue::Error(error) => json.push_str(&format!("{{\"error\":{}}}", json_string(&error))),
+        RespValue::Integer(integer) => json.push_str(&integer.to_string()),
+        RespValue::BulkString(bytes) => match str::from_utf8(&bytes) {
+            Ok(string) => json.push_str(&json_string(string)),
+            Err(_) => json.push_str(&format!("{{\"base64\":\"{}\"}}", base64::encode(&bytes))),
+        },
+        RespValue::Array(values) | RespValue::Push(values) => {
+            json.push('[');
+            for (i, value) in values.into_iter().enumerate() {
+                if i != 0 {
+                    json.push(',');
+                }
+                json_value(value, json);
+            }
+            json.push(']');
+        }
+        RespValue::Nil => json.push_str("null"),
+        RespValue::Map(pairs) => {
+            json.push('{');
+            for (i, (key, value)) in pairs.into_iter().enumerate() {
+                if i != 0 {
+                    json.push(',');
+                }
+                let key = match key {
+                    RespValue::SimpleString(string) => string,
+                    RespValue::BulkString(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                    key => {
+                        let mut string = String::new();
+                        json_value(key, &mut string);
+                        string
+                    }
+                };
+                json.push_str(&json_string(&key));
+                json.push(':');
+                json_value(value, json);
+            }
+            json.push('}');
+        }
+        RespValue::Double(double) if double.is_finite() => json.push_str(&double.to_string()),
+        RespValue::Double(_) => json.push_str("null"),
+    }
+}
+
+/// Spawns the thread that sends the responses of the connection, as JSON
+/// text frames if `json` is `true` and as RESP binary frames otherwise.
+fn spawn_writer(
+    receiver: mpsc::Receiver<Result<Response, String>>,
+    writer: Arc<Mutex<TcpStream>>,
+    json: bool,
+    mut codec: ServerCodec,
+) -> io::Result<()> {
+    thread::Builder::new()
+        .name(String::from("websocket-writer"))
+        .spawn(move || {
+            for response in receiver.wait() {
+                let response = match response {
+                    Ok(response) => response,
+                    Err(_) => break,
+                };
+
+                let result = if json {
+                    let value = match response {
+                        Ok(response) => response.into(),
+                        Err(error) => RespValue::Error(error),
+                    };
+                    let mut text = String::new();
+                    json_value(value, &mut text);
+                    write_frame(&writer, OPCODE_TEXT, text.as_bytes())
+                } else {
+                    let mut buf = BytesMut::new();
+                    match codec.encode(response, &mut buf) {
+                        Ok(()) => write_frame(&writer, OPCODE_BINARY, &buf),
+                        Err(e) => {
+                            error!("error encoding a response; {}", e);
+                            continue;
+                        }
+                    }
+                };
+
+                if result.is_err() {
+                    info!("encountered closed websocket");
+                    break;
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+/// The state of a WebSocket connection, its commands are run like the ones of a RESP connection.
+struct Connection<'a> {
+    gateway: &'a Gateway,
+    authentication: Authentication,
+    sender: mpsc::Sender<Result<Response, String>>,
+    writer: Arc<Mutex<TcpStream>>,
+    codec: ServerCodec,
+    /// The RESP bytes received that do not form a complete command yet.
+    buffer: BytesMut,
+}
+
+impl Connection<'_> {
+    fn send_error(&self, error: String) {
+        if self.sender.clone().send(Err(error)).wait().is_err() {
+            info!("encountered closed channel");
+        }
+    }
+
+    /// Returns the commands of a message, the text messages contain a JSON command and the
+    /// binary ones RESP commands, which can be split across the messages.
+    fn commands(&mut self, opcode: u8, message: Vec<u8>) -> Result<Vec<Request>, String> {
+        if opcode == OPCODE_TEXT {
+            let text = str::from_utf8(&message).ok();
+            let command = text.and_then(parse_json_command).ok_or_else(|| {
+                ErrorCode::Err.error("expected a JSON array of strings and numbers")
+            })?;
+            let request = Request::from_resp(command).map_err(|e| ErrorCode::Err.error(e))?;
+            return Ok(vec![request]);
+        }
+
+        self.buffer.extend_from_slice(&message);
+        let mut requests = Vec::new();
+        while let Some(request) = self
+            .codec
+            .decode(&mut self.buffer)
+            .map_err(|e| ErrorCode::Err.error(e))?
+        {
+            requests.push(request);
+        }
+        Ok(requests)
+    }
+
+    fn read_messages(&mut self, reader: &mut impl Read, client: &Client) -> io::Result<()> {
+        let mut acks = self.gateway.acks.connection();
+        let max_len = self.gateway.max_body_size();
+        let mut message = Vec::new();
+        let mut message_opcode = OPCODE_BINARY;
+
+        loop {
+            let frame = read_frame(reader, max_len)?;
+            match frame.opcode {
+                OPCODE_PING => write_frame(&self.writer, OPCODE_PONG, &frame.payload)?,
+                OPCODE_PONG => (),
+                OPCODE_CLOSE => {
+                    // the close frame is echoed with the status code of the client
+                    let status = &frame.payload[..frame.payload.len().min(2)];
+                    return write_frame(&self.writer, OPCODE_CLOSE, status);
+                }
+                OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION => {
+                    if frame.opcode != OPCODE_CONTINUATION {
+                        message_opcode = frame.opcode;
+                    }
+                    message.extend_from_slice(&frame.payload);
+                    if message.len() > max_len {
+                        return Err(invalid_data("the message is too large"));
+                    }
+                    if !frame.fin {
+                        continue;
+                    }
+
+                    let message = mem::replace(&mut message, Vec::new());
+                    let requests = match self.commands(message_opcode, message) {
+                        Ok(requests) => requests,
+                        // a RESP connection is closed on an invalid command
+                        Err(error) if message_opcode == OPCODE_BINARY => {
+                            self.send_error(error);
+                            return Ok(());
+                        }
+                        Err(error) => {
+                            self.send_error(error);
+                            continue;
+                        }
+                    };
+
+                    for request in requests {
+                        let result = dispatch_blocking(
+                            request,
+                            self.gateway.db.clone(),
+                            self.sender.clone(),
+                            client,
+                            &mut self.authentication,
+                            &self.gateway.replication,
+                            &mut acks,
+                        );
+                        if let Err(e) = result {
+                            error!("error; {}", e);
+                            self.send_error(ErrorCode::Err.error(e));
+                            return Ok(());
+                        }
+                    }
+                }
+                _otherwise => return Err(invalid_data("unknown opcode")),
+            }
+        }
+    }
+}
+
+/// Upgrades the connection to a WebSocket one and runs the commands it receives
+/// until it is closed, the subscriptions send their events as responses.
+pub fn serve(
+    gateway: &Gateway,
+    addr: SocketAddr,
+    authentication: Authentication,
+    handshake: Handshake,
+    mut stream: TcpStream,
+) -> io::Result<()> {
+    handshake.write_to(&mut stream)?;
+    // a connection waiting for the events of its subscriptions is idle
+    stream.set_read_timeout(None)?;
+
+    let codec = match gateway.max_event_size {
+        Some(size) => ServerCodec::with_max_event_size(size),
+        None => ServerCodec::default(),
+    };
+    let json = handshake.protocol == Some(JSON_PROTOCOL);
+    let writer = Arc::new(Mutex::new(stream.try_clone()?));
+    let (sender, receiver) = mpsc::channel(10);
+    spawn_writer(receiver, writer.clone(), json, codec)?;
+
+    let (client, _killed) = gateway.clients.register(addr, authentication.user_name());
+    let mut connection = Connection {
+        gateway,
+        authentication,
+        sender,
+        writer,
+        codec,
+        buffer: BytesMut::new(),
+    };
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let result = connection.read_messages(&mut reader, &client);
+    info!("websocket {} closed", addr);
+
+    // the writer stops once the subscriptions notice the connection is closed
+    let _ = stream.shutdown(Shutdown::Both);
+    result
+}
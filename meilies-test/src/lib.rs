@@ -0,0 +1,122 @@
+use std::net::SocketAddr;
+
+#[cfg(feature = "client")]
+use meilies_client::ConnectBuilder;
+use meilies_server::{EmbeddedClient, EmbeddedServer};
+
+#[cfg(feature = "simulation")]
+pub use self::simulation::{Simulation, SERVER};
+
+#[cfg(feature = "simulation")]
+mod simulation;
+
+/// The address the servers listen on, the port is chosen by the OS.
+const LOCALHOST: ([u8; 4], u16) = ([127, 0, 0, 1], 0);
+
+/// A server with its own temporary database, kept in memory on Linux. Its threads and its
+/// connections are stopped when it is dropped, the database is removed once its clients are.
+///
+/// The servers of a test process share the ephemeral streams, the `~` streams.
+pub struct TestServer {
+    server: EmbeddedServer,
+    addr: Option<SocketAddr>,
+}
+
+impl TestServer {
+    fn open() -> TestServer {
+        let server = EmbeddedServer::temporary().expect("error opening the database");
+        TestServer { server, addr: None }
+    }
+
+    /// Starts a server listening on a free port of the loopback interface.
+    /// Panics if the server cannot be started, like the assertions of a test.
+    pub fn start() -> TestServer {
+        let mut server = TestServer::open();
+        let addr = server.server.listen(&SocketAddr::from(LOCALHOST));
+        server.addr = Some(addr.expect("error listening on the loopback interface"));
+        server
+    }
+
+    /// Starts a server that does not listen, its clients are only the `EmbeddedClient`s.
+    pub fn unlisted() -> TestServer {
+        TestServer::open()
+    }
+
+    /// The address the server listens on, `None` if it has been started `unlisted`.
+    pub fn addr(&self) -> Option<SocketAddr> {
+        self.addr
+    }
+
+    /// Returns a client that runs its commands without any connection.
+    pub fn client(&self) -> EmbeddedClient {
+        self.server.connect()
+    }
+
+    /// Returns a builder of the connections to the server, panics if it does not listen.
+    #[cfg(feature = "client")]
+    pub fn connect_builder(&self) -> ConnectBuilder {
+        let addr = self.addr.expect("the server has been started unlisted");
+        ConnectBuilder::new(addr)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+
+    use bytes::Bytes;
+    use meilies::reqresp::{Request, Response};
+    use meilies::stream::{EventData, EventNumber, StreamName};
+
+    #[test]
+    fn unlisted_clients() {
+        let server = TestServer::unlisted();
+        assert_eq!(server.addr(), None);
+
+        let stream: StreamName = "test-stream".parse().unwrap();
+        let event = (
+            "test-event".parse().unwrap(),
+            EventData(Bytes::from("hello")),
+        );
+        let mut publisher = server.client();
+        let publish = Request::PublishBatch {
+            stream: stream.clone(),
+            events: vec![event.clone(), event],
+        };
+        assert!(publisher.request(publish).is_ok());
+
+        // the clients share the database of the server
+        let mut reader = server.client();
+        match reader.request(Request::LastEventNumber { stream }) {
+            Ok(Response::LastEventNumber { number, .. }) => {
+                assert_eq!(number, Some(EventNumber(1)))
+            }
+            otherwise => panic!("unexpected response {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn started_address() {
+        let server = TestServer::start();
+        let addr = server.addr().unwrap();
+        assert!(addr.ip().is_loopback());
+        assert_ne!(addr.port(), 0);
+    }
+
+    #[test]
+    fn dropped_server_stops_listening() {
+        let server = TestServer::start();
+        let addr = server.addr().unwrap();
+        assert!(TcpStream::connect(addr).is_ok());
+
+        drop(server);
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use meilies::reqresp::{Request, Response};
+use meilies::sim::{Envelope, Network, NodeId, Scheduler};
+use meilies::stream::Timestamp;
+use meilies_server::{EmbeddedClient, EmbeddedServer};
+use tempfile::TempDir;
+
+/// The node of the server, the clients are the following nodes.
+pub const SERVER: NodeId = 0;
+
+/// The virtual time the simulations start at, 2021-01-01.
+const START: Timestamp = Timestamp(1_609_459_200_000);
+
+/// The latencies of the network of a new simulation.
+const MIN_LATENCY: Duration = Duration::from_millis(1);
+const MAX_LATENCY: Duration = Duration::from_millis(50);
+
+/// The intervals at which the database is flushed and the maintenance of the streams is run.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+const MAINTENANCE_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// The messages between the clients and the server.
+#[derive(Debug)]
+enum Message {
+    Request(Request),
+    Response(Result<Response, String>),
+}
+
+/// What the scheduler of a simulation runs.
+enum Step {
+    Deliver(Envelope<Message>),
+    Flush,
+    Maintenance,
+}
+
+impl From<Envelope<Message>> for Step {
+    fn from(envelope: Envelope<Message>) -> Step {
+        Step::Deliver(envelope)
+    }
+}
+
+/// A server and its clients connected by a simulated network, the time, the latency of
+/// the messages, the flushes of the database and the deliveries of the subscriptions are
+/// driven by a scheduler seeded with the seed of the simulation, a run with the same seed
+/// and the same requests is the same.
+///
+/// The events of the subscriptions are sent after each step of the scheduler, the
+/// subscriptions the simulated server can not poll, e.g. the ones to all the streams,
+/// are rejected.
+pub struct Simulation {
+    scheduler: Scheduler<Step>,
+    network: Network,
+    server: EmbeddedServer,
+    clients: Vec<EmbeddedClient>,
+    /// The responses received by each client, in the order they arrived.
+    received: HashMap<NodeId, Vec<Result<Response, String>>>,
+    // the database is removed once the server is dropped
+    _dir: TempDir,
+}
+
+impl Simulation {
+    /// Starts a simulation, the virtual time drives `Timestamp::now` of the calling thread
+    /// until the simulation is dropped, the simulation must be run on this thread.
+    pub fn new(seed: u64) -> Simulation {
+        let dir = TempDir::new().expect("error creating the database directory");
+        let server = EmbeddedServer::simulated(dir.path()).expect("error opening the database");
+
+        let mut scheduler = Scheduler::new(seed, START).drive_clock();
+        scheduler.schedule(FLUSH_INTERVAL, Step::Flush);
+        scheduler.schedule(MAINTENANCE_INTERVAL, Step::Maintenance);
+
+        Simulation {
+            scheduler,
+            network: Network::new(MIN_LATENCY, MAX_LATENCY),
+            server,
+            clients: Vec::new(),
+            received: HashMap::new(),
+            _dir: dir,
+        }
+    }
+
+    /// The virtual time of the simulation.
+    pub fn now(&self) -> Timestamp {
+        self.scheduler.now()
+    }
+
+    /// The network between the clients and the server, to partition or crash the nodes.
+    pub fn network(&mut self) -> &mut Network {
+        &mut self.network
+    }
+
+    /// Connects a new client to the server, returns its node.
+    pub fn add_client(&mut self) -> NodeId {
+        self.clients.push(self.server.connect());
+        self.clients.len()
+    }
+
+    /// Sends a request from the client to the server, returns `false` if it has been lost.
+    pub fn send(&mut self, client: NodeId, request: Request) -> bool {
+        let message = Message::Request(request);
+        self.network
+            .send(&mut self.scheduler, client, SERVER, message)
+    }
+
+    /// Runs the simulation for this duration of virtual time.
+    pub fn run_for(&mut self, duration: Duration) {
+        let deadline = Timestamp(self.now().0.saturating_add(duration.as_millis() as u64));
+        while let Some(step) = self.scheduler.step_until(deadline) {
+            self.run_step(step);
+            self.send_responses();
+        }
+    }
+
+    /// Returns the responses received by the client since the last call.
+    pub fn responses(&mut self, client: NodeId) -> Vec<Result<Response, String>> {
+        self.received.remove(&client).unwrap_or_default()
+    }
+
+    fn run_step(&mut self, step: Step) {
+        match step {
+            Step::Deliver(envelope) => match self.network.deliver(envelope) {
+                Some(Envelope {
+                    from,
+                    message: Message::Request(request),
+                    ..
+                }) => self.clients[from - 1].send(request),
+                Some(Envelope {
+                    to,
+                    message: Message::Response(response),
+                    ..
+                }) => self.received.entry(to).or_default().push(response),
+                None => (),
+            },
+            Step::Flush => {
+                self.server.flush().expect("error flushing the database");
+                self.scheduler.schedule(FLUSH_INTERVAL, Step::Flush);
+            }
+            Step::Maintenance => {
+                let maintenance = self.server.run_maintenance();
+                maintenance.expect("error running the maintenance of the streams");
+                self.scheduler
+                    .schedule(MAINTENANCE_INTERVAL, Step::Maintenance);
+            }
+        }
+    }
+
+    /// Sends the events of the subscriptions, then sends the responses of
+    /// the server to the clients over the network, client by client.
+    fn send_responses(&mut self) {
+        self.server.poll_subscriptions();
+
+        for (index, client) in self.clients.iter_mut().enumerate() {
+            while let Some(response) = client.try_recv() {
+                let message = Message::Response(response);
+                self.network
+                    .send(&mut self.scheduler, SERVER, index + 1, message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use meilies::stream::{EventData, EventName, GroupName, Stream, StreamName};
+
+    /// Publishes to the same stream from several clients, the numbers of the events
+    /// depend on the order the publications arrive in, which is drawn from the seed.
+    fn run(seed: u64) -> (Timestamp, Vec<Vec<Result<Response, String>>>) {
+        let mut simulation = Simulation::new(seed);
+        let clients: Vec<_> = (0..3).map(|_| simulation.add_client()).collect();
+
+        let stream: StreamName = "simulated".parse().unwrap();
+        let name: EventName = "published".parse().unwrap();
+        for _ in 0..5 {
+            for &client in &clients {
+                let data = EventData(client.to_string().into_bytes().into());
+                let events = vec![(name.clone(), data)];
+                let request = Request::PublishBatch {
+                    stream: stream.clone(),
+                    events,
+                };
+                simulation.send(client, request);
+            }
+            simulation.run_for(Duration::from_millis(20));
+        }
+        simulation.run_for(Duration::from_secs(2));
+
+        let responses = clients.iter().map(|&c| simulation.responses(c)).collect();
+        (simulation.now(), responses)
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        let (now, responses) = run(42);
+        assert_eq!(responses.iter().map(Vec::len).sum::<usize>(), 15);
+        assert!(responses.iter().flatten().all(Result::is_ok));
+
+        assert_eq!(run(42), (now, responses));
+    }
+
+    fn event_numbers(responses: &[Result<Response, String>]) -> Vec<u64> {
+        let numbers = responses.iter().filter_map(|response| match response {
+            Ok(Response::Event { number, .. }) => Some(number.0),
+            _otherwise => None,
+        });
+        numbers.collect()
+    }
+
+    /// Publishes while a client is subscribed to the stream and two consumers of a group
+    /// share it, the events each consumer receives depend on the order of the deliveries.
+    fn run_subscriptions(seed: u64) -> Vec<Vec<Result<Response, String>>> {
+        let mut simulation = Simulation::new(seed);
+        let publisher = simulation.add_client();
+        let subscriber = simulation.add_client();
+        let consumers = [simulation.add_client(), simulation.add_client()];
+
+        let stream: StreamName = "simulated".parse().unwrap();
+        let from_start: Stream = "simulated:0".parse().unwrap();
+        let request = Request::Subscribe {
+            streams: vec![from_start],
+        };
+        simulation.send(subscriber, request);
+
+        let group: GroupName = "readers".parse().unwrap();
+        for (index, &consumer) in consumers.iter().enumerate() {
+            let request = Request::SubscribeGroup {
+                group: group.clone(),
+                stream: stream.clone(),
+                consumer: format!("consumer-{}", index),
+            };
+            simulation.send(consumer, request);
+        }
+        simulation.run_for(Duration::from_millis(100));
+
+        let name: EventName = "published".parse().unwrap();
+        for _ in 0..10 {
+            let data = EventData(b"data".to_vec().into());
+            let request = Request::PublishBatch {
+                stream: stream.clone(),
+                events: vec![(name.clone(), data)],
+            };
+            simulation.send(publisher, request);
+            simulation.run_for(Duration::from_millis(10));
+        }
+        simulation.run_for(Duration::from_secs(1));
+
+        let clients = [publisher, subscriber, consumers[0], consumers[1]];
+        clients.iter().map(|&c| simulation.responses(c)).collect()
+    }
+
+    #[test]
+    fn same_seed_same_deliveries() {
+        let responses = run_subscriptions(7);
+
+        // the network reorders the messages, the events are compared once sorted
+        let published: Vec<_> = (0..10).collect();
+        let mut subscribed = event_numbers(&responses[1]);
+        subscribed.sort();
+        assert_eq!(subscribed, published);
+
+        let mut consumed = event_numbers(&responses[2]);
+        consumed.extend(event_numbers(&responses[3]));
+        consumed.sort();
+        assert_eq!(consumed, published);
+
+        assert_eq!(run_subscriptions(7), responses);
+    }
+}
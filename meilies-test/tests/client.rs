@@ -0,0 +1,93 @@
+use bytes::Bytes;
+use futures03::TryFutureExt;
+use meilies::stream::{EventData, EventName, EventNumber, Stream, StreamName};
+use meilies_client::{Error, ServerError};
+use meilies_test::TestServer;
+use tokio::runtime::current_thread::Runtime;
+
+fn stream(name: &str) -> StreamName {
+    name.parse().unwrap()
+}
+
+fn event_name() -> EventName {
+    "test-event".parse().unwrap()
+}
+
+fn data(data: &'static str) -> EventData {
+    EventData(Bytes::from(data))
+}
+
+#[test]
+fn blocking_client() {
+    let server = TestServer::start();
+    let mut client = server.connect_builder().blocking().unwrap();
+
+    let first = client.publish(stream("orders"), event_name(), data("first"));
+    let second = client.publish(stream("orders"), event_name(), data("second"));
+    assert_eq!(first.unwrap(), EventNumber(0));
+    assert_eq!(second.unwrap(), EventNumber(1));
+
+    let last = client.last_event_number(stream("orders")).unwrap();
+    assert_eq!(last, Some(EventNumber(1)));
+
+    let names = client.stream_names(Some(String::from("ord*"))).unwrap();
+    assert_eq!(names, vec![stream("orders")]);
+
+    let events = client.get_range(stream("orders"), None, None).unwrap();
+    let datas: Vec<_> = events.into_iter().map(|e| e.data).collect();
+    assert_eq!(datas, vec![data("first"), data("second")]);
+
+    match client.get_range(stream("unknown"), None, None) {
+        Err(Error::Server(ServerError::NoStream(_))) => (),
+        otherwise => panic!("unexpected result {:?}", otherwise),
+    }
+}
+
+#[test]
+fn subscription_range() {
+    let server = TestServer::start();
+    let mut client = server.connect_builder().blocking().unwrap();
+
+    for &payload in &["first", "second", "third"] {
+        client
+            .publish(stream("orders"), event_name(), data(payload))
+            .unwrap();
+    }
+
+    // the end of the range is excluded, the subscription ends once it is reached
+    let range = Stream::new_from_to(stream("orders"), Some(0), Some(2));
+    let events = client.subscribe_iter(range).unwrap();
+    let numbers: Vec<_> = events.map(|e| e.unwrap().number).collect();
+    assert_eq!(numbers, vec![EventNumber(0), EventNumber(1)]);
+}
+
+#[test]
+fn pool_clients() {
+    let server = TestServer::start();
+    let builder = server.connect_builder();
+
+    let checks = async move {
+        let pool = builder.pool(2).await?;
+        assert_eq!(pool.size(), 2);
+
+        // the second checkout takes the free client while the first one is checked out
+        let mut first = pool.get().await?;
+        let mut second = pool.get().await?;
+        first
+            .publish(stream("orders"), event_name(), data("first"))
+            .await?;
+        second
+            .publish(stream("orders"), event_name(), data("second"))
+            .await?;
+        drop((first, second));
+
+        assert_eq!(pool.check_health().await, 2);
+
+        let mut client = pool.get().await?;
+        client.last_event_number(stream("orders")).await
+    };
+
+    let mut runtime = Runtime::new().unwrap();
+    let last = runtime.block_on(Box::pin(checks).compat()).unwrap();
+    assert_eq!(last, Some(EventNumber(1)));
+}
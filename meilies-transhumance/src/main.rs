@@ -5,7 +5,7 @@ use futures::{future, Future, Stream};
 use log::{error, info};
 use meilies::reqresp::Response;
 use meilies::stream::Stream as EsStream;
-use meilies_client::{paired_connect, sub_connect};
+use meilies_client::compat::{paired_connect, sub_connect};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -87,11 +87,16 @@ fn main() {
                                 number,
                                 event_name,
                                 event_data,
+                                headers,
+                                ..
                             }) => {
                                 info!("{:?} {:?} {:?}", stream, event_name, number);
                                 Either::A(
                                     dst_conn
-                                        .publish(stream, event_name, event_data)
+                                        .publish_with_headers(
+                                            stream, event_name, event_data, headers,
+                                        )
+                                        .map(|(_number, dst_conn)| dst_conn)
                                         .map_err(|e| error!("{}", e)),
                                 )
                             }
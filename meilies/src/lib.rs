@@ -1,3 +1,6 @@
 pub mod reqresp;
 pub mod resp;
+#[cfg(any(test, feature = "simulation"))]
+pub mod sim;
+pub mod storage;
 pub mod stream;
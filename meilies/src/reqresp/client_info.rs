@@ -0,0 +1,114 @@
+use std::fmt;
+
+use crate::resp::{FromResp, RespValue};
+use crate::stream::StreamName;
+
+/// A client connected to the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub id: u64,
+    pub addr: String,
+    /// The user the client is authenticated as, if any.
+    pub user: Option<String>,
+    pub subscriptions: Vec<StreamName>,
+    /// The number of milliseconds elapsed since the last command of the client.
+    pub idle: u64,
+}
+
+impl From<ClientInfo> for RespValue {
+    fn from(client: ClientInfo) -> RespValue {
+        let subscriptions = client
+            .subscriptions
+            .into_iter()
+            .map(|stream| RespValue::bulk_string(stream.into_bytes()))
+            .collect();
+
+        RespValue::Array(vec![
+            RespValue::Integer(client.id as i64),
+            RespValue::bulk_string(client.addr),
+            client.user.map_or(RespValue::Nil, RespValue::bulk_string),
+            RespValue::Array(subscriptions),
+            RespValue::Integer(client.idle as i64),
+        ])
+    }
+}
+
+#[derive(Debug)]
+pub enum RespClientInfoConvertError {
+    InvalidRespType,
+    InvalidArgumentRespType,
+    MissingArgument,
+    TooManyArguments,
+}
+
+impl fmt::Display for RespClientInfoConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use RespClientInfoConvertError::*;
+        match self {
+            InvalidRespType => write!(f, "invalid RESP type found, expected Array"),
+            InvalidArgumentRespType => write!(f, "Invalid argument resp type"),
+            MissingArgument => write!(f, "Missing argument"),
+            TooManyArguments => write!(f, "Too many arguments"),
+        }
+    }
+}
+
+fn integer_from_resp(value: RespValue) -> Result<u64, RespClientInfoConvertError> {
+    match value {
+        RespValue::Integer(integer) if integer >= 0 => Ok(integer as u64),
+        _otherwise => Err(RespClientInfoConvertError::InvalidArgumentRespType),
+    }
+}
+
+impl FromResp for ClientInfo {
+    type Error = RespClientInfoConvertError;
+
+    fn from_resp(value: RespValue) -> Result<Self, Self::Error> {
+        use RespClientInfoConvertError::*;
+
+        let mut iter = match value {
+            RespValue::Array(array) => array.into_iter(),
+            _otherwise => return Err(InvalidRespType),
+        };
+
+        let id = iter
+            .next()
+            .map(integer_from_resp)
+            .ok_or(MissingArgument)??;
+
+        let addr = iter
+            .next()
+            .map(String::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        let user = iter
+            .next()
+            .map(FromResp::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        let subscriptions = iter
+            .next()
+            .map(Vec::<StreamName>::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        let idle = iter
+            .next()
+            .map(integer_from_resp)
+            .ok_or(MissingArgument)??;
+
+        if iter.next().is_some() {
+            return Err(TooManyArguments);
+        }
+
+        Ok(ClientInfo {
+            id,
+            addr,
+            user,
+            subscriptions,
+            idle,
+        })
+    }
+}
@@ -8,17 +8,27 @@ use super::{Request, RespRequestConvertError, RespResponseConvertError, Response
 use crate::resp::{FromResp, RespCodec, RespMsgError, RespValue};
 
 #[derive(Debug, Default)]
-pub struct ClientCodec;
+pub struct ClientCodec {
+    frame_size: Option<usize>,
+}
 
 impl Decoder for ClientCodec {
     type Item = Result<Response, String>;
     type Error = ResponseMsgError;
 
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match RespCodec.decode(buf)? {
-            Some(value) => Ok(Some(FromResp::from_resp(value)?)),
-            None => Ok(None),
+        // the server only compresses its responses once asked to
+        let response = match RespCodec.decode_compressed(buf)? {
+            Some(value) => Result::<Response, String>::from_resp(value)?,
+            None => return Ok(None),
+        };
+
+        // the requests that follow the agreement are sent in chunks too
+        if let Ok(Response::FrameSize { size }) = &response {
+            self.frame_size = Some(*size as usize);
         }
+
+        Ok(Some(response))
     }
 }
 
@@ -27,19 +37,40 @@ impl Encoder for ClientCodec {
     type Error = RequestMsgError;
 
     fn encode(&mut self, msg: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
-        Ok(RespCodec.encode(msg.into(), buf)?)
+        match self.frame_size {
+            Some(frame_size) => Ok(RespCodec.encode_chunked(msg.into(), buf, frame_size)?),
+            None => Ok(RespCodec.encode(msg.into(), buf)?),
+        }
     }
 }
 
-#[derive(Debug, Default)]
-pub struct ServerCodec;
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ServerCodec {
+    max_event_size: Option<usize>,
+    compression_threshold: Option<usize>,
+    frame_size: Option<usize>,
+    resp3: bool,
+}
+
+impl ServerCodec {
+    /// Creates a codec that rejects the requests containing an event data, or
+    /// any other bulk string, larger than `max_event_size` bytes without buffering it.
+    pub fn with_max_event_size(max_event_size: usize) -> ServerCodec {
+        ServerCodec {
+            max_event_size: Some(max_event_size),
+            compression_threshold: None,
+            frame_size: None,
+            resp3: false,
+        }
+    }
+}
 
 impl Decoder for ServerCodec {
     type Item = Request;
     type Error = RequestMsgError;
 
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match RespCodec.decode(buf)? {
+        match RespCodec.decode_command(buf, self.max_event_size)? {
             Some(value) => Ok(Some(FromResp::from_resp(value)?)),
             None => Ok(None),
         }
@@ -51,12 +82,54 @@ impl Encoder for ServerCodec {
     type Error = ResponseMsgError;
 
     fn encode(&mut self, msg: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        let compression = match &msg {
+            Ok(Response::Compression { threshold }) => Some(*threshold as usize),
+            _otherwise => None,
+        };
+
+        let frame_size = match &msg {
+            Ok(Response::FrameSize { size }) => Some(*size as usize),
+            _otherwise => None,
+        };
+
+        // the reply to the hello command is sent using the new version of the protocol
+        if let Ok(Response::Hello { proto, .. }) = &msg {
+            self.resp3 = *proto == 3;
+        }
+
+        let push = match &msg {
+            Ok(response) => response.is_push(),
+            Err(_) => false,
+        };
+
         let msg = match msg {
             Ok(item) => item.into(),
             Err(error) => RespValue::Error(error),
         };
 
-        Ok(RespCodec.encode(msg, buf)?)
+        let msg = match msg {
+            RespValue::Array(values) if self.resp3 && push => RespValue::Push(values),
+            msg if self.resp3 => msg,
+            msg => msg.into_resp2(),
+        };
+
+        // a compressed response is a single frame that is never chunked
+        match (self.compression_threshold, self.frame_size) {
+            (Some(threshold), _) => RespCodec.encode_compressed(msg, buf, threshold)?,
+            (None, Some(frame_size)) => RespCodec.encode_chunked(msg, buf, frame_size)?,
+            (None, None) => RespCodec.encode(msg, buf)?,
+        }
+
+        // the response that enables the compression is not compressed itself
+        if compression.is_some() {
+            self.compression_threshold = compression;
+        }
+
+        if frame_size.is_some() {
+            self.frame_size = frame_size;
+        }
+
+        Ok(())
     }
 }
 
@@ -0,0 +1,112 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The code that prefixes the errors sent by the server, the clients can rely
+/// on it to know the kind of an error, the message that follows is free text.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// A generic error, e.g. an invalid argument or a storage failure.
+    Err,
+    /// The connection must be authenticated to send this command.
+    NoAuth,
+    /// The username or the password given to authenticate is invalid.
+    WrongPass,
+    /// The authenticated user is not allowed to send this command.
+    NoPerm,
+    /// The version of the protocol asked is not supported.
+    NoProto,
+    /// The stream is not at the version expected by the publication.
+    WrongVer,
+    /// The stream has been deleted permanently.
+    Tombstoned,
+    /// The stream does not exist, it has never been created nor published to.
+    NoStream,
+    /// The server does not accept writes.
+    ReadOnly,
+    /// The events have been published but not acknowledged by enough replicas.
+    NotReplicated,
+    /// The server is not the leader of its cluster, followed by the address of the leader.
+    Moved,
+    /// The cluster has no leader.
+    ClusterDown,
+    /// The publication exceeds a rate limit, followed by the milliseconds to wait.
+    Throttled,
+    /// The free space of the data directory is too low, the server does not accept writes.
+    DiskFull,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::Err => "ERR",
+            ErrorCode::NoAuth => "NOAUTH",
+            ErrorCode::WrongPass => "WRONGPASS",
+            ErrorCode::NoPerm => "NOPERM",
+            ErrorCode::NoProto => "NOPROTO",
+            ErrorCode::WrongVer => "WRONGVER",
+            ErrorCode::Tombstoned => "TOMBSTONED",
+            ErrorCode::NoStream => "NOSTREAM",
+            ErrorCode::ReadOnly => "READONLY",
+            ErrorCode::NotReplicated => "NOTREPLICATED",
+            ErrorCode::Moved => "MOVED",
+            ErrorCode::ClusterDown => "CLUSTERDOWN",
+            ErrorCode::Throttled => "THROTTLED",
+            ErrorCode::DiskFull => "DISKFULL",
+        }
+    }
+
+    /// Returns the error to send, the message prefixed by the code.
+    pub fn error(self, message: impl fmt::Display) -> String {
+        format!("{} {}", self, message)
+    }
+
+    /// Splits an error received into its code and its message,
+    /// returns `None` if the error does not start with a known code.
+    pub fn split(error: &str) -> Option<(ErrorCode, &str)> {
+        let (code, message) = match error.find(' ') {
+            Some(index) => (&error[..index], &error[index + 1..]),
+            None => (error, ""),
+        };
+        let code = code.parse().ok()?;
+        Some((code, message))
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownErrorCode(pub String);
+
+impl fmt::Display for UnknownErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown error code {:?}", self.0)
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = UnknownErrorCode;
+
+    fn from_str(s: &str) -> Result<ErrorCode, UnknownErrorCode> {
+        match s {
+            "ERR" => Ok(ErrorCode::Err),
+            "NOAUTH" => Ok(ErrorCode::NoAuth),
+            "WRONGPASS" => Ok(ErrorCode::WrongPass),
+            "NOPERM" => Ok(ErrorCode::NoPerm),
+            "NOPROTO" => Ok(ErrorCode::NoProto),
+            "WRONGVER" => Ok(ErrorCode::WrongVer),
+            "TOMBSTONED" => Ok(ErrorCode::Tombstoned),
+            "NOSTREAM" => Ok(ErrorCode::NoStream),
+            "READONLY" => Ok(ErrorCode::ReadOnly),
+            "NOTREPLICATED" => Ok(ErrorCode::NotReplicated),
+            "MOVED" => Ok(ErrorCode::Moved),
+            "CLUSTERDOWN" => Ok(ErrorCode::ClusterDown),
+            "THROTTLED" => Ok(ErrorCode::Throttled),
+            "DISKFULL" => Ok(ErrorCode::DiskFull),
+            _otherwise => Err(UnknownErrorCode(s.to_owned())),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+
+use crate::resp::{FromResp, RespValue};
+
+/// The latency distribution of a command or an internal operation of the server,
+/// the percentiles are estimated from the buckets of its histogram.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// The name of the command or of the operation, e.g. `publish` or `append`.
+    pub name: String,
+    /// The number of durations recorded.
+    pub count: u64,
+    /// The percentiles, in microseconds.
+    pub p50: u64,
+    pub p99: u64,
+    pub p999: u64,
+}
+
+impl From<LatencyStats> for RespValue {
+    fn from(stats: LatencyStats) -> RespValue {
+        RespValue::Array(vec![
+            RespValue::bulk_string(stats.name),
+            RespValue::Integer(stats.count as i64),
+            RespValue::Integer(stats.p50 as i64),
+            RespValue::Integer(stats.p99 as i64),
+            RespValue::Integer(stats.p999 as i64),
+        ])
+    }
+}
+
+#[derive(Debug)]
+pub enum RespLatencyStatsConvertError {
+    InvalidRespType,
+    InvalidArgumentRespType,
+    MissingArgument,
+    TooManyArguments,
+}
+
+impl fmt::Display for RespLatencyStatsConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use RespLatencyStatsConvertError::*;
+        match self {
+            InvalidRespType => write!(f, "invalid RESP type found, expected Array"),
+            InvalidArgumentRespType => write!(f, "Invalid argument resp type"),
+            MissingArgument => write!(f, "Missing argument"),
+            TooManyArguments => write!(f, "Too many arguments"),
+        }
+    }
+}
+
+fn integer_from_resp(value: RespValue) -> Result<u64, RespLatencyStatsConvertError> {
+    match value {
+        RespValue::Integer(integer) if integer >= 0 => Ok(integer as u64),
+        _otherwise => Err(RespLatencyStatsConvertError::InvalidArgumentRespType),
+    }
+}
+
+impl FromResp for LatencyStats {
+    type Error = RespLatencyStatsConvertError;
+
+    fn from_resp(value: RespValue) -> Result<Self, Self::Error> {
+        use RespLatencyStatsConvertError::*;
+
+        let mut iter = match value {
+            RespValue::Array(array) => array.into_iter(),
+            _otherwise => return Err(InvalidRespType),
+        };
+
+        let name = iter
+            .next()
+            .map(String::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        let count = iter
+            .next()
+            .map(integer_from_resp)
+            .ok_or(MissingArgument)??;
+
+        let p50 = iter
+            .next()
+            .map(integer_from_resp)
+            .ok_or(MissingArgument)??;
+
+        let p99 = iter
+            .next()
+            .map(integer_from_resp)
+            .ok_or(MissingArgument)??;
+
+        let p999 = iter
+            .next()
+            .map(integer_from_resp)
+            .ok_or(MissingArgument)??;
+
+        if iter.next().is_some() {
+            return Err(TooManyArguments);
+        }
+
+        Ok(LatencyStats {
+            name,
+            count,
+            p50,
+            p99,
+            p999,
+        })
+    }
+}
@@ -1,7 +1,18 @@
+mod client_info;
+#[cfg(feature = "tokio")]
 mod codec;
+mod error_code;
+mod latency_stats;
 mod request;
 mod response;
+mod slow_log_entry;
 
+pub use self::client_info::{ClientInfo, RespClientInfoConvertError};
+#[cfg(feature = "tokio")]
 pub use self::codec::{ClientCodec, RequestMsgError, ResponseMsgError, ServerCodec};
-pub use self::request::{Request, RespRequestConvertError};
+pub use self::error_code::{ErrorCode, UnknownErrorCode};
+pub use self::latency_stats::{LatencyStats, RespLatencyStatsConvertError};
+pub use self::request::{AckLevel, ExpectedVersion, Request, RespRequestConvertError};
+pub use self::request::{EMPTY_STREAM, RANGE_END, RANGE_START};
 pub use self::response::{RespResponseConvertError, Response};
+pub use self::slow_log_entry::{RespSlowLogEntryConvertError, SlowLogEntry};
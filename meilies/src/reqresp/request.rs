@@ -1,33 +1,453 @@
 use crate::resp::{FromResp, RespValue};
 use crate::stream::ALL_STREAMS;
-use crate::stream::{EventData, EventName, ReadRange, Stream, StreamName};
+use crate::stream::{Batch, ReadRange, SlowConsumer, Stream, StreamName, Timestamp};
+use crate::stream::{EventData, EventHeaders, EventName, EventNumber, GroupName};
 use std::fmt;
+use std::str::FromStr;
+
+/// The bound used to specify an open range start in the `get-range` command.
+pub const RANGE_START: &str = "-";
+
+/// The bound used to specify an open range end in the `get-range` command.
+pub const RANGE_END: &str = "+";
+
+/// The version used to specify that a stream must not contain any event in the `publish` command.
+pub const EMPTY_STREAM: &str = "empty";
+
+/// The `publish` command option that specifies the expected version of the stream.
+const VERSION_OPTION: &str = "version";
+
+/// The `publish` command option that specifies the deduplication ID of the event.
+const DEDUP_OPTION: &str = "dedup";
+
+/// The `publish` command option that adds a header to the event, followed by its key and value.
+const HEADER_OPTION: &str = "header";
+
+/// The `publish` command option that specifies the time at which the event must be appended.
+const DELIVER_AT_OPTION: &str = "deliver-at";
+
+/// The `publish` command option that specifies the acknowledgement level of the publication.
+const ACK_OPTION: &str = "ack";
+
+/// The `delete-stream` command option that deletes the stream permanently.
+const HARD_OPTION: &str = "hard";
+
+/// The `slowlog` command option that clears the slow log.
+const RESET_OPTION: &str = "reset";
+
+/// The `replica-of` command argument that stops the replication.
+const NO_ONE: &str = "no-one";
+
+/// The `read-only` command arguments that enable and disable the read-only mode.
+const ON: &str = "on";
+const OFF: &str = "off";
+
+/// The version a stream must be at for an event to be published to it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExpectedVersion {
+    /// The stream must not contain any event.
+    Empty,
+    /// The last event of the stream must have this number.
+    Number(EventNumber),
+}
+
+impl ExpectedVersion {
+    /// Returns `true` if a stream with the given last event number is at this version.
+    pub fn matches(self, last: Option<EventNumber>) -> bool {
+        match (self, last) {
+            (ExpectedVersion::Empty, None) => true,
+            (ExpectedVersion::Number(number), Some(last)) => number == last,
+            (_, _) => false,
+        }
+    }
+}
+
+impl fmt::Display for ExpectedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpectedVersion::Empty => f.write_str(EMPTY_STREAM),
+            ExpectedVersion::Number(number) => write!(f, "{}", number.0),
+        }
+    }
+}
+
+/// The servers that must have received an event before its publication is acknowledged.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AckLevel {
+    /// The event has been written by the server the client is connected to.
+    Local,
+    /// The event has also been replicated by at least one replica.
+    OneReplica,
+    /// The event has been replicated by a majority of the primary and its connected replicas.
+    Quorum,
+}
+
+impl Default for AckLevel {
+    fn default() -> AckLevel {
+        AckLevel::Local
+    }
+}
+
+impl fmt::Display for AckLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AckLevel::Local => f.write_str("local"),
+            AckLevel::OneReplica => f.write_str("one-replica"),
+            AckLevel::Quorum => f.write_str("quorum"),
+        }
+    }
+}
+
+impl FromStr for AckLevel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<AckLevel, ()> {
+        match s {
+            "local" => Ok(AckLevel::Local),
+            "one-replica" => Ok(AckLevel::OneReplica),
+            "quorum" => Ok(AckLevel::Quorum),
+            _otherwise => Err(()),
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Request {
     SubscribeAll {
         range: ReadRange,
+        event_names: Option<Vec<EventName>>,
+        slow_consumer: SlowConsumer,
+        credits: Option<u64>,
+        batch: Option<Batch>,
     },
     Subscribe {
         streams: Vec<Stream>,
     },
+    Unsubscribe {
+        streams: Vec<StreamName>,
+    },
     Publish {
         stream: StreamName,
         event_name: EventName,
         event_data: EventData,
+        expected_version: Option<ExpectedVersion>,
+        /// An ID identifying the event, an event published again with the
+        /// same ID is not appended and the number of the original one is returned.
+        dedup_id: Option<String>,
+        headers: EventHeaders,
+        /// The time at which the event is appended to the stream,
+        /// it is kept aside by the server until then.
+        deliver_at: Option<Timestamp>,
+        /// Overrides the acknowledgement level of the connection for this publication.
+        ack: Option<AckLevel>,
+    },
+    PublishBatch {
+        stream: StreamName,
+        events: Vec<(EventName, EventData)>,
+    },
+    PublishMulti {
+        writes: Vec<(StreamName, EventName, EventData)>,
     },
     LastEventNumber {
         stream: StreamName,
     },
-    StreamNames,
+    CreateStream {
+        stream: StreamName,
+    },
+    DeleteStream {
+        stream: StreamName,
+        /// A stream deleted permanently can not be recreated, otherwise the
+        /// events published after the deletion continue its numbering.
+        hard: bool,
+    },
+    TrimStream {
+        stream: StreamName,
+        up_to: u64,
+    },
+    StreamNames {
+        pattern: Option<String>,
+    },
+    GetRange {
+        stream: StreamName,
+        from: Option<EventNumber>,
+        to: Option<EventNumber>,
+    },
+    Ping {
+        payload: Option<Vec<u8>>,
+    },
+    Auth {
+        username: Option<String>,
+        password: String,
+    },
+    SubscribeGroup {
+        group: GroupName,
+        stream: StreamName,
+        consumer: String,
+    },
+    Ack {
+        group: GroupName,
+        stream: StreamName,
+        event_number: EventNumber,
+    },
+    /// Gives credits to the flow controlled subscription to a stream,
+    /// the server sends one event of the subscription per credit.
+    Flow {
+        stream: StreamName,
+        credits: u64,
+    },
+    CommitOffset {
+        group: GroupName,
+        stream: StreamName,
+        event_number: EventNumber,
+    },
+    FetchOffset {
+        group: GroupName,
+        stream: StreamName,
+    },
+    Pending {
+        group: GroupName,
+        stream: StreamName,
+    },
+    Claim {
+        group: GroupName,
+        stream: StreamName,
+        event_number: EventNumber,
+        new_consumer: String,
+    },
+    SetStreamMeta {
+        stream: StreamName,
+        key: String,
+        value: String,
+    },
+    GetStreamMeta {
+        stream: StreamName,
+    },
+    StreamInfo {
+        stream: StreamName,
+    },
+    /// Checks the checksums of all the events kept in the stream.
+    Verify {
+        stream: StreamName,
+    },
+    /// Compacts the streams for which it is enabled and lets
+    /// the database reclaim the space of the events removed.
+    Compact,
+    /// Writes a snapshot of all the streams to an archive file on the server.
+    Backup {
+        path: String,
+    },
+    /// Makes the server replicate the events of the primary at this address,
+    /// `None` stops the replication and makes the server writable again.
+    ReplicaOf {
+        primary: Option<String>,
+    },
+    ReplicationInfo,
+    /// Sets the acknowledgement level of the publications of the connection.
+    SetAckLevel {
+        level: AckLevel,
+    },
+    /// Sent by a replica to its primary once it has replicated
+    /// all the events up to this global event number.
+    ReplicaAck {
+        global: EventNumber,
+    },
+    /// Sent by a cluster node to the other ones to become the leader of the term.
+    RequestVote {
+        term: u64,
+        /// The address of the node asking for votes.
+        candidate: String,
+        /// The global number of the last event of the candidate.
+        last_global: Option<EventNumber>,
+    },
+    /// Sent by the leader of a cluster to the other nodes to maintain its leadership.
+    Heartbeat {
+        term: u64,
+        leader: String,
+    },
+    /// Makes the server reject the publications and the administration
+    /// commands that modify the streams, the subscriptions are still served.
+    ReadOnly {
+        enabled: bool,
+    },
+    /// Requests the statistics of the server.
+    Info,
+    /// Lists the clients connected to the server.
+    ClientList,
+    /// Closes the connection of a client, its subscriptions are stopped.
+    ClientKill {
+        id: u64,
+    },
+    /// Changes a configuration parameter of the running server, e.g. `log-level`.
+    ConfigSet {
+        key: String,
+        value: String,
+    },
+    /// Returns the commands and the deliveries that exceeded the latency thresholds
+    /// of the slow log, the most recent first, or clears the slow log if `reset`.
+    SlowLog {
+        reset: bool,
+    },
+    /// Returns the latency percentiles of the commands and of the appends,
+    /// the catch-up reads and the deliveries of the events.
+    Latency,
+    /// Turns the connection into a stream of every command processed by the server.
+    Monitor,
+    /// Asks the server to compress the responses larger than `threshold` bytes once encoded,
+    /// the server starts compressing after answering with a `Compression` response.
+    Compress {
+        threshold: u64,
+    },
+    /// Returns the version and the features of the server, and switches the connection
+    /// to the RESP2 or RESP3 protocol if one is given. In RESP3 the events of the
+    /// subscriptions are sent as push messages that can not be mistaken for replies.
+    Hello {
+        proto: Option<u64>,
+    },
+    /// Asks the peers to send the bulk strings larger than `size` bytes in chunks of at
+    /// most `size` bytes, the server starts chunking after answering with a `FrameSize`
+    /// response and the client after receiving it.
+    FrameSize {
+        size: u64,
+    },
+}
+
+impl Request {
+    /// Returns the name of the command as sent on the wire.
+    pub fn command_name(&self) -> &'static str {
+        match self {
+            Request::SubscribeAll { .. } | Request::Subscribe { .. } => "subscribe",
+            Request::Unsubscribe { .. } => "unsubscribe",
+            Request::Publish { .. } => "publish",
+            Request::PublishBatch { .. } => "publish-batch",
+            Request::PublishMulti { .. } => "publish-multi",
+            Request::LastEventNumber { .. } => "last-event-number",
+            Request::CreateStream { .. } => "create-stream",
+            Request::DeleteStream { .. } => "delete-stream",
+            Request::TrimStream { .. } => "trim-stream",
+            Request::StreamNames { .. } => "stream-names",
+            Request::GetRange { .. } => "get-range",
+            Request::Ping { .. } => "ping",
+            Request::Auth { .. } => "auth",
+            Request::SubscribeGroup { .. } => "subscribe-group",
+            Request::Ack { .. } => "ack",
+            Request::Flow { .. } => "flow",
+            Request::CommitOffset { .. } => "commit-offset",
+            Request::FetchOffset { .. } => "fetch-offset",
+            Request::Pending { .. } => "pending",
+            Request::Claim { .. } => "claim",
+            Request::SetStreamMeta { .. } => "set-stream-meta",
+            Request::GetStreamMeta { .. } => "get-stream-meta",
+            Request::StreamInfo { .. } => "stream-info",
+            Request::Verify { .. } => "verify",
+            Request::Compact => "compact",
+            Request::Backup { .. } => "backup",
+            Request::ReplicaOf { .. } => "replica-of",
+            Request::ReplicationInfo => "replication-info",
+            Request::SetAckLevel { .. } => "ack-level",
+            Request::ReplicaAck { .. } => "replica-ack",
+            Request::RequestVote { .. } => "request-vote",
+            Request::Heartbeat { .. } => "heartbeat",
+            Request::ReadOnly { .. } => "read-only",
+            Request::Info => "info",
+            Request::ClientList => "client-list",
+            Request::ClientKill { .. } => "client-kill",
+            Request::ConfigSet { .. } => "config-set",
+            Request::SlowLog { .. } => "slowlog",
+            Request::Latency => "latency",
+            Request::Monitor => "monitor",
+            Request::Compress { .. } => "compress",
+            Request::Hello { .. } => "hello",
+            Request::FrameSize { .. } => "frame-size",
+        }
+    }
+}
+
+fn range_bound(bound: Option<EventNumber>, open: &str) -> RespValue {
+    match bound {
+        Some(number) => RespValue::bulk_string(number.0.to_string()),
+        None => RespValue::bulk_string(open),
+    }
+}
+
+fn number_from_resp(value: RespValue) -> Result<u64, RespRequestConvertError> {
+    use RespRequestConvertError::*;
+
+    match value {
+        RespValue::Integer(integer) if integer < 0 => Err(InvalidArgumentRespType),
+        RespValue::Integer(integer) => Ok(integer as u64),
+        value => {
+            let string = String::from_resp(value).map_err(|_| InvalidArgumentRespType)?;
+            string.parse().map_err(|_| InvalidArgumentRespType)
+        }
+    }
+}
+
+fn expected_version_from_resp(
+    value: RespValue,
+) -> Result<ExpectedVersion, RespRequestConvertError> {
+    use RespRequestConvertError::*;
+
+    if let RespValue::Integer(_) = value {
+        return number_from_resp(value).map(|n| ExpectedVersion::Number(EventNumber(n)));
+    }
+
+    let string = String::from_resp(value).map_err(|_| InvalidArgumentRespType)?;
+    if string == EMPTY_STREAM {
+        return Ok(ExpectedVersion::Empty);
+    }
+
+    let number = string.parse().map_err(|_| InvalidArgumentRespType)?;
+    Ok(ExpectedVersion::Number(EventNumber(number)))
+}
+
+fn timestamp_from_resp(value: RespValue) -> Result<Timestamp, RespRequestConvertError> {
+    use RespRequestConvertError::*;
+
+    if let RespValue::Integer(_) = value {
+        return number_from_resp(value).map(Timestamp);
+    }
+
+    let string = String::from_resp(value).map_err(|_| InvalidArgumentRespType)?;
+    Timestamp::from_str(&string).map_err(|_| InvalidArgumentRespType)
+}
+
+fn ack_level_from_resp(value: RespValue) -> Result<AckLevel, RespRequestConvertError> {
+    use RespRequestConvertError::*;
+
+    let string = String::from_resp(value).map_err(|_| InvalidArgumentRespType)?;
+    AckLevel::from_str(&string).map_err(|()| InvalidArgumentRespType)
+}
+
+fn range_bound_from_resp(
+    value: RespValue,
+    open: &str,
+) -> Result<Option<EventNumber>, RespRequestConvertError> {
+    if value == open {
+        return Ok(None);
+    }
+
+    number_from_resp(value).map(|n| Some(EventNumber(n)))
 }
 
 impl Into<RespValue> for Request {
     fn into(self) -> RespValue {
         match self {
-            Request::SubscribeAll { range } => {
+            Request::SubscribeAll {
+                range,
+                event_names,
+                slow_consumer,
+                credits,
+                batch,
+            } => {
                 let command = RespValue::bulk_string(&"subscribe"[..]);
-                let all = Stream::all(range).into();
+                let all = Stream {
+                    event_names,
+                    slow_consumer,
+                    credits,
+                    batch,
+                    ..Stream::all(range)
+                };
+                let all = all.into();
                 RespValue::Array(vec![command, all])
             }
             Request::Subscribe { streams } => {
@@ -36,23 +456,281 @@ impl Into<RespValue> for Request {
                 let args = Some(command).into_iter().chain(streams).collect();
                 RespValue::Array(args)
             }
+            Request::Unsubscribe { streams } => {
+                let command = RespValue::bulk_string("unsubscribe");
+                let streams = streams
+                    .into_iter()
+                    .map(|s| RespValue::bulk_string(s.into_bytes()));
+                let args = Some(command).into_iter().chain(streams).collect();
+                RespValue::Array(args)
+            }
             Request::Publish {
                 stream,
                 event_name,
                 event_data,
+                expected_version,
+                dedup_id,
+                headers,
+                deliver_at,
+                ack,
+            } => {
+                let mut args = vec![
+                    RespValue::bulk_string(&"publish"[..]),
+                    RespValue::bulk_string(stream.to_string()),
+                    RespValue::bulk_string(event_name.to_string()),
+                    RespValue::bulk_string(event_data.0),
+                ];
+                if let Some(version) = expected_version {
+                    args.push(RespValue::bulk_string(VERSION_OPTION));
+                    args.push(RespValue::bulk_string(version.to_string()));
+                }
+                if let Some(dedup_id) = dedup_id {
+                    args.push(RespValue::bulk_string(DEDUP_OPTION));
+                    args.push(RespValue::bulk_string(dedup_id));
+                }
+                for (key, value) in headers.0 {
+                    args.push(RespValue::bulk_string(HEADER_OPTION));
+                    args.push(RespValue::bulk_string(key));
+                    args.push(RespValue::bulk_string(value));
+                }
+                if let Some(deliver_at) = deliver_at {
+                    args.push(RespValue::bulk_string(DELIVER_AT_OPTION));
+                    args.push(RespValue::bulk_string(deliver_at.to_string()));
+                }
+                if let Some(ack) = ack {
+                    args.push(RespValue::bulk_string(ACK_OPTION));
+                    args.push(RespValue::bulk_string(ack.to_string()));
+                }
+                RespValue::Array(args)
+            }
+            Request::PublishBatch { stream, events } => {
+                let mut args = Vec::with_capacity(2 + events.len() * 2);
+                args.push(RespValue::bulk_string("publish-batch"));
+                args.push(RespValue::bulk_string(stream.to_string()));
+                for (event_name, event_data) in events {
+                    args.push(RespValue::bulk_string(event_name.to_string()));
+                    args.push(RespValue::bulk_string(event_data.0));
+                }
+                RespValue::Array(args)
+            }
+            Request::PublishMulti { writes } => {
+                let mut args = Vec::with_capacity(1 + writes.len() * 3);
+                args.push(RespValue::bulk_string("publish-multi"));
+                for (stream, event_name, event_data) in writes {
+                    args.push(RespValue::bulk_string(stream.to_string()));
+                    args.push(RespValue::bulk_string(event_name.to_string()));
+                    args.push(RespValue::bulk_string(event_data.0));
+                }
+                RespValue::Array(args)
+            }
+            Request::LastEventNumber { stream } => RespValue::Array(vec![
+                RespValue::bulk_string(&"last-event-number"[..]),
+                RespValue::bulk_string(stream.to_string()),
+            ]),
+            Request::CreateStream { stream } => RespValue::Array(vec![
+                RespValue::bulk_string("create-stream"),
+                RespValue::bulk_string(stream.to_string()),
+            ]),
+            Request::DeleteStream { stream, hard } => {
+                let mut args = vec![
+                    RespValue::bulk_string("delete-stream"),
+                    RespValue::bulk_string(stream.to_string()),
+                ];
+                if hard {
+                    args.push(RespValue::bulk_string(HARD_OPTION));
+                }
+                RespValue::Array(args)
+            }
+            Request::TrimStream { stream, up_to } => RespValue::Array(vec![
+                RespValue::bulk_string("trim-stream"),
+                RespValue::bulk_string(stream.to_string()),
+                RespValue::bulk_string(up_to.to_string()),
+            ]),
+            Request::StreamNames { pattern } => {
+                let command = RespValue::bulk_string(&"stream-names"[..]);
+                let pattern = pattern.map(RespValue::bulk_string);
+                let args = Some(command).into_iter().chain(pattern).collect();
+                RespValue::Array(args)
+            }
+            Request::Ping { payload } => {
+                let command = RespValue::bulk_string("ping");
+                let payload = payload.map(RespValue::bulk_string);
+                let args = Some(command).into_iter().chain(payload).collect();
+                RespValue::Array(args)
+            }
+            Request::Auth { username, password } => {
+                let command = RespValue::bulk_string("auth");
+                let username = username.map(RespValue::bulk_string);
+                let password = RespValue::bulk_string(password);
+                let args = Some(command)
+                    .into_iter()
+                    .chain(username)
+                    .chain(Some(password))
+                    .collect();
+                RespValue::Array(args)
+            }
+            Request::SubscribeGroup {
+                group,
+                stream,
+                consumer,
             } => RespValue::Array(vec![
-                RespValue::bulk_string(&"publish"[..]),
+                RespValue::bulk_string("subscribe-group"),
+                RespValue::bulk_string(group.to_string()),
                 RespValue::bulk_string(stream.to_string()),
-                RespValue::bulk_string(event_name.to_string()),
-                RespValue::bulk_string(event_data.0),
+                RespValue::bulk_string(consumer),
             ]),
-            Request::LastEventNumber { stream } => RespValue::Array(vec![
-                RespValue::bulk_string(&"last-event-number"[..]),
+            Request::Ack {
+                group,
+                stream,
+                event_number,
+            } => RespValue::Array(vec![
+                RespValue::bulk_string("ack"),
+                RespValue::bulk_string(group.to_string()),
                 RespValue::bulk_string(stream.to_string()),
+                RespValue::Integer(event_number.0 as i64),
+            ]),
+            Request::Flow { stream, credits } => RespValue::Array(vec![
+                RespValue::bulk_string("flow"),
+                RespValue::bulk_string(stream.to_string()),
+                RespValue::Integer(credits as i64),
+            ]),
+            Request::CommitOffset {
+                group,
+                stream,
+                event_number,
+            } => RespValue::Array(vec![
+                RespValue::bulk_string("commit-offset"),
+                RespValue::bulk_string(group.to_string()),
+                RespValue::bulk_string(stream.to_string()),
+                RespValue::Integer(event_number.0 as i64),
+            ]),
+            Request::FetchOffset { group, stream } => RespValue::Array(vec![
+                RespValue::bulk_string("fetch-offset"),
+                RespValue::bulk_string(group.to_string()),
+                RespValue::bulk_string(stream.to_string()),
+            ]),
+            Request::Pending { group, stream } => RespValue::Array(vec![
+                RespValue::bulk_string("pending"),
+                RespValue::bulk_string(group.to_string()),
+                RespValue::bulk_string(stream.to_string()),
+            ]),
+            Request::Claim {
+                group,
+                stream,
+                event_number,
+                new_consumer,
+            } => RespValue::Array(vec![
+                RespValue::bulk_string("claim"),
+                RespValue::bulk_string(group.to_string()),
+                RespValue::bulk_string(stream.to_string()),
+                RespValue::Integer(event_number.0 as i64),
+                RespValue::bulk_string(new_consumer),
+            ]),
+            Request::GetRange { stream, from, to } => RespValue::Array(vec![
+                RespValue::bulk_string("get-range"),
+                RespValue::bulk_string(stream.to_string()),
+                range_bound(from, RANGE_START),
+                range_bound(to, RANGE_END),
+            ]),
+            Request::SetStreamMeta { stream, key, value } => RespValue::Array(vec![
+                RespValue::bulk_string("set-stream-meta"),
+                RespValue::bulk_string(stream.to_string()),
+                RespValue::bulk_string(key),
+                RespValue::bulk_string(value),
+            ]),
+            Request::GetStreamMeta { stream } => RespValue::Array(vec![
+                RespValue::bulk_string("get-stream-meta"),
+                RespValue::bulk_string(stream.to_string()),
+            ]),
+            Request::StreamInfo { stream } => RespValue::Array(vec![
+                RespValue::bulk_string("stream-info"),
+                RespValue::bulk_string(stream.to_string()),
+            ]),
+            Request::Verify { stream } => RespValue::Array(vec![
+                RespValue::bulk_string("verify"),
+                RespValue::bulk_string(stream.to_string()),
+            ]),
+            Request::Compact => RespValue::Array(vec![RespValue::bulk_string("compact")]),
+            Request::Backup { path } => RespValue::Array(vec![
+                RespValue::bulk_string("backup"),
+                RespValue::bulk_string(path),
+            ]),
+            Request::ReplicaOf { primary } => RespValue::Array(vec![
+                RespValue::bulk_string("replica-of"),
+                RespValue::bulk_string(primary.unwrap_or_else(|| NO_ONE.to_owned())),
+            ]),
+            Request::ReplicationInfo => {
+                RespValue::Array(vec![RespValue::bulk_string("replication-info")])
+            }
+            Request::SetAckLevel { level } => RespValue::Array(vec![
+                RespValue::bulk_string("ack-level"),
+                RespValue::bulk_string(level.to_string()),
+            ]),
+            Request::ReplicaAck { global } => RespValue::Array(vec![
+                RespValue::bulk_string("replica-ack"),
+                RespValue::Integer(global.0 as i64),
+            ]),
+            Request::RequestVote {
+                term,
+                candidate,
+                last_global,
+            } => {
+                let last_global = match last_global {
+                    Some(number) => RespValue::Integer(number.0 as i64),
+                    None => RespValue::Nil,
+                };
+
+                RespValue::Array(vec![
+                    RespValue::bulk_string("request-vote"),
+                    RespValue::Integer(term as i64),
+                    RespValue::bulk_string(candidate),
+                    last_global,
+                ])
+            }
+            Request::Heartbeat { term, leader } => RespValue::Array(vec![
+                RespValue::bulk_string("heartbeat"),
+                RespValue::Integer(term as i64),
+                RespValue::bulk_string(leader),
             ]),
-            Request::StreamNames => {
-                RespValue::Array(vec![RespValue::bulk_string(&"stream-names"[..])])
+            Request::ReadOnly { enabled } => RespValue::Array(vec![
+                RespValue::bulk_string("read-only"),
+                RespValue::bulk_string(if enabled { ON } else { OFF }),
+            ]),
+            Request::Info => RespValue::Array(vec![RespValue::bulk_string("info")]),
+            Request::ClientList => RespValue::Array(vec![RespValue::bulk_string("client-list")]),
+            Request::ClientKill { id } => RespValue::Array(vec![
+                RespValue::bulk_string("client-kill"),
+                RespValue::Integer(id as i64),
+            ]),
+            Request::ConfigSet { key, value } => RespValue::Array(vec![
+                RespValue::bulk_string("config-set"),
+                RespValue::bulk_string(key),
+                RespValue::bulk_string(value),
+            ]),
+            Request::SlowLog { reset } => {
+                let mut values = vec![RespValue::bulk_string("slowlog")];
+                if reset {
+                    values.push(RespValue::bulk_string(RESET_OPTION));
+                }
+                RespValue::Array(values)
+            }
+            Request::Latency => RespValue::Array(vec![RespValue::bulk_string("latency")]),
+            Request::Monitor => RespValue::Array(vec![RespValue::bulk_string("monitor")]),
+            Request::Compress { threshold } => RespValue::Array(vec![
+                RespValue::bulk_string("compress"),
+                RespValue::Integer(threshold as i64),
+            ]),
+            Request::Hello { proto } => {
+                let mut args = vec![RespValue::bulk_string("hello")];
+                if let Some(proto) = proto {
+                    args.push(RespValue::Integer(proto as i64));
+                }
+                RespValue::Array(args)
             }
+            Request::FrameSize { size } => RespValue::Array(vec![
+                RespValue::bulk_string("frame-size"),
+                RespValue::Integer(size as i64),
+            ]),
         }
     }
 }
@@ -65,6 +743,7 @@ pub enum RespRequestConvertError {
     UnknownCommandName,
     MissingArgument,
     TooManyArguments,
+    UnknownOptionName,
 }
 
 impl fmt::Display for RespRequestConvertError {
@@ -77,6 +756,7 @@ impl fmt::Display for RespRequestConvertError {
             UnknownCommandName => write!(f, "Unknown command name"),
             MissingArgument => write!(f, "Missing argument"),
             TooManyArguments => write!(f, "Too many arguments"),
+            UnknownOptionName => write!(f, "Unknown option name"),
         }
     }
 }
@@ -101,16 +781,31 @@ impl FromResp for Request {
         match command.as_str() {
             "subscribe" => {
                 let streams: Result<Vec<_>, _> = iter.map(Stream::from_resp).collect();
-                let streams = streams.map_err(|_| InvalidArgumentRespType)?;
+                let mut streams = streams.map_err(|_| InvalidArgumentRespType)?;
 
-                if let Some(stream) = streams.iter().find(|s| s.name == ALL_STREAMS) {
+                if let Some(pos) = streams.iter().position(|s| s.name == ALL_STREAMS) {
+                    let stream = streams.swap_remove(pos);
                     return Ok(Request::SubscribeAll {
                         range: stream.range,
+                        event_names: stream.event_names,
+                        slow_consumer: stream.slow_consumer,
+                        credits: stream.credits,
+                        batch: stream.batch,
                     });
                 }
 
                 Ok(Request::Subscribe { streams })
             }
+            "unsubscribe" => {
+                let streams: Result<Vec<_>, _> = iter.map(StreamName::from_resp).collect();
+                let streams = streams.map_err(|_| InvalidArgumentRespType)?;
+
+                if streams.is_empty() {
+                    return Err(MissingArgument);
+                }
+
+                Ok(Request::Unsubscribe { streams })
+            }
             "publish" => {
                 let stream = iter
                     .next()
@@ -130,16 +825,110 @@ impl FromResp for Request {
                     .ok_or(MissingArgument)?
                     .map_err(|_| InvalidArgumentRespType)?;
 
-                if iter.next().is_some() {
-                    return Err(TooManyArguments);
+                let mut expected_version = None;
+                let mut dedup_id = None;
+                let mut headers = EventHeaders::new();
+                let mut deliver_at = None;
+                let mut ack = None;
+
+                while let Some(option) = iter.next() {
+                    let option = String::from_resp(option).map_err(|_| InvalidArgumentRespType)?;
+                    let value = iter.next().ok_or(MissingArgument)?;
+
+                    match option.as_str() {
+                        VERSION_OPTION if expected_version.is_none() => {
+                            expected_version = Some(expected_version_from_resp(value)?);
+                        }
+                        DEDUP_OPTION if dedup_id.is_none() => {
+                            let id =
+                                String::from_resp(value).map_err(|_| InvalidArgumentRespType)?;
+                            dedup_id = Some(id);
+                        }
+                        HEADER_OPTION => {
+                            let key =
+                                String::from_resp(value).map_err(|_| InvalidArgumentRespType)?;
+                            let value = iter
+                                .next()
+                                .map(String::from_resp)
+                                .ok_or(MissingArgument)?
+                                .map_err(|_| InvalidArgumentRespType)?;
+                            headers.insert(key, value);
+                        }
+                        DELIVER_AT_OPTION if deliver_at.is_none() => {
+                            deliver_at = Some(timestamp_from_resp(value)?);
+                        }
+                        ACK_OPTION if ack.is_none() => {
+                            ack = Some(ack_level_from_resp(value)?);
+                        }
+                        _otherwise => return Err(UnknownOptionName),
+                    }
                 }
 
                 Ok(Request::Publish {
                     stream,
                     event_name,
                     event_data,
+                    expected_version,
+                    dedup_id,
+                    headers,
+                    deliver_at,
+                    ack,
                 })
             }
+            "publish-batch" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let mut events = Vec::new();
+                while let Some(event_name) = iter.next() {
+                    let event_name =
+                        EventName::from_resp(event_name).map_err(|_| InvalidArgumentRespType)?;
+
+                    let event_data = iter
+                        .next()
+                        .map(EventData::from_resp)
+                        .ok_or(MissingArgument)?
+                        .map_err(|_| InvalidArgumentRespType)?;
+
+                    events.push((event_name, event_data));
+                }
+
+                if events.is_empty() {
+                    return Err(MissingArgument);
+                }
+
+                Ok(Request::PublishBatch { stream, events })
+            }
+            "publish-multi" => {
+                let mut writes = Vec::new();
+                while let Some(stream) = iter.next() {
+                    let stream =
+                        StreamName::from_resp(stream).map_err(|_| InvalidArgumentRespType)?;
+
+                    let event_name = iter
+                        .next()
+                        .map(EventName::from_resp)
+                        .ok_or(MissingArgument)?
+                        .map_err(|_| InvalidArgumentRespType)?;
+
+                    let event_data = iter
+                        .next()
+                        .map(EventData::from_resp)
+                        .ok_or(MissingArgument)?
+                        .map_err(|_| InvalidArgumentRespType)?;
+
+                    writes.push((stream, event_name, event_data));
+                }
+
+                if writes.is_empty() {
+                    return Err(MissingArgument);
+                }
+
+                Ok(Request::PublishMulti { writes })
+            }
             "last-event-number" => {
                 let stream = iter
                     .next()
@@ -153,8 +942,711 @@ impl FromResp for Request {
 
                 Ok(Request::LastEventNumber { stream })
             }
-            "stream-names" => Ok(Request::StreamNames),
-            _otherwise => Err(UnknownCommandName),
-        }
+            "create-stream" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::CreateStream { stream })
+            }
+            "delete-stream" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let hard = match iter.next() {
+                    Some(option) => {
+                        let option =
+                            String::from_resp(option).map_err(|_| InvalidArgumentRespType)?;
+                        if option != HARD_OPTION {
+                            return Err(UnknownOptionName);
+                        }
+                        true
+                    }
+                    None => false,
+                };
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::DeleteStream { stream, hard })
+            }
+            "trim-stream" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let up_to = iter.next().map(number_from_resp).ok_or(MissingArgument)??;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::TrimStream { stream, up_to })
+            }
+            "stream-names" => {
+                let pattern = match iter.next() {
+                    Some(value) => {
+                        Some(String::from_resp(value).map_err(|_| InvalidArgumentRespType)?)
+                    }
+                    None => None,
+                };
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::StreamNames { pattern })
+            }
+            "get-range" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let from = match iter.next() {
+                    Some(value) => range_bound_from_resp(value, RANGE_START)?,
+                    None => None,
+                };
+
+                let to = match iter.next() {
+                    Some(value) => range_bound_from_resp(value, RANGE_END)?,
+                    None => None,
+                };
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::GetRange { stream, from, to })
+            }
+            "ping" => {
+                let payload = match iter.next() {
+                    Some(value) => {
+                        Some(Vec::<u8>::from_resp(value).map_err(|_| InvalidArgumentRespType)?)
+                    }
+                    None => None,
+                };
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Ping { payload })
+            }
+            "auth" => {
+                let first = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let second = match iter.next() {
+                    Some(value) => {
+                        Some(String::from_resp(value).map_err(|_| InvalidArgumentRespType)?)
+                    }
+                    None => None,
+                };
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                // the username is optional and comes before the password
+                let (username, password) = match second {
+                    Some(password) => (Some(first), password),
+                    None => (None, first),
+                };
+
+                Ok(Request::Auth { username, password })
+            }
+            "subscribe-group" => {
+                let group = iter
+                    .next()
+                    .map(GroupName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let consumer = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::SubscribeGroup {
+                    group,
+                    stream,
+                    consumer,
+                })
+            }
+            "ack" => {
+                let group = iter
+                    .next()
+                    .map(GroupName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let event_number = iter.next().map(number_from_resp).ok_or(MissingArgument)??;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Ack {
+                    group,
+                    stream,
+                    event_number: EventNumber(event_number),
+                })
+            }
+            "flow" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let credits = iter.next().map(number_from_resp).ok_or(MissingArgument)??;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Flow { stream, credits })
+            }
+            "commit-offset" => {
+                let group = iter
+                    .next()
+                    .map(GroupName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let event_number = iter.next().map(number_from_resp).ok_or(MissingArgument)??;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::CommitOffset {
+                    group,
+                    stream,
+                    event_number: EventNumber(event_number),
+                })
+            }
+            "fetch-offset" => {
+                let group = iter
+                    .next()
+                    .map(GroupName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::FetchOffset { group, stream })
+            }
+            "pending" => {
+                let group = iter
+                    .next()
+                    .map(GroupName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Pending { group, stream })
+            }
+            "claim" => {
+                let group = iter
+                    .next()
+                    .map(GroupName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let event_number = iter.next().map(number_from_resp).ok_or(MissingArgument)??;
+
+                let new_consumer = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Claim {
+                    group,
+                    stream,
+                    event_number: EventNumber(event_number),
+                    new_consumer,
+                })
+            }
+            "set-stream-meta" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let key = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let value = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::SetStreamMeta { stream, key, value })
+            }
+            "get-stream-meta" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::GetStreamMeta { stream })
+            }
+            "stream-info" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::StreamInfo { stream })
+            }
+            "verify" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Verify { stream })
+            }
+            "compact" => {
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Compact)
+            }
+            "backup" => {
+                let path = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Backup { path })
+            }
+            "replica-of" => {
+                let primary = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                let primary = if primary == NO_ONE {
+                    None
+                } else {
+                    Some(primary)
+                };
+
+                Ok(Request::ReplicaOf { primary })
+            }
+            "replication-info" => {
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::ReplicationInfo)
+            }
+            "ack-level" => {
+                let level = iter.next().ok_or(MissingArgument)?;
+                let level = ack_level_from_resp(level)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::SetAckLevel { level })
+            }
+            "replica-ack" => {
+                let global = iter.next().ok_or(MissingArgument)?;
+                let global = EventNumber(number_from_resp(global)?);
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::ReplicaAck { global })
+            }
+            "request-vote" => {
+                let term = iter.next().ok_or(MissingArgument)?;
+                let term = number_from_resp(term)?;
+
+                let candidate = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let last_global = match iter.next().ok_or(MissingArgument)? {
+                    RespValue::Nil => None,
+                    value => Some(EventNumber(number_from_resp(value)?)),
+                };
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::RequestVote {
+                    term,
+                    candidate,
+                    last_global,
+                })
+            }
+            "heartbeat" => {
+                let term = iter.next().ok_or(MissingArgument)?;
+                let term = number_from_resp(term)?;
+
+                let leader = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Heartbeat { term, leader })
+            }
+            "read-only" => {
+                let enabled = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                let enabled = match enabled.as_str() {
+                    ON => true,
+                    OFF => false,
+                    _otherwise => return Err(InvalidArgumentRespType),
+                };
+
+                Ok(Request::ReadOnly { enabled })
+            }
+            "info" => {
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Info)
+            }
+            "client-list" => {
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::ClientList)
+            }
+            "client-kill" => {
+                let id = iter.next().ok_or(MissingArgument)?;
+                let id = number_from_resp(id)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::ClientKill { id })
+            }
+            "config-set" => {
+                let key = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let value = iter
+                    .next()
+                    .map(String::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::ConfigSet { key, value })
+            }
+            "slowlog" => {
+                let reset = match iter.next() {
+                    Some(option) => {
+                        let option =
+                            String::from_resp(option).map_err(|_| InvalidArgumentRespType)?;
+                        if option != RESET_OPTION {
+                            return Err(UnknownOptionName);
+                        }
+                        true
+                    }
+                    None => false,
+                };
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::SlowLog { reset })
+            }
+            "latency" => {
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Latency)
+            }
+            "monitor" => {
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Monitor)
+            }
+            "compress" => {
+                let threshold = iter.next().ok_or(MissingArgument)?;
+                let threshold = number_from_resp(threshold)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Compress { threshold })
+            }
+            "hello" => {
+                let proto = iter.next().map(number_from_resp).transpose()?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::Hello { proto })
+            }
+            "frame-size" => {
+                let size = iter.next().ok_or(MissingArgument)?;
+                let size = number_from_resp(size)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Request::FrameSize { size })
+            }
+            _otherwise => Err(UnknownCommandName),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_name(name: &str) -> StreamName {
+        StreamName::new(name.to_owned()).unwrap()
+    }
+
+    fn round_trip(request: Request) {
+        let value: RespValue = request.clone().into();
+        assert_eq!(Request::from_resp(value).unwrap(), request);
+    }
+
+    #[test]
+    fn unsubscribe_round_trip() {
+        round_trip(Request::Unsubscribe {
+            streams: vec![stream_name("payments")],
+        });
+        round_trip(Request::Unsubscribe {
+            streams: vec![stream_name("payments"), stream_name("orders")],
+        });
+    }
+
+    #[test]
+    fn subscribe_round_trip() {
+        let streams = vec![
+            Stream::from_str("payments").unwrap(),
+            Stream::from_str("orders:5").unwrap(),
+            Stream::from_str("users:1:5").unwrap(),
+        ];
+        round_trip(Request::Subscribe { streams });
+    }
+
+    #[test]
+    fn publish_round_trip() {
+        round_trip(Request::Publish {
+            stream: stream_name("payments"),
+            event_name: EventName::new("Paid".to_owned()).unwrap(),
+            event_data: EventData(b"{\"amount\":42}".to_vec().into()),
+            expected_version: None,
+            dedup_id: None,
+            headers: EventHeaders::new(),
+            deliver_at: None,
+            ack: None,
+        });
+
+        let mut headers = EventHeaders::new();
+        headers.insert("source".to_owned(), "tests".to_owned());
+        round_trip(Request::Publish {
+            stream: stream_name("payments"),
+            event_name: EventName::new("Paid".to_owned()).unwrap(),
+            event_data: EventData(Vec::new().into()),
+            expected_version: Some(ExpectedVersion::Number(EventNumber(3))),
+            dedup_id: Some("payment-42".to_owned()),
+            headers,
+            deliver_at: Some(Timestamp(1_500_000_000_000)),
+            ack: Some(AckLevel::Quorum),
+        });
+    }
+
+    #[test]
+    fn get_range_round_trip() {
+        round_trip(Request::GetRange {
+            stream: stream_name("payments"),
+            from: None,
+            to: None,
+        });
+        round_trip(Request::GetRange {
+            stream: stream_name("payments"),
+            from: Some(EventNumber(2)),
+            to: Some(EventNumber(10)),
+        });
+    }
+
+    #[test]
+    fn stream_commands_round_trip() {
+        round_trip(Request::LastEventNumber {
+            stream: stream_name("payments"),
+        });
+        round_trip(Request::DeleteStream {
+            stream: stream_name("payments"),
+            hard: true,
+        });
+        round_trip(Request::TrimStream {
+            stream: stream_name("payments"),
+            up_to: 12,
+        });
+        round_trip(Request::StreamNames { pattern: None });
+        round_trip(Request::StreamInfo {
+            stream: stream_name("payments"),
+        });
+    }
+
+    #[test]
+    fn connection_commands_round_trip() {
+        round_trip(Request::Ping { payload: None });
+        round_trip(Request::Ping {
+            payload: Some(b"hello".to_vec()),
+        });
+        round_trip(Request::Auth {
+            username: Some("admin".to_owned()),
+            password: "secret".to_owned(),
+        });
+        round_trip(Request::Hello { proto: Some(3) });
+        round_trip(Request::Info);
+    }
+
+    #[test]
+    fn unknown_command() {
+        let value = RespValue::Array(vec![RespValue::bulk_string("no-such-command")]);
+        let result = Request::from_resp(value);
+        assert!(matches!(result, Err(RespRequestConvertError::UnknownCommandName)));
     }
 }
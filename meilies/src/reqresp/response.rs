@@ -1,5 +1,9 @@
+use super::{ClientInfo, LatencyStats, SlowLogEntry};
 use crate::resp::{FromResp, RespValue};
-use crate::stream::{EventData, EventName, EventNumber, StreamName};
+use crate::stream::{
+    Event, EventData, EventHeaders, EventName, EventNumber, GroupName, PendingEvent, StreamName,
+    Timestamp,
+};
 use std::fmt;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -8,11 +12,54 @@ pub enum Response {
     Subscribed {
         stream: StreamName,
     },
+    Unsubscribed {
+        stream: StreamName,
+    },
+    CaughtUp {
+        stream: StreamName,
+    },
+    RangeEnd {
+        stream: StreamName,
+    },
+    StreamDeleted {
+        stream: StreamName,
+    },
+    StreamTrimmed {
+        stream: StreamName,
+        first: EventNumber,
+    },
+    /// Events of the subscription have been dropped because the subscriber did not keep up.
+    EventsDropped {
+        stream: StreamName,
+        count: u64,
+    },
+    Published {
+        stream: StreamName,
+        number: EventNumber,
+    },
+    /// The event will be appended to the stream at the given time.
+    Scheduled {
+        stream: StreamName,
+        deliver_at: Timestamp,
+    },
+    PublishedBatch {
+        stream: StreamName,
+        first: EventNumber,
+        last: EventNumber,
+    },
+    PublishedMulti {
+        events: Vec<(StreamName, EventNumber)>,
+    },
     Event {
         stream: StreamName,
         number: EventNumber,
+        timestamp: Timestamp,
         event_name: EventName,
         event_data: EventData,
+        headers: EventHeaders,
+        /// The number of the event in the `$all` stream, i.e. its position among
+        /// the events of all the streams, only sent to the `$all` subscribers.
+        global_number: Option<EventNumber>,
     },
     LastEventNumber {
         stream: StreamName,
@@ -21,6 +68,142 @@ pub enum Response {
     StreamNames {
         streams: Vec<StreamName>,
     },
+    Range {
+        stream: StreamName,
+        events: Vec<Event>,
+    },
+    Pong {
+        payload: Option<Vec<u8>>,
+    },
+    Offset {
+        group: GroupName,
+        stream: StreamName,
+        number: Option<EventNumber>,
+    },
+    Pending {
+        group: GroupName,
+        stream: StreamName,
+        events: Vec<PendingEvent>,
+    },
+    StreamMeta {
+        stream: StreamName,
+        meta: Vec<(String, String)>,
+    },
+    StreamInfo {
+        stream: StreamName,
+        /// The number of the first event kept, the events below have been removed.
+        first: EventNumber,
+        last: Option<EventNumber>,
+        /// The size in bytes of the events kept.
+        bytes: u64,
+        /// The number of events kept, the compacted streams have fewer events than numbers.
+        events: u64,
+        /// The time the stream was created, unknown for the streams created by older versions.
+        created: Option<Timestamp>,
+        /// The number of milliseconds the events are kept, set with the `retention.ms` metadata.
+        retention_ms: Option<u64>,
+        max_events: Option<u64>,
+        max_bytes: Option<u64>,
+        /// The number of subscriptions of the connected clients to the stream.
+        subscribers: u64,
+    },
+    Verified {
+        stream: StreamName,
+        /// The number of events whose checksum matches their content.
+        checked: u64,
+        /// The number of events stored without a checksum, before the checksums were introduced.
+        unchecked: u64,
+        /// The numbers of the events whose checksum does not match their content.
+        corrupted: Vec<EventNumber>,
+    },
+    Compacted {
+        /// The size in bytes of the database on disk before the compaction.
+        before: u64,
+        /// The size in bytes of the database on disk after the compaction.
+        after: u64,
+    },
+    ReplicationInfo {
+        /// The address of the primary if the server is a replica.
+        primary: Option<String>,
+        /// Whether the replica has received all the events published before it connected.
+        caught_up: bool,
+        /// The global number of the last event replicated.
+        last_global: Option<EventNumber>,
+        /// The number of milliseconds between the publication of the
+        /// last event replicated and its replication.
+        lag: Option<u64>,
+    },
+    /// The answer of a cluster node to a vote request or a heartbeat.
+    RaftReply {
+        /// The current term of the node.
+        term: u64,
+        /// Whether the vote has been granted or the leader accepted.
+        success: bool,
+    },
+    /// The statistics of the server by name.
+    Info {
+        fields: Vec<(String, String)>,
+    },
+    ClientList {
+        clients: Vec<ClientInfo>,
+    },
+    SlowLog {
+        entries: Vec<SlowLogEntry>,
+    },
+    Latency {
+        commands: Vec<LatencyStats>,
+        /// The appends, the catch-up reads and the deliveries of the events.
+        operations: Vec<LatencyStats>,
+    },
+    /// A command processed by the server, sent to the monitoring connections.
+    Monitored {
+        timestamp: Timestamp,
+        /// The id of the client that sent the command.
+        client: u64,
+        /// The name and the arguments of the command.
+        command: Vec<String>,
+    },
+    /// The responses that follow, larger than `threshold` bytes once encoded, are compressed.
+    Compression {
+        threshold: u64,
+    },
+    /// The version and the features of the server, the responses
+    /// that follow are sent using this version of the protocol.
+    Hello {
+        version: String,
+        proto: u64,
+        /// The names of the features supported by the server, e.g. `resp3`.
+        features: Vec<String>,
+    },
+    /// The bulk strings that follow, larger than `size` bytes, are sent in chunks.
+    FrameSize {
+        size: u64,
+    },
+    /// Consecutive events of a batched subscription sent in a single message,
+    /// each one is an `Event` response.
+    Batch {
+        events: Vec<Response>,
+    },
+}
+
+impl Response {
+    /// Returns `true` if the response is sent by the server without being the reply of
+    /// a command, e.g. the events of a subscription. It is sent as a push in RESP3.
+    pub fn is_push(&self) -> bool {
+        match self {
+            Response::Subscribed { .. }
+            | Response::Unsubscribed { .. }
+            | Response::CaughtUp { .. }
+            | Response::RangeEnd { .. }
+            | Response::StreamDeleted { .. }
+            | Response::StreamTrimmed { .. }
+            | Response::EventsDropped { .. }
+            | Response::Event { .. }
+            | Response::Batch { .. }
+            | Response::Monitored { .. } => true,
+            _otherwise => false,
+        }
+    }
 }
 
 impl Into<RespValue> for Response {
@@ -31,18 +214,84 @@ impl Into<RespValue> for Response {
                 RespValue::string("subscribed"),
                 RespValue::string(stream),
             ]),
+            Response::Unsubscribed { stream } => RespValue::Array(vec![
+                RespValue::string("unsubscribed"),
+                RespValue::string(stream),
+            ]),
+            Response::Published { stream, number } => RespValue::Array(vec![
+                RespValue::string("published"),
+                RespValue::string(stream),
+                RespValue::Integer(number.0 as i64),
+            ]),
+            Response::Scheduled { stream, deliver_at } => RespValue::Array(vec![
+                RespValue::string("scheduled"),
+                RespValue::string(stream),
+                RespValue::Integer(deliver_at.0 as i64),
+            ]),
+            Response::PublishedBatch {
+                stream,
+                first,
+                last,
+            } => RespValue::Array(vec![
+                RespValue::string("published-batch"),
+                RespValue::string(stream),
+                RespValue::Integer(first.0 as i64),
+                RespValue::Integer(last.0 as i64),
+            ]),
+            Response::PublishedMulti { events } => {
+                let mut values = Vec::with_capacity(1 + events.len() * 2);
+                values.push(RespValue::string("published-multi"));
+                for (stream, number) in events {
+                    values.push(RespValue::string(stream));
+                    values.push(RespValue::Integer(number.0 as i64));
+                }
+                RespValue::Array(values)
+            }
+            Response::CaughtUp { stream } => RespValue::Array(vec![
+                RespValue::string("caught-up"),
+                RespValue::string(stream),
+            ]),
+            Response::RangeEnd { stream } => RespValue::Array(vec![
+                RespValue::string("range-end"),
+                RespValue::string(stream),
+            ]),
+            Response::StreamDeleted { stream } => RespValue::Array(vec![
+                RespValue::string("stream-deleted"),
+                RespValue::string(stream),
+            ]),
+            Response::StreamTrimmed { stream, first } => RespValue::Array(vec![
+                RespValue::string("stream-trimmed"),
+                RespValue::string(stream),
+                RespValue::Integer(first.0 as i64),
+            ]),
+            Response::EventsDropped { stream, count } => RespValue::Array(vec![
+                RespValue::string("events-dropped"),
+                RespValue::string(stream),
+                RespValue::Integer(count as i64),
+            ]),
             Response::Event {
                 stream,
                 number,
+                timestamp,
                 event_name,
                 event_data,
-            } => RespValue::Array(vec![
-                RespValue::string("event"),
-                RespValue::string(stream),
-                RespValue::Integer(number.0 as i64),
-                RespValue::string(event_name),
-                RespValue::bulk_string(event_data.0),
-            ]),
+                headers,
+                global_number,
+            } => {
+                let mut values = vec![
+                    RespValue::string("event"),
+                    RespValue::string(stream),
+                    RespValue::Integer(number.0 as i64),
+                    RespValue::Integer(timestamp.0 as i64),
+                    RespValue::string(event_name),
+                    RespValue::bulk_string(event_data.0),
+                    headers.into(),
+                ];
+                if let Some(global_number) = global_number {
+                    values.push(RespValue::Integer(global_number.0 as i64));
+                }
+                RespValue::Array(values)
+            }
             Response::LastEventNumber { stream, number } => {
                 let number = match number {
                     Some(number) => RespValue::Integer(number.0 as i64),
@@ -63,6 +312,226 @@ impl Into<RespValue> for Response {
                 let args = Some(command).into_iter().chain(streams).collect();
                 RespValue::Array(args)
             }
+            Response::Pong { payload: None } => RespValue::string("PONG"),
+            Response::Pong {
+                payload: Some(payload),
+            } => RespValue::Array(vec![
+                RespValue::string("pong"),
+                RespValue::bulk_string(payload),
+            ]),
+            Response::Range { stream, events } => {
+                let events = events.into_iter().map(Into::into).collect();
+
+                RespValue::Array(vec![
+                    RespValue::string("range"),
+                    RespValue::string(stream),
+                    RespValue::Array(events),
+                ])
+            }
+            Response::Offset {
+                group,
+                stream,
+                number,
+            } => {
+                let number = match number {
+                    Some(number) => RespValue::Integer(number.0 as i64),
+                    None => RespValue::Nil,
+                };
+
+                RespValue::Array(vec![
+                    RespValue::string("offset"),
+                    RespValue::string(group),
+                    RespValue::string(stream),
+                    number,
+                ])
+            }
+            Response::Pending {
+                group,
+                stream,
+                events,
+            } => {
+                let events = events.into_iter().map(Into::into).collect();
+
+                RespValue::Array(vec![
+                    RespValue::string("pending"),
+                    RespValue::string(group),
+                    RespValue::string(stream),
+                    RespValue::Array(events),
+                ])
+            }
+            Response::StreamInfo {
+                stream,
+                first,
+                last,
+                bytes,
+                events,
+                created,
+                retention_ms,
+                max_events,
+                max_bytes,
+                subscribers,
+            } => {
+                let integer = |value: Option<u64>| match value {
+                    Some(value) => RespValue::Integer(value as i64),
+                    None => RespValue::Nil,
+                };
+
+                RespValue::Array(vec![
+                    RespValue::string("stream-info"),
+                    RespValue::string(stream),
+                    RespValue::Integer(first.0 as i64),
+                    integer(last.map(|number| number.0)),
+                    RespValue::Integer(bytes as i64),
+                    RespValue::Integer(events as i64),
+                    integer(created.map(|timestamp| timestamp.0)),
+                    integer(retention_ms),
+                    integer(max_events),
+                    integer(max_bytes),
+                    RespValue::Integer(subscribers as i64),
+                ])
+            }
+            Response::Verified {
+                stream,
+                checked,
+                unchecked,
+                corrupted,
+            } => {
+                let mut values = Vec::with_capacity(4 + corrupted.len());
+                values.push(RespValue::string("verified"));
+                values.push(RespValue::string(stream));
+                values.push(RespValue::Integer(checked as i64));
+                values.push(RespValue::Integer(unchecked as i64));
+                for number in corrupted {
+                    values.push(RespValue::Integer(number.0 as i64));
+                }
+                RespValue::Array(values)
+            }
+            Response::Compacted { before, after } => RespValue::Array(vec![
+                RespValue::string("compacted"),
+                RespValue::Integer(before as i64),
+                RespValue::Integer(after as i64),
+            ]),
+            Response::StreamMeta { stream, meta } => {
+                let mut values = Vec::with_capacity(2 + meta.len() * 2);
+                values.push(RespValue::string("stream-meta"));
+                values.push(RespValue::string(stream));
+                for (key, value) in meta {
+                    values.push(RespValue::bulk_string(key));
+                    values.push(RespValue::bulk_string(value));
+                }
+                RespValue::Array(values)
+            }
+            Response::ReplicationInfo {
+                primary,
+                caught_up,
+                last_global,
+                lag,
+            } => {
+                let primary = primary.map_or(RespValue::Nil, RespValue::bulk_string);
+                let last_global = match last_global {
+                    Some(number) => RespValue::Integer(number.0 as i64),
+                    None => RespValue::Nil,
+                };
+                let lag = match lag {
+                    Some(lag) => RespValue::Integer(lag as i64),
+                    None => RespValue::Nil,
+                };
+
+                RespValue::Array(vec![
+                    RespValue::string("replication-info"),
+                    primary,
+                    RespValue::Integer(caught_up as i64),
+                    last_global,
+                    lag,
+                ])
+            }
+            Response::RaftReply { term, success } => RespValue::Array(vec![
+                RespValue::string("raft-reply"),
+                RespValue::Integer(term as i64),
+                RespValue::Integer(success as i64),
+            ]),
+            Response::Info { fields } => {
+                let mut values = Vec::with_capacity(1 + fields.len() * 2);
+                values.push(RespValue::string("info"));
+                for (name, value) in fields {
+                    values.push(RespValue::bulk_string(name));
+                    values.push(RespValue::bulk_string(value));
+                }
+                RespValue::Array(values)
+            }
+            Response::ClientList { clients } => {
+                let clients = clients.into_iter().map(Into::into).collect();
+
+                RespValue::Array(vec![
+                    RespValue::string("client-list"),
+                    RespValue::Array(clients),
+                ])
+            }
+            Response::SlowLog { entries } => {
+                let entries = entries.into_iter().map(Into::into).collect();
+
+                RespValue::Array(vec![
+                    RespValue::string("slowlog"),
+                    RespValue::Array(entries),
+                ])
+            }
+            Response::Latency {
+                commands,
+                operations,
+            } => {
+                let commands = commands.into_iter().map(Into::into).collect();
+                let operations = operations.into_iter().map(Into::into).collect();
+
+                RespValue::Array(vec![
+                    RespValue::string("latency"),
+                    RespValue::Array(commands),
+                    RespValue::Array(operations),
+                ])
+            }
+            Response::Monitored {
+                timestamp,
+                client,
+                command,
+            } => {
+                let command = command.into_iter().map(RespValue::bulk_string).collect();
+
+                RespValue::Array(vec![
+                    RespValue::string("monitored"),
+                    RespValue::Integer(timestamp.0 as i64),
+                    RespValue::Integer(client as i64),
+                    RespValue::Array(command),
+                ])
+            }
+            Response::Compression { threshold } => RespValue::Array(vec![
+                RespValue::string("compression"),
+                RespValue::Integer(threshold as i64),
+            ]),
+            Response::Hello {
+                version,
+                proto,
+                features,
+            } => {
+                let features = features.into_iter().map(RespValue::bulk_string).collect();
+                let field = |name: &str, value| (RespValue::bulk_string(name), value);
+
+                RespValue::Array(vec![
+                    RespValue::string("hello"),
+                    RespValue::Map(vec![
+                        field("server", RespValue::bulk_string("meilies")),
+                        field("version", RespValue::bulk_string(version)),
+                        field("proto", RespValue::Integer(proto as i64)),
+                        field("features", RespValue::Array(features)),
+                    ]),
+                ])
+            }
+            Response::FrameSize { size } => RespValue::Array(vec![
+                RespValue::string("frame-size"),
+                RespValue::Integer(size as i64),
+            ]),
+            Response::Batch { events } => {
+                let events = events.into_iter().map(Into::into).collect();
+                RespValue::Array(vec![RespValue::string("batch"), RespValue::Array(events)])
+            }
         }
     }
 }
@@ -99,7 +568,10 @@ impl FromResp for Response {
 
         let mut iter = match value {
             RespValue::SimpleString(ref text) if text == "OK" => return Ok(Response::Ok),
-            RespValue::Array(array) => array.into_iter(),
+            RespValue::SimpleString(ref text) if text == "PONG" => {
+                return Ok(Response::Pong { payload: None })
+            }
+            RespValue::Array(array) | RespValue::Push(array) => array.into_iter(),
             _otherwise => return Err(InvalidResponseRespType),
         };
 
@@ -123,28 +595,87 @@ impl FromResp for Response {
 
                 Ok(Response::Subscribed { stream })
             }
-            "event" => {
+            "unsubscribed" => {
                 let stream = iter
                     .next()
                     .map(StreamName::from_resp)
                     .ok_or(MissingArgument)?
                     .map_err(|_| InvalidArgumentRespType)?;
 
-                let number = iter
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::Unsubscribed { stream })
+            }
+            "caught-up" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::CaughtUp { stream })
+            }
+            "range-end" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::RangeEnd { stream })
+            }
+            "stream-deleted" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::StreamDeleted { stream })
+            }
+            "stream-trimmed" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let first = iter
                     .next()
                     .map(EventNumber::from_resp)
                     .ok_or(MissingArgument)?
                     .map_err(|_| InvalidArgumentRespType)?;
 
-                let event_name = iter
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::StreamTrimmed { stream, first })
+            }
+            "events-dropped" => {
+                let stream = iter
                     .next()
-                    .map(EventName::from_resp)
+                    .map(StreamName::from_resp)
                     .ok_or(MissingArgument)?
                     .map_err(|_| InvalidArgumentRespType)?;
 
-                let event_data = iter
+                let count = iter
                     .next()
-                    .map(EventData::from_resp)
+                    .map(i64::from_resp)
                     .ok_or(MissingArgument)?
                     .map_err(|_| InvalidArgumentRespType)?;
 
@@ -152,14 +683,12 @@ impl FromResp for Response {
                     return Err(TooManyArguments);
                 }
 
-                Ok(Response::Event {
+                Ok(Response::EventsDropped {
                     stream,
-                    number,
-                    event_name,
-                    event_data,
+                    count: count as u64,
                 })
             }
-            "last-event-number" => {
+            "published" => {
                 let stream = iter
                     .next()
                     .map(StreamName::from_resp)
@@ -168,7 +697,7 @@ impl FromResp for Response {
 
                 let number = iter
                     .next()
-                    .map(FromResp::from_resp)
+                    .map(EventNumber::from_resp)
                     .ok_or(MissingArgument)?
                     .map_err(|_| InvalidArgumentRespType)?;
 
@@ -176,13 +705,724 @@ impl FromResp for Response {
                     return Err(TooManyArguments);
                 }
 
-                Ok(Response::LastEventNumber { stream, number })
+                Ok(Response::Published { stream, number })
             }
-            "stream-names" => match iter.map(StreamName::from_resp).collect() {
-                Ok(streams) => Ok(Response::StreamNames { streams }),
-                Err(_) => Err(InvalidArgumentRespType),
-            },
-            _otherwise => Err(UnknownTypeName),
-        }
+            "scheduled" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let deliver_at = iter
+                    .next()
+                    .map(Timestamp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::Scheduled { stream, deliver_at })
+            }
+            "published-multi" => {
+                let mut events = Vec::new();
+                while let Some(stream) = iter.next() {
+                    let stream =
+                        StreamName::from_resp(stream).map_err(|_| InvalidArgumentRespType)?;
+
+                    let number = iter
+                        .next()
+                        .map(EventNumber::from_resp)
+                        .ok_or(MissingArgument)?
+                        .map_err(|_| InvalidArgumentRespType)?;
+
+                    events.push((stream, number));
+                }
+
+                Ok(Response::PublishedMulti { events })
+            }
+            "published-batch" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let first = iter
+                    .next()
+                    .map(EventNumber::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let last = iter
+                    .next()
+                    .map(EventNumber::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::PublishedBatch {
+                    stream,
+                    first,
+                    last,
+                })
+            }
+            "event" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let number = iter
+                    .next()
+                    .map(EventNumber::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let timestamp = iter
+                    .next()
+                    .map(Timestamp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let event_name = iter
+                    .next()
+                    .map(EventName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let event_data = iter
+                    .next()
+                    .map(EventData::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let headers = iter
+                    .next()
+                    .map(EventHeaders::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let global_number = iter
+                    .next()
+                    .map(EventNumber::from_resp)
+                    .transpose()
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::Event {
+                    stream,
+                    number,
+                    timestamp,
+                    event_name,
+                    event_data,
+                    headers,
+                    global_number,
+                })
+            }
+            "last-event-number" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let number = iter
+                    .next()
+                    .map(FromResp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::LastEventNumber { stream, number })
+            }
+            "stream-names" => match iter.map(StreamName::from_resp).collect() {
+                Ok(streams) => Ok(Response::StreamNames { streams }),
+                Err(_) => Err(InvalidArgumentRespType),
+            },
+            "range" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let events = iter
+                    .next()
+                    .map(Vec::<Event>::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::Range { stream, events })
+            }
+            "pong" => {
+                let payload = iter
+                    .next()
+                    .map(Vec::<u8>::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::Pong {
+                    payload: Some(payload),
+                })
+            }
+            "offset" => {
+                let group = iter
+                    .next()
+                    .map(GroupName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let number = iter
+                    .next()
+                    .map(FromResp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::Offset {
+                    group,
+                    stream,
+                    number,
+                })
+            }
+            "pending" => {
+                let group = iter
+                    .next()
+                    .map(GroupName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let events = iter
+                    .next()
+                    .map(Vec::<PendingEvent>::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::Pending {
+                    group,
+                    stream,
+                    events,
+                })
+            }
+            "stream-info" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let first = iter
+                    .next()
+                    .map(EventNumber::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let last = iter
+                    .next()
+                    .map(FromResp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let bytes = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let events = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let created = iter
+                    .next()
+                    .map(FromResp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let retention_ms: Option<i64> = iter
+                    .next()
+                    .map(FromResp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let max_events: Option<i64> = iter
+                    .next()
+                    .map(FromResp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let max_bytes: Option<i64> = iter
+                    .next()
+                    .map(FromResp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let subscribers = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::StreamInfo {
+                    stream,
+                    first,
+                    last,
+                    bytes: bytes as u64,
+                    events: events as u64,
+                    created,
+                    retention_ms: retention_ms.map(|ms| ms as u64),
+                    max_events: max_events.map(|max| max as u64),
+                    max_bytes: max_bytes.map(|max| max as u64),
+                    subscribers: subscribers as u64,
+                })
+            }
+            "verified" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let checked = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let unchecked = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let corrupted = iter
+                    .map(EventNumber::from_resp)
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                Ok(Response::Verified {
+                    stream,
+                    checked: checked as u64,
+                    unchecked: unchecked as u64,
+                    corrupted,
+                })
+            }
+            "compacted" => {
+                let before = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let after = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::Compacted {
+                    before: before as u64,
+                    after: after as u64,
+                })
+            }
+            "stream-meta" => {
+                let stream = iter
+                    .next()
+                    .map(StreamName::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let mut meta = Vec::new();
+                while let Some(key) = iter.next() {
+                    let key = String::from_resp(key).map_err(|_| InvalidArgumentRespType)?;
+
+                    let value = iter
+                        .next()
+                        .map(String::from_resp)
+                        .ok_or(MissingArgument)?
+                        .map_err(|_| InvalidArgumentRespType)?;
+
+                    meta.push((key, value));
+                }
+
+                Ok(Response::StreamMeta { stream, meta })
+            }
+            "replication-info" => {
+                let primary = iter
+                    .next()
+                    .map(FromResp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let caught_up = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let last_global = iter
+                    .next()
+                    .map(FromResp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let lag: Option<i64> = iter
+                    .next()
+                    .map(FromResp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::ReplicationInfo {
+                    primary,
+                    caught_up: caught_up != 0,
+                    last_global,
+                    lag: lag.map(|lag| lag as u64),
+                })
+            }
+            "raft-reply" => {
+                let term = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let success = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::RaftReply {
+                    term: term as u64,
+                    success: success != 0,
+                })
+            }
+            "info" => {
+                let mut fields = Vec::new();
+                while let Some(name) = iter.next() {
+                    let name = String::from_resp(name).map_err(|_| InvalidArgumentRespType)?;
+
+                    let value = iter
+                        .next()
+                        .map(String::from_resp)
+                        .ok_or(MissingArgument)?
+                        .map_err(|_| InvalidArgumentRespType)?;
+
+                    fields.push((name, value));
+                }
+
+                Ok(Response::Info { fields })
+            }
+            "client-list" => {
+                let clients = iter
+                    .next()
+                    .map(Vec::<ClientInfo>::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::ClientList { clients })
+            }
+            "slowlog" => {
+                let entries = iter
+                    .next()
+                    .map(Vec::<SlowLogEntry>::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::SlowLog { entries })
+            }
+            "latency" => {
+                let commands = iter
+                    .next()
+                    .map(Vec::<LatencyStats>::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let operations = iter
+                    .next()
+                    .map(Vec::<LatencyStats>::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::Latency {
+                    commands,
+                    operations,
+                })
+            }
+            "monitored" => {
+                let timestamp = iter
+                    .next()
+                    .map(Timestamp::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let client = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                let command = iter
+                    .next()
+                    .map(Vec::<String>::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::Monitored {
+                    timestamp,
+                    client: client as u64,
+                    command,
+                })
+            }
+            "compression" => {
+                let threshold = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::Compression {
+                    threshold: threshold as u64,
+                })
+            }
+            "hello" => {
+                let fields = iter.next().ok_or(MissingArgument)?;
+                let fields = fields.into_pairs().ok_or(InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                // the unknown fields are ignored, newer servers can send more
+                let (mut version, mut proto, mut features) = (None, None, None);
+                for (key, value) in fields {
+                    if key == "version" {
+                        let value =
+                            String::from_resp(value).map_err(|_| InvalidArgumentRespType)?;
+                        version = Some(value);
+                    } else if key == "proto" {
+                        let value = i64::from_resp(value).map_err(|_| InvalidArgumentRespType)?;
+                        proto = Some(value as u64);
+                    } else if key == "features" {
+                        let value =
+                            Vec::<String>::from_resp(value).map_err(|_| InvalidArgumentRespType)?;
+                        features = Some(value);
+                    }
+                }
+
+                Ok(Response::Hello {
+                    version: version.ok_or(MissingArgument)?,
+                    proto: proto.ok_or(MissingArgument)?,
+                    features: features.ok_or(MissingArgument)?,
+                })
+            }
+            "frame-size" => {
+                let size = iter
+                    .next()
+                    .map(i64::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                Ok(Response::FrameSize { size: size as u64 })
+            }
+            "batch" => {
+                let events = iter
+                    .next()
+                    .map(Vec::<Response>::from_resp)
+                    .ok_or(MissingArgument)?
+                    .map_err(|_| InvalidArgumentRespType)?;
+
+                if iter.next().is_some() {
+                    return Err(TooManyArguments);
+                }
+
+                // a batch only contains events, it can not be nested
+                if !events.iter().all(|e| matches!(e, Response::Event { .. })) {
+                    return Err(InvalidArgumentRespType);
+                }
+
+                Ok(Response::Batch { events })
+            }
+            _otherwise => Err(UnknownTypeName),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_name(name: &str) -> StreamName {
+        StreamName::new(name.to_owned()).unwrap()
+    }
+
+    fn round_trip(response: Response) {
+        let value: RespValue = response.clone().into();
+        assert_eq!(Response::from_resp(value).unwrap(), response);
+    }
+
+    #[test]
+    fn unsubscribed_round_trip() {
+        round_trip(Response::Unsubscribed {
+            stream: stream_name("payments"),
+        });
+    }
+
+    #[test]
+    fn subscription_notifications_round_trip() {
+        round_trip(Response::Subscribed {
+            stream: stream_name("payments"),
+        });
+        round_trip(Response::CaughtUp {
+            stream: stream_name("payments"),
+        });
+        round_trip(Response::RangeEnd {
+            stream: stream_name("payments"),
+        });
+        round_trip(Response::StreamTrimmed {
+            stream: stream_name("payments"),
+            first: EventNumber(4),
+        });
+        round_trip(Response::EventsDropped {
+            stream: stream_name("payments"),
+            count: 12,
+        });
+    }
+
+    #[test]
+    fn event_round_trip() {
+        let mut headers = EventHeaders::new();
+        headers.insert("source".to_owned(), "tests".to_owned());
+        round_trip(Response::Event {
+            stream: stream_name("payments"),
+            number: EventNumber(7),
+            timestamp: Timestamp(1_500_000_000_000),
+            event_name: EventName::new("Paid".to_owned()).unwrap(),
+            event_data: EventData(b"{\"amount\":42}".to_vec().into()),
+            headers,
+            global_number: None,
+        });
+    }
+
+    #[test]
+    fn range_round_trip() {
+        let event = Event {
+            number: EventNumber(0),
+            timestamp: Timestamp(1_500_000_000_000),
+            name: EventName::new("Paid".to_owned()).unwrap(),
+            data: EventData(b"42".to_vec().into()),
+            headers: EventHeaders::new(),
+        };
+        round_trip(Response::Range {
+            stream: stream_name("payments"),
+            events: vec![event],
+        });
+        round_trip(Response::Range {
+            stream: stream_name("payments"),
+            events: Vec::new(),
+        });
+    }
+
+    #[test]
+    fn replies_round_trip() {
+        round_trip(Response::Ok);
+        round_trip(Response::Published {
+            stream: stream_name("payments"),
+            number: EventNumber(3),
+        });
+        round_trip(Response::LastEventNumber {
+            stream: stream_name("payments"),
+            number: None,
+        });
+        round_trip(Response::LastEventNumber {
+            stream: stream_name("payments"),
+            number: Some(EventNumber(3)),
+        });
+        round_trip(Response::StreamNames {
+            streams: vec![stream_name("payments"), stream_name("orders")],
+        });
+        round_trip(Response::Pong { payload: None });
     }
 }
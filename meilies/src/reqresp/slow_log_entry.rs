@@ -0,0 +1,117 @@
+use std::fmt;
+
+use crate::resp::{FromResp, RespValue};
+use crate::stream::Timestamp;
+
+/// A command or a delivery of an event that exceeded the latency threshold of the slow log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowLogEntry {
+    /// The id of the entry, the ids of the entries follow each other.
+    pub id: u64,
+    pub timestamp: Timestamp,
+    /// The number of microseconds the command took to be processed,
+    /// or the event waited between its publication and its delivery.
+    pub duration: u64,
+    /// The address of the client that sent the command or received the event.
+    pub client: String,
+    /// The name and the arguments of the command, or `deliver` followed
+    /// by the stream and the number of the event delivered.
+    pub command: Vec<String>,
+}
+
+impl From<SlowLogEntry> for RespValue {
+    fn from(entry: SlowLogEntry) -> RespValue {
+        let command = entry
+            .command
+            .into_iter()
+            .map(RespValue::bulk_string)
+            .collect();
+
+        RespValue::Array(vec![
+            RespValue::Integer(entry.id as i64),
+            RespValue::Integer(entry.timestamp.0 as i64),
+            RespValue::Integer(entry.duration as i64),
+            RespValue::bulk_string(entry.client),
+            RespValue::Array(command),
+        ])
+    }
+}
+
+#[derive(Debug)]
+pub enum RespSlowLogEntryConvertError {
+    InvalidRespType,
+    InvalidArgumentRespType,
+    MissingArgument,
+    TooManyArguments,
+}
+
+impl fmt::Display for RespSlowLogEntryConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use RespSlowLogEntryConvertError::*;
+        match self {
+            InvalidRespType => write!(f, "invalid RESP type found, expected Array"),
+            InvalidArgumentRespType => write!(f, "Invalid argument resp type"),
+            MissingArgument => write!(f, "Missing argument"),
+            TooManyArguments => write!(f, "Too many arguments"),
+        }
+    }
+}
+
+fn integer_from_resp(value: RespValue) -> Result<u64, RespSlowLogEntryConvertError> {
+    match value {
+        RespValue::Integer(integer) if integer >= 0 => Ok(integer as u64),
+        _otherwise => Err(RespSlowLogEntryConvertError::InvalidArgumentRespType),
+    }
+}
+
+impl FromResp for SlowLogEntry {
+    type Error = RespSlowLogEntryConvertError;
+
+    fn from_resp(value: RespValue) -> Result<Self, Self::Error> {
+        use RespSlowLogEntryConvertError::*;
+
+        let mut iter = match value {
+            RespValue::Array(array) => array.into_iter(),
+            _otherwise => return Err(InvalidRespType),
+        };
+
+        let id = iter
+            .next()
+            .map(integer_from_resp)
+            .ok_or(MissingArgument)??;
+
+        let timestamp = iter
+            .next()
+            .map(integer_from_resp)
+            .ok_or(MissingArgument)??;
+
+        let duration = iter
+            .next()
+            .map(integer_from_resp)
+            .ok_or(MissingArgument)??;
+
+        let client = iter
+            .next()
+            .map(String::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        let command = iter
+            .next()
+            .map(Vec::<String>::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        if iter.next().is_some() {
+            return Err(TooManyArguments);
+        }
+
+        Ok(SlowLogEntry {
+            id,
+            timestamp: Timestamp(timestamp),
+            duration,
+            client,
+            command,
+        })
+    }
+}
@@ -1,204 +1,72 @@
-use std::{fmt, num, str};
-
-use bytes::{BufMut, BytesMut};
-use subslice::SubsliceExt;
+use bytes::BytesMut;
 use tokio::codec::{Decoder, Encoder};
-use tokio::io;
-
-use super::RespValue;
-
-const CRLF_NEWLINE: &[u8; 2] = &[b'\r', b'\n'];
-const SIMPLE_STRING_CHAR: u8 = b'+';
-const ERROR_CHAR: u8 = b'-';
-const INTEGER_CHAR: u8 = b':';
-const BULK_STRING_CHAR: u8 = b'$';
-const ARRAY_CHAR: u8 = b'*';
-
-#[derive(Debug)]
-pub enum RespMsgError {
-    InvalidPrefixByte(u8),
-    InvalidInteger(num::ParseIntError),
-    InvalidUtf8String(str::Utf8Error),
-    SimpleStringContainCrlf,
-    MissingBulkStringFinalCrlf,
-    IoError(io::Error),
-}
-
-impl fmt::Display for RespMsgError {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        use RespMsgError::*;
-        match self {
-            InvalidPrefixByte(byte) => write!(fmt, "invalid prefix byte: {:?}", byte),
-            InvalidInteger(error) => write!(fmt, "invalid integer: {}", error),
-            InvalidUtf8String(error) => write!(fmt, "invalid utf8 string: {}", error),
-            SimpleStringContainCrlf => write!(fmt, "simple string contain crlf"),
-            MissingBulkStringFinalCrlf => write!(fmt, "missing bulk string final crlf"),
-            IoError(error) => write!(fmt, "io error: {}", error),
-        }
-    }
-}
-
-impl From<io::Error> for RespMsgError {
-    fn from(error: io::Error) -> RespMsgError {
-        RespMsgError::IoError(error)
-    }
-}
-
-impl From<io::ErrorKind> for RespMsgError {
-    fn from(error: io::ErrorKind) -> RespMsgError {
-        RespMsgError::IoError(error.into())
-    }
-}
-
-impl From<num::ParseIntError> for RespMsgError {
-    fn from(error: num::ParseIntError) -> RespMsgError {
-        RespMsgError::InvalidInteger(error)
-    }
-}
-
-impl From<str::Utf8Error> for RespMsgError {
-    fn from(error: str::Utf8Error) -> RespMsgError {
-        RespMsgError::InvalidUtf8String(error)
-    }
-}
-
-fn decode_until_crlf(buf: &[u8]) -> Option<&[u8]> {
-    buf.find(CRLF_NEWLINE).map(|off| buf.split_at(off).0)
-}
 
-fn decode_simple_string(buf: &[u8]) -> Result<Option<(RespValue, usize)>, RespMsgError> {
-    match decode_until_crlf(buf) {
-        Some(bytes_string) => {
-            let string = str::from_utf8(bytes_string)?;
-            let advance = bytes_string.len() + CRLF_NEWLINE.len();
-            Ok(Some((RespValue::SimpleString(string.to_owned()), advance)))
-        }
-        None => Ok(None),
-    }
-}
+use super::protocol::{decode_bounded, decode_command, decode_compressed};
+use super::{RespMsgError, RespValue};
 
-fn decode_error(buf: &[u8]) -> Result<Option<(RespValue, usize)>, RespMsgError> {
-    match decode_until_crlf(buf) {
-        Some(bytes_string) => {
-            let string = str::from_utf8(bytes_string)?;
-            let advance = bytes_string.len() + CRLF_NEWLINE.len();
-            Ok(Some((RespValue::Error(string.to_owned()), advance)))
-        }
-        None => Ok(None),
-    }
-}
+/// The tokio codec of the RESP messages, a thin wrapper of the parsing and
+/// the encoding functions of the `protocol` module, which do no IO.
+#[derive(Debug, Default)]
+pub struct RespCodec;
 
-fn decode_integer(buf: &[u8]) -> Result<Option<(RespValue, usize)>, RespMsgError> {
-    match decode_until_crlf(buf) {
-        Some(bytes_string) => {
-            let string = str::from_utf8(bytes_string)?;
-            let integer = i64::from_str_radix(string, 10)?;
-            let advance = bytes_string.len() + CRLF_NEWLINE.len();
-            Ok(Some((RespValue::Integer(integer), advance)))
-        }
-        None => Ok(None),
+impl RespCodec {
+    /// Decodes a message, returns a `BulkStringTooLarge` error as soon as the length
+    /// of one of its bulk strings is read if it is greater than `max` bytes.
+    pub fn decode_bounded(
+        &mut self,
+        buf: &mut BytesMut,
+        max: Option<usize>,
+    ) -> Result<Option<RespValue>, RespMsgError> {
+        decode_bounded(buf, max)
     }
-}
 
-fn decode_bulk_string(buf: &[u8]) -> Result<Option<(RespValue, usize)>, RespMsgError> {
-    match decode_until_crlf(buf) {
-        Some(bytes_string) => {
-            let string = str::from_utf8(bytes_string)?;
-            let length = i64::from_str_radix(string, 10)?;
-
-            let advance = bytes_string.len() + CRLF_NEWLINE.len();
-            let buf = &buf[advance..];
-
-            match length {
-                len if len < 0 => Ok(Some((RespValue::Nil, advance))),
-                _ => {
-                    // FIXME handle overflows !!!
-                    if buf.len() as i64 >= length + CRLF_NEWLINE.len() as i64 {
-                        let bytes = match decode_until_crlf(buf) {
-                            Some(bytes_string) => bytes_string.to_vec(),
-                            None => return Err(RespMsgError::MissingBulkStringFinalCrlf),
-                        };
-
-                        let advance = advance + bytes.len() + CRLF_NEWLINE.len();
-                        Ok(Some((RespValue::BulkString(bytes), advance)))
-                    } else {
-                        Ok(None)
-                    }
-                }
-            }
-        }
-        None => Ok(None),
+    /// Decodes a command sent to the server, either as an array or inline if the
+    /// message does not start with an array prefix. The blank lines are skipped.
+    pub fn decode_command(
+        &mut self,
+        buf: &mut BytesMut,
+        max: Option<usize>,
+    ) -> Result<Option<RespValue>, RespMsgError> {
+        decode_command(buf, max)
     }
-}
 
-fn decode_array(buf: &[u8]) -> Result<Option<(RespValue, usize)>, RespMsgError> {
-    match decode_until_crlf(buf) {
-        Some(bytes_string) => {
-            let string = str::from_utf8(bytes_string)?;
-            let length = i64::from_str_radix(string, 10)?;
-
-            let mut advance = bytes_string.len() + CRLF_NEWLINE.len();
-
-            match length {
-                len if len < 0 => Ok(Some((RespValue::Nil, advance))),
-                _ => {
-                    let mut array = Vec::with_capacity(length as usize);
-                    for _ in 0..length {
-                        match decode_message(&buf[advance..]) {
-                            Ok(Some((msg, adv))) => {
-                                array.push(msg);
-                                advance += adv;
-                            }
-                            Ok(None) => return Ok(None),
-                            Err(e) => return Err(e),
-                        }
-                    }
-
-                    Ok(Some((RespValue::Array(array), advance)))
-                }
-            }
-        }
-        None => Ok(None),
+    /// Decodes a message that may have been compressed by `encode_compressed`.
+    pub fn decode_compressed(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<Option<RespValue>, RespMsgError> {
+        decode_compressed(buf, None)
     }
-}
 
-fn decode_message(buf: &[u8]) -> Result<Option<(RespValue, usize)>, RespMsgError> {
-    if buf.is_empty() {
-        return Ok(None);
+    /// Encodes a message, it is compressed with zstd if it is larger than `threshold`
+    /// bytes once encoded. The compressed message is prefixed by a `^` and its length.
+    pub fn encode_compressed(
+        &mut self,
+        msg: RespValue,
+        buf: &mut BytesMut,
+        threshold: usize,
+    ) -> Result<(), RespMsgError> {
+        msg.write_compressed(buf, threshold)
     }
 
-    let result = match buf[0] {
-        SIMPLE_STRING_CHAR => decode_simple_string(&buf[1..]),
-        ERROR_CHAR => decode_error(&buf[1..]),
-        INTEGER_CHAR => decode_integer(&buf[1..]),
-        BULK_STRING_CHAR => decode_bulk_string(&buf[1..]),
-        ARRAY_CHAR => decode_array(&buf[1..]),
-        invalid_byte => Err(RespMsgError::InvalidPrefixByte(invalid_byte)),
-    };
-
-    match result {
-        Ok(Some((msg, advance))) => Ok(Some((msg, advance + 1))),
-        Ok(None) => Ok(None),
-        Err(e) => Err(e),
+    /// Encodes a message, the bulk strings larger than `frame_size` bytes are sent in chunks
+    /// of at most `frame_size` bytes followed by an empty one, as RESP3 streamed strings.
+    pub fn encode_chunked(
+        &mut self,
+        msg: RespValue,
+        buf: &mut BytesMut,
+        frame_size: usize,
+    ) -> Result<(), RespMsgError> {
+        msg.write_chunked(buf, frame_size)
     }
 }
 
-#[derive(Debug, Default)]
-pub struct RespCodec;
-
 impl Decoder for RespCodec {
     type Item = RespValue;
     type Error = RespMsgError;
 
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match decode_message(buf) {
-            Ok(Some((msg, advance))) => {
-                buf.split_to(advance);
-                Ok(Some(msg))
-            }
-            Ok(None) => Ok(None),
-            Err(e) => Err(e),
-        }
+        decode_bounded(buf, None)
     }
 }
 
@@ -207,88 +75,17 @@ impl Encoder for RespCodec {
     type Error = RespMsgError;
 
     fn encode(&mut self, msg: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
-        match msg {
-            RespValue::SimpleString(string) => {
-                if string.as_bytes().find(CRLF_NEWLINE).is_some() {
-                    return Err(RespMsgError::SimpleStringContainCrlf);
-                }
-
-                buf.reserve(1 + string.len() + CRLF_NEWLINE.len());
-
-                buf.put_u8(SIMPLE_STRING_CHAR);
-                buf.put(string);
-                buf.put(&CRLF_NEWLINE[..]);
-
-                Ok(())
-            }
-            RespValue::Error(string) => {
-                if string.as_bytes().find(CRLF_NEWLINE).is_some() {
-                    return Err(RespMsgError::SimpleStringContainCrlf);
-                }
-
-                buf.reserve(1 + string.len() + CRLF_NEWLINE.len());
-
-                buf.put_u8(ERROR_CHAR);
-                buf.put(string);
-                buf.put(&CRLF_NEWLINE[..]);
-
-                Ok(())
-            }
-            RespValue::Integer(integer) => {
-                let integer_string = integer.to_string();
-                buf.reserve(1 + integer_string.len() + CRLF_NEWLINE.len());
-
-                buf.put_u8(INTEGER_CHAR);
-                buf.put(integer_string);
-                buf.put(&CRLF_NEWLINE[..]);
-
-                Ok(())
-            }
-            RespValue::BulkString(bytes_string) => {
-                let length = bytes_string.len();
-                let integer_string = length.to_string();
-                buf.reserve(1 + integer_string.len() + length + CRLF_NEWLINE.len() * 2);
-
-                buf.put_u8(BULK_STRING_CHAR);
-                buf.put(integer_string);
-                buf.put(&CRLF_NEWLINE[..]);
-                buf.put(bytes_string);
-                buf.put(&CRLF_NEWLINE[..]);
-
-                Ok(())
-            }
-            RespValue::Array(array) => {
-                let length = array.len();
-                let integer_string = length.to_string();
-                buf.reserve(1 + integer_string.len() + CRLF_NEWLINE.len());
-
-                buf.put_u8(ARRAY_CHAR);
-                buf.put(integer_string);
-                buf.put(&CRLF_NEWLINE[..]);
-
-                for msg in array {
-                    self.encode(msg, buf)?;
-                }
-
-                Ok(())
-            }
-            RespValue::Nil => {
-                // We chose to use the Bulk String to represent nil values.
-                let integer_string = "-1";
-                buf.reserve(1 + integer_string.len() + CRLF_NEWLINE.len());
-
-                buf.put_u8(BULK_STRING_CHAR);
-                buf.put(integer_string);
-                buf.put(&CRLF_NEWLINE[..]);
-
-                Ok(())
-            }
-        }
+        // the whole message is reserved at once and written without intermediate buffers
+        buf.reserve(msg.encoded_len());
+        msg.write_to(buf)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use bytes::Bytes;
+
+    use super::super::protocol::COMPRESSED_CHAR;
     use super::*;
 
     #[test]
@@ -336,20 +133,54 @@ mod tests {
         assert!(buf.is_empty());
     }
 
+    #[test]
+    fn encoded_len() {
+        let msg = RespValue::Array(vec![
+            RespValue::string("published"),
+            RespValue::Integer(i64::min_value()),
+            RespValue::Integer(i64::max_value()),
+            RespValue::Integer(0),
+            RespValue::bulk_string(vec![0u8; 1000]),
+            RespValue::Nil,
+            RespValue::Map(vec![(
+                RespValue::bulk_string("ratio"),
+                RespValue::Double(0.5),
+            )]),
+        ]);
+
+        let mut buf = BytesMut::new();
+        RespCodec.encode(msg.clone(), &mut buf).unwrap();
+
+        assert_eq!(msg.encoded_len(), buf.len());
+        assert_eq!(RespCodec.decode(&mut buf).unwrap(), Some(msg));
+    }
+
     #[test]
     fn one_bulk_string() {
         let mut buf = BytesMut::new();
 
-        let inmsg = RespValue::BulkString(vec![]);
+        let inmsg = RespValue::bulk_string(Bytes::new());
+        RespCodec.encode(inmsg.clone(), &mut buf).unwrap();
+        let outmsg = RespCodec.decode(&mut buf).unwrap();
+
+        assert_eq!(Some(inmsg), outmsg);
+        assert!(buf.is_empty());
+
+        let mut buf = BytesMut::new();
+
+        let inmsg = RespValue::bulk_string(vec![1u8, 2, 3, 4, 5, 35, 70]);
         RespCodec.encode(inmsg.clone(), &mut buf).unwrap();
         let outmsg = RespCodec.decode(&mut buf).unwrap();
 
         assert_eq!(Some(inmsg), outmsg);
         assert!(buf.is_empty());
+    }
 
+    #[test]
+    fn bulk_string_with_crlf() {
         let mut buf = BytesMut::new();
 
-        let inmsg = RespValue::BulkString(vec![1, 2, 3, 4, 5, 35, 70]);
+        let inmsg = RespValue::bulk_string(&b"hello\r\nworld\r\n"[..]);
         RespCodec.encode(inmsg.clone(), &mut buf).unwrap();
         let outmsg = RespCodec.decode(&mut buf).unwrap();
 
@@ -368,7 +199,7 @@ mod tests {
         assert_eq!(Some(inmsg), outmsg);
         assert!(buf.is_empty());
 
-        let inmsg = RespValue::Array(vec![RespValue::BulkString(b"hello".to_vec())]);
+        let inmsg = RespValue::Array(vec![RespValue::bulk_string(b"hello".to_vec())]);
         RespCodec.encode(inmsg.clone(), &mut buf).unwrap();
         let outmsg = RespCodec.decode(&mut buf).unwrap();
 
@@ -379,7 +210,7 @@ mod tests {
             RespValue::SimpleString("hello".to_owned()),
             RespValue::Error("what the f*ck!".to_owned()),
             RespValue::Integer(25),
-            RespValue::BulkString(b"hello".to_vec()),
+            RespValue::bulk_string(b"hello".to_vec()),
             RespValue::Array(vec![RespValue::Integer(45)]),
         ]);
         RespCodec.encode(inmsg.clone(), &mut buf).unwrap();
@@ -471,9 +302,9 @@ mod tests {
     fn multiple_bulk_string() {
         let mut buf = BytesMut::new();
 
-        let inmsg1 = RespValue::BulkString(vec![8, 7, 6, 5, 4]);
-        let inmsg2 = RespValue::BulkString(vec![1, 2, 3, 4, 5, 35, 70]);
-        let inmsg3 = RespValue::BulkString(vec![]);
+        let inmsg1 = RespValue::bulk_string(vec![8u8, 7, 6, 5, 4]);
+        let inmsg2 = RespValue::bulk_string(vec![1u8, 2, 3, 4, 5, 35, 70]);
+        let inmsg3 = RespValue::bulk_string(Bytes::new());
 
         RespCodec.encode(inmsg1.clone(), &mut buf).unwrap();
         RespCodec.encode(inmsg2.clone(), &mut buf).unwrap();
@@ -496,13 +327,13 @@ mod tests {
         let inmsg1 = RespValue::SimpleString("kiki".to_owned());
         let inmsg2 = RespValue::Error("whoops, it is and error".to_owned());
         let inmsg3 = RespValue::Integer(12);
-        let inmsg4 = RespValue::BulkString(vec![8, 7, 6, 5, 4]);
-        let inmsg5 = RespValue::BulkString(vec![1, 2, 3, 4, 5, 35, 70]);
+        let inmsg4 = RespValue::bulk_string(vec![8u8, 7, 6, 5, 4]);
+        let inmsg5 = RespValue::bulk_string(vec![1u8, 2, 3, 4, 5, 35, 70]);
         let inmsg6 = RespValue::Array(vec![
             RespValue::SimpleString("hello".to_owned()),
             RespValue::Error("what the f*ck!".to_owned()),
             RespValue::Integer(25),
-            RespValue::BulkString(b"hello".to_vec()),
+            RespValue::bulk_string(b"hello".to_vec()),
             RespValue::Array(vec![RespValue::Integer(45)]),
         ]);
 
@@ -553,7 +384,7 @@ mod tests {
     fn partial_bulk_string() {
         let mut buf = BytesMut::new();
 
-        let inmsg = RespValue::BulkString(vec![1, 2, 3, 4, 5, 35, 70]);
+        let inmsg = RespValue::bulk_string(vec![1u8, 2, 3, 4, 5, 35, 70]);
 
         RespCodec.encode(inmsg.clone(), &mut buf).unwrap();
 
@@ -577,7 +408,7 @@ mod tests {
             RespValue::SimpleString("hello".to_owned()),
             RespValue::Error("what the f*ck!".to_owned()),
             RespValue::Integer(25),
-            RespValue::BulkString(b"hello".to_vec()),
+            RespValue::bulk_string(b"hello".to_vec()),
             RespValue::Array(vec![RespValue::Integer(45)]),
         ]);
 
@@ -600,4 +431,187 @@ mod tests {
         assert_eq!(Some(inmsg), outmsg);
         assert!(buf.is_empty());
     }
+
+    #[test]
+    fn bounded_bulk_string() {
+        let mut buf = BytesMut::new();
+
+        let inmsg = RespValue::Array(vec![RespValue::bulk_string(vec![0u8; 100])]);
+        RespCodec.encode(inmsg.clone(), &mut buf).unwrap();
+
+        // only the length of the bulk string is needed to reject it
+        buf.truncate(10);
+        match RespCodec.decode_bounded(&mut buf, Some(99)) {
+            Err(RespMsgError::BulkStringTooLarge {
+                length: 100,
+                max: 99,
+            }) => (),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+
+        let mut buf = BytesMut::new();
+        RespCodec.encode(inmsg.clone(), &mut buf).unwrap();
+        let outmsg = RespCodec.decode_bounded(&mut buf, Some(100)).unwrap();
+
+        assert_eq!(Some(inmsg), outmsg);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn chunked_bulk_string() {
+        let mut buf = BytesMut::new();
+
+        let inmsg = RespValue::Array(vec![
+            RespValue::bulk_string("publish"),
+            RespValue::bulk_string("hello world"),
+        ]);
+        RespCodec
+            .encode_chunked(inmsg.clone(), &mut buf, 4)
+            .unwrap();
+
+        assert_eq!(inmsg.len_with(Some(4)), buf.len());
+        assert!(buf.ends_with(b"$?\r\n;4\r\nhell\r\n;4\r\no wo\r\n;3\r\nrld\r\n;0\r\n"));
+
+        let buf2 = buf.split_off(buf.len() - 4);
+        let outmsg = RespCodec.decode(&mut buf).unwrap();
+
+        assert_eq!(None, outmsg);
+
+        buf.unsplit(buf2);
+        let mut bounded = buf.clone();
+        let outmsg = RespCodec.decode(&mut buf).unwrap();
+
+        assert_eq!(Some(inmsg), outmsg);
+        assert!(buf.is_empty());
+
+        match RespCodec.decode_bounded(&mut bounded, Some(10)) {
+            Err(RespMsgError::BulkStringTooLarge {
+                length: 11,
+                max: 10,
+            }) => (),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn compressed_array() {
+        let mut buf = BytesMut::new();
+
+        let inmsg = RespValue::Array(vec![
+            RespValue::SimpleString("event".to_owned()),
+            RespValue::bulk_string(b"hello".repeat(100)),
+        ]);
+        RespCodec
+            .encode_compressed(inmsg.clone(), &mut buf, 100)
+            .unwrap();
+
+        assert_eq!(buf.first(), Some(&COMPRESSED_CHAR));
+        assert!(buf.len() < 100);
+
+        let buf2 = buf.split_off(5);
+        let outmsg = RespCodec.decode_compressed(&mut buf).unwrap();
+
+        assert_eq!(None, outmsg);
+
+        buf.unsplit(buf2);
+        let outmsg = RespCodec.decode_compressed(&mut buf).unwrap();
+
+        assert_eq!(Some(inmsg), outmsg);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn uncompressed_below_threshold() {
+        let mut buf = BytesMut::new();
+
+        let inmsg = RespValue::bulk_string(b"hello".to_vec());
+        RespCodec
+            .encode_compressed(inmsg.clone(), &mut buf, 100)
+            .unwrap();
+        let outmsg = RespCodec.decode_compressed(&mut buf).unwrap();
+
+        assert_eq!(Some(inmsg), outmsg);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn resp3_types() {
+        let mut buf = BytesMut::new();
+
+        let inmsg = RespValue::Push(vec![
+            RespValue::Map(vec![
+                (RespValue::bulk_string("proto"), RespValue::Integer(3)),
+                (RespValue::bulk_string("ratio"), RespValue::Double(0.5)),
+            ]),
+            RespValue::Double(f64::INFINITY),
+            RespValue::Nil,
+        ]);
+        RespCodec.encode(inmsg.clone(), &mut buf).unwrap();
+        let outmsg = RespCodec.decode(&mut buf).unwrap();
+
+        assert_eq!(Some(inmsg), outmsg);
+        assert!(buf.is_empty());
+
+        buf.extend_from_slice(b"_\r\n");
+        let outmsg = RespCodec.decode(&mut buf).unwrap();
+
+        assert_eq!(Some(RespValue::Nil), outmsg);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn resp3_into_resp2() {
+        let inmsg = RespValue::Push(vec![RespValue::Map(vec![(
+            RespValue::bulk_string("ratio"),
+            RespValue::Double(0.5),
+        )])]);
+
+        let outmsg = RespValue::Array(vec![RespValue::Array(vec![
+            RespValue::bulk_string("ratio"),
+            RespValue::bulk_string("0.5"),
+        ])]);
+
+        assert_eq!(inmsg.into_resp2(), outmsg);
+    }
+
+    #[test]
+    fn inline_command() {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"\r\npublish  my-stream hello\r\nping\n");
+
+        let outmsg = RespCodec.decode_command(&mut buf, None).unwrap();
+        let inmsg = RespValue::Array(vec![
+            RespValue::bulk_string("publish"),
+            RespValue::bulk_string("my-stream"),
+            RespValue::bulk_string("hello"),
+        ]);
+
+        assert_eq!(Some(inmsg), outmsg);
+
+        let outmsg = RespCodec.decode_command(&mut buf, None).unwrap();
+        let inmsg = RespValue::Array(vec![RespValue::bulk_string("ping")]);
+
+        assert_eq!(Some(inmsg), outmsg);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn partial_inline_command() {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"ping hel");
+
+        let outmsg = RespCodec.decode_command(&mut buf, None).unwrap();
+
+        assert_eq!(None, outmsg);
+
+        buf.extend_from_slice(b"lo\r\n");
+        let outmsg = RespCodec.decode_command(&mut buf, None).unwrap();
+        let inmsg = RespValue::Array(vec![
+            RespValue::bulk_string("ping"),
+            RespValue::bulk_string("hello"),
+        ]);
+
+        assert_eq!(Some(inmsg), outmsg);
+        assert!(buf.is_empty());
+    }
 }
@@ -1,4 +1,5 @@
 use super::RespValue;
+use bytes::Bytes;
 use std::fmt;
 use std::string::FromUtf8Error;
 
@@ -42,7 +43,9 @@ impl FromResp for String {
         match value {
             RespValue::SimpleString(string) => Ok(string),
             RespValue::Error(string) => Ok(string),
-            RespValue::BulkString(bytes) => String::from_utf8(bytes).map_err(InvalidUtf8String),
+            RespValue::BulkString(bytes) => {
+                String::from_utf8(bytes.to_vec()).map_err(InvalidUtf8String)
+            }
             _ => Err(InvalidRespType),
         }
     }
@@ -97,6 +100,19 @@ impl FromResp for Vec<u8> {
         match value {
             RespValue::SimpleString(string) => Ok(string.into_bytes()),
             RespValue::Error(string) => Ok(string.into_bytes()),
+            RespValue::BulkString(bytes) => Ok(bytes.to_vec()),
+            _ => Err(RespBytesConvertError::InvalidRespType),
+        }
+    }
+}
+
+impl FromResp for Bytes {
+    type Error = RespBytesConvertError;
+
+    fn from_resp(value: RespValue) -> Result<Self, Self::Error> {
+        match value {
+            RespValue::SimpleString(string) => Ok(Bytes::from(string)),
+            RespValue::Error(string) => Ok(Bytes::from(string)),
             RespValue::BulkString(bytes) => Ok(bytes),
             _ => Err(RespBytesConvertError::InvalidRespType),
         }
@@ -1,10 +1,14 @@
+#[cfg(feature = "tokio")]
 mod codec;
 mod from_resp;
+mod protocol;
 mod resp_value;
 
-pub use self::codec::{RespCodec, RespMsgError};
+#[cfg(feature = "tokio")]
+pub use self::codec::RespCodec;
 pub use self::from_resp::{
     FromResp, RespBytesConvertError, RespIntConvertError, RespStringConvertError,
     RespVecConvertError,
 };
+pub use self::protocol::{RespMsgError, RespParser};
 pub use self::resp_value::RespValue;
@@ -0,0 +1,997 @@
+use std::io::Read;
+use std::{fmt, io, num, str};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use subslice::SubsliceExt;
+
+use super::RespValue;
+
+const CRLF_NEWLINE: &[u8; 2] = &[b'\r', b'\n'];
+const SIMPLE_STRING_CHAR: u8 = b'+';
+const ERROR_CHAR: u8 = b'-';
+const INTEGER_CHAR: u8 = b':';
+const BULK_STRING_CHAR: u8 = b'$';
+const ARRAY_CHAR: u8 = b'*';
+pub(crate) const COMPRESSED_CHAR: u8 = b'^';
+const NULL_CHAR: u8 = b'_';
+const DOUBLE_CHAR: u8 = b',';
+const MAP_CHAR: u8 = b'%';
+const PUSH_CHAR: u8 = b'>';
+const CHUNK_CHAR: u8 = b';';
+
+/// The length of a bulk string sent in chunks, it is followed by the chunks and an empty one.
+const STREAMED_LENGTH: &[u8] = b"?";
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// The longest inline command accepted, without a newline the message is rejected above.
+const MAX_INLINE_LENGTH: usize = 64 * 1024;
+
+/// The largest message accepted once decompressed, a few compressed bytes can expand to any size.
+const MAX_DECOMPRESSED_LENGTH: usize = 512 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum RespMsgError {
+    InvalidPrefixByte(u8),
+    InvalidInteger(num::ParseIntError),
+    InvalidDouble(num::ParseFloatError),
+    InvalidUtf8String(str::Utf8Error),
+    SimpleStringContainCrlf,
+    MissingBulkStringFinalCrlf,
+    BulkStringTooLarge { length: usize, max: usize },
+    InvalidCompressedMessage,
+    CompressedMessageTooLarge { max: usize },
+    InlineCommandTooLong,
+    IoError(io::Error),
+}
+
+impl fmt::Display for RespMsgError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use RespMsgError::*;
+        match self {
+            InvalidPrefixByte(byte) => write!(fmt, "invalid prefix byte: {:?}", byte),
+            InvalidInteger(error) => write!(fmt, "invalid integer: {}", error),
+            InvalidDouble(error) => write!(fmt, "invalid double: {}", error),
+            InvalidUtf8String(error) => write!(fmt, "invalid utf8 string: {}", error),
+            SimpleStringContainCrlf => write!(fmt, "simple string contain crlf"),
+            MissingBulkStringFinalCrlf => write!(fmt, "missing bulk string final crlf"),
+            BulkStringTooLarge { length, max } => write!(
+                fmt,
+                "bulk string of {} bytes exceeds the maximum size of {} bytes",
+                length, max
+            ),
+            InvalidCompressedMessage => write!(fmt, "invalid compressed message"),
+            CompressedMessageTooLarge { max } => write!(
+                fmt,
+                "compressed message exceeds the maximum size of {} bytes once decompressed",
+                max
+            ),
+            InlineCommandTooLong => write!(fmt, "inline command too long"),
+            IoError(error) => write!(fmt, "io error: {}", error),
+        }
+    }
+}
+
+impl From<io::Error> for RespMsgError {
+    fn from(error: io::Error) -> RespMsgError {
+        RespMsgError::IoError(error)
+    }
+}
+
+impl From<io::ErrorKind> for RespMsgError {
+    fn from(error: io::ErrorKind) -> RespMsgError {
+        RespMsgError::IoError(error.into())
+    }
+}
+
+impl From<num::ParseIntError> for RespMsgError {
+    fn from(error: num::ParseIntError) -> RespMsgError {
+        RespMsgError::InvalidInteger(error)
+    }
+}
+
+impl From<num::ParseFloatError> for RespMsgError {
+    fn from(error: num::ParseFloatError) -> RespMsgError {
+        RespMsgError::InvalidDouble(error)
+    }
+}
+
+impl From<str::Utf8Error> for RespMsgError {
+    fn from(error: str::Utf8Error) -> RespMsgError {
+        RespMsgError::InvalidUtf8String(error)
+    }
+}
+
+/// Makes the bytes of a bulk string from the part of the buffer that contains them.
+type Slicer<'a> = &'a dyn Fn(&[u8]) -> Bytes;
+
+/// The arguments of an inline command, separated by spaces.
+type InlineArgs = Vec<Vec<u8>>;
+
+fn decode_until_crlf(buf: &[u8]) -> Option<&[u8]> {
+    buf.find(CRLF_NEWLINE).map(|off| buf.split_at(off).0)
+}
+
+/// Returns the slice of the frame that is the given subset of it, without copy.
+fn slice_ref(frame: &Bytes, subset: &[u8]) -> Bytes {
+    let start = subset.as_ptr() as usize - frame.as_ptr() as usize;
+    frame.slice(start, start + subset.len())
+}
+
+/// Decodes a whole message from a frame, the bulk strings are slices of it.
+fn decode_frame(
+    frame: &Bytes,
+    max: Option<usize>,
+) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    decode_message(frame, max, &|subset| slice_ref(frame, subset))
+}
+
+fn decode_simple_string(buf: &[u8]) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    match decode_until_crlf(buf) {
+        Some(bytes_string) => {
+            let string = str::from_utf8(bytes_string)?;
+            let advance = bytes_string.len() + CRLF_NEWLINE.len();
+            Ok(Some((RespValue::SimpleString(string.to_owned()), advance)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn decode_error(buf: &[u8]) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    match decode_until_crlf(buf) {
+        Some(bytes_string) => {
+            let string = str::from_utf8(bytes_string)?;
+            let advance = bytes_string.len() + CRLF_NEWLINE.len();
+            Ok(Some((RespValue::Error(string.to_owned()), advance)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn decode_integer(buf: &[u8]) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    match decode_until_crlf(buf) {
+        Some(bytes_string) => {
+            let string = str::from_utf8(bytes_string)?;
+            let integer = string.parse::<i64>()?;
+            let advance = bytes_string.len() + CRLF_NEWLINE.len();
+            Ok(Some((RespValue::Integer(integer), advance)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn decode_null(buf: &[u8]) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    match decode_until_crlf(buf) {
+        Some([]) => Ok(Some((RespValue::Nil, CRLF_NEWLINE.len()))),
+        Some(_) => Err(RespMsgError::InvalidPrefixByte(NULL_CHAR)),
+        None => Ok(None),
+    }
+}
+
+fn decode_double(buf: &[u8]) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    match decode_until_crlf(buf) {
+        Some(bytes_string) => {
+            let string = str::from_utf8(bytes_string)?;
+            let double = match string {
+                "inf" | "+inf" => f64::INFINITY,
+                "-inf" => f64::NEG_INFINITY,
+                "nan" => f64::NAN,
+                string => string.parse()?,
+            };
+            let advance = bytes_string.len() + CRLF_NEWLINE.len();
+            Ok(Some((RespValue::Double(double), advance)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn decode_bulk_string(
+    buf: &[u8],
+    max: Option<usize>,
+    slice: Slicer,
+) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    match decode_until_crlf(buf) {
+        Some(bytes_string) if bytes_string == STREAMED_LENGTH => {
+            let advance = bytes_string.len() + CRLF_NEWLINE.len();
+            match decode_streamed_string(&buf[advance..], max, slice)? {
+                Some((msg, adv)) => Ok(Some((msg, advance + adv))),
+                None => Ok(None),
+            }
+        }
+        Some(bytes_string) => {
+            let string = str::from_utf8(bytes_string)?;
+            let length = string.parse::<i64>()?;
+
+            // the length is checked before waiting for the whole bulk string to be buffered
+            if let Some(max) = max {
+                if length > 0 && length as u64 > max as u64 {
+                    let length = length as usize;
+                    return Err(RespMsgError::BulkStringTooLarge { length, max });
+                }
+            }
+
+            let advance = bytes_string.len() + CRLF_NEWLINE.len();
+            let buf = &buf[advance..];
+
+            if length < 0 {
+                return Ok(Some((RespValue::Nil, advance)));
+            }
+
+            // the bytes can contain crlfs, the length must be used
+            let length = length as usize;
+            if buf.len() < length + CRLF_NEWLINE.len() {
+                return Ok(None);
+            }
+            if &buf[length..length + CRLF_NEWLINE.len()] != CRLF_NEWLINE {
+                return Err(RespMsgError::MissingBulkStringFinalCrlf);
+            }
+
+            let bytes = slice(&buf[..length]);
+            let advance = advance + length + CRLF_NEWLINE.len();
+            Ok(Some((RespValue::BulkString(bytes), advance)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Decodes the chunks of a bulk string sent in chunks, each one is prefixed by
+/// a `;` and its length, the bulk string ends with an empty chunk.
+fn decode_streamed_string(
+    buf: &[u8],
+    max: Option<usize>,
+    slice: Slicer,
+) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    let mut bytes = BytesMut::new();
+    let mut length = 0;
+    let mut advance = 0;
+
+    loop {
+        let chunk = &buf[advance..];
+        match chunk.first() {
+            Some(&CHUNK_CHAR) => (),
+            Some(&invalid_byte) => return Err(RespMsgError::InvalidPrefixByte(invalid_byte)),
+            None => return Ok(None),
+        }
+
+        let bytes_string = match decode_until_crlf(&chunk[1..]) {
+            Some(bytes_string) => bytes_string,
+            None => return Ok(None),
+        };
+        let chunk_length: usize = str::from_utf8(bytes_string)?.parse()?;
+        let start = 1 + bytes_string.len() + CRLF_NEWLINE.len();
+
+        if chunk_length == 0 {
+            let advance = advance + start;
+            return Ok(Some((RespValue::BulkString(bytes.freeze()), advance)));
+        }
+
+        // the whole bulk string is rejected as soon as one of its chunks exceeds the
+        // maximum, the length of the chunk is checked before being added to the total
+        let max_length = max.unwrap_or(usize::MAX);
+        if chunk_length > max_length - length {
+            let length = length.saturating_add(chunk_length);
+            return Err(RespMsgError::BulkStringTooLarge {
+                length,
+                max: max_length,
+            });
+        }
+        length += chunk_length;
+
+        let end = match start.checked_add(chunk_length) {
+            Some(end) if end <= usize::MAX - CRLF_NEWLINE.len() => end,
+            _otherwise => {
+                return Err(RespMsgError::BulkStringTooLarge {
+                    length,
+                    max: max_length,
+                })
+            }
+        };
+        if chunk.len() < end + CRLF_NEWLINE.len() {
+            return Ok(None);
+        }
+        if &chunk[end..end + CRLF_NEWLINE.len()] != CRLF_NEWLINE {
+            return Err(RespMsgError::MissingBulkStringFinalCrlf);
+        }
+
+        bytes.extend_from_slice(&slice(&chunk[start..end]));
+        advance += end + CRLF_NEWLINE.len();
+    }
+}
+
+fn decode_array(
+    buf: &[u8],
+    max: Option<usize>,
+    slice: Slicer,
+) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    match decode_until_crlf(buf) {
+        Some(bytes_string) => {
+            let string = str::from_utf8(bytes_string)?;
+            let length = string.parse::<i64>()?;
+
+            let mut advance = bytes_string.len() + CRLF_NEWLINE.len();
+
+            match length {
+                len if len < 0 => Ok(Some((RespValue::Nil, advance))),
+                _ => {
+                    // the length is chosen by the peer, each element takes at least a byte
+                    let capacity = (length as u64).min(buf[advance..].len() as u64);
+                    let mut array = Vec::with_capacity(capacity as usize);
+                    for _ in 0..length {
+                        match decode_message(&buf[advance..], max, slice) {
+                            Ok(Some((msg, adv))) => {
+                                array.push(msg);
+                                advance += adv;
+                            }
+                            Ok(None) => return Ok(None),
+                            Err(e) => return Err(e),
+                        }
+                    }
+
+                    Ok(Some((RespValue::Array(array), advance)))
+                }
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+fn decode_map(
+    buf: &[u8],
+    max: Option<usize>,
+    slice: Slicer,
+) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    match decode_until_crlf(buf) {
+        Some(bytes_string) => {
+            let string = str::from_utf8(bytes_string)?;
+            let length: usize = string.parse()?;
+
+            let mut advance = bytes_string.len() + CRLF_NEWLINE.len();
+            let mut pairs = Vec::with_capacity(length.min(buf[advance..].len()));
+            for _ in 0..length {
+                let key = match decode_message(&buf[advance..], max, slice)? {
+                    Some((key, adv)) => {
+                        advance += adv;
+                        key
+                    }
+                    None => return Ok(None),
+                };
+                match decode_message(&buf[advance..], max, slice)? {
+                    Some((value, adv)) => {
+                        pairs.push((key, value));
+                        advance += adv;
+                    }
+                    None => return Ok(None),
+                }
+            }
+
+            Ok(Some((RespValue::Map(pairs), advance)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn decode_push(
+    buf: &[u8],
+    max: Option<usize>,
+    slice: Slicer,
+) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    match decode_array(buf, max, slice)? {
+        Some((RespValue::Array(array), advance)) => Ok(Some((RespValue::Push(array), advance))),
+        Some((_nil, _)) => Err(RespMsgError::InvalidPrefixByte(PUSH_CHAR)),
+        None => Ok(None),
+    }
+}
+
+fn decode_message(
+    buf: &[u8],
+    max: Option<usize>,
+    slice: Slicer,
+) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    if buf.is_empty() {
+        return Ok(None);
+    }
+
+    let result = match buf[0] {
+        SIMPLE_STRING_CHAR => decode_simple_string(&buf[1..]),
+        ERROR_CHAR => decode_error(&buf[1..]),
+        INTEGER_CHAR => decode_integer(&buf[1..]),
+        BULK_STRING_CHAR => decode_bulk_string(&buf[1..], max, slice),
+        ARRAY_CHAR => decode_array(&buf[1..], max, slice),
+        NULL_CHAR => decode_null(&buf[1..]),
+        DOUBLE_CHAR => decode_double(&buf[1..]),
+        MAP_CHAR => decode_map(&buf[1..], max, slice),
+        PUSH_CHAR => decode_push(&buf[1..], max, slice),
+        invalid_byte => Err(RespMsgError::InvalidPrefixByte(invalid_byte)),
+    };
+
+    match result {
+        Ok(Some((msg, advance))) => Ok(Some((msg, advance + 1))),
+        Ok(None) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Decodes a message compressed with zstd: the length of the compressed
+/// bytes, the compressed bytes and then a final crlf.
+fn decode_compressed_frame(
+    buf: &[u8],
+    max: Option<usize>,
+) -> Result<Option<(RespValue, usize)>, RespMsgError> {
+    let bytes_string = match decode_until_crlf(buf) {
+        Some(bytes_string) => bytes_string,
+        None => return Ok(None),
+    };
+
+    let length: usize = str::from_utf8(bytes_string)?.parse()?;
+    let start = bytes_string.len() + CRLF_NEWLINE.len();
+    let end = match start.checked_add(length) {
+        Some(end) if end <= usize::MAX - CRLF_NEWLINE.len() => end,
+        _otherwise => return Err(RespMsgError::InvalidCompressedMessage),
+    };
+
+    // the compressed bytes can contain crlfs, the length must be used
+    if buf.len() < end + CRLF_NEWLINE.len() {
+        return Ok(None);
+    }
+    if &buf[end..end + CRLF_NEWLINE.len()] != CRLF_NEWLINE {
+        return Err(RespMsgError::MissingBulkStringFinalCrlf);
+    }
+
+    // the decoder is stopped one byte after the limit to know if it is exceeded
+    let mut decompressed = Vec::new();
+    let decoder = zstd::Decoder::new(&buf[start..end])?;
+    decoder
+        .take(MAX_DECOMPRESSED_LENGTH as u64 + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() > MAX_DECOMPRESSED_LENGTH {
+        return Err(RespMsgError::CompressedMessageTooLarge {
+            max: MAX_DECOMPRESSED_LENGTH,
+        });
+    }
+
+    let decompressed = Bytes::from(decompressed);
+    match decode_frame(&decompressed, max)? {
+        Some((msg, advance)) if advance == decompressed.len() => {
+            Ok(Some((msg, end + CRLF_NEWLINE.len())))
+        }
+        _otherwise => Err(RespMsgError::InvalidCompressedMessage),
+    }
+}
+
+/// Decodes an inline command: the arguments separated by spaces on a single line, as typed
+/// in a telnet session. The arguments are empty for a blank line.
+fn decode_inline(buf: &[u8]) -> Result<Option<(InlineArgs, usize)>, RespMsgError> {
+    let end = match buf.iter().position(|b| *b == b'\n') {
+        Some(end) => end,
+        None if buf.len() > MAX_INLINE_LENGTH => return Err(RespMsgError::InlineCommandTooLong),
+        None => return Ok(None),
+    };
+
+    // the final carriage return is removed with the spaces
+    let args = buf[..end]
+        .split(u8::is_ascii_whitespace)
+        .filter(|arg| !arg.is_empty())
+        .map(ToOwned::to_owned)
+        .collect();
+
+    Ok(Some((args, end + 1)))
+}
+
+/// Decodes a message, returns a `BulkStringTooLarge` error as soon as the length
+/// of one of its bulk strings is read if it is greater than `max` bytes.
+pub(crate) fn decode_bounded(
+    buf: &mut BytesMut,
+    max: Option<usize>,
+) -> Result<Option<RespValue>, RespMsgError> {
+    // the message is first measured without copying its bulk strings,
+    // they are then sliced from the frame split from the buffer
+    let advance = match decode_message(buf, max, &|_| Bytes::new())? {
+        Some((_msg, advance)) => advance,
+        None => return Ok(None),
+    };
+
+    let frame = buf.split_to(advance).freeze();
+    match decode_frame(&frame, max)? {
+        Some((msg, _advance)) => Ok(Some(msg)),
+        None => unreachable!("the frame contains a whole message"),
+    }
+}
+
+/// Decodes a command sent to the server, either as an array or inline if the
+/// message does not start with an array prefix. The blank lines are skipped.
+pub(crate) fn decode_command(
+    buf: &mut BytesMut,
+    max: Option<usize>,
+) -> Result<Option<RespValue>, RespMsgError> {
+    loop {
+        match buf.first() {
+            Some(&ARRAY_CHAR) => return decode_bounded(buf, max),
+            Some(_) => (),
+            None => return Ok(None),
+        }
+
+        let (args, advance) = match decode_inline(buf)? {
+            Some(inline) => inline,
+            None => return Ok(None),
+        };
+        buf.split_to(advance);
+
+        if args.is_empty() {
+            continue;
+        }
+
+        if let Some(max) = max {
+            if let Some(length) = args.iter().map(Vec::len).find(|length| *length > max) {
+                return Err(RespMsgError::BulkStringTooLarge { length, max });
+            }
+        }
+
+        let args = args.into_iter().map(RespValue::bulk_string).collect();
+        return Ok(Some(RespValue::Array(args)));
+    }
+}
+
+/// Decodes a message that may have been compressed by `RespValue::write_compressed`.
+pub(crate) fn decode_compressed(
+    buf: &mut BytesMut,
+    max: Option<usize>,
+) -> Result<Option<RespValue>, RespMsgError> {
+    if buf.first() != Some(&COMPRESSED_CHAR) {
+        return decode_bounded(buf, max);
+    }
+
+    match decode_compressed_frame(&buf[1..], max) {
+        Ok(Some((msg, advance))) => {
+            buf.split_to(advance + 1);
+            Ok(Some(msg))
+        }
+        Ok(None) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// What a parser expects the messages it decodes to be.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    Messages,
+    /// The commands sent to a server, they can be sent inline.
+    Commands,
+    /// The messages sent by a server that negotiated the compression.
+    Compressed,
+}
+
+/// An incremental parser of RESP messages that does no IO: the bytes are fed to it as they
+/// are received, in pieces of any size, and the messages are taken out once whole. It can
+/// be used from blocking code or from anything that can not use the tokio codecs.
+#[derive(Debug)]
+pub struct RespParser {
+    buf: BytesMut,
+    mode: Mode,
+    max_bulk_len: Option<usize>,
+}
+
+impl Default for RespParser {
+    fn default() -> RespParser {
+        RespParser::new()
+    }
+}
+
+impl RespParser {
+    /// Returns a parser of the messages, as written by `RespValue::write_to`.
+    pub fn new() -> RespParser {
+        RespParser {
+            buf: BytesMut::new(),
+            mode: Mode::Messages,
+            max_bulk_len: None,
+        }
+    }
+
+    /// Returns a parser of the commands received by a server, the inline commands
+    /// typed in a telnet session are parsed as arrays of bulk strings.
+    pub fn commands() -> RespParser {
+        RespParser {
+            mode: Mode::Commands,
+            ..RespParser::new()
+        }
+    }
+
+    /// Returns a parser of the messages that may be compressed, as written
+    /// by `RespValue::write_compressed`.
+    pub fn compressed() -> RespParser {
+        RespParser {
+            mode: Mode::Compressed,
+            ..RespParser::new()
+        }
+    }
+
+    /// Rejects the bulk strings larger than `max` bytes with a `BulkStringTooLarge` error as
+    /// soon as their length is read, once decompressed for the compressed messages.
+    pub fn with_max_bulk_len(mut self, max: usize) -> RespParser {
+        self.max_bulk_len = Some(max);
+        self
+    }
+
+    /// Appends the bytes received to the ones not parsed yet.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Returns the next message if all its bytes have been fed, the parser must not be
+    /// used after an error, the bytes that follow the invalid message can not be parsed.
+    pub fn next_message(&mut self) -> Result<Option<RespValue>, RespMsgError> {
+        match self.mode {
+            Mode::Messages => decode_bounded(&mut self.buf, self.max_bulk_len),
+            Mode::Commands => decode_command(&mut self.buf, self.max_bulk_len),
+            Mode::Compressed => decode_compressed(&mut self.buf, self.max_bulk_len),
+        }
+    }
+
+    /// The number of bytes fed that are not part of a message returned yet.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+}
+
+/// Returns the number of bytes of the decimal representation of an integer.
+fn integer_len(integer: i64) -> usize {
+    let mut len = if integer < 0 { 2 } else { 1 };
+    let mut rest = integer / 10;
+    while rest != 0 {
+        len += 1;
+        rest /= 10;
+    }
+    len
+}
+
+/// Writes the decimal representation of an integer without allocating.
+fn put_integer(buf: &mut impl BufMut, integer: i64) {
+    let mut digits = [0; 20];
+    let mut start = digits.len();
+    let mut rest = integer;
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (rest % 10).unsigned_abs() as u8;
+        rest /= 10;
+        if rest == 0 {
+            break;
+        }
+    }
+
+    if integer < 0 {
+        buf.put_u8(b'-');
+    }
+    buf.put_slice(&digits[start..]);
+}
+
+fn double_string(double: f64) -> String {
+    if double.is_nan() {
+        String::from("nan")
+    } else if double.is_infinite() && double > 0.0 {
+        String::from("inf")
+    } else if double.is_infinite() {
+        String::from("-inf")
+    } else {
+        double.to_string()
+    }
+}
+
+/// Writes a prefix byte followed by a length or an integer and a crlf.
+fn put_header(buf: &mut impl BufMut, prefix: u8, integer: i64) {
+    buf.put_u8(prefix);
+    put_integer(buf, integer);
+    buf.put_slice(CRLF_NEWLINE);
+}
+
+fn header_len(integer: i64) -> usize {
+    1 + integer_len(integer) + CRLF_NEWLINE.len()
+}
+
+impl RespValue {
+    /// Returns the number of bytes written by `write_to`.
+    pub fn encoded_len(&self) -> usize {
+        self.len_with(None)
+    }
+
+    /// Writes the message directly into the buffer, the buffer must have
+    /// `encoded_len` bytes of remaining capacity.
+    pub fn write_to(&self, buf: &mut impl BufMut) -> Result<(), RespMsgError> {
+        self.write_with(buf, None)
+    }
+
+    /// Writes the message, it is compressed with zstd if it is larger than `threshold`
+    /// bytes once encoded. The compressed message is prefixed by a `^` and its length.
+    pub fn write_compressed(
+        &self,
+        buf: &mut BytesMut,
+        threshold: usize,
+    ) -> Result<(), RespMsgError> {
+        // the small messages are written directly into the buffer
+        let encoded_len = self.encoded_len();
+        if encoded_len <= threshold {
+            buf.reserve(encoded_len);
+            return self.write_to(buf);
+        }
+
+        let mut encoded = BytesMut::with_capacity(encoded_len);
+        self.write_to(&mut encoded)?;
+
+        let compressed = zstd::encode_all(&encoded[..], COMPRESSION_LEVEL)?;
+        let length = compressed.len() as i64;
+        buf.reserve(header_len(length) + compressed.len() + CRLF_NEWLINE.len());
+
+        put_header(buf, COMPRESSED_CHAR, length);
+        buf.put_slice(&compressed);
+        buf.put_slice(CRLF_NEWLINE);
+
+        Ok(())
+    }
+
+    /// Writes the message, the bulk strings larger than `frame_size` bytes are sent in chunks
+    /// of at most `frame_size` bytes followed by an empty one, as RESP3 streamed strings.
+    pub fn write_chunked(&self, buf: &mut BytesMut, frame_size: usize) -> Result<(), RespMsgError> {
+        // a chunk can not be empty, it would end the bulk string
+        let frame_size = Some(frame_size.max(1));
+        buf.reserve(self.len_with(frame_size));
+        self.write_with(buf, frame_size)
+    }
+
+    pub(crate) fn len_with(&self, frame_size: Option<usize>) -> usize {
+        match self {
+            RespValue::SimpleString(string) | RespValue::Error(string) => {
+                1 + string.len() + CRLF_NEWLINE.len()
+            }
+            RespValue::Integer(integer) => header_len(*integer),
+            RespValue::BulkString(bytes) => match frame_size {
+                Some(size) if bytes.len() > size => {
+                    let chunks: usize = bytes
+                        .chunks(size)
+                        .map(|chunk| {
+                            header_len(chunk.len() as i64) + chunk.len() + CRLF_NEWLINE.len()
+                        })
+                        .sum();
+                    1 + STREAMED_LENGTH.len() + CRLF_NEWLINE.len() + chunks + header_len(0)
+                }
+                _otherwise => header_len(bytes.len() as i64) + bytes.len() + CRLF_NEWLINE.len(),
+            },
+            RespValue::Array(array) | RespValue::Push(array) => {
+                let values: usize = array.iter().map(|msg| msg.len_with(frame_size)).sum();
+                header_len(array.len() as i64) + values
+            }
+            RespValue::Nil => header_len(-1),
+            RespValue::Map(pairs) => {
+                let values: usize = pairs
+                    .iter()
+                    .map(|(key, value)| key.len_with(frame_size) + value.len_with(frame_size))
+                    .sum();
+                header_len(pairs.len() as i64) + values
+            }
+            RespValue::Double(double) => 1 + double_string(*double).len() + CRLF_NEWLINE.len(),
+        }
+    }
+
+    /// Writes the message, the bulk strings larger than `frame_size` bytes are sent in chunks.
+    fn write_with(
+        &self,
+        buf: &mut impl BufMut,
+        frame_size: Option<usize>,
+    ) -> Result<(), RespMsgError> {
+        match self {
+            RespValue::SimpleString(string) | RespValue::Error(string) => {
+                if string.as_bytes().find(CRLF_NEWLINE).is_some() {
+                    return Err(RespMsgError::SimpleStringContainCrlf);
+                }
+
+                let prefix = match self {
+                    RespValue::SimpleString(_) => SIMPLE_STRING_CHAR,
+                    _error => ERROR_CHAR,
+                };
+                buf.put_u8(prefix);
+                buf.put_slice(string.as_bytes());
+                buf.put_slice(CRLF_NEWLINE);
+            }
+            RespValue::Integer(integer) => put_header(buf, INTEGER_CHAR, *integer),
+            RespValue::BulkString(bytes) => match frame_size {
+                Some(size) if bytes.len() > size => {
+                    buf.put_u8(BULK_STRING_CHAR);
+                    buf.put_slice(STREAMED_LENGTH);
+                    buf.put_slice(CRLF_NEWLINE);
+                    for chunk in bytes.chunks(size) {
+                        put_header(buf, CHUNK_CHAR, chunk.len() as i64);
+                        buf.put_slice(chunk);
+                        buf.put_slice(CRLF_NEWLINE);
+                    }
+                    put_header(buf, CHUNK_CHAR, 0);
+                }
+                _otherwise => {
+                    put_header(buf, BULK_STRING_CHAR, bytes.len() as i64);
+                    buf.put_slice(bytes);
+                    buf.put_slice(CRLF_NEWLINE);
+                }
+            },
+            RespValue::Array(array) | RespValue::Push(array) => {
+                let prefix = match self {
+                    RespValue::Array(_) => ARRAY_CHAR,
+                    _push => PUSH_CHAR,
+                };
+                put_header(buf, prefix, array.len() as i64);
+                for msg in array {
+                    msg.write_with(buf, frame_size)?;
+                }
+            }
+            // We chose to use the Bulk String to represent nil values.
+            RespValue::Nil => put_header(buf, BULK_STRING_CHAR, -1),
+            RespValue::Map(pairs) => {
+                put_header(buf, MAP_CHAR, pairs.len() as i64);
+                for (key, value) in pairs {
+                    key.write_with(buf, frame_size)?;
+                    value.write_with(buf, frame_size)?;
+                }
+            }
+            RespValue::Double(double) => {
+                buf.put_u8(DOUBLE_CHAR);
+                buf.put_slice(double_string(*double).as_bytes());
+                buf.put_slice(CRLF_NEWLINE);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(msg: &RespValue) -> BytesMut {
+        let mut buf = BytesMut::with_capacity(msg.encoded_len());
+        msg.write_to(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn feed_byte_by_byte() {
+        let msg = RespValue::Array(vec![
+            RespValue::bulk_string(&b"publish"[..]),
+            RespValue::bulk_string(&b"with\r\ncrlf"[..]),
+            RespValue::Integer(-42),
+            RespValue::Nil,
+        ]);
+        let bytes = encode(&msg);
+
+        let mut parser = RespParser::new();
+        for (i, byte) in bytes.iter().enumerate() {
+            assert_eq!(parser.next_message().unwrap(), None);
+            parser.feed(&[*byte]);
+            assert_eq!(parser.buffered(), i + 1);
+        }
+
+        assert_eq!(parser.next_message().unwrap(), Some(msg));
+        assert_eq!(parser.buffered(), 0);
+        assert_eq!(parser.next_message().unwrap(), None);
+    }
+
+    #[test]
+    fn several_messages_at_once() {
+        let first = RespValue::SimpleString(String::from("OK"));
+        let second = RespValue::Error(String::from("ERR whoops"));
+
+        let mut bytes = encode(&first);
+        bytes.extend_from_slice(&encode(&second));
+        bytes.extend_from_slice(b"+PAR");
+
+        let mut parser = RespParser::new();
+        parser.feed(&bytes);
+        assert_eq!(parser.next_message().unwrap(), Some(first));
+        assert_eq!(parser.next_message().unwrap(), Some(second));
+        assert_eq!(parser.next_message().unwrap(), None);
+
+        parser.feed(b"TIAL\r\n");
+        let partial = RespValue::SimpleString(String::from("PARTIAL"));
+        assert_eq!(parser.next_message().unwrap(), Some(partial));
+    }
+
+    #[test]
+    fn inline_commands() {
+        let mut parser = RespParser::commands();
+        parser.feed(b"\r\nPING  hello\r");
+        assert_eq!(parser.next_message().unwrap(), None);
+
+        parser.feed(b"\n");
+        let ping = RespValue::Array(vec![
+            RespValue::bulk_string(&b"PING"[..]),
+            RespValue::bulk_string(&b"hello"[..]),
+        ]);
+        assert_eq!(parser.next_message().unwrap(), Some(ping));
+    }
+
+    #[test]
+    fn bulk_string_too_large() {
+        let mut parser = RespParser::new().with_max_bulk_len(4);
+        // the error is returned before the bulk string is received
+        parser.feed(b"$5\r\n");
+        match parser.next_message() {
+            Err(RespMsgError::BulkStringTooLarge { length: 5, max: 4 }) => (),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn huge_lengths_are_not_preallocated() {
+        // the lengths are announced before the elements are received
+        let mut parser = RespParser::new();
+        parser.feed(b"*1\r\n%99999999999\r\n");
+        assert_eq!(parser.next_message().unwrap(), None);
+
+        let mut parser = RespParser::new();
+        parser.feed(b"*99999999999\r\n");
+        assert_eq!(parser.next_message().unwrap(), None);
+    }
+
+    #[test]
+    fn compressed_messages() {
+        let msg = RespValue::bulk_string(vec![b'a'; 1024]);
+        let mut buf = BytesMut::new();
+        msg.write_compressed(&mut buf, 100).unwrap();
+        assert_eq!(buf.first(), Some(&COMPRESSED_CHAR));
+
+        let mut parser = RespParser::compressed();
+        let (head, tail) = buf.split_at(buf.len() / 2);
+        parser.feed(head);
+        assert_eq!(parser.next_message().unwrap(), None);
+        parser.feed(tail);
+        assert_eq!(parser.next_message().unwrap(), Some(msg));
+    }
+
+    #[test]
+    fn compressed_length_overflow() {
+        let mut parser = RespParser::compressed();
+        parser.feed(b"^18446744073709551615\r\nxx\r\n");
+        match parser.next_message() {
+            Err(RespMsgError::InvalidCompressedMessage) => (),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn compressed_bulk_string_too_large() {
+        let msg = RespValue::bulk_string(vec![b'a'; 1024]);
+        let mut buf = BytesMut::new();
+        msg.write_compressed(&mut buf, 100).unwrap();
+
+        // the compressed bytes are smaller than the maximum, not the bulk string
+        let mut parser = RespParser::compressed().with_max_bulk_len(512);
+        parser.feed(&buf);
+        match parser.next_message() {
+            Err(RespMsgError::BulkStringTooLarge {
+                length: 1024,
+                max: 512,
+            }) => (),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn streamed_chunk_length_overflow() {
+        let mut parser = RespParser::new();
+        parser.feed(b"$?\r\n;18446744073709551615\r\nab\r\n");
+        match parser.next_message() {
+            Err(RespMsgError::BulkStringTooLarge { .. }) => (),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+
+        // the lengths of the chunks would overflow once added
+        let mut parser = RespParser::new().with_max_bulk_len(4);
+        parser.feed(b"$?\r\n;1\r\na\r\n;18446744073709551615\r\nab\r\n");
+        match parser.next_message() {
+            Err(RespMsgError::BulkStringTooLarge { max: 4, .. }) => (),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+
+        let mut parser = RespParser::new();
+        parser.feed(b"$?\r\n;1\r\na\r\n;18446744073709551615\r\nab\r\n");
+        match parser.next_message() {
+            Err(RespMsgError::BulkStringTooLarge { .. }) => (),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+    }
+}
@@ -1,13 +1,23 @@
 use std::{fmt, str};
 
-#[derive(Clone, PartialEq, Eq)]
+use bytes::Bytes;
+
+#[derive(Clone, PartialEq)]
 pub enum RespValue {
     SimpleString(String),
     Error(String),
     Integer(i64),
-    BulkString(Vec<u8>),
+    /// The bytes of a bulk string, a decoded one is a slice of the buffer it was read from.
+    BulkString(Bytes),
     Array(Vec<RespValue>),
     Nil,
+    /// A RESP3 map, the pairs are kept in the order they were received.
+    Map(Vec<(RespValue, RespValue)>),
+    /// A RESP3 floating point number.
+    Double(f64),
+    /// A RESP3 out-of-band message, sent by the server without being
+    /// the reply of a command, e.g. the events of a subscription.
+    Push(Vec<RespValue>),
 }
 
 impl RespValue {
@@ -19,9 +29,46 @@ impl RespValue {
         RespValue::Error(string.to_string())
     }
 
-    pub fn bulk_string(string: impl Into<Vec<u8>>) -> RespValue {
+    pub fn bulk_string(string: impl Into<Bytes>) -> RespValue {
         RespValue::BulkString(string.into())
     }
+
+    /// Converts the RESP3 types into the RESP2 ones: the maps are flattened into
+    /// arrays of keys and values, the doubles become bulk strings and the pushes arrays.
+    pub fn into_resp2(self) -> RespValue {
+        match self {
+            RespValue::Array(values) | RespValue::Push(values) => {
+                RespValue::Array(values.into_iter().map(RespValue::into_resp2).collect())
+            }
+            RespValue::Map(pairs) => {
+                let mut values = Vec::with_capacity(pairs.len() * 2);
+                for (key, value) in pairs {
+                    values.push(key.into_resp2());
+                    values.push(value.into_resp2());
+                }
+                RespValue::Array(values)
+            }
+            RespValue::Double(double) => RespValue::bulk_string(double.to_string()),
+            value => value,
+        }
+    }
+
+    /// Returns the pairs of a map or of an array of keys and values, e.g. a map
+    /// converted into RESP2. Returns `None` if the array has an odd length.
+    pub fn into_pairs(self) -> Option<Vec<(RespValue, RespValue)>> {
+        match self {
+            RespValue::Map(pairs) => Some(pairs),
+            RespValue::Array(values) if values.len() % 2 == 0 => {
+                let mut pairs = Vec::with_capacity(values.len() / 2);
+                let mut values = values.into_iter();
+                while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                    pairs.push((key, value));
+                }
+                Some(pairs)
+            }
+            _otherwise => None,
+        }
+    }
 }
 
 impl PartialEq<&'_ str> for RespValue {
@@ -29,7 +76,7 @@ impl PartialEq<&'_ str> for RespValue {
         match self {
             RespValue::SimpleString(string) => string == other,
             RespValue::Error(error) => error == other,
-            RespValue::BulkString(bytes) => bytes.as_slice() == other.as_bytes(),
+            RespValue::BulkString(bytes) => bytes.as_ref() == other.as_bytes(),
             _ => false,
         }
     }
@@ -67,6 +114,9 @@ impl fmt::Debug for RespValue {
             }
             RespValue::Array(elements) => fmt.debug_tuple("Array").field(&elements).finish(),
             RespValue::Nil => fmt.debug_tuple("Nil").finish(),
+            RespValue::Map(pairs) => fmt.debug_tuple("Map").field(&pairs).finish(),
+            RespValue::Double(double) => fmt.debug_tuple("Double").field(&double).finish(),
+            RespValue::Push(elements) => fmt.debug_tuple("Push").field(&elements).finish(),
         }
     }
 }
@@ -0,0 +1,26 @@
+mod network;
+mod rng;
+mod scheduler;
+
+use std::cell::Cell;
+
+use crate::stream::Timestamp;
+
+pub use self::network::{Envelope, Network, NodeId};
+pub use self::rng::SimRng;
+pub use self::scheduler::Scheduler;
+
+thread_local! {
+    static VIRTUAL_TIME: Cell<Option<Timestamp>> = const { Cell::new(None) };
+}
+
+/// Makes `Timestamp::now` return this time in the calling thread, the other threads
+/// and the other simulations keep their own time. The real time is used again once
+/// it is set to `None`.
+pub fn set_virtual_time(time: Option<Timestamp>) {
+    VIRTUAL_TIME.with(|virtual_time| virtual_time.set(time));
+}
+
+pub(crate) fn virtual_time() -> Option<Timestamp> {
+    VIRTUAL_TIME.with(Cell::get)
+}
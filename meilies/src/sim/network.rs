@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::Scheduler;
+
+/// The identifier of a node of a simulated network, e.g. a server or a client.
+pub type NodeId = usize;
+
+/// A message in flight between two nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope<M> {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub message: M,
+}
+
+/// A network whose messages are delivered by a scheduler after a latency drawn from its seed,
+/// the messages can be reordered, lost, or cut by the partitions and the crashed nodes.
+#[derive(Debug, Clone)]
+pub struct Network {
+    min_latency: Duration,
+    max_latency: Duration,
+    loss: f64,
+    /// The pairs of nodes that can not reach each other, the lowest node first.
+    partitions: HashSet<(NodeId, NodeId)>,
+    crashed: HashSet<NodeId>,
+}
+
+impl Network {
+    pub fn new(min_latency: Duration, max_latency: Duration) -> Network {
+        assert!(min_latency <= max_latency, "invalid latency range");
+        Network {
+            min_latency,
+            max_latency,
+            loss: 0.0,
+            partitions: HashSet::new(),
+            crashed: HashSet::new(),
+        }
+    }
+
+    /// Loses the messages with this probability, between 0 and 1.
+    pub fn with_loss(mut self, probability: f64) -> Network {
+        self.loss = probability;
+        self
+    }
+
+    fn link(a: NodeId, b: NodeId) -> (NodeId, NodeId) {
+        (a.min(b), a.max(b))
+    }
+
+    /// Cuts the link between the two nodes, in both directions.
+    pub fn partition(&mut self, a: NodeId, b: NodeId) {
+        self.partitions.insert(Network::link(a, b));
+    }
+
+    pub fn heal(&mut self, a: NodeId, b: NodeId) {
+        self.partitions.remove(&Network::link(a, b));
+    }
+
+    pub fn heal_all(&mut self) {
+        self.partitions.clear();
+    }
+
+    /// Cuts all the links of the node until it is restarted.
+    pub fn crash(&mut self, node: NodeId) {
+        self.crashed.insert(node);
+    }
+
+    pub fn restart(&mut self, node: NodeId) {
+        self.crashed.remove(&node);
+    }
+
+    pub fn is_connected(&self, a: NodeId, b: NodeId) -> bool {
+        !self.crashed.contains(&a)
+            && !self.crashed.contains(&b)
+            && !self.partitions.contains(&Network::link(a, b))
+    }
+
+    /// Schedules the delivery of the message, returns `false` if it has been lost.
+    pub fn send<M, E>(
+        &self,
+        scheduler: &mut Scheduler<E>,
+        from: NodeId,
+        to: NodeId,
+        message: M,
+    ) -> bool
+    where
+        E: From<Envelope<M>>,
+    {
+        if !self.is_connected(from, to) || scheduler.rng().chance(self.loss) {
+            return false;
+        }
+
+        let min = self.min_latency.as_millis() as u64;
+        let max = self.max_latency.as_millis() as u64;
+        let latency = Duration::from_millis(scheduler.rng().between(min, max));
+        scheduler.schedule(latency, E::from(Envelope { from, to, message }));
+        true
+    }
+
+    /// Returns the envelope if the link is still up when it arrives, the messages
+    /// in flight are lost when the link is cut or one of the nodes crashes.
+    pub fn deliver<M>(&self, envelope: Envelope<M>) -> Option<Envelope<M>> {
+        if self.is_connected(envelope.from, envelope.to) {
+            Some(envelope)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::Timestamp;
+
+    fn scheduler(seed: u64) -> Scheduler<Envelope<u32>> {
+        Scheduler::new(seed, Timestamp(0))
+    }
+
+    #[test]
+    fn messages_are_delayed_and_reordered() {
+        let network = Network::new(Duration::from_millis(10), Duration::from_millis(100));
+
+        let orders: Vec<Vec<u32>> = (0..16)
+            .map(|seed| {
+                let mut scheduler = scheduler(seed);
+                for message in 0..8 {
+                    assert!(network.send(&mut scheduler, 0, 1, message));
+                }
+
+                let mut order = Vec::new();
+                while let Some(envelope) = scheduler.step() {
+                    let now = scheduler.now().0;
+                    assert!((10..=100).contains(&now));
+                    order.push(envelope.message);
+                }
+                order
+            })
+            .collect();
+
+        let sent: Vec<_> = (0..8).collect();
+        assert!(orders.iter().any(|order| order != &sent));
+    }
+
+    #[test]
+    fn partitions_and_crashes() {
+        let mut network = Network::new(Duration::from_millis(1), Duration::from_millis(1));
+        let mut scheduler = scheduler(0);
+
+        network.partition(1, 0);
+        assert!(!network.send(&mut scheduler, 0, 1, 1));
+        assert!(network.send(&mut scheduler, 0, 2, 2));
+        network.heal(0, 1);
+        assert!(network.send(&mut scheduler, 1, 0, 3));
+
+        // the messages in flight are lost with the link
+        network.crash(2);
+        let delivered: Vec<_> = std::iter::from_fn(|| scheduler.step())
+            .filter_map(|envelope| network.deliver(envelope))
+            .map(|envelope| envelope.message)
+            .collect();
+        assert_eq!(delivered, vec![3]);
+
+        network.restart(2);
+        assert!(network.is_connected(0, 2));
+    }
+
+    #[test]
+    fn lossy_network() {
+        let network = Network::new(Duration::from_millis(1), Duration::from_millis(1));
+        let mut scheduler = scheduler(3);
+
+        let lossy = network.clone().with_loss(1.0);
+        assert!(!lossy.send(&mut scheduler, 0, 1, 1));
+
+        let lossy = network.with_loss(0.5);
+        let sent = (0..1000)
+            .filter(|&message| lossy.send(&mut scheduler, 0, 1, message))
+            .count();
+        assert!((400..600).contains(&sent));
+    }
+}
@@ -0,0 +1,68 @@
+/// A pseudo-random generator seeded by the simulations, the same seed
+/// always gives the same numbers, whatever the platform (SplitMix64).
+#[derive(Debug, Clone)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> SimRng {
+        SimRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number between `low` and `high`, both included.
+    pub fn between(&mut self, low: u64, high: u64) -> u64 {
+        assert!(low <= high, "empty range");
+        match (high - low).checked_add(1) {
+            Some(len) => low + self.next_u64() % len,
+            None => self.next_u64(),
+        }
+    }
+
+    /// Returns `true` with the given probability, between 0 and 1.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        // the 53 bits of precision of a f64
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        unit < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let mut a = SimRng::new(42);
+        let mut b = SimRng::new(42);
+        let mut c = SimRng::new(43);
+
+        let a: Vec<_> = (0..16).map(|_| a.next_u64()).collect();
+        let b: Vec<_> = (0..16).map(|_| b.next_u64()).collect();
+        let c: Vec<_> = (0..16).map(|_| c.next_u64()).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn between_bounds() {
+        let mut rng = SimRng::new(7);
+        for _ in 0..1000 {
+            let n = rng.between(10, 20);
+            assert!((10..=20).contains(&n));
+        }
+        assert_eq!(rng.between(5, 5), 5);
+        rng.between(0, u64::MAX);
+
+        assert!(!rng.chance(0.0));
+        assert!(rng.chance(1.0));
+    }
+}
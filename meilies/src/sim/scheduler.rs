@@ -0,0 +1,189 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::time::Duration;
+
+use crate::stream::Timestamp;
+
+use super::{set_virtual_time, SimRng};
+
+/// An event waiting for its time in the queue of a scheduler.
+struct Entry<E> {
+    time: Timestamp,
+    /// Orders the events of the same time, drawn from the seed.
+    tiebreak: u64,
+    id: u64,
+    event: E,
+}
+
+impl<E> Entry<E> {
+    fn key(&self) -> (Timestamp, u64, u64) {
+        (self.time, self.tiebreak, self.id)
+    }
+}
+
+impl<E> PartialEq for Entry<E> {
+    fn eq(&self, other: &Entry<E>) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<E> Eq for Entry<E> {}
+
+impl<E> PartialOrd for Entry<E> {
+    fn partial_cmp(&self, other: &Entry<E>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<E> Ord for Entry<E> {
+    fn cmp(&self, other: &Entry<E>) -> Ordering {
+        // the heap pops the greatest entry, the earliest one must be the greatest
+        other.key().cmp(&self.key())
+    }
+}
+
+/// Runs the events of a simulation in the order of their virtual time, the events
+/// of the same time are run in an order drawn from the seed. A run with the
+/// same seed and the same events always runs them in the same order.
+pub struct Scheduler<E> {
+    now: Timestamp,
+    next_id: u64,
+    queue: BinaryHeap<Entry<E>>,
+    rng: SimRng,
+    drives_clock: bool,
+}
+
+impl<E> Scheduler<E> {
+    pub fn new(seed: u64, start: Timestamp) -> Scheduler<E> {
+        Scheduler {
+            now: start,
+            next_id: 0,
+            queue: BinaryHeap::new(),
+            rng: SimRng::new(seed),
+            drives_clock: false,
+        }
+    }
+
+    /// Makes `Timestamp::now` return the virtual time of the scheduler until it is
+    /// dropped, in the thread the scheduler is created and run on.
+    pub fn drive_clock(mut self) -> Scheduler<E> {
+        self.drives_clock = true;
+        set_virtual_time(Some(self.now));
+        self
+    }
+
+    /// The virtual time of the event being run.
+    pub fn now(&self) -> Timestamp {
+        self.now
+    }
+
+    /// The generator the random choices of the simulation must be drawn from.
+    pub fn rng(&mut self) -> &mut SimRng {
+        &mut self.rng
+    }
+
+    /// Schedules the event to be run after this delay of virtual time.
+    pub fn schedule(&mut self, delay: Duration, event: E) {
+        let time = Timestamp(self.now.0.saturating_add(delay.as_millis() as u64));
+        let entry = Entry {
+            time,
+            tiebreak: self.rng.next_u64(),
+            id: self.next_id,
+            event,
+        };
+        self.next_id += 1;
+        self.queue.push(entry);
+    }
+
+    /// Returns `true` if there is no event left to run.
+    pub fn is_idle(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Advances the virtual time to the next event and returns it.
+    pub fn step(&mut self) -> Option<E> {
+        let entry = self.queue.pop()?;
+        self.advance(entry.time);
+        Some(entry.event)
+    }
+
+    /// Returns the next event if it is due before the deadline, the virtual
+    /// time is advanced to the deadline once there is no such event left.
+    pub fn step_until(&mut self, deadline: Timestamp) -> Option<E> {
+        match self.queue.peek() {
+            Some(entry) if entry.time <= deadline => self.step(),
+            _otherwise => {
+                self.advance(deadline.max(self.now));
+                None
+            }
+        }
+    }
+
+    fn advance(&mut self, time: Timestamp) {
+        self.now = time;
+        if self.drives_clock {
+            set_virtual_time(Some(time));
+        }
+    }
+}
+
+impl<E> Drop for Scheduler<E> {
+    fn drop(&mut self) {
+        if self.drives_clock {
+            set_virtual_time(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(seed: u64) -> Vec<(u64, &'static str)> {
+        let mut scheduler = Scheduler::new(seed, Timestamp(1_000));
+        scheduler.schedule(Duration::from_millis(20), "c");
+        scheduler.schedule(Duration::from_millis(10), "a");
+        scheduler.schedule(Duration::from_millis(10), "b");
+        scheduler.schedule(Duration::from_millis(10), "d");
+
+        let mut trace = Vec::new();
+        while let Some(event) = scheduler.step() {
+            trace.push((scheduler.now().0, event));
+        }
+        trace
+    }
+
+    #[test]
+    fn events_in_time_order() {
+        let trace = run(1);
+        let times: Vec<_> = trace.iter().map(|(time, _)| *time).collect();
+        assert_eq!(times, vec![1_010, 1_010, 1_010, 1_020]);
+        assert_eq!(trace[3].1, "c");
+    }
+
+    #[test]
+    fn same_seed_same_order() {
+        assert_eq!(run(42), run(42));
+
+        // the events of the same time are run in different orders with different seeds
+        let orders: Vec<_> = (0..16).map(run).collect();
+        assert!(orders.iter().any(|order| order != &orders[0]));
+    }
+
+    #[test]
+    fn step_until_deadline() {
+        let mut scheduler = Scheduler::new(0, Timestamp(0));
+        scheduler.schedule(Duration::from_millis(50), 1);
+        scheduler.schedule(Duration::from_millis(150), 2);
+
+        assert_eq!(scheduler.step_until(Timestamp(100)), Some(1));
+        assert_eq!(scheduler.now(), Timestamp(50));
+        assert_eq!(scheduler.step_until(Timestamp(100)), None);
+        assert_eq!(scheduler.now(), Timestamp(100));
+        assert!(!scheduler.is_idle());
+
+        assert_eq!(scheduler.step(), Some(2));
+        assert_eq!(scheduler.now(), Timestamp(150));
+        assert!(scheduler.is_idle());
+    }
+}
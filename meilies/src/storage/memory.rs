@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::RwLock;
+
+use crate::stream::{
+    Event, EventData, EventHeaders, EventName, EventNumber, StreamName, Timestamp,
+};
+
+use super::StorageBackend;
+
+#[derive(Debug)]
+struct MemoryStream {
+    /// The number of the first event kept, the events below have been trimmed.
+    first: EventNumber,
+    last: Option<EventNumber>,
+    events: BTreeMap<EventNumber, Event>,
+}
+
+impl Default for MemoryStream {
+    fn default() -> MemoryStream {
+        MemoryStream {
+            first: EventNumber::zero(),
+            last: None,
+            events: BTreeMap::new(),
+        }
+    }
+}
+
+/// A storage backend that keeps the events in memory, they are lost when it is dropped.
+/// It is used on its own, e.g. to test the code that reads or writes an event log.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    streams: RwLock<BTreeMap<StreamName, MemoryStream>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+}
+
+impl StorageBackend for MemoryStorage {
+    type Error = Infallible;
+
+    fn append(
+        &self,
+        stream: &StreamName,
+        events: &[(EventName, EventData)],
+    ) -> Result<(EventNumber, EventNumber), Self::Error> {
+        assert!(!events.is_empty(), "no events to append");
+
+        let mut streams = self.streams.write().unwrap();
+        let memory = streams.entry(stream.clone()).or_default();
+
+        let timestamp = Timestamp::now();
+        let first = memory.last.map_or(EventNumber::zero(), EventNumber::next);
+        let mut last = first;
+        for (i, (name, data)) in events.iter().enumerate() {
+            last = EventNumber(first.0 + i as u64);
+            let event = Event {
+                number: last,
+                timestamp,
+                name: name.clone(),
+                data: data.clone(),
+                headers: EventHeaders::new(),
+            };
+            memory.events.insert(last, event);
+        }
+        memory.last = Some(last);
+
+        Ok((first, last))
+    }
+
+    fn contains(&self, stream: &StreamName) -> Result<bool, Self::Error> {
+        let streams = self.streams.read().unwrap();
+        Ok(streams.contains_key(stream))
+    }
+
+    fn read_range(
+        &self,
+        stream: &StreamName,
+        from: EventNumber,
+        to: Option<EventNumber>,
+    ) -> Result<Vec<Event>, Self::Error> {
+        let streams = self.streams.read().unwrap();
+        let memory = match streams.get(stream) {
+            Some(memory) => memory,
+            None => return Ok(Vec::new()),
+        };
+
+        let events = match to {
+            Some(to) if to <= from => return Ok(Vec::new()),
+            Some(to) => memory.events.range(from..to),
+            None => memory.events.range(from..),
+        };
+
+        Ok(events.map(|(_, event)| event.clone()).collect())
+    }
+
+    fn head(&self, stream: &StreamName) -> Result<Option<EventNumber>, Self::Error> {
+        let streams = self.streams.read().unwrap();
+        Ok(streams.get(stream).and_then(|memory| memory.last))
+    }
+
+    fn trim(&self, stream: &StreamName, up_to: EventNumber) -> Result<EventNumber, Self::Error> {
+        let mut streams = self.streams.write().unwrap();
+        let memory = streams.entry(stream.clone()).or_default();
+
+        // The floor never goes backward nor after the next event to be published.
+        let next_number = memory.last.map_or(EventNumber::zero(), EventNumber::next);
+        let floor = up_to.min(next_number).max(memory.first);
+
+        memory.first = floor;
+        memory.events = memory.events.split_off(&floor);
+
+        Ok(floor)
+    }
+
+    fn stream_names(&self) -> Result<Vec<StreamName>, Self::Error> {
+        let streams = self.streams.read().unwrap();
+        Ok(streams.keys().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_name(name: &str) -> StreamName {
+        StreamName::new(name.to_owned()).unwrap()
+    }
+
+    fn event(name: &str, data: &str) -> (EventName, EventData) {
+        let name = EventName::new(name.to_owned()).unwrap();
+        let data = EventData(data.as_bytes().to_vec().into());
+        (name, data)
+    }
+
+    #[test]
+    fn append_and_read_range() {
+        let storage = MemoryStorage::new();
+        let stream = stream_name("users");
+
+        assert_eq!(storage.head(&stream).unwrap(), None);
+
+        let events = [event("created", "alice"), event("created", "bob")];
+        let numbers = storage.append(&stream, &events).unwrap();
+        assert_eq!(numbers, (EventNumber(0), EventNumber(1)));
+
+        let numbers = storage
+            .append(&stream, &[event("deleted", "alice")])
+            .unwrap();
+        assert_eq!(numbers, (EventNumber(2), EventNumber(2)));
+        assert_eq!(storage.head(&stream).unwrap(), Some(EventNumber(2)));
+
+        let events = storage.read_range(&stream, EventNumber(1), None).unwrap();
+        let numbers: Vec<_> = events.iter().map(|e| e.number).collect();
+        assert_eq!(numbers, vec![EventNumber(1), EventNumber(2)]);
+        assert_eq!(events[1].name.as_str(), "deleted");
+        assert_eq!(&events[1].data.0[..], b"alice");
+
+        let to = Some(EventNumber(2));
+        let events = storage.read_range(&stream, EventNumber(0), to).unwrap();
+        assert_eq!(events.len(), 2);
+
+        let other = stream_name("orders");
+        assert!(!storage.contains(&other).unwrap());
+        assert!(storage
+            .read_range(&other, EventNumber(0), None)
+            .unwrap()
+            .is_empty());
+        assert!(!storage.contains(&other).unwrap());
+    }
+
+    #[test]
+    fn trim_keeps_the_numbers() {
+        let storage = MemoryStorage::new();
+        let stream = stream_name("users");
+
+        let events = [event("a", "0"), event("b", "1"), event("c", "2")];
+        storage.append(&stream, &events).unwrap();
+
+        assert_eq!(
+            storage.trim(&stream, EventNumber(2)).unwrap(),
+            EventNumber(2)
+        );
+        // the floor never goes backward nor after the next event
+        assert_eq!(
+            storage.trim(&stream, EventNumber(1)).unwrap(),
+            EventNumber(2)
+        );
+        assert_eq!(
+            storage.trim(&stream, EventNumber(10)).unwrap(),
+            EventNumber(3)
+        );
+
+        assert!(storage
+            .read_range(&stream, EventNumber(0), None)
+            .unwrap()
+            .is_empty());
+        assert_eq!(storage.head(&stream).unwrap(), Some(EventNumber(2)));
+
+        let numbers = storage.append(&stream, &[event("d", "3")]).unwrap();
+        assert_eq!(numbers, (EventNumber(3), EventNumber(3)));
+    }
+
+    #[test]
+    fn stream_names_are_sorted() {
+        let storage = MemoryStorage::new();
+        storage
+            .append(&stream_name("users"), &[event("a", "")])
+            .unwrap();
+        storage
+            .append(&stream_name("orders"), &[event("a", "")])
+            .unwrap();
+
+        let names = storage.stream_names().unwrap();
+        assert_eq!(names, vec![stream_name("orders"), stream_name("users")]);
+    }
+}
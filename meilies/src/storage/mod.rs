@@ -0,0 +1,53 @@
+mod memory;
+
+use std::fmt;
+
+use crate::stream::{Event, EventData, EventName, EventNumber, StreamName};
+
+pub use self::memory::MemoryStorage;
+
+/// The log of the events of the streams: appending, reading a range, the last event number,
+/// trimming and listing the streams.
+///
+/// It is not an extension point of the server, which always stores its streams in sled and
+/// only uses its `SledStorage` implementation for the publications, the range reads, the
+/// trims and the listing of the streams. The subscriptions, the consumer groups, the
+/// deletions, the metadata, the retention and the replication read and write the sled
+/// trees directly, a server can not be backed by another implementation like `MemoryStorage`.
+pub trait StorageBackend {
+    type Error: fmt::Display;
+
+    /// Appends the events to the stream, returns the numbers of the first and the last events
+    /// appended. The events of a stream are numbered from zero and are never renumbered.
+    ///
+    /// `events` must not be empty.
+    fn append(
+        &self,
+        stream: &StreamName,
+        events: &[(EventName, EventData)],
+    ) -> Result<(EventNumber, EventNumber), Self::Error>;
+
+    /// Returns `true` if the stream has been created or published to.
+    fn contains(&self, stream: &StreamName) -> Result<bool, Self::Error>;
+
+    /// Returns the events of the stream from `from` included to `to` excluded,
+    /// up to the last event of the stream if `to` is `None`.
+    ///
+    /// Reading a stream that does not exist returns no events and does not create it.
+    fn read_range(
+        &self,
+        stream: &StreamName,
+        from: EventNumber,
+        to: Option<EventNumber>,
+    ) -> Result<Vec<Event>, Self::Error>;
+
+    /// Returns the number of the last event appended to the stream, `None` if it is empty.
+    fn head(&self, stream: &StreamName) -> Result<Option<EventNumber>, Self::Error>;
+
+    /// Removes the events of the stream that are strictly below the given
+    /// event number, returns the number of the first event kept.
+    fn trim(&self, stream: &StreamName, up_to: EventNumber) -> Result<EventNumber, Self::Error>;
+
+    /// Returns the names of the streams stored.
+    fn stream_names(&self) -> Result<Vec<StreamName>, Self::Error>;
+}
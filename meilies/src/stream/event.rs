@@ -0,0 +1,102 @@
+use std::fmt;
+
+use crate::resp::{FromResp, RespValue};
+
+use super::{EventData, EventHeaders, EventName, EventNumber, Timestamp};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub number: EventNumber,
+    /// The time at which the server appended the event.
+    pub timestamp: Timestamp,
+    pub name: EventName,
+    pub data: EventData,
+    pub headers: EventHeaders,
+}
+
+impl From<Event> for RespValue {
+    fn from(event: Event) -> RespValue {
+        RespValue::Array(vec![
+            RespValue::Integer(event.number.0 as i64),
+            RespValue::Integer(event.timestamp.0 as i64),
+            RespValue::string(event.name),
+            RespValue::bulk_string(event.data.0),
+            event.headers.into(),
+        ])
+    }
+}
+
+#[derive(Debug)]
+pub enum RespEventConvertError {
+    InvalidRespType,
+    InvalidArgumentRespType,
+    MissingArgument,
+    TooManyArguments,
+}
+
+impl fmt::Display for RespEventConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use RespEventConvertError::*;
+        match self {
+            InvalidRespType => write!(f, "invalid RESP type found, expected Array"),
+            InvalidArgumentRespType => write!(f, "Invalid argument resp type"),
+            MissingArgument => write!(f, "Missing argument"),
+            TooManyArguments => write!(f, "Too many arguments"),
+        }
+    }
+}
+
+impl FromResp for Event {
+    type Error = RespEventConvertError;
+
+    fn from_resp(value: RespValue) -> Result<Self, Self::Error> {
+        use RespEventConvertError::*;
+
+        let mut iter = match value {
+            RespValue::Array(array) => array.into_iter(),
+            _otherwise => return Err(InvalidRespType),
+        };
+
+        let number = iter
+            .next()
+            .map(EventNumber::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        let timestamp = iter
+            .next()
+            .map(Timestamp::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        let name = iter
+            .next()
+            .map(EventName::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        let data = iter
+            .next()
+            .map(EventData::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        let headers = iter
+            .next()
+            .map(EventHeaders::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        if iter.next().is_some() {
+            return Err(TooManyArguments);
+        }
+
+        Ok(Event {
+            number,
+            timestamp,
+            name,
+            data,
+            headers,
+        })
+    }
+}
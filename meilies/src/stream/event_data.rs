@@ -1,8 +1,9 @@
 use crate::resp::{FromResp, RespBytesConvertError, RespValue};
+use bytes::Bytes;
 use std::{fmt, str};
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct EventData(pub Vec<u8>);
+pub struct EventData(pub Bytes);
 
 impl fmt::Debug for EventData {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -20,11 +21,6 @@ impl FromResp for EventData {
     type Error = RespBytesConvertError;
 
     fn from_resp(value: RespValue) -> Result<Self, Self::Error> {
-        match value {
-            RespValue::SimpleString(string) => Ok(EventData(string.into_bytes())),
-            RespValue::Error(string) => Ok(EventData(string.into_bytes())),
-            RespValue::BulkString(bytes) => Ok(EventData(bytes)),
-            _ => Err(RespBytesConvertError::InvalidRespType),
-        }
+        Bytes::from_resp(value).map(EventData)
     }
 }
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::resp::{FromResp, RespValue};
+
+use super::{TraceContext, TRACEPARENT_HEADER};
+
+/// A small key-value map sent along with an event,
+/// e.g. a correlation ID or the content type of the data.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EventHeaders(pub BTreeMap<String, String>);
+
+impl EventHeaders {
+    pub fn new() -> EventHeaders {
+        EventHeaders::default()
+    }
+
+    pub fn insert(&mut self, key: String, value: String) -> Option<String> {
+        self.0.insert(key, value)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// Returns the trace context of the publisher of the event, `None` if the
+    /// `traceparent` header is missing or invalid.
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        self.get(TRACEPARENT_HEADER)?.parse().ok()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Encodes the headers as stored by the server: the length
+    /// of the key, the key, the length of the value and the value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (key, value) in &self.0 {
+            bytes.extend_from_slice(&key.len().to_be_bytes());
+            bytes.extend_from_slice(key.as_bytes());
+            bytes.extend_from_slice(&value.len().to_be_bytes());
+            bytes.extend_from_slice(value.as_bytes());
+        }
+        bytes
+    }
+
+    /// Decodes the headers encoded by `to_bytes`, returns `None` if they are malformed.
+    pub fn from_bytes(mut bytes: &[u8]) -> Option<EventHeaders> {
+        fn next_string(bytes: &mut &[u8]) -> Option<String> {
+            let len = bytes.get(..8)?;
+            let len = usize::from_be_bytes(<[u8; 8]>::try_from(len).ok()?);
+            let string = bytes.get(8..8 + len)?;
+            let string = String::from_utf8(string.to_owned()).ok()?;
+            *bytes = &bytes[8 + len..];
+            Some(string)
+        }
+
+        let mut headers = EventHeaders::new();
+        while !bytes.is_empty() {
+            let key = next_string(&mut bytes)?;
+            let value = next_string(&mut bytes)?;
+            headers.insert(key, value);
+        }
+
+        Some(headers)
+    }
+}
+
+impl From<EventHeaders> for RespValue {
+    fn from(headers: EventHeaders) -> RespValue {
+        let mut values = Vec::with_capacity(headers.0.len() * 2);
+        for (key, value) in headers.0 {
+            values.push(RespValue::bulk_string(key));
+            values.push(RespValue::bulk_string(value));
+        }
+        RespValue::Array(values)
+    }
+}
+
+#[derive(Debug)]
+pub enum RespEventHeadersConvertError {
+    InvalidRespType,
+    InvalidArgumentRespType,
+    MissingArgument,
+}
+
+impl fmt::Display for RespEventHeadersConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use RespEventHeadersConvertError::*;
+        match self {
+            InvalidRespType => write!(f, "invalid RESP type found, expected Array"),
+            InvalidArgumentRespType => write!(f, "Invalid argument resp type"),
+            MissingArgument => write!(f, "Missing argument"),
+        }
+    }
+}
+
+impl FromResp for EventHeaders {
+    type Error = RespEventHeadersConvertError;
+
+    fn from_resp(value: RespValue) -> Result<Self, Self::Error> {
+        use RespEventHeadersConvertError::*;
+
+        let mut iter = match value {
+            RespValue::Array(array) => array.into_iter(),
+            _otherwise => return Err(InvalidRespType),
+        };
+
+        let mut headers = EventHeaders::new();
+        while let Some(key) = iter.next() {
+            let key = String::from_resp(key).map_err(|_| InvalidArgumentRespType)?;
+
+            let value = iter
+                .next()
+                .map(String::from_resp)
+                .ok_or(MissingArgument)?
+                .map_err(|_| InvalidArgumentRespType)?;
+
+            headers.insert(key, value);
+        }
+
+        Ok(headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_bytes_round_trip() {
+        let mut headers = EventHeaders::new();
+        headers.insert("correlation-id".to_owned(), "42".to_owned());
+        headers.insert("content-type".to_owned(), "application/json".to_owned());
+        headers.insert("empty".to_owned(), String::new());
+
+        let bytes = headers.to_bytes();
+        assert_eq!(EventHeaders::from_bytes(&bytes), Some(headers));
+
+        assert_eq!(EventHeaders::from_bytes(&[]), Some(EventHeaders::new()));
+        assert_eq!(EventHeaders::from_bytes(&bytes[..bytes.len() - 1]), None);
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt;
+use std::str::FromStr;
+use std::string::FromUtf8Error;
+
+use crate::resp::{FromResp, RespStringConvertError, RespValue};
+
+/// The name of a consumer group, the events of a stream are distributed among its members.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GroupName(String);
+
+impl GroupName {
+    pub fn new(name: String) -> Result<GroupName, GroupNameError> {
+        if name.is_empty() {
+            return Err(GroupNameError::EmptyName);
+        }
+
+        if name.contains(':') {
+            return Err(GroupNameError::ContainColon);
+        }
+
+        Ok(GroupName(name))
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for GroupName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl AsRef<[u8]> for GroupName {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+#[derive(Debug)]
+pub enum RespGroupNameConvertError {
+    InvalidRespType,
+    InvalidUtf8String(FromUtf8Error),
+    InnerGroupNameConvertError(GroupNameError),
+}
+
+impl fmt::Display for RespGroupNameConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use RespGroupNameConvertError::*;
+        match self {
+            InvalidRespType => write!(f, "invalid RESP type found, expected String"),
+            InvalidUtf8String(e) => write!(f, "invalid UTF8 string; {}", e),
+            InnerGroupNameConvertError(e) => write!(f, "inner GroupName convert error: {}", e),
+        }
+    }
+}
+
+impl FromResp for GroupName {
+    type Error = RespGroupNameConvertError;
+    fn from_resp(value: RespValue) -> Result<Self, Self::Error> {
+        use RespGroupNameConvertError::*;
+        match String::from_resp(value) {
+            Ok(string) => GroupName::from_str(&string).map_err(InnerGroupNameConvertError),
+            Err(RespStringConvertError::InvalidRespType) => Err(InvalidRespType),
+            Err(RespStringConvertError::InvalidUtf8String(error)) => Err(InvalidUtf8String(error)),
+        }
+    }
+}
+
+impl FromStr for GroupName {
+    type Err = GroupNameError;
+
+    fn from_str(s: &str) -> Result<GroupName, Self::Err> {
+        GroupName::new(s.to_owned())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GroupNameError {
+    EmptyName,
+    ContainColon,
+}
+
+impl fmt::Display for GroupNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GroupNameError::EmptyName => f.write_str("group name is empty"),
+            GroupNameError::ContainColon => f.write_str("group name contains a colon (:)"),
+        }
+    }
+}
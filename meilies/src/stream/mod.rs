@@ -1,14 +1,26 @@
+mod event;
 mod event_data;
+mod event_headers;
 mod event_name;
 mod event_number;
+mod group_name;
+mod pending_event;
 mod raw_event;
 mod stream;
 mod stream_name;
+mod timestamp;
+mod trace_context;
 
+pub use self::event::{Event, RespEventConvertError};
 pub use self::event_data::EventData;
-pub use self::event_name::EventName;
+pub use self::event_headers::{EventHeaders, RespEventHeadersConvertError};
+pub use self::event_name::{EventName, EventNameError};
 pub use self::event_number::EventNumber;
-pub use self::raw_event::RawEvent;
-pub use self::stream::{ParseStreamError, ReadRange, Stream};
-pub use self::stream_name::ALL_STREAMS;
+pub use self::group_name::{GroupName, GroupNameError};
+pub use self::pending_event::{PendingEvent, RespPendingEventConvertError};
+pub use self::raw_event::{RawEvent, CHECKSUM_FLAG};
+pub use self::stream::{Batch, ParseStreamError, ReadRange, SlowConsumer, Stream};
 pub use self::stream_name::{StreamName, StreamNameError};
+pub use self::stream_name::{ALL_STREAMS, CATEGORY_PREFIX, EPHEMERAL_PREFIX};
+pub use self::timestamp::{ParseTimestampError, Timestamp};
+pub use self::trace_context::{ParseTraceContextError, TraceContext, TRACEPARENT_HEADER};
@@ -0,0 +1,100 @@
+use std::fmt;
+
+use crate::resp::{FromResp, RespValue};
+
+use super::EventNumber;
+
+/// An event delivered to a consumer group member that has not been acknowledged yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingEvent {
+    pub number: EventNumber,
+    /// The consumer the event has been delivered to.
+    pub consumer: String,
+    /// The number of milliseconds elapsed since the last delivery.
+    pub idle: u64,
+    /// The number of times the event has been delivered.
+    pub deliveries: u64,
+}
+
+impl From<PendingEvent> for RespValue {
+    fn from(event: PendingEvent) -> RespValue {
+        RespValue::Array(vec![
+            RespValue::Integer(event.number.0 as i64),
+            RespValue::bulk_string(event.consumer),
+            RespValue::Integer(event.idle as i64),
+            RespValue::Integer(event.deliveries as i64),
+        ])
+    }
+}
+
+#[derive(Debug)]
+pub enum RespPendingEventConvertError {
+    InvalidRespType,
+    InvalidArgumentRespType,
+    MissingArgument,
+    TooManyArguments,
+}
+
+impl fmt::Display for RespPendingEventConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use RespPendingEventConvertError::*;
+        match self {
+            InvalidRespType => write!(f, "invalid RESP type found, expected Array"),
+            InvalidArgumentRespType => write!(f, "Invalid argument resp type"),
+            MissingArgument => write!(f, "Missing argument"),
+            TooManyArguments => write!(f, "Too many arguments"),
+        }
+    }
+}
+
+fn integer_from_resp(value: RespValue) -> Result<u64, RespPendingEventConvertError> {
+    match value {
+        RespValue::Integer(integer) if integer >= 0 => Ok(integer as u64),
+        _otherwise => Err(RespPendingEventConvertError::InvalidArgumentRespType),
+    }
+}
+
+impl FromResp for PendingEvent {
+    type Error = RespPendingEventConvertError;
+
+    fn from_resp(value: RespValue) -> Result<Self, Self::Error> {
+        use RespPendingEventConvertError::*;
+
+        let mut iter = match value {
+            RespValue::Array(array) => array.into_iter(),
+            _otherwise => return Err(InvalidRespType),
+        };
+
+        let number = iter
+            .next()
+            .map(EventNumber::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        let consumer = iter
+            .next()
+            .map(String::from_resp)
+            .ok_or(MissingArgument)?
+            .map_err(|_| InvalidArgumentRespType)?;
+
+        let idle = iter
+            .next()
+            .map(integer_from_resp)
+            .ok_or(MissingArgument)??;
+        let deliveries = iter
+            .next()
+            .map(integer_from_resp)
+            .ok_or(MissingArgument)??;
+
+        if iter.next().is_some() {
+            return Err(TooManyArguments);
+        }
+
+        Ok(PendingEvent {
+            number,
+            consumer,
+            idle,
+            deliveries,
+        })
+    }
+}
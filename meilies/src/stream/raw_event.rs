@@ -1,7 +1,20 @@
 use std::error::Error;
 
-use super::{EventData, EventName};
+use bytes::Bytes;
 
+use super::{EventData, EventHeaders, EventName, Timestamp};
+
+/// The flag set on the length of the name of the events stored with a checksum.
+pub const CHECKSUM_FLAG: u64 = 1 << 63;
+
+/// The size of the CRC32 checksum that follows the data of the events stored with one.
+const CHECKSUM_SIZE: usize = 4;
+
+/// An event as stored by the server: the timestamp, the length of the name,
+/// the name, the length of the headers, the headers and then the data.
+///
+/// The events stored with a checksum have the `CHECKSUM_FLAG` set on the length of
+/// their name and end with the CRC32 checksum of all the bytes that precede it.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct RawEvent<T>(T);
 
@@ -10,31 +23,126 @@ impl<T: AsRef<[u8]>> RawEvent<T> {
         RawEvent(content)
     }
 
-    fn name_size(&self) -> usize {
+    fn name_size_field(&self) -> u64 {
         // FIXME: prefer using TryFrom
         let mut event_name_size: [u8; 8] = [0; 8];
-        for (i, b) in self.0.as_ref().iter().enumerate() {
+        for (i, b) in self.0.as_ref()[8..].iter().enumerate() {
             if i == 8 {
                 break;
             }
             event_name_size[i] = *b;
         }
-        usize::from_be_bytes(event_name_size)
+        u64::from_be_bytes(event_name_size)
+    }
+
+    fn name_size(&self) -> usize {
+        (self.name_size_field() & !CHECKSUM_FLAG) as usize
+    }
+
+    fn is_checksummed(&self) -> bool {
+        self.name_size_field() & CHECKSUM_FLAG != 0
+    }
+
+    /// Returns the checksum stored after the data and the bytes it covers,
+    /// `None` if the event has been stored without a checksum.
+    pub fn checksum(&self) -> Option<(u32, &[u8])> {
+        let bytes = self.0.as_ref();
+        if bytes.len() < 16 + CHECKSUM_SIZE || !self.is_checksummed() {
+            return None;
+        }
+
+        let (covered, checksum) = bytes.split_at(bytes.len() - CHECKSUM_SIZE);
+        let mut raw_checksum = [0; CHECKSUM_SIZE];
+        raw_checksum.copy_from_slice(checksum);
+        Some((u32::from_be_bytes(raw_checksum), covered))
+    }
+
+    fn headers_size(&self) -> usize {
+        let start = 16 + self.name_size();
+        let mut headers_size: [u8; 8] = [0; 8];
+        headers_size.copy_from_slice(&self.0.as_ref()[start..start + 8]);
+        usize::from_be_bytes(headers_size)
+    }
+
+    pub fn timestamp(&self) -> Timestamp {
+        let mut timestamp: [u8; 8] = [0; 8];
+        timestamp.copy_from_slice(&self.0.as_ref()[..8]);
+        Timestamp::from_be_bytes(timestamp)
     }
 
     // FIXME: Prefer using a typed Error
     pub fn name(&self) -> Result<EventName, Box<Error>> {
         let name_size = self.name_size();
-        let raw_name = &self.0.as_ref()[8..(8 + name_size)];
+        let raw_name = &self.0.as_ref()[16..(16 + name_size)];
         let name = String::from_utf8(raw_name.to_owned())?;
 
         Ok(EventName::new(name)?)
     }
 
+    pub fn headers(&self) -> Option<EventHeaders> {
+        let start = 16 + self.name_size() + 8;
+        let raw_headers = &self.0.as_ref()[start..(start + self.headers_size())];
+
+        EventHeaders::from_bytes(raw_headers)
+    }
+
     pub fn data(&self) -> EventData {
-        let name_size = self.name_size();
-        let raw_name = &self.0.as_ref()[(8 + name_size)..];
+        let start = 16 + self.name_size() + 8 + self.headers_size();
+        let end = match self.checksum() {
+            Some((_, covered)) => covered.len(),
+            None => self.0.as_ref().len(),
+        };
+        let raw_data = &self.0.as_ref()[start..end];
+
+        EventData(Bytes::from(raw_data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_event(name: &str, data: &[u8], checksum: Option<u32>) -> Vec<u8> {
+        let mut name_size = name.len() as u64;
+        if checksum.is_some() {
+            name_size |= CHECKSUM_FLAG;
+        }
+
+        let headers = EventHeaders::new().to_bytes();
+        let mut raw_event = Vec::new();
+        raw_event.extend_from_slice(&Timestamp(42).to_be_bytes());
+        raw_event.extend_from_slice(&name_size.to_be_bytes());
+        raw_event.extend_from_slice(name.as_bytes());
+        raw_event.extend_from_slice(&headers.len().to_be_bytes());
+        raw_event.extend_from_slice(&headers);
+        raw_event.extend_from_slice(data);
+        if let Some(checksum) = checksum {
+            raw_event.extend_from_slice(&checksum.to_be_bytes());
+        }
+        raw_event
+    }
+
+    #[test]
+    fn raw_event_with_checksum() {
+        let bytes = raw_event("created", b"hello", Some(0xdead_beef));
+        let event = RawEvent::new(&bytes);
+
+        assert_eq!(event.timestamp(), Timestamp(42));
+        assert_eq!(event.name().unwrap().as_str(), "created");
+        assert_eq!(&event.data().0[..], b"hello");
+
+        let (checksum, covered) = event.checksum().unwrap();
+        assert_eq!(checksum, 0xdead_beef);
+        assert_eq!(covered, &bytes[..bytes.len() - 4]);
+    }
+
+    #[test]
+    fn raw_event_without_checksum() {
+        let bytes = raw_event("created", b"hello", None);
+        let event = RawEvent::new(&bytes);
 
-        EventData(raw_name.to_owned())
+        assert_eq!(event.name().unwrap().as_str(), "created");
+        assert_eq!(&event.data().0[..], b"hello");
+        assert_eq!(event.checksum(), None);
     }
 }
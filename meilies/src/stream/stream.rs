@@ -4,12 +4,51 @@ use std::str::FromStr;
 use std::string::FromUtf8Error;
 
 use crate::resp::{FromResp, RespStringConvertError, RespValue};
-use crate::stream::{StreamName, StreamNameError};
+use crate::stream::{EventName, EventNameError, GroupName, GroupNameError};
+use crate::stream::{ParseTimestampError, StreamName, StreamNameError, Timestamp};
+
+/// The position used to only read the events published after the subscription (e.g. `stream:$`).
+const END_POSITION: &str = "$";
+
+/// The prefix of the position used to read the events appended from a point
+/// in time (e.g. `stream:@2021-06-01T00:00:00Z` or `stream:@1622505600000`).
+const TIME_POSITION_PREFIX: &str = "@";
+
+/// The query key used to filter the events of a stream by name (e.g. `stream:0?type=A,B`).
+const EVENT_NAMES_FILTER_KEY: &str = "type";
+
+/// The query key used to read from the offset committed by a consumer group
+/// (e.g. `stream:0?from=committed&group=workers`).
+const FROM_KEY: &str = "from";
+
+/// The only value accepted for the `from` query key.
+const FROM_COMMITTED: &str = "committed";
+
+/// The query key used to specify the consumer group that committed the offset.
+const GROUP_KEY: &str = "group";
+
+/// The query key used to choose what happens when the subscriber can not
+/// keep up with the events of the stream (e.g. `stream:0?slow=drop-oldest`).
+const SLOW_CONSUMER_KEY: &str = "slow";
+
+/// The query key used to only send the events the subscriber has given credits
+/// for, more credits are given with the `flow` command (e.g. `stream:0?credits=100`).
+const CREDITS_KEY: &str = "credits";
+
+/// The query key used to receive up to this number of events in a single message
+/// (e.g. `stream:0?batch=100`), the client flattens the batches it receives.
+const BATCH_KEY: &str = "batch";
+
+/// The query key used to wait up to this number of milliseconds for more events
+/// before sending an incomplete batch (e.g. `stream:0?batch=100&window=10`).
+const WINDOW_KEY: &str = "window";
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ReadRange {
     ReadFromUntil(u64, u64),
     ReadFrom(u64),
+    /// Read from the first event appended at or after this time.
+    ReadFromTime(Timestamp),
     ReadFromEnd,
 }
 
@@ -21,6 +60,13 @@ impl ReadRange {
         }
     }
 
+    pub fn from_time(&self) -> Option<Timestamp> {
+        match self {
+            ReadRange::ReadFromTime(timestamp) => Some(*timestamp),
+            _ => None,
+        }
+    }
+
     pub fn to(&self) -> Option<u64> {
         match self {
             ReadRange::ReadFromUntil(_, to) => Some(*to),
@@ -34,15 +80,91 @@ impl fmt::Display for ReadRange {
         match self {
             ReadRange::ReadFromUntil(from, to) => write!(f, ":{}:{}", from, to),
             ReadRange::ReadFrom(from) => write!(f, ":{}", from),
+            ReadRange::ReadFromTime(timestamp) => {
+                write!(f, ":{}{}", TIME_POSITION_PREFIX, timestamp)
+            }
             ReadRange::ReadFromEnd => write!(f, ""),
         }
     }
 }
 
+/// What the server does with the events of a subscription when the subscriber
+/// does not read them fast enough and the buffer of the connection is full.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SlowConsumer {
+    /// Wait for the subscriber to read the events, the default.
+    Block,
+    /// Drop the oldest events buffered to make room for the new ones.
+    DropOldest,
+    /// Drop the new events until there is room in the buffer.
+    DropNewest,
+    /// Close the connection of the subscriber.
+    Disconnect,
+}
+
+impl Default for SlowConsumer {
+    fn default() -> SlowConsumer {
+        SlowConsumer::Block
+    }
+}
+
+impl SlowConsumer {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SlowConsumer::Block => "block",
+            SlowConsumer::DropOldest => "drop-oldest",
+            SlowConsumer::DropNewest => "drop-newest",
+            SlowConsumer::Disconnect => "disconnect",
+        }
+    }
+}
+
+impl fmt::Display for SlowConsumer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SlowConsumer {
+    type Err = ParseStreamError;
+
+    fn from_str(s: &str) -> Result<SlowConsumer, Self::Err> {
+        match s {
+            "block" => Ok(SlowConsumer::Block),
+            "drop-oldest" => Ok(SlowConsumer::DropOldest),
+            "drop-newest" => Ok(SlowConsumer::DropNewest),
+            "disconnect" => Ok(SlowConsumer::Disconnect),
+            _otherwise => Err(ParseStreamError::SlowConsumerError),
+        }
+    }
+}
+
+/// How the events of a subscription are coalesced into a single message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Batch {
+    /// The maximum number of events sent in a message.
+    pub size: u64,
+    /// The number of milliseconds to wait for more events before sending an incomplete
+    /// batch, only the events already available are sent together if zero.
+    pub window: u64,
+}
+
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Stream {
     pub name: StreamName,
     pub range: ReadRange,
+    /// Only the events with one of these names are sent, all of them are sent if `None`.
+    pub event_names: Option<Vec<EventName>>,
+    /// Start after the offset committed by this consumer group if there is one,
+    /// the range start is used otherwise.
+    pub committed: Option<GroupName>,
+    /// What is done with the events when the subscriber can not keep up.
+    pub slow_consumer: SlowConsumer,
+    /// The number of events that can be sent before the subscriber gives more
+    /// credits, the events are sent without waiting for credits if `None`.
+    pub credits: Option<u64>,
+    /// The events are sent in batches if specified, one by one otherwise.
+    pub batch: Option<Batch>,
 }
 
 impl Stream {
@@ -51,7 +173,15 @@ impl Stream {
     }
 
     pub fn new(name: StreamName, range: ReadRange) -> Stream {
-        Stream { name, range }
+        Stream {
+            name,
+            range,
+            event_names: None,
+            committed: None,
+            slow_consumer: SlowConsumer::Block,
+            credits: None,
+            batch: None,
+        }
     }
 
     pub fn new_from_to(name: StreamName, from: Option<u64>, to: Option<u64>) -> Stream {
@@ -60,7 +190,46 @@ impl Stream {
             (Some(from), None) => ReadRange::ReadFrom(from),
             (_, _) => ReadRange::ReadFromEnd,
         };
-        Stream { name, range }
+        Stream::new(name, range)
+    }
+
+    /// Restrict the events sent to the ones with the given names.
+    pub fn with_event_names(mut self, event_names: Vec<EventName>) -> Stream {
+        self.event_names = Some(event_names);
+        self
+    }
+
+    /// Start after the offset committed by the given consumer group, if there is one.
+    pub fn from_committed(mut self, group: GroupName) -> Stream {
+        self.committed = Some(group);
+        self
+    }
+
+    /// Choose what is done with the events when the subscriber can not keep up.
+    pub fn with_slow_consumer(mut self, slow_consumer: SlowConsumer) -> Stream {
+        self.slow_consumer = slow_consumer;
+        self
+    }
+
+    /// Only send the given number of events until the subscriber gives more credits.
+    pub fn with_credits(mut self, credits: u64) -> Stream {
+        self.credits = Some(credits);
+        self
+    }
+
+    /// Send the events in batches of at most `size` events, waiting up
+    /// to `window` milliseconds for more events to fill a batch.
+    pub fn with_batch(mut self, size: u64, window: u64) -> Stream {
+        self.batch = Some(Batch { size, window });
+        self
+    }
+
+    /// Returns `true` if the event with the given name must be sent.
+    pub fn accepts(&self, event_name: &EventName) -> bool {
+        match &self.event_names {
+            Some(event_names) => event_names.contains(event_name),
+            None => true,
+        }
     }
 }
 
@@ -72,23 +241,52 @@ impl fmt::Debug for Stream {
 
 impl fmt::Display for Stream {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.range {
-            ReadRange::ReadFromUntil(from, to) => write!(f, "{}:{}:{}", self.name, from, to),
-            ReadRange::ReadFrom(from) => write!(f, "{}:{}", self.name, from),
-            ReadRange::ReadFromEnd => write!(f, "{}", self.name),
+        write!(f, "{}{}", self.name, self.range)?;
+
+        let mut separator = '?';
+
+        if let Some(event_names) = &self.event_names {
+            write!(f, "{}{}=", separator, EVENT_NAMES_FILTER_KEY)?;
+            for (i, event_name) in event_names.iter().enumerate() {
+                if i != 0 {
+                    f.write_str(",")?;
+                }
+                write!(f, "{}", event_name)?;
+            }
+            separator = '&';
+        }
+
+        if let Some(group) = &self.committed {
+            write!(f, "{}{}={}", separator, FROM_KEY, FROM_COMMITTED)?;
+            write!(f, "&{}={}", GROUP_KEY, group)?;
+            separator = '&';
+        }
+
+        let slow_consumer = self.slow_consumer;
+        if slow_consumer != SlowConsumer::Block {
+            write!(f, "{}{}={}", separator, SLOW_CONSUMER_KEY, slow_consumer)?;
+            separator = '&';
         }
+
+        if let Some(credits) = self.credits {
+            write!(f, "{}{}={}", separator, CREDITS_KEY, credits)?;
+            separator = '&';
+        }
+
+        if let Some(Batch { size, window }) = self.batch {
+            write!(f, "{}{}={}", separator, BATCH_KEY, size)?;
+            if window != 0 {
+                write!(f, "&{}={}", WINDOW_KEY, window)?;
+            }
+        }
+
+        Ok(())
     }
 }
 
 impl Into<RespValue> for Stream {
     fn into(self) -> RespValue {
-        let text = match self.range {
-            ReadRange::ReadFromUntil(from, to) => format!("{}:{}:{}", self.name, from, to),
-            ReadRange::ReadFrom(from) => format!("{}:{}", self.name, from),
-            ReadRange::ReadFromEnd => format!("{}", self.name),
-        };
-
-        RespValue::BulkString(text.into_bytes())
+        RespValue::bulk_string(self.to_string())
     }
 }
 
@@ -124,11 +322,86 @@ impl FromResp for Stream {
 
 impl From<StreamName> for Stream {
     fn from(name: StreamName) -> Stream {
-        Stream {
-            name,
-            range: ReadRange::ReadFromEnd,
+        Stream::new(name, ReadRange::ReadFromEnd)
+    }
+}
+
+/// The parameters that can follow the stream position, separated by `&`.
+#[derive(Default)]
+struct Query {
+    event_names: Option<Vec<EventName>>,
+    committed: Option<GroupName>,
+    slow_consumer: SlowConsumer,
+    credits: Option<u64>,
+    batch: Option<Batch>,
+}
+
+fn parse_query(query: &str) -> Result<Query, ParseStreamError> {
+    use ParseStreamError::*;
+
+    let mut event_names = None;
+    let mut from_committed = false;
+    let mut group = None;
+    let mut slow_consumer = None;
+    let mut credits = None;
+    let mut batch_size = None;
+    let mut window = None;
+
+    for param in query.split('&') {
+        let mut split = param.splitn(2, '=');
+        match (split.next(), split.next()) {
+            (Some(EVENT_NAMES_FILTER_KEY), Some(names)) if event_names.is_none() => {
+                let names = names
+                    .split(',')
+                    .map(|name| EventName::from_str(name).map_err(EventNameError))
+                    .collect::<Result<_, _>>()?;
+                event_names = Some(names);
+            }
+            (Some(FROM_KEY), Some(FROM_COMMITTED)) if !from_committed => from_committed = true,
+            (Some(GROUP_KEY), Some(name)) if group.is_none() => {
+                group = Some(GroupName::from_str(name).map_err(GroupNameError)?);
+            }
+            (Some(SLOW_CONSUMER_KEY), Some(policy)) if slow_consumer.is_none() => {
+                slow_consumer = Some(SlowConsumer::from_str(policy)?);
+            }
+            (Some(CREDITS_KEY), Some(count)) if credits.is_none() => {
+                credits = Some(u64::from_str_radix(count, 10).map_err(CreditsError)?);
+            }
+            (Some(BATCH_KEY), Some(size)) if batch_size.is_none() => {
+                batch_size = Some(u64::from_str_radix(size, 10).map_err(BatchError)?);
+            }
+            (Some(WINDOW_KEY), Some(millis)) if window.is_none() => {
+                window = Some(u64::from_str_radix(millis, 10).map_err(BatchError)?);
+            }
+            (_, _) => return Err(FilterError),
         }
     }
+
+    // the committed offsets are stored per consumer group
+    let committed = match (from_committed, group) {
+        (true, Some(group)) => Some(group),
+        (false, None) => None,
+        (_, _) => return Err(CommittedError),
+    };
+
+    // a batch contains at least one event and the window only applies to batches
+    let batch = match (batch_size, window) {
+        (Some(0), _) => return Err(BatchSizeError),
+        (Some(size), window) => Some(Batch {
+            size,
+            window: window.unwrap_or(0),
+        }),
+        (None, None) => None,
+        (None, Some(_)) => return Err(BatchSizeError),
+    };
+
+    Ok(Query {
+        event_names,
+        committed,
+        slow_consumer: slow_consumer.unwrap_or_default(),
+        credits,
+        batch,
+    })
 }
 
 impl FromStr for Stream {
@@ -137,19 +410,46 @@ impl FromStr for Stream {
     fn from_str(s: &str) -> Result<Stream, Self::Err> {
         use ParseStreamError::*;
 
+        let mut split = s.splitn(2, '?');
+        let (s, query) = (split.next().unwrap_or_default(), split.next());
+        let query = match query {
+            Some(query) => parse_query(query)?,
+            None => Query::default(),
+        };
+
+        // a date contains colons, it can not be followed by an end bound
+        let mut split = s.splitn(2, ':');
+        if let (Some(name), Some(time)) = (split.next(), split.next()) {
+            if time.starts_with(TIME_POSITION_PREFIX) {
+                let name = StreamName::new(name.to_owned()).map_err(StreamNameError)?;
+                let time = &time[TIME_POSITION_PREFIX.len()..];
+                let timestamp = Timestamp::from_str(time).map_err(StartTimeError)?;
+                let stream = Stream::new(name, ReadRange::ReadFromTime(timestamp));
+                return Ok(Stream {
+                    event_names: query.event_names,
+                    committed: query.committed,
+                    slow_consumer: query.slow_consumer,
+                    credits: query.credits,
+                    batch: query.batch,
+                    ..stream
+                });
+            }
+        }
+
         let mut split = s.split(':');
-        match (split.next(), split.next(), split.next(), split.next()) {
+        let stream = match (split.next(), split.next(), split.next(), split.next()) {
             (Some(name), None, None, None) => {
                 let name = StreamName::from_str(name).map_err(StreamNameError)?;
-                Ok(Stream::from(name))
+                Stream::from(name)
+            }
+            (Some(name), Some(END_POSITION), None, None) => {
+                let name = StreamName::new(name.to_owned()).map_err(StreamNameError)?;
+                Stream::new(name, ReadRange::ReadFromEnd)
             }
             (Some(name), Some(from), None, None) => {
                 let name = StreamName::new(name.to_owned()).map_err(StreamNameError)?;
                 let number = u64::from_str_radix(from, 10).map_err(StartFromError)?;
-                Ok(Stream {
-                    name,
-                    range: ReadRange::ReadFrom(number),
-                })
+                Stream::new(name, ReadRange::ReadFrom(number))
             }
             (Some(name), Some(from), Some(to), None) => {
                 let name = StreamName::new(name.to_owned()).map_err(StreamNameError)?;
@@ -158,13 +458,19 @@ impl FromStr for Stream {
                 if from >= to {
                     return Err(BoundsError);
                 }
-                Ok(Stream {
-                    name,
-                    range: ReadRange::ReadFromUntil(from, to),
-                })
+                Stream::new(name, ReadRange::ReadFromUntil(from, to))
             }
-            (_, _, _, _) => Err(FormatError),
-        }
+            (_, _, _, _) => return Err(FormatError),
+        };
+
+        Ok(Stream {
+            event_names: query.event_names,
+            committed: query.committed,
+            slow_consumer: query.slow_consumer,
+            credits: query.credits,
+            batch: query.batch,
+            ..stream
+        })
     }
 }
 
@@ -172,8 +478,17 @@ impl FromStr for Stream {
 pub enum ParseStreamError {
     StreamNameError(StreamNameError),
     StartFromError(ParseIntError),
+    StartTimeError(ParseTimestampError),
     EndToError(ParseIntError),
+    EventNameError(EventNameError),
+    GroupNameError(GroupNameError),
     BoundsError,
+    FilterError,
+    CommittedError,
+    SlowConsumerError,
+    CreditsError(ParseIntError),
+    BatchError(ParseIntError),
+    BatchSizeError,
     FormatError,
 }
 
@@ -184,8 +499,19 @@ impl fmt::Display for ParseStreamError {
         match self {
             StreamNameError(e) => write!(f, "stream not properly formatted; {}", e),
             StartFromError(e) => write!(f, "stream \"start from\" not properly formatted; {}", e),
+            StartTimeError(e) => write!(f, "stream \"start time\" not properly formatted; {}", e),
             EndToError(e) => write!(f, "stream \"end to\" not properly formatted; {}", e),
+            EventNameError(e) => write!(f, "stream filter not properly formatted; {}", e),
+            GroupNameError(e) => write!(f, "stream group not properly formatted; {}", e),
             BoundsError => f.write_str("The end bound must be greater than the start bound"),
+            FilterError => f.write_str("stream filter is not properly formatted"),
+            CommittedError => f.write_str("reading from the committed offset requires a group"),
+            SlowConsumerError => f.write_str(
+                "the slow consumer policy must be block, drop-oldest, drop-newest or disconnect",
+            ),
+            CreditsError(e) => write!(f, "stream credits not properly formatted; {}", e),
+            BatchError(e) => write!(f, "stream batch not properly formatted; {}", e),
+            BatchSizeError => f.write_str("the batch size must be specified and not be zero"),
             FormatError => f.write_str("stream is not properly formatted"),
         }
     }
@@ -232,9 +558,19 @@ mod tests {
         );
         assert_eq!(test_stream1, test_stream2);
 
+        let test_stream1 = Stream::from_str("default:$").unwrap();
+        let test_stream2 = Stream::new(
+            StreamName::new("default".to_owned()).unwrap(),
+            ReadRange::ReadFromEnd,
+        );
+        assert_eq!(test_stream1, test_stream2);
+
         let result = Stream::from_str("default:");
         assert!(result.is_err());
 
+        let result = Stream::from_str("default:$:5");
+        assert!(result.is_err());
+
         let result = Stream::from_str("default:-1");
         assert!(result.is_err());
 
@@ -256,4 +592,197 @@ mod tests {
         let result = Stream::from_str("default:1:0");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn create_stream_with_event_names_from_str() {
+        let user_created = EventName::new("UserCreated".to_owned()).unwrap();
+        let user_deleted = EventName::new("UserDeleted".to_owned()).unwrap();
+
+        let test_stream1 = Stream::from_str("default:0?type=UserCreated,UserDeleted").unwrap();
+        let test_stream2 = Stream::new(
+            StreamName::new("default".to_owned()).unwrap(),
+            ReadRange::ReadFrom(0),
+        )
+        .with_event_names(vec![user_created.clone(), user_deleted.clone()]);
+        assert_eq!(test_stream1, test_stream2);
+        assert_eq!(
+            test_stream1.to_string(),
+            "default:0?type=UserCreated,UserDeleted"
+        );
+
+        let test_stream1 = Stream::from_str("default?type=UserCreated").unwrap();
+        let test_stream2 = Stream::new(
+            StreamName::new("default".to_owned()).unwrap(),
+            ReadRange::ReadFromEnd,
+        )
+        .with_event_names(vec![user_created.clone()]);
+        assert_eq!(test_stream1, test_stream2);
+        assert!(test_stream1.accepts(&user_created));
+        assert!(!test_stream1.accepts(&user_deleted));
+
+        let result = Stream::from_str("default:0?");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default:0?type=");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default:0?name=UserCreated");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default:0?type=UserCreated,");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn create_stream_from_committed_from_str() {
+        let workers = GroupName::new("workers".to_owned()).unwrap();
+        let user_created = EventName::new("UserCreated".to_owned()).unwrap();
+
+        let test_stream1 = Stream::from_str("default:3?from=committed&group=workers").unwrap();
+        let test_stream2 = Stream::new(
+            StreamName::new("default".to_owned()).unwrap(),
+            ReadRange::ReadFrom(3),
+        )
+        .from_committed(workers.clone());
+        assert_eq!(test_stream1, test_stream2);
+        assert_eq!(
+            test_stream1.to_string(),
+            "default:3?from=committed&group=workers"
+        );
+
+        let test_stream1 =
+            Stream::from_str("default?group=workers&type=UserCreated&from=committed").unwrap();
+        let test_stream2 = Stream::from(StreamName::new("default".to_owned()).unwrap())
+            .with_event_names(vec![user_created])
+            .from_committed(workers);
+        assert_eq!(test_stream1, test_stream2);
+        assert_eq!(
+            test_stream1.to_string(),
+            "default?type=UserCreated&from=committed&group=workers"
+        );
+
+        let result = Stream::from_str("default?from=committed");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default?group=workers");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default?from=start&group=workers");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default?from=committed&group=");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn create_stream_with_slow_consumer_from_str() {
+        let test_stream1 = Stream::from_str("default:0?slow=drop-oldest").unwrap();
+        let test_stream2 = Stream::new(
+            StreamName::new("default".to_owned()).unwrap(),
+            ReadRange::ReadFrom(0),
+        )
+        .with_slow_consumer(SlowConsumer::DropOldest);
+        assert_eq!(test_stream1, test_stream2);
+        assert_eq!(test_stream1.to_string(), "default:0?slow=drop-oldest");
+
+        let test_stream1 = Stream::from_str("default?type=UserCreated&slow=disconnect").unwrap();
+        assert_eq!(test_stream1.slow_consumer, SlowConsumer::Disconnect);
+        assert_eq!(
+            test_stream1.to_string(),
+            "default?type=UserCreated&slow=disconnect"
+        );
+
+        let test_stream1 = Stream::from_str("default?slow=block").unwrap();
+        assert_eq!(test_stream1.to_string(), "default");
+
+        let result = Stream::from_str("default?slow=drop");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default?slow=drop-newest&slow=block");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn create_stream_with_credits_from_str() {
+        let test_stream1 = Stream::from_str("default:0?credits=100").unwrap();
+        let test_stream2 = Stream::new(
+            StreamName::new("default".to_owned()).unwrap(),
+            ReadRange::ReadFrom(0),
+        )
+        .with_credits(100);
+        assert_eq!(test_stream1, test_stream2);
+        assert_eq!(test_stream1.to_string(), "default:0?credits=100");
+
+        let test_stream1 = Stream::from_str("default?credits=0&slow=drop-newest").unwrap();
+        assert_eq!(test_stream1.credits, Some(0));
+        assert_eq!(
+            test_stream1.to_string(),
+            "default?slow=drop-newest&credits=0"
+        );
+
+        let result = Stream::from_str("default?credits=-1");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default?credits=");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn create_stream_with_batch_from_str() {
+        let test_stream1 = Stream::from_str("default:0?batch=100").unwrap();
+        let test_stream2 = Stream::new(
+            StreamName::new("default".to_owned()).unwrap(),
+            ReadRange::ReadFrom(0),
+        )
+        .with_batch(100, 0);
+        assert_eq!(test_stream1, test_stream2);
+        assert_eq!(test_stream1.to_string(), "default:0?batch=100");
+
+        let test_stream1 = Stream::from_str("default?window=10&credits=5&batch=50").unwrap();
+        assert_eq!(
+            test_stream1.batch,
+            Some(Batch {
+                size: 50,
+                window: 10
+            })
+        );
+        assert_eq!(
+            test_stream1.to_string(),
+            "default?credits=5&batch=50&window=10"
+        );
+
+        let result = Stream::from_str("default?batch=0");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default?window=10");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default?batch=10&window=-1");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn create_stream_from_time_from_str() {
+        let test_stream1 = Stream::from_str("default:@2021-06-01T00:00:00Z").unwrap();
+        let test_stream2 = Stream::new(
+            StreamName::new("default".to_owned()).unwrap(),
+            ReadRange::ReadFromTime(Timestamp(1_622_505_600_000)),
+        );
+        assert_eq!(test_stream1, test_stream2);
+        assert_eq!(test_stream1.to_string(), "default:@1622505600000");
+
+        let test_stream1 = Stream::from_str("default:@1622505600000?type=UserCreated").unwrap();
+        let test_stream2 =
+            test_stream2.with_event_names(vec![EventName::new("UserCreated".to_owned()).unwrap()]);
+        assert_eq!(test_stream1, test_stream2);
+
+        let result = Stream::from_str("default:@");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default:@yesterday");
+        assert!(result.is_err());
+
+        let result = Stream::from_str("default:@1622505600000:5");
+        assert!(result.is_err());
+    }
 }
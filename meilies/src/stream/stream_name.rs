@@ -6,6 +6,14 @@ use crate::resp::{FromResp, RespStringConvertError, RespValue};
 
 pub const ALL_STREAMS: &str = "$all";
 
+/// The prefix of the category streams, e.g. `$category-order` contains
+/// the events of all the streams whose name starts with `order-`.
+pub const CATEGORY_PREFIX: &str = "$category-";
+
+/// The prefix of the ephemeral streams, e.g. `~cpu-load`, their
+/// events are only kept in memory by the server and are never stored.
+pub const EPHEMERAL_PREFIX: &str = "~";
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct StreamName(String);
 
@@ -37,6 +45,86 @@ impl StreamName {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Returns the category of a category stream name, e.g. `order` for `$category-order`.
+    pub fn category(&self) -> Option<&str> {
+        self.0
+            .strip_prefix(CATEGORY_PREFIX)
+            .filter(|c| !c.is_empty())
+    }
+
+    /// Returns `true` if this stream name is a glob pattern, e.g. `sensor-*`.
+    pub fn is_pattern(&self) -> bool {
+        self.0.contains(['*', '?'])
+    }
+
+    /// Returns `true` if the events of this stream are the ones published to other streams,
+    /// i.e. if it is the `$all` stream, a category stream or a pattern.
+    pub fn is_virtual(&self) -> bool {
+        self.0 == ALL_STREAMS || self.category().is_some() || self.is_pattern()
+    }
+
+    /// Returns `true` if the events of this stream are only kept in memory, i.e. if its name
+    /// starts with the ephemeral prefix and it is not a pattern.
+    pub fn is_ephemeral(&self) -> bool {
+        self.0.starts_with(EPHEMERAL_PREFIX) && !self.is_pattern()
+    }
+
+    /// Returns `true` if the events of the given stream are part of this stream, i.e. if it is
+    /// the same stream, the `$all` stream, the category of the stream or a matching pattern.
+    pub fn includes(&self, stream: &StreamName) -> bool {
+        if let Some(category) = self.category() {
+            return match stream.0.strip_prefix(category) {
+                Some(rest) => rest.starts_with('-'),
+                None => false,
+            };
+        }
+
+        if self.is_pattern() {
+            return stream.matches(&self.0);
+        }
+
+        self.0 == ALL_STREAMS || self == stream
+    }
+
+    /// Returns `true` if this stream name matches the given glob pattern.
+    ///
+    /// A `*` matches any sequence of characters and a `?` matches any single character.
+    pub fn matches(&self, pattern: &str) -> bool {
+        glob_match(pattern.as_bytes(), self.0.as_bytes())
+    }
+}
+
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(b'?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bn)) => {
+                    backtrack = Some((bp, bn + 1));
+                    p = bp + 1;
+                    n = bn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
 }
 
 impl fmt::Display for StreamName {
@@ -109,3 +197,67 @@ impl PartialEq<&'_ str> for StreamName {
         self.0.eq(other)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_name_matches_pattern() {
+        let name = StreamName::from_str("sensor-42").unwrap();
+
+        assert!(name.matches("sensor-42"));
+        assert!(name.matches("*"));
+        assert!(name.matches("sensor-*"));
+        assert!(name.matches("*-42"));
+        assert!(name.matches("s*r-4?"));
+        assert!(name.matches("sensor-4?"));
+
+        assert!(!name.matches(""));
+        assert!(!name.matches("sensor"));
+        assert!(!name.matches("sensor-?"));
+        assert!(!name.matches("order-*"));
+        assert!(!name.matches("*-43"));
+    }
+
+    #[test]
+    fn category_stream_includes_streams() {
+        let category = StreamName::from_str("$category-order").unwrap();
+        assert_eq!(category.category(), Some("order"));
+        assert_eq!(StreamName::from_str("order-1").unwrap().category(), None);
+        assert_eq!(StreamName::from_str("$category-").unwrap().category(), None);
+
+        assert!(category.includes(&StreamName::from_str("order-1").unwrap()));
+        assert!(category.includes(&StreamName::from_str("order-eu-2").unwrap()));
+        assert!(!category.includes(&StreamName::from_str("order").unwrap()));
+        assert!(!category.includes(&StreamName::from_str("orders-1").unwrap()));
+        assert!(!category.includes(&StreamName::from_str("user-1").unwrap()));
+
+        let name = StreamName::from_str("order-1").unwrap();
+        assert!(StreamName::all().includes(&name));
+        assert!(name.includes(&name));
+        assert!(!name.includes(&StreamName::from_str("order-2").unwrap()));
+    }
+
+    #[test]
+    fn pattern_stream_includes_streams() {
+        let pattern = StreamName::from_str("sensor-*").unwrap();
+        assert!(pattern.is_pattern());
+        assert!(pattern.is_virtual());
+        assert!(StreamName::from_str("sensor-?").unwrap().is_pattern());
+        assert!(!StreamName::from_str("sensor-1").unwrap().is_pattern());
+
+        assert!(pattern.includes(&StreamName::from_str("sensor-1").unwrap()));
+        assert!(pattern.includes(&StreamName::from_str("sensor-kitchen").unwrap()));
+        assert!(!pattern.includes(&StreamName::from_str("sensor").unwrap()));
+        assert!(!pattern.includes(&StreamName::from_str("order-1").unwrap()));
+    }
+
+    #[test]
+    fn ephemeral_stream_name() {
+        assert!(StreamName::from_str("~cpu-load").unwrap().is_ephemeral());
+        assert!(!StreamName::from_str("~cpu-*").unwrap().is_ephemeral());
+        assert!(!StreamName::from_str("cpu-load").unwrap().is_ephemeral());
+        assert!(!StreamName::from_str("cpu~load").unwrap().is_ephemeral());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::resp::{FromResp, RespIntConvertError, RespValue};
+use core::array::TryFromSliceError;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The number of milliseconds elapsed since the unix epoch.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(pub u64);
+
+impl Timestamp {
+    /// The current time, or the virtual time of a simulation if one drives the clock of the
+    /// calling thread, the virtual time is only read when the `simulation` feature is enabled.
+    pub fn now() -> Timestamp {
+        #[cfg(any(test, feature = "simulation"))]
+        {
+            if let Some(now) = crate::sim::virtual_time() {
+                return now;
+            }
+        }
+
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time before the unix epoch");
+        Timestamp(elapsed.as_millis() as u64)
+    }
+
+    pub fn from_be_bytes(bytes: [u8; 8]) -> Timestamp {
+        Timestamp(u64::from_be_bytes(bytes))
+    }
+
+    pub fn to_be_bytes(self) -> [u8; 8] {
+        self.0.to_be_bytes()
+    }
+
+    /// Formats the timestamp as an UTC date and time with
+    /// milliseconds (e.g. `2021-06-01T00:00:00.250Z`).
+    pub fn to_datetime(self) -> String {
+        let (days, millis) = (self.0 / 86_400_000, self.0 % 86_400_000);
+        let (year, month, day) = civil_from_days(days);
+        let seconds = millis / 1_000;
+
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            year,
+            month,
+            day,
+            seconds / 3_600,
+            seconds / 60 % 60,
+            seconds % 60,
+            millis % 1_000
+        )
+    }
+}
+
+impl TryFrom<&[u8]> for Timestamp {
+    type Error = TryFromSliceError;
+
+    fn try_from(slice: &[u8]) -> Result<Timestamp, Self::Error> {
+        TryFrom::try_from(slice).map(Timestamp::from_be_bytes)
+    }
+}
+
+impl FromResp for Timestamp {
+    type Error = RespIntConvertError;
+
+    fn from_resp(value: RespValue) -> Result<Self, Self::Error> {
+        i64::from_resp(value).map(|i| Timestamp(i as u64))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Parses a number of milliseconds since the unix epoch (e.g. `1622505600000`)
+/// or an UTC date and time (e.g. `2021-06-01T00:00:00Z` or `2021-06-01T00:00:00.250Z`).
+impl FromStr for Timestamp {
+    type Err = ParseTimestampError;
+
+    fn from_str(s: &str) -> Result<Timestamp, Self::Err> {
+        if s.bytes().all(|b| b.is_ascii_digit()) {
+            return u64::from_str(s)
+                .map(Timestamp)
+                .map_err(|_| ParseTimestampError);
+        }
+
+        parse_datetime(s).ok_or(ParseTimestampError)
+    }
+}
+
+fn parse_datetime(s: &str) -> Option<Timestamp> {
+    let s = s.strip_suffix('Z').or_else(|| s.strip_suffix('z'))?;
+    let mut split = s.splitn(2, ['T', 't']);
+    let (date, time) = (split.next()?, split.next()?);
+
+    let mut date = date.split('-');
+    let year = parse_digits(date.next()?, 4)?;
+    let month = parse_digits(date.next()?, 2)?;
+    let day = parse_digits(date.next()?, 2)?;
+    if date.next().is_some() {
+        return None;
+    }
+
+    let mut split = time.splitn(2, '.');
+    let (time, fraction) = (split.next()?, split.next());
+
+    let mut time = time.split(':');
+    let hour = parse_digits(time.next()?, 2)?;
+    let minute = parse_digits(time.next()?, 2)?;
+    let second = parse_digits(time.next()?, 2)?;
+    if time.next().is_some() {
+        return None;
+    }
+
+    // only the milliseconds of the fraction of second are kept
+    let millis = match fraction {
+        Some(fraction) if !fraction.is_empty() && fraction.bytes().all(|b| b.is_ascii_digit()) => {
+            let digits = &fraction[..fraction.len().min(3)];
+            let padding = 10u64.pow(3 - digits.len() as u32);
+            u64::from_str(digits).ok()? * padding
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+
+    if year < 1970 || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+
+    Some(Timestamp(seconds * 1_000 + millis))
+}
+
+fn parse_digits(s: &str, len: usize) -> Option<u64> {
+    if s.len() != len || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    u64::from_str(s).ok()
+}
+
+fn is_leap_year(year: u64) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days since the unix epoch of a date after the unix epoch,
+/// see http://howardhinnant.github.io/date_algorithms.html#days_from_civil.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the year, the month and the day of a number of days since the unix epoch,
+/// see http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ParseTimestampError;
+
+impl fmt::Display for ParseTimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(
+            "expected milliseconds since the epoch or an UTC date (e.g. 2021-06-01T00:00:00Z)",
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_timestamp() {
+        assert_eq!(Timestamp::from_str("0"), Ok(Timestamp(0)));
+        assert_eq!(
+            Timestamp::from_str("1622505600000"),
+            Ok(Timestamp(1_622_505_600_000))
+        );
+        assert_eq!(
+            Timestamp::from_str("1970-01-01T00:00:00Z"),
+            Ok(Timestamp(0))
+        );
+        assert_eq!(
+            Timestamp::from_str("2021-06-01T00:00:00Z"),
+            Ok(Timestamp(1_622_505_600_000))
+        );
+        assert_eq!(
+            Timestamp::from_str("2020-02-29T23:59:59.25Z"),
+            Ok(Timestamp(1_583_020_799_250))
+        );
+
+        assert!(Timestamp::from_str("").is_err());
+        assert!(Timestamp::from_str("-1").is_err());
+        assert!(Timestamp::from_str("2021-06-01").is_err());
+        assert!(Timestamp::from_str("2021-06-01T00:00:00").is_err());
+        assert!(Timestamp::from_str("2021-06-01T00:00:00+02:00").is_err());
+        assert!(Timestamp::from_str("2021-02-29T00:00:00Z").is_err());
+        assert!(Timestamp::from_str("2021-06-01T24:00:00Z").is_err());
+        assert!(Timestamp::from_str("2021-06-01T00:00:00.Z").is_err());
+        assert!(Timestamp::from_str("1969-12-31T23:59:59Z").is_err());
+    }
+
+    #[test]
+    fn format_timestamp() {
+        assert_eq!(Timestamp(0).to_datetime(), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            Timestamp(1_583_020_799_250).to_datetime(),
+            "2020-02-29T23:59:59.250Z"
+        );
+
+        let timestamp = Timestamp(1_622_505_600_042);
+        assert_eq!(Timestamp::from_str(&timestamp.to_datetime()), Ok(timestamp));
+    }
+}
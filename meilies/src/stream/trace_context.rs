@@ -0,0 +1,148 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The header of an event that carries the trace context of its publisher.
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// The W3C trace context of an event, as carried by its `traceparent` header
+/// (e.g. `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TraceContext {
+    pub trace_id: u128,
+    /// The id of the span the event belongs to.
+    pub parent_id: u64,
+    pub flags: u8,
+}
+
+impl TraceContext {
+    /// Returns the context of a span of the same trace whose parent is this context.
+    pub fn child(self, span_id: u64) -> TraceContext {
+        TraceContext {
+            parent_id: span_id,
+            ..self
+        }
+    }
+
+    pub fn is_sampled(self) -> bool {
+        self.flags & 1 == 1
+    }
+}
+
+/// Parses a lowercase hexadecimal field of exactly `len` digits.
+fn parse_hex(s: &str, len: usize) -> Option<u128> {
+    let digits = s
+        .bytes()
+        .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+    if s.len() != len || !digits {
+        return None;
+    }
+    u128::from_str_radix(s, 16).ok()
+}
+
+fn parse_traceparent(s: &str) -> Option<TraceContext> {
+    let mut fields = s.split('-');
+    let version = parse_hex(fields.next()?, 2)?;
+    let trace_id = parse_hex(fields.next()?, 32)?;
+    let parent_id = parse_hex(fields.next()?, 16)?;
+    let flags = parse_hex(fields.next()?, 2)?;
+
+    // the next versions can only add fields at the end
+    let valid_version = match version {
+        0 => fields.next().is_none(),
+        0xff => false,
+        _otherwise => true,
+    };
+
+    if !valid_version || trace_id == 0 || parent_id == 0 {
+        return None;
+    }
+
+    Some(TraceContext {
+        trace_id,
+        parent_id: parent_id as u64,
+        flags: flags as u8,
+    })
+}
+
+impl FromStr for TraceContext {
+    type Err = ParseTraceContextError;
+
+    fn from_str(s: &str) -> Result<TraceContext, Self::Err> {
+        parse_traceparent(s).ok_or(ParseTraceContextError)
+    }
+}
+
+/// Formats the context as a version `00` `traceparent` header.
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ParseTraceContextError;
+
+impl fmt::Display for ParseTraceContextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("expected a W3C traceparent (e.g. 00-<trace-id>-<parent-id>-<flags>)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn parse_and_format_traceparent() {
+        let context = TraceContext::from_str(TRACEPARENT).unwrap();
+        assert_eq!(context.trace_id, 0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736);
+        assert_eq!(context.parent_id, 0x00f0_67aa_0ba9_02b7);
+        assert!(context.is_sampled());
+        assert_eq!(context.to_string(), TRACEPARENT);
+
+        let child = context.child(0xb7ad_6b71_6920_3331);
+        assert_eq!(
+            child.to_string(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-b7ad6b7169203331-01"
+        );
+
+        // the fields added by the next versions are ignored
+        let future = "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra";
+        let context = TraceContext::from_str(future).unwrap();
+        assert!(!context.is_sampled());
+        assert_eq!(
+            context.to_string(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"
+        );
+    }
+
+    #[test]
+    fn invalid_traceparent() {
+        let invalid = [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+            "00-+bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ];
+
+        for traceparent in &invalid {
+            assert_eq!(
+                TraceContext::from_str(traceparent),
+                Err(ParseTraceContextError),
+                "{:?}",
+                traceparent
+            );
+        }
+    }
+}